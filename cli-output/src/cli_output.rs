@@ -938,7 +938,9 @@ impl fmt::Display for CliValidatorInfoVec {
 #[serde(rename_all = "camelCase")]
 pub struct CliValidatorInfo {
     pub identity_pubkey: String,
-    pub info_pubkey: String,
+    /// Config account holding the info, `None` for the info published in gossip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub info_pubkey: Option<String>,
     pub info: Map<String, Value>,
 }
 
@@ -948,7 +950,9 @@ impl VerboseDisplay for CliValidatorInfo {}
 impl fmt::Display for CliValidatorInfo {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         writeln_name_value(f, "Validator Identity:", &self.identity_pubkey)?;
-        writeln_name_value(f, "  Info Address:", &self.info_pubkey)?;
+        if let Some(info_pubkey) = &self.info_pubkey {
+            writeln_name_value(f, "  Info Address:", info_pubkey)?;
+        }
         for (key, value) in self.info.iter() {
             writeln_name_value(
                 f,
@@ -280,6 +280,7 @@ pub enum CliCommand {
     },
     // Validator Info Commands
     GetValidatorInfo(Option<Pubkey>),
+    GetGossipValidatorInfo(Option<Pubkey>),
     SetValidatorInfo {
         validator_info: Value,
         force_keybase: bool,
@@ -1871,6 +1872,10 @@ pub fn process_command(config: &CliConfig) -> ProcessResult {
         CliCommand::GetValidatorInfo(info_pubkey) => {
             process_get_validator_info(&rpc_client, config, *info_pubkey)
         }
+        // Return the validator info published in gossip, of all or a single validator
+        CliCommand::GetGossipValidatorInfo(identity_pubkey) => {
+            process_get_gossip_validator_info(&rpc_client, config, *identity_pubkey)
+        }
         // Publish validator info
         CliCommand::SetValidatorInfo {
             validator_info,
@@ -1313,6 +1313,7 @@ pub fn process_show_stakes(
             encoding: Some(solana_account_decoder::UiAccountEncoding::Base64),
             ..RpcAccountInfoConfig::default()
         },
+        ..RpcProgramAccountsConfig::default()
     };
 
     if let Some(vote_account_pubkeys) = vote_account_pubkeys {
@@ -1505,6 +1506,7 @@ pub fn process_transaction_history(
             before,
             until,
             limit: Some(limit),
+            ..GetConfirmedSignaturesForAddress2Config::default()
         },
     )?;
 
@@ -204,6 +204,12 @@ impl ValidatorInfoSubCommands for App<'_, '_> {
                                 .takes_value(true)
                                 .validator(is_pubkey)
                                 .help("The pubkey of the Validator info account; without this argument, returns all"),
+                        )
+                        .arg(
+                            Arg::with_name("gossip")
+                                .long("gossip")
+                                .takes_value(false)
+                                .help("Get the info validators published in gossip instead; PUBKEY is then the validator identity"),
                         ),
                 )
         )
@@ -232,8 +238,13 @@ pub fn parse_get_validator_info_command(
     matches: &ArgMatches<'_>,
 ) -> Result<CliCommandInfo, CliError> {
     let info_pubkey = pubkey_of(matches, "info_pubkey");
+    let command = if matches.is_present("gossip") {
+        CliCommand::GetGossipValidatorInfo(info_pubkey)
+    } else {
+        CliCommand::GetValidatorInfo(info_pubkey)
+    };
     Ok(CliCommandInfo {
-        command: CliCommand::GetValidatorInfo(info_pubkey),
+        command,
         signers: vec![],
     })
 }
@@ -399,7 +410,7 @@ pub fn process_get_validator_info(
             parse_validator_info(&validator_info_pubkey, &validator_info_account)?;
         validator_info_list.push(CliValidatorInfo {
             identity_pubkey: validator_pubkey.to_string(),
-            info_pubkey: validator_info_pubkey.to_string(),
+            info_pubkey: Some(validator_info_pubkey.to_string()),
             info: validator_info,
         });
     }
@@ -408,6 +419,38 @@ pub fn process_get_validator_info(
         .formatted_string(&CliValidatorInfoVec::new(validator_info_list)))
 }
 
+pub fn process_get_gossip_validator_info(
+    rpc_client: &RpcClient,
+    config: &CliConfig,
+    identity_pubkey: Option<Pubkey>,
+) -> ProcessResult {
+    let identity_pubkey = identity_pubkey.map(|pubkey| pubkey.to_string());
+    let validator_info_list: Vec<CliValidatorInfo> = rpc_client
+        .get_cluster_nodes()?
+        .into_iter()
+        .filter(|node| identity_pubkey.is_none() || identity_pubkey.as_ref() == Some(&node.pubkey))
+        .filter_map(|node| {
+            let validator_info = node.validator_info?;
+            let mut info = Map::new();
+            info.insert("name".to_string(), Value::String(validator_info.name));
+            if let Some(website) = validator_info.website {
+                info.insert("website".to_string(), Value::String(website));
+            }
+            if let Some(icon_url) = validator_info.icon_url {
+                info.insert("iconUrl".to_string(), Value::String(icon_url));
+            }
+            Some(CliValidatorInfo {
+                identity_pubkey: node.pubkey,
+                info_pubkey: None,
+                info,
+            })
+        })
+        .collect();
+    Ok(config
+        .output_format
+        .formatted_string(&CliValidatorInfoVec::new(validator_info_list)))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -4,9 +4,9 @@ use crate::{
     mock_sender::{MockSender, Mocks},
     rpc_config::RpcAccountInfoConfig,
     rpc_config::{
-        RpcGetConfirmedSignaturesForAddress2Config, RpcLargestAccountsConfig,
+        RpcGetConfirmedSignaturesForAddress2Config, RpcLargestAccountsConfig, RpcLeaderSlotsConfig,
         RpcProgramAccountsConfig, RpcSendTransactionConfig, RpcSimulateTransactionConfig,
-        RpcTokenAccountsFilter,
+        RpcStakeConfig, RpcTokenAccountsFilter,
     },
     rpc_request::{RpcError, RpcRequest, TokenAccountsFilter},
     rpc_response::*,
@@ -22,7 +22,7 @@ use solana_account_decoder::{
 };
 use solana_sdk::{
     account::Account,
-    clock::{Slot, UnixTimestamp, CFG as CLOCK_CFG},
+    clock::{Epoch, Slot, UnixTimestamp, CFG as CLOCK_CFG},
     commitment_config::{CommitmentConfig, CommitmentLevel},
     epoch_info::EpochInfo,
     epoch_schedule::EpochSchedule,
@@ -406,6 +406,8 @@ impl RpcClient {
             before: config.before.map(|signature| signature.to_string()),
             until: config.until.map(|signature| signature.to_string()),
             limit: config.limit,
+            min_slot: config.min_slot,
+            max_slot: config.max_slot,
         };
 
         let result: Vec<RpcConfirmedTransactionStatusWithSignature> = self.send(
@@ -473,6 +475,10 @@ impl RpcClient {
         )
     }
 
+    pub fn get_leader_slots(&self, config: RpcLeaderSlotsConfig) -> ClientResult<RpcLeaderSlots> {
+        self.send(RpcRequest::GetLeaderSlots, json!([config]))
+    }
+
     pub fn get_epoch_schedule(&self) -> ClientResult<EpochSchedule> {
         self.send(RpcRequest::GetEpochSchedule, Value::Null)
     }
@@ -488,6 +494,31 @@ impl RpcClient {
         })
     }
 
+    pub fn get_account_lock_contention(
+        &self,
+        limit: Option<usize>,
+    ) -> ClientResult<Vec<RpcAccountLockContention>> {
+        self.send(RpcRequest::GetAccountLockContention, json!([limit]))
+    }
+
+    pub fn get_accounts_hash_verification(&self) -> ClientResult<RpcAccountsHashVerification> {
+        self.send(RpcRequest::GetAccountsHashVerification, Value::Null)
+    }
+
+    pub fn get_snapshot_download_progress(
+        &self,
+    ) -> ClientResult<Option<RpcSnapshotDownloadProgress>> {
+        self.send(RpcRequest::GetSnapshotDownloadProgress, Value::Null)
+    }
+
+    pub fn get_replay_progress(&self) -> ClientResult<Option<RpcReplayProgress>> {
+        self.send(RpcRequest::GetReplayProgress, Value::Null)
+    }
+
+    pub fn get_supermajority_progress(&self) -> ClientResult<Option<RpcSupermajorityProgress>> {
+        self.send(RpcRequest::GetSupermajorityProgress, Value::Null)
+    }
+
     pub fn get_inflation_governor(&self) -> ClientResult<RpcInflationGovernor> {
         self.send(RpcRequest::GetInflationGovernor, Value::Null)
     }
@@ -496,6 +527,28 @@ impl RpcClient {
         self.send(RpcRequest::GetInflationRate, Value::Null)
     }
 
+    /// Returns the rewards `addresses` were paid for `epoch`, by default the last completed one
+    pub fn get_inflation_reward(
+        &self,
+        addresses: &[Pubkey],
+        epoch: Option<Epoch>,
+    ) -> ClientResult<Vec<Option<RpcInflationReward>>> {
+        let addresses: Vec<_> = addresses
+            .iter()
+            .map(|address| address.to_string())
+            .collect();
+        self.send(
+            RpcRequest::GetInflationReward,
+            json!([
+                addresses,
+                RpcStakeConfig {
+                    epoch,
+                    commitment: None,
+                }
+            ]),
+        )
+    }
+
     pub fn get_version(&self) -> ClientResult<RpcVersionInfo> {
         self.send(RpcRequest::GetVersion, Value::Null)
     }
@@ -680,6 +733,7 @@ impl RpcClient {
                     encoding: Some(UiAccountEncoding::Base64),
                     ..RpcAccountInfoConfig::default()
                 },
+                ..RpcProgramAccountsConfig::default()
             },
         )
     }
@@ -696,6 +750,28 @@ impl RpcClient {
         parse_keyed_accounts(accounts, RpcRequest::GetProgramAccounts)
     }
 
+    /// Request a page of at most `limit` accounts of a program, starting at `cursor`. Returns the
+    /// accounts and the cursor of the next page, if there are more accounts
+    pub fn get_program_accounts_page(
+        &self,
+        pubkey: &Pubkey,
+        config: RpcProgramAccountsConfig,
+        limit: usize,
+        cursor: Option<String>,
+    ) -> ClientResult<(Vec<(Pubkey, Account)>, Option<String>)> {
+        let config = RpcProgramAccountsConfig {
+            limit: Some(limit),
+            cursor,
+            ..config
+        };
+        let page: RpcKeyedAccountsPage = self.send(
+            RpcRequest::GetProgramAccounts,
+            json!([pubkey.to_string(), config]),
+        )?;
+        let accounts = parse_keyed_accounts(page.accounts, RpcRequest::GetProgramAccounts)?;
+        Ok((accounts, page.next_cursor))
+    }
+
     /// Request the transaction count.
     pub fn get_transaction_count(&self) -> ClientResult<u64> {
         self.get_transaction_count_with_commitment(CommitmentConfig::default())
@@ -1337,6 +1413,8 @@ pub struct GetConfirmedSignaturesForAddress2Config {
     pub before: Option<Signature>,
     pub until: Option<Signature>,
     pub limit: Option<usize>,
+    pub min_slot: Option<Slot>,
+    pub max_slot: Option<Slot>,
 }
 
 fn new_spinner_progress_bar() -> ProgressBar {
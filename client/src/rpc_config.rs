@@ -1,7 +1,7 @@
 use crate::rpc_filter::RpcFilterType;
 use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
 use solana_sdk::{
-    clock::Epoch,
+    clock::{Epoch, Slot},
     commitment_config::{CommitmentConfig, CommitmentLevel},
 };
 use solana_transaction_status::UiTransactionEncoding;
@@ -19,6 +19,8 @@ pub struct RpcSendTransactionConfig {
     pub skip_preflight: bool,
     pub preflight_commitment: Option<CommitmentLevel>,
     pub encoding: Option<UiTransactionEncoding>,
+    /// Emit latency trace events tagged with this id as the node processes the transaction
+    pub trace_id: Option<u64>,
 }
 
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
@@ -29,6 +31,19 @@ pub struct RpcSimulateTransactionConfig {
     #[serde(flatten)]
     pub commitment: Option<CommitmentConfig>,
     pub encoding: Option<UiTransactionEncoding>,
+    /// Simulate against the bank of this slot instead of the one selected by `commitment`
+    pub slot: Option<Slot>,
+    /// Record and return the instructions invoked by each transaction instruction
+    #[serde(default)]
+    pub inner_instructions: bool,
+    pub accounts: Option<RpcSimulateTransactionAccountsConfig>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSimulateTransactionAccountsConfig {
+    pub encoding: Option<UiAccountEncoding>,
+    pub addresses: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -54,6 +69,20 @@ pub struct RpcStakeConfig {
     pub commitment: Option<CommitmentConfig>,
 }
 
+/// Selects the slots of `getLeaderSlots`: those of `epoch`, or from `start_slot`, by default
+/// the current slot, to `end_slot`, by default the last slot of the epoch of `start_slot`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcLeaderSlotsConfig {
+    pub epoch: Option<Epoch>,
+    pub start_slot: Option<Slot>,
+    pub end_slot: Option<Slot>,
+    /// Only return the slots led by this identity
+    pub identity: Option<String>,
+    #[serde(flatten)]
+    pub commitment: Option<CommitmentConfig>,
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RpcAccountInfoConfig {
@@ -69,6 +98,11 @@ pub struct RpcProgramAccountsConfig {
     pub filters: Option<Vec<RpcFilterType>>,
     #[serde(flatten)]
     pub account_config: RpcAccountInfoConfig,
+    /// Maximum number of accounts returned by getProgramAccounts. When set, the accounts are
+    /// returned as a page along with the cursor of the next page
+    pub limit: Option<usize>,
+    /// Cursor returned with the previous page
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -86,10 +120,29 @@ pub struct RpcSignatureSubscribeConfig {
     pub enable_received_notification: Option<bool>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTransactionLogsConfig {
+    pub mentions: Option<String>, // Pubkey as base-58 string
+    pub limit: Option<usize>,
+    #[serde(flatten)]
+    pub commitment: Option<CommitmentConfig>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTransactionLogsSubscribeConfig {
+    pub mentions: Option<String>, // Pubkey as base-58 string
+    #[serde(flatten)]
+    pub commitment: Option<CommitmentConfig>,
+}
+
 #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RpcGetConfirmedSignaturesForAddress2Config {
     pub before: Option<String>, // Signature as base-58 string
     pub until: Option<String>,  // Signature as base-58 string
     pub limit: Option<usize>,
+    pub min_slot: Option<Slot>,
+    pub max_slot: Option<Slot>,
 }
@@ -10,6 +10,7 @@ pub const JSON_RPC_SERVER_ERROR_TRANSACTION_SIGNATURE_VERIFICATION_FAILURE: i64
 pub const JSON_RPC_SERVER_ERROR_BLOCK_NOT_AVAILABLE: i64 = -32004;
 pub const JSON_RPC_SERVER_ERROR_NODE_UNHEALTHLY: i64 = -32005;
 pub const JSON_RPC_SERVER_ERROR_TRANSACTION_PRECOMPILE_VERIFICATION_FAILURE: i64 = -32006;
+pub const JSON_RPC_SERVER_ERROR_BATCH_COMPUTE_BUDGET_EXCEEDED: i64 = -32007;
 
 pub enum RpcCustomError {
     BlockCleanedUp {
@@ -26,6 +27,9 @@ pub enum RpcCustomError {
     },
     RpcNodeUnhealthy,
     TransactionPrecompileVerificationFailure(solana_sdk::transaction::TransactionError),
+    BatchComputeBudgetExceeded {
+        budget_ms: u64,
+    },
 }
 
 impl From<RpcCustomError> for Error {
@@ -73,6 +77,14 @@ impl From<RpcCustomError> for Error {
                 message: format!("Transaction precompile verification failure {:?}", e),
                 data: None,
             },
+            RpcCustomError::BatchComputeBudgetExceeded { budget_ms } => Self {
+                code: ErrorCode::ServerError(JSON_RPC_SERVER_ERROR_BATCH_COMPUTE_BUDGET_EXCEEDED),
+                message: format!(
+                    "Batch compute budget of {}ms exceeded, request not executed",
+                    budget_ms
+                ),
+                data: None,
+            },
         }
     }
 }
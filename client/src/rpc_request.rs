@@ -8,6 +8,8 @@ pub enum RpcRequest {
     DeregisterNode,
     ValidatorExit,
     GetAccountInfo,
+    GetAccountLockContention,
+    GetAccountsHashVerification,
     GetBalance,
     GetBlockTime,
     GetClusterNodes,
@@ -27,19 +29,24 @@ pub enum RpcRequest {
     GetIdentity,
     GetInflationGovernor,
     GetInflationRate,
+    GetInflationReward,
     GetLargestAccounts,
     GetLeaderSchedule,
+    GetLeaderSlots,
     GetMinimumBalanceForRentExemption,
     GetMultipleAccounts,
     GetProgramAccounts,
     GetRecentBlockhash,
+    GetReplayProgress,
     GetSignatureStatuses,
     GetSlot,
     GetSlotLeader,
     GetStorageTurn,
     GetStorageTurnRate,
     GetSlotsPerSegment,
+    GetSnapshotDownloadProgress,
     GetStoragePubkeysForSlot,
+    GetSupermajorityProgress,
     GetSupply,
     GetTokenAccountBalance,
     GetTokenAccountsByDelegate,
@@ -63,6 +70,8 @@ impl fmt::Display for RpcRequest {
             RpcRequest::DeregisterNode => "deregisterNode",
             RpcRequest::ValidatorExit => "validatorExit",
             RpcRequest::GetAccountInfo => "getAccountInfo",
+            RpcRequest::GetAccountLockContention => "getAccountLockContention",
+            RpcRequest::GetAccountsHashVerification => "getAccountsHashVerification",
             RpcRequest::GetBalance => "getBalance",
             RpcRequest::GetBlockTime => "getBlockTime",
             RpcRequest::GetClusterNodes => "getClusterNodes",
@@ -82,19 +91,24 @@ impl fmt::Display for RpcRequest {
             RpcRequest::GetIdentity => "getIdentity",
             RpcRequest::GetInflationGovernor => "getInflationGovernor",
             RpcRequest::GetInflationRate => "getInflationRate",
+            RpcRequest::GetInflationReward => "getInflationReward",
             RpcRequest::GetLargestAccounts => "getLargestAccounts",
             RpcRequest::GetLeaderSchedule => "getLeaderSchedule",
+            RpcRequest::GetLeaderSlots => "getLeaderSlots",
             RpcRequest::GetMinimumBalanceForRentExemption => "getMinimumBalanceForRentExemption",
             RpcRequest::GetMultipleAccounts => "getMultipleAccounts",
             RpcRequest::GetProgramAccounts => "getProgramAccounts",
             RpcRequest::GetRecentBlockhash => "getRecentBlockhash",
+            RpcRequest::GetReplayProgress => "getReplayProgress",
             RpcRequest::GetSignatureStatuses => "getSignatureStatuses",
             RpcRequest::GetSlot => "getSlot",
             RpcRequest::GetSlotLeader => "getSlotLeader",
             RpcRequest::GetStorageTurn => "getStorageTurn",
             RpcRequest::GetStorageTurnRate => "getStorageTurnRate",
             RpcRequest::GetSlotsPerSegment => "getSlotsPerSegment",
+            RpcRequest::GetSnapshotDownloadProgress => "getSnapshotDownloadProgress",
             RpcRequest::GetStoragePubkeysForSlot => "getStoragePubkeysForSlot",
+            RpcRequest::GetSupermajorityProgress => "getSupermajorityProgress",
             RpcRequest::GetSupply => "getSupply",
             RpcRequest::GetTokenAccountBalance => "getTokenAccountBalance",
             RpcRequest::GetTokenAccountsByDelegate => "getTokenAccountsByDelegate",
@@ -119,9 +133,14 @@ impl fmt::Display for RpcRequest {
 pub const MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS: usize = 256;
 pub const MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS_SLOT_RANGE: u64 = 10_000;
 pub const MAX_GET_CONFIRMED_BLOCKS_RANGE: u64 = 500_000;
+pub const MAX_GET_LEADER_SLOTS_RANGE: u64 = 500_000;
 pub const MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT: usize = 1_000;
 pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+pub const MAX_GET_PROGRAM_ACCOUNTS_LIMIT: usize = 1_000;
+pub const MAX_GET_RECENT_TRANSACTION_LOGS_LIMIT: usize = 1_000;
 pub const NUM_LARGEST_ACCOUNTS: usize = 20;
+pub const NUM_MOST_CONTENDED_ACCOUNTS: usize = 20;
+pub const MAX_GET_ACCOUNT_LOCK_CONTENTION_LIMIT: usize = 1_000;
 
 // Validators that are this number of slots behind are considered delinquent
 pub const DELINQUENT_VALIDATOR_SLOT_DISTANCE: u64 = 128;
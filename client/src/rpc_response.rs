@@ -6,7 +6,7 @@ use solana_sdk::{
     inflation::Inflation,
     transaction::{Result, TransactionError},
 };
-use solana_transaction_status::ConfirmedTransactionStatusWithSignature;
+use solana_transaction_status::{ConfirmedTransactionStatusWithSignature, UiInnerInstructions};
 use std::{collections::HashMap, fmt, net::SocketAddr};
 
 pub type RpcResult<T> = client_error::Result<Response<T>>;
@@ -87,6 +87,33 @@ pub struct RpcInflationRate {
     pub epoch: Epoch,
 }
 
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcInflationReward {
+    pub epoch: Epoch,
+    /// Slot of the block which paid the reward
+    pub effective_slot: Slot,
+    pub amount: u64,
+    pub post_balance: u64,
+    /// Stake the reward was earned with, if still known to the node
+    pub effective_stake: Option<u64>,
+    /// Commission of the vote account during the rewarded epoch
+    pub commission: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcEpochRewardsDistribution {
+    pub rewarded_epoch: Epoch,
+    pub calculation_slot: Slot,
+    pub calculation_block_height: u64,
+    pub end_block_height: u64,
+    pub num_partitions: u64,
+    pub distributed_partitions: u64,
+    pub num_stake_accounts: u64,
+    pub total_stake_rewards: u64,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct RpcKeyedAccount {
@@ -94,6 +121,61 @@ pub struct RpcKeyedAccount {
     pub account: UiAccount,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcKeyedAccountsPage {
+    pub accounts: Vec<RpcKeyedAccount>,
+    /// Cursor of the next page, if there are more accounts
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", untagged)]
+pub enum RpcProgramAccounts {
+    Page(RpcKeyedAccountsPage),
+    Accounts(Vec<RpcKeyedAccount>),
+}
+
+/// An event in the processing of a slot, with the time it occurred, in milliseconds since the
+/// UNIX epoch
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum SlotUpdate {
+    FirstShredReceived {
+        slot: Slot,
+        timestamp: u64,
+    },
+    ReplayStarted {
+        slot: Slot,
+        parent: Slot,
+        timestamp: u64,
+    },
+    Frozen {
+        slot: Slot,
+        timestamp: u64,
+    },
+    OptimisticConfirmation {
+        slot: Slot,
+        timestamp: u64,
+    },
+    Root {
+        slot: Slot,
+        timestamp: u64,
+    },
+}
+
+impl SlotUpdate {
+    pub fn slot(&self) -> Slot {
+        match self {
+            Self::FirstShredReceived { slot, .. }
+            | Self::ReplayStarted { slot, .. }
+            | Self::Frozen { slot, .. }
+            | Self::OptimisticConfirmation { slot, .. }
+            | Self::Root { slot, .. } => *slot,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
 pub struct SlotInfo {
     pub slot: Slot,
@@ -135,11 +217,73 @@ pub struct RpcContactInfo {
     pub version: Option<String>,
     /// First 4 bytes of the FeatureSet identifier
     pub feature_set: Option<u32>,
+    /// Info the validator published in gossip
+    pub validator_info: Option<RpcValidatorInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcValidatorInfo {
+    pub name: String,
+    pub website: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcPeerBandwidth {
+    /// Pubkey of the node as a base-58 string
+    pub pubkey: String,
+    /// Total bytes served to the node over repair and retransmit
+    pub bytes_served: u64,
+    /// Total bytes received from the node over repair and retransmit
+    pub bytes_received: u64,
+    /// Ratio of recent bytes received from the node to recent bytes served to it
+    pub score: f64,
+    /// Whether the node is taking far more than it gives back
+    pub leecher: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcGossipPeerStats {
+    /// Pubkey of the node as a base-58 string
+    pub pubkey: String,
+    pub push_messages_received: u64,
+    pub pull_requests_served: u64,
+    pub prunes_sent: u64,
+    pub prunes_received: u64,
+    /// Bytes of the gossip packets received claiming to be from the node
+    pub bytes_received: u64,
+    /// Bytes of the gossip messages sent to the node
+    pub bytes_sent: u64,
+    /// Why the last rejected gossip message from the node was rejected
+    pub last_error: Option<String>,
+    /// Timestamp of the last gossip message exchanged with the node, in ms
+    pub last_updated: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAncestorHashesReport {
+    /// Slot replay marked dead
+    pub slot: Slot,
+    /// Earliest slot whose hash differs from the one reported by most peers, if any
+    pub divergent_slot: Option<Slot>,
+    /// Number of peers which responded with their ancestor hashes
+    pub num_responses: usize,
+    /// Number of the responding peers which agree on `divergent_slot`
+    pub num_agreeing: usize,
+    /// Time of the report, in milliseconds since the UNIX epoch
+    pub timestamp: u64,
 }
 
 /// Map of leader base58 identity pubkeys to the slot indices relative to the first epoch slot
 pub type RpcLeaderSchedule = HashMap<String, Vec<usize>>;
 
+/// Map of leader base58 identity pubkeys to the absolute slots they lead
+pub type RpcLeaderSlots = HashMap<String, Vec<Slot>>;
+
 #[derive(Serialize, Deserialize, Clone)]
 #[serde(rename_all = "kebab-case")]
 pub struct RpcVersionInfo {
@@ -221,6 +365,8 @@ pub struct RpcSignatureConfirmation {
 pub struct RpcSimulateTransactionResult {
     pub err: Option<TransactionError>,
     pub logs: Option<Vec<String>>,
+    pub inner_instructions: Option<Vec<UiInnerInstructions>>,
+    pub accounts: Option<Vec<Option<UiAccount>>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -237,6 +383,118 @@ pub struct RpcAccountBalance {
     pub lamports: u64,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcQuarantinedAccount {
+    pub address: String,
+    pub data_len: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum RpcMetricValue {
+    Counter {
+        value: u64,
+    },
+    Gauge {
+        value: i64,
+    },
+    Histogram {
+        count: u64,
+        sum: u64,
+        min: u64,
+        max: u64,
+    },
+}
+
+/// Comparison of the accounts hashes of the node with the ones of its trusted validators
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccountsHashVerification {
+    /// Highest slot whose accounts hash a quorum of the trusted validators agreed with
+    pub last_verified_slot: Option<Slot>,
+    pub last_verified_hash: Option<String>,
+    /// Slot whose accounts hash a quorum of the trusted validators conflicted with
+    pub mismatch_slot: Option<Slot>,
+}
+
+/// Progress of the snapshot download of a bootstrapping node
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSnapshotDownloadProgress {
+    pub slot: Slot,
+    pub downloaded_bytes: u64,
+    /// Size of the snapshot, if the node it is downloaded from reported it
+    pub total_bytes: Option<u64>,
+    pub bytes_per_second: u64,
+    /// Estimated time left, in seconds
+    pub estimated_remaining_secs: Option<u64>,
+}
+
+/// Progress of the ledger replay of a starting node
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcReplayProgress {
+    /// Last slot replayed
+    pub slot: Slot,
+    /// Highest slot in the ledger, up to which the node replays
+    pub highest_slot: Slot,
+    pub num_entries: usize,
+    pub num_txs: usize,
+    /// Time it took to replay `slot`, in milliseconds
+    pub slot_replay_ms: u64,
+    /// Estimated time left, in seconds
+    pub estimated_remaining_secs: u64,
+}
+
+/// Stake observed in gossip while the node waits for a supermajority to restart the cluster
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSupermajorityProgress {
+    pub slot: Slot,
+    pub bank_hash: String,
+    pub total_stake: u64,
+    pub online_stake: u64,
+    pub stake_by_version: HashMap<String, u64>,
+    /// Stake of the online nodes by the bank hash they advertised for `slot`
+    pub stake_by_bank_hash: HashMap<String, u64>,
+    pub wrong_shred_version: Vec<RpcSupermajorityNode>,
+    /// Staked nodes not yet in gossip
+    pub offline: Vec<RpcSupermajorityNode>,
+    /// Online nodes restarting from another bank hash than the node
+    pub mismatched_bank_hash: Vec<RpcSupermajorityNode>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSupermajorityNode {
+    pub identity: String,
+    pub stake: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bank_hash: Option<String>,
+}
+
+/// How often the locks of an account kept transactions from being batched with others
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAccountLockContention {
+    pub pubkey: String,
+    pub write_conflicts: u64,
+    pub read_conflicts: u64,
+    pub shared_readonly_locks: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcMetric {
+    pub namespace: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    pub name: String,
+    #[serde(flatten)]
+    pub value: RpcMetricValue,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct RpcSupply {
@@ -280,6 +538,15 @@ pub struct RpcConfirmedTransactionStatusWithSignature {
     pub memo: Option<String>,
 }
 
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcLogsResponse {
+    pub signature: String, // Signature as base58 string
+    pub slot: Slot,
+    pub err: Option<TransactionError>,
+    pub logs: Vec<String>,
+}
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct RpcPerfSample {
@@ -6,11 +6,13 @@ use crossbeam_channel::unbounded;
 use log::*;
 use rand::{thread_rng, Rng};
 use rayon::prelude::*;
+use solana_core::banking_scheduler::BankingScheduler;
 use solana_core::banking_stage::{create_test_recorder, BankingStage};
 use solana_core::cluster_info::ClusterInfo;
 use solana_core::cluster_info::Node;
 use solana_core::poh_recorder::WorkingBankEntry;
 use solana_ledger::blockstore_processor::process_entries;
+use solana_ledger::cost_model::CostTracker;
 use solana_ledger::entry::{next_hash, Entry};
 use solana_ledger::genesis_utils::{create_genesis_config, GenesisConfigInfo};
 use solana_ledger::{blockstore::Blockstore, get_tmp_ledger_path};
@@ -30,7 +32,7 @@ use solana_sdk::timing::{duration_as_us, timestamp};
 use solana_sdk::transaction::Transaction;
 use std::sync::atomic::Ordering;
 use std::sync::mpsc::Receiver;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::{Duration, Instant};
 use test::Bencher;
 
@@ -74,6 +76,8 @@ fn bench_consume_buffered(bencher: &mut Bencher) {
             packets.push((batch, vec![0usize; batch_len]));
         }
         let (s, _r) = unbounded();
+        let cost_tracker = RwLock::new(CostTracker::default());
+        let scheduler = BankingScheduler::default();
         // This tests the performance of buffering packets.
         // If the packet buffers are copied, performance will be poor.
         bencher.iter(move || {
@@ -84,6 +88,8 @@ fn bench_consume_buffered(bencher: &mut Bencher) {
                 10_000,
                 None,
                 &s,
+                &cost_tracker,
+                &scheduler,
             );
         });
 
@@ -4,7 +4,7 @@ extern crate test;
 
 use rand::{thread_rng, Rng};
 use rayon::ThreadPoolBuilder;
-use solana_core::crds::Crds;
+use solana_core::crds::{Crds, CrdsTtls};
 use solana_core::crds_gossip_pull::CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS;
 use solana_core::crds_value::CrdsValue;
 use solana_sdk::pubkey::Pubkey;
@@ -23,7 +23,7 @@ fn bench_find_old_labels(bencher: &mut Bencher) {
     let mut timeouts = HashMap::new();
     timeouts.insert(Pubkey::default(), CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS);
     bencher.iter(|| {
-        let out = crds.find_old_labels(&thread_pool, now, &timeouts);
+        let out = crds.find_old_labels(&thread_pool, now, &timeouts, &CrdsTtls::default());
         assert!(out.len() > 10);
         assert!(out.len() < 250);
         out
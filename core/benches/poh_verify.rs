@@ -1,7 +1,8 @@
 #![feature(test)]
 extern crate test;
 
-use solana_ledger::entry::{next_entry_mut, Entry, EntrySlice};
+use solana_ledger::entry::{next_entry_mut, Entry, EntrySlice, EntryVerificationStatus};
+use solana_perf::perf_libs;
 use solana_sdk::hash::{hash, Hash};
 use solana_sdk::signature::{Keypair, Signer};
 use solana_sdk::system_transaction;
@@ -10,9 +11,7 @@ use test::Bencher;
 const NUM_HASHES: u64 = 400;
 const NUM_ENTRIES: usize = 800;
 
-#[bench]
-fn bench_poh_verify_ticks(bencher: &mut Bencher) {
-    solana_logger::setup();
+fn create_ticks() -> (Hash, Vec<Entry>) {
     let zero = Hash::default();
     let start_hash = hash(&zero.as_ref());
     let mut cur_hash = start_hash;
@@ -21,6 +20,40 @@ fn bench_poh_verify_ticks(bencher: &mut Bencher) {
     for _ in 0..NUM_ENTRIES {
         ticks.push(next_entry_mut(&mut cur_hash, NUM_HASHES, vec![]));
     }
+    (start_hash, ticks)
+}
+
+#[bench]
+fn bench_poh_verify_ticks(bencher: &mut Bencher) {
+    solana_logger::setup();
+    let (start_hash, ticks) = create_ticks();
+
+    bencher.iter(|| {
+        assert!(ticks.verify(&start_hash));
+    })
+}
+
+#[bench]
+fn bench_poh_verify_ticks_cpu(bencher: &mut Bencher) {
+    let (start_hash, ticks) = create_ticks();
+
+    bencher.iter(|| {
+        assert_eq!(
+            ticks.verify_cpu(&start_hash).status(),
+            EntryVerificationStatus::Success
+        );
+    })
+}
+
+// Once CUDA is initialized, the other benchmarks of this process verify on the GPU as well.
+// Without CUDA this is a no-op.
+#[bench]
+fn bench_poh_verify_ticks_gpu(bencher: &mut Bencher) {
+    perf_libs::init_cuda();
+    if perf_libs::api().is_none() {
+        return;
+    }
+    let (start_hash, ticks) = create_ticks();
 
     bencher.iter(|| {
         assert!(ticks.verify(&start_hash));
@@ -78,6 +78,7 @@ fn bench_retransmitter(bencher: &mut Bencher) {
         &leader_schedule_cache,
         cluster_info,
         packet_receiver,
+        None,
     );
 
     let total = Arc::new(AtomicUsize::new(0));
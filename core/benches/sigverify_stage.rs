@@ -23,7 +23,8 @@ fn bench_sigverify_stage(bencher: &mut Bencher) {
     solana_logger::setup();
     let (packet_s, packet_r) = channel();
     let (verified_s, verified_r) = unbounded();
-    let verifier = TransactionSigVerifier::default();
+    // The same transactions are sent over and over
+    let verifier = TransactionSigVerifier::new(false);
     let stage = SigVerifyStage::new(packet_r, verified_s, verifier);
 
     let now = Instant::now();
@@ -0,0 +1,7 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use solana_core::cluster_info::check_gossip_packet;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = check_gossip_packet(data);
+});
@@ -0,0 +1,36 @@
+#![no_main]
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use solana_core::{cluster_info::ClusterInfo, contact_info::ContactInfo};
+use solana_perf::packet::{Packet, Packets, PACKET_DATA_SIZE};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    timing::timestamp,
+};
+use std::sync::Arc;
+
+lazy_static! {
+    static ref CLUSTER_INFO: ClusterInfo = {
+        let keypair = Arc::new(Keypair::new());
+        ClusterInfo::new(
+            ContactInfo::new_localhost(&keypair.pubkey(), timestamp()),
+            keypair,
+        )
+    };
+    static ref THREAD_POOL: ThreadPool = ThreadPoolBuilder::new().num_threads(1).build().unwrap();
+}
+
+// Every PACKET_DATA_SIZE bytes of the input make one packet
+fuzz_target!(|data: &[u8]| {
+    let packets = data
+        .chunks(PACKET_DATA_SIZE)
+        .map(|chunk| {
+            let mut packet = Packet::default();
+            packet.data[..chunk.len()].copy_from_slice(chunk);
+            packet.meta.size = chunk.len();
+            packet
+        })
+        .collect();
+    CLUSTER_INFO.process_packets_for_fuzzing(vec![Packets::new(packets)], &THREAD_POOL);
+});
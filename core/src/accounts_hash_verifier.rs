@@ -1,10 +1,11 @@
 // Service to verify accounts hashes with other trusted validator nodes.
 //
 // Each interval, publish the snapshat hash which is the full accounts state
-// hash on gossip. Monitor gossip for messages from validators in the --trusted-validators
-// set and halt the node if a mismatch is detected.
+// hash on gossip. Periodically compare the recent hashes with the ones published by the
+// validators in the --trusted-validators set, and halt the node if a quorum of them conflicts
+// with it.
 
-use crate::cluster_info::{ClusterInfo, CFG};
+use crate::cluster_info::{ClusterInfo, CFG as CLUSTER_INFO_CFG};
 use solana_runtime::snapshot_package::{
     AccountsPackage, AccountsPackageReceiver, AccountsPackageSender,
 };
@@ -14,12 +15,27 @@ use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc::RecvTimeoutError,
-        Arc,
+        Arc, RwLock,
     },
     thread::{self, Builder, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
+toml_config::package_config! {
+    ACCOUNTS_HASH_VERIFY_INTERVAL_MS: u64,
+    ACCOUNTS_HASH_QUORUM: f64,
+}
+
+/// Latest comparison of the accounts hashes with the ones of the trusted validators
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AccountsHashVerification {
+    /// Highest slot, and its hash, a quorum of the trusted validators agreed with
+    pub last_verified: Option<(Slot, Hash)>,
+    /// Slot a quorum of the trusted validators conflicted with, the lowest one of the window of
+    /// recent hashes in which a mismatch was last detected. Cleared once a later slot is verified
+    pub mismatch: Option<Slot>,
+}
+
 pub struct AccountsHashVerifier {
     t_accounts_hash_verifier: JoinHandle<()>,
 }
@@ -34,6 +50,7 @@ impl AccountsHashVerifier {
         halt_on_trusted_validators_accounts_hash_mismatch: bool,
         fault_injection_rate_slots: u64,
         snapshot_interval_slots: u64,
+        verification: Arc<RwLock<AccountsHashVerification>>,
     ) -> Self {
         let exit = exit.clone();
         let cluster_info = cluster_info.clone();
@@ -41,6 +58,8 @@ impl AccountsHashVerifier {
             .name("solana-accounts-hash".to_string())
             .spawn(move || {
                 let mut hashes = vec![];
+                let verify_interval = Duration::from_millis(CFG.ACCOUNTS_HASH_VERIFY_INTERVAL_MS);
+                let mut last_verify = Instant::now();
                 loop {
                     if exit.load(Ordering::Relaxed) {
                         break;
@@ -51,11 +70,8 @@ impl AccountsHashVerifier {
                             Self::process_accounts_package(
                                 accounts_package,
                                 &cluster_info,
-                                &trusted_validators,
-                                halt_on_trusted_validators_accounts_hash_mismatch,
                                 &accounts_package_sender,
                                 &mut hashes,
-                                &exit,
                                 fault_injection_rate_slots,
                                 snapshot_interval_slots,
                             );
@@ -63,6 +79,20 @@ impl AccountsHashVerifier {
                         Err(RecvTimeoutError::Disconnected) => break,
                         Err(RecvTimeoutError::Timeout) => (),
                     }
+
+                    if last_verify.elapsed() >= verify_interval {
+                        last_verify = Instant::now();
+                        if let Some(trusted_validators) = trusted_validators.as_ref() {
+                            Self::verify(
+                                &cluster_info,
+                                trusted_validators,
+                                halt_on_trusted_validators_accounts_hash_mismatch,
+                                &hashes,
+                                &verification,
+                                &exit,
+                            );
+                        }
+                    }
                 }
             })
             .unwrap();
@@ -74,11 +104,8 @@ impl AccountsHashVerifier {
     fn process_accounts_package(
         accounts_package: AccountsPackage,
         cluster_info: &ClusterInfo,
-        trusted_validators: &Option<HashSet<Pubkey>>,
-        halt_on_trusted_validator_accounts_hash_mismatch: bool,
         accounts_package_sender: &Option<AccountsPackageSender>,
         hashes: &mut Vec<(Slot, Hash)>,
-        exit: &Arc<AtomicBool>,
         fault_injection_rate_slots: u64,
         snapshot_interval_slots: u64,
     ) {
@@ -96,20 +123,10 @@ impl AccountsHashVerifier {
             hashes.push((accounts_package.root, accounts_package.hash));
         }
 
-        while hashes.len() > CFG.MAX_SNAPSHOT_HASHES {
+        while hashes.len() > CLUSTER_INFO_CFG.MAX_SNAPSHOT_HASHES {
             hashes.remove(0);
         }
 
-        if halt_on_trusted_validator_accounts_hash_mismatch {
-            let mut slot_to_hash = HashMap::new();
-            for (slot, hash) in hashes.iter() {
-                slot_to_hash.insert(*slot, *hash);
-            }
-            if Self::should_halt(&cluster_info, trusted_validators, &mut slot_to_hash) {
-                exit.store(true, Ordering::Relaxed);
-            }
-        }
-
         if accounts_package.block_height % snapshot_interval_slots == 0 {
             if let Some(sender) = accounts_package_sender.as_ref() {
                 if sender.send(accounts_package).is_err() {}
@@ -119,50 +136,104 @@ impl AccountsHashVerifier {
         cluster_info.push_accounts_hashes(hashes.clone());
     }
 
-    fn should_halt(
+    fn verify(
         cluster_info: &ClusterInfo,
-        trusted_validators: &Option<HashSet<Pubkey>>,
-        slot_to_hash: &mut HashMap<Slot, Hash>,
-    ) -> bool {
-        let mut verified_count = 0;
-        let mut highest_slot = 0;
-        if let Some(trusted_validators) = trusted_validators.as_ref() {
-            for trusted_validator in trusted_validators {
-                let is_conflicting = cluster_info.get_accounts_hash_for_node(trusted_validator, |accounts_hashes|
-                {
-                    accounts_hashes.iter().any(|(slot, hash)| {
-                        if let Some(reference_hash) = slot_to_hash.get(slot) {
-                            if *hash != *reference_hash {
-                                error!("Trusted validator {} produced conflicting hashes for slot: {} ({} != {})",
-                                    trusted_validator,
-                                    slot,
-                                    hash,
-                                    reference_hash,
-                                );
-                                true
-                            } else {
-                                verified_count += 1;
-                                false
-                            }
+        trusted_validators: &HashSet<Pubkey>,
+        halt_on_trusted_validators_accounts_hash_mismatch: bool,
+        hashes: &[(Slot, Hash)],
+        verification: &RwLock<AccountsHashVerification>,
+        exit: &AtomicBool,
+    ) {
+        let window_verification = Self::compare_hashes(
+            cluster_info,
+            trusted_validators,
+            hashes,
+            CFG.ACCOUNTS_HASH_QUORUM,
+        );
+        if window_verification.mismatch.is_some()
+            && halt_on_trusted_validators_accounts_hash_mismatch
+        {
+            exit.store(true, Ordering::Relaxed);
+        }
+        let mut verification = verification.write().unwrap();
+        // The slots verified earlier may have left the window of recent hashes
+        if window_verification.last_verified.is_some() {
+            verification.last_verified = window_verification.last_verified;
+        }
+        let last_verified_slot = verification.last_verified.map(|(slot, _)| slot);
+        let mismatch = window_verification.mismatch.or_else(|| {
+            verification
+                .mismatch
+                .filter(|mismatch| last_verified_slot.map_or(true, |slot| slot < *mismatch))
+        });
+        verification.mismatch = mismatch;
+    }
+
+    // Compares the hashes of the slots in `hashes` with the ones the trusted validators published
+    // for the same slots. A slot is verified once more than `quorum` of all the trusted validators
+    // agree with this node, and mismatched once more than `quorum` of them conflict with it, so
+    // that a few faulty trusted validators, or the only ones which published so far, can neither
+    // halt the node nor vouch for it.
+    fn compare_hashes(
+        cluster_info: &ClusterInfo,
+        trusted_validators: &HashSet<Pubkey>,
+        hashes: &[(Slot, Hash)],
+        quorum: f64,
+    ) -> AccountsHashVerification {
+        let slot_to_hash: HashMap<Slot, Hash> = hashes.iter().cloned().collect();
+        // Trusted validators agreeing and conflicting with this node for each slot
+        let mut slot_votes: HashMap<Slot, (Vec<Pubkey>, Vec<Pubkey>)> = HashMap::new();
+        for trusted_validator in trusted_validators {
+            cluster_info.get_accounts_hash_for_node(trusted_validator, |accounts_hashes| {
+                for (slot, hash) in accounts_hashes {
+                    if let Some(reference_hash) = slot_to_hash.get(slot) {
+                        let (agreeing, conflicting) = slot_votes.entry(*slot).or_default();
+                        if hash == reference_hash {
+                            agreeing.push(*trusted_validator);
                         } else {
-                            highest_slot = std::cmp::max(*slot, highest_slot);
-                            slot_to_hash.insert(*slot, *hash);
-                            false
+                            conflicting.push(*trusted_validator);
                         }
-                    })
-                }).unwrap_or(false);
+                    }
+                }
+            });
+        }
 
-                if is_conflicting {
-                    return true;
+        let mut verification = AccountsHashVerification::default();
+        let mut verified_count = 0;
+        let quorum_count = quorum * trusted_validators.len() as f64;
+        for (slot, (agreeing, conflicting)) in slot_votes {
+            if conflicting.len() as f64 > quorum_count {
+                error!(
+                    "Trusted validators {:?} produced conflicting hashes for slot: {} ({} expected)",
+                    conflicting, slot, slot_to_hash[&slot],
+                );
+                if verification
+                    .mismatch
+                    .map_or(true, |mismatch| slot < mismatch)
+                {
+                    verification.mismatch = Some(slot);
+                }
+            } else if agreeing.len() as f64 > quorum_count {
+                verified_count += 1;
+                if verification
+                    .last_verified
+                    .map_or(true, |(last_verified, _)| slot > last_verified)
+                {
+                    verification.last_verified = Some((slot, slot_to_hash[&slot]));
                 }
             }
         }
         inc_new_counter_info!("accounts_hash_verifier-hashes_verified", verified_count);
         datapoint_info!(
             "accounts_hash_verifier",
-            ("highest_slot_verified", highest_slot, i64),
+            (
+                "highest_slot_verified",
+                verification.last_verified.map_or(0, |(slot, _)| slot),
+                i64
+            ),
+            ("mismatch", verification.mismatch.is_some(), bool),
         );
-        false
+        verification
     }
 
     pub fn join(self) -> thread::Result<()> {
@@ -175,7 +246,9 @@ mod tests {
     use super::*;
     use crate::cluster_info::make_accounts_hashes_message;
     use crate::contact_info::ContactInfo;
-    use solana_runtime::bank_forks::CompressionType;
+    use solana_runtime::bank_forks::{
+        CompressionOptions, CompressionType, SnapshotThrottleOptions,
+    };
     use solana_runtime::snapshot_utils::SnapshotVersion;
     use solana_sdk::{
         hash::hash,
@@ -183,7 +256,7 @@ mod tests {
     };
 
     #[test]
-    fn test_should_halt() {
+    fn test_compare_hashes() {
         let keypair = Keypair::new();
 
         let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
@@ -191,28 +264,47 @@ mod tests {
         let cluster_info = Arc::new(cluster_info);
 
         let mut trusted_validators = HashSet::new();
-        let mut slot_to_hash = HashMap::new();
-        assert!(!AccountsHashVerifier::should_halt(
-            &cluster_info,
-            &Some(trusted_validators.clone()),
-            &mut slot_to_hash,
-        ));
-
-        let validator1 = Keypair::new();
         let hash1 = hash(&[1]);
         let hash2 = hash(&[2]);
-        {
-            let message = make_accounts_hashes_message(&validator1, vec![(0, hash1)]).unwrap();
+        let hashes = vec![(0, hash2), (1, hash1)];
+        assert_eq!(
+            AccountsHashVerifier::compare_hashes(&cluster_info, &trusted_validators, &hashes, 0.5),
+            AccountsHashVerification::default()
+        );
+
+        let validators: Vec<_> = (0..3).map(|_| Keypair::new()).collect();
+        let publish = |validator: &Keypair, hashes| {
+            let message = make_accounts_hashes_message(validator, hashes).unwrap();
             cluster_info.push_message(message);
             cluster_info.flush_push_queue();
-        }
-        slot_to_hash.insert(0, hash2);
-        trusted_validators.insert(validator1.pubkey());
-        assert!(AccountsHashVerifier::should_halt(
-            &cluster_info,
-            &Some(trusted_validators),
-            &mut slot_to_hash,
-        ));
+        };
+        publish(&validators[0], vec![(0, hash1), (1, hash1)]);
+        trusted_validators.insert(validators[0].pubkey());
+        assert_eq!(
+            AccountsHashVerifier::compare_hashes(&cluster_info, &trusted_validators, &hashes, 0.5),
+            AccountsHashVerification {
+                last_verified: Some((1, hash1)),
+                mismatch: Some(0),
+            }
+        );
+
+        // A single conflicting trusted validator out of three is not a quorum
+        trusted_validators.extend(validators[1..].iter().map(|validator| validator.pubkey()));
+        assert_eq!(
+            AccountsHashVerifier::compare_hashes(&cluster_info, &trusted_validators, &hashes, 0.5),
+            AccountsHashVerification::default()
+        );
+
+        // The other trusted validators outvote the conflicting one
+        publish(&validators[1], vec![(0, hash2)]);
+        publish(&validators[2], vec![(0, hash2), (1, hash2)]);
+        assert_eq!(
+            AccountsHashVerifier::compare_hashes(&cluster_info, &trusted_validators, &hashes, 0.5),
+            AccountsHashVerification {
+                last_verified: Some((0, hash2)),
+                mismatch: None,
+            }
+        );
     }
 
     #[test]
@@ -226,10 +318,8 @@ mod tests {
         let cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
         let cluster_info = Arc::new(cluster_info);
 
-        let trusted_validators = HashSet::new();
-        let exit = Arc::new(AtomicBool::new(false));
         let mut hashes = vec![];
-        for i in 0..CFG.MAX_SNAPSHOT_HASHES + 1 {
+        for i in 0..CLUSTER_INFO_CFG.MAX_SNAPSHOT_HASHES + 1 {
             let snapshot_links = TempDir::new().unwrap();
             let accounts_package = AccountsPackage {
                 hash: hash(&[i as u8]),
@@ -240,17 +330,16 @@ mod tests {
                 tar_output_file: PathBuf::from("."),
                 storages: vec![],
                 compression: CompressionType::Bzip2,
+                compression_options: CompressionOptions::default(),
+                throttle_options: SnapshotThrottleOptions::default(),
                 snapshot_version: SnapshotVersion::default(),
             };
 
             AccountsHashVerifier::process_accounts_package(
                 accounts_package,
                 &cluster_info,
-                &Some(trusted_validators.clone()),
-                false,
                 &None,
                 &mut hashes,
-                &exit,
                 0,
                 100,
             );
@@ -260,14 +349,14 @@ mod tests {
             .get_accounts_hash_for_node(&keypair.pubkey(), |c| c.clone())
             .unwrap();
         info!("{:?}", cluster_hashes);
-        assert_eq!(hashes.len(), CFG.MAX_SNAPSHOT_HASHES);
-        assert_eq!(cluster_hashes.len(), CFG.MAX_SNAPSHOT_HASHES);
+        assert_eq!(hashes.len(), CLUSTER_INFO_CFG.MAX_SNAPSHOT_HASHES);
+        assert_eq!(cluster_hashes.len(), CLUSTER_INFO_CFG.MAX_SNAPSHOT_HASHES);
         assert_eq!(cluster_hashes[0], (101, hash(&[1])));
         assert_eq!(
-            cluster_hashes[CFG.MAX_SNAPSHOT_HASHES - 1],
+            cluster_hashes[CLUSTER_INFO_CFG.MAX_SNAPSHOT_HASHES - 1],
             (
-                100 + CFG.MAX_SNAPSHOT_HASHES as u64,
-                hash(&[CFG.MAX_SNAPSHOT_HASHES as u8])
+                100 + CLUSTER_INFO_CFG.MAX_SNAPSHOT_HASHES as u64,
+                hash(&[CLUSTER_INFO_CFG.MAX_SNAPSHOT_HASHES as u8])
             )
         );
     }
@@ -0,0 +1,387 @@
+//! The `ancestor_hashes_service` module looks for the point at which the ledger of this node
+//! diverged from the cluster when replay marks a slot dead. For each new dead slot, it asks a few
+//! trusted peers, or repair peers if no trusted validators are configured, for the hashes of the
+//! slot and its ancestors, and compares them with the local ledger. The earliest ancestor whose
+//! hash differs is reported as the divergence point in the `ancestor-hashes-divergence`
+//! datapoint and through the `getAncestorHashesReports` RPC method.
+
+use crate::{
+    cluster_info::ClusterInfo,
+    contact_info::ContactInfo,
+    serve_repair::{AncestorHashesResponse, ServeRepair, MAX_ANCESTOR_HASHES_RESPONSES},
+};
+use rand::{seq::SliceRandom, Rng};
+use solana_ledger::{blockstore::Blockstore, shred::Nonce};
+use solana_perf::packet::{limited_deserialize, PACKET_DATA_SIZE};
+use solana_sdk::{clock::Slot, hash::Hash, pubkey::Pubkey, timing::timestamp};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::ErrorKind,
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{self, Builder, JoinHandle},
+    time::Duration,
+};
+
+toml_config::package_config! {
+    ANCESTOR_HASHES_NUM_PEERS: usize,
+    ANCESTOR_HASHES_RESPONSE_TIMEOUT_MS: u64,
+}
+
+const RECV_TIMEOUT: Duration = Duration::from_millis(200);
+// Dead slots queried at the same time
+const MAX_PENDING_SLOTS: usize = 16;
+// Reports kept for the RPC, newest slots first
+const MAX_REPORTS: usize = 256;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AncestorHashesReport {
+    pub slot: Slot,
+    /// Earliest ancestor of the dead slot, or the dead slot itself, whose hash differs from the
+    /// one reported by most responding peers. None if they agree with the local ledger, in which
+    /// case the slot is dead for a reason other than a divergent ledger.
+    pub divergent_slot: Option<Slot>,
+    pub num_responses: usize,
+    /// Number of the responding peers which agree on `divergent_slot`
+    pub num_agreeing: usize,
+    pub timestamp: u64,
+}
+
+#[derive(Default)]
+pub struct AncestorHashesReports {
+    reports: RwLock<BTreeMap<Slot, AncestorHashesReport>>,
+}
+
+impl AncestorHashesReports {
+    pub fn insert(&self, report: AncestorHashesReport) {
+        let mut reports = self.reports.write().unwrap();
+        reports.insert(report.slot, report);
+        while reports.len() > MAX_REPORTS {
+            let oldest = *reports.keys().next().unwrap();
+            reports.remove(&oldest);
+        }
+    }
+
+    pub fn contains(&self, slot: Slot) -> bool {
+        self.reports.read().unwrap().contains_key(&slot)
+    }
+
+    /// Reports of the dead slots, in ascending slot order
+    pub fn reports(&self) -> Vec<AncestorHashesReport> {
+        self.reports.read().unwrap().values().cloned().collect()
+    }
+}
+
+struct PendingRequest {
+    nonce: Nonce,
+    sent: u64,
+    // Serve repair address and pubkey of the queried peers
+    peers: HashMap<SocketAddr, Pubkey>,
+    hashes: Vec<(Slot, Hash)>,
+    responses: HashMap<Pubkey, Option<Slot>>,
+}
+
+pub struct AncestorHashesService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl AncestorHashesService {
+    pub fn new(
+        blockstore: Arc<Blockstore>,
+        cluster_info: Arc<ClusterInfo>,
+        socket: UdpSocket,
+        trusted_validators: Option<HashSet<Pubkey>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let exit = exit.clone();
+        let thread_hdl = Builder::new()
+            .name("solana-ancestor-hashes".to_string())
+            .spawn(move || {
+                socket
+                    .set_read_timeout(Some(RECV_TIMEOUT))
+                    .expect("set_read_timeout");
+                let serve_repair = ServeRepair::new(cluster_info.clone());
+                let mut pending = HashMap::new();
+                while !exit.load(Ordering::Relaxed) {
+                    Self::recv_responses(&socket, &mut pending);
+                    Self::finalize_requests(&cluster_info, &mut pending);
+                    Self::request_dead_slots(
+                        &blockstore,
+                        &cluster_info,
+                        &serve_repair,
+                        &socket,
+                        &trusted_validators,
+                        &mut pending,
+                    );
+                }
+            })
+            .unwrap();
+        Self { thread_hdl }
+    }
+
+    fn recv_responses(socket: &UdpSocket, pending: &mut HashMap<Slot, PendingRequest>) {
+        let mut buf = [0u8; PACKET_DATA_SIZE];
+        loop {
+            let (size, from_addr) = match socket.recv_from(&mut buf) {
+                Ok(recv) => recv,
+                Err(err) => {
+                    if err.kind() != ErrorKind::WouldBlock && err.kind() != ErrorKind::TimedOut {
+                        warn!("ancestor hashes recv error: {:?}", err);
+                    }
+                    return;
+                }
+            };
+            let response: AncestorHashesResponse = match limited_deserialize(&buf[..size]) {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+            let request = pending
+                .values_mut()
+                .find(|request| request.nonce == response.nonce);
+            if let Some(request) = request {
+                // Only the queried peers may respond, once
+                if let Some(peer) = request.peers.remove(&from_addr) {
+                    let divergent_slot = divergent_slot(&request.hashes, &response.hashes);
+                    request.responses.insert(peer, divergent_slot);
+                }
+            }
+        }
+    }
+
+    fn finalize_requests(cluster_info: &ClusterInfo, pending: &mut HashMap<Slot, PendingRequest>) {
+        let now = timestamp();
+        let reports = cluster_info.ancestor_hashes_reports();
+        pending.retain(|slot, request| {
+            let timed_out =
+                now.saturating_sub(request.sent) >= CFG.ANCESTOR_HASHES_RESPONSE_TIMEOUT_MS;
+            if !request.peers.is_empty() && !timed_out {
+                return true;
+            }
+            // Without any response, the slot is queried again
+            if let Some((divergent_slot, num_agreeing)) = summarize(&request.responses) {
+                match divergent_slot {
+                    Some(divergent_slot) => warn!(
+                        "dead slot {} diverged from {} of {} peers at slot {}",
+                        slot,
+                        num_agreeing,
+                        request.responses.len(),
+                        divergent_slot
+                    ),
+                    None => info!(
+                        "dead slot {} matches the ancestor hashes of {} of {} peers",
+                        slot,
+                        num_agreeing,
+                        request.responses.len()
+                    ),
+                }
+                datapoint_info!(
+                    "ancestor-hashes-divergence",
+                    ("slot", *slot, i64),
+                    (
+                        "divergent_slot",
+                        divergent_slot.map(|s| s as i64).unwrap_or(-1),
+                        i64
+                    ),
+                    ("num_responses", request.responses.len(), i64),
+                    ("num_agreeing", num_agreeing, i64),
+                );
+                reports.insert(AncestorHashesReport {
+                    slot: *slot,
+                    divergent_slot,
+                    num_responses: request.responses.len(),
+                    num_agreeing,
+                    timestamp: now,
+                });
+            }
+            false
+        });
+    }
+
+    fn request_dead_slots(
+        blockstore: &Blockstore,
+        cluster_info: &ClusterInfo,
+        serve_repair: &ServeRepair,
+        socket: &UdpSocket,
+        trusted_validators: &Option<HashSet<Pubkey>>,
+        pending: &mut HashMap<Slot, PendingRequest>,
+    ) {
+        let root = blockstore.last_root();
+        let dead_slots: Vec<_> = match blockstore.dead_slots_iterator(root + 1) {
+            Ok(dead_slots) => dead_slots
+                .filter(|slot| {
+                    !pending.contains_key(slot)
+                        && !cluster_info.ancestor_hashes_reports().contains(*slot)
+                })
+                .take(MAX_PENDING_SLOTS.saturating_sub(pending.len()))
+                .collect(),
+            Err(_) => return,
+        };
+        let mut rng = rand::thread_rng();
+        for slot in dead_slots {
+            let mut peers = Self::peers(cluster_info, trusted_validators, slot);
+            peers.shuffle(&mut rng);
+            peers.truncate(CFG.ANCESTOR_HASHES_NUM_PEERS);
+            if peers.is_empty() {
+                continue;
+            }
+            let nonce = rng.gen();
+            let req = match serve_repair.ancestor_hashes_request_bytes(slot, nonce) {
+                Ok(req) => req,
+                Err(err) => {
+                    warn!("ancestor hashes request serialize error: {:?}", err);
+                    continue;
+                }
+            };
+            for peer in &peers {
+                if let Err(err) = socket.send_to(&req, peer.serve_repair) {
+                    info!(
+                        "ancestor hashes request send_to {} ({}) error {:?}",
+                        peer.id, peer.serve_repair, err
+                    );
+                }
+            }
+            let hashes =
+                ServeRepair::get_ancestor_hashes(blockstore, slot, MAX_ANCESTOR_HASHES_RESPONSES);
+            pending.insert(
+                slot,
+                PendingRequest {
+                    nonce,
+                    sent: timestamp(),
+                    peers: peers
+                        .into_iter()
+                        .map(|peer| (peer.serve_repair, peer.id))
+                        .collect(),
+                    hashes,
+                    responses: HashMap::new(),
+                },
+            );
+        }
+    }
+
+    fn peers(
+        cluster_info: &ClusterInfo,
+        trusted_validators: &Option<HashSet<Pubkey>>,
+        slot: Slot,
+    ) -> Vec<ContactInfo> {
+        match trusted_validators {
+            Some(trusted_validators) => trusted_validators
+                .iter()
+                .filter(|pubkey| **pubkey != cluster_info.id())
+                .filter_map(|pubkey| cluster_info.lookup_contact_info(pubkey, |ci| ci.clone()))
+                .filter(|ci| ContactInfo::is_valid_address(&ci.serve_repair))
+                .collect(),
+            None => cluster_info.repair_peers(slot),
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+/// Returns the earliest slot whose hash differs between the local ancestor hashes `ours` and a
+/// peer's `theirs`, both in descending slot order, or None if they agree. Only the slots at or
+/// above the lowest slot both sides report on are compared; a slot present on one side only is a
+/// divergence, since the two ledgers then do not chain through the same ancestors.
+fn divergent_slot(ours: &[(Slot, Hash)], theirs: &[(Slot, Hash)]) -> Option<Slot> {
+    let lowest = match (ours.last(), theirs.last()) {
+        (Some((ours, _)), Some((theirs, _))) => *ours.max(theirs),
+        (Some((slot, _)), None) | (None, Some((slot, _))) => return Some(*slot),
+        (None, None) => return None,
+    };
+    let ours: HashMap<_, _> = ours.iter().copied().collect();
+    let theirs: HashMap<_, _> = theirs.iter().copied().collect();
+    ours.keys()
+        .chain(theirs.keys())
+        .filter(|slot| **slot >= lowest)
+        .filter(|slot| ours.get(slot) != theirs.get(slot))
+        .min()
+        .copied()
+}
+
+/// Returns the divergent slot reported by most peers along with the number of those peers, or
+/// None if there are no responses
+fn summarize(responses: &HashMap<Pubkey, Option<Slot>>) -> Option<(Option<Slot>, usize)> {
+    let mut counts: HashMap<Option<Slot>, usize> = HashMap::new();
+    for divergent_slot in responses.values() {
+        *counts.entry(*divergent_slot).or_default() += 1;
+    }
+    // Ties are broken towards reporting a divergence, the earliest one
+    counts.into_iter().max_by_key(|(divergent_slot, count)| {
+        (
+            *count,
+            divergent_slot.is_some(),
+            std::cmp::Reverse(*divergent_slot),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::hash::hash;
+
+    #[test]
+    fn test_divergent_slot() {
+        let slot_hash = |slot: Slot, fork: u8| (slot, hash(&[slot as u8, fork]));
+        let ours = vec![
+            slot_hash(9, 0),
+            slot_hash(7, 0),
+            slot_hash(6, 0),
+            slot_hash(4, 0),
+        ];
+        assert_eq!(divergent_slot(&ours, &ours), None);
+        assert_eq!(divergent_slot(&ours, &[]), Some(4));
+        assert_eq!(divergent_slot(&[], &[]), None);
+
+        // The peer has a different version of slot 7
+        let theirs = vec![
+            slot_hash(9, 1),
+            slot_hash(7, 1),
+            slot_hash(6, 0),
+            slot_hash(4, 0),
+        ];
+        assert_eq!(divergent_slot(&ours, &theirs), Some(7));
+
+        // The peer chains 9 through 8 instead of 7
+        let theirs = vec![slot_hash(9, 0), slot_hash(8, 0), slot_hash(6, 0)];
+        assert_eq!(divergent_slot(&ours, &theirs), Some(7));
+
+        // Slots below the lowest slot both sides report on are not compared
+        let theirs = vec![slot_hash(9, 0), slot_hash(7, 0)];
+        assert_eq!(divergent_slot(&ours, &theirs), None);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let mut responses = HashMap::new();
+        assert_eq!(summarize(&responses), None);
+        responses.insert(solana_sdk::pubkey::new_rand(), Some(7));
+        responses.insert(solana_sdk::pubkey::new_rand(), None);
+        assert_eq!(summarize(&responses), Some((Some(7), 1)));
+        responses.insert(solana_sdk::pubkey::new_rand(), None);
+        assert_eq!(summarize(&responses), Some((None, 2)));
+    }
+
+    #[test]
+    fn test_reports() {
+        let reports = AncestorHashesReports::default();
+        for slot in 0..MAX_REPORTS as Slot + 2 {
+            reports.insert(AncestorHashesReport {
+                slot,
+                divergent_slot: None,
+                num_responses: 1,
+                num_agreeing: 1,
+                timestamp: 0,
+            });
+        }
+        assert!(!reports.contains(1));
+        assert!(reports.contains(2));
+        let reports = reports.reports();
+        assert_eq!(reports.len(), MAX_REPORTS);
+        assert_eq!(reports[0].slot, 2);
+    }
+}
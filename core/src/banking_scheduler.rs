@@ -0,0 +1,218 @@
+//! The `banking_scheduler` module orders the transactions of a banking thread for processing.
+//! Transactions are sorted by the priority the `TransactionPriority` hook assigns them, and
+//! layered along the dependency graph of the accounts they write: a transaction is scheduled in
+//! the level after the last transaction of higher priority it conflicts with, so the
+//! transactions of a level never lock the same writable account. The batches of a level are
+//! processed in parallel by the worker threads, and the levels one after the other.
+
+use crate::write_lock_qos::WriteLockQos;
+use solana_ledger::cost_model;
+use solana_runtime::bank::Bank;
+use solana_sdk::{pubkey::Pubkey, sanitize::Sanitize, transaction::Transaction};
+use std::{cmp::Reverse, collections::HashMap, sync::RwLock};
+
+toml_config::package_config! {
+    BANKING_SCHEDULER_NUM_WORKERS: usize,
+}
+
+// Scales the fee per unit of cost, which is otherwise mostly below 1
+const FEE_PRIORITY_SCALE: u64 = 1_000_000;
+
+/// Hook deciding the order in which the transactions are processed
+pub trait TransactionPriority: Send + Sync {
+    /// Transactions of higher priority are processed first
+    fn priority(&self, bank: &Bank, transaction: &Transaction) -> u64;
+}
+
+/// Transactions paying the highest fee per unit of cost are processed first
+#[derive(Default)]
+pub struct FeePriority;
+
+impl TransactionPriority for FeePriority {
+    fn priority(&self, bank: &Bank, transaction: &Transaction) -> u64 {
+        let message = &transaction.message;
+        let fee = bank
+            .get_fee_calculator(&message.recent_blockhash)
+            .map(|fee_calculator| fee_calculator.calculate_fee(message))
+            .unwrap_or_default();
+        let cost = cost_model::transaction_cost(transaction).sum().max(1);
+        fee.saturating_mul(FEE_PRIORITY_SCALE) / cost
+    }
+}
+
+/// Transactions paid for by the identity of a staked node are processed first, by stake
+#[derive(Default)]
+pub struct StakePriority;
+
+impl TransactionPriority for StakePriority {
+    fn priority(&self, bank: &Bank, transaction: &Transaction) -> u64 {
+        transaction
+            .message
+            .account_keys
+            .first()
+            .and_then(|fee_payer| bank.epoch_vote_accounts_for_node_id(fee_payer))
+            .map(|node_vote_accounts| node_vote_accounts.total_stake)
+            .unwrap_or_default()
+    }
+}
+
+pub struct BankingScheduler {
+    thread_pool: rayon::ThreadPool,
+    priority: Box<dyn TransactionPriority>,
+    write_lock_qos: RwLock<WriteLockQos>,
+}
+
+impl Default for BankingScheduler {
+    fn default() -> Self {
+        Self::new(CFG.BANKING_SCHEDULER_NUM_WORKERS, Box::new(FeePriority))
+    }
+}
+
+impl BankingScheduler {
+    pub fn new(num_workers: usize, priority: Box<dyn TransactionPriority>) -> Self {
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_workers.max(1))
+            .thread_name(|i| format!("solana-banking-worker-{}", i))
+            .build()
+            .unwrap();
+        Self {
+            thread_pool,
+            priority,
+            write_lock_qos: RwLock::new(WriteLockQos::default()),
+        }
+    }
+
+    pub fn thread_pool(&self) -> &rayon::ThreadPool {
+        &self.thread_pool
+    }
+
+    pub fn write_lock_qos(&self) -> &RwLock<WriteLockQos> {
+        &self.write_lock_qos
+    }
+
+    /// Returns the order in which to process `transactions`, and the levels of batches of at
+    /// most `max_batch_size` transactions, as ranges of that order. The batches of a level do
+    /// not lock the same writable accounts.
+    pub fn schedule(
+        &self,
+        bank: &Bank,
+        transactions: &[Transaction],
+        max_batch_size: usize,
+    ) -> (Vec<usize>, Vec<Vec<std::ops::Range<usize>>>) {
+        let priorities: Vec<_> = transactions
+            .iter()
+            .map(|transaction| self.priority.priority(bank, transaction))
+            .collect();
+        schedule(transactions, &priorities, max_batch_size)
+    }
+}
+
+fn schedule(
+    transactions: &[Transaction],
+    priorities: &[u64],
+    max_batch_size: usize,
+) -> (Vec<usize>, Vec<Vec<std::ops::Range<usize>>>) {
+    let mut order: Vec<_> = (0..transactions.len()).collect();
+    // The sort is stable, so transactions of the same priority keep their arrival order
+    order.sort_by_key(|index| Reverse(priorities[*index]));
+
+    // Last level in which each account is write or read locked
+    let mut last_write: HashMap<&Pubkey, usize> = HashMap::new();
+    let mut last_read: HashMap<&Pubkey, usize> = HashMap::new();
+    let mut levels: Vec<Vec<usize>> = vec![];
+    for index in order {
+        let message = &transactions[index].message;
+        // Transactions which fail to sanitize do not lock any account
+        let (writable, readonly) = if transactions[index].sanitize().is_ok() {
+            message.get_account_keys_by_lock_type()
+        } else {
+            (vec![], vec![])
+        };
+        let after = |locks: &HashMap<&Pubkey, usize>, key: &Pubkey| {
+            locks.get(key).map(|level| level + 1).unwrap_or_default()
+        };
+        let level = writable
+            .iter()
+            .map(|key| after(&last_write, *key).max(after(&last_read, *key)))
+            .chain(readonly.iter().map(|key| after(&last_write, *key)))
+            .max()
+            .unwrap_or_default();
+        for key in writable {
+            last_write.insert(key, level);
+        }
+        for key in readonly {
+            let read_level = last_read.entry(key).or_default();
+            *read_level = (*read_level).max(level);
+        }
+        if levels.len() <= level {
+            levels.resize_with(level + 1, Vec::new);
+        }
+        levels[level].push(index);
+    }
+
+    let mut order = Vec::with_capacity(transactions.len());
+    let batches = levels
+        .into_iter()
+        .map(|level| {
+            let start = order.len();
+            order.extend(level);
+            (start..order.len())
+                .step_by(max_batch_size.max(1))
+                .map(|batch_start| batch_start..(batch_start + max_batch_size).min(order.len()))
+                .collect()
+        })
+        .collect();
+    (order, batches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        message::Message,
+    };
+
+    fn new_transaction(writable: &[Pubkey], readonly: &[Pubkey]) -> Transaction {
+        let accounts = writable
+            .iter()
+            .map(|key| AccountMeta::new(*key, false))
+            .chain(
+                readonly
+                    .iter()
+                    .map(|key| AccountMeta::new_readonly(*key, false)),
+            )
+            .collect();
+        let instruction = Instruction::new(Pubkey::default(), &0u8, accounts);
+        let payer = solana_sdk::pubkey::new_rand();
+        let mut transaction = Transaction::new_unsigned(Message::new(&[instruction], Some(&payer)));
+        transaction.message.recent_blockhash = Hash::default();
+        transaction
+    }
+
+    #[test]
+    fn test_schedule() {
+        let keys: Vec<_> = std::iter::repeat_with(solana_sdk::pubkey::new_rand)
+            .take(4)
+            .collect();
+        let transactions = vec![
+            new_transaction(&[keys[0]], &[]),
+            new_transaction(&[keys[1]], &[keys[2]]),
+            new_transaction(&[], &[keys[2]]),
+            new_transaction(&[keys[2]], &[]),
+            new_transaction(&[keys[3]], &[keys[0]]),
+            new_transaction(&[keys[0]], &[keys[1]]),
+        ];
+        let (order, levels) = schedule(&transactions, &[0; 6], 2);
+        // Transactions reading an account are scheduled along each other, and after the last
+        // transaction writing it
+        assert_eq!(order, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(levels, vec![vec![0..2, 2..3], vec![3..5], vec![5..6]]);
+
+        // Higher priority transactions are scheduled first
+        let (order, levels) = schedule(&transactions, &[0, 0, 0, 5, 0, 10], 8);
+        assert_eq!(order, vec![5, 3, 0, 1, 2, 4]);
+        assert_eq!(levels, vec![vec![0..2], vec![2..5], vec![5..6]]);
+    }
+}
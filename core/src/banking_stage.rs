@@ -2,17 +2,23 @@
 //! to contruct a software pipeline. The stage uses all available CPU cores and
 //! can do its processing in parallel with signature verification on the GPU.
 use crate::{
+    banking_scheduler::BankingScheduler,
     cluster_info::ClusterInfo,
     poh_recorder::{PohRecorder, PohRecorderError, WorkingBankEntry},
     poh_service::PohService,
+    transaction_forwarder::TransactionForwarder,
+    write_lock_qos::WriteLockQos,
 };
 use crossbeam_channel::{Receiver as CrossbeamReceiver, RecvTimeoutError};
 use itertools::Itertools;
+use rayon::prelude::*;
 use solana_ledger::{
     blockstore::Blockstore,
     blockstore_processor::{send_transaction_status_batch, TransactionStatusSender},
+    cost_model::{self, CostTracker},
     entry::hash_transactions,
     leader_schedule_cache::LeaderScheduleCache,
+    transaction_trace::{self, TraceEvent},
 };
 use solana_measure::{measure::Measure, thread_mem_usage};
 use solana_metrics::{inc_new_counter_debug, inc_new_counter_info, inc_new_counter_warn};
@@ -40,7 +46,7 @@ use std::{
     net::UdpSocket,
     sync::atomic::AtomicBool,
     sync::mpsc::Receiver,
-    sync::{Arc, Mutex},
+    sync::{Arc, Mutex, RwLock},
     thread::{self, Builder, JoinHandle},
     time::Duration,
     time::Instant,
@@ -105,6 +111,10 @@ impl BankingStage {
         // This thread talks to poh_service and broadcasts the entries once they have been recorded.
         // Once an entry has been recorded, its blockhash is registered with the bank.
         let my_pubkey = cluster_info.id();
+        // The cost of the block being packed is shared by all the threads
+        let cost_tracker = Arc::new(RwLock::new(CostTracker::default()));
+        // The worker threads processing the scheduled batches are shared by all the threads
+        let scheduler = Arc::new(BankingScheduler::default());
         // Many banks that process transactions in parallel.
         let bank_thread_hdls: Vec<JoinHandle<()>> = (0..num_threads)
             .map(|i| {
@@ -120,6 +130,8 @@ impl BankingStage {
                 let mut recv_start = Instant::now();
                 let transaction_status_sender = transaction_status_sender.clone();
                 let gossip_vote_sender = gossip_vote_sender.clone();
+                let cost_tracker = cost_tracker.clone();
+                let scheduler = scheduler.clone();
                 Builder::new()
                     .name("solana-banking-stage-tx".to_string())
                     .spawn(move || {
@@ -135,6 +147,8 @@ impl BankingStage {
                             batch_limit,
                             transaction_status_sender,
                             gossip_vote_sender,
+                            &cost_tracker,
+                            &scheduler,
                         );
                     })
                     .unwrap()
@@ -150,20 +164,30 @@ impl BankingStage {
             .collect()
     }
 
+    /// Forwards the buffered packets to the upcoming leaders other than this node. Returns false
+    /// if none of them is known, in which case the packets are kept buffered.
     fn forward_buffered_packets(
-        socket: &std::net::UdpSocket,
-        tpu_forwards: &std::net::SocketAddr,
+        my_pubkey: &Pubkey,
+        forwarder: &mut TransactionForwarder,
+        poh_recorder: &Mutex<PohRecorder>,
+        cluster_info: &ClusterInfo,
         unprocessed_packets: &[PacketsAndOffsets],
-    ) -> std::io::Result<()> {
+    ) -> bool {
+        let leaders: Vec<_> = poh_recorder
+            .lock()
+            .unwrap()
+            .upcoming_leaders(
+                CFG.FORWARD_TRANSACTIONS_TO_LEADER_AT_SLOT_OFFSET,
+                TransactionForwarder::num_leaders(),
+            )
+            .into_iter()
+            .filter(|leader| leader != my_pubkey)
+            .collect();
         let packets = Self::filter_valid_packets_for_forwarding(unprocessed_packets);
-        inc_new_counter_info!("banking_stage-forwarded_packets", packets.len());
-        for p in packets {
-            socket.send_to(&p.data[..p.meta.size], &tpu_forwards)?;
-        }
-
-        Ok(())
+        forwarder.forward(cluster_info, &leaders, &packets)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn consume_buffered_packets(
         my_pubkey: &Pubkey,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
@@ -171,6 +195,8 @@ impl BankingStage {
         batch_limit: usize,
         transaction_status_sender: Option<TransactionStatusSender>,
         gossip_vote_sender: &ReplayVoteSender,
+        cost_tracker: &RwLock<CostTracker>,
+        scheduler: &BankingScheduler,
     ) -> UnprocessedPackets {
         let mut unprocessed_packets = vec![];
         let mut rebuffered_packets = 0;
@@ -203,6 +229,8 @@ impl BankingStage {
                     unprocessed_indexes.to_owned(),
                     transaction_status_sender.clone(),
                     gossip_vote_sender,
+                    cost_tracker,
+                    scheduler,
                 );
 
             new_tx_count += processed;
@@ -290,7 +318,7 @@ impl BankingStage {
     #[allow(clippy::too_many_arguments)]
     fn process_buffered_packets(
         my_pubkey: &Pubkey,
-        socket: &std::net::UdpSocket,
+        forwarder: &mut TransactionForwarder,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         cluster_info: &ClusterInfo,
         buffered_packets: &mut Vec<PacketsAndOffsets>,
@@ -298,6 +326,8 @@ impl BankingStage {
         batch_limit: usize,
         transaction_status_sender: Option<TransactionStatusSender>,
         gossip_vote_sender: &ReplayVoteSender,
+        cost_tracker: &RwLock<CostTracker>,
+        scheduler: &BankingScheduler,
     ) -> BufferedPacketsDecision {
         let (leader_at_slot_offset, poh_has_bank, would_be_leader) = {
             let poh = poh_recorder.lock().unwrap();
@@ -327,30 +357,22 @@ impl BankingStage {
                     batch_limit,
                     transaction_status_sender,
                     gossip_vote_sender,
+                    cost_tracker,
+                    scheduler,
                 );
                 buffered_packets.append(&mut unprocessed);
             }
             BufferedPacketsDecision::Forward => {
                 if enable_forwarding {
-                    let next_leader = poh_recorder
-                        .lock()
-                        .unwrap()
-                        .leader_after_n_slots(CFG.FORWARD_TRANSACTIONS_TO_LEADER_AT_SLOT_OFFSET);
-                    next_leader.map_or((), |leader_pubkey| {
-                        let leader_addr = {
-                            cluster_info
-                                .lookup_contact_info(&leader_pubkey, |leader| leader.tpu_forwards)
-                        };
-
-                        leader_addr.map_or((), |leader_addr| {
-                            let _ = Self::forward_buffered_packets(
-                                &socket,
-                                &leader_addr,
-                                &buffered_packets,
-                            );
-                            buffered_packets.clear();
-                        })
-                    })
+                    if Self::forward_buffered_packets(
+                        my_pubkey,
+                        forwarder,
+                        poh_recorder,
+                        cluster_info,
+                        &buffered_packets,
+                    ) {
+                        buffered_packets.clear();
+                    }
                 } else {
                     buffered_packets.clear();
                 }
@@ -372,14 +394,17 @@ impl BankingStage {
         batch_limit: usize,
         transaction_status_sender: Option<TransactionStatusSender>,
         gossip_vote_sender: ReplayVoteSender,
+        cost_tracker: &RwLock<CostTracker>,
+        scheduler: &BankingScheduler,
     ) {
-        let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let mut forwarder = TransactionForwarder::new(UdpSocket::bind("0.0.0.0:0").unwrap());
         let mut buffered_packets = vec![];
         loop {
+            forwarder.send_pending();
             while !buffered_packets.is_empty() {
                 let decision = Self::process_buffered_packets(
                     &my_pubkey,
-                    &socket,
+                    &mut forwarder,
                     poh_recorder,
                     cluster_info,
                     &mut buffered_packets,
@@ -387,6 +412,8 @@ impl BankingStage {
                     batch_limit,
                     transaction_status_sender.clone(),
                     &gossip_vote_sender,
+                    cost_tracker,
+                    scheduler,
                 );
                 if decision == BufferedPacketsDecision::Hold {
                     // If we are waiting on a new bank,
@@ -415,6 +442,8 @@ impl BankingStage {
                 batch_limit,
                 transaction_status_sender.clone(),
                 &gossip_vote_sender,
+                cost_tracker,
+                scheduler,
             ) {
                 Err(RecvTimeoutError::Timeout) => (),
                 Err(RecvTimeoutError::Disconnected) => break,
@@ -568,6 +597,14 @@ impl BankingStage {
             );
 
             bank_utils::find_and_send_votes(txs, &tx_results, Some(gossip_vote_sender));
+            transaction_trace::tracer().record_all(
+                txs.iter()
+                    .zip(&results)
+                    .filter(|(_tx, (result, _hash_age_kind))| Bank::can_commit(result))
+                    .filter_map(|(tx, _result)| tx.signatures.first()),
+                TraceEvent::Packed,
+                Some(bank.slot()),
+            );
             if let Some(sender) = transaction_status_sender {
                 let post_balances = bank.collect_balances(batch);
                 send_transaction_status_batch(
@@ -637,7 +674,8 @@ impl BankingStage {
         (result, retryable_txs)
     }
 
-    /// Sends transactions to the bank.
+    /// Sends transactions to the bank, in the order and batches given by `scheduler`. The
+    /// batches of a level are processed in parallel by its worker threads.
     ///
     /// Returns the number of transactions successfully processed by the bank, which may be less
     /// than the total number if max PoH height was reached and the bank halted
@@ -647,45 +685,73 @@ impl BankingStage {
         poh: &Arc<Mutex<PohRecorder>>,
         transaction_status_sender: Option<TransactionStatusSender>,
         gossip_vote_sender: &ReplayVoteSender,
+        scheduler: &BankingScheduler,
     ) -> (usize, Vec<usize>) {
-        let mut chunk_start = 0;
+        let (order, levels) =
+            scheduler.schedule(bank, transactions, CFG.MAX_NUM_TRANSACTIONS_PER_BATCH);
+        let scheduled_transactions: Vec<_> = order
+            .iter()
+            .map(|index| transactions[*index].clone())
+            .collect();
+        let mut processed = 0;
         let mut unprocessed_txs = vec![];
-        while chunk_start != transactions.len() {
-            let chunk_end = std::cmp::min(
-                transactions.len(),
-                chunk_start + CFG.MAX_NUM_TRANSACTIONS_PER_BATCH,
-            );
-
-            let (result, retryable_txs_in_chunk) = Self::process_and_record_transactions(
-                bank,
-                &transactions[chunk_start..chunk_end],
-                poh,
-                chunk_start,
-                transaction_status_sender.clone(),
-                gossip_vote_sender,
-            );
-            trace!("process_transactions result: {:?}", result);
-
-            // Add the retryable txs (transactions that errored in a way that warrants a retry)
-            // to the list of unprocessed txs.
-            unprocessed_txs.extend_from_slice(&retryable_txs_in_chunk);
-            if let Err(PohRecorderError::MaxHeightReached) = result {
+        for (level_index, level) in levels.iter().enumerate() {
+            let results: Vec<_> = scheduler.thread_pool().install(|| {
+                level
+                    .par_iter()
+                    .map(|batch| {
+                        let batch_transactions = &scheduled_transactions[batch.clone()];
+                        let mut process_time = Measure::start("process_time");
+                        let (result, retryable_txs) = Self::process_and_record_transactions(
+                            bank,
+                            batch_transactions,
+                            poh,
+                            batch.start,
+                            transaction_status_sender.clone(),
+                            gossip_vote_sender,
+                        );
+                        process_time.stop();
+                        trace!("process_transactions result: {:?}", result);
+                        scheduler.write_lock_qos().write().unwrap().record(
+                            bank.slot(),
+                            batch_transactions,
+                            process_time.as_us(),
+                        );
+                        (batch.len(), result, retryable_txs)
+                    })
+                    .collect()
+            });
+            let mut max_height_reached = false;
+            for (batch_len, result, retryable_txs) in results {
+                // Add the retryable txs (transactions that errored in a way that warrants a
+                // retry) to the list of unprocessed txs.
+                unprocessed_txs.extend(retryable_txs.into_iter().map(|index| order[index]));
+                if let Err(PohRecorderError::MaxHeightReached) = result {
+                    max_height_reached = true;
+                } else {
+                    // Don't exit early on any other type of error, continue processing...
+                    processed += batch_len;
+                }
+            }
+            if max_height_reached {
                 info!(
                     "process transactions: max height reached slot: {} height: {}",
                     bank.slot(),
                     bank.tick_height()
                 );
-                // process_and_record_transactions has returned all retryable errors in
-                // transactions[chunk_start..chunk_end], so we just need to push the remaining
-                // transactions into the unprocessed queue.
-                unprocessed_txs.extend(chunk_end..transactions.len());
+                // process_and_record_transactions has returned all retryable errors in the
+                // batches of this level, so we just need to push the transactions of the
+                // remaining levels into the unprocessed queue.
+                let remaining = levels[level_index + 1..]
+                    .iter()
+                    .flatten()
+                    .flat_map(|batch| batch.clone());
+                unprocessed_txs.extend(remaining.map(|index| order[index]));
                 break;
             }
-            // Don't exit early on any other type of error, continue processing...
-            chunk_start = chunk_end;
         }
 
-        (chunk_start, unprocessed_txs)
+        (processed, unprocessed_txs)
     }
 
     // This function returns a vector of transactions that are not None. It also returns a vector
@@ -804,6 +870,7 @@ impl BankingStage {
         Self::filter_valid_transaction_indexes(&result, transaction_to_packet_indexes)
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_received_packets(
         bank: &Arc<Bank>,
         poh: &Arc<Mutex<PohRecorder>>,
@@ -811,6 +878,8 @@ impl BankingStage {
         packet_indexes: Vec<usize>,
         transaction_status_sender: Option<TransactionStatusSender>,
         gossip_vote_sender: &ReplayVoteSender,
+        cost_tracker: &RwLock<CostTracker>,
+        scheduler: &BankingScheduler,
     ) -> (usize, usize, Vec<usize>) {
         let (transactions, transaction_to_packet_indexes) = Self::transactions_from_packets(
             msgs,
@@ -823,6 +892,23 @@ impl BankingStage {
             transactions.len()
         );
 
+        let (transactions, transaction_to_packet_indexes, deferred_packet_indexes) =
+            Self::filter_transactions_by_write_lock_qos(
+                bank,
+                scheduler.write_lock_qos(),
+                transactions,
+                transaction_to_packet_indexes,
+            );
+
+        let (transactions, transaction_to_packet_indexes, cost_limited_packet_indexes) =
+            Self::filter_transactions_by_cost(
+                bank,
+                cost_tracker,
+                transactions,
+                transaction_to_packet_indexes,
+            );
+        // Callers take fewer processed transactions than this as the end of the block, so the
+        // ones deferred to a later block are not counted
         let tx_len = transactions.len();
 
         let (processed, unprocessed_tx_indexes) = Self::process_transactions(
@@ -831,11 +917,21 @@ impl BankingStage {
             poh,
             transaction_status_sender,
             gossip_vote_sender,
+            scheduler,
         );
 
         let unprocessed_tx_count = unprocessed_tx_indexes.len();
+        {
+            // The unprocessed transactions are retried, possibly in another block
+            let mut cost_tracker = cost_tracker.write().unwrap();
+            if cost_tracker.slot() == bank.slot() {
+                for index in &unprocessed_tx_indexes {
+                    cost_tracker.remove(&cost_model::transaction_cost(&transactions[*index]));
+                }
+            }
+        }
 
-        let filtered_unprocessed_packet_indexes = Self::filter_pending_packets_from_pending_txs(
+        let mut filtered_unprocessed_packet_indexes = Self::filter_pending_packets_from_pending_txs(
             bank,
             &transactions,
             &transaction_to_packet_indexes,
@@ -845,10 +941,91 @@ impl BankingStage {
             "banking_stage-dropped_tx_before_forwarding",
             unprocessed_tx_count.saturating_sub(filtered_unprocessed_packet_indexes.len())
         );
+        filtered_unprocessed_packet_indexes.extend(cost_limited_packet_indexes);
+        filtered_unprocessed_packet_indexes.extend(deferred_packet_indexes);
 
         (processed, tx_len, filtered_unprocessed_packet_indexes)
     }
 
+    // Adds the cost of `transactions` to the block of `bank`, up to its limits. Returns the
+    // transactions that fit, with their packet indexes, and the packet indexes of the others, to
+    // be retried in a later block
+    fn filter_transactions_by_cost(
+        bank: &Arc<Bank>,
+        cost_tracker: &RwLock<CostTracker>,
+        transactions: Vec<Transaction>,
+        transaction_to_packet_indexes: Vec<usize>,
+    ) -> (Vec<Transaction>, Vec<usize>, Vec<usize>) {
+        let mut cost_tracker = cost_tracker.write().unwrap();
+        cost_tracker.reset_if_new_bank(bank.slot());
+        let mut cost_limited_packet_indexes = vec![];
+        let (transactions, transaction_to_packet_indexes) = transactions
+            .into_iter()
+            .zip(transaction_to_packet_indexes)
+            .filter(|(transaction, packet_index)| {
+                let cost = cost_model::transaction_cost(transaction);
+                match cost_tracker.try_add(&cost) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        debug!("bank: {} transaction not packed: {}", bank.slot(), err);
+                        cost_limited_packet_indexes.push(*packet_index);
+                        false
+                    }
+                }
+            })
+            .unzip();
+        inc_new_counter_info!(
+            "banking_stage-cost_limited_txs",
+            cost_limited_packet_indexes.len()
+        );
+        (
+            transactions,
+            transaction_to_packet_indexes,
+            cost_limited_packet_indexes,
+        )
+    }
+
+    // Defers the transactions writing an account which used up its processing time budget in the
+    // block of `bank`. Returns the other transactions, with their packet indexes, and the packet
+    // indexes of the deferred ones, to be retried in a later block
+    fn filter_transactions_by_write_lock_qos(
+        bank: &Arc<Bank>,
+        write_lock_qos: &RwLock<WriteLockQos>,
+        transactions: Vec<Transaction>,
+        transaction_to_packet_indexes: Vec<usize>,
+    ) -> (Vec<Transaction>, Vec<usize>, Vec<usize>) {
+        let mut write_lock_qos = write_lock_qos.write().unwrap();
+        write_lock_qos.reset_if_new_bank(bank.slot());
+        let mut deferred_packet_indexes = vec![];
+        let (transactions, transaction_to_packet_indexes) = transactions
+            .into_iter()
+            .zip(transaction_to_packet_indexes)
+            .filter(|(transaction, packet_index)| {
+                match write_lock_qos.over_budget_account(transaction) {
+                    None => true,
+                    Some(account) => {
+                        debug!(
+                            "bank: {} transaction deferred, account {} is over budget",
+                            bank.slot(),
+                            account
+                        );
+                        deferred_packet_indexes.push(*packet_index);
+                        false
+                    }
+                }
+            })
+            .unzip();
+        inc_new_counter_info!(
+            "banking_stage-write_lock_qos_deferred_txs",
+            deferred_packet_indexes.len()
+        );
+        (
+            transactions,
+            transaction_to_packet_indexes,
+            deferred_packet_indexes,
+        )
+    }
+
     fn filter_unprocessed_packets(
         bank: &Arc<Bank>,
         msgs: &Packets,
@@ -916,6 +1093,8 @@ impl BankingStage {
         batch_limit: usize,
         transaction_status_sender: Option<TransactionStatusSender>,
         gossip_vote_sender: &ReplayVoteSender,
+        cost_tracker: &RwLock<CostTracker>,
+        scheduler: &BankingScheduler,
     ) -> Result<UnprocessedPackets, RecvTimeoutError> {
         let mut recv_time = Measure::start("process_packets_recv");
         let mms = verified_receiver.recv_timeout(recv_timeout)?;
@@ -959,6 +1138,8 @@ impl BankingStage {
                 packet_indexes,
                 transaction_status_sender.clone(),
                 gossip_vote_sender,
+                cost_tracker,
+                scheduler,
             );
 
             new_tx_count += processed;
@@ -1869,6 +2050,160 @@ mod tests {
         Blockstore::destroy(&ledger_path).unwrap();
     }
 
+    #[test]
+    fn test_filter_transactions_by_cost() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10_000);
+        let bank = Arc::new(Bank::new(&genesis_config));
+        let transactions: Vec<_> = (0..3)
+            .map(|_| {
+                let to = solana_sdk::pubkey::new_rand();
+                system_transaction::transfer(&mint_keypair, &to, 1, genesis_config.hash())
+            })
+            .collect();
+        let tx_cost = cost_model::transaction_cost(&transactions[0]).sum();
+
+        // Only two transactions fit in the block
+        let cost_tracker = RwLock::new(CostTracker::new(2 * tx_cost, 10 * tx_cost));
+        let (packed, packet_indexes, cost_limited_packet_indexes) =
+            BankingStage::filter_transactions_by_cost(
+                &bank,
+                &cost_tracker,
+                transactions.clone(),
+                vec![3, 4, 5],
+            );
+        assert_eq!(packed, transactions[..2].to_vec());
+        assert_eq!(packet_indexes, vec![3, 4]);
+        assert_eq!(cost_limited_packet_indexes, vec![5]);
+
+        // The cost of the next block is tracked from scratch
+        let bank = Arc::new(Bank::new_from_parent(&bank, &Pubkey::default(), 1));
+        let (packed, _, cost_limited_packet_indexes) = BankingStage::filter_transactions_by_cost(
+            &bank,
+            &cost_tracker,
+            transactions[2..].to_vec(),
+            vec![5],
+        );
+        assert_eq!(packed.len(), 1);
+        assert!(cost_limited_packet_indexes.is_empty());
+    }
+
+    #[test]
+    fn test_filter_transactions_by_write_lock_qos() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10_000);
+        let bank = Arc::new(Bank::new(&genesis_config));
+        let hot_account = solana_sdk::pubkey::new_rand();
+        let transactions = vec![
+            system_transaction::transfer(&mint_keypair, &hot_account, 1, genesis_config.hash()),
+            system_transaction::transfer(
+                &Keypair::new(),
+                &solana_sdk::pubkey::new_rand(),
+                1,
+                genesis_config.hash(),
+            ),
+        ];
+        let write_lock_qos = RwLock::new(WriteLockQos::new(100));
+        write_lock_qos
+            .write()
+            .unwrap()
+            .record(bank.slot(), &transactions[..1], 100);
+
+        let (transactions, packet_indexes, deferred_packet_indexes) =
+            BankingStage::filter_transactions_by_write_lock_qos(
+                &bank,
+                &write_lock_qos,
+                transactions,
+                vec![3, 4],
+            );
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(packet_indexes, vec![4]);
+        assert_eq!(deferred_packet_indexes, vec![3]);
+    }
+
+    #[test]
+    fn test_process_received_packets_with_deferred_transactions() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10_000);
+        let bank = Arc::new(Bank::new(&genesis_config));
+        let hot_account = solana_sdk::pubkey::new_rand();
+        let transactions = vec![
+            system_transaction::transfer(&mint_keypair, &hot_account, 1, genesis_config.hash()),
+            system_transaction::transfer(
+                &mint_keypair,
+                &solana_sdk::pubkey::new_rand(),
+                1,
+                genesis_config.hash(),
+            ),
+        ];
+        let packets = to_packets(&transactions).pop().unwrap();
+
+        let scheduler = BankingScheduler::default();
+        scheduler.write_lock_qos().write().unwrap().record(
+            bank.slot(),
+            &[system_transaction::transfer(
+                &Keypair::new(),
+                &hot_account,
+                1,
+                genesis_config.hash(),
+            )],
+            u64::MAX,
+        );
+
+        let working_bank = WorkingBank {
+            bank: bank.clone(),
+            min_tick_height: bank.tick_height(),
+            max_tick_height: bank.tick_height() + 1,
+        };
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&ledger_path)
+                .expect("Expected to be able to open database ledger");
+            let (poh_recorder, _entry_receiver) = PohRecorder::new(
+                bank.tick_height(),
+                bank.last_blockhash(),
+                bank.slot(),
+                Some((4, 4)),
+                bank.ticks_per_slot(),
+                &solana_sdk::pubkey::new_rand(),
+                &Arc::new(blockstore),
+                &Arc::new(LeaderScheduleCache::new_from_bank(&bank)),
+                &Arc::new(PohConfig::default()),
+            );
+            let poh_recorder = Arc::new(Mutex::new(poh_recorder));
+            poh_recorder.lock().unwrap().set_working_bank(working_bank);
+            let (gossip_vote_sender, _gossip_vote_receiver) = unbounded();
+
+            // The transaction writing the hot account is deferred to a later block, without
+            // counting as a transaction left unprocessed by the end of this one
+            let (processed, tx_len, unprocessed_packet_indexes) =
+                BankingStage::process_received_packets(
+                    &bank,
+                    &poh_recorder,
+                    &packets,
+                    vec![0, 1],
+                    None,
+                    &gossip_vote_sender,
+                    &RwLock::new(CostTracker::default()),
+                    &scheduler,
+                );
+            assert_eq!(processed, 1);
+            assert_eq!(tx_len, 1);
+            assert_eq!(unprocessed_packet_indexes, vec![0]);
+        }
+        Blockstore::destroy(&ledger_path).unwrap();
+    }
+
     #[test]
     fn test_filter_valid_packets() {
         solana_logger::setup();
@@ -1953,6 +2288,7 @@ mod tests {
                     &poh_recorder,
                     None,
                     &gossip_vote_sender,
+                    &BankingScheduler::default(),
                 );
 
             assert_eq!(processed_transactions_count, 0,);
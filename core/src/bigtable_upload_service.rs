@@ -1,5 +1,6 @@
-use solana_ledger::blockstore::Blockstore;
+use solana_ledger::{bigtable_upload::UploadCheckpoint, blockstore::Blockstore};
 use solana_runtime::commitment::BlockCommitmentCache;
+use solana_sdk::clock::Slot;
 use std::{
     sync::atomic::{AtomicBool, Ordering},
     sync::{Arc, RwLock},
@@ -24,12 +25,18 @@ pub struct BigTableUploadService {
 impl BigTableUploadService {
     pub fn new(
         runtime_handle: runtime::Handle,
-        bigtable_ledger_storage: solana_storage_bigtable::LedgerStorage,
+        bigtable_ledger_storage: Arc<dyn solana_storage_bigtable::HistoricalLedgerStorage>,
         blockstore: Arc<Blockstore>,
         block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
+        starting_slot: Slot,
+        ending_slot: Option<Slot>,
+        checkpoint: UploadCheckpoint,
         exit: Arc<AtomicBool>,
     ) -> Self {
-        info!("Starting BigTable upload service");
+        info!(
+            "Starting BigTable upload service from slot {} to {:?}",
+            starting_slot, ending_slot
+        );
         let thread = Builder::new()
             .name("bigtable-upload".to_string())
             .spawn(move || {
@@ -38,6 +45,9 @@ impl BigTableUploadService {
                     bigtable_ledger_storage,
                     blockstore,
                     block_commitment_cache,
+                    starting_slot,
+                    ending_slot,
+                    checkpoint,
                     exit,
                 )
             })
@@ -48,22 +58,34 @@ impl BigTableUploadService {
 
     fn run(
         runtime: runtime::Handle,
-        bigtable_ledger_storage: solana_storage_bigtable::LedgerStorage,
+        bigtable_ledger_storage: Arc<dyn solana_storage_bigtable::HistoricalLedgerStorage>,
         blockstore: Arc<Blockstore>,
         block_commitment_cache: Arc<RwLock<BlockCommitmentCache>>,
+        starting_slot: Slot,
+        ending_slot: Option<Slot>,
+        checkpoint: UploadCheckpoint,
         exit: Arc<AtomicBool>,
     ) {
-        let mut start_slot = 0;
+        let mut start_slot = starting_slot;
         loop {
             if exit.load(Ordering::Relaxed) {
                 break;
             }
+            if let Some(ending_slot) = ending_slot {
+                if start_slot >= ending_slot {
+                    info!("BigTable upload reached ending slot {}", ending_slot);
+                    break;
+                }
+            }
 
-            let end_slot = block_commitment_cache
+            let mut end_slot = block_commitment_cache
                 .read()
                 .unwrap()
                 .highest_confirmed_root()
                 .saturating_sub(CFG.LARGEST_CONFIRMED_ROOT_UPLOAD_DELAY as u64);
+            if let Some(ending_slot) = ending_slot {
+                end_slot = end_slot.min(ending_slot);
+            }
 
             if end_slot <= start_slot {
                 std::thread::sleep(std::time::Duration::from_secs(1));
@@ -76,6 +98,7 @@ impl BigTableUploadService {
                 start_slot,
                 Some(end_slot),
                 true,
+                Some(&checkpoint),
                 exit.clone(),
             ));
 
@@ -0,0 +1,200 @@
+//! The `bootstrap_rpc_service` module answers on the RPC port of a validator while it downloads
+//! a snapshot to bootstrap from and replays its ledger, before the JSON RPC service starts:
+//! `/health` reports the node as starting, and `getSnapshotDownloadProgress` and
+//! `getReplayProgress` report how far the download and the replay are, so that operators can
+//! tell a stalled node from a slow one.
+
+use jsonrpc_core::{MetaIoHandler, Params, Value};
+use jsonrpc_http_server::{
+    hyper, CloseHandle, RequestMiddleware, RequestMiddlewareAction, ServerBuilder,
+};
+use solana_client::{
+    rpc_custom_error::RpcCustomError,
+    rpc_response::{RpcReplayProgress, RpcSnapshotDownloadProgress},
+};
+use std::{
+    net::SocketAddr,
+    sync::{mpsc::channel, Arc, RwLock},
+    thread::{self, Builder, JoinHandle},
+};
+
+struct BootstrapRequestMiddleware;
+
+impl RequestMiddleware for BootstrapRequestMiddleware {
+    fn on_request(&self, request: hyper::Request<hyper::Body>) -> RequestMiddlewareAction {
+        if request.uri().path() == "/health" {
+            RequestMiddlewareAction::Respond {
+                should_validate_hosts: true,
+                response: Box::new(jsonrpc_core::futures::future::ok(
+                    hyper::Response::builder()
+                        .status(hyper::StatusCode::OK)
+                        .body(hyper::Body::from("starting"))
+                        .unwrap(),
+                )),
+            }
+        } else {
+            RequestMiddlewareAction::Proceed {
+                should_continue_on_invalid_cors: false,
+                request,
+            }
+        }
+    }
+}
+
+/// Progress of the startup of the node, updated as it goes
+#[derive(Default)]
+pub struct BootstrapProgress {
+    pub snapshot_download: RwLock<Option<RpcSnapshotDownloadProgress>>,
+    pub replay: RwLock<Option<RpcReplayProgress>>,
+}
+
+impl BootstrapProgress {
+    fn health_error(&self) -> jsonrpc_core::Error {
+        let mut error: jsonrpc_core::Error = RpcCustomError::RpcNodeUnhealthy.into();
+        if let Some(replay) = &*self.replay.read().unwrap() {
+            error.message = "Node is replaying the ledger".to_string();
+            error.data = Some(serde_json::to_value(replay).unwrap());
+        } else {
+            error.message = "Node is downloading a snapshot".to_string();
+            error.data = self
+                .snapshot_download
+                .read()
+                .unwrap()
+                .as_ref()
+                .map(|progress| serde_json::to_value(progress).unwrap());
+        }
+        error
+    }
+}
+
+pub struct BootstrapRpcService {
+    thread_hdl: JoinHandle<()>,
+    close_handle: Option<CloseHandle>,
+}
+
+impl BootstrapRpcService {
+    pub fn new(rpc_addr: SocketAddr, progress: Arc<BootstrapProgress>) -> Self {
+        info!("bootstrap rpc bound to {:?}", rpc_addr);
+        let (close_handle_sender, close_handle_receiver) = channel();
+        let thread_hdl = Builder::new()
+            .name("solana-bootstrap-rpc".to_string())
+            .spawn(move || {
+                let mut io = MetaIoHandler::<()>::default();
+                let health_progress = progress.clone();
+                io.add_method("getHealth", move |_params: Params| {
+                    Err(health_progress.health_error())
+                });
+                let download_progress = progress.clone();
+                io.add_method("getSnapshotDownloadProgress", move |_params: Params| {
+                    Ok(
+                        serde_json::to_value(&*download_progress.snapshot_download.read().unwrap())
+                            .unwrap_or(Value::Null),
+                    )
+                });
+                io.add_method("getReplayProgress", move |_params: Params| {
+                    Ok(serde_json::to_value(&*progress.replay.read().unwrap())
+                        .unwrap_or(Value::Null))
+                });
+
+                let server = ServerBuilder::new(io)
+                    .threads(1)
+                    .request_middleware(BootstrapRequestMiddleware)
+                    .start_http(&rpc_addr);
+
+                let server = match server {
+                    Ok(server) => server,
+                    Err(err) => {
+                        warn!("Bootstrap RPC service unavailable error: {:?}", err);
+                        return;
+                    }
+                };
+                close_handle_sender.send(server.close_handle()).unwrap();
+                server.wait();
+            })
+            .unwrap();
+
+        Self {
+            thread_hdl,
+            close_handle: close_handle_receiver.recv().ok(),
+        }
+    }
+
+    /// Stops the service, releasing the RPC port for the JSON RPC service
+    pub fn close(mut self) -> thread::Result<()> {
+        if let Some(close_handle) = self.close_handle.take() {
+            close_handle.close();
+        }
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::rpc_client::RpcClient;
+    use std::{
+        io::{Read, Write},
+        net::{IpAddr, Ipv4Addr, TcpStream},
+    };
+
+    #[test]
+    fn test_bootstrap_rpc_service() {
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let rpc_addr = SocketAddr::new(
+            ip_addr,
+            solana_net_utils::find_available_port_in_range(ip_addr, (10000, 65535)).unwrap(),
+        );
+        let progress = Arc::<BootstrapProgress>::default();
+        let bootstrap_rpc_service = BootstrapRpcService::new(rpc_addr, progress.clone());
+
+        let rpc_client = RpcClient::new_socket(rpc_addr);
+        assert_eq!(rpc_client.get_snapshot_download_progress().unwrap(), None);
+        let download_progress = RpcSnapshotDownloadProgress {
+            slot: 42,
+            downloaded_bytes: 1000,
+            total_bytes: Some(4000),
+            bytes_per_second: 100,
+            estimated_remaining_secs: Some(30),
+        };
+        *progress.snapshot_download.write().unwrap() = Some(download_progress.clone());
+        assert_eq!(
+            rpc_client.get_snapshot_download_progress().unwrap(),
+            Some(download_progress)
+        );
+
+        assert_eq!(rpc_client.get_replay_progress().unwrap(), None);
+        let replay_progress = RpcReplayProgress {
+            slot: 100,
+            highest_slot: 150,
+            num_entries: 64,
+            num_txs: 10,
+            slot_replay_ms: 20,
+            estimated_remaining_secs: 5,
+        };
+        *progress.replay.write().unwrap() = Some(replay_progress.clone());
+        assert_eq!(
+            rpc_client.get_replay_progress().unwrap(),
+            Some(replay_progress.clone())
+        );
+        // getHealth reports the replay once it started
+        let health_error = progress.health_error();
+        assert_eq!(health_error.message, "Node is replaying the ledger");
+        assert_eq!(
+            health_error.data,
+            Some(serde_json::to_value(&replay_progress).unwrap())
+        );
+
+        let mut stream = TcpStream::connect(rpc_addr).unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("starting"));
+
+        bootstrap_rpc_service.close().unwrap();
+        // The port is free again for the JSON RPC service
+        assert!(TcpStream::connect(rpc_addr).is_err());
+    }
+}
@@ -6,6 +6,7 @@ use self::{
 };
 use crate::contact_info::ContactInfo;
 use crate::crds_gossip_pull::CFG as GOSSIP_PULL_CFG;
+use crate::turbine_tree::{self, TurbineTree};
 use crate::weighted_shuffle::weighted_best;
 use crate::{
     cluster_info::{ClusterInfo, ClusterInfoError},
@@ -34,6 +35,7 @@ use std::{
     time::{Duration, Instant},
 };
 
+pub mod adaptive_fec_rate;
 mod broadcast_fake_shreds_run;
 pub mod broadcast_metrics;
 pub(crate) mod broadcast_utils;
@@ -42,6 +44,12 @@ mod standard_broadcast_run;
 
 toml_config::package_config! {
     NUM_INSERT_THREADS: usize,
+    BROADCAST_MIN_FEC_RATE: f64,
+    BROADCAST_MAX_FEC_RATE: f64,
+    BROADCAST_FEC_RATE_STEP: f64,
+    BROADCAST_FEC_HIGH_LOSS: f64,
+    BROADCAST_FEC_LOW_LOSS: f64,
+    BROADCAST_FEC_FEEDBACK_DELAY_MS: u64,
 }
 pub(crate) type RetransmitSlotsSender = CrossbeamSender<HashMap<Slot, Arc<Bank>>>;
 pub(crate) type RetransmitSlotsReceiver = CrossbeamReceiver<HashMap<Slot, Arc<Bank>>>;
@@ -371,11 +379,14 @@ pub fn get_broadcast_peers<S: std::hash::BuildHasher>(
 
 /// broadcast messages from the leader to layer 1 nodes
 /// # Remarks
+/// Given the pubkey of the leader and its turbine tree, each shred is sent to the root of its
+/// retransmit tree, see `turbine_tree`, or else to a stake weighted peer.
 pub fn broadcast_shreds(
     s: &UdpSocket,
     shreds: &Arc<Vec<Shred>>,
     peers_and_stakes: &[(u64, usize)],
     peers: &[ContactInfo],
+    turbine_tree: Option<(&Pubkey, &TurbineTree)>,
     last_datapoint_submit: &Arc<AtomicU64>,
     transmit_stats: &mut TransmitShredsStats,
 ) -> Result<()> {
@@ -385,12 +396,28 @@ pub fn broadcast_shreds(
         return Ok(());
     }
     let mut shred_select = Measure::start("shred_select");
+    let peer_indexes: HashMap<Pubkey, usize> = match turbine_tree {
+        Some(_) => peers
+            .iter()
+            .enumerate()
+            .map(|(index, peer)| (peer.id, index))
+            .collect(),
+        None => HashMap::new(),
+    };
     let packets: Vec<_> = shreds
         .iter()
-        .map(|shred| {
-            let broadcast_index = weighted_best(&peers_and_stakes, shred.seed());
+        .filter_map(|shred| {
+            let broadcast_index = match turbine_tree {
+                Some((leader, tree)) => {
+                    let seed = turbine_tree::shred_seed(shred.slot(), shred.index(), leader);
+                    let peer = tree
+                        .get_broadcast_peer(leader, seed, |node| peer_indexes.contains_key(node))?;
+                    peer_indexes[&peer]
+                }
+                None => weighted_best(&peers_and_stakes, shred.seed()),
+            };
 
-            (&shred.payload, &peers[broadcast_index].tvu)
+            Some((&shred.payload, &peers[broadcast_index].tvu))
         })
         .collect();
     shred_select.stop();
@@ -0,0 +1,209 @@
+//! The `adaptive_fec_rate` module tunes the ratio of coding shreds to data shreds the leader
+//! generates to the loss observed downstream. Once a slot the leader broadcast is
+//! `BROADCAST_FEC_FEEDBACK_DELAY_MS` old, the stake which has not reported the slot as completed
+//! in its gossiped EpochSlots is taken as the loss for the slot: the FEC rate is increased by
+//! `BROADCAST_FEC_RATE_STEP` when the loss is above `BROADCAST_FEC_HIGH_LOSS`, and decreased when
+//! it is below `BROADCAST_FEC_LOW_LOSS`, within
+//! [`BROADCAST_MIN_FEC_RATE`, `BROADCAST_MAX_FEC_RATE`].
+//! The rate stays fixed when both bounds are equal.
+
+use super::CFG;
+use crate::{cluster_info::ClusterInfo, epoch_slots::EpochSlots};
+use solana_sdk::{clock::Slot, pubkey::Pubkey, timing::timestamp};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+// Slots broadcast more than this many slots ago are no longer evaluated
+const MAX_PENDING_SLOTS: usize = 64;
+
+struct State {
+    fec_rate: f32,
+    // Slots fully broadcast, with the time their last shred was sent
+    pending_slots: VecDeque<(Slot, u64)>,
+}
+
+pub struct AdaptiveFecRate {
+    min_fec_rate: f32,
+    max_fec_rate: f32,
+    step: f32,
+    state: Mutex<State>,
+}
+
+impl Default for AdaptiveFecRate {
+    fn default() -> Self {
+        Self::new(
+            CFG.BROADCAST_MIN_FEC_RATE as f32,
+            CFG.BROADCAST_MAX_FEC_RATE as f32,
+            CFG.BROADCAST_FEC_RATE_STEP as f32,
+        )
+    }
+}
+
+impl AdaptiveFecRate {
+    /// Starts at `max_fec_rate`, until the cluster is known to be healthy
+    pub fn new(min_fec_rate: f32, max_fec_rate: f32, step: f32) -> Self {
+        assert!(0.0 <= min_fec_rate && min_fec_rate <= max_fec_rate && max_fec_rate <= 1.0);
+        Self {
+            min_fec_rate,
+            max_fec_rate,
+            step,
+            state: Mutex::new(State {
+                fec_rate: max_fec_rate,
+                pending_slots: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn is_adaptive(&self) -> bool {
+        self.min_fec_rate < self.max_fec_rate
+    }
+
+    pub fn fec_rate(&self) -> f32 {
+        self.state.lock().unwrap().fec_rate
+    }
+
+    /// Records that the last shred of `slot` was broadcast
+    pub fn record_slot_broadcast(&self, slot: Slot) {
+        if !self.is_adaptive() {
+            return;
+        }
+        let mut state = self.state.lock().unwrap();
+        state.pending_slots.push_back((slot, timestamp()));
+        if state.pending_slots.len() > MAX_PENDING_SLOTS {
+            state.pending_slots.pop_front();
+        }
+    }
+
+    /// Evaluates the loss of the pending slots which are old enough, and adjusts the FEC rate
+    /// accordingly
+    pub fn update(&self, cluster_info: &ClusterInfo, stakes: Option<&HashMap<Pubkey, u64>>) {
+        let stakes = match stakes {
+            Some(stakes) if self.is_adaptive() => stakes,
+            _ => return,
+        };
+        let now = timestamp();
+        let mut state = self.state.lock().unwrap();
+        let due = |(_, broadcast_ts): &(Slot, u64)| {
+            broadcast_ts.saturating_add(CFG.BROADCAST_FEC_FEEDBACK_DELAY_MS) <= now
+        };
+        let since = match state.pending_slots.front() {
+            Some(front) if due(front) => front.1,
+            _ => return,
+        };
+        // Only EpochSlots updated after a slot was broadcast may report it as completed
+        let (epoch_slots, _) = cluster_info.get_epoch_slots_since(Some(since));
+        while let Some(&(slot, broadcast_ts)) = state.pending_slots.front() {
+            if !due(&(slot, broadcast_ts)) {
+                break;
+            }
+            state.pending_slots.pop_front();
+            let loss = slot_loss(slot, &epoch_slots, stakes, &cluster_info.id());
+            state.fec_rate = self.next_fec_rate(state.fec_rate, loss);
+            datapoint_info!(
+                "broadcast-adaptive-fec-rate",
+                ("slot", slot, i64),
+                ("loss", loss, f64),
+                ("fec_rate", state.fec_rate as f64, f64),
+            );
+        }
+    }
+
+    fn next_fec_rate(&self, fec_rate: f32, loss: f64) -> f32 {
+        if loss > CFG.BROADCAST_FEC_HIGH_LOSS {
+            (fec_rate + self.step).min(self.max_fec_rate)
+        } else if loss < CFG.BROADCAST_FEC_LOW_LOSS {
+            (fec_rate - self.step).max(self.min_fec_rate)
+        } else {
+            fec_rate
+        }
+    }
+}
+
+/// Fraction of the stake, other than the leader's, which has not reported `slot` as completed
+fn slot_loss(
+    slot: Slot,
+    epoch_slots: &[EpochSlots],
+    stakes: &HashMap<Pubkey, u64>,
+    leader: &Pubkey,
+) -> f64 {
+    let total_stake: u64 = stakes
+        .iter()
+        .filter(|(node, _)| *node != leader)
+        .map(|(_, stake)| stake)
+        .sum();
+    if total_stake == 0 {
+        return 0.0;
+    }
+    let mut reported: Vec<_> = epoch_slots
+        .iter()
+        .filter(|epoch_slots| epoch_slots.from != *leader)
+        .filter(|epoch_slots| epoch_slots.to_slots(slot).contains(&slot))
+        .map(|epoch_slots| epoch_slots.from)
+        .collect();
+    // A node may report the slot in more than one of its EpochSlots
+    reported.sort_unstable();
+    reported.dedup();
+    let reported_stake: u64 = reported.iter().filter_map(|node| stakes.get(node)).sum();
+    1.0 - reported_stake as f64 / total_stake as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_loss() {
+        let leader = solana_sdk::pubkey::new_rand();
+        let nodes: Vec<_> = std::iter::repeat_with(solana_sdk::pubkey::new_rand)
+            .take(4)
+            .collect();
+        let stakes: HashMap<_, _> = nodes
+            .iter()
+            .map(|node| (*node, 10))
+            .chain(std::iter::once((leader, 1000)))
+            .collect();
+        let completed = |node: Pubkey, slots: &[Slot]| {
+            let mut epoch_slots = EpochSlots::new(node, 0);
+            epoch_slots.fill(slots, 0);
+            epoch_slots
+        };
+        assert_eq!(slot_loss(5, &[], &stakes, &leader), 1.0);
+        let epoch_slots = vec![
+            completed(leader, &[5]),
+            completed(nodes[0], &[5]),
+            completed(nodes[0], &[4, 5]),
+            completed(nodes[1], &[4, 5, 6]),
+            completed(nodes[2], &[4]),
+        ];
+        assert_eq!(slot_loss(5, &epoch_slots, &stakes, &leader), 0.5);
+        assert_eq!(slot_loss(4, &epoch_slots, &stakes, &leader), 0.25);
+        assert_eq!(slot_loss(5, &epoch_slots, &HashMap::new(), &leader), 0.0);
+    }
+
+    #[test]
+    fn test_next_fec_rate() {
+        let fec_rate = AdaptiveFecRate::new(0.25, 1.0, 0.25);
+        assert!(fec_rate.is_adaptive());
+        assert_eq!(fec_rate.fec_rate(), 1.0);
+        let high_loss = CFG.BROADCAST_FEC_HIGH_LOSS + 0.01;
+        let low_loss = CFG.BROADCAST_FEC_LOW_LOSS / 2.0;
+        assert_eq!(fec_rate.next_fec_rate(1.0, low_loss), 0.75);
+        assert_eq!(fec_rate.next_fec_rate(0.25, low_loss), 0.25);
+        assert_eq!(fec_rate.next_fec_rate(0.5, high_loss), 0.75);
+        assert_eq!(fec_rate.next_fec_rate(1.0, high_loss), 1.0);
+        let loss = (CFG.BROADCAST_FEC_LOW_LOSS + CFG.BROADCAST_FEC_HIGH_LOSS) / 2.0;
+        assert_eq!(fec_rate.next_fec_rate(0.5, loss), 0.5);
+
+        let fixed_fec_rate = AdaptiveFecRate::new(1.0, 1.0, 0.25);
+        assert!(!fixed_fec_rate.is_adaptive());
+        fixed_fec_rate.record_slot_broadcast(1);
+        assert!(fixed_fec_rate
+            .state
+            .lock()
+            .unwrap()
+            .pending_slots
+            .is_empty());
+    }
+}
@@ -144,6 +144,7 @@ impl BroadcastRun for FailEntryVerificationBroadcastRun {
             &shreds,
             &peers_and_stakes,
             &peers,
+            None,
             &Arc::new(AtomicU64::new(0)),
             &mut TransmitShredsStats::default(),
         )?;
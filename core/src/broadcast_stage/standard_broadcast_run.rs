@@ -1,13 +1,16 @@
 use super::{
+    adaptive_fec_rate::AdaptiveFecRate,
     broadcast_utils::{self, ReceiveResults},
     *,
 };
 use crate::broadcast_stage::broadcast_utils::UnfinishedSlotInfo;
 use solana_ledger::{
     entry::Entry,
-    shred::{Shred, Shredder, RECOMMENDED_FEC_RATE, SHRED_TICK_REFERENCE_MASK},
+    shred::{Shred, Shredder, SHRED_TICK_REFERENCE_MASK},
+};
+use solana_sdk::{
+    clock::Slot, feature_set, pubkey::Pubkey, signature::Keypair, timing::duration_as_us,
 };
-use solana_sdk::{pubkey::Pubkey, signature::Keypair, timing::duration_as_us};
 use std::collections::HashMap;
 use std::sync::RwLock;
 use std::time::Duration;
@@ -26,12 +29,19 @@ pub struct StandardBroadcastRun {
     num_batches: usize,
     broadcast_peer_cache: Arc<RwLock<BroadcastPeerCache>>,
     last_peer_update: Arc<AtomicU64>,
+    adaptive_fec_rate: Arc<AdaptiveFecRate>,
+    // FEC rate of the current slot
+    fec_rate: f32,
+    // First slot whose shreds are broadcast along the turbine tree, once the turbine_tree
+    // feature is active
+    turbine_tree_slot: Arc<RwLock<Option<Slot>>>,
 }
 
 #[derive(Default)]
 struct BroadcastPeerCache {
     peers: Vec<ContactInfo>,
     peers_and_stakes: Vec<(u64, usize)>,
+    turbine_tree: TurbineTree,
 }
 
 impl StandardBroadcastRun {
@@ -49,6 +59,9 @@ impl StandardBroadcastRun {
             num_batches: 0,
             broadcast_peer_cache: Arc::new(RwLock::new(BroadcastPeerCache::default())),
             last_peer_update: Arc::new(AtomicU64::new(0)),
+            adaptive_fec_rate: Arc::new(AdaptiveFecRate::default()),
+            fec_rate: 0.0,
+            turbine_tree_slot: Arc::new(RwLock::new(None)),
         }
     }
 
@@ -100,7 +113,7 @@ impl StandardBroadcastRun {
             Shredder::new(
                 slot,
                 parent_slot,
-                RECOMMENDED_FEC_RATE,
+                self.fec_rate,
                 self.keypair.clone(),
                 reference_tick,
                 self.shred_version,
@@ -181,6 +194,10 @@ impl StandardBroadcastRun {
         // 1) Check if slot was interrupted
         let last_unfinished_slot_shred =
             self.check_for_interrupted_slot(bank.ticks_per_slot() as u8);
+        if self.unfinished_slot.is_none() {
+            // All the shreds of a slot are generated with the same FEC rate
+            self.fec_rate = self.adaptive_fec_rate.fec_rate();
+        }
 
         // 2) Convert entries to shreds and coding shreds
         let (shredder, next_shred_index) = self.init_shredder(
@@ -208,6 +225,9 @@ impl StandardBroadcastRun {
         let bank_epoch = bank.get_leader_schedule_epoch(bank.slot());
         let stakes = staking_utils::staked_nodes_at_epoch(&bank, bank_epoch);
         let stakes = stakes.map(Arc::new);
+        *self.turbine_tree_slot.write().unwrap() = bank
+            .feature_set
+            .activated_slot(&feature_set::turbine_tree::id());
 
         // Broadcast the last shred of the interrupted slot if necessary
         if let Some(last_shred) = last_unfinished_slot_shred {
@@ -256,6 +276,7 @@ impl StandardBroadcastRun {
         if last_tick_height == bank.max_tick_height() {
             self.report_and_reset_stats();
             self.unfinished_slot = None;
+            self.adaptive_fec_rate.record_slot_broadcast(bank.slot());
         }
 
         Ok(())
@@ -308,6 +329,8 @@ impl StandardBroadcastRun {
             BROADCAST_PEER_UPDATE_INTERVAL_MS: u64,
         }
         trace!("Broadcasting {:?} shreds", shreds.len());
+        self.adaptive_fec_rate
+            .update(cluster_info, stakes.as_deref());
         // Get the list of peers to broadcast to
         let mut get_peers_time = Measure::start("broadcast::get_peers");
         let now = timestamp();
@@ -319,12 +342,27 @@ impl StandardBroadcastRun {
                 == last
         {
             let mut w_broadcast_peer_cache = self.broadcast_peer_cache.write().unwrap();
-            let (peers, peers_and_stakes) = get_broadcast_peers(cluster_info, stakes);
+            let (peers, peers_and_stakes) = get_broadcast_peers(cluster_info, stakes.clone());
+            w_broadcast_peer_cache.turbine_tree = TurbineTree::new(
+                peers
+                    .iter()
+                    .map(|peer| peer.id)
+                    .chain(std::iter::once(cluster_info.id())),
+                stakes.as_deref(),
+            );
             w_broadcast_peer_cache.peers = peers;
             w_broadcast_peer_cache.peers_and_stakes = peers_and_stakes;
         }
         get_peers_time.stop();
         let r_broadcast_peer_cache = self.broadcast_peer_cache.read().unwrap();
+        // All the shreds of a batch are of the same slot
+        let id = cluster_info.id();
+        let turbine_tree = match (*self.turbine_tree_slot.read().unwrap(), shreds.first()) {
+            (Some(turbine_tree_slot), Some(shred)) if shred.slot() >= turbine_tree_slot => {
+                Some((&id, &r_broadcast_peer_cache.turbine_tree))
+            }
+            _ => None,
+        };
 
         let mut transmit_stats = TransmitShredsStats::default();
         // Broadcast the shreds
@@ -334,6 +372,7 @@ impl StandardBroadcastRun {
             &shreds,
             &r_broadcast_peer_cache.peers_and_stakes,
             &r_broadcast_peer_cache.peers,
+            turbine_tree,
             &self.last_datapoint_submit,
             &mut transmit_stats,
         )?;
@@ -366,6 +405,7 @@ impl StandardBroadcastRun {
             ("slot", self.unfinished_slot.unwrap().slot as i64, i64),
             ("shredding_time", stats.shredding_elapsed as i64, i64),
             ("receive_time", stats.receive_elapsed as i64, i64),
+            ("fec_rate", self.fec_rate as f64, f64),
             (
                 "num_data_shreds",
                 i64::from(self.unfinished_slot.unwrap().next_shred_index),
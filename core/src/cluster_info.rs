@@ -13,16 +13,24 @@
 //!
 //! Bank needs to provide an interface for us to query the stake weight
 use crate::{
+    ancestor_hashes_service::AncestorHashesReports,
     contact_info::ContactInfo,
-    crds_gossip::CrdsGossip,
+    crds::{Crds, CrdsTtls},
+    crds_gossip::{CrdsGossip, InsertPolicyStats},
     crds_gossip_error::CrdsGossipError,
     crds_gossip_pull::{CrdsFilter, ProcessPullStats, CFG as GOSSIP_PULL_CFG},
+    crds_gossip_push::PushConfig,
     crds_value::{
-        self, CrdsData, CrdsValue, CrdsValueLabel, EpochSlotsIndex, LowestSlot, SnapshotHash,
-        Version, Vote, MAX_WALLCLOCK,
+        self, CrdsData, CrdsValue, CrdsValueLabel, EpochSlotsIndex, LowestSlot, NodeCapabilities,
+        NodeInstance, PlannedHardFork, RestartBankHash, SnapshotHash, ValidatorInfo, Version, Vote,
+        CAPABILITY_GOSSIP_COMPRESSION, CAPABILITY_NETWORK_NAMESPACE, CAPABILITY_TPU_VOTE,
+        MAX_WALLCLOCK,
     },
     data_budget::DataBudget,
-    epoch_slots::EpochSlots,
+    epoch_slots::{self, EpochSlots, EpochSlotsShard},
+    gossip_compression::{self, GOSSIP_COMPRESSION_CODEC},
+    gossip_peer_stats::GossipPeerStatsTable,
+    peer_bandwidth::PeerBandwidthTracker,
     ping_pong::{self, PingCache, Pong},
     result::{Error, Result},
     weighted_shuffle::weighted_shuffle,
@@ -41,7 +49,10 @@ use rayon::{ThreadPool, ThreadPoolBuilder};
 use solana_ledger::staking_utils;
 use solana_measure::measure::Measure;
 use solana_measure::thread_mem_usage;
-use solana_metrics::{inc_new_counter_debug, inc_new_counter_error};
+use solana_metrics::{
+    inc_new_counter_debug, inc_new_counter_error,
+    registry::{self, Counter, Gauge, Namespace},
+};
 use solana_net_utils::{
     bind_common, bind_common_in_range, bind_in_range, find_available_port_in_range,
     multi_bind_in_range, PortRange,
@@ -66,18 +77,20 @@ use solana_streamer::streamer::{PacketReceiver, PacketSender};
 use std::{
     borrow::Cow,
     cmp::min,
-    collections::{hash_map::Entry, HashMap, HashSet},
+    collections::{hash_map::Entry, BTreeMap, HashMap, HashSet},
+    convert::TryInto,
     fmt,
     net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, UdpSocket},
     ops::{Deref, DerefMut},
-    sync::atomic::{AtomicBool, AtomicU64, Ordering},
-    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
     thread::{sleep, Builder, JoinHandle},
     time::{Duration, Instant},
 };
 
 pub const VALIDATOR_PORT_RANGE: PortRange = (8000, 10_000);
-pub const MINIMUM_VALIDATOR_PORT_RANGE_WIDTH: u16 = 10; // VALIDATOR_PORT_RANGE must be at least this wide
+pub const MINIMUM_VALIDATOR_PORT_RANGE_WIDTH: u16 = 11; // VALIDATOR_PORT_RANGE must be at least this wide
 
 toml_config::package_config! {
     DATA_PLANE_FANOUT: usize,
@@ -87,6 +100,11 @@ toml_config::package_config! {
     MAX_SNAPSHOT_HASHES: usize ,
     GOSSIP_PING_CACHE_CAPACITY: usize,
     GOSSIP_PING_CACHE_TTL: u64,
+    CRDS_CACHE_SAVE_INTERVAL_MS: u64,
+    CRDS_CACHE_MAX_AGE_MS: u64,
+    CRDS_MAX_TABLE_SIZE: usize,
+    CRDS_VOTE_TIMEOUT_MS: u64,
+    CRDS_CONTACT_INFO_TIMEOUT_MS: u64,
 }
 
 toml_config::derived_values! {
@@ -94,6 +112,9 @@ toml_config::derived_values! {
     MAX_PROTOCOL_PAYLOAD_SIZE: u64 = PACKET_DATA_SIZE as u64 - CFG.MAX_PROTOCOL_HEADER_SIZE;
 }
 
+/// Name of the file, relative to the ledger directory, that the CRDS cache is persisted to
+pub const CRDS_CACHE_FILE: &str = "gossip-cache.bin";
+
 /// A hard limit on incoming gossip messages
 /// Chosen to be able to handle 1Gbps of pure gossip traffic
 /// 128MB/PACKET_DATA_SIZE
@@ -144,7 +165,8 @@ impl<'a> DerefMut for GossipWriteLock<'a> {
 
 impl<'a> Drop for GossipWriteLock<'a> {
     fn drop(&mut self) {
-        self.counter.add_measure(&mut self.timer);
+        self.timer.stop();
+        self.counter.add(self.timer.as_us());
     }
 }
 
@@ -177,73 +199,169 @@ impl<'a> Deref for GossipReadLock<'a> {
 
 impl<'a> Drop for GossipReadLock<'a> {
     fn drop(&mut self) {
-        self.counter.add_measure(&mut self.timer);
+        self.timer.stop();
+        self.counter.add(self.timer.as_us());
     }
 }
 
-#[derive(Default)]
-struct Counter(AtomicU64);
+/// Gossip metrics, submitted as the `cluster_info_stats` to `cluster_info_stats5` datapoints. The
+/// namespaces are registered for the node identity, so that the stats of several `ClusterInfo` in
+/// a process, as in tests and local clusters, are not merged
+struct GossipStats {
+    entrypoint: Arc<Counter>,
+    entrypoint2: Arc<Counter>,
+    push_vote_read: Arc<Counter>,
+    vote_process_push: Arc<Counter>,
+    get_votes: Arc<Counter>,
+    get_accounts_hash: Arc<Counter>,
+    get_snapshot_hash: Arc<Counter>,
+    all_tvu_peers: Arc<Counter>,
+    tvu_peers: Arc<Counter>,
+    retransmit_peers: Arc<Counter>,
+    repair_peers: Arc<Counter>,
+    new_push_requests: Arc<Counter>,
+    new_push_requests2: Arc<Counter>,
+    new_push_requests_num: Arc<Counter>,
+    filter_pull_response: Arc<Counter>,
+    process_gossip_packets_time: Arc<Counter>,
+    process_pull_response: Arc<Counter>,
+    process_pull_response_count: Arc<Counter>,
+    process_pull_response_len: Arc<Counter>,
+    process_pull_response_timeout: Arc<Counter>,
+    process_pull_response_fail_insert: Arc<Counter>,
+    process_pull_response_fail_timeout: Arc<Counter>,
+    process_pull_response_success: Arc<Counter>,
+    process_pull_requests: Arc<Counter>,
+    generate_pull_responses: Arc<Counter>,
+    process_prune: Arc<Counter>,
+    process_push_message: Arc<Counter>,
+    prune_received_cache: Arc<Counter>,
+    prune_message_count: Arc<Counter>,
+    prune_message_len: Arc<Counter>,
+    pull_request_ping_pong_check_failed_count: Arc<Counter>,
+    purge: Arc<Counter>,
+    trim: Arc<Counter>,
+    epoch_slots_lookup: Arc<Counter>,
+    epoch_slots_push: Arc<Counter>,
+    push_message: Arc<Counter>,
+    new_pull_requests: Arc<Counter>,
+    new_pull_requests_count: Arc<Counter>,
+    mark_pull_request: Arc<Counter>,
+    skip_pull_response_shred_version: Arc<Counter>,
+    skip_pull_shred_version: Arc<Counter>,
+    skip_push_message_shred_version: Arc<Counter>,
+    filter_inserts: Arc<Counter>,
+    reject_insert_origin_limit: Arc<Counter>,
+    reject_insert_unstaked_limit: Arc<Counter>,
+    reject_insert_table_budget: Arc<Counter>,
+    reject_insert_validator_info_limit: Arc<Counter>,
+    push_message_count: Arc<Counter>,
+    push_message_value_count: Arc<Counter>,
+    push_response_count: Arc<Counter>,
+    pull_requests_count: Arc<Counter>,
+    packet_rejected_size: Arc<Counter>,
+    packet_rejected_message_type: Arc<Counter>,
+    packet_rejected_length_prefix: Arc<Counter>,
+    packet_rejected_deserialize: Arc<Counter>,
+    packet_rejected_sanitize: Arc<Counter>,
+    packet_rejected_verify: Arc<Counter>,
+    packet_rejected_decompress: Arc<Counter>,
+    table_size: Arc<Gauge>,
+    purged_values_size: Arc<Gauge>,
+    failed_inserts_size: Arc<Gauge>,
+    namespaces: Vec<Arc<Namespace>>,
+    /// The above broken down by peer, for the counters that can be
+    peers: GossipPeerStatsTable,
+}
 
-impl Counter {
-    fn add_measure(&self, x: &mut Measure) {
-        x.stop();
-        self.0.fetch_add(x.as_us(), Ordering::Relaxed);
-    }
-    fn add_relaxed(&self, x: u64) {
-        self.0.fetch_add(x, Ordering::Relaxed);
-    }
-    fn clear(&self) -> u64 {
-        self.0.swap(0, Ordering::Relaxed)
+impl GossipStats {
+    fn new(id: &Pubkey) -> Self {
+        let instance = id.to_string();
+        let stats = registry::instance_namespace("cluster_info_stats", &instance);
+        let stats2 = registry::instance_namespace("cluster_info_stats2", &instance);
+        let stats3 = registry::instance_namespace("cluster_info_stats3", &instance);
+        let stats4 = registry::instance_namespace("cluster_info_stats4", &instance);
+        let stats5 = registry::instance_namespace("cluster_info_stats5", &instance);
+        Self {
+            entrypoint: stats.counter("entrypoint"),
+            entrypoint2: stats.counter("entrypoint2"),
+            push_vote_read: stats.counter("push_vote_read"),
+            vote_process_push: stats.counter("vote_process_push"),
+            get_votes: stats.counter("get_votes"),
+            get_accounts_hash: stats.counter("get_accounts_hash"),
+            get_snapshot_hash: stats.counter("get_snapshot_hash"),
+            all_tvu_peers: stats.counter("all_tvu_peers"),
+            tvu_peers: stats.counter("tvu_peers"),
+            retransmit_peers: stats2.counter("retransmit_peers"),
+            repair_peers: stats2.counter("repair_peers"),
+            new_push_requests: stats2.counter("new_push_requests"),
+            new_push_requests2: stats2.counter("new_push_requests2"),
+            new_push_requests_num: stats.counter("new_push_requests_num"),
+            filter_pull_response: stats2.counter("filter_pull_resp"),
+            process_gossip_packets_time: stats2.counter("process_gossip_packets_time"),
+            process_pull_response: stats2.counter("process_pull_resp"),
+            process_pull_response_count: stats2.counter("process_pull_resp_count"),
+            process_pull_response_len: stats3.counter("process_pull_resp_len"),
+            process_pull_response_timeout: stats2.counter("process_pull_resp_timeout"),
+            process_pull_response_fail_insert: stats2.counter("pull_response_fail_insert"),
+            process_pull_response_fail_timeout: stats2.counter("pull_response_fail_timeout"),
+            process_pull_response_success: stats2.counter("pull_response_success"),
+            process_pull_requests: stats3.counter("process_pull_requests"),
+            generate_pull_responses: stats3.counter("generate_pull_responses"),
+            process_prune: stats3.counter("process_prune"),
+            process_push_message: stats3.counter("process_push_message"),
+            prune_received_cache: stats3.counter("prune_received_cache"),
+            prune_message_count: stats4.counter("prune_message_count"),
+            prune_message_len: stats4.counter("prune_message_len"),
+            pull_request_ping_pong_check_failed_count: stats3
+                .counter("pull_request_ping_pong_check_failed_count"),
+            purge: stats2.counter("purge"),
+            trim: stats2.counter("trim"),
+            epoch_slots_lookup: stats3.counter("epoch_slots_lookup"),
+            epoch_slots_push: stats3.counter("epoch_slots_push"),
+            push_message: stats3.counter("push_message"),
+            new_pull_requests: stats3.counter("new_pull_requests"),
+            new_pull_requests_count: stats4.counter("new_pull_requests_count"),
+            mark_pull_request: stats3.counter("mark_pull_request"),
+            skip_pull_response_shred_version: stats4.counter("skip_pull_response_shred_version"),
+            skip_pull_shred_version: stats4.counter("skip_pull_shred_version"),
+            skip_push_message_shred_version: stats4.counter("skip_push_message_shred_version"),
+            filter_inserts: stats5.counter("filter_inserts"),
+            reject_insert_origin_limit: stats5.counter("reject_insert_origin_limit"),
+            reject_insert_unstaked_limit: stats5.counter("reject_insert_unstaked_limit"),
+            reject_insert_table_budget: stats5.counter("reject_insert_table_budget"),
+            reject_insert_validator_info_limit: stats5
+                .counter("reject_insert_validator_info_limit"),
+            push_message_count: stats4.counter("push_message_count"),
+            push_message_value_count: stats4.counter("push_message_value_count"),
+            push_response_count: stats2.counter("push_response_count"),
+            pull_requests_count: stats5.counter("pull_requests_count"),
+            packet_rejected_size: stats5.counter("packet_rejected_size"),
+            packet_rejected_message_type: stats5.counter("packet_rejected_message_type"),
+            packet_rejected_length_prefix: stats5.counter("packet_rejected_length_prefix"),
+            packet_rejected_deserialize: stats5.counter("packet_rejected_deserialize"),
+            packet_rejected_sanitize: stats5.counter("packet_rejected_sanitize"),
+            packet_rejected_verify: stats5.counter("packet_rejected_verify"),
+            packet_rejected_decompress: stats5.counter("packet_rejected_decompress"),
+            table_size: stats.gauge("table_size"),
+            purged_values_size: stats.gauge("purged_values_size"),
+            failed_inserts_size: stats.gauge("failed_inserts_size"),
+            namespaces: vec![stats, stats2, stats3, stats4, stats5],
+            peers: GossipPeerStatsTable::default(),
+        }
     }
-}
 
-#[derive(Default)]
-struct GossipStats {
-    entrypoint: Counter,
-    entrypoint2: Counter,
-    push_vote_read: Counter,
-    vote_process_push: Counter,
-    get_votes: Counter,
-    get_accounts_hash: Counter,
-    get_snapshot_hash: Counter,
-    all_tvu_peers: Counter,
-    tvu_peers: Counter,
-    retransmit_peers: Counter,
-    repair_peers: Counter,
-    new_push_requests: Counter,
-    new_push_requests2: Counter,
-    new_push_requests_num: Counter,
-    filter_pull_response: Counter,
-    process_gossip_packets_time: Counter,
-    process_pull_response: Counter,
-    process_pull_response_count: Counter,
-    process_pull_response_len: Counter,
-    process_pull_response_timeout: Counter,
-    process_pull_response_fail_insert: Counter,
-    process_pull_response_fail_timeout: Counter,
-    process_pull_response_success: Counter,
-    process_pull_requests: Counter,
-    generate_pull_responses: Counter,
-    process_prune: Counter,
-    process_push_message: Counter,
-    prune_received_cache: Counter,
-    prune_message_count: Counter,
-    prune_message_len: Counter,
-    pull_request_ping_pong_check_failed_count: Counter,
-    purge: Counter,
-    epoch_slots_lookup: Counter,
-    epoch_slots_push: Counter,
-    push_message: Counter,
-    new_pull_requests: Counter,
-    new_pull_requests_count: Counter,
-    mark_pull_request: Counter,
-    skip_pull_response_shred_version: Counter,
-    skip_pull_shred_version: Counter,
-    skip_push_message_shred_version: Counter,
-    push_message_count: Counter,
-    push_message_value_count: Counter,
-    push_response_count: Counter,
-    pull_requests_count: Counter,
+    fn packet_rejected(&self, rejection: PacketRejection) -> &Counter {
+        match rejection {
+            PacketRejection::Size => &self.packet_rejected_size,
+            PacketRejection::MessageType => &self.packet_rejected_message_type,
+            PacketRejection::LengthPrefix => &self.packet_rejected_length_prefix,
+            PacketRejection::Deserialize => &self.packet_rejected_deserialize,
+            PacketRejection::Sanitize => &self.packet_rejected_sanitize,
+            PacketRejection::Verify => &self.packet_rejected_verify,
+            PacketRejection::Decompress => &self.packet_rejected_decompress,
+        }
+    }
 }
 
 pub struct ClusterInfo {
@@ -255,13 +373,25 @@ pub struct ClusterInfo {
     entrypoint: RwLock<Option<ContactInfo>>,
     outbound_budget: DataBudget,
     my_contact_info: RwLock<ContactInfo>,
+    // Network namespace advertised in the capabilities of this node
+    network_namespace: RwLock<Option<String>>,
     ping_cache: RwLock<PingCache>,
     id: Pubkey,
     stats: GossipStats,
     socket: UdpSocket,
     local_message_pending_push_queue: RwLock<Vec<(CrdsValue, u64)>>,
+    crds_cache_path: RwLock<Option<PathBuf>>,
+    peer_bandwidth: PeerBandwidthTracker,
+    ancestor_hashes_reports: AncestorHashesReports,
+    instance: NodeInstance,
+    // Set once another instance of this node was seen in gossip
+    duplicate_instance: AtomicBool,
+    // Pushes waiting for the feature gating their CrdsData variant, see `push_once_active`
+    gated_pushes: Mutex<Vec<GatedPush>>,
 }
 
+type GatedPush = (Pubkey, Box<dyn FnOnce(&ClusterInfo) + Send>);
+
 impl Default for ClusterInfo {
     fn default() -> Self {
         Self::new_with_invalid_keypair(ContactInfo::default())
@@ -374,6 +504,9 @@ enum Protocol {
     PruneMessage(Pubkey, PruneData),
     PingMessage(Ping),
     PongMessage(Pong),
+    /// Values compressed by `gossip_compression`, sent only to the nodes accepting them
+    CompressedPullResponse(Pubkey, Vec<u8>),
+    CompressedPushMessage(Pubkey, Vec<u8>),
 }
 
 impl Protocol {
@@ -441,6 +574,8 @@ impl Protocol {
                     None
                 }
             }
+            // Decompressed before being verified
+            Protocol::CompressedPullResponse(..) | Protocol::CompressedPushMessage(..) => None,
         }
     }
 }
@@ -457,10 +592,108 @@ impl Sanitize for Protocol {
             Protocol::PruneMessage(_, val) => val.sanitize(),
             Protocol::PingMessage(ping) => ping.sanitize(),
             Protocol::PongMessage(pong) => pong.sanitize(),
+            // Decompressed before being sanitized
+            Protocol::CompressedPullResponse(..) | Protocol::CompressedPushMessage(..) => {
+                Err(SanitizeError::InvalidValue)
+            }
+        }
+    }
+}
+
+/// Smallest serialized `CrdsValue`: its signature and the tag of its data
+const MIN_CRDS_VALUE_SIZE: usize = 64 + 4;
+
+/// Why a gossip packet was rejected before its message was handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketRejection {
+    /// Empty, or larger than a packet
+    Size,
+    MessageType,
+    /// A vector claims more elements than the packet can hold
+    LengthPrefix,
+    Deserialize,
+    Sanitize,
+    Verify,
+    /// The values of a compressed message do not decompress
+    Decompress,
+}
+
+/// Checks the vector length prefix at `offset` of `data` against the bytes left after it, each
+/// element taking at least `min_element_size` bytes
+fn check_length_prefix(
+    data: &[u8],
+    offset: usize,
+    min_element_size: usize,
+) -> std::result::Result<(), PacketRejection> {
+    let prefix = data
+        .get(offset..offset + 8)
+        .ok_or(PacketRejection::LengthPrefix)?;
+    let len = u64::from_le_bytes(prefix.try_into().unwrap());
+    let max_len = (data.len() - offset - 8) / min_element_size;
+    if len > max_len as u64 {
+        Err(PacketRejection::LengthPrefix)
+    } else {
+        Ok(())
+    }
+}
+
+/// Deserializes, sanitizes and verifies the gossip message of a packet. The sizes bincode would
+/// allocate for are checked against the packet length beforehand.
+fn parse_protocol(data: &[u8]) -> std::result::Result<Protocol, PacketRejection> {
+    if data.is_empty() || data.len() > PACKET_DATA_SIZE {
+        return Err(PacketRejection::Size);
+    }
+    let message_type = data
+        .get(0..4)
+        .map(|tag| u32::from_le_bytes(tag.try_into().unwrap()))
+        .ok_or(PacketRejection::MessageType)?;
+    match message_type {
+        // PullRequest: the keys of the bloom filter, first in the message
+        0 => check_length_prefix(data, 4, std::mem::size_of::<u64>())?,
+        // PullResponse and PushMessage: the values, after the pubkey of the sender
+        1 | 2 => check_length_prefix(data, 4 + 32, MIN_CRDS_VALUE_SIZE)?,
+        // PruneMessage: the pruned pubkeys, after the pubkeys of the sender and prune data
+        3 => check_length_prefix(data, 4 + 32 + 32, std::mem::size_of::<Pubkey>())?,
+        4 | 5 => (),
+        // CompressedPullResponse and CompressedPushMessage: the compressed bytes
+        6 | 7 => check_length_prefix(data, 4 + 32, 1)?,
+        _ => return Err(PacketRejection::MessageType),
+    }
+    let protocol: Protocol = limited_deserialize(data).map_err(|_| PacketRejection::Deserialize)?;
+    let decompress = |data: Vec<u8>| {
+        gossip_compression::decompress(&data).map_err(|_| PacketRejection::Decompress)
+    };
+    let protocol = match protocol {
+        Protocol::CompressedPullResponse(from, data) => {
+            Protocol::PullResponse(from, decompress(data)?)
+        }
+        Protocol::CompressedPushMessage(from, data) => {
+            Protocol::PushMessage(from, decompress(data)?)
         }
+        protocol => protocol,
+    };
+    protocol.sanitize().map_err(|_| PacketRejection::Sanitize)?;
+    protocol.par_verify().ok_or(PacketRejection::Verify)
+}
+
+/// The pubkey the sender of a gossip packet claims to have, read without deserializing the
+/// message. Pull requests have the contact info of the caller after the bloom filter, so aren't
+/// covered.
+fn claimed_sender(data: &[u8]) -> Option<Pubkey> {
+    let message_type = u32::from_le_bytes(data.get(0..4)?.try_into().unwrap());
+    if (1..=7).contains(&message_type) {
+        data.get(4..36).map(Pubkey::new)
+    } else {
+        None
     }
 }
 
+/// Checks a gossip packet the way the packets received from the network are, before their
+/// messages are handled
+pub fn check_gossip_packet(data: &[u8]) -> std::result::Result<(), PacketRejection> {
+    parse_protocol(data).map(|_| ())
+}
+
 // Rating for pull requests
 // A response table is generated as a
 // 2-d table arranged by target nodes and a
@@ -486,14 +719,21 @@ impl ClusterInfo {
             entrypoint: RwLock::new(None),
             outbound_budget: DataBudget::default(),
             my_contact_info: RwLock::new(contact_info),
+            network_namespace: RwLock::default(),
             ping_cache: RwLock::new(PingCache::new(
                 Duration::from_secs(CFG.GOSSIP_PING_CACHE_TTL),
                 CFG.GOSSIP_PING_CACHE_CAPACITY,
             )),
             id,
-            stats: GossipStats::default(),
+            stats: GossipStats::new(&id),
             socket: UdpSocket::bind("0.0.0.0:0").unwrap(),
             local_message_pending_push_queue: RwLock::new(vec![]),
+            crds_cache_path: RwLock::new(None),
+            peer_bandwidth: PeerBandwidthTracker::default(),
+            ancestor_hashes_reports: AncestorHashesReports::default(),
+            instance: NodeInstance::new(&mut rand::thread_rng(), id, timestamp()),
+            duplicate_instance: AtomicBool::default(),
+            gated_pushes: Mutex::default(),
         };
         {
             let mut gossip = me.gossip.write().unwrap();
@@ -517,9 +757,10 @@ impl ClusterInfo {
             entrypoint: RwLock::new(self.entrypoint.read().unwrap().clone()),
             outbound_budget: self.outbound_budget.clone_non_atomic(),
             my_contact_info: RwLock::new(my_contact_info),
+            network_namespace: RwLock::new(self.network_namespace.read().unwrap().clone()),
             ping_cache: RwLock::new(self.ping_cache.read().unwrap().mock_clone()),
             id: *new_id,
-            stats: GossipStats::default(),
+            stats: GossipStats::new(new_id),
             socket: UdpSocket::bind("0.0.0.0:0").unwrap(),
             local_message_pending_push_queue: RwLock::new(
                 self.local_message_pending_push_queue
@@ -527,6 +768,12 @@ impl ClusterInfo {
                     .unwrap()
                     .clone(),
             ),
+            crds_cache_path: RwLock::new(self.crds_cache_path.read().unwrap().clone()),
+            peer_bandwidth: PeerBandwidthTracker::default(),
+            ancestor_hashes_reports: AncestorHashesReports::default(),
+            instance: NodeInstance::new(&mut rand::thread_rng(), *new_id, timestamp()),
+            duplicate_instance: AtomicBool::default(),
+            gated_pushes: Mutex::default(),
         }
     }
 
@@ -569,6 +816,142 @@ impl ClusterInfo {
         *self.entrypoint.write().unwrap() = Some(entrypoint)
     }
 
+    pub fn push_config(&self) -> PushConfig {
+        self.gossip.read().unwrap().push.config()
+    }
+
+    /// Change the push fanout and active set size, taking effect with the next push
+    pub fn set_push_config(&self, config: PushConfig) -> Result<(), CrdsGossipError> {
+        info!("gossip push config: {:?}", config);
+        self.gossip.write().unwrap().push.set_config(config)
+    }
+
+    /// Load any CRDS cache previously saved to `crds_cache_path`, and have the gossip thread
+    /// periodically save the CRDS cache there from now on
+    pub fn enable_crds_cache(&self, crds_cache_path: PathBuf) {
+        match self.load_crds_cache(&crds_cache_path) {
+            Ok(num_loaded) => info!(
+                "Loaded {} gossip values from {:?}",
+                num_loaded, crds_cache_path
+            ),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => warn!(
+                "Unable to load gossip cache from {:?}: {}",
+                crds_cache_path, err
+            ),
+        }
+        *self.crds_cache_path.write().unwrap() = Some(crds_cache_path)
+    }
+
+    fn is_crds_cache_value(value: &CrdsValue) -> bool {
+        matches!(
+            value.data,
+            CrdsData::ContactInfo(_)
+                | CrdsData::LegacyVersion(_)
+                | CrdsData::Version(_)
+                | CrdsData::NodeCapabilities(_)
+                | CrdsData::ValidatorInfo(_)
+                | CrdsData::SnapshotHashes(_)
+                | CrdsData::AccountsHashes(_)
+        )
+    }
+
+    /// Save the recent peer contact info, version and snapshot/accounts hashes in the CRDS
+    /// table to `path`. Returns the number of values saved.
+    pub fn save_crds_cache(&self, path: &Path) -> std::io::Result<usize> {
+        let now = timestamp();
+        let values: Vec<CrdsValue> = self
+            .gossip
+            .read()
+            .unwrap()
+            .crds
+            .table
+            .values()
+            .map(|versioned| &versioned.value)
+            .filter(|value| {
+                value.pubkey() != self.id
+                    && Self::is_crds_cache_value(value)
+                    && now.saturating_sub(value.wallclock()) < CFG.CRDS_CACHE_MAX_AGE_MS
+            })
+            .cloned()
+            .collect();
+        let data = serialize(&values)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+        let tmp_path = path.with_extension("tmp");
+        std::fs::write(&tmp_path, data)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(values.len())
+    }
+
+    /// Insert the values saved by `save_crds_cache` into the CRDS table, discarding any that
+    /// fail signature verification or are older than `CRDS_CACHE_MAX_AGE_MS`. Returns the
+    /// number of values inserted.
+    pub fn load_crds_cache(&self, path: &Path) -> std::io::Result<usize> {
+        let data = std::fs::read(path)?;
+        let values: Vec<CrdsValue> = bincode::deserialize(&data)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        let now = timestamp();
+        let mut gossip = self.gossip.write().unwrap();
+        let num_inserted = values
+            .into_iter()
+            .filter(|value| {
+                value.pubkey() != self.id
+                    && Self::is_crds_cache_value(value)
+                    && now.saturating_sub(value.wallclock()) < CFG.CRDS_CACHE_MAX_AGE_MS
+                    && value.verify()
+            })
+            .filter(|value| gossip.crds.insert(value.clone(), now).is_ok())
+            .count();
+        Ok(num_inserted)
+    }
+
+    fn maybe_save_crds_cache(&self, last_save: &mut u64) {
+        let now = timestamp();
+        if now.saturating_sub(*last_save) < CFG.CRDS_CACHE_SAVE_INTERVAL_MS {
+            return;
+        }
+        *last_save = now;
+        let crds_cache_path = self.crds_cache_path.read().unwrap().clone();
+        if let Some(crds_cache_path) = crds_cache_path {
+            match self.save_crds_cache(&crds_cache_path) {
+                Ok(num_saved) => {
+                    debug!("Saved {} gossip values to {:?}", num_saved, crds_cache_path)
+                }
+                Err(err) => warn!(
+                    "Unable to save gossip cache to {:?}: {}",
+                    crds_cache_path, err
+                ),
+            }
+        }
+    }
+
+    /// Gossip counters of each peer
+    pub fn gossip_peer_stats(&self) -> &GossipPeerStatsTable {
+        &self.stats.peers
+    }
+
+    /// Bytes exchanged with each peer over repair and retransmit
+    pub fn peer_bandwidth(&self) -> &PeerBandwidthTracker {
+        &self.peer_bandwidth
+    }
+
+    /// Divergence points found for the slots replay marked dead
+    pub fn ancestor_hashes_reports(&self) -> &AncestorHashesReports {
+        &self.ancestor_hashes_reports
+    }
+
+    fn maybe_roll_peer_bandwidth(&self) {
+        if self.peer_bandwidth.maybe_roll() {
+            // Shreds arrive from a peer's tvu or serve_repair sockets
+            let peer_addrs: Vec<_> = self
+                .all_tvu_peers()
+                .into_iter()
+                .flat_map(|ci| vec![(ci.tvu.ip(), ci.id), (ci.serve_repair.ip(), ci.id)])
+                .collect();
+            self.peer_bandwidth.set_peer_addrs(peer_addrs);
+        }
+    }
+
     pub fn id(&self) -> Pubkey {
         self.id
     }
@@ -691,6 +1074,7 @@ impl ClusterInfo {
         let mut different_shred_nodes = 0;
         let my_pubkey = self.id();
         let my_shred_version = self.my_shred_version();
+        let rtts = self.peer_rtts();
         let nodes: Vec<_> = self
             .all_peers()
             .into_iter()
@@ -717,7 +1101,7 @@ impl ClusterInfo {
                     }
                     let ip_addr = node.gossip.ip();
                     Some(format!(
-                        "{:15} {:2}| {:5} | {:44} |{:^9}| {:5}| {:5}| {:5}| {:5}| {:5}| {:5}| {:5}| {}\n",
+                        "{:15} {:2}| {:5} | {:44} |{:^9}| {:5}| {:5}| {:5}| {:5}| {:5}| {:5}| {:5}| {:8}| {}\n",
                         if ContactInfo::is_valid_address(&node.gossip) {
                             ip_addr.to_string()
                         } else {
@@ -739,6 +1123,9 @@ impl ClusterInfo {
                         addr_to_string(&ip_addr, &node.repair),
                         addr_to_string(&ip_addr, &node.serve_repair),
                         node.shred_version,
+                        rtts.get(&node.id)
+                            .map(|rtt| rtt.as_millis().to_string())
+                            .unwrap_or_else(|| "-".to_string()),
                     ))
                 }
             })
@@ -746,9 +1133,9 @@ impl ClusterInfo {
 
         format!(
             "IP Address        |Age(ms)| Node identifier                              \
-             | Version |Gossip| TPU  |TPUfwd| TVU  |TVUfwd|Repair|ServeR|ShredVer\n\
+             | Version |Gossip| TPU  |TPUfwd| TVU  |TVUfwd|Repair|ServeR|ShredVer|RTT(ms)\n\
              ------------------+-------+----------------------------------------------+---------+\
-             ------+------+------+------+------+------+------+--------\n\
+             ------+------+------+------+------+------+------+---------+-------\n\
              {}\
              Nodes: {}{}{}",
             nodes.join(""),
@@ -769,6 +1156,45 @@ impl ClusterInfo {
         )
     }
 
+    /// Smoothed round trip time of the gossip pings to the peer, if it answered any
+    pub fn peer_rtt(&self, pubkey: &Pubkey) -> Option<Duration> {
+        let ping_cache = self.ping_cache.read().unwrap();
+        ping_cache.rtt(pubkey).map(|(_, rtt)| rtt)
+    }
+
+    pub fn peer_rtts(&self) -> HashMap<Pubkey, Duration> {
+        let ping_cache = self.ping_cache.read().unwrap();
+        ping_cache
+            .rtts()
+            .map(|(pubkey, rtt)| (*pubkey, rtt))
+            .collect()
+    }
+
+    /// Scales down the selection weights of the peers slower to answer gossip pings than the
+    /// fastest one, in proportion, so that low latency peers are preferred. The weights are
+    /// paired with the index of their peer; peers without measurements keep their weight.
+    pub fn prefer_low_latency_peers(&self, peers: &[ContactInfo], weights: &mut [(u64, usize)]) {
+        let ping_cache = self.ping_cache.read().unwrap();
+        let rtts: Vec<_> = peers
+            .iter()
+            .map(|peer| {
+                ping_cache
+                    .rtt(&peer.id)
+                    .map(|(_, rtt)| rtt.as_micros().max(1))
+            })
+            .collect();
+        drop(ping_cache);
+        let min_rtt = match rtts.iter().flatten().min() {
+            Some(min_rtt) => *min_rtt,
+            None => return,
+        };
+        for (weight, index) in weights.iter_mut() {
+            if let Some(rtt) = rtts[*index] {
+                *weight = ((u128::from(*weight) * min_rtt / rtt) as u64).max(1);
+            }
+        }
+    }
+
     pub fn push_lowest_slot(&self, id: Pubkey, min: Slot) {
         let now = timestamp();
         let last = self
@@ -854,6 +1280,87 @@ impl ClusterInfo {
         }
     }
 
+    /// Publishes the slots of `update` missing from the EpochSlotsShard values of this node, in
+    /// place of `push_epoch_slots` once the sharded_epoch_slots feature is active: the latest
+    /// shard is filled up, then shards of the next generations take the remaining slots. The
+    /// first generation of a node is the time it first publishes shards, later than the
+    /// generations of its shards before a restart.
+    pub fn push_epoch_slots_shards(&self, update: &[Slot]) {
+        let self_id = self.id();
+        let mut shards = self.get_node_epoch_slots_shards(&self_id);
+        let min_update_slot = update.iter().min().copied().unwrap_or(0);
+        let published: HashSet<Slot> = shards
+            .iter()
+            .flat_map(|shard| shard.epoch_slots.to_slots(min_update_slot))
+            .collect();
+        let update: Vec<Slot> = update
+            .iter()
+            .filter(|slot| !published.contains(slot))
+            .copied()
+            .collect();
+        if update.is_empty() {
+            return;
+        }
+        let min_slot: Slot = shards
+            .iter()
+            .filter_map(|shard| shard.epoch_slots.first_slot())
+            .min()
+            .unwrap_or(0);
+        let max_slot: Slot = update.iter().max().cloned().unwrap_or(0);
+        let total_slots = max_slot as isize - min_slot as isize;
+        // WARN if CRDS is not storing at least a full epoch worth of slots
+        if *DEFAULT_SLOTS_PER_EPOCH as isize > total_slots
+            && crds_value::MAX_EPOCH_SLOTS as usize <= shards.len()
+        {
+            inc_new_counter_warn!("cluster_info-epoch_slots-filled", 1);
+            warn!(
+                "EPOCH_SLOTS are filling up FAST {}/{}",
+                total_slots,
+                shards.len()
+            );
+        }
+        let now = timestamp();
+        let mut shard = shards
+            .pop()
+            .unwrap_or_else(|| EpochSlotsShard::new(self_id, now, now));
+        let mut num = 0;
+        while num < update.len() {
+            let n = shard.fill(&update[num..], now);
+            if n > 0 {
+                let entry = CrdsValue::new_signed(
+                    CrdsData::EpochSlotsShard(shard.index(), shard.clone()),
+                    &self.keypair,
+                );
+                self.local_message_pending_push_queue
+                    .write()
+                    .unwrap()
+                    .push((entry, now));
+            } else if shard.epoch_slots.slots.is_empty() {
+                break;
+            }
+            num += n;
+            shard = EpochSlotsShard::new(self_id, shard.generation + 1, now);
+        }
+    }
+
+    /// The current EpochSlotsShard values of the node, ordered by generation
+    pub fn get_node_epoch_slots_shards(&self, pubkey: &Pubkey) -> Vec<EpochSlotsShard> {
+        let shards = {
+            let gossip =
+                self.time_gossip_read_lock("lookup_epoch_slots", &self.stats.epoch_slots_lookup);
+            (0..crds_value::MAX_EPOCH_SLOTS)
+                .filter_map(|ix| {
+                    gossip
+                        .crds
+                        .lookup(&CrdsValueLabel::EpochSlotsShard(ix, *pubkey))
+                })
+                .filter_map(CrdsValue::epoch_slots_shard)
+                .cloned()
+                .collect()
+        };
+        epoch_slots::current_shards(shards)
+    }
+
     fn time_gossip_read_lock<'a>(
         &'a self,
         label: &'static str,
@@ -862,6 +1369,72 @@ impl ClusterInfo {
         GossipReadLock::new(self.gossip.read().unwrap(), label, counter)
     }
 
+    /// Drops the incoming values which unstaked origins may not add to the table, see
+    /// `CrdsInsertPolicy`
+    fn filter_inserts<'a, I>(&self, values: I, stakes: &HashMap<Pubkey, u64>)
+    where
+        I: IntoIterator<Item = &'a mut Vec<CrdsValue>>,
+    {
+        let gossip = self.time_gossip_read_lock("filter_inserts", &self.stats.filter_inserts);
+        let mut insert_policy = gossip.insert_policy(stakes);
+        for values in values {
+            insert_policy.filter(values);
+        }
+        let InsertPolicyStats {
+            origin_limit,
+            unstaked_limit,
+            table_budget,
+            validator_info_limit,
+        } = insert_policy.stats;
+        self.stats
+            .reject_insert_origin_limit
+            .add(origin_limit as u64);
+        self.stats
+            .reject_insert_unstaked_limit
+            .add(unstaked_limit as u64);
+        self.stats
+            .reject_insert_table_budget
+            .add(table_budget as u64);
+        self.stats
+            .reject_insert_validator_info_limit
+            .add(validator_info_limit as u64);
+    }
+
+    /// Looks for another instance of this node among the incoming values. Running two nodes with
+    /// the same identity corrupts their towers and produces duplicate blocks, so the instance
+    /// started first stops voting, see `is_duplicate_instance`.
+    fn check_duplicate_instance<'a, I>(&self, values: I)
+    where
+        I: IntoIterator<Item = &'a CrdsValue>,
+    {
+        if self.is_duplicate_instance() {
+            return;
+        }
+        if let Some(value) = values
+            .into_iter()
+            .find(|value| self.instance.check_duplicate(value))
+        {
+            self.duplicate_instance.store(true, Ordering::Relaxed);
+            error!(
+                "Another instance of {} is running: {:?}, this instance started at {}. \
+                 Voting is disabled, this validator should be shut down",
+                self.id(),
+                value.data,
+                self.instance.wallclock,
+            );
+            datapoint_error!(
+                "cluster_info-duplicate_instance",
+                ("id", self.id().to_string(), String),
+            );
+        }
+    }
+
+    /// Whether another instance of this node, started later, was seen in gossip. The node must
+    /// not vote anymore.
+    pub fn is_duplicate_instance(&self) -> bool {
+        self.duplicate_instance.load(Ordering::Relaxed)
+    }
+
     fn time_gossip_write_lock<'a>(
         &'a self,
         label: &'static str,
@@ -922,13 +1495,49 @@ impl ClusterInfo {
             .push((entry, now));
     }
 
-    pub fn send_vote(&self, vote: &Transaction) -> Result<()> {
-        let tpu = self.my_contact_info().tpu;
+    /// Returns the wallclock of the latest vote this node pushed to gossip, if any
+    pub fn my_last_vote_wallclock(&self) -> Option<u64> {
+        let self_id = self.id();
+        let gossip = self.gossip.read().unwrap();
+        (0..crds_value::MAX_VOTES)
+            .filter_map(|ix| gossip.crds.lookup(&CrdsValueLabel::Vote(ix, self_id)))
+            .filter_map(|value| value.vote())
+            .map(|vote| vote.wallclock)
+            .max()
+    }
+
+    /// Sends the vote to the tpu_vote port of `leader` if it advertises one, or else to the TPU
+    /// of this node, which forwards it to the leader
+    pub fn send_vote(&self, vote: &Transaction, leader: Option<&Pubkey>) -> Result<()> {
+        let addr = leader
+            .and_then(|leader| self.lookup_tpu_vote(leader))
+            .unwrap_or_else(|| self.my_contact_info().tpu);
         let buf = serialize(vote)?;
-        self.socket.send_to(&buf, &tpu)?;
+        self.socket.send_to(&buf, &addr)?;
         Ok(())
     }
 
+    /// The address the node accepts vote transactions on, if it advertises one
+    pub fn lookup_tpu_vote(&self, pubkey: &Pubkey) -> Option<SocketAddr> {
+        let port = self
+            .get_node_capabilities(pubkey)?
+            .get(CAPABILITY_TPU_VOTE)?
+            .parse()
+            .ok()?;
+        let tpu = self.lookup_contact_info(pubkey, |ci| ci.tpu)?;
+        let tpu_vote = SocketAddr::new(tpu.ip(), port);
+        if ContactInfo::is_valid_address(&tpu_vote) {
+            Some(tpu_vote)
+        } else {
+            None
+        }
+    }
+
+    /// Address of the socket votes are sent to the leader from
+    pub fn vote_socket_addr(&self) -> SocketAddr {
+        self.socket.local_addr().unwrap()
+    }
+
     /// Get votes in the crds
     /// * since - The timestamp of when the vote inserted must be greater than
     /// since. This allows the bank to query for new votes only.
@@ -1019,11 +1628,12 @@ impl ClusterInfo {
             .map(|x| map(x.value.lowest_slot().unwrap(), x.insert_timestamp))
     }
 
+    /// EpochSlots values, and the slots of the EpochSlotsShard values not yet replaced, inserted
+    /// after `since`
     pub fn get_epoch_slots_since(&self, since: Option<u64>) -> (Vec<EpochSlots>, Option<u64>) {
-        let vals: Vec<_> = self
-            .gossip
-            .read()
-            .unwrap()
+        let gossip = self.gossip.read().unwrap();
+        let mut latest_generations: HashMap<Pubkey, Option<u64>> = HashMap::new();
+        let vals: Vec<_> = gossip
             .crds
             .table
             .values()
@@ -1032,7 +1642,22 @@ impl ClusterInfo {
                     .map(|since| x.insert_timestamp > since)
                     .unwrap_or(true)
             })
-            .filter_map(|x| Some((x.value.epoch_slots()?.clone(), x.insert_timestamp)))
+            .filter_map(|x| {
+                let epoch_slots = match x.value.epoch_slots_shard() {
+                    None => x.value.epoch_slots()?,
+                    Some(shard) => {
+                        let from = &shard.epoch_slots.from;
+                        let latest = *latest_generations
+                            .entry(*from)
+                            .or_insert_with(|| gossip.crds.epoch_slots_generation(from));
+                        if latest.map_or(false, |latest| shard.is_stale(latest)) {
+                            return None;
+                        }
+                        &shard.epoch_slots
+                    }
+                };
+                Some((epoch_slots.clone(), x.insert_timestamp))
+            })
             .collect();
         let max = vals.iter().map(|x| x.1).max().or(since);
         let vec = vals.into_iter().map(|x| x.0).collect();
@@ -1066,6 +1691,156 @@ impl ClusterInfo {
         }
     }
 
+    /// Advertise the optional capabilities of this node, replacing the ones advertised before
+    pub fn push_node_capabilities(&self, capabilities: BTreeMap<String, String>) {
+        let capabilities = NodeCapabilities::new(self.id(), capabilities);
+        if let Err(err) = capabilities.sanitize() {
+            warn!("node capabilities too large, ignored: {:?}", err);
+            return;
+        }
+        self.set_network_namespace(
+            capabilities
+                .get(CAPABILITY_NETWORK_NAMESPACE)
+                .map(String::from),
+        );
+        let message = CrdsData::NodeCapabilities(capabilities);
+        self.push_message(CrdsValue::new_signed(message, &self.keypair));
+    }
+
+    /// Tell apart the peers of other networks before the capabilities of this node are pushed
+    pub fn set_network_namespace(&self, network_namespace: Option<String>) {
+        *self.network_namespace.write().unwrap() = network_namespace;
+    }
+
+    /// Network namespace this node advertises, if any
+    pub fn my_network_namespace(&self) -> Option<String> {
+        self.network_namespace.read().unwrap().clone()
+    }
+
+    /// Whether the node is on the network of this one as far as their network namespaces tell.
+    /// Nodes which do not advertise a namespace are assumed to be, so that namespaces can be
+    /// rolled out one node at a time.
+    pub fn same_network_namespace(&self, pubkey: &Pubkey) -> bool {
+        match self.my_network_namespace() {
+            None => true,
+            Some(namespace) => {
+                Self::in_network_namespace(&self.gossip.read().unwrap().crds, &namespace, pubkey)
+            }
+        }
+    }
+
+    fn in_network_namespace(crds: &Crds, namespace: &str, pubkey: &Pubkey) -> bool {
+        crds.lookup(&CrdsValueLabel::NodeCapabilities(*pubkey))
+            .and_then(CrdsValue::node_capabilities)
+            .and_then(|capabilities| capabilities.get(CAPABILITY_NETWORK_NAMESPACE))
+            .map(|peer_namespace| peer_namespace == namespace)
+            .unwrap_or(true)
+    }
+
+    /// Capabilities advertised by the node, if any
+    pub fn get_node_capabilities(&self, pubkey: &Pubkey) -> Option<NodeCapabilities> {
+        self.gossip
+            .read()
+            .unwrap()
+            .crds
+            .table
+            .get(&CrdsValueLabel::NodeCapabilities(*pubkey))
+            .and_then(|x| x.value.node_capabilities())
+            .cloned()
+    }
+
+    /// Whether the node advertises the capability `name`
+    pub fn node_has_capability(&self, pubkey: &Pubkey, name: &str) -> bool {
+        self.get_node_capabilities(pubkey)
+            .map(|capabilities| capabilities.has(name))
+            .unwrap_or(false)
+    }
+
+    /// Whether the node accepts the gossip messages compressed by this node
+    pub fn accepts_compressed_gossip(&self, pubkey: &Pubkey) -> bool {
+        self.get_node_capabilities(pubkey)
+            .and_then(|capabilities| {
+                capabilities
+                    .get(CAPABILITY_GOSSIP_COMPRESSION)
+                    .map(|codec| codec == *GOSSIP_COMPRESSION_CODEC)
+            })
+            .unwrap_or(false)
+    }
+
+    // Gossip is compressed only between the nodes which both enabled it
+    fn compress_gossip_to(&self, pubkey: &Pubkey) -> bool {
+        self.accepts_compressed_gossip(&self.id()) && self.accepts_compressed_gossip(pubkey)
+    }
+
+    /// Publish the name, website and icon of this validator, replacing the ones published before.
+    /// Peers drop updates sooner than `VALIDATOR_INFO_MIN_UPDATE_INTERVAL_MS` after the last one,
+    /// and the info of unstaked validators.
+    pub fn push_validator_info(
+        &self,
+        name: String,
+        website: Option<String>,
+        icon_url: Option<String>,
+    ) {
+        let info = ValidatorInfo::new(self.id(), name, website, icon_url);
+        if let Err(err) = info.sanitize() {
+            warn!("validator info too large, ignored: {:?}", err);
+            return;
+        }
+        let message = CrdsData::ValidatorInfo(info);
+        self.push_message(CrdsValue::new_signed(message, &self.keypair));
+    }
+
+    /// Name, website and icon published by the validator, if any
+    pub fn get_validator_info(&self, pubkey: &Pubkey) -> Option<ValidatorInfo> {
+        self.gossip
+            .read()
+            .unwrap()
+            .crds
+            .table
+            .get(&CrdsValueLabel::ValidatorInfo(*pubkey))
+            .and_then(|x| x.value.validator_info())
+            .cloned()
+    }
+
+    /// Advertise the hard fork this validator is configured to restart from, and the shred
+    /// version of the cluster after the restart
+    pub fn push_planned_hard_fork(&self, slot: Slot, shred_version: u16) {
+        let message =
+            CrdsData::PlannedHardFork(PlannedHardFork::new(self.id(), slot, shred_version));
+        self.push_message(CrdsValue::new_signed(message, &self.keypair));
+    }
+
+    /// Hard forks advertised by the nodes in gossip, this one included
+    pub fn get_planned_hard_forks(&self) -> Vec<PlannedHardFork> {
+        self.gossip
+            .read()
+            .unwrap()
+            .crds
+            .table
+            .values()
+            .filter_map(|x| x.value.planned_hard_fork())
+            .cloned()
+            .collect()
+    }
+
+    /// Advertise the bank hash of the slot this validator waits for a supermajority at
+    pub fn push_restart_bank_hash(&self, slot: Slot, bank_hash: Hash) {
+        let message = CrdsData::RestartBankHash(RestartBankHash::new(self.id(), slot, bank_hash));
+        self.push_message(CrdsValue::new_signed(message, &self.keypair));
+    }
+
+    /// Slot and bank hash the validator advertised while waiting for a supermajority, if any
+    pub fn get_restart_bank_hash(&self, pubkey: &Pubkey) -> Option<(Slot, Hash)> {
+        self.gossip
+            .read()
+            .unwrap()
+            .crds
+            .table
+            .get(&CrdsValueLabel::RestartBankHash(*pubkey))
+            .and_then(|x| x.value.restart_bank_hash())
+            .map(|bank_hash| (bank_hash.slot, bank_hash.bank_hash))
+    }
+
     /// all validators that have a valid rpc port regardless of `shred_version`.
     pub fn all_rpc_peers(&self) -> Vec<ContactInfo> {
         self.gossip
@@ -1123,9 +1898,12 @@ impl ClusterInfo {
             .collect()
     }
 
-    /// all validators that have a valid tvu port and are on the same `shred_version`.
+    /// all validators that have a valid tvu port and are on the same `shred_version` and
+    /// network namespace.
     pub fn tvu_peers(&self) -> Vec<ContactInfo> {
-        self.time_gossip_read_lock("tvu_peers", &self.stats.tvu_peers)
+        let namespace = self.my_network_namespace();
+        let gossip = self.time_gossip_read_lock("tvu_peers", &self.stats.tvu_peers);
+        gossip
             .crds
             .table
             .values()
@@ -1134,6 +1912,9 @@ impl ClusterInfo {
                 ContactInfo::is_valid_address(&x.tvu)
                     && x.id != self.id()
                     && x.shred_version == self.my_shred_version()
+                    && namespace.as_ref().map_or(true, |namespace| {
+                        Self::in_network_namespace(&gossip.crds, namespace, &x.id)
+                    })
             })
             .cloned()
             .collect()
@@ -1141,7 +1922,9 @@ impl ClusterInfo {
 
     /// all peers that have a valid tvu
     pub fn retransmit_peers(&self) -> Vec<ContactInfo> {
-        self.time_gossip_read_lock("retransmit_peers", &self.stats.retransmit_peers)
+        let namespace = self.my_network_namespace();
+        let gossip = self.time_gossip_read_lock("retransmit_peers", &self.stats.retransmit_peers);
+        gossip
             .crds
             .table
             .values()
@@ -1149,6 +1932,9 @@ impl ClusterInfo {
             .filter(|x| {
                 x.id != self.id()
                     && x.shred_version == self.my_shred_version()
+                    && namespace.as_ref().map_or(true, |namespace| {
+                        Self::in_network_namespace(&gossip.crds, namespace, &x.id)
+                    })
                     && ContactInfo::is_valid_address(&x.tvu)
                     && ContactInfo::is_valid_address(&x.tvu_forwards)
             })
@@ -1173,7 +1959,8 @@ impl ClusterInfo {
                     }
             })
             .collect();
-        self.stats.repair_peers.add_measure(&mut time);
+        time.stop();
+        self.stats.repair_peers.add(time.as_us());
         ret
     }
 
@@ -1542,6 +2329,27 @@ impl ClusterInfo {
         messages
     }
 
+    /// Splits `values` into messages fitting in a packet, built with `compressed_message` out of
+    /// compressed values if `compress`, and with `message` out of the values left uncompressed
+    fn new_gossip_messages(
+        values: Vec<CrdsValue>,
+        compress: bool,
+        message: impl Fn(Vec<CrdsValue>) -> Protocol,
+        compressed_message: impl Fn(Vec<u8>) -> Protocol,
+    ) -> Vec<Protocol> {
+        let (payloads, values) = if compress {
+            let max_size = *MAX_PROTOCOL_PAYLOAD_SIZE as usize - std::mem::size_of::<u64>();
+            gossip_compression::split_compressed(values, max_size)
+        } else {
+            (vec![], values)
+        };
+        payloads
+            .into_iter()
+            .map(compressed_message)
+            .chain(Self::split_gossip_messages(values).into_iter().map(message))
+            .collect()
+    }
+
     fn new_pull_requests(
         &self,
         thread_pool: &ThreadPool,
@@ -1572,9 +2380,7 @@ impl ClusterInfo {
                 .collect()
         };
         self.append_entrypoint_to_pulls(thread_pool, &mut pulls);
-        self.stats
-            .new_pull_requests_count
-            .add_relaxed(pulls.len() as u64);
+        self.stats.new_pull_requests_count.add(pulls.len() as u64);
         // There are at most 2 unique peers here: The randomly
         // selected pull peer, and possibly also the entrypoint.
         let peers: Vec<Pubkey> = pulls.iter().map(|(peer, _, _, _)| *peer).dedup().collect();
@@ -1585,11 +2391,19 @@ impl ClusterInfo {
                 gossip.mark_pull_request_creation_time(&peer, now);
             }
         }
-        pulls
+        let pulls: Vec<_> = pulls
             .into_iter()
-            .map(|(_, filter, gossip, self_info)| {
-                (gossip, Protocol::PullRequest(filter, self_info))
+            .map(|(peer, filter, gossip, self_info)| {
+                (peer, gossip, Protocol::PullRequest(filter, self_info))
             })
+            .collect();
+        self.stats.peers.update(
+            pulls.iter().map(|(peer, _, protocol)| (*peer, protocol)),
+            |stats, protocol| stats.bytes_sent += serialized_size(protocol).unwrap_or(0),
+        );
+        pulls
+            .into_iter()
+            .map(|(_, gossip, protocol)| (gossip, protocol))
             .collect()
     }
     fn drain_push_queue(&self) -> Vec<(CrdsValue, u64)> {
@@ -1611,22 +2425,34 @@ impl ClusterInfo {
             .into_iter()
             .filter_map(|(peer, messages)| {
                 let peer_label = CrdsValueLabel::ContactInfo(peer);
-                self.time_gossip_read_lock("push_req_lookup", &self.stats.new_push_requests2)
+                let addr = self
+                    .time_gossip_read_lock("push_req_lookup", &self.stats.new_push_requests2)
                     .crds
                     .lookup(&peer_label)
                     .and_then(CrdsValue::contact_info)
-                    .map(|p| (p.gossip, messages))
+                    .map(|p| p.gossip)?;
+                Some((peer, addr, self.compress_gossip_to(&peer), messages))
             })
-            .flat_map(|(peer, msgs)| {
-                Self::split_gossip_messages(msgs)
-                    .into_iter()
-                    .map(move |payload| (peer, Protocol::PushMessage(self_id, payload)))
+            .flat_map(|(peer, addr, compress, msgs)| {
+                Self::new_gossip_messages(
+                    msgs,
+                    compress,
+                    |payload| Protocol::PushMessage(self_id, payload),
+                    |payload| Protocol::CompressedPushMessage(self_id, payload),
+                )
+                .into_iter()
+                .map(move |protocol| (peer, addr, protocol))
             })
             .collect();
-        self.stats
-            .new_push_requests_num
-            .add_relaxed(messages.len() as u64);
+        self.stats.new_push_requests_num.add(messages.len() as u64);
+        self.stats.peers.update(
+            messages.iter().map(|(peer, _, protocol)| (*peer, protocol)),
+            |stats, protocol| stats.bytes_sent += serialized_size(protocol).unwrap_or(0),
+        );
         messages
+            .into_iter()
+            .map(|(_, addr, protocol)| (addr, protocol))
+            .collect()
     }
 
     // Generate new push and pull requests
@@ -1722,10 +2548,60 @@ impl ClusterInfo {
             }
         };
         let timeouts = self.gossip.read().unwrap().make_timeouts(stakes, timeout);
+        let ttls = CrdsTtls {
+            vote: CFG.CRDS_VOTE_TIMEOUT_MS,
+            contact_info: CFG.CRDS_CONTACT_INFO_TIMEOUT_MS,
+        };
         let num_purged = self
             .time_gossip_write_lock("purge", &self.stats.purge)
-            .purge(thread_pool, timestamp(), &timeouts);
+            .purge(thread_pool, timestamp(), &timeouts, &ttls);
         inc_new_counter_info!("cluster_info-purge-count", num_purged);
+        let num_evicted = self
+            .time_gossip_write_lock("trim", &self.stats.trim)
+            .trim(CFG.CRDS_MAX_TABLE_SIZE, stakes);
+        inc_new_counter_info!("cluster_info-trim-count", num_evicted);
+    }
+
+    // Using the root bank so that a feature enabled on a minority fork does not roll back
+    fn is_feature_active(bank_forks: &Option<Arc<RwLock<BankForks>>>, feature_id: &Pubkey) -> bool {
+        bank_forks.as_ref().map_or(false, |bank_forks| {
+            bank_forks
+                .read()
+                .unwrap()
+                .root_bank()
+                .feature_set
+                .is_active(feature_id)
+        })
+    }
+
+    /// Has the gossip thread call `push` once the feature `feature_id` is active on the root bank.
+    /// Nodes which do not know a CrdsData variant fail to deserialize the gossip messages holding
+    /// one, so new variants are published only once the cluster runs a release knowing them.
+    pub fn push_once_active<F>(&self, feature_id: Pubkey, push: F)
+    where
+        F: FnOnce(&ClusterInfo) + Send + 'static,
+    {
+        self.gated_pushes
+            .lock()
+            .unwrap()
+            .push((feature_id, Box::new(push)));
+    }
+
+    fn handle_gated_pushes(&self, bank_forks: &Option<Arc<RwLock<BankForks>>>) {
+        let ready = {
+            let mut gated_pushes = self.gated_pushes.lock().unwrap();
+            if gated_pushes.is_empty() {
+                return;
+            }
+            let (ready, pending): (Vec<GatedPush>, Vec<GatedPush>) = gated_pushes
+                .drain(..)
+                .partition(|(feature_id, _)| Self::is_feature_active(bank_forks, feature_id));
+            *gated_pushes = pending;
+            ready
+        };
+        for (_, push) in ready {
+            push(self);
+        }
     }
 
     /// randomly pick a node and ask them for updates asynchronously
@@ -1747,11 +2623,20 @@ impl ClusterInfo {
             .spawn(move || {
                 let mut last_push = timestamp();
                 let mut last_contact_info_trace = timestamp();
+                let mut last_crds_cache_save = timestamp();
                 let mut adopt_shred_version = self.my_shred_version() == 0;
                 let recycler = PacketsRecycler::default();
 
                 let message = CrdsData::Version(Version::new(self.id()));
                 self.push_message(CrdsValue::new_signed(message, &self.keypair));
+                // Nodes which do not know the NodeInstance variant fail to deserialize the gossip
+                // messages holding one. The instance keeps the wallclock of when it started, so
+                // it is published at startup only, by the nodes started once the feature is
+                // active
+                if Self::is_feature_active(&bank_forks, &feature_set::gossip_node_instance::id()) {
+                    let message = CrdsData::NodeInstance(self.instance.clone());
+                    self.push_message(CrdsValue::new_signed(message, &self.keypair));
+                }
                 let mut generate_pull_requests = true;
                 loop {
                     let start = timestamp();
@@ -1789,6 +2674,12 @@ impl ClusterInfo {
 
                     self.handle_adopt_shred_version(&mut adopt_shred_version);
 
+                    self.handle_gated_pushes(&bank_forks);
+
+                    self.maybe_save_crds_cache(&mut last_crds_cache_save);
+
+                    self.maybe_roll_peer_bandwidth();
+
                     //TODO: possibly tune this parameter
                     //we saw a deadlock passing an self.read().unwrap().timeout into sleep
                     if start - last_push > GOSSIP_PULL_CFG.CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS / 2 {
@@ -1810,10 +2701,13 @@ impl ClusterInfo {
         if messages.is_empty() {
             return;
         }
+        self.stats.prune_message_count.add(messages.len() as u64);
         self.stats
-            .prune_message_count
-            .add_relaxed(messages.len() as u64);
-        self.stats.prune_message_len.add_relaxed(
+            .peers
+            .update(messages.iter().map(|(from, _)| (*from, ())), |stats, ()| {
+                stats.prunes_received += 1
+            });
+        self.stats.prune_message_len.add(
             messages
                 .iter()
                 .map(|(_, data)| data.prunes.len() as u64)
@@ -1821,6 +2715,7 @@ impl ClusterInfo {
         );
         let mut prune_message_timeout = 0;
         let mut bad_prune_destination = 0;
+        let mut errors = vec![];
         {
             let mut gossip =
                 self.time_gossip_write_lock("process_prune", &self.stats.process_prune);
@@ -1833,16 +2728,21 @@ impl ClusterInfo {
                     data.wallclock,
                     now,
                 ) {
-                    Err(CrdsGossipError::PruneMessageTimeout) => {
+                    Err(err @ CrdsGossipError::PruneMessageTimeout) => {
                         prune_message_timeout += 1;
+                        errors.push((from, err));
                     }
-                    Err(CrdsGossipError::BadPruneDestination) => {
+                    Err(err @ CrdsGossipError::BadPruneDestination) => {
                         bad_prune_destination += 1;
+                        errors.push((from, err));
                     }
                     _ => (),
                 }
             }
         }
+        self.stats.peers.update(errors, |stats, err| {
+            stats.last_error = Some(format!("{:?}", err));
+        });
         if prune_message_timeout != 0 {
             inc_new_counter_debug!("cluster_info-prune_message_timeout", prune_message_timeout);
         }
@@ -1878,11 +2778,12 @@ impl ClusterInfo {
                         false
                     }
                     Some(caller) => {
-                        if self_shred_version != 0
+                        if (self_shred_version != 0
                             && caller.shred_version != 0
-                            && caller.shred_version != self_shred_version
+                            && caller.shred_version != self_shred_version)
+                            || !self.same_network_namespace(&caller.id)
                         {
-                            self.stats.skip_pull_shred_version.add_relaxed(1);
+                            self.stats.skip_pull_shred_version.add(1);
                             false
                         } else {
                             true
@@ -1897,9 +2798,7 @@ impl ClusterInfo {
                 .collect()
         });
         if !requests.is_empty() {
-            self.stats
-                .pull_requests_count
-                .add_relaxed(requests.len() as u64);
+            self.stats.pull_requests_count.add(requests.len() as u64);
             let response = self.handle_pull_requests(recycler, requests, stakes, feature_set);
             if !response.is_empty() {
                 let _ = response_sender.send(response);
@@ -1947,9 +2846,7 @@ impl ClusterInfo {
                 packets.packets.push(ping);
             }
             if !check {
-                self.stats
-                    .pull_request_ping_pong_check_failed_count
-                    .add_relaxed(1)
+                self.stats.pull_request_ping_pong_check_failed_count.add(1)
             }
             check || !check_enabled
         };
@@ -1974,8 +2871,10 @@ impl ClusterInfo {
         feature_set: Option<&FeatureSet>,
     ) -> Packets {
         let mut time = Measure::start("handle_pull_requests");
+        let mut callers: Vec<_> = requests.iter().map(|r| r.caller.clone()).collect();
+        self.filter_inserts(std::iter::once(&mut callers), stakes);
         self.time_gossip_write_lock("process_pull_reqs", &self.stats.process_pull_requests)
-            .process_pull_requests(requests.iter().map(|r| r.caller.clone()), timestamp());
+            .process_pull_requests(callers, timestamp());
         self.update_data_budget(stakes.len());
         let mut packets = Packets::new_with_recycler(recycler.clone(), 64, "handle_pull_requests");
         let (caller_and_filters, addrs): (Vec<_>, Vec<_>) = {
@@ -1990,6 +2889,19 @@ impl ClusterInfo {
         };
         let now = timestamp();
         let self_id = self.id();
+        let callers: Vec<_> = caller_and_filters
+            .iter()
+            .map(|(caller, _)| caller.pubkey())
+            .collect();
+        self.stats
+            .peers
+            .update(callers.iter().map(|caller| (*caller, ())), |stats, ()| {
+                stats.pull_requests_served += 1
+            });
+        let compress: Vec<_> = callers
+            .iter()
+            .map(|caller| self.compress_gossip_to(caller))
+            .collect();
 
         let pull_responses = self
             .time_gossip_read_lock(
@@ -2001,7 +2913,12 @@ impl ClusterInfo {
         let pull_responses: Vec<_> = pull_responses
             .into_iter()
             .zip(addrs.into_iter())
-            .filter(|(response, _)| !response.is_empty())
+            .zip(compress.into_iter())
+            .zip(callers.into_iter())
+            .map(|(((response, from_addr), compress), caller)| {
+                (response, from_addr, compress, caller)
+            })
+            .filter(|(response, _, _, _)| !response.is_empty())
             .collect();
 
         if pull_responses.is_empty() {
@@ -2011,7 +2928,7 @@ impl ClusterInfo {
         let mut stats: Vec<_> = pull_responses
             .iter()
             .enumerate()
-            .map(|(i, (responses, _from_addr))| {
+            .map(|(i, (responses, _from_addr, _compress, _caller))| {
                 let score: u64 = if stakes.get(&responses[0].pubkey()).is_some() {
                     2
                 } else {
@@ -2038,26 +2955,55 @@ impl ClusterInfo {
         let weighted_index = WeightedIndex::new(weights).unwrap();
 
         let mut total_bytes = 0;
+        let mut bytes_sent = vec![];
         let mut sent = HashSet::new();
+        // Responses to the peers accepting compressed ones, batched once the budget is spent
+        let mut compressed: HashMap<usize, Vec<CrdsValue>> = HashMap::new();
         while sent.len() < stats.len() {
             let index = weighted_index.sample(rng);
             if sent.contains(&index) {
                 continue;
             }
             let stat = &stats[index];
-            let from_addr = pull_responses[stat.to].1;
-            let response = pull_responses[stat.to].0[stat.responses_index].clone();
+            let (responses, from_addr, compress, caller) = &pull_responses[stat.to];
+            let response = responses[stat.responses_index].clone();
+            // The budget is charged the uncompressed size of the responses
             let protocol = Protocol::PullResponse(self_id, vec![response]);
-            let new_packet = Packet::from_data(&from_addr, protocol);
+            let new_packet = Packet::from_data(from_addr, protocol);
             if self.outbound_budget.take(new_packet.meta.size) {
                 sent.insert(index);
+                if *compress {
+                    compressed
+                        .entry(stat.to)
+                        .or_default()
+                        .push(responses[stat.responses_index].clone());
+                    continue;
+                }
                 total_bytes += new_packet.meta.size;
+                bytes_sent.push((*caller, new_packet.meta.size));
                 packets.packets.push(new_packet)
             } else {
                 inc_new_counter_info!("gossip_pull_request-no_budget", 1);
                 break;
             }
         }
+        for (to, responses) in compressed {
+            let (_, from_addr, _, caller) = &pull_responses[to];
+            for protocol in Self::new_gossip_messages(
+                responses,
+                true,
+                |payload| Protocol::PullResponse(self_id, payload),
+                |payload| Protocol::CompressedPullResponse(self_id, payload),
+            ) {
+                let new_packet = Packet::from_data(from_addr, protocol);
+                total_bytes += new_packet.meta.size;
+                bytes_sent.push((*caller, new_packet.meta.size));
+                packets.packets.push(new_packet);
+            }
+        }
+        self.stats.peers.update(bytes_sent, |stats, size| {
+            stats.bytes_sent += size as u64;
+        });
         time.stop();
         inc_new_counter_info!("gossip_pull_request-sent_requests", sent.len());
         inc_new_counter_info!(
@@ -2116,7 +3062,7 @@ impl ClusterInfo {
             }
             hash_map
         }
-        let responses = thread_pool.install(|| {
+        let mut responses = thread_pool.install(|| {
             responses
                 .into_par_iter()
                 .with_min_len(1024)
@@ -2126,6 +3072,8 @@ impl ClusterInfo {
                 })
                 .reduce(HashMap::new, merge)
         });
+        self.filter_inserts(responses.values_mut(), stakes);
+        self.check_duplicate_instance(responses.values().flatten());
         if !responses.is_empty() {
             let timeouts = self
                 .gossip
@@ -2155,6 +3103,7 @@ impl ClusterInfo {
             &mut crds_values,
             shred_version,
             self.my_shred_version(),
+            self.same_network_namespace(from),
         );
         let filtered_len = crds_values.len();
 
@@ -2180,23 +3129,23 @@ impl ClusterInfo {
 
         self.stats
             .skip_pull_response_shred_version
-            .add_relaxed((len - filtered_len) as u64);
-        self.stats.process_pull_response_count.add_relaxed(1);
+            .add((len - filtered_len) as u64);
+        self.stats.process_pull_response_count.add(1);
         self.stats
             .process_pull_response_len
-            .add_relaxed(filtered_len as u64);
+            .add(filtered_len as u64);
         self.stats
             .process_pull_response_timeout
-            .add_relaxed(pull_stats.timeout_count as u64);
+            .add(pull_stats.timeout_count as u64);
         self.stats
             .process_pull_response_fail_insert
-            .add_relaxed(pull_stats.failed_insert as u64);
+            .add(pull_stats.failed_insert as u64);
         self.stats
             .process_pull_response_fail_timeout
-            .add_relaxed(pull_stats.failed_timeout as u64);
+            .add(pull_stats.failed_timeout as u64);
         self.stats
             .process_pull_response_success
-            .add_relaxed(pull_stats.success as u64);
+            .add(pull_stats.success as u64);
 
         (
             pull_stats.failed_insert + pull_stats.failed_timeout,
@@ -2210,9 +3159,10 @@ impl ClusterInfo {
         crds_values: &mut Vec<CrdsValue>,
         shred_version: u16,
         my_shred_version: u16,
+        same_network_namespace: bool,
     ) {
         // Always run filter on spies
-        if my_shred_version != 0 && shred_version != my_shred_version {
+        if !same_network_namespace || (my_shred_version != 0 && shred_version != my_shred_version) {
             // Allow someone to update their own ContactInfo so they
             // can change shred versions if needed.
             crds_values.retain(|crds_value| match &crds_value.data {
@@ -2272,11 +3222,21 @@ impl ClusterInfo {
 
     fn handle_batch_push_messages(
         &self,
-        messages: Vec<(Pubkey, Vec<CrdsValue>)>,
+        mut messages: Vec<(Pubkey, Vec<CrdsValue>)>,
         recycler: &PacketsRecycler,
         stakes: &HashMap<Pubkey, u64>,
         response_sender: &PacketSender,
     ) {
+        if messages.is_empty() {
+            return;
+        }
+        self.stats
+            .peers
+            .update(messages.iter().map(|(from, _)| (*from, ())), |stats, ()| {
+                stats.push_messages_received += 1
+            });
+        self.filter_inserts(messages.iter_mut().map(|(_, values)| values), stakes);
+        self.check_duplicate_instance(messages.iter().flat_map(|(_, values)| values));
         for (from, data) in messages {
             let response = self.handle_push_message(recycler, &from, data, stakes);
             if let Some(response) = response {
@@ -2293,7 +3253,7 @@ impl ClusterInfo {
         stakes: &HashMap<Pubkey, u64>,
     ) -> Option<Packets> {
         let self_id = self.id();
-        self.stats.push_message_count.add_relaxed(1);
+        self.stats.push_message_count.add(1);
         let len = crds_values.len();
 
         let shred_version = self
@@ -2304,14 +3264,13 @@ impl ClusterInfo {
             &mut crds_values,
             shred_version,
             self.my_shred_version(),
+            self.same_network_namespace(from),
         );
         let filtered_len = crds_values.len();
-        self.stats
-            .push_message_value_count
-            .add_relaxed(filtered_len as u64);
+        self.stats.push_message_value_count.add(filtered_len as u64);
         self.stats
             .skip_push_message_shred_version
-            .add_relaxed((len - filtered_len) as u64);
+            .add((len - filtered_len) as u64);
 
         let updated: Vec<_> = self
             .time_gossip_write_lock("process_push", &self.stats.process_push_message)
@@ -2336,17 +3295,29 @@ impl ClusterInfo {
                     };
                     prune_msg.sign(&self.keypair);
                     let rsp = Protocol::PruneMessage(self_id, prune_msg);
-                    (ci.gossip, rsp)
+                    (from, (ci.gossip, rsp))
                 })
             })
             .collect();
         if rsp.is_empty() {
             return None;
         }
+        let (destinations, rsp): (Vec<_>, Vec<_>) = rsp.into_iter().unzip();
         let mut packets = to_packets_with_destination(recycler.clone(), &rsp);
+        self.stats.peers.update(
+            destinations
+                .into_iter()
+                .zip(packets.packets.iter().map(|packet| packet.meta.size))
+                // Prunes to unknown destinations are dropped
+                .filter(|(_, size)| *size != 0),
+            |stats, size| {
+                stats.prunes_sent += 1;
+                stats.bytes_sent += size as u64;
+            },
+        );
         self.stats
             .push_response_count
-            .add_relaxed(packets.packets.len() as u64);
+            .add(packets.packets.len() as u64);
         if !packets.is_empty() {
             let pushes: Vec<_> = self.new_push_requests();
             inc_new_counter_debug!("cluster_info-push_message-pushes", pushes.len());
@@ -2401,15 +3372,35 @@ impl ClusterInfo {
             requests
                 .into_par_iter()
                 .flat_map(|request| request.packets.into_par_iter())
-                .filter_map(|packet| {
-                    let protocol: Protocol =
-                        limited_deserialize(&packet.data[..packet.meta.size]).ok()?;
-                    protocol.sanitize().ok()?;
-                    let protocol = protocol.par_verify()?;
-                    Some((packet.meta.addr(), protocol))
+                .map(|packet| {
+                    let data = &packet.data[..packet.meta.size];
+                    let protocol = parse_protocol(data);
+                    let sender = match &protocol {
+                        Ok(Protocol::PullRequest(_, caller)) => Some(caller.pubkey()),
+                        _ => claimed_sender(data),
+                    };
+                    if let Err(rejection) = &protocol {
+                        self.stats.packet_rejected(*rejection).add(1);
+                    }
+                    (packet.meta.addr(), sender, data.len(), protocol)
                 })
                 .collect()
         });
+        self.stats.peers.update(
+            packets
+                .iter()
+                .filter_map(|(_, sender, size, _)| Some(((*sender)?, *size))),
+            |stats, size| stats.bytes_received += size as u64,
+        );
+        self.stats.peers.update(
+            packets.iter().filter_map(|(_, sender, _, protocol)| {
+                Some(((*sender)?, protocol.as_ref().err()?))
+            }),
+            |stats, rejection| stats.last_error = Some(format!("{:?}", rejection)),
+        );
+        let packets = packets
+            .into_iter()
+            .filter_map(|(from_addr, _, _, protocol)| Some((from_addr, protocol.ok()?)));
         // Split packets based on their types.
         let mut pull_requests = vec![];
         let mut pull_responses = vec![];
@@ -2427,6 +3418,8 @@ impl ClusterInfo {
                 Protocol::PruneMessage(from, data) => prune_messages.push((from, data)),
                 Protocol::PingMessage(ping) => ping_messages.push((from_addr, ping)),
                 Protocol::PongMessage(pong) => pong_messages.push((from_addr, pong)),
+                // Decompressed by parse_protocol
+                Protocol::CompressedPullResponse(..) | Protocol::CompressedPushMessage(..) => (),
             }
         }
         self.handle_batch_ping_messages(ping_messages, recycler, response_sender);
@@ -2442,9 +3435,30 @@ impl ClusterInfo {
             response_sender,
             feature_set,
         );
-        self.stats
-            .process_gossip_packets_time
-            .add_measure(&mut timer);
+        timer.stop();
+        self.stats.process_gossip_packets_time.add(timer.as_us());
+    }
+
+    /// Handles gossip packets as if received from the network and returns the responses, for
+    /// fuzzing
+    #[doc(hidden)]
+    pub fn process_packets_for_fuzzing(
+        &self,
+        packets: Vec<Packets>,
+        thread_pool: &ThreadPool,
+    ) -> Vec<Packets> {
+        let recycler = PacketsRecycler::default();
+        let (response_sender, response_receiver) = std::sync::mpsc::channel();
+        self.process_packets(
+            packets,
+            thread_pool,
+            &recycler,
+            &response_sender,
+            HashMap::new(),
+            None,
+            GOSSIP_PULL_CFG.CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS,
+        );
+        response_receiver.try_iter().collect()
     }
 
     /// Process messages from the network
@@ -2513,196 +3527,14 @@ impl ClusterInfo {
                     r_gossip.pull.failed_inserts.len(),
                 )
             };
-            datapoint_info!(
-                "cluster_info_stats",
-                ("entrypoint", self.stats.entrypoint.clear(), i64),
-                ("entrypoint2", self.stats.entrypoint2.clear(), i64),
-                ("push_vote_read", self.stats.push_vote_read.clear(), i64),
-                (
-                    "vote_process_push",
-                    self.stats.vote_process_push.clear(),
-                    i64
-                ),
-                ("get_votes", self.stats.get_votes.clear(), i64),
-                (
-                    "get_accounts_hash",
-                    self.stats.get_accounts_hash.clear(),
-                    i64
-                ),
-                ("all_tvu_peers", self.stats.all_tvu_peers.clear(), i64),
-                ("tvu_peers", self.stats.tvu_peers.clear(), i64),
-                (
-                    "new_push_requests_num",
-                    self.stats.new_push_requests_num.clear(),
-                    i64
-                ),
-                ("table_size", table_size as i64, i64),
-                ("purged_values_size", purged_values_size as i64, i64),
-                ("failed_inserts_size", failed_inserts_size as i64, i64),
-            );
-            datapoint_info!(
-                "cluster_info_stats2",
-                ("retransmit_peers", self.stats.retransmit_peers.clear(), i64),
-                ("repair_peers", self.stats.repair_peers.clear(), i64),
-                (
-                    "new_push_requests",
-                    self.stats.new_push_requests.clear(),
-                    i64
-                ),
-                (
-                    "new_push_requests2",
-                    self.stats.new_push_requests2.clear(),
-                    i64
-                ),
-                ("purge", self.stats.purge.clear(), i64),
-                (
-                    "process_gossip_packets_time",
-                    self.stats.process_gossip_packets_time.clear(),
-                    i64
-                ),
-                (
-                    "process_pull_resp",
-                    self.stats.process_pull_response.clear(),
-                    i64
-                ),
-                (
-                    "filter_pull_resp",
-                    self.stats.filter_pull_response.clear(),
-                    i64
-                ),
-                (
-                    "process_pull_resp_count",
-                    self.stats.process_pull_response_count.clear(),
-                    i64
-                ),
-                (
-                    "pull_response_fail_insert",
-                    self.stats.process_pull_response_fail_insert.clear(),
-                    i64
-                ),
-                (
-                    "pull_response_fail_timeout",
-                    self.stats.process_pull_response_fail_timeout.clear(),
-                    i64
-                ),
-                (
-                    "pull_response_success",
-                    self.stats.process_pull_response_success.clear(),
-                    i64
-                ),
-                (
-                    "process_pull_resp_timeout",
-                    self.stats.process_pull_response_timeout.clear(),
-                    i64
-                ),
-                (
-                    "push_response_count",
-                    self.stats.push_response_count.clear(),
-                    i64
-                ),
-            );
-            datapoint_info!(
-                "cluster_info_stats3",
-                (
-                    "process_pull_resp_len",
-                    self.stats.process_pull_response_len.clear(),
-                    i64
-                ),
-                (
-                    "process_pull_requests",
-                    self.stats.process_pull_requests.clear(),
-                    i64
-                ),
-                (
-                    "pull_request_ping_pong_check_failed_count",
-                    self.stats.pull_request_ping_pong_check_failed_count.clear(),
-                    i64
-                ),
-                (
-                    "generate_pull_responses",
-                    self.stats.generate_pull_responses.clear(),
-                    i64
-                ),
-                ("process_prune", self.stats.process_prune.clear(), i64),
-                (
-                    "process_push_message",
-                    self.stats.process_push_message.clear(),
-                    i64
-                ),
-                (
-                    "prune_received_cache",
-                    self.stats.prune_received_cache.clear(),
-                    i64
-                ),
-                (
-                    "epoch_slots_lookup",
-                    self.stats.epoch_slots_lookup.clear(),
-                    i64
-                ),
-                ("epoch_slots_push", self.stats.epoch_slots_push.clear(), i64),
-                ("push_message", self.stats.push_message.clear(), i64),
-                (
-                    "new_pull_requests",
-                    self.stats.new_pull_requests.clear(),
-                    i64
-                ),
-                (
-                    "mark_pull_request",
-                    self.stats.mark_pull_request.clear(),
-                    i64
-                ),
-            );
-            datapoint_info!(
-                "cluster_info_stats4",
-                (
-                    "skip_push_message_shred_version",
-                    self.stats.skip_push_message_shred_version.clear(),
-                    i64
-                ),
-                (
-                    "skip_pull_response_shred_version",
-                    self.stats.skip_pull_response_shred_version.clear(),
-                    i64
-                ),
-                (
-                    "skip_pull_shred_version",
-                    self.stats.skip_pull_shred_version.clear(),
-                    i64
-                ),
-                (
-                    "push_message_count",
-                    self.stats.push_message_count.clear(),
-                    i64
-                ),
-                (
-                    "push_message_value_count",
-                    self.stats.push_message_value_count.clear(),
-                    i64
-                ),
-                (
-                    "new_pull_requests_count",
-                    self.stats.new_pull_requests_count.clear(),
-                    i64
-                ),
-                (
-                    "prune_message_count",
-                    self.stats.prune_message_count.clear(),
-                    i64
-                ),
-                (
-                    "prune_message_len",
-                    self.stats.prune_message_len.clear(),
-                    i64
-                ),
-            );
-            datapoint_info!(
-                "cluster_info_stats5",
-                (
-                    "pull_requests_count",
-                    self.stats.pull_requests_count.clear(),
-                    i64
-                ),
-            );
+            self.stats.table_size.set(table_size as i64);
+            self.stats.purged_values_size.set(purged_values_size as i64);
+            self.stats
+                .failed_inserts_size
+                .set(failed_inserts_size as i64);
+            for namespace in &self.stats.namespaces {
+                namespace.submit(log::Level::Info);
+            }
 
             *last_print = Instant::now();
         }
@@ -2834,11 +3666,16 @@ pub struct Sockets {
     pub tvu: Vec<UdpSocket>,
     pub tvu_forwards: Vec<UdpSocket>,
     pub tpu: Vec<UdpSocket>,
+    /// Listener for transactions submitted over TCP, on the port of the UDP tpu sockets
+    pub tpu_tcp: Option<TcpListener>,
     pub tpu_forwards: Vec<UdpSocket>,
+    /// Vote transactions, on the port advertised by the `tpu-vote` capability
+    pub tpu_vote: Vec<UdpSocket>,
     pub broadcast: Vec<UdpSocket>,
     pub repair: UdpSocket,
     pub retransmit_sockets: Vec<UdpSocket>,
     pub serve_repair: UdpSocket,
+    pub ancestor_hashes_requests: UdpSocket,
 }
 
 #[derive(Debug)]
@@ -2855,12 +3692,14 @@ impl Node {
     pub fn new_localhost_with_pubkey(pubkey: &Pubkey) -> Self {
         let bind_ip_addr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
         let tpu = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tpu_tcp = TcpListener::bind(tpu.local_addr().unwrap()).ok();
         let (gossip_port, (gossip, ip_echo)) =
             bind_common_in_range(bind_ip_addr, (1024, 65535)).unwrap();
         let gossip_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), gossip_port);
         let tvu = UdpSocket::bind("127.0.0.1:0").unwrap();
         let tvu_forwards = UdpSocket::bind("127.0.0.1:0").unwrap();
         let tpu_forwards = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tpu_vote = UdpSocket::bind("127.0.0.1:0").unwrap();
         let repair = UdpSocket::bind("127.0.0.1:0").unwrap();
         let rpc_port = find_available_port_in_range(bind_ip_addr, (1024, 65535)).unwrap();
         let rpc_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), rpc_port);
@@ -2874,6 +3713,7 @@ impl Node {
         let broadcast = vec![UdpSocket::bind("0.0.0.0:0").unwrap()];
         let retransmit_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
         let serve_repair = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let ancestor_hashes_requests = UdpSocket::bind("127.0.0.1:0").unwrap();
         let info = ContactInfo {
             id: *pubkey,
             gossip: gossip_addr,
@@ -2897,11 +3737,14 @@ impl Node {
                 tvu: vec![tvu],
                 tvu_forwards: vec![tvu_forwards],
                 tpu: vec![tpu],
+                tpu_tcp,
                 tpu_forwards: vec![tpu_forwards],
+                tpu_vote: vec![tpu_vote],
                 broadcast,
                 repair,
                 retransmit_sockets: vec![retransmit_socket],
                 serve_repair,
+                ancestor_hashes_requests,
             },
         }
     }
@@ -2942,15 +3785,20 @@ impl Node {
 
         let (tpu_port, tpu_sockets) =
             multi_bind_in_range(bind_ip_addr, port_range, 32).expect("tpu multi_bind");
+        let tpu_tcp = TcpListener::bind(SocketAddr::new(bind_ip_addr, tpu_port)).ok();
 
         let (tpu_forwards_port, tpu_forwards_sockets) =
             multi_bind_in_range(bind_ip_addr, port_range, 8).expect("tpu_forwards multi_bind");
 
+        let (_, tpu_vote_sockets) =
+            multi_bind_in_range(bind_ip_addr, port_range, 1).expect("tpu_vote multi_bind");
+
         let (_, retransmit_sockets) =
             multi_bind_in_range(bind_ip_addr, port_range, 8).expect("retransmit multi_bind");
 
         let (repair_port, repair) = Self::bind(bind_ip_addr, port_range);
         let (serve_repair_port, serve_repair) = Self::bind(bind_ip_addr, port_range);
+        let (_, ancestor_hashes_requests) = Self::bind(bind_ip_addr, port_range);
 
         let (_, broadcast) =
             multi_bind_in_range(bind_ip_addr, port_range, 4).expect("broadcast multi_bind");
@@ -2979,11 +3827,14 @@ impl Node {
                 tvu: tvu_sockets,
                 tvu_forwards: tvu_forwards_sockets,
                 tpu: tpu_sockets,
+                tpu_tcp,
                 tpu_forwards: tpu_forwards_sockets,
+                tpu_vote: tpu_vote_sockets,
                 broadcast,
                 repair,
                 retransmit_sockets,
                 serve_repair,
+                ancestor_hashes_requests,
                 ip_echo: Some(ip_echo),
             },
         }
@@ -3162,6 +4013,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_prefer_low_latency_peers() {
+        let mut rng = rand::thread_rng();
+        let this_node = Arc::new(Keypair::new());
+        let cluster_info = ClusterInfo::new(
+            ContactInfo::new_localhost(&this_node.pubkey(), timestamp()),
+            this_node.clone(),
+        );
+        let keypairs: Vec<_> = repeat_with(Keypair::new).take(3).collect();
+        let peers: Vec<_> = keypairs
+            .iter()
+            .map(|keypair| ContactInfo::new_localhost(&keypair.pubkey(), timestamp()))
+            .collect();
+        // The last peer never answers
+        let now = Instant::now();
+        for (keypair, (peer, rtt)) in keypairs.iter().zip(peers.iter().zip(&[10, 40])) {
+            let mut ping_cache = cluster_info.ping_cache.write().unwrap();
+            let pingf = || Ping::new_rand(&mut rng, &this_node).ok();
+            let (_, ping) = ping_cache.check(now, (peer.id, peer.gossip), pingf);
+            let pong = Pong::new(&ping.unwrap(), keypair).unwrap();
+            let now = now + Duration::from_millis(*rtt);
+            assert!(ping_cache.add(&pong, peer.gossip, now));
+        }
+        assert_eq!(
+            cluster_info.peer_rtt(&peers[1].id),
+            Some(Duration::from_millis(40))
+        );
+        assert_eq!(cluster_info.peer_rtt(&peers[2].id), None);
+        assert_eq!(cluster_info.peer_rtts().len(), 2);
+
+        let mut weights = vec![(100, 0), (100, 1), (100, 2)];
+        cluster_info.prefer_low_latency_peers(&peers, &mut weights);
+        assert_eq!(weights, vec![(100, 0), (25, 1), (100, 2)]);
+        // Weights are not scaled below 1
+        let mut weights = vec![(1, 1), (7, 2)];
+        cluster_info.prefer_low_latency_peers(&peers, &mut weights);
+        assert_eq!(weights, vec![(1, 1), (7, 2)]);
+    }
+
     fn test_crds_values(pubkey: Pubkey) -> Vec<CrdsValue> {
         let entrypoint = ContactInfo::new_localhost(&pubkey, timestamp());
         let entrypoint_crdsvalue = CrdsValue::new_unsigned(CrdsData::ContactInfo(entrypoint));
@@ -3181,6 +4071,7 @@ mod tests {
             &mut values,
             other_shred_version,
             my_shred_version,
+            true,
         );
         assert_eq!(values.len(), 1);
 
@@ -3191,6 +4082,7 @@ mod tests {
             &mut values,
             other_shred_version,
             my_shred_version,
+            true,
         );
         assert_eq!(values.len(), 1);
 
@@ -3201,6 +4093,7 @@ mod tests {
             &mut values,
             other_shred_version,
             my_shred_version,
+            true,
         );
         assert_eq!(values.len(), 1);
 
@@ -3209,7 +4102,7 @@ mod tests {
             hashes: vec![],
             wallclock: 0,
         }));
-        values.push(snapshot_hash_data);
+        values.push(snapshot_hash_data.clone());
         // Change to sender's ContactInfo version, allow that.
         let other_shred_version = 2;
         ClusterInfo::filter_by_shred_version(
@@ -3217,8 +4110,21 @@ mod tests {
             &mut values,
             other_shred_version,
             my_shred_version,
+            true,
+        );
+        assert_eq!(values.len(), 1);
+
+        // Same shred_version but another network namespace, only the ContactInfo is allowed
+        values.push(snapshot_hash_data);
+        ClusterInfo::filter_by_shred_version(
+            &from,
+            &mut values,
+            my_shred_version,
+            my_shred_version,
+            false,
         );
         assert_eq!(values.len(), 1);
+        assert!(values[0].contact_info().is_some());
     }
 
     #[test]
@@ -3251,6 +4157,22 @@ mod tests {
         assert_eq!(d.id, cluster_info.id());
     }
 
+    #[test]
+    fn test_gossip_stats_per_instance() {
+        let new_cluster_info = || {
+            let d = ContactInfo::new_localhost(&solana_sdk::pubkey::new_rand(), timestamp());
+            ClusterInfo::new_with_invalid_keypair(d)
+        };
+        let cluster_info = new_cluster_info();
+        let other_cluster_info = new_cluster_info();
+        cluster_info.stats.push_message_count.add(3);
+        assert_eq!(cluster_info.stats.push_message_count.total(), 3);
+        assert_eq!(other_cluster_info.stats.push_message_count.total(), 0);
+        let namespace =
+            registry::instance_namespace("cluster_info_stats4", &cluster_info.id().to_string());
+        assert_eq!(namespace.counter("push_message_count").total(), 3);
+    }
+
     #[test]
     fn insert_info_test() {
         let d = ContactInfo::new_localhost(&solana_sdk::pubkey::new_rand(), timestamp());
@@ -3324,6 +4246,7 @@ mod tests {
     fn check_node_sockets(node: &Node, ip: IpAddr, range: (u16, u16)) {
         check_socket(&node.sockets.gossip, ip, range);
         check_socket(&node.sockets.repair, ip, range);
+        check_socket(&node.sockets.ancestor_hashes_requests, ip, range);
 
         check_sockets(&node.sockets.tvu, ip, range);
         check_sockets(&node.sockets.tpu, ip, range);
@@ -3588,6 +4511,349 @@ mod tests {
         assert_eq!(max_ts, new_max_ts);
     }
 
+    #[test]
+    fn test_push_once_active() {
+        use solana_runtime::{
+            bank::Bank,
+            genesis_utils::{create_genesis_config, GenesisConfigInfo},
+        };
+        let feature_id = feature_set::gossip_node_capabilities::id();
+        let new_bank_forks = |active: bool| {
+            let GenesisConfigInfo {
+                mut genesis_config, ..
+            } = create_genesis_config(10_000);
+            if !active {
+                genesis_config.accounts.remove(&feature_id);
+            }
+            Some(Arc::new(RwLock::new(BankForks::new(Bank::new(
+                &genesis_config,
+            )))))
+        };
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(ContactInfo::new_localhost(
+            &solana_sdk::pubkey::new_rand(),
+            0,
+        ));
+        let pushed = Arc::new(AtomicBool::default());
+        {
+            let pushed = pushed.clone();
+            cluster_info
+                .push_once_active(feature_id, move |_| pushed.store(true, Ordering::Relaxed));
+        }
+
+        // Held back with no bank to tell the features from, or while the feature is inactive
+        cluster_info.handle_gated_pushes(&None);
+        cluster_info.handle_gated_pushes(&new_bank_forks(false));
+        assert!(!pushed.load(Ordering::Relaxed));
+
+        cluster_info.handle_gated_pushes(&new_bank_forks(true));
+        assert!(pushed.load(Ordering::Relaxed));
+        assert!(cluster_info.gated_pushes.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_push_node_capabilities() {
+        let keys = Keypair::new();
+        let contact_info = ContactInfo::new_localhost(&keys.pubkey(), 0);
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
+        let id = keys.pubkey();
+        assert_eq!(cluster_info.get_node_capabilities(&id), None);
+
+        let mut capabilities = BTreeMap::new();
+        capabilities.insert(crds_value::CAPABILITY_TPU_TCP.to_string(), String::new());
+        cluster_info.push_node_capabilities(capabilities.clone());
+        cluster_info.flush_push_queue();
+        assert_eq!(
+            cluster_info
+                .get_node_capabilities(&id)
+                .unwrap()
+                .capabilities,
+            capabilities
+        );
+        assert!(cluster_info.node_has_capability(&id, crds_value::CAPABILITY_TPU_TCP));
+        assert!(!cluster_info.node_has_capability(&id, crds_value::CAPABILITY_SNAPSHOTS));
+
+        // Capabilities which would not sanitize are not pushed
+        let capabilities = (0..=crds_value::MAX_NODE_CAPABILITIES)
+            .map(|i| (i.to_string(), String::new()))
+            .collect();
+        cluster_info.push_node_capabilities(capabilities);
+        cluster_info.flush_push_queue();
+        assert!(cluster_info.node_has_capability(&id, crds_value::CAPABILITY_TPU_TCP));
+    }
+
+    #[test]
+    fn test_send_vote() {
+        let keys = Keypair::new();
+        let tpu = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tpu_vote = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut contact_info = ContactInfo::new_localhost(&keys.pubkey(), 0);
+        contact_info.tpu = tpu.local_addr().unwrap();
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
+        let id = keys.pubkey();
+        let vote = Transaction::default();
+        let receive = |socket: &UdpSocket| {
+            socket
+                .set_read_timeout(Some(Duration::from_secs(1)))
+                .unwrap();
+            let mut buf = [0u8; PACKET_DATA_SIZE];
+            socket.recv(&mut buf).is_ok()
+        };
+
+        // Without a tpu_vote port, or a known leader, the vote goes to the TPU of this node
+        assert_eq!(cluster_info.lookup_tpu_vote(&id), None);
+        cluster_info.send_vote(&vote, Some(&id)).unwrap();
+        assert!(receive(&tpu));
+        cluster_info.send_vote(&vote, None).unwrap();
+        assert!(receive(&tpu));
+
+        let mut capabilities = BTreeMap::new();
+        capabilities.insert(
+            crds_value::CAPABILITY_TPU_VOTE.to_string(),
+            tpu_vote.local_addr().unwrap().port().to_string(),
+        );
+        cluster_info.push_node_capabilities(capabilities);
+        cluster_info.flush_push_queue();
+        assert_eq!(
+            cluster_info.lookup_tpu_vote(&id),
+            Some(tpu_vote.local_addr().unwrap())
+        );
+        cluster_info.send_vote(&vote, Some(&id)).unwrap();
+        assert!(receive(&tpu_vote));
+        assert_eq!(
+            cluster_info.lookup_tpu_vote(&solana_sdk::pubkey::new_rand()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_network_namespace() {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let cluster_info = ClusterInfo::new(contact_info, keypair);
+        let namespace = |name: &str| {
+            let mut capabilities = BTreeMap::new();
+            capabilities.insert(CAPABILITY_NETWORK_NAMESPACE.to_string(), name.to_string());
+            capabilities
+        };
+        let peers: Vec<_> = vec![Some("testnet"), Some("devnet"), None]
+            .into_iter()
+            .map(|name| {
+                let peer = Keypair::new();
+                cluster_info.insert_info(ContactInfo::new_localhost(&peer.pubkey(), timestamp()));
+                if let Some(name) = name {
+                    let value = CrdsValue::new_signed(
+                        CrdsData::NodeCapabilities(NodeCapabilities::new(
+                            peer.pubkey(),
+                            namespace(name),
+                        )),
+                        &peer,
+                    );
+                    let mut gossip = cluster_info.gossip.write().unwrap();
+                    gossip.crds.insert(value, timestamp()).unwrap();
+                }
+                peer.pubkey()
+            })
+            .collect();
+        // Without a namespace of its own, the node is on the network of all its peers
+        assert_eq!(cluster_info.my_network_namespace(), None);
+        assert!(peers
+            .iter()
+            .all(|peer| cluster_info.same_network_namespace(peer)));
+        assert_eq!(cluster_info.tvu_peers().len(), 3);
+
+        cluster_info.push_node_capabilities(namespace("testnet"));
+        assert_eq!(
+            cluster_info.my_network_namespace(),
+            Some("testnet".to_string())
+        );
+        assert!(cluster_info.same_network_namespace(&peers[0]));
+        assert!(!cluster_info.same_network_namespace(&peers[1]));
+        // Peers not advertising a namespace are assumed to be on the same network
+        assert!(cluster_info.same_network_namespace(&peers[2]));
+        let mut tvu_peers: Vec<_> = cluster_info.tvu_peers().iter().map(|ci| ci.id).collect();
+        tvu_peers.sort();
+        let mut expected = vec![peers[0], peers[2]];
+        expected.sort();
+        assert_eq!(tvu_peers, expected);
+        assert_eq!(cluster_info.retransmit_peers().len(), 2);
+    }
+
+    #[test]
+    fn test_compressed_gossip_messages() {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let cluster_info = ClusterInfo::new(contact_info, keypair.clone());
+        let peer = Keypair::new();
+        let mut capabilities = BTreeMap::new();
+        capabilities.insert(
+            crds_value::CAPABILITY_GOSSIP_COMPRESSION.to_string(),
+            GOSSIP_COMPRESSION_CODEC.clone(),
+        );
+        let value = CrdsValue::new_signed(
+            CrdsData::NodeCapabilities(NodeCapabilities::new(peer.pubkey(), capabilities.clone())),
+            &peer,
+        );
+        cluster_info
+            .gossip
+            .write()
+            .unwrap()
+            .crds
+            .insert(value, timestamp())
+            .unwrap();
+        assert!(cluster_info.accepts_compressed_gossip(&peer.pubkey()));
+        // Gossip is not compressed until this node enables it too
+        assert!(!cluster_info.compress_gossip_to(&peer.pubkey()));
+        cluster_info.push_node_capabilities(capabilities);
+        cluster_info.flush_push_queue();
+        assert!(cluster_info.compress_gossip_to(&peer.pubkey()));
+
+        let recent_blockhash = Hash::new_unique();
+        let votes: Vec<_> = (0..crds_value::MAX_VOTES as u64)
+            .map(|i| {
+                let tx = solana_vote_program::vote_transaction::new_vote_transaction(
+                    vec![i],
+                    Hash::new_unique(),
+                    recent_blockhash,
+                    &keypair,
+                    &keypair,
+                    &keypair,
+                    None,
+                );
+                let vote = CrdsVote::new(&keypair.pubkey(), tx, i);
+                CrdsValue::new_signed(CrdsData::Vote(i as u8, vote), &keypair)
+            })
+            .collect();
+        let self_id = keypair.pubkey();
+        let uncompressed = ClusterInfo::new_gossip_messages(
+            votes.clone(),
+            false,
+            |payload| Protocol::PushMessage(self_id, payload),
+            |payload| Protocol::CompressedPushMessage(self_id, payload),
+        );
+        let compressed = ClusterInfo::new_gossip_messages(
+            votes.clone(),
+            true,
+            |payload| Protocol::PushMessage(self_id, payload),
+            |payload| Protocol::CompressedPushMessage(self_id, payload),
+        );
+        assert!(compressed.len() < uncompressed.len());
+        let values: Vec<_> = compressed
+            .iter()
+            .flat_map(|protocol| {
+                let packet = Packet::from_data(&socketaddr!("127.0.0.1:1234"), protocol);
+                match parse_protocol(&packet.data[..packet.meta.size]) {
+                    Ok(Protocol::PushMessage(from, values)) => {
+                        assert_eq!(from, self_id);
+                        values
+                    }
+                    _ => panic!("invalid compressed push message"),
+                }
+            })
+            .collect();
+        assert_eq!(values, votes);
+    }
+
+    #[test]
+    fn test_push_validator_info() {
+        let keys = Keypair::new();
+        let contact_info = ContactInfo::new_localhost(&keys.pubkey(), 0);
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
+        let id = keys.pubkey();
+        assert_eq!(cluster_info.get_validator_info(&id), None);
+
+        cluster_info.push_validator_info(
+            "validator".to_string(),
+            Some("https://example.com".to_string()),
+            None,
+        );
+        cluster_info.flush_push_queue();
+        let info = cluster_info.get_validator_info(&id).unwrap();
+        assert_eq!(info.name, "validator");
+        assert_eq!(info.website, Some("https://example.com".to_string()));
+        assert_eq!(info.icon_url, None);
+
+        // Info which would not sanitize is not pushed
+        cluster_info.push_validator_info(
+            "x".repeat(crds_value::MAX_VALIDATOR_INFO_NAME_LEN + 1),
+            None,
+            None,
+        );
+        cluster_info.flush_push_queue();
+        assert_eq!(
+            cluster_info.get_validator_info(&id).unwrap().name,
+            "validator"
+        );
+    }
+
+    #[test]
+    fn test_push_planned_hard_fork() {
+        let keys = Keypair::new();
+        let contact_info = ContactInfo::new_localhost(&keys.pubkey(), 0);
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
+        assert!(cluster_info.get_planned_hard_forks().is_empty());
+
+        cluster_info.push_planned_hard_fork(42, 1234);
+        cluster_info.flush_push_queue();
+        let hard_forks = cluster_info.get_planned_hard_forks();
+        assert_eq!(hard_forks.len(), 1);
+        assert_eq!(hard_forks[0].from, keys.pubkey());
+        assert_eq!(
+            (hard_forks[0].slot, hard_forks[0].shred_version),
+            (42, 1234)
+        );
+
+        // A new plan replaces the previous one
+        sleep(Duration::from_millis(1));
+        cluster_info.push_planned_hard_fork(43, 4321);
+        cluster_info.flush_push_queue();
+        let hard_forks = cluster_info.get_planned_hard_forks();
+        assert_eq!(hard_forks.len(), 1);
+        assert_eq!(hard_forks[0].slot, 43);
+    }
+
+    #[test]
+    fn test_push_restart_bank_hash() {
+        let keys = Keypair::new();
+        let contact_info = ContactInfo::new_localhost(&keys.pubkey(), 0);
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
+        assert_eq!(cluster_info.get_restart_bank_hash(&keys.pubkey()), None);
+
+        let bank_hash = Hash::new_unique();
+        cluster_info.push_restart_bank_hash(42, bank_hash);
+        cluster_info.flush_push_queue();
+        assert_eq!(
+            cluster_info.get_restart_bank_hash(&keys.pubkey()),
+            Some((42, bank_hash))
+        );
+    }
+
+    #[test]
+    fn test_check_duplicate_instance() {
+        let keys = Keypair::new();
+        let contact_info = ContactInfo::new_localhost(&keys.pubkey(), 0);
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
+        let instance = cluster_info.instance.clone();
+        let as_value =
+            |instance: NodeInstance| CrdsValue::new_unsigned(CrdsData::NodeInstance(instance));
+
+        // Its own instance, and older instances, do not stop the node from voting
+        let older = NodeInstance {
+            wallclock: instance.wallclock - 1,
+            token: instance.token.wrapping_add(1),
+            ..instance.clone()
+        };
+        cluster_info.check_duplicate_instance(&[as_value(instance.clone()), as_value(older)]);
+        assert!(!cluster_info.is_duplicate_instance());
+
+        let newer = NodeInstance {
+            wallclock: instance.wallclock + 1,
+            token: instance.token.wrapping_add(1),
+            ..instance
+        };
+        cluster_info.check_duplicate_instance(&[as_value(newer)]);
+        assert!(cluster_info.is_duplicate_instance());
+    }
+
     #[test]
     fn test_push_epoch_slots() {
         let keys = Keypair::new();
@@ -3612,6 +4878,56 @@ mod tests {
         assert_eq!(since2, since);
     }
 
+    #[test]
+    fn test_push_epoch_slots_shards() {
+        let keys = Keypair::new();
+        let contact_info = ContactInfo::new_localhost(&keys.pubkey(), 0);
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
+        let id = keys.pubkey();
+        cluster_info.push_epoch_slots_shards(&[1, 2, 3]);
+        cluster_info.flush_push_queue();
+        let shards = cluster_info.get_node_epoch_slots_shards(&id);
+        assert_eq!(shards.len(), 1);
+        let generation = shards[0].generation;
+
+        // Slots already published are not pushed again
+        cluster_info.push_epoch_slots_shards(&[2, 3]);
+        assert!(cluster_info.drain_push_queue().is_empty());
+        cluster_info.push_epoch_slots_shards(&[2, 3, 4]);
+        cluster_info.flush_push_queue();
+        let shards = cluster_info.get_node_epoch_slots_shards(&id);
+        assert_eq!(shards.len(), 1);
+        assert_eq!(shards[0].generation, generation);
+        assert_eq!(shards[0].epoch_slots.to_slots(0), vec![1, 2, 3, 4]);
+
+        // The EpochSlots values published before the feature activation are reported along
+        cluster_info.push_epoch_slots(&[5]);
+        cluster_info.flush_push_queue();
+        let (slots, _) = cluster_info.get_epoch_slots_since(None);
+        assert_eq!(slots.len(), 2);
+
+        // Shards of earlier generations are reported, unless replaced since, as those left
+        // from before a restart
+        let insert_shard = |generation| {
+            let shard = EpochSlotsShard::new(id, generation, timestamp());
+            let value = CrdsValue::new_unsigned(CrdsData::EpochSlotsShard(shard.index(), shard));
+            cluster_info
+                .gossip
+                .write()
+                .unwrap()
+                .crds
+                .insert(value, timestamp())
+                .unwrap();
+        };
+        insert_shard(generation - 1);
+        let (slots, _) = cluster_info.get_epoch_slots_since(None);
+        assert_eq!(slots.len(), 3);
+        insert_shard(generation - crds_value::MAX_EPOCH_SLOTS as u64 - 2);
+        let (slots, _) = cluster_info.get_epoch_slots_since(None);
+        assert_eq!(slots.len(), 3);
+        assert_eq!(cluster_info.get_node_epoch_slots_shards(&id).len(), 2);
+    }
+
     #[test]
     fn test_append_entrypoint_to_pulls() {
         let thread_pool = ThreadPoolBuilder::new().build().unwrap();
@@ -3897,6 +5213,78 @@ mod tests {
         assert_eq!(msg.sanitize(), Err(SanitizeError::ValueOutOfBounds));
     }
 
+    #[test]
+    fn test_check_gossip_packet() {
+        let keypair = Keypair::new();
+        let contact_info =
+            CrdsData::ContactInfo(ContactInfo::new_localhost(&keypair.pubkey(), timestamp()));
+        let push = serialize(&Protocol::PushMessage(
+            keypair.pubkey(),
+            vec![CrdsValue::new_signed(contact_info.clone(), &keypair)],
+        ))
+        .unwrap();
+        assert_eq!(check_gossip_packet(&push), Ok(()));
+
+        assert_eq!(check_gossip_packet(&[]), Err(PacketRejection::Size));
+        assert_eq!(
+            check_gossip_packet(&[0; PACKET_DATA_SIZE + 1]),
+            Err(PacketRejection::Size)
+        );
+        assert_eq!(
+            check_gossip_packet(&[8, 0, 0, 0]),
+            Err(PacketRejection::MessageType)
+        );
+
+        // Values which cannot fit in the packet
+        let mut oversized = push.clone();
+        oversized[36..44].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert_eq!(
+            check_gossip_packet(&oversized),
+            Err(PacketRejection::LengthPrefix)
+        );
+        assert_eq!(
+            check_gossip_packet(&push[..40]),
+            Err(PacketRejection::LengthPrefix)
+        );
+        assert_eq!(
+            check_gossip_packet(&push[..push.len() - 10]),
+            Err(PacketRejection::Deserialize)
+        );
+
+        let mut prune_data = PruneData::default();
+        prune_data.wallclock = MAX_WALLCLOCK;
+        let prune = serialize(&Protocol::PruneMessage(Pubkey::default(), prune_data)).unwrap();
+        assert_eq!(check_gossip_packet(&prune), Err(PacketRejection::Sanitize));
+
+        let unsigned = serialize(&Protocol::PushMessage(
+            keypair.pubkey(),
+            vec![CrdsValue::new_unsigned(contact_info)],
+        ))
+        .unwrap();
+        assert_eq!(check_gossip_packet(&unsigned), Err(PacketRejection::Verify));
+
+        let value = CrdsValue::new_signed(contact_info, &keypair);
+        let payload = gossip_compression::compress(&[value.clone()]).unwrap();
+        let compressed =
+            serialize(&Protocol::CompressedPushMessage(keypair.pubkey(), payload)).unwrap();
+        match parse_protocol(&compressed) {
+            Ok(Protocol::PushMessage(from, values)) => {
+                assert_eq!(from, keypair.pubkey());
+                assert_eq!(values, vec![value]);
+            }
+            _ => panic!("invalid compressed push message"),
+        }
+        let garbage = serialize(&Protocol::CompressedPullResponse(
+            keypair.pubkey(),
+            vec![1; 64],
+        ))
+        .unwrap();
+        assert_eq!(
+            check_gossip_packet(&garbage),
+            Err(PacketRejection::Decompress)
+        );
+    }
+
     // computes the maximum size for pull request blooms
     fn max_bloom_size() -> usize {
         let filter_size = serialized_size(&CrdsFilter::default())
@@ -3993,4 +5381,42 @@ mod tests {
         );
         assert_eq!(cluster_info.my_shred_version(), 1);
     }
+
+    #[test]
+    fn test_crds_cache_save_and_load() {
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(ContactInfo::new_localhost(
+            &solana_sdk::pubkey::new_rand(),
+            timestamp(),
+        ));
+        let peer_keypair = Keypair::new();
+        let peer = ContactInfo::new_localhost(&peer_keypair.pubkey(), timestamp());
+        let value = CrdsValue::new_signed(CrdsData::ContactInfo(peer.clone()), &peer_keypair);
+        cluster_info
+            .gossip
+            .write()
+            .unwrap()
+            .crds
+            .insert(value, timestamp())
+            .unwrap();
+        // A value whose signature does not match its contents must not be loaded
+        let forged = ContactInfo::new_localhost(&solana_sdk::pubkey::new_rand(), timestamp());
+        cluster_info.insert_info(forged.clone());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let crds_cache_path = temp_dir.path().join(CRDS_CACHE_FILE);
+        assert_eq!(cluster_info.save_crds_cache(&crds_cache_path).unwrap(), 2);
+
+        let restarted = ClusterInfo::new_with_invalid_keypair(ContactInfo::new_localhost(
+            &solana_sdk::pubkey::new_rand(),
+            timestamp(),
+        ));
+        assert_eq!(restarted.load_crds_cache(&crds_cache_path).unwrap(), 1);
+        assert_eq!(
+            restarted.lookup_contact_info(&peer.id, |ci| ci.clone()),
+            Some(peer)
+        );
+        assert!(restarted
+            .lookup_contact_info(&forged.id, |ci| ci.clone())
+            .is_none());
+    }
 }
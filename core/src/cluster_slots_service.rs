@@ -2,7 +2,7 @@ use crate::{cluster_info::ClusterInfo, cluster_slots::ClusterSlots};
 use solana_ledger::blockstore::{Blockstore, CompletedSlotsReceiver};
 use solana_measure::measure::Measure;
 use solana_runtime::bank_forks::BankForks;
-use solana_sdk::{clock::Slot, pubkey::Pubkey};
+use solana_sdk::{clock::Slot, feature_set, pubkey::Pubkey};
 use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -41,7 +41,12 @@ impl ClusterSlotsService {
     ) -> Self {
         let id = cluster_info.id();
         Self::initialize_lowest_slot(id, &blockstore, &cluster_info);
-        Self::initialize_epoch_slots(&blockstore, &cluster_info, &completed_slots_receiver);
+        Self::initialize_epoch_slots(
+            &blockstore,
+            &bank_forks,
+            &cluster_info,
+            &completed_slots_receiver,
+        );
         let t_cluster_slots_service = Builder::new()
             .name("solana-cluster-slots-service".to_string())
             .spawn(move || {
@@ -87,7 +92,7 @@ impl ClusterSlotsService {
             lowest_slot_elapsed.stop();
             let mut update_completed_slots_elapsed =
                 Measure::start("update_completed_slots_elapsed");
-            Self::update_completed_slots(&completed_slots_receiver, &cluster_info);
+            Self::update_completed_slots(&completed_slots_receiver, &bank_forks, &cluster_info);
             cluster_slots.update(new_root, &cluster_info, &bank_forks);
             update_completed_slots_elapsed.stop();
 
@@ -119,6 +124,7 @@ impl ClusterSlotsService {
 
     fn update_completed_slots(
         completed_slots_receiver: &CompletedSlotsReceiver,
+        bank_forks: &RwLock<BankForks>,
         cluster_info: &ClusterInfo,
     ) {
         let mut slots: Vec<Slot> = vec![];
@@ -127,7 +133,27 @@ impl ClusterSlotsService {
         }
         slots.sort();
         if !slots.is_empty() {
-            cluster_info.push_epoch_slots(&slots);
+            Self::push_epoch_slots(&slots, bank_forks, cluster_info);
+        }
+    }
+
+    /// Publishes the slots as EpochSlotsShard values once the cluster deserializes them, see
+    /// the `epoch_slots` module
+    fn push_epoch_slots(
+        slots: &[Slot],
+        bank_forks: &RwLock<BankForks>,
+        cluster_info: &ClusterInfo,
+    ) {
+        let sharded = bank_forks
+            .read()
+            .unwrap()
+            .root_bank()
+            .feature_set
+            .is_active(&feature_set::sharded_epoch_slots::id());
+        if sharded {
+            cluster_info.push_epoch_slots_shards(slots);
+        } else {
+            cluster_info.push_epoch_slots(slots);
         }
     }
 
@@ -145,6 +171,7 @@ impl ClusterSlotsService {
 
     fn initialize_epoch_slots(
         blockstore: &Blockstore,
+        bank_forks: &RwLock<BankForks>,
         cluster_info: &ClusterInfo,
         completed_slots_receiver: &CompletedSlotsReceiver,
     ) {
@@ -166,7 +193,7 @@ impl ClusterSlotsService {
         slots.sort();
         slots.dedup();
         if !slots.is_empty() {
-            cluster_info.push_epoch_slots(&slots);
+            Self::push_epoch_slots(&slots, bank_forks, cluster_info);
         }
     }
 }
@@ -1,6 +1,7 @@
 use crate::{
     progress_map::{LockoutIntervals, ProgressMap},
     pubkey_references::PubkeyReferences,
+    tower_storage::TowerStorage,
 };
 use chrono::prelude::*;
 use solana_ledger::{ancestor_iterator::AncestorIterator, blockstore::Blockstore, blockstore_db};
@@ -22,10 +23,7 @@ use solana_vote_program::{
 use std::{
     cmp::Ordering,
     collections::{HashMap, HashSet},
-    fs::{self, File},
-    io::BufReader,
     ops::Bound::{Included, Unbounded},
-    path::{Path, PathBuf},
     sync::Arc,
 };
 use thiserror::Error;
@@ -101,10 +99,6 @@ pub struct Tower {
     last_vote: Vote,
     last_timestamp: BlockTimestamp,
     #[serde(skip)]
-    path: PathBuf,
-    #[serde(skip)]
-    tmp_path: PathBuf, // used before atomic fs::rename()
-    #[serde(skip)]
     // Restored last voted slot which cannot be found in SlotHistory at replayed root
     // (This is a special field for slashing-free validator restart with edge cases).
     // This could be emptied after some time; but left intact indefinitely for easier
@@ -125,8 +119,6 @@ impl Default for Tower {
             lockouts: VoteState::default(),
             last_vote: Vote::default(),
             last_timestamp: BlockTimestamp::default(),
-            path: PathBuf::default(),
-            tmp_path: PathBuf::default(),
             stray_restored_slot: Option::default(),
             last_switch_threshold_check: Option::default(),
         };
@@ -142,14 +134,9 @@ impl Tower {
         vote_account_pubkey: &Pubkey,
         root: Slot,
         bank: &Bank,
-        path: &Path,
     ) -> Self {
-        let path = Self::get_filename(&path, node_pubkey);
-        let tmp_path = Self::get_tmp_filename(&path);
         let mut tower = Self {
             node_pubkey: *node_pubkey,
-            path,
-            tmp_path,
             ..Tower::default()
         };
         tower.initialize_lockouts_from_bank(vote_account_pubkey, root, bank);
@@ -176,7 +163,6 @@ impl Tower {
 
     pub fn new_from_bankforks(
         bank_forks: &BankForks,
-        ledger_path: &Path,
         my_pubkey: &Pubkey,
         vote_account: &Pubkey,
     ) -> Self {
@@ -199,13 +185,7 @@ impl Tower {
             Tower::find_heaviest_bank(&bank_forks, &my_pubkey).unwrap_or_else(|| root_bank.clone())
         };
 
-        Self::new(
-            &my_pubkey,
-            &vote_account,
-            root,
-            &heaviest_bank,
-            &ledger_path,
-        )
+        Self::new(&my_pubkey, &vote_account, root, &heaviest_bank)
     }
 
     pub(crate) fn collect_vote_lockouts<F>(
@@ -1095,16 +1075,11 @@ impl Tower {
         self.lockouts.root_slot = Some(root);
     }
 
-    pub fn get_filename(path: &Path, node_pubkey: &Pubkey) -> PathBuf {
-        path.join(format!("tower-{}", node_pubkey))
-            .with_extension("bin")
-    }
-
-    pub fn get_tmp_filename(path: &Path) -> PathBuf {
-        path.with_extension("bin.new")
-    }
-
-    pub fn save(&self, node_keypair: &Arc<Keypair>) -> Result<()> {
+    pub fn save(
+        &self,
+        tower_storage: &dyn TowerStorage,
+        node_keypair: &Arc<Keypair>,
+    ) -> Result<()> {
         let mut measure = Measure::start("tower_save-ms");
 
         if self.node_pubkey != node_keypair.pubkey() {
@@ -1115,18 +1090,9 @@ impl Tower {
             )));
         }
 
-        let filename = &self.path;
-        let new_filename = &self.tmp_path;
-        {
-            // overwrite anything if exists
-            let mut file = File::create(&new_filename)?;
-            let saved_tower = SavedTower::new(self, node_keypair)?;
-            bincode::serialize_into(&mut file, &saved_tower)?;
-            // file.sync_all() hurts performance; pipeline sync-ing and submitting votes to the cluster!
-        }
+        let saved_tower = SavedTower::new(self, node_keypair)?;
+        tower_storage.store(&self.node_pubkey, &saved_tower)?;
         trace!("persisted votes: {:?}", self.voted_slots());
-        fs::rename(&new_filename, &filename)?;
-        // self.path.parent().sync_all() hurts performance same as the above sync
 
         measure.stop();
         inc_new_counter_info!("tower_save-ms", measure.as_ms() as usize);
@@ -1134,22 +1100,12 @@ impl Tower {
         Ok(())
     }
 
-    pub fn restore(path: &Path, node_pubkey: &Pubkey) -> Result<Self> {
-        let filename = Self::get_filename(path, node_pubkey);
-
-        // Ensure to create parent dir here, because restore() precedes save() always
-        fs::create_dir_all(&filename.parent().unwrap())?;
-
-        let file = File::open(&filename)?;
-        let mut stream = BufReader::new(file);
-
-        let saved_tower: SavedTower = bincode::deserialize_from(&mut stream)?;
+    pub fn restore(tower_storage: &dyn TowerStorage, node_pubkey: &Pubkey) -> Result<Self> {
+        let saved_tower = tower_storage.load(node_pubkey)?;
         if !saved_tower.verify(node_pubkey) {
             return Err(TowerError::InvalidSignature);
         }
-        let mut tower = saved_tower.deserialize()?;
-        tower.path = filename;
-        tower.tmp_path = Self::get_tmp_filename(&tower.path);
+        let tower = saved_tower.deserialize()?;
 
         // check that the tower actually belongs to this node
         if &tower.node_pubkey != node_pubkey {
@@ -1160,6 +1116,71 @@ impl Tower {
         }
         Ok(tower)
     }
+
+    // Used to move a tower between machines; the signature of the saved tower is not exported
+    // because the tower is re-signed by save() on import
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| e.into())
+    }
+
+    pub fn from_json(json: &str, node_pubkey: &Pubkey) -> Result<Self> {
+        let tower: Tower = serde_json::from_str(json)?;
+        if &tower.node_pubkey != node_pubkey {
+            return Err(TowerError::WrongTower(format!(
+                "node_pubkey is {:?} but found tower for {:?}",
+                node_pubkey, tower.node_pubkey
+            )));
+        }
+        if tower.lockouts.root_slot.is_none() {
+            return Err(TowerError::InconsistentWithBlockstore(
+                "tower has no root".to_string(),
+            ));
+        }
+        Ok(tower)
+    }
+
+    // Checks an imported tower against the local blockstore. Slots missing from the blockstore
+    // are tolerated, as the tower may be ahead of the ledger of a freshly bootstrapped machine,
+    // but the slots that are present must not contradict the tower.
+    pub fn check_blockstore_consistency(&self, blockstore: &Blockstore) -> Result<()> {
+        let root = self.root();
+        if root >= blockstore.lowest_slot()
+            && root <= blockstore.last_root()
+            && !blockstore.is_root(root)
+        {
+            return Err(TowerError::InconsistentWithBlockstore(format!(
+                "tower root {} is not rooted in blockstore (last root: {})",
+                root,
+                blockstore.last_root()
+            )));
+        }
+
+        let mut previous_slot = None;
+        for slot in std::iter::once(root).chain(self.voted_slots()) {
+            if blockstore.is_dead(slot) {
+                return Err(TowerError::InconsistentWithBlockstore(format!(
+                    "voted slot {} is dead in blockstore",
+                    slot
+                )));
+            }
+            if !matches!(blockstore.meta(slot), Ok(Some(_))) {
+                continue;
+            }
+            if let Some(previous_slot) = previous_slot {
+                let is_descendant = AncestorIterator::new(slot, blockstore)
+                    .take_while(|ancestor| *ancestor >= previous_slot)
+                    .any(|ancestor| ancestor == previous_slot);
+                if !is_descendant {
+                    return Err(TowerError::InconsistentWithBlockstore(format!(
+                        "voted slot {} does not descend from voted slot {} in blockstore",
+                        slot, previous_slot
+                    )));
+                }
+            }
+            previous_slot = Some(slot);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Error, Debug)]
@@ -1170,6 +1191,9 @@ pub enum TowerError {
     #[error("Serialization Error: {0}")]
     SerializeError(#[from] bincode::Error),
 
+    #[error("JSON Error: {0}")]
+    JsonError(#[from] serde_json::Error),
+
     #[error("The signature on the saved tower is invalid")]
     InvalidSignature,
 
@@ -1184,6 +1208,9 @@ pub enum TowerError {
 
     #[error("The tower is fatally inconsistent with blockstore: {0}")]
     FatallyInconsistent(&'static str),
+
+    #[error("The tower is inconsistent with blockstore: {0}")]
+    InconsistentWithBlockstore(String),
 }
 
 impl TowerError {
@@ -1275,6 +1302,7 @@ pub mod test {
         heaviest_subtree_fork_choice::HeaviestSubtreeForkChoice,
         progress_map::ForkProgress,
         replay_stage::{HeaviestForkFailures, ReplayStage},
+        tower_storage::{FileTowerStorage, TowerSyncMode},
     };
     use solana_ledger::{blockstore::make_slot_entries, get_tmp_ledger_path};
     use solana_runtime::{
@@ -1295,6 +1323,7 @@ pub mod test {
         collections::HashMap,
         fs::{remove_file, OpenOptions},
         io::{Read, Seek, SeekFrom, Write},
+        path::PathBuf,
         rc::Rc,
         sync::RwLock,
     };
@@ -2453,17 +2482,16 @@ pub mod test {
     {
         let dir = TempDir::new().unwrap();
         let identity_keypair = Arc::new(Keypair::new());
+        let tower_storage = FileTowerStorage::new(dir.path().to_path_buf(), TowerSyncMode::None);
 
         // Use values that will not match the default derived from BankForks
         let mut tower = Tower::new_for_tests(10, 0.9);
-        tower.path = Tower::get_filename(&dir.path().to_path_buf(), &identity_keypair.pubkey());
-        tower.tmp_path = Tower::get_tmp_filename(&tower.path);
 
         modify_original(&mut tower, &identity_keypair.pubkey());
 
-        tower.save(&identity_keypair).unwrap();
-        modify_serialized(&tower.path);
-        let loaded = Tower::restore(&dir.path(), &identity_keypair.pubkey());
+        tower.save(&tower_storage, &identity_keypair).unwrap();
+        modify_serialized(&tower_storage.filename(&identity_keypair.pubkey()));
+        let loaded = Tower::restore(&tower_storage, &identity_keypair.pubkey());
 
         (tower, loaded)
     }
@@ -2671,8 +2699,9 @@ pub mod test {
     fn test_load_tower_wrong_identity() {
         let identity_keypair = Arc::new(Keypair::new());
         let tower = Tower::new_with_key(&Pubkey::default());
+        let tower_storage = FileTowerStorage::new(PathBuf::default(), TowerSyncMode::None);
         assert_matches!(
-            tower.save(&identity_keypair),
+            tower.save(&tower_storage, &identity_keypair),
             Err(TowerError::WrongTower(_))
         )
     }
@@ -2723,6 +2752,81 @@ pub mod test {
         assert_matches!(loaded, Err(TowerError::IOError(_)))
     }
 
+    #[test]
+    fn test_tower_json_export_import() {
+        let dir = TempDir::new().unwrap();
+        let identity_keypair = Arc::new(Keypair::new());
+        let mut tower = Tower::new_for_tests(10, 0.9);
+        tower.node_pubkey = identity_keypair.pubkey();
+        tower.initialize_root(0);
+        tower.record_vote(1, Hash::default());
+        tower.record_vote(3, Hash::default());
+
+        let json = tower.to_json().unwrap();
+        let imported = Tower::from_json(&json, &identity_keypair.pubkey()).unwrap();
+        assert_eq!(imported.lockouts, tower.lockouts);
+        assert_eq!(imported.last_vote, tower.last_vote);
+        assert_eq!(imported.threshold_depth, tower.threshold_depth);
+
+        let tower_storage = FileTowerStorage::new(dir.path().to_path_buf(), TowerSyncMode::None);
+        imported.save(&tower_storage, &identity_keypair).unwrap();
+        let restored = Tower::restore(&tower_storage, &identity_keypair.pubkey()).unwrap();
+        assert_eq!(restored, imported);
+
+        assert_matches!(
+            Tower::from_json(&json, &Pubkey::default()),
+            Err(TowerError::WrongTower(_))
+        );
+        assert_matches!(
+            Tower::from_json("{}", &identity_keypair.pubkey()),
+            Err(TowerError::JsonError(_))
+        );
+    }
+
+    #[test]
+    fn test_tower_check_blockstore_consistency() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let (shreds, _) = make_slot_entries(1, 0, 42);
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+            let (shreds, _) = make_slot_entries(3, 1, 42);
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+            let (shreds, _) = make_slot_entries(4, 1, 42);
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+
+            let mut tower = Tower::new_with_key(&Pubkey::default());
+            tower.initialize_root(0);
+            tower.record_vote(1, Hash::default());
+            tower.record_vote(3, Hash::default());
+            assert!(tower.check_blockstore_consistency(&blockstore).is_ok());
+
+            // Slots missing from blockstore are tolerated
+            tower.record_vote(5, Hash::default());
+            assert!(tower.check_blockstore_consistency(&blockstore).is_ok());
+
+            // 4 is on a different fork than 3
+            let mut tower = Tower::new_with_key(&Pubkey::default());
+            tower.initialize_root(0);
+            tower.record_vote(3, Hash::default());
+            tower.record_vote(4, Hash::default());
+            assert_matches!(
+                tower.check_blockstore_consistency(&blockstore),
+                Err(TowerError::InconsistentWithBlockstore(_))
+            );
+
+            blockstore.set_dead_slot(1).unwrap();
+            let mut tower = Tower::new_with_key(&Pubkey::default());
+            tower.initialize_root(0);
+            tower.record_vote(1, Hash::default());
+            assert_matches!(
+                tower.check_blockstore_consistency(&blockstore),
+                Err(TowerError::InconsistentWithBlockstore(_))
+            );
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_reconcile_blockstore_roots_with_tower_normal() {
         solana_logger::setup();
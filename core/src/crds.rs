@@ -22,17 +22,18 @@
 //!     impl PartialOrd for VersionedCrdsValue
 //!
 //! A value is updated to a new version if the labels match, and the value
-//! wallclock is later, or the value hash is greater.
+//! wallclock is later, or the value hash is greater. EpochSlotsShard values
+//! are first ordered by their generation.
 
 use crate::crds_shards::CrdsShards;
-use crate::crds_value::{CrdsValue, CrdsValueLabel};
+use crate::crds_value::{CrdsData, CrdsValue, CrdsValueLabel, MAX_EPOCH_SLOTS};
 use bincode::serialize;
 use indexmap::map::{Entry, IndexMap};
 use rayon::{prelude::*, ThreadPool};
 use solana_sdk::hash::{hash, Hash};
 use solana_sdk::pubkey::Pubkey;
 use std::cmp;
-use std::collections::HashMap;
+use std::collections::{hash_map, HashMap};
 use std::ops::Index;
 
 const CRDS_SHARDS_BITS: u32 = 8;
@@ -43,6 +44,8 @@ pub struct Crds {
     pub table: IndexMap<CrdsValueLabel, VersionedCrdsValue>,
     pub num_inserts: usize,
     pub shards: CrdsShards,
+    /// Number of values in the table per origin pubkey
+    origins: HashMap<Pubkey, usize>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -68,6 +71,8 @@ impl PartialOrd for VersionedCrdsValue {
     fn partial_cmp(&self, other: &VersionedCrdsValue) -> Option<cmp::Ordering> {
         if self.value.label() != other.value.label() {
             None
+        } else if self.generation() != other.generation() {
+            Some(self.generation().cmp(&other.generation()))
         } else if self.value.wallclock() == other.value.wallclock() {
             Some(self.value_hash.cmp(&other.value_hash))
         } else {
@@ -76,6 +81,10 @@ impl PartialOrd for VersionedCrdsValue {
     }
 }
 impl VersionedCrdsValue {
+    fn generation(&self) -> Option<u64> {
+        self.value.epoch_slots_shard().map(|shard| shard.generation)
+    }
+
     pub fn new(local_timestamp: u64, value: CrdsValue) -> Self {
         let value_hash = hash(&serialize(&value).unwrap());
         VersionedCrdsValue {
@@ -87,12 +96,43 @@ impl VersionedCrdsValue {
     }
 }
 
+/// Purge timeouts by type of value, bounding the pubkey specific timeouts: votes are purged
+/// after at most `vote` ms since active validators vote every slot, while contact infos are
+/// kept for at least `contact_info` ms so that nodes are not dropped between two pushes of their
+/// contact info. Values with an infinite timeout, those of the local node, are never purged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CrdsTtls {
+    pub vote: u64,
+    pub contact_info: u64,
+}
+
+impl Default for CrdsTtls {
+    fn default() -> Self {
+        Self {
+            vote: std::u64::MAX,
+            contact_info: 0,
+        }
+    }
+}
+
+impl CrdsTtls {
+    fn timeout(&self, data: &CrdsData, timeout: u64) -> u64 {
+        match data {
+            _ if timeout == std::u64::MAX => timeout,
+            CrdsData::Vote(_, _) => timeout.min(self.vote),
+            CrdsData::ContactInfo(_) => timeout.max(self.contact_info),
+            _ => timeout,
+        }
+    }
+}
+
 impl Default for Crds {
     fn default() -> Self {
         Crds {
             table: IndexMap::new(),
             num_inserts: 0,
             shards: CrdsShards::new(CRDS_SHARDS_BITS),
+            origins: HashMap::new(),
         }
     }
 }
@@ -112,7 +152,22 @@ impl Crds {
         // New value is outdated and fails to insert, if it already exists in
         // the table with a more recent wallclock.
         let outdated = matches!(self.table.get(&label), Some(current) if new_value <= *current);
-        (!outdated, new_value)
+        // Neither are EpochSlotsShard values replaced by the later shards of their node
+        let stale = !outdated
+            && new_value.value.epoch_slots_shard().map_or(false, |shard| {
+                self.epoch_slots_generation(&shard.epoch_slots.from)
+                    .map_or(false, |latest| shard.is_stale(latest))
+            });
+        (!outdated && !stale, new_value)
+    }
+
+    /// Latest generation of the EpochSlotsShard values of `origin` in the table
+    pub fn epoch_slots_generation(&self, origin: &Pubkey) -> Option<u64> {
+        (0..MAX_EPOCH_SLOTS)
+            .filter_map(|ix| self.lookup(&CrdsValueLabel::EpochSlotsShard(ix, *origin)))
+            .filter_map(CrdsValue::epoch_slots_shard)
+            .map(|shard| shard.generation)
+            .max()
     }
     /// insert the new value, returns the old value if insert succeeds
     pub fn insert_versioned(
@@ -123,6 +178,7 @@ impl Crds {
         match self.table.entry(label) {
             Entry::Vacant(entry) => {
                 assert!(self.shards.insert(entry.index(), &new_value));
+                *self.origins.entry(new_value.value.pubkey()).or_default() += 1;
                 entry.insert(new_value);
                 self.num_inserts += 1;
                 Ok(None)
@@ -160,6 +216,18 @@ impl Crds {
         self.table.get(label)
     }
 
+    /// Number of values in the table originating from `origin`
+    pub fn num_values(&self, origin: &Pubkey) -> usize {
+        self.origins.get(origin).copied().unwrap_or_default()
+    }
+
+    /// Origins of the values in the table, with the number of values of each
+    pub fn origins(&self) -> impl Iterator<Item = (&Pubkey, usize)> {
+        self.origins
+            .iter()
+            .map(|(origin, num_values)| (origin, *num_values))
+    }
+
     fn update_label_timestamp(&mut self, id: &CrdsValueLabel, now: u64) {
         if let Some(e) = self.table.get_mut(id) {
             e.local_timestamp = cmp::max(e.local_timestamp, now);
@@ -175,11 +243,13 @@ impl Crds {
 
     /// Find all the keys that are older or equal to the timeout.
     /// * timeouts - Pubkey specific timeouts with Pubkey::default() as the default timeout.
+    /// * ttls - Per value type bounds on the pubkey specific timeouts.
     pub fn find_old_labels(
         &self,
         thread_pool: &ThreadPool,
         now: u64,
         timeouts: &HashMap<Pubkey, u64>,
+        ttls: &CrdsTtls,
     ) -> Vec<CrdsValueLabel> {
         let default_timeout = *timeouts
             .get(&Pubkey::default())
@@ -190,7 +260,8 @@ impl Crds {
                 .with_min_len(1024)
                 .filter_map(|(k, v)| {
                     let timeout = timeouts.get(&k.pubkey()).unwrap_or(&default_timeout);
-                    if v.local_timestamp.saturating_add(*timeout) <= now {
+                    let timeout = ttls.timeout(&v.value.data, *timeout);
+                    if v.local_timestamp.saturating_add(timeout) <= now {
                         Some(k.clone())
                     } else {
                         None
@@ -200,9 +271,42 @@ impl Crds {
         })
     }
 
+    /// Evicts the values with the lowest eviction priority until the table holds at most `cap`
+    /// values. Values of `keep`, the local node, are never evicted.
+    /// Returns the evicted values.
+    pub fn trim(
+        &mut self,
+        cap: usize,
+        keep: &Pubkey,
+        stakes: &HashMap<Pubkey, u64>,
+    ) -> Vec<VersionedCrdsValue> {
+        if self.table.len() <= cap {
+            return vec![];
+        }
+        let mut labels: Vec<_> = self
+            .table
+            .iter()
+            .filter(|(label, _)| label.pubkey() != *keep)
+            .map(|(label, value)| (eviction_priority(value, stakes), label.clone()))
+            .collect();
+        let num_evicted = (self.table.len() - cap).min(labels.len());
+        labels.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        labels
+            .into_iter()
+            .take(num_evicted)
+            .filter_map(|(_, label)| self.remove(&label))
+            .collect()
+    }
+
     pub fn remove(&mut self, key: &CrdsValueLabel) -> Option<VersionedCrdsValue> {
         let (index, _, value) = self.table.swap_remove_full(key)?;
         assert!(self.shards.remove(index, &value));
+        if let hash_map::Entry::Occupied(mut entry) = self.origins.entry(value.value.pubkey()) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
         // The previously last element in the table is now moved to the
         // 'index' position. Shards need to be updated accordingly.
         if index < self.table.len() {
@@ -214,13 +318,34 @@ impl Crds {
     }
 }
 
+/// Values are evicted from a full table in ascending order of (tier, stake, local timestamp):
+/// EpochSlots and EpochSlotsShard values of unstaked nodes (spies) first, then the other values
+/// of unstaked nodes, their contact infos, the other values of staked nodes, and finally the
+/// contact infos and votes of staked nodes.
+fn eviction_priority(value: &VersionedCrdsValue, stakes: &HashMap<Pubkey, u64>) -> (u8, u64, u64) {
+    let stake = stakes
+        .get(&value.value.pubkey())
+        .copied()
+        .unwrap_or_default();
+    let tier = match (&value.value.data, stake > 0) {
+        (CrdsData::EpochSlots(_, _), false) | (CrdsData::EpochSlotsShard(_, _), false) => 0,
+        (CrdsData::ContactInfo(_), false) => 2,
+        (_, false) => 1,
+        (CrdsData::ContactInfo(_), true) | (CrdsData::Vote(_, _), true) => 4,
+        (_, true) => 3,
+    };
+    (tier, stake, value.local_timestamp)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::contact_info::ContactInfo;
-    use crate::crds_value::CrdsData;
+    use crate::crds_value::{LowestSlot, Vote};
+    use crate::epoch_slots::{EpochSlots, EpochSlotsShard};
     use rand::{thread_rng, Rng};
     use rayon::ThreadPoolBuilder;
+    use solana_sdk::transaction::Transaction;
 
     #[test]
     fn test_insert() {
@@ -299,15 +424,17 @@ mod test {
         assert_eq!(crds.insert(val.clone(), 1), Ok(None));
         let mut set = HashMap::new();
         set.insert(Pubkey::default(), 0);
-        assert!(crds.find_old_labels(&thread_pool, 0, &set).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 0, &set, &CrdsTtls::default())
+            .is_empty());
         set.insert(Pubkey::default(), 1);
         assert_eq!(
-            crds.find_old_labels(&thread_pool, 2, &set),
+            crds.find_old_labels(&thread_pool, 2, &set, &CrdsTtls::default()),
             vec![val.label()]
         );
         set.insert(Pubkey::default(), 2);
         assert_eq!(
-            crds.find_old_labels(&thread_pool, 4, &set),
+            crds.find_old_labels(&thread_pool, 4, &set, &CrdsTtls::default()),
             vec![val.label()]
         );
     }
@@ -320,23 +447,79 @@ mod test {
         let val = CrdsValue::new_rand(&mut rng);
         timeouts.insert(Pubkey::default(), 3);
         assert_eq!(crds.insert(val.clone(), 0), Ok(None));
-        assert!(crds.find_old_labels(&thread_pool, 2, &timeouts).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 2, &timeouts, &CrdsTtls::default())
+            .is_empty());
         timeouts.insert(val.pubkey(), 1);
         assert_eq!(
-            crds.find_old_labels(&thread_pool, 2, &timeouts),
+            crds.find_old_labels(&thread_pool, 2, &timeouts, &CrdsTtls::default()),
             vec![val.label()]
         );
         timeouts.insert(val.pubkey(), u64::MAX);
-        assert!(crds.find_old_labels(&thread_pool, 2, &timeouts).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 2, &timeouts, &CrdsTtls::default())
+            .is_empty());
         timeouts.insert(Pubkey::default(), 1);
-        assert!(crds.find_old_labels(&thread_pool, 2, &timeouts).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 2, &timeouts, &CrdsTtls::default())
+            .is_empty());
         timeouts.remove(&val.pubkey());
         assert_eq!(
-            crds.find_old_labels(&thread_pool, 2, &timeouts),
+            crds.find_old_labels(&thread_pool, 2, &timeouts, &CrdsTtls::default()),
             vec![val.label()]
         );
     }
     #[test]
+    fn test_find_old_records_with_ttls() {
+        let thread_pool = ThreadPoolBuilder::new().build().unwrap();
+        let mut crds = Crds::default();
+        let local = solana_sdk::pubkey::new_rand();
+        let node = solana_sdk::pubkey::new_rand();
+        let contact_info =
+            CrdsValue::new_unsigned(CrdsData::ContactInfo(ContactInfo::new_localhost(&node, 0)));
+        let vote = CrdsValue::new_unsigned(CrdsData::Vote(
+            0,
+            Vote::new(&node, Transaction::default(), 0),
+        ));
+        let lowest_slot =
+            CrdsValue::new_unsigned(CrdsData::LowestSlot(0, LowestSlot::new(node, 0, 0)));
+        let local_vote = CrdsValue::new_unsigned(CrdsData::Vote(
+            0,
+            Vote::new(&local, Transaction::default(), 0),
+        ));
+        for value in vec![&contact_info, &vote, &lowest_slot, &local_vote] {
+            assert_eq!(crds.insert(value.clone(), 0), Ok(None));
+        }
+        let mut timeouts = HashMap::new();
+        timeouts.insert(Pubkey::default(), 4);
+        timeouts.insert(local, u64::MAX);
+        let ttls = CrdsTtls {
+            vote: 2,
+            contact_info: 6,
+        };
+        // Votes expire first, then the other values, then the contact infos
+        assert!(crds
+            .find_old_labels(&thread_pool, 1, &timeouts, &ttls)
+            .is_empty());
+        assert_eq!(
+            crds.find_old_labels(&thread_pool, 2, &timeouts, &ttls),
+            vec![vote.label()]
+        );
+        assert_eq!(
+            crds.find_old_labels(&thread_pool, 4, &timeouts, &ttls),
+            vec![vote.label(), lowest_slot.label()]
+        );
+        assert_eq!(
+            crds.find_old_labels(&thread_pool, 6, &timeouts, &ttls),
+            vec![contact_info.label(), vote.label(), lowest_slot.label()]
+        );
+        // The local node's values are never purged
+        assert!(crds
+            .find_old_labels(&thread_pool, u64::MAX, &timeouts, &ttls)
+            .iter()
+            .all(|label| label.pubkey() != local));
+    }
+    #[test]
     fn test_remove_default() {
         let thread_pool = ThreadPoolBuilder::new().build().unwrap();
         let mut crds = Crds::default();
@@ -345,11 +528,13 @@ mod test {
         let mut set = HashMap::new();
         set.insert(Pubkey::default(), 1);
         assert_eq!(
-            crds.find_old_labels(&thread_pool, 2, &set),
+            crds.find_old_labels(&thread_pool, 2, &set, &CrdsTtls::default()),
             vec![val.label()]
         );
         crds.remove(&val.label());
-        assert!(crds.find_old_labels(&thread_pool, 2, &set).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 2, &set, &CrdsTtls::default())
+            .is_empty());
     }
     #[test]
     fn test_find_old_records_staked() {
@@ -361,24 +546,30 @@ mod test {
         //now < timestamp
         set.insert(Pubkey::default(), 0);
         set.insert(val.pubkey(), 0);
-        assert!(crds.find_old_labels(&thread_pool, 0, &set).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 0, &set, &CrdsTtls::default())
+            .is_empty());
 
         //pubkey shouldn't expire since its timeout is MAX
         set.insert(val.pubkey(), std::u64::MAX);
-        assert!(crds.find_old_labels(&thread_pool, 2, &set).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 2, &set, &CrdsTtls::default())
+            .is_empty());
 
         //default has max timeout, but pubkey should still expire
         set.insert(Pubkey::default(), std::u64::MAX);
         set.insert(val.pubkey(), 1);
         assert_eq!(
-            crds.find_old_labels(&thread_pool, 2, &set),
+            crds.find_old_labels(&thread_pool, 2, &set, &CrdsTtls::default()),
             vec![val.label()]
         );
 
         set.insert(val.pubkey(), 2);
-        assert!(crds.find_old_labels(&thread_pool, 2, &set).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 2, &set, &CrdsTtls::default())
+            .is_empty());
         assert_eq!(
-            crds.find_old_labels(&thread_pool, 3, &set),
+            crds.find_old_labels(&thread_pool, 3, &set, &CrdsTtls::default()),
             vec![val.label()]
         );
     }
@@ -388,6 +579,12 @@ mod test {
         fn check_crds_shards(crds: &Crds) {
             crds.shards
                 .check(&crds.table.values().cloned().collect::<Vec<_>>());
+            // Per origin counts stay in sync with the table as well.
+            let mut origins: HashMap<Pubkey, usize> = HashMap::new();
+            for label in crds.table.keys() {
+                *origins.entry(label.pubkey()).or_default() += 1;
+            }
+            assert_eq!(origins, crds.origins);
         }
 
         let mut crds = Crds::default();
@@ -436,11 +633,66 @@ mod test {
         set.insert(Pubkey::default(), std::u64::MAX);
         set.insert(val.pubkey(), 1);
         assert_eq!(
-            crds.find_old_labels(&thread_pool, 2, &set),
+            crds.find_old_labels(&thread_pool, 2, &set, &CrdsTtls::default()),
             vec![val.label()]
         );
         crds.remove(&val.label());
-        assert!(crds.find_old_labels(&thread_pool, 2, &set).is_empty());
+        assert!(crds
+            .find_old_labels(&thread_pool, 2, &set, &CrdsTtls::default())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_trim() {
+        let mut crds = Crds::default();
+        let local = solana_sdk::pubkey::new_rand();
+        let staked = solana_sdk::pubkey::new_rand();
+        let spy = solana_sdk::pubkey::new_rand();
+        let stakes: HashMap<_, _> = vec![(local, 1), (staked, 10)].into_iter().collect();
+        let values = vec![
+            CrdsData::ContactInfo(ContactInfo::new_localhost(&staked, 0)),
+            CrdsData::Vote(0, Vote::new(&staked, Transaction::default(), 0)),
+            CrdsData::EpochSlots(0, EpochSlots::new(staked, 0)),
+            CrdsData::ContactInfo(ContactInfo::new_localhost(&spy, 0)),
+            CrdsData::EpochSlots(0, EpochSlots::new(spy, 0)),
+            CrdsData::LowestSlot(0, LowestSlot::new(spy, 0, 0)),
+            CrdsData::ContactInfo(ContactInfo::new_localhost(&local, 0)),
+            CrdsData::EpochSlots(0, EpochSlots::new(local, 0)),
+        ];
+        for value in values {
+            assert_eq!(crds.insert(CrdsValue::new_unsigned(value), 0), Ok(None));
+        }
+        assert!(crds.trim(8, &local, &stakes).is_empty());
+        assert_eq!(crds.num_values(&spy), 3);
+
+        // Spies are evicted first, EpochSlots before the contact info
+        let evicted: Vec<_> = crds
+            .trim(4, &local, &stakes)
+            .into_iter()
+            .map(|value| value.value.label())
+            .collect();
+        assert_eq!(
+            evicted,
+            vec![
+                CrdsValueLabel::EpochSlots(0, spy),
+                CrdsValueLabel::LowestSlot(spy),
+                CrdsValueLabel::ContactInfo(spy),
+                CrdsValueLabel::EpochSlots(0, staked),
+            ]
+        );
+        assert!(crds.lookup(&CrdsValueLabel::ContactInfo(staked)).is_some());
+        assert!(crds.lookup(&CrdsValueLabel::Vote(0, staked)).is_some());
+        assert_eq!(crds.num_values(&spy), 0);
+        assert_eq!(crds.num_values(&staked), 2);
+        crds.shards
+            .check(&crds.table.values().cloned().collect::<Vec<_>>());
+
+        // The local node's values are never evicted
+        assert_eq!(crds.trim(1, &local, &stakes).len(), 2);
+        assert_eq!(crds.table.len(), 2);
+        assert!(crds.lookup(&CrdsValueLabel::EpochSlots(0, local)).is_some());
+        crds.shards
+            .check(&crds.table.values().cloned().collect::<Vec<_>>());
     }
 
     #[test]
@@ -515,6 +767,43 @@ mod test {
         assert_eq!(v2.partial_cmp(&v1), Some(cmp::Ordering::Less));
     }
     #[test]
+    fn test_epoch_slots_shard_generation_order() {
+        let origin = Pubkey::new_unique();
+        let shard = |generation, wallclock| {
+            let shard = EpochSlotsShard::new(origin, generation, wallclock);
+            CrdsValue::new_unsigned(CrdsData::EpochSlotsShard(shard.index(), shard))
+        };
+        // A later generation replaces the shard at the same index, whatever its wallclock
+        let old = VersionedCrdsValue::new(1, shard(1, 10));
+        let new = VersionedCrdsValue::new(1, shard(1 + MAX_EPOCH_SLOTS as u64, 5));
+        assert_eq!(old.value.label(), new.value.label());
+        assert!(new > old);
+        // Within a generation, the later wallclock wins
+        assert!(VersionedCrdsValue::new(1, shard(1, 11)) > old);
+
+        // Shards replaced by the later shards of their origin are not pulled back
+        let mut crds = Crds::default();
+        crds.insert(shard(2 + MAX_EPOCH_SLOTS as u64, 0), 0)
+            .unwrap();
+        assert_eq!(
+            crds.epoch_slots_generation(&origin),
+            Some(2 + MAX_EPOCH_SLOTS as u64)
+        );
+        assert!(!crds.would_insert(shard(1, 0), 0).0);
+        assert!(!crds.would_insert(shard(2, 0), 0).0);
+        assert!(crds.would_insert(shard(3, 0), 0).0);
+        assert_eq!(crds.epoch_slots_generation(&Pubkey::new_unique()), None);
+
+        // EpochSlots values are merged by wallclock alone, as before the shards
+        let epoch_slots = |wallclock| {
+            CrdsValue::new_unsigned(CrdsData::EpochSlots(0, EpochSlots::new(origin, wallclock)))
+        };
+        assert!(crds.would_insert(epoch_slots(0), 0).0);
+        crds.insert(epoch_slots(5), 0).unwrap();
+        assert!(!crds.would_insert(epoch_slots(4), 0).0);
+        assert!(crds.would_insert(epoch_slots(6), 0).0);
+    }
+    #[test]
     #[allow(clippy::neg_cmp_op_on_partial_ord)]
     fn test_label_order() {
         let v1 = VersionedCrdsValue::new(
@@ -4,11 +4,11 @@
 //! packet::PACKET_DATA_SIZE size.
 
 use crate::{
-    crds::{Crds, VersionedCrdsValue},
+    crds::{Crds, CrdsTtls, VersionedCrdsValue},
     crds_gossip_error::CrdsGossipError,
     crds_gossip_pull::{CrdsFilter, CrdsGossipPull, ProcessPullStats},
     crds_gossip_push::{CrdsGossipPush, CFG as GOSSIP_PUSH_CFG},
-    crds_value::{CrdsValue, CrdsValueLabel},
+    crds_value::{CrdsData, CrdsValue, CrdsValueLabel},
 };
 use rayon::ThreadPool;
 use solana_sdk::{hash::Hash, pubkey::Pubkey};
@@ -16,6 +16,118 @@ use std::collections::{HashMap, HashSet};
 
 toml_config::package_config! {
     CRDS_GOSSIP_DEFAULT_BLOOM_ITEMS: usize,
+    CRDS_MAX_VALUES_PER_UNSTAKED_ORIGIN: usize,
+    CRDS_MAX_UNSTAKED_VALUES: usize,
+    CRDS_STAKED_ONLY_TABLE_SIZE: usize,
+}
+
+/// Minimum time, in milliseconds, between two updates of the `ValidatorInfo` of an origin
+pub const VALIDATOR_INFO_MIN_UPDATE_INTERVAL_MS: u64 = 60_000;
+
+/// Number of values dropped by `CrdsInsertPolicy`, by the limit they ran into
+#[derive(Debug, Default, PartialEq)]
+pub struct InsertPolicyStats {
+    pub origin_limit: usize,
+    pub unstaked_limit: usize,
+    pub table_budget: usize,
+    pub validator_info_limit: usize,
+}
+
+/// Limits how many values unstaked origins (spies, or a sybil attacker spinning up keypairs) may
+/// add to the table, so that they cannot flood it with values until the next purge:
+/// * at most `CRDS_MAX_VALUES_PER_UNSTAKED_ORIGIN` values per unstaked origin,
+/// * at most `CRDS_MAX_UNSTAKED_VALUES` values of unstaked origins in total,
+/// * none at all once the table holds `CRDS_STAKED_ONLY_TABLE_SIZE` values, keeping the room
+///   left under the table size budget for staked origins.
+///
+/// Values which update a value already in the table, and values of staked origins and of the
+/// local node, are always let through, except for `ValidatorInfo`: only staked origins may
+/// publish one, and no more often than every `VALIDATOR_INFO_MIN_UPDATE_INTERVAL_MS`. Until the
+/// stakes are known, every origin is treated as staked.
+pub struct CrdsInsertPolicy<'a> {
+    crds: &'a Crds,
+    id: Pubkey,
+    stakes: &'a HashMap<Pubkey, u64>,
+    num_unstaked_values: usize,
+    num_values: usize,
+    // Values let through so far for each unstaked origin which are not yet in the table
+    num_new_values: HashMap<Pubkey, usize>,
+    pub stats: InsertPolicyStats,
+}
+
+impl<'a> CrdsInsertPolicy<'a> {
+    fn new(crds: &'a Crds, id: Pubkey, stakes: &'a HashMap<Pubkey, u64>) -> Self {
+        let is_staked = |origin: &Pubkey| stakes.get(origin).copied().unwrap_or_default() > 0;
+        let num_unstaked_values = crds
+            .origins()
+            .filter(|(origin, _)| **origin != id && !is_staked(origin))
+            .map(|(_, num_values)| num_values)
+            .sum();
+        Self {
+            crds,
+            id,
+            stakes,
+            num_unstaked_values,
+            num_values: crds.table.len(),
+            num_new_values: HashMap::new(),
+            stats: InsertPolicyStats::default(),
+        }
+    }
+
+    fn is_staked(&self, origin: &Pubkey) -> bool {
+        self.stakes.is_empty()
+            || *origin == self.id
+            || self.stakes.get(origin).copied().unwrap_or_default() > 0
+    }
+
+    fn check(&mut self, value: &CrdsValue) -> bool {
+        let origin = value.pubkey();
+        if let CrdsData::ValidatorInfo(info) = &value.data {
+            if !self.check_validator_info(info.wallclock, &value.label()) {
+                self.stats.validator_info_limit += 1;
+                return false;
+            }
+        }
+        if self.is_staked(&origin) || self.crds.table.contains_key(&value.label()) {
+            return true;
+        }
+        let num_new_values = self.num_new_values.entry(origin).or_default();
+        if self.num_values >= CFG.CRDS_STAKED_ONLY_TABLE_SIZE {
+            self.stats.table_budget += 1;
+            false
+        } else if self.num_unstaked_values >= CFG.CRDS_MAX_UNSTAKED_VALUES {
+            self.stats.unstaked_limit += 1;
+            false
+        } else if self.crds.num_values(&origin) + *num_new_values
+            >= CFG.CRDS_MAX_VALUES_PER_UNSTAKED_ORIGIN
+        {
+            self.stats.origin_limit += 1;
+            false
+        } else {
+            *num_new_values += 1;
+            self.num_unstaked_values += 1;
+            self.num_values += 1;
+            true
+        }
+    }
+
+    fn check_validator_info(&self, wallclock: u64, label: &CrdsValueLabel) -> bool {
+        if !self.is_staked(&label.pubkey()) {
+            return false;
+        }
+        match self.crds.table.get(label) {
+            // Older or identical values are dropped by the table itself
+            Some(current) if wallclock > current.value.wallclock() => {
+                wallclock >= current.value.wallclock() + VALIDATOR_INFO_MIN_UPDATE_INTERVAL_MS
+            }
+            _ => true,
+        }
+    }
+
+    /// Drops the values which the table should not take in
+    pub fn filter(&mut self, values: &mut Vec<CrdsValue>) {
+        values.retain(|value| self.check(value))
+    }
 }
 
 #[derive(Clone)]
@@ -47,6 +159,12 @@ impl CrdsGossip {
         self.shred_version = shred_version;
     }
 
+    /// Returns the policy limiting the values unstaked origins may add to the table, to filter
+    /// incoming values with before they are inserted
+    pub fn insert_policy<'a>(&'a self, stakes: &'a HashMap<Pubkey, u64>) -> CrdsInsertPolicy<'a> {
+        CrdsInsertPolicy::new(&self.crds, self.id, stakes)
+    }
+
     /// process a push message to the network
     pub fn process_push_message(
         &mut self,
@@ -241,6 +359,7 @@ impl CrdsGossip {
         thread_pool: &ThreadPool,
         now: u64,
         timeouts: &HashMap<Pubkey, u64>,
+        ttls: &CrdsTtls,
     ) -> usize {
         let mut rv = 0;
         if now > self.push.msg_timeout {
@@ -258,7 +377,7 @@ impl CrdsGossip {
             assert_eq!(timeouts[&Pubkey::default()], min);
             rv = self
                 .pull
-                .purge_active(thread_pool, &mut self.crds, now, &timeouts);
+                .purge_active(thread_pool, &mut self.crds, now, &timeouts, ttls);
         }
         if now > 5 * self.pull.crds_timeout {
             let min = now - 5 * self.pull.crds_timeout;
@@ -267,6 +386,49 @@ impl CrdsGossip {
         self.pull.purge_failed_inserts(now);
         rv
     }
+
+    /// Evicts values once the table holds more than `cap` values, see `Crds::trim`.
+    /// Returns the number of evicted values.
+    pub fn trim(&mut self, cap: usize, stakes: &HashMap<Pubkey, u64>) -> usize {
+        let evicted = self.crds.trim(cap, &self.id, stakes);
+        if evicted.is_empty() {
+            return 0;
+        }
+        let num_staked = evicted
+            .iter()
+            .filter(|value| {
+                stakes
+                    .get(&value.value.pubkey())
+                    .copied()
+                    .unwrap_or_default()
+                    > 0
+            })
+            .count();
+        let num_epoch_slots = evicted
+            .iter()
+            .filter(|value| {
+                matches!(
+                    value.value.data,
+                    CrdsData::EpochSlots(_, _) | CrdsData::EpochSlotsShard(_, _)
+                )
+            })
+            .count();
+        datapoint_info!(
+            "crds_gossip-trim",
+            ("num_evicted", evicted.len(), i64),
+            ("num_evicted_staked", num_staked, i64),
+            ("num_evicted_epoch_slots", num_epoch_slots, i64),
+            ("table_size", self.crds.table.len(), i64),
+        );
+        // Like purged values, evicted values are advertised in pull requests so that peers do
+        // not send them right back
+        self.pull.purged_values.extend(
+            evicted
+                .iter()
+                .map(|value| (value.value_hash, value.local_timestamp)),
+        );
+        evicted.len()
+    }
 }
 
 /// Computes a normalized(log of actual stake) stake
@@ -290,9 +452,11 @@ pub fn get_weight(max_weight: f32, time_since_last_selected: u32, stake: f32) ->
 mod test {
     use super::*;
     use crate::contact_info::ContactInfo;
-    use crate::crds_value::CrdsData;
+    use crate::crds_value::{CrdsData, ValidatorInfo, Vote, MAX_EPOCH_SLOTS, MAX_VOTES};
+    use crate::epoch_slots::EpochSlots;
     use solana_sdk::hash::hash;
     use solana_sdk::timing::timestamp;
+    use solana_sdk::transaction::Transaction;
 
     #[test]
     fn test_prune_errors() {
@@ -327,4 +491,99 @@ mod test {
         res = crds_gossip.process_prune_msg(&ci.id, &id, &[prune_pubkey], now, timeout);
         assert_eq!(res.err(), Some(CrdsGossipError::PruneMessageTimeout));
     }
+
+    #[test]
+    fn test_insert_policy() {
+        let mut crds_gossip = CrdsGossip::default();
+        let staked = solana_sdk::pubkey::new_rand();
+        let spy = solana_sdk::pubkey::new_rand();
+        let stakes: HashMap<_, _> = vec![(staked, 1)].into_iter().collect();
+        let epoch_slots = |origin, index, now| {
+            CrdsValue::new_unsigned(CrdsData::EpochSlots(index, EpochSlots::new(origin, now)))
+        };
+        let vote = |origin, index| {
+            CrdsValue::new_unsigned(CrdsData::Vote(
+                index,
+                Vote::new(&origin, Transaction::default(), 0),
+            ))
+        };
+        let limit = CFG.CRDS_MAX_VALUES_PER_UNSTAKED_ORIGIN;
+        // An unstaked node may publish all of its EpochSlots and Vote values, and a few others
+        assert!(limit > MAX_EPOCH_SLOTS as usize + MAX_VOTES as usize + 8);
+        assert!(limit < 2 * 256);
+
+        // Values of the unstaked origin are capped, those of the staked origin are not
+        let mut values: Vec<_> = (0..=255)
+            .map(|index| vote(spy, index))
+            .chain((0..=255).map(|index| epoch_slots(spy, index, 0)))
+            .chain((0..=255).map(|index| epoch_slots(staked, index, 0)))
+            .collect();
+        let mut insert_policy = crds_gossip.insert_policy(&stakes);
+        insert_policy.filter(&mut values);
+        assert_eq!(
+            insert_policy.stats,
+            InsertPolicyStats {
+                origin_limit: 2 * 256 - limit,
+                ..InsertPolicyStats::default()
+            }
+        );
+        assert_eq!(values.len(), limit + 256);
+        for value in values {
+            crds_gossip.crds.insert(value, 0).unwrap();
+        }
+        assert_eq!(crds_gossip.crds.num_values(&spy), limit);
+        assert_eq!(crds_gossip.crds.num_values(&staked), 256);
+
+        // Updates of values already in the table are still let through
+        let mut values = vec![epoch_slots(spy, 0, 1), epoch_slots(spy, 255, 1)];
+        let mut insert_policy = crds_gossip.insert_policy(&stakes);
+        insert_policy.filter(&mut values);
+        assert_eq!(values, vec![epoch_slots(spy, 0, 1)]);
+        assert_eq!(insert_policy.stats.origin_limit, 1);
+
+        // Until the stakes are known, no origin is capped
+        let other_spy = solana_sdk::pubkey::new_rand();
+        let mut values: Vec<_> = (0..=255).map(|index| vote(other_spy, index)).collect();
+        let no_stakes = HashMap::new();
+        let mut insert_policy = crds_gossip.insert_policy(&no_stakes);
+        insert_policy.filter(&mut values);
+        assert_eq!(values.len(), 256);
+        assert_eq!(insert_policy.stats, InsertPolicyStats::default());
+    }
+
+    #[test]
+    fn test_insert_policy_validator_info() {
+        let mut crds_gossip = CrdsGossip::default();
+        let staked = solana_sdk::pubkey::new_rand();
+        let spy = solana_sdk::pubkey::new_rand();
+        let stakes: HashMap<_, _> = vec![(staked, 1)].into_iter().collect();
+        let validator_info = |origin, wallclock| {
+            CrdsValue::new_unsigned(CrdsData::ValidatorInfo(ValidatorInfo {
+                wallclock,
+                ..ValidatorInfo::new(origin, "name".to_string(), None, None)
+            }))
+        };
+
+        // Unstaked origins may not publish their info
+        let mut values = vec![validator_info(spy, 0), validator_info(staked, 0)];
+        let mut insert_policy = crds_gossip.insert_policy(&stakes);
+        insert_policy.filter(&mut values);
+        assert_eq!(values, vec![validator_info(staked, 0)]);
+        assert_eq!(insert_policy.stats.validator_info_limit, 1);
+        crds_gossip
+            .crds
+            .insert(validator_info(staked, 0), 0)
+            .unwrap();
+
+        // Updates sooner than the minimum interval are dropped
+        let interval = VALIDATOR_INFO_MIN_UPDATE_INTERVAL_MS;
+        let mut values = vec![
+            validator_info(staked, interval - 1),
+            validator_info(staked, interval),
+        ];
+        let mut insert_policy = crds_gossip.insert_policy(&stakes);
+        insert_policy.filter(&mut values);
+        assert_eq!(values, vec![validator_info(staked, interval)]);
+        assert_eq!(insert_policy.stats.validator_info_limit, 1);
+    }
 }
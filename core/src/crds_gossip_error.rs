@@ -5,4 +5,5 @@ pub enum CrdsGossipError {
     PushMessageOldVersion,
     BadPruneDestination,
     PruneMessageTimeout,
+    BadPushConfig,
 }
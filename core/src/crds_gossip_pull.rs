@@ -10,7 +10,7 @@
 //! of false positives.
 
 use crate::contact_info::ContactInfo;
-use crate::crds::{Crds, VersionedCrdsValue};
+use crate::crds::{Crds, CrdsTtls, VersionedCrdsValue};
 use crate::crds_gossip::{get_stake, get_weight, CFG as GOSSIP_CFG};
 use crate::crds_gossip_error::CrdsGossipError;
 use crate::crds_value::{CrdsValue, CrdsValueLabel};
@@ -540,10 +540,11 @@ impl CrdsGossipPull {
         crds: &mut Crds,
         now: u64,
         timeouts: &HashMap<Pubkey, u64>,
+        ttls: &CrdsTtls,
     ) -> usize {
         let num_purged_values = self.purged_values.len();
         self.purged_values.extend(
-            crds.find_old_labels(thread_pool, now, timeouts)
+            crds.find_old_labels(thread_pool, now, timeouts, ttls)
                 .into_iter()
                 .filter_map(|label| {
                     let val = crds.remove(&label)?;
@@ -1233,7 +1234,13 @@ mod test {
 
         // purge
         let timeouts = node.make_timeouts_def(&node_pubkey, &HashMap::new(), 0, 1);
-        node.purge_active(&thread_pool, &mut node_crds, 2, &timeouts);
+        node.purge_active(
+            &thread_pool,
+            &mut node_crds,
+            2,
+            &timeouts,
+            &CrdsTtls::default(),
+        );
 
         //verify self is still valid after purge
         assert_eq!(node_crds.lookup(&node_label).unwrap().label(), node_label);
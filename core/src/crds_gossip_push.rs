@@ -39,6 +39,28 @@ toml_config::package_config! {
     MAX_PUSHED_TO_TIMEOUT_MS: u64,
 }
 
+/// Push overlay settings which may be changed at runtime
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PushConfig {
+    /// number of active set peers each value is pushed to
+    pub fanout: usize,
+    /// number of peers in the active set
+    pub active_set_size: usize,
+    /// derive the fanout from the number of nodes in the cluster, up to `active_set_size`,
+    /// instead of using `fanout`
+    pub adaptive_fanout: bool,
+}
+
+impl Default for PushConfig {
+    fn default() -> Self {
+        Self {
+            fanout: CFG.CRDS_GOSSIP_PUSH_FANOUT,
+            active_set_size: CFG.CRDS_GOSSIP_NUM_ACTIVE,
+            adaptive_fanout: false,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct CrdsGossipPush {
     /// max bytes per message
@@ -56,6 +78,9 @@ pub struct CrdsGossipPush {
     last_pushed_to_cleanup_ts: u64,
     pub num_active: usize,
     pub push_fanout: usize,
+    pub adaptive_fanout: bool,
+    /// number of push peers as of the last active set refresh
+    num_nodes: usize,
     pub msg_timeout: u64,
     pub prune_timeout: u64,
     pub num_total: usize,
@@ -75,6 +100,8 @@ impl Default for CrdsGossipPush {
             last_pushed_to_cleanup_ts: 0,
             num_active: CFG.CRDS_GOSSIP_NUM_ACTIVE,
             push_fanout: CFG.CRDS_GOSSIP_PUSH_FANOUT,
+            adaptive_fanout: false,
+            num_nodes: 0,
             msg_timeout: CFG.CRDS_GOSSIP_PUSH_MSG_TIMEOUT_MS,
             prune_timeout: CFG.CRDS_GOSSIP_PRUNE_MSG_TIMEOUT_MS,
             num_total: 0,
@@ -88,6 +115,44 @@ impl CrdsGossipPush {
         self.push_messages.len()
     }
 
+    pub fn config(&self) -> PushConfig {
+        PushConfig {
+            fanout: self.push_fanout,
+            active_set_size: self.num_active,
+            adaptive_fanout: self.adaptive_fanout,
+        }
+    }
+
+    /// Applies `config`, unless its fanout or active set size is 0
+    pub fn set_config(&mut self, config: PushConfig) -> Result<(), CrdsGossipError> {
+        if config.fanout == 0 || config.active_set_size == 0 {
+            return Err(CrdsGossipError::BadPushConfig);
+        }
+        self.push_fanout = config.fanout;
+        self.num_active = config.active_set_size;
+        self.adaptive_fanout = config.adaptive_fanout;
+        // Shrink the active set right away rather than at the next refresh
+        while self.active_set.len() > self.num_active {
+            self.active_set.pop();
+        }
+        Ok(())
+    }
+
+    /// Fanout reaching a cluster of `num_nodes` nodes in about four hops
+    fn adaptive_fanout(num_nodes: usize) -> usize {
+        (num_nodes as f64).powf(0.25).ceil() as usize
+    }
+
+    /// The number of active set peers each value is currently pushed to
+    pub fn fanout(&self) -> usize {
+        let fanout = if self.adaptive_fanout {
+            Self::adaptive_fanout(self.num_nodes).min(self.num_active)
+        } else {
+            self.push_fanout
+        };
+        fanout.min(self.active_set.len())
+    }
+
     fn prune_stake_threshold(self_stake: u64, origin_stake: u64) -> u64 {
         let min_path_stake = self_stake.min(origin_stake);
         ((CFG.CRDS_GOSSIP_PRUNE_STAKE_THRESHOLD_PCT * min_path_stake as f64).round() as u64).max(1)
@@ -220,7 +285,7 @@ impl CrdsGossipPush {
     /// pruned the source addresses.
     pub fn new_push_messages(&mut self, crds: &Crds, now: u64) -> HashMap<Pubkey, Vec<CrdsValue>> {
         trace!("new_push_messages {}", self.push_messages.len());
-        let push_fanout = self.push_fanout.min(self.active_set.len());
+        let push_fanout = self.fanout();
         if push_fanout == 0 {
             return HashMap::default();
         }
@@ -296,7 +361,7 @@ impl CrdsGossipPush {
 
     fn compute_need(num_active: usize, active_set_len: usize, ratio: usize) -> usize {
         let num = active_set_len / ratio;
-        cmp::min(num_active, (num_active - active_set_len) + num)
+        cmp::min(num_active, num_active.saturating_sub(active_set_len) + num)
     }
 
     /// refresh the push active set
@@ -322,6 +387,7 @@ impl CrdsGossipPush {
             stakes,
             gossip_validators,
         );
+        self.num_nodes = options.len();
         if options.is_empty() {
             return;
         }
@@ -576,6 +642,65 @@ mod test {
         assert_eq!(CrdsGossipPush::compute_need(30, 1, 10), 29);
         assert_eq!(CrdsGossipPush::compute_need(30, 30, 10), 3);
         assert_eq!(CrdsGossipPush::compute_need(30, 29, 10), 3);
+        assert_eq!(CrdsGossipPush::compute_need(10, 30, 10), 3);
+    }
+
+    #[test]
+    fn test_adaptive_fanout() {
+        assert_eq!(CrdsGossipPush::adaptive_fanout(1), 1);
+        assert_eq!(CrdsGossipPush::adaptive_fanout(10), 2);
+        assert_eq!(CrdsGossipPush::adaptive_fanout(100), 4);
+        assert_eq!(CrdsGossipPush::adaptive_fanout(1000), 6);
+        assert_eq!(CrdsGossipPush::adaptive_fanout(5000), 9);
+    }
+
+    #[test]
+    fn test_set_config() {
+        let mut crds = Crds::default();
+        let mut push = CrdsGossipPush::default();
+        let now = timestamp();
+        for _ in 0..20 {
+            let peer = CrdsValue::new_unsigned(CrdsData::ContactInfo(ContactInfo::new_localhost(
+                &solana_sdk::pubkey::new_rand(),
+                0,
+            )));
+            crds.insert(peer, now).unwrap();
+        }
+        push.refresh_push_active_set(&crds, &HashMap::new(), None, &Pubkey::default(), 0, 1, 1);
+        assert_eq!(push.active_set.len(), 20);
+        assert_eq!(push.fanout(), CFG.CRDS_GOSSIP_PUSH_FANOUT);
+
+        let config = PushConfig {
+            fanout: 3,
+            active_set_size: 10,
+            adaptive_fanout: false,
+        };
+        assert_eq!(
+            push.set_config(PushConfig {
+                fanout: 0,
+                ..config
+            }),
+            Err(CrdsGossipError::BadPushConfig)
+        );
+        assert_eq!(
+            push.set_config(PushConfig {
+                active_set_size: 0,
+                ..config
+            }),
+            Err(CrdsGossipError::BadPushConfig)
+        );
+        assert_eq!(push.config().fanout, CFG.CRDS_GOSSIP_PUSH_FANOUT);
+        push.set_config(config).unwrap();
+        assert_eq!(push.config(), config);
+        assert_eq!(push.active_set.len(), 10);
+        assert_eq!(push.fanout(), 3);
+
+        push.set_config(PushConfig {
+            adaptive_fanout: true,
+            ..config
+        })
+        .unwrap();
+        assert_eq!(push.fanout(), CrdsGossipPush::adaptive_fanout(20));
     }
     #[test]
     fn test_refresh_active_set() {
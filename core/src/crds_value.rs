@@ -1,6 +1,6 @@
 use crate::contact_info::ContactInfo;
 use crate::deprecated;
-use crate::epoch_slots::EpochSlots;
+use crate::epoch_slots::{EpochSlots, EpochSlotsShard};
 use bincode::{serialize, serialized_size};
 use solana_sdk::sanitize::{Sanitize, SanitizeError};
 use solana_sdk::timing::timestamp;
@@ -13,7 +13,7 @@ use solana_sdk::{
 };
 use std::{
     borrow::{Borrow, Cow},
-    collections::{BTreeSet, HashSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
     fmt,
 };
 
@@ -26,6 +26,33 @@ pub const MAX_VOTES: VoteIndex = 32;
 pub type EpochSlotsIndex = u8;
 pub const MAX_EPOCH_SLOTS: EpochSlotsIndex = 255;
 
+/// Maximum number of capabilities a node may advertise
+pub const MAX_NODE_CAPABILITIES: usize = 8;
+/// Maximum length, in bytes, of the name of a capability
+pub const MAX_NODE_CAPABILITY_NAME_LEN: usize = 32;
+/// Maximum length, in bytes, of the value of a capability
+pub const MAX_NODE_CAPABILITY_VALUE_LEN: usize = 64;
+
+/// The node accepts transactions over TCP on its TPU port
+pub const CAPABILITY_TPU_TCP: &str = "tpu-tcp";
+/// The node serves snapshot archives from its RPC port
+pub const CAPABILITY_SNAPSHOTS: &str = "snapshots";
+/// Comma separated list of the optional groups of RPC methods the node has enabled
+pub const CAPABILITY_RPC_METHODS: &str = "rpc-methods";
+/// The node accepts compressed push messages and pull responses, with the codec in the value
+pub const CAPABILITY_GOSSIP_COMPRESSION: &str = "gossip-compression";
+/// Label of the network the node is on, checked alongside the shred version
+pub const CAPABILITY_NETWORK_NAMESPACE: &str = "network-namespace";
+/// The node accepts vote transactions on the port in the value, at the IP address of its TPU
+pub const CAPABILITY_TPU_VOTE: &str = "tpu-vote";
+/// The node serves `ShredRange` repair requests
+pub const CAPABILITY_REPAIR_SHRED_RANGE: &str = "repair-shred-range";
+
+/// Maximum length, in bytes, of the name a validator publishes in its `ValidatorInfo`
+pub const MAX_VALIDATOR_INFO_NAME_LEN: usize = 80;
+/// Maximum length, in bytes, of the website and icon URLs a validator publishes
+pub const MAX_VALIDATOR_INFO_URL_LEN: usize = 128;
+
 /// CrdsValue that is replicated across the cluster
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
 pub struct CrdsValue {
@@ -77,6 +104,12 @@ pub enum CrdsData {
     EpochSlots(EpochSlotsIndex, EpochSlots),
     LegacyVersion(LegacyVersion),
     Version(Version),
+    NodeCapabilities(NodeCapabilities),
+    ValidatorInfo(ValidatorInfo),
+    NodeInstance(NodeInstance),
+    PlannedHardFork(PlannedHardFork),
+    RestartBankHash(RestartBankHash),
+    EpochSlotsShard(EpochSlotsIndex, EpochSlotsShard),
 }
 
 impl Sanitize for CrdsData {
@@ -105,6 +138,21 @@ impl Sanitize for CrdsData {
             }
             CrdsData::LegacyVersion(version) => version.sanitize(),
             CrdsData::Version(version) => version.sanitize(),
+            CrdsData::NodeCapabilities(capabilities) => capabilities.sanitize(),
+            CrdsData::ValidatorInfo(info) => info.sanitize(),
+            CrdsData::NodeInstance(instance) => instance.sanitize(),
+            CrdsData::PlannedHardFork(hard_fork) => hard_fork.sanitize(),
+            CrdsData::RestartBankHash(bank_hash) => bank_hash.sanitize(),
+            CrdsData::EpochSlotsShard(ix, shard) => {
+                if *ix as usize >= MAX_EPOCH_SLOTS as usize {
+                    return Err(SanitizeError::ValueOutOfBounds);
+                }
+                // The index of a shard is derived from its generation
+                if *ix != shard.index() {
+                    return Err(SanitizeError::InvalidValue);
+                }
+                shard.sanitize()
+            }
         }
     }
 }
@@ -254,6 +302,202 @@ impl Version {
     }
 }
 
+/// Optional capabilities advertised by a node alongside its ContactInfo, by name
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
+pub struct NodeCapabilities {
+    pub from: Pubkey,
+    pub wallclock: u64,
+    pub capabilities: BTreeMap<String, String>,
+}
+
+impl Sanitize for NodeCapabilities {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        if self.wallclock >= MAX_WALLCLOCK {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        if self.capabilities.len() > MAX_NODE_CAPABILITIES
+            || self.capabilities.iter().any(|(name, value)| {
+                name.len() > MAX_NODE_CAPABILITY_NAME_LEN
+                    || value.len() > MAX_NODE_CAPABILITY_VALUE_LEN
+            })
+        {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        self.from.sanitize()
+    }
+}
+
+impl NodeCapabilities {
+    pub fn new(from: Pubkey, capabilities: BTreeMap<String, String>) -> Self {
+        Self {
+            from,
+            wallclock: timestamp(),
+            capabilities,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.capabilities.get(name).map(String::as_str)
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.capabilities.contains_key(name)
+    }
+}
+
+/// Name, website and icon the operator of a validator publishes for it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
+pub struct ValidatorInfo {
+    pub from: Pubkey,
+    pub wallclock: u64,
+    pub name: String,
+    pub website: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+impl Sanitize for ValidatorInfo {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        if self.wallclock >= MAX_WALLCLOCK {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        let url_too_long = |url: &Option<String>| {
+            url.as_ref()
+                .map(|url| url.len() > MAX_VALIDATOR_INFO_URL_LEN)
+                .unwrap_or(false)
+        };
+        if self.name.len() > MAX_VALIDATOR_INFO_NAME_LEN
+            || url_too_long(&self.website)
+            || url_too_long(&self.icon_url)
+        {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        self.from.sanitize()
+    }
+}
+
+impl ValidatorInfo {
+    pub fn new(
+        from: Pubkey,
+        name: String,
+        website: Option<String>,
+        icon_url: Option<String>,
+    ) -> Self {
+        Self {
+            from,
+            wallclock: timestamp(),
+            name,
+            website,
+            icon_url,
+        }
+    }
+}
+
+/// Identifies one run of a node, so that a node can tell when another one was started with the
+/// same identity
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
+pub struct NodeInstance {
+    pub from: Pubkey,
+    /// When the instance started; the value is never updated afterwards
+    pub wallclock: u64,
+    /// Random token told apart from the tokens of other instances of the node
+    pub token: u64,
+}
+
+impl Sanitize for NodeInstance {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        if self.wallclock >= MAX_WALLCLOCK {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        self.from.sanitize()
+    }
+}
+
+impl NodeInstance {
+    pub fn new<R: rand::Rng>(rng: &mut R, from: Pubkey, now: u64) -> Self {
+        Self {
+            from,
+            wallclock: now,
+            token: rng.gen(),
+        }
+    }
+
+    /// Whether `other` is another instance of the same node started after this one, in which
+    /// case this instance should stand down. Instances started at the same time are ordered by
+    /// their tokens.
+    pub fn check_duplicate(&self, other: &CrdsValue) -> bool {
+        match &other.data {
+            CrdsData::NodeInstance(other) => {
+                self.from == other.from
+                    && self.token != other.token
+                    && (self.wallclock, self.token) < (other.wallclock, other.token)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Hard fork the operator of a node plans to restart the cluster from, published ahead of the
+/// restart so that the other operators can check they have it configured too. The value is signed
+/// by the identity of the node, as any other.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
+pub struct PlannedHardFork {
+    pub from: Pubkey,
+    pub wallclock: u64,
+    pub slot: Slot,
+    /// Shred version of the cluster once restarted with the hard fork
+    pub shred_version: u16,
+}
+
+impl Sanitize for PlannedHardFork {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        if self.wallclock >= MAX_WALLCLOCK || self.slot >= MAX_SLOT {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        self.from.sanitize()
+    }
+}
+
+impl PlannedHardFork {
+    pub fn new(from: Pubkey, slot: Slot, shred_version: u16) -> Self {
+        Self {
+            from,
+            wallclock: timestamp(),
+            slot,
+            shred_version,
+        }
+    }
+}
+
+/// Bank hash of the slot a node waits for a supermajority of the stake at, published while it
+/// waits so that the nodes restarting from another bank are spotted before the cluster starts
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, AbiExample)]
+pub struct RestartBankHash {
+    pub from: Pubkey,
+    pub wallclock: u64,
+    pub slot: Slot,
+    pub bank_hash: Hash,
+}
+
+impl Sanitize for RestartBankHash {
+    fn sanitize(&self) -> Result<(), SanitizeError> {
+        if self.wallclock >= MAX_WALLCLOCK || self.slot >= MAX_SLOT {
+            return Err(SanitizeError::ValueOutOfBounds);
+        }
+        self.from.sanitize()
+    }
+}
+
+impl RestartBankHash {
+    pub fn new(from: Pubkey, slot: Slot, bank_hash: Hash) -> Self {
+        Self {
+            from,
+            wallclock: timestamp(),
+            slot,
+            bank_hash,
+        }
+    }
+}
+
 /// Type of the replicated value
 /// These are labels for values in a record that is associated with `Pubkey`
 #[derive(PartialEq, Hash, Eq, Clone, Debug)]
@@ -266,6 +510,12 @@ pub enum CrdsValueLabel {
     AccountsHashes(Pubkey),
     LegacyVersion(Pubkey),
     Version(Pubkey),
+    NodeCapabilities(Pubkey),
+    ValidatorInfo(Pubkey),
+    NodeInstance(Pubkey),
+    PlannedHardFork(Pubkey),
+    RestartBankHash(Pubkey),
+    EpochSlotsShard(EpochSlotsIndex, Pubkey),
 }
 
 impl fmt::Display for CrdsValueLabel {
@@ -279,6 +529,20 @@ impl fmt::Display for CrdsValueLabel {
             CrdsValueLabel::AccountsHashes(_) => write!(f, "AccountsHashes({})", self.pubkey()),
             CrdsValueLabel::LegacyVersion(_) => write!(f, "LegacyVersion({})", self.pubkey()),
             CrdsValueLabel::Version(_) => write!(f, "Version({})", self.pubkey()),
+            CrdsValueLabel::NodeCapabilities(_) => {
+                write!(f, "NodeCapabilities({})", self.pubkey())
+            }
+            CrdsValueLabel::ValidatorInfo(_) => write!(f, "ValidatorInfo({})", self.pubkey()),
+            CrdsValueLabel::NodeInstance(_) => write!(f, "NodeInstance({})", self.pubkey()),
+            CrdsValueLabel::PlannedHardFork(_) => {
+                write!(f, "PlannedHardFork({})", self.pubkey())
+            }
+            CrdsValueLabel::RestartBankHash(_) => {
+                write!(f, "RestartBankHash({})", self.pubkey())
+            }
+            CrdsValueLabel::EpochSlotsShard(ix, _) => {
+                write!(f, "EpochSlotsShard({}, {})", ix, self.pubkey())
+            }
         }
     }
 }
@@ -294,6 +558,12 @@ impl CrdsValueLabel {
             CrdsValueLabel::AccountsHashes(p) => *p,
             CrdsValueLabel::LegacyVersion(p) => *p,
             CrdsValueLabel::Version(p) => *p,
+            CrdsValueLabel::NodeCapabilities(p) => *p,
+            CrdsValueLabel::ValidatorInfo(p) => *p,
+            CrdsValueLabel::NodeInstance(p) => *p,
+            CrdsValueLabel::PlannedHardFork(p) => *p,
+            CrdsValueLabel::RestartBankHash(p) => *p,
+            CrdsValueLabel::EpochSlotsShard(_, p) => *p,
         }
     }
 }
@@ -335,6 +605,12 @@ impl CrdsValue {
             CrdsData::EpochSlots(_, p) => p.wallclock,
             CrdsData::LegacyVersion(version) => version.wallclock,
             CrdsData::Version(version) => version.wallclock,
+            CrdsData::NodeCapabilities(capabilities) => capabilities.wallclock,
+            CrdsData::ValidatorInfo(info) => info.wallclock,
+            CrdsData::NodeInstance(instance) => instance.wallclock,
+            CrdsData::PlannedHardFork(hard_fork) => hard_fork.wallclock,
+            CrdsData::RestartBankHash(bank_hash) => bank_hash.wallclock,
+            CrdsData::EpochSlotsShard(_, shard) => shard.epoch_slots.wallclock,
         }
     }
     pub fn pubkey(&self) -> Pubkey {
@@ -347,6 +623,12 @@ impl CrdsValue {
             CrdsData::EpochSlots(_, p) => p.from,
             CrdsData::LegacyVersion(version) => version.from,
             CrdsData::Version(version) => version.from,
+            CrdsData::NodeCapabilities(capabilities) => capabilities.from,
+            CrdsData::ValidatorInfo(info) => info.from,
+            CrdsData::NodeInstance(instance) => instance.from,
+            CrdsData::PlannedHardFork(hard_fork) => hard_fork.from,
+            CrdsData::RestartBankHash(bank_hash) => bank_hash.from,
+            CrdsData::EpochSlotsShard(_, shard) => shard.epoch_slots.from,
         }
     }
     pub fn label(&self) -> CrdsValueLabel {
@@ -359,6 +641,12 @@ impl CrdsValue {
             CrdsData::EpochSlots(ix, _) => CrdsValueLabel::EpochSlots(*ix, self.pubkey()),
             CrdsData::LegacyVersion(_) => CrdsValueLabel::LegacyVersion(self.pubkey()),
             CrdsData::Version(_) => CrdsValueLabel::Version(self.pubkey()),
+            CrdsData::NodeCapabilities(_) => CrdsValueLabel::NodeCapabilities(self.pubkey()),
+            CrdsData::ValidatorInfo(_) => CrdsValueLabel::ValidatorInfo(self.pubkey()),
+            CrdsData::NodeInstance(_) => CrdsValueLabel::NodeInstance(self.pubkey()),
+            CrdsData::PlannedHardFork(_) => CrdsValueLabel::PlannedHardFork(self.pubkey()),
+            CrdsData::RestartBankHash(_) => CrdsValueLabel::RestartBankHash(self.pubkey()),
+            CrdsData::EpochSlotsShard(ix, _) => CrdsValueLabel::EpochSlotsShard(*ix, self.pubkey()),
         }
     }
     pub fn contact_info(&self) -> Option<&ContactInfo> {
@@ -423,6 +711,41 @@ impl CrdsValue {
         }
     }
 
+    pub fn node_capabilities(&self) -> Option<&NodeCapabilities> {
+        match &self.data {
+            CrdsData::NodeCapabilities(capabilities) => Some(capabilities),
+            _ => None,
+        }
+    }
+
+    pub fn validator_info(&self) -> Option<&ValidatorInfo> {
+        match &self.data {
+            CrdsData::ValidatorInfo(info) => Some(info),
+            _ => None,
+        }
+    }
+
+    pub fn planned_hard_fork(&self) -> Option<&PlannedHardFork> {
+        match &self.data {
+            CrdsData::PlannedHardFork(hard_fork) => Some(hard_fork),
+            _ => None,
+        }
+    }
+
+    pub fn restart_bank_hash(&self) -> Option<&RestartBankHash> {
+        match &self.data {
+            CrdsData::RestartBankHash(bank_hash) => Some(bank_hash),
+            _ => None,
+        }
+    }
+
+    pub fn epoch_slots_shard(&self) -> Option<&EpochSlotsShard> {
+        match &self.data {
+            CrdsData::EpochSlotsShard(_, shard) => Some(shard),
+            _ => None,
+        }
+    }
+
     /// Return all the possible labels for a record identified by Pubkey.
     pub fn record_labels(key: &Pubkey) -> Vec<CrdsValueLabel> {
         let mut labels = vec![
@@ -432,9 +755,15 @@ impl CrdsValue {
             CrdsValueLabel::AccountsHashes(*key),
             CrdsValueLabel::LegacyVersion(*key),
             CrdsValueLabel::Version(*key),
+            CrdsValueLabel::NodeCapabilities(*key),
+            CrdsValueLabel::ValidatorInfo(*key),
+            CrdsValueLabel::NodeInstance(*key),
+            CrdsValueLabel::PlannedHardFork(*key),
+            CrdsValueLabel::RestartBankHash(*key),
         ];
         labels.extend((0..MAX_VOTES).map(|ix| CrdsValueLabel::Vote(ix, *key)));
         labels.extend((0..MAX_EPOCH_SLOTS).map(|ix| CrdsValueLabel::EpochSlots(ix, *key)));
+        labels.extend((0..MAX_EPOCH_SLOTS).map(|ix| CrdsValueLabel::EpochSlotsShard(ix, *key)));
         labels
     }
 
@@ -482,7 +811,7 @@ mod test {
 
     #[test]
     fn test_labels() {
-        let mut hits = [false; 6 + MAX_VOTES as usize + MAX_EPOCH_SLOTS as usize];
+        let mut hits = [false; 11 + MAX_VOTES as usize + 2 * MAX_EPOCH_SLOTS as usize];
         // this method should cover all the possible labels
         for v in &CrdsValue::record_labels(&Pubkey::default()) {
             match v {
@@ -492,9 +821,17 @@ mod test {
                 CrdsValueLabel::AccountsHashes(_) => hits[3] = true,
                 CrdsValueLabel::LegacyVersion(_) => hits[4] = true,
                 CrdsValueLabel::Version(_) => hits[5] = true,
-                CrdsValueLabel::Vote(ix, _) => hits[*ix as usize + 6] = true,
+                CrdsValueLabel::NodeCapabilities(_) => hits[6] = true,
+                CrdsValueLabel::ValidatorInfo(_) => hits[7] = true,
+                CrdsValueLabel::NodeInstance(_) => hits[8] = true,
+                CrdsValueLabel::PlannedHardFork(_) => hits[9] = true,
+                CrdsValueLabel::RestartBankHash(_) => hits[10] = true,
+                CrdsValueLabel::Vote(ix, _) => hits[*ix as usize + 11] = true,
                 CrdsValueLabel::EpochSlots(ix, _) => {
-                    hits[*ix as usize + MAX_VOTES as usize + 6] = true
+                    hits[*ix as usize + MAX_VOTES as usize + 11] = true
+                }
+                CrdsValueLabel::EpochSlotsShard(ix, _) => {
+                    hits[*ix as usize + MAX_VOTES as usize + MAX_EPOCH_SLOTS as usize + 11] = true
                 }
             }
         }
@@ -550,6 +887,116 @@ mod test {
         assert_eq!(v.sanitize(), Err(SanitizeError::InvalidValue));
     }
 
+    #[test]
+    fn test_node_capabilities_sanitize() {
+        let mut capabilities: BTreeMap<_, _> = (0..MAX_NODE_CAPABILITIES)
+            .map(|i| {
+                (
+                    format!("{:0>1$}", i, MAX_NODE_CAPABILITY_NAME_LEN),
+                    "x".repeat(MAX_NODE_CAPABILITY_VALUE_LEN),
+                )
+            })
+            .collect();
+        let v = CrdsValue::new_unsigned(CrdsData::NodeCapabilities(NodeCapabilities::new(
+            Pubkey::default(),
+            capabilities.clone(),
+        )));
+        assert_eq!(v.sanitize(), Ok(()));
+        // The largest capabilities leave room in a packet for the gossip message header
+        assert!(v.size() + 128 < solana_sdk::packet::PACKET_DATA_SIZE as u64);
+
+        capabilities.insert("too many".to_string(), String::default());
+        let v = CrdsValue::new_unsigned(CrdsData::NodeCapabilities(NodeCapabilities::new(
+            Pubkey::default(),
+            capabilities,
+        )));
+        assert_eq!(v.sanitize(), Err(SanitizeError::ValueOutOfBounds));
+
+        let mut capabilities = BTreeMap::new();
+        capabilities.insert(
+            CAPABILITY_RPC_METHODS.to_string(),
+            "x".repeat(MAX_NODE_CAPABILITY_VALUE_LEN + 1),
+        );
+        let v = CrdsValue::new_unsigned(CrdsData::NodeCapabilities(NodeCapabilities::new(
+            Pubkey::default(),
+            capabilities,
+        )));
+        assert_eq!(v.sanitize(), Err(SanitizeError::ValueOutOfBounds));
+    }
+
+    #[test]
+    fn test_validator_info_sanitize() {
+        let info = |name: usize, website: usize, icon_url: usize| {
+            CrdsValue::new_unsigned(CrdsData::ValidatorInfo(ValidatorInfo::new(
+                Pubkey::default(),
+                "x".repeat(name),
+                Some("x".repeat(website)),
+                Some("x".repeat(icon_url)),
+            )))
+        };
+        let v = info(
+            MAX_VALIDATOR_INFO_NAME_LEN,
+            MAX_VALIDATOR_INFO_URL_LEN,
+            MAX_VALIDATOR_INFO_URL_LEN,
+        );
+        assert_eq!(v.sanitize(), Ok(()));
+        // The largest info leaves room in a packet for the gossip message header
+        assert!(v.size() + 128 < solana_sdk::packet::PACKET_DATA_SIZE as u64);
+
+        let v = info(
+            MAX_VALIDATOR_INFO_NAME_LEN + 1,
+            MAX_VALIDATOR_INFO_URL_LEN,
+            MAX_VALIDATOR_INFO_URL_LEN,
+        );
+        assert_eq!(v.sanitize(), Err(SanitizeError::ValueOutOfBounds));
+        let v = info(
+            MAX_VALIDATOR_INFO_NAME_LEN,
+            MAX_VALIDATOR_INFO_URL_LEN,
+            MAX_VALIDATOR_INFO_URL_LEN + 1,
+        );
+        assert_eq!(v.sanitize(), Err(SanitizeError::ValueOutOfBounds));
+    }
+
+    #[test]
+    fn test_node_instance_check_duplicate() {
+        let mut rng = rand::thread_rng();
+        let pubkey = Pubkey::new_unique();
+        let instance = NodeInstance::new(&mut rng, pubkey, timestamp());
+        let as_value = |instance: &NodeInstance| {
+            CrdsValue::new_unsigned(CrdsData::NodeInstance(instance.clone()))
+        };
+        // The instance itself, echoed back by peers
+        assert!(!instance.check_duplicate(&as_value(&instance)));
+        // Another node
+        let other = NodeInstance {
+            from: Pubkey::new_unique(),
+            wallclock: instance.wallclock + 1,
+            ..instance.clone()
+        };
+        assert!(!instance.check_duplicate(&as_value(&other)));
+        // An older instance of the node stands down, the newer one does not
+        let newer = NodeInstance {
+            wallclock: instance.wallclock + 1,
+            token: instance.token.wrapping_add(1),
+            ..instance.clone()
+        };
+        assert!(instance.check_duplicate(&as_value(&newer)));
+        assert!(!newer.check_duplicate(&as_value(&instance)));
+        // Instances started at the same time are ordered by token
+        let other_token = NodeInstance {
+            token: !instance.token,
+            ..instance.clone()
+        };
+        assert_ne!(
+            instance.check_duplicate(&as_value(&other_token)),
+            other_token.check_duplicate(&as_value(&instance))
+        );
+        // Other values are ignored
+        let contact_info = ContactInfo::new_localhost(&pubkey, timestamp());
+        let value = CrdsValue::new_unsigned(CrdsData::ContactInfo(contact_info));
+        assert!(!instance.check_duplicate(&value));
+    }
+
     #[test]
     fn test_signature() {
         let keypair = Keypair::new();
@@ -595,6 +1042,17 @@ mod test {
             &keypair,
         );
         assert_eq!(item.sanitize(), Err(SanitizeError::ValueOutOfBounds));
+
+        // The index of a shard is derived from its generation
+        let shard = EpochSlotsShard::new(keypair.pubkey(), 3, timestamp());
+        let item = CrdsValue::new_signed(CrdsData::EpochSlotsShard(3, shard.clone()), &keypair);
+        assert_eq!(item.sanitize(), Ok(()));
+        let item = CrdsValue::new_signed(CrdsData::EpochSlotsShard(4, shard), &keypair);
+        assert_eq!(item.sanitize(), Err(SanitizeError::InvalidValue));
+        let shard = EpochSlotsShard::new(keypair.pubkey(), MAX_EPOCH_SLOTS as u64, timestamp());
+        let item =
+            CrdsValue::new_signed(CrdsData::EpochSlotsShard(MAX_EPOCH_SLOTS, shard), &keypair);
+        assert_eq!(item.sanitize(), Err(SanitizeError::ValueOutOfBounds));
     }
     #[test]
     fn test_compute_vote_index_empty() {
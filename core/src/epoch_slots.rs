@@ -1,4 +1,23 @@
+//! The `epoch_slots` module holds the slots a node has completed, published in gossip either
+//! as `EpochSlots` values, or as `EpochSlotsShard` values once the `sharded_epoch_slots` feature
+//! is active.
+//!
+//! Each shard has a generation number, one more than the previous shard of the node, and holds
+//! only the slots missing from the previous shards, so that the slots of a node are the union of
+//! its current shards. A shard replaces the one at the same index, `MAX_EPOCH_SLOTS` generations
+//! older, and any shard older than that is stale.
+//!
+//! Nodes which do not know the `EpochSlotsShard` variant fail to deserialize the gossip messages
+//! holding one, so the transition takes two steps:
+//! * a release which stores, propagates and reads the shards of other nodes, but keeps
+//!   publishing `EpochSlots` values;
+//! * the activation of the feature, once the cluster runs that release, after which the nodes
+//!   publish shards only and their `EpochSlots` values age out of the tables. Readers take the
+//!   union of both in the meantime.
+
 use crate::cluster_info::CFG as CLUSTER_CFG;
+use crate::crds_value::EpochSlotsIndex;
+use crate::crds_value::MAX_EPOCH_SLOTS;
 use crate::crds_value::MAX_SLOT;
 use crate::crds_value::MAX_WALLCLOCK;
 use bincode::serialized_size;
@@ -254,6 +273,10 @@ impl EpochSlots {
         }
     }
     pub fn fill(&mut self, slots: &[Slot], now: u64) -> usize {
+        self.fill_with_overhead(slots, now, 0)
+    }
+    /// Fills the slots into a value serialized with `overhead` more bytes than `self`
+    fn fill_with_overhead(&mut self, slots: &[Slot], now: u64, overhead: u64) -> usize {
         let mut num = 0;
         self.wallclock = std::cmp::max(now, self.wallclock + 1);
         while num < slots.len() {
@@ -262,7 +285,7 @@ impl EpochSlots {
                 if self.deflate().is_err() {
                     return num;
                 }
-                let space = self.max_compressed_slot_size();
+                let space = self.max_compressed_slot_size() - overhead as isize;
                 if space > 0 {
                     let cslot = CompressedSlots::new(space as usize);
                     self.slots.push(cslot);
@@ -309,6 +332,54 @@ impl EpochSlots {
     }
 }
 
+/// `EpochSlots` published as one of the shards of a node
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, AbiExample)]
+pub struct EpochSlotsShard {
+    /// Orders the shards of a node, see `is_stale`
+    pub generation: u64,
+    pub epoch_slots: EpochSlots,
+}
+
+impl Sanitize for EpochSlotsShard {
+    fn sanitize(&self) -> std::result::Result<(), SanitizeError> {
+        self.epoch_slots.sanitize()
+    }
+}
+
+impl EpochSlotsShard {
+    pub fn new(from: Pubkey, generation: u64, now: u64) -> Self {
+        Self {
+            generation,
+            epoch_slots: EpochSlots::new(from, now),
+        }
+    }
+
+    /// Index of the shard in gossip, derived from its generation
+    pub fn index(&self) -> EpochSlotsIndex {
+        (self.generation % MAX_EPOCH_SLOTS as u64) as EpochSlotsIndex
+    }
+
+    /// Whether the shard was replaced by the shards up to generation `latest` of its node
+    pub fn is_stale(&self, latest: u64) -> bool {
+        self.generation.saturating_add(MAX_EPOCH_SLOTS as u64) <= latest
+    }
+
+    pub fn fill(&mut self, slots: &[Slot], now: u64) -> usize {
+        let overhead = serialized_size(&self.generation).unwrap();
+        self.epoch_slots.fill_with_overhead(slots, now, overhead)
+    }
+}
+
+/// The current shards of a node out of `shards`, ordered by generation
+pub fn current_shards(mut shards: Vec<EpochSlotsShard>) -> Vec<EpochSlotsShard> {
+    let latest = shards.iter().map(|shard| shard.generation).max();
+    if let Some(latest) = latest {
+        shards.retain(|shard| !shard.is_stale(latest));
+    }
+    shards.sort_by_key(|shard| shard.generation);
+    shards
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -415,6 +486,45 @@ mod tests {
         assert_eq!(o.sanitize(), Err(SanitizeError::ValueOutOfBounds));
     }
 
+    #[test]
+    fn test_epoch_slots_shards() {
+        let from = Pubkey::new_unique();
+        let shard = EpochSlotsShard::new(from, MAX_EPOCH_SLOTS as u64 + 2, 0);
+        assert_eq!(shard.index(), 2);
+        assert!(!shard.is_stale(shard.generation));
+        assert!(!shard.is_stale(shard.generation + MAX_EPOCH_SLOTS as u64 - 1));
+        assert!(shard.is_stale(shard.generation + MAX_EPOCH_SLOTS as u64));
+
+        // The shard replaced by generation 300 is dropped, the others are ordered
+        let shards: Vec<_> = [300, 45, 46, 100]
+            .iter()
+            .map(|generation| EpochSlotsShard::new(from, *generation, 0))
+            .collect();
+        let current: Vec<_> = current_shards(shards)
+            .iter()
+            .map(|shard| shard.generation)
+            .collect();
+        assert_eq!(current, vec![46, 100, 300]);
+        assert!(current_shards(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_epoch_slots_shard_fill() {
+        use rand::Rng;
+        let mut range: Vec<Slot> = vec![];
+        for _ in 0..5000 {
+            let last = *range.last().unwrap_or(&0);
+            range.push(last + rand::thread_rng().gen_range(1, 5));
+        }
+        let mut shard = EpochSlotsShard::new(Pubkey::new_unique(), 1, 0);
+        let num = shard.fill(&range, 1);
+        assert!(num > 0);
+        assert_eq!(shard.epoch_slots.wallclock, 1);
+        assert_eq!(shard.epoch_slots.to_slots(0), range[..num]);
+        // The generation fits in the space left for the slots
+        assert!(serialized_size(&shard).unwrap() as usize <= CLUSTER_CFG.MAX_CRDS_OBJECT_SIZE);
+    }
+
     #[test]
     fn test_epoch_slots_fill_range() {
         let range: Vec<Slot> = (0..5000).collect();
@@ -1,6 +1,7 @@
 //! The `fetch_stage` batches input from a UDP socket and sends it to a channel.
 
 use crate::banking_stage::CFG as BANKING_STAGE_CFG;
+use crate::link_conditioner::{conditioned_sender, LinkSocket, NodeLinkConditioner};
 use crate::poh_recorder::PohRecorder;
 use crate::result::{Error, Result};
 use solana_measure::thread_mem_usage;
@@ -29,25 +30,44 @@ impl FetchStage {
     ) -> (Self, PacketReceiver) {
         let (sender, receiver) = channel();
         (
-            Self::new_with_sender(sockets, tpu_forwards_sockets, exit, &sender, &poh_recorder),
+            Self::new_with_sender(
+                sockets,
+                tpu_forwards_sockets,
+                vec![],
+                exit,
+                &sender,
+                &sender,
+                &poh_recorder,
+                None,
+            ),
             receiver,
         )
     }
+    /// The packets received on `tpu_vote_sockets` are sent to `vote_sender`, apart from the
+    /// other transactions, so that votes have their own sigverify lane
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_sender(
         sockets: Vec<UdpSocket>,
         tpu_forwards_sockets: Vec<UdpSocket>,
+        tpu_vote_sockets: Vec<UdpSocket>,
         exit: &Arc<AtomicBool>,
         sender: &PacketSender,
+        vote_sender: &PacketSender,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
+        link_conditioner: Option<&NodeLinkConditioner>,
     ) -> Self {
         let tx_sockets = sockets.into_iter().map(Arc::new).collect();
         let tpu_forwards_sockets = tpu_forwards_sockets.into_iter().map(Arc::new).collect();
+        let tpu_vote_sockets = tpu_vote_sockets.into_iter().map(Arc::new).collect();
         Self::new_multi_socket(
             tx_sockets,
             tpu_forwards_sockets,
+            tpu_vote_sockets,
             exit,
             &sender,
+            &vote_sender,
             &poh_recorder,
+            link_conditioner,
         )
     }
 
@@ -87,26 +107,46 @@ impl FetchStage {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn new_multi_socket(
         sockets: Vec<Arc<UdpSocket>>,
         tpu_forwards_sockets: Vec<Arc<UdpSocket>>,
+        tpu_vote_sockets: Vec<Arc<UdpSocket>>,
         exit: &Arc<AtomicBool>,
         sender: &PacketSender,
+        vote_sender: &PacketSender,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
+        link_conditioner: Option<&NodeLinkConditioner>,
     ) -> Self {
         let recycler: PacketsRecycler = Recycler::warmed(1000, 1024);
 
+        let (tpu_sender, tpu_conditioner_hdl) =
+            conditioned_sender(link_conditioner, LinkSocket::Tpu, sender.clone());
         let tpu_threads = sockets.into_iter().map(|socket| {
             streamer::receiver(
                 socket,
                 &exit,
-                sender.clone(),
+                tpu_sender.clone(),
                 recycler.clone(),
                 "fetch_stage",
             )
         });
 
+        let (vote_sender, vote_conditioner_hdl) =
+            conditioned_sender(link_conditioner, LinkSocket::Tpu, vote_sender.clone());
+        let tpu_vote_threads = tpu_vote_sockets.into_iter().map(|socket| {
+            streamer::receiver(
+                socket,
+                &exit,
+                vote_sender.clone(),
+                recycler.clone(),
+                "fetch_vote_stage",
+            )
+        });
+
         let (forward_sender, forward_receiver) = channel();
+        let (forward_sender, forward_conditioner_hdl) =
+            conditioned_sender(link_conditioner, LinkSocket::Tpu, forward_sender);
         let tpu_forwards_threads = tpu_forwards_sockets.into_iter().map(|socket| {
             streamer::receiver(
                 socket,
@@ -138,8 +178,14 @@ impl FetchStage {
             })
             .unwrap();
 
-        let mut thread_hdls: Vec<_> = tpu_threads.chain(tpu_forwards_threads).collect();
+        let mut thread_hdls: Vec<_> = tpu_threads
+            .chain(tpu_vote_threads)
+            .chain(tpu_forwards_threads)
+            .collect();
         thread_hdls.push(fwd_thread_hdl);
+        thread_hdls.extend(tpu_conditioner_hdl);
+        thread_hdls.extend(vote_conditioner_hdl);
+        thread_hdls.extend(forward_conditioner_hdl);
         Self { thread_hdls }
     }
 
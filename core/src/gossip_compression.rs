@@ -0,0 +1,220 @@
+//! The `gossip_compression` module compresses the values of gossip push messages and pull
+//! responses with zstd, so that more of them fit in a packet. The zstd dictionary is made of
+//! samples of the most common values, votes and contact infos: their keys, hashes and
+//! signatures do not compress, but the program ids and layout around them are the same for
+//! every node. Nodes advertise the codec they accept in their `NodeCapabilities`, and are only
+//! sent compressed messages by peers using the same dictionary.
+
+use crate::{
+    contact_info::ContactInfo,
+    crds_value::{CrdsData, CrdsValue, Vote},
+};
+use bincode::{serialize, Options};
+use lazy_static::lazy_static;
+use solana_perf::packet::PACKET_DATA_SIZE;
+use solana_sdk::{hash::hash, message::Message, pubkey::Pubkey, transaction::Transaction};
+use solana_vote_program::{vote_instruction, vote_state};
+use std::{cell::RefCell, io, mem};
+use zstd::block::{Compressor, Decompressor};
+
+const COMPRESSION_LEVEL: i32 = 3;
+/// Values are compressed in chunks of up to this many times the size of a packet, each packed
+/// into as few payloads as they fit in
+const MAX_COMPRESSION_RATIO: usize = 3;
+/// Limit on the size of the values of a compressed message once decompressed
+const MAX_DECOMPRESSED_SIZE: usize = 8 * PACKET_DATA_SIZE;
+
+lazy_static! {
+    static ref DICTIONARY: Vec<u8> = dictionary();
+    /// Advertised as the value of the `gossip-compression` capability, identifies the dictionary
+    pub static ref GOSSIP_COMPRESSION_CODEC: String =
+        format!("zstd-{}", &hash(&DICTIONARY).to_string()[..8]);
+}
+
+thread_local!(static COMPRESSOR: RefCell<Compressor> =
+    RefCell::new(Compressor::with_dict(DICTIONARY.clone())));
+thread_local!(static DECOMPRESSOR: RefCell<Decompressor> =
+    RefCell::new(Decompressor::with_dict(DICTIONARY.clone())));
+
+fn dictionary() -> Vec<u8> {
+    let vote_pubkey = Pubkey::new(&[1; 32]);
+    let node_pubkey = Pubkey::new(&[2; 32]);
+    let vote = vote_state::Vote::new(vec![0], Default::default());
+    let vote_ix = vote_instruction::vote(&vote_pubkey, &node_pubkey, vote);
+    let vote_tx = Transaction::new_unsigned(Message::new(&[vote_ix], Some(&node_pubkey)));
+    let samples = vec![
+        CrdsValue::new_unsigned(CrdsData::Vote(0, Vote::new(&node_pubkey, vote_tx, 0))),
+        CrdsValue::new_unsigned(CrdsData::ContactInfo(ContactInfo::new_localhost(
+            &node_pubkey,
+            0,
+        ))),
+    ];
+    samples
+        .iter()
+        .flat_map(|sample| serialize(sample).unwrap())
+        .collect()
+}
+
+pub fn compress(values: &[CrdsValue]) -> io::Result<Vec<u8>> {
+    let data = serialize(values).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    COMPRESSOR.with(|compressor| compressor.borrow_mut().compress(&data, COMPRESSION_LEVEL))
+}
+
+pub fn decompress(data: &[u8]) -> io::Result<Vec<CrdsValue>> {
+    let data = DECOMPRESSOR.with(|decompressor| {
+        decompressor
+            .borrow_mut()
+            .decompress(data, MAX_DECOMPRESSED_SIZE)
+    })?;
+    bincode::options()
+        .with_limit(MAX_DECOMPRESSED_SIZE as u64)
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(&data)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Packs `values`, in order, into compressed payloads of at most `max_size` bytes. The values
+/// which do not fit in a payload on their own are returned apart, to be sent uncompressed.
+pub fn split_compressed(values: Vec<CrdsValue>, max_size: usize) -> (Vec<Vec<u8>>, Vec<CrdsValue>) {
+    let mut payloads = vec![];
+    let mut uncompressed = vec![];
+    let mut chunk = vec![];
+    let mut chunk_size = 0;
+    for value in values {
+        let size = value.size() as usize;
+        if !chunk.is_empty() && chunk_size + size > max_size * MAX_COMPRESSION_RATIO {
+            compress_chunk(
+                mem::take(&mut chunk),
+                max_size,
+                &mut payloads,
+                &mut uncompressed,
+            );
+            chunk_size = 0;
+        }
+        chunk_size += size;
+        chunk.push(value);
+    }
+    if !chunk.is_empty() {
+        compress_chunk(chunk, max_size, &mut payloads, &mut uncompressed);
+    }
+    (payloads, uncompressed)
+}
+
+// Compresses `values` into payloads, each holding as many of the remaining values as fit in
+// `max_size` bytes
+fn compress_chunk(
+    values: Vec<CrdsValue>,
+    max_size: usize,
+    payloads: &mut Vec<Vec<u8>>,
+    uncompressed: &mut Vec<CrdsValue>,
+) {
+    let fits = |values: &[CrdsValue]| {
+        compress(values)
+            .ok()
+            .filter(|payload| payload.len() <= max_size)
+    };
+    let mut values = &values[..];
+    while !values.is_empty() {
+        if let Some(payload) = fits(values) {
+            payloads.push(payload);
+            return;
+        }
+        // Binary search for the longest prefix that fits
+        let (mut fitting, mut not_fitting) = (0, values.len());
+        let mut payload = None;
+        while not_fitting - fitting > 1 {
+            let len = (fitting + not_fitting) / 2;
+            match fits(&values[..len]) {
+                Some(prefix_payload) => {
+                    fitting = len;
+                    payload = Some(prefix_payload);
+                }
+                None => not_fitting = len,
+            }
+        }
+        match payload {
+            Some(payload) => {
+                payloads.push(payload);
+                values = &values[fitting..];
+            }
+            None => {
+                uncompressed.push(values[0].clone());
+                values = &values[1..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crds_value::LowestSlot;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn new_votes(num_votes: usize) -> Vec<CrdsValue> {
+        let node_keypair = Keypair::new();
+        let vote_keypair = Keypair::new();
+        let recent_blockhash = solana_sdk::hash::Hash::new_unique();
+        (0..num_votes)
+            .map(|i| {
+                let vote_tx = solana_vote_program::vote_transaction::new_vote_transaction(
+                    vec![i as u64],
+                    solana_sdk::hash::Hash::new_unique(),
+                    recent_blockhash,
+                    &node_keypair,
+                    &vote_keypair,
+                    &vote_keypair,
+                    None,
+                );
+                let vote = Vote::new(&node_keypair.pubkey(), vote_tx, i as u64);
+                CrdsValue::new_signed(CrdsData::Vote(i as u8, vote), &node_keypair)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compress_decompress() {
+        let values = new_votes(4);
+        let payload = compress(&values).unwrap();
+        assert!(payload.len() < serialize(&values).unwrap().len());
+        assert_eq!(decompress(&payload).unwrap(), values);
+
+        assert!(decompress(&payload[..payload.len() - 1]).is_err());
+        assert!(decompress(&[0; 64]).is_err());
+        // Decompressing beyond the limit fails
+        let large = compress(&vec![new_votes(1)[0].clone(); 64]).unwrap();
+        assert!(large.len() < PACKET_DATA_SIZE);
+        assert!(decompress(&large).is_err());
+        assert!(GOSSIP_COMPRESSION_CODEC.starts_with("zstd-"));
+    }
+
+    #[test]
+    fn test_split_compressed() {
+        let max_size = 1000;
+        let values = new_votes(32);
+        let values_size: usize = values.iter().map(|value| value.size() as usize).sum();
+        let (payloads, uncompressed) = split_compressed(values.clone(), max_size);
+        assert!(uncompressed.is_empty());
+        assert!(payloads.iter().all(|payload| payload.len() <= max_size));
+        // Compressed, the values take fewer packets than they would uncompressed
+        assert!(payloads.len() < (values_size + max_size - 1) / max_size);
+        let decompressed: Vec<_> = payloads
+            .iter()
+            .flat_map(|payload| decompress(payload).unwrap())
+            .collect();
+        assert_eq!(decompressed, values);
+
+        // A value too large to be compressed into a payload is returned apart
+        let lowest_slot = CrdsValue::new_unsigned(CrdsData::LowestSlot(
+            0,
+            LowestSlot::new(Pubkey::new_unique(), 0, 0),
+        ));
+        let mut values = vec![lowest_slot];
+        values.extend(new_votes(1));
+        let (payloads, uncompressed) = split_compressed(values.clone(), 200);
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(decompress(&payloads[0]).unwrap(), &values[..1]);
+        assert_eq!(uncompressed, &values[1..]);
+    }
+}
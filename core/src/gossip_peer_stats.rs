@@ -0,0 +1,143 @@
+//! The `gossip_peer_stats` module keeps gossip counters for each peer, so that the aggregated
+//! gossip stats can be broken down to tell which peers the traffic comes from and goes to.
+
+use solana_sdk::{pubkey::Pubkey, timing::timestamp};
+use std::{collections::HashMap, sync::Mutex};
+
+toml_config::package_config! {
+    GOSSIP_PEER_STATS_CAPACITY: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GossipPeerStats {
+    pub push_messages_received: u64,
+    pub pull_requests_served: u64,
+    /// Prune messages sent to the peer
+    pub prunes_sent: u64,
+    /// Prune messages received from the peer
+    pub prunes_received: u64,
+    /// Bytes of the gossip packets claiming to be from the peer
+    pub bytes_received: u64,
+    /// Bytes of the gossip messages sent to the peer
+    pub bytes_sent: u64,
+    /// Why the last rejected message from the peer was rejected
+    pub last_error: Option<String>,
+    /// Timestamp of the last update, in ms
+    pub last_updated: u64,
+}
+
+/// Stats of the most recently active peers, up to `GOSSIP_PEER_STATS_CAPACITY` of them
+pub struct GossipPeerStatsTable {
+    capacity: usize,
+    peers: Mutex<HashMap<Pubkey, GossipPeerStats>>,
+}
+
+impl Default for GossipPeerStatsTable {
+    fn default() -> Self {
+        Self::new(CFG.GOSSIP_PEER_STATS_CAPACITY)
+    }
+}
+
+impl GossipPeerStatsTable {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            peers: Mutex::default(),
+        }
+    }
+
+    /// Applies `update` to the stats of each of the given peers along with its value
+    pub fn update<I, T, F>(&self, updates: I, update: F)
+    where
+        I: IntoIterator<Item = (Pubkey, T)>,
+        F: Fn(&mut GossipPeerStats, T),
+    {
+        let now = timestamp();
+        let mut peers = self.peers.lock().unwrap();
+        for (pubkey, value) in updates {
+            let peer = peers.entry(pubkey).or_default();
+            update(peer, value);
+            peer.last_updated = now;
+        }
+        if peers.len() > self.capacity {
+            // Forget the least recently updated peers, leaving some room so that this is not
+            // done again on the next update
+            let mut by_last_updated: Vec<_> = peers
+                .iter()
+                .map(|(pubkey, peer)| (peer.last_updated, *pubkey))
+                .collect();
+            by_last_updated.sort_unstable();
+            let num_evicted = peers.len() - self.capacity * 9 / 10;
+            for (_, pubkey) in &by_last_updated[..num_evicted] {
+                peers.remove(pubkey);
+            }
+        }
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<GossipPeerStats> {
+        self.peers.lock().unwrap().get(pubkey).cloned()
+    }
+
+    pub fn peers(&self) -> Vec<(Pubkey, GossipPeerStats)> {
+        self.peers
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(pubkey, peer)| (*pubkey, peer.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gossip_peer_stats() {
+        let table = GossipPeerStatsTable::new(10);
+        let peer = solana_sdk::pubkey::new_rand();
+        let other = solana_sdk::pubkey::new_rand();
+        table.update(
+            vec![(peer, 100), (other, 20), (peer, 50)],
+            |stats, bytes| stats.bytes_received += bytes,
+        );
+        table.update(vec![(peer, ())], |stats, ()| {
+            stats.push_messages_received += 1
+        });
+        table.update(vec![(other, "bad signature")], |stats, error| {
+            stats.last_error = Some(error.to_string())
+        });
+
+        let stats = table.get(&peer).unwrap();
+        assert_eq!(stats.bytes_received, 150);
+        assert_eq!(stats.push_messages_received, 1);
+        assert_eq!(stats.last_error, None);
+        assert!(stats.last_updated > 0);
+        let stats = table.get(&other).unwrap();
+        assert_eq!(stats.bytes_received, 20);
+        assert_eq!(stats.last_error, Some("bad signature".to_string()));
+        assert_eq!(table.peers().len(), 2);
+    }
+
+    #[test]
+    fn test_gossip_peer_stats_capacity() {
+        let table = GossipPeerStatsTable::new(10);
+        let pubkeys: Vec<_> = (0..10).map(|_| solana_sdk::pubkey::new_rand()).collect();
+        table.update(pubkeys.iter().map(|pubkey| (*pubkey, ())), |_, ()| ());
+        assert_eq!(table.peers().len(), 10);
+        // Backdate the first peers, to be evicted first
+        {
+            let mut peers = table.peers.lock().unwrap();
+            for pubkey in &pubkeys[..5] {
+                peers.get_mut(pubkey).unwrap().last_updated = 0;
+            }
+        }
+        let new_peer = solana_sdk::pubkey::new_rand();
+        table.update(vec![(new_peer, ())], |_, ()| ());
+        assert_eq!(table.peers().len(), 9);
+        assert!(table.get(&new_peer).is_some());
+        assert!(pubkeys[5..]
+            .iter()
+            .all(|pubkey| table.get(pubkey).is_some()));
+    }
+}
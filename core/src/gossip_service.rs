@@ -2,6 +2,7 @@
 
 use crate::cluster_info::{ClusterInfo, VALIDATOR_PORT_RANGE};
 use crate::contact_info::ContactInfo;
+use crate::link_conditioner::{conditioned_sender, LinkSocket, NodeLinkConditioner};
 use rand::{thread_rng, Rng};
 use solana_client::thin_client::{create_client, ThinClient};
 use solana_perf::recycler::Recycler;
@@ -34,8 +35,28 @@ impl GossipService {
         gossip_socket: UdpSocket,
         gossip_validators: Option<HashSet<Pubkey>>,
         exit: &Arc<AtomicBool>,
+    ) -> Self {
+        Self::new_with_link_conditioner(
+            cluster_info,
+            bank_forks,
+            gossip_socket,
+            gossip_validators,
+            None,
+            exit,
+        )
+    }
+
+    pub fn new_with_link_conditioner(
+        cluster_info: &Arc<ClusterInfo>,
+        bank_forks: Option<Arc<RwLock<BankForks>>>,
+        gossip_socket: UdpSocket,
+        gossip_validators: Option<HashSet<Pubkey>>,
+        link_conditioner: Option<&NodeLinkConditioner>,
+        exit: &Arc<AtomicBool>,
     ) -> Self {
         let (request_sender, request_receiver) = channel();
+        let (request_sender, t_conditioner) =
+            conditioned_sender(link_conditioner, LinkSocket::Gossip, request_sender);
         let gossip_socket = Arc::new(gossip_socket);
         trace!(
             "GossipService: id: {}, listening on: {:?}",
@@ -65,7 +86,8 @@ impl GossipService {
             gossip_validators,
             exit,
         );
-        let thread_hdls = vec![t_receiver, t_responder, t_listen, t_gossip];
+        let mut thread_hdls = vec![t_receiver, t_responder, t_listen, t_gossip];
+        thread_hdls.extend(t_conditioner);
         Self { thread_hdls }
     }
 
@@ -155,6 +177,100 @@ pub fn discover(
     ))
 }
 
+/// Criteria the RPC nodes found through gossip must meet
+#[derive(Clone, Debug, Default)]
+pub struct RpcNodeFilter {
+    /// Shred version of the nodes, or 0 for the shred version adopted from the entrypoint
+    pub shred_version: u16,
+    /// Minimum (major, minor, patch) software version of the nodes
+    pub min_version: Option<(u16, u16, u16)>,
+}
+
+/// Returns the RPC nodes known to `cluster_info` which match `filter`
+pub fn get_rpc_peers(cluster_info: &ClusterInfo, filter: &RpcNodeFilter) -> Vec<ContactInfo> {
+    let shred_version = if filter.shred_version == 0 {
+        cluster_info.my_shred_version()
+    } else {
+        filter.shred_version
+    };
+    if shred_version == 0 {
+        // The shred version of the entrypoint is not known yet
+        return vec![];
+    }
+    cluster_info
+        .all_rpc_peers()
+        .into_iter()
+        .filter(|contact_info| contact_info.shred_version == shred_version)
+        .filter(|contact_info| cluster_info.same_network_namespace(&contact_info.id))
+        .filter(|contact_info| match filter.min_version {
+            None => true,
+            Some(min_version) => cluster_info
+                .get_node_version(&contact_info.id)
+                .map_or(false, |version| {
+                    (version.major, version.minor, version.patch) >= min_version
+                }),
+        })
+        .collect()
+}
+
+/// Spies on the cluster rendezvoused with at `entrypoint` until `num_nodes` RPC nodes matching
+/// `filter` are found, or `timeout` elapses. Returns the RPC nodes found, or an error if none
+/// was found by the timeout.
+pub fn discover_rpc_nodes(
+    entrypoint: &SocketAddr,
+    num_nodes: usize,
+    filter: &RpcNodeFilter,
+    timeout: Duration,
+) -> std::io::Result<Vec<ContactInfo>> {
+    let exit = Arc::new(AtomicBool::new(false));
+    let (gossip_service, _ip_echo, spy_ref) = make_gossip_node(
+        Arc::new(Keypair::new()),
+        Some(entrypoint),
+        &exit,
+        None,
+        filter.shred_version,
+    );
+    info!("Entrypoint: {:?}", entrypoint);
+    info!("Node Id: {:?}", spy_ref.id());
+
+    let now = Instant::now();
+    let mut rpc_peers = vec![];
+    let mut i = 1;
+    while now.elapsed() < timeout {
+        rpc_peers = get_rpc_peers(&spy_ref, filter);
+        if rpc_peers.len() >= num_nodes {
+            break;
+        }
+        if i % 20 == 0 {
+            info!("discovering RPC nodes...\n{}", spy_ref.rpc_info_trace());
+        }
+        sleep(Duration::from_millis(
+            crate::cluster_info::CFG.GOSSIP_SLEEP_MILLIS,
+        ));
+        i += 1;
+    }
+
+    exit.store(true, Ordering::Relaxed);
+    gossip_service.join().unwrap();
+
+    if rpc_peers.is_empty() {
+        info!("discover RPC nodes failed...\n{}", spy_ref.rpc_info_trace());
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "No RPC node discovered",
+        ));
+    }
+    if rpc_peers.len() < num_nodes {
+        info!(
+            "discovered {} of {} RPC nodes by timeout...\n{}",
+            rpc_peers.len(),
+            num_nodes,
+            spy_ref.rpc_info_trace()
+        );
+    }
+    Ok(rpc_peers)
+}
+
 /// Creates a ThinClient per valid node
 pub fn get_clients(nodes: &[ContactInfo]) -> Vec<ThinClient> {
     nodes
@@ -262,7 +378,7 @@ fn spy(
 
 /// Makes a spy or gossip node based on whether or not a gossip_addr was passed in
 /// Pass in a gossip addr to fully participate in gossip instead of relying on just pulls
-fn make_gossip_node(
+pub fn make_gossip_node(
     keypair: Arc<Keypair>,
     entrypoint: Option<&SocketAddr>,
     exit: &Arc<AtomicBool>,
@@ -368,4 +484,63 @@ mod tests {
         );
         assert_eq!(met_criteria, false);
     }
+
+    #[test]
+    fn test_get_rpc_peers() {
+        let keypair = Keypair::new();
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let cluster_info = ClusterInfo::new(contact_info, Arc::new(keypair));
+        let new_peer = |shred_version| {
+            let mut contact_info = ContactInfo::new_localhost(&solana_sdk::pubkey::new_rand(), 0);
+            contact_info.shred_version = shred_version;
+            cluster_info.insert_info(contact_info.clone());
+            contact_info
+        };
+        let peer0 = new_peer(1);
+        let peer1 = new_peer(1);
+        new_peer(2);
+
+        // The shred version of the entrypoint is not known yet
+        assert!(get_rpc_peers(&cluster_info, &RpcNodeFilter::default()).is_empty());
+
+        let filter = RpcNodeFilter {
+            shred_version: 1,
+            min_version: None,
+        };
+        let mut rpc_peers = get_rpc_peers(&cluster_info, &filter);
+        rpc_peers.sort_by_key(|contact_info| contact_info.id);
+        let mut expected = vec![peer0.clone(), peer1];
+        expected.sort_by_key(|contact_info| contact_info.id);
+        assert_eq!(rpc_peers, expected);
+
+        // Only the nodes known to run at least the minimum version are returned
+        let version = crate::crds_value::Version {
+            from: peer0.id,
+            wallclock: 0,
+            version: solana_version::Version::default(),
+        };
+        let value = crate::crds_value::CrdsValue::new_unsigned(
+            crate::crds_value::CrdsData::Version(version.clone()),
+        );
+        let _ = cluster_info
+            .gossip
+            .write()
+            .unwrap()
+            .crds
+            .insert(value, solana_sdk::timing::timestamp());
+        let filter = RpcNodeFilter {
+            shred_version: 1,
+            min_version: Some((
+                version.version.major,
+                version.version.minor,
+                version.version.patch,
+            )),
+        };
+        assert_eq!(get_rpc_peers(&cluster_info, &filter), vec![peer0]);
+        let filter = RpcNodeFilter {
+            shred_version: 1,
+            min_version: Some((version.version.major + 1, 0, 0)),
+        };
+        assert!(get_rpc_peers(&cluster_info, &filter).is_empty());
+    }
 }
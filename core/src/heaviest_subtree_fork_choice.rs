@@ -190,6 +190,33 @@ impl HeaviestSubtreeForkChoice {
         self.last_root_time = Instant::now();
     }
 
+    /// Removes `slot` and its descendants, for the forks pruned from `BankForks`
+    pub fn remove_subtree(&mut self, slot: Slot) {
+        let parent = match self.parent(slot) {
+            Some(parent) => parent,
+            // Either the root, which is not removed, or already removed
+            None => return,
+        };
+        let mut remove_slots = vec![slot];
+        while let Some(remove_slot) = remove_slots.pop() {
+            if let Some(fork_info) = self.fork_infos.remove(&remove_slot) {
+                remove_slots.extend(fork_info.children);
+            }
+        }
+        self.fork_infos
+            .get_mut(&parent)
+            .expect("parent must exist in self.fork_infos")
+            .children
+            .retain(|child| *child != slot);
+
+        // Update the stake and best slot of the ancestors
+        self.aggregate_slot(parent);
+        let ancestors: Vec<_> = self.ancestor_iterator(parent).collect();
+        for ancestor in ancestors {
+            self.aggregate_slot(ancestor);
+        }
+    }
+
     pub fn add_root_parent(&mut self, root_parent: Slot) {
         assert!(root_parent < self.root);
         assert!(self.fork_infos.get(&root_parent).is_none());
@@ -793,6 +820,36 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_remove_subtree() {
+        let mut heaviest_subtree_fork_choice = setup_forks();
+        let stake = 100;
+        let (bank, vote_pubkeys) = bank_utils::setup_bank_and_vote_pubkeys(1, stake);
+        heaviest_subtree_fork_choice.add_votes(
+            &[(vote_pubkeys[0], 4)],
+            bank.epoch_stakes_map(),
+            bank.epoch_schedule(),
+        );
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot(), 4);
+
+        // Removing the fork of slot 2 takes its stake away from the ancestors
+        heaviest_subtree_fork_choice.remove_subtree(2);
+        for i in 0..=6 {
+            let exists = i != 2 && i != 4;
+            assert_eq!(
+                heaviest_subtree_fork_choice.fork_infos.contains_key(&i),
+                exists
+            );
+        }
+        assert_eq!(heaviest_subtree_fork_choice.children(1).unwrap(), &[3]);
+        assert_eq!(heaviest_subtree_fork_choice.stake_voted_subtree(0), Some(0));
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot(), 6);
+
+        // The root is never removed
+        heaviest_subtree_fork_choice.remove_subtree(0);
+        assert_eq!(heaviest_subtree_fork_choice.best_overall_slot(), 6);
+    }
+
     #[test]
     fn test_set_root_and_add_votes() {
         let mut heaviest_subtree_fork_choice = setup_forks();
@@ -3,6 +3,7 @@
 use solana_ledger::blockstore::{Blockstore, PurgeType};
 use solana_ledger::blockstore_db::Result as BlockstoreResult;
 use solana_measure::measure::Measure;
+use solana_metrics::registry;
 use solana_sdk::clock::{Slot, CFG as CLOCK_CFG, TICKS_PER_DAY};
 use std::string::ToString;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -24,6 +25,41 @@ toml_config::derived_values! {
     DEFAULT_COMPACTION_SLOT_INTERVAL: u64 = *TICKS_PER_DAY / CLOCK_CFG.DEFAULT_TICKS_PER_SLOT;
 }
 
+/// Retention of the transaction statuses and address signatures, which take more space than
+/// the shreds when RPC transaction history is enabled. Enforced on top of the shred limit, which
+/// purges them along with the shreds of their slots
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TransactionStatusRetention {
+    /// Purge the transaction statuses of slots this many slots older than the root. They are
+    /// purged half at a time, so between this many and twice as many slots are retained
+    pub max_slots: Option<u64>,
+    /// Purge the older half of the transaction statuses once they take more than this many bytes
+    pub max_bytes: Option<u64>,
+}
+
+impl TransactionStatusRetention {
+    pub fn is_enabled(&self) -> bool {
+        self.max_slots.is_some() || self.max_bytes.is_some()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TransactionStatusCleanup {
+    retention: TransactionStatusRetention,
+    // Size of the columns when they were last purged for their size. The size only drops once the
+    // purged data is compacted, so they are not purged for their size again until then
+    size_at_last_purge: Option<u64>,
+}
+
+impl TransactionStatusCleanup {
+    pub fn new(retention: TransactionStatusRetention) -> Self {
+        Self {
+            retention,
+            size_at_last_purge: None,
+        }
+    }
+}
+
 pub struct LedgerCleanupService {
     t_cleanup: JoinHandle<()>,
 }
@@ -32,16 +68,19 @@ impl LedgerCleanupService {
     pub fn new(
         new_root_receiver: Receiver<Slot>,
         blockstore: Arc<Blockstore>,
-        max_ledger_shreds: u64,
+        max_ledger_shreds: Option<u64>,
+        transaction_status_retention: TransactionStatusRetention,
         exit: &Arc<AtomicBool>,
     ) -> Self {
         info!(
-            "LedgerCleanupService active. Max Ledger Slots {}",
-            max_ledger_shreds
+            "LedgerCleanupService active. Max Ledger Slots {:?}, transaction status retention {:?}",
+            max_ledger_shreds, transaction_status_retention
         );
         let exit = exit.clone();
         let mut last_purge_slot = 0;
         let mut last_compaction_slot = 0;
+        let mut transaction_status_cleanup =
+            TransactionStatusCleanup::new(transaction_status_retention);
 
         let t_cleanup = Builder::new()
             .name("solana-ledger-cleanup".to_string())
@@ -49,10 +88,12 @@ impl LedgerCleanupService {
                 if exit.load(Ordering::Relaxed) {
                     break;
                 }
+                blockstore.run_scheduled_compactions();
                 if let Err(e) = Self::cleanup_ledger(
                     &new_root_receiver,
                     &blockstore,
                     max_ledger_shreds,
+                    &mut transaction_status_cleanup,
                     &mut last_purge_slot,
                     CFG.DEFAULT_PURGE_SLOT_INTERVAL,
                     &mut last_compaction_slot,
@@ -123,10 +164,12 @@ impl LedgerCleanupService {
         Ok(root)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn cleanup_ledger(
         new_root_receiver: &Receiver<Slot>,
         blockstore: &Arc<Blockstore>,
-        max_ledger_shreds: u64,
+        max_ledger_shreds: Option<u64>,
+        transaction_status_cleanup: &mut TransactionStatusCleanup,
         last_purge_slot: &mut u64,
         purge_interval: u64,
         last_compaction_slot: &mut u64,
@@ -145,8 +188,15 @@ impl LedgerCleanupService {
 
         *last_purge_slot = root;
 
+        Self::cleanup_transaction_status(blockstore, root, transaction_status_cleanup);
+
         let (slots_to_clean, purge_first_slot, lowest_cleanup_slot, total_shreds) =
-            Self::find_slots_to_clean(&blockstore, root, max_ledger_shreds);
+            match max_ledger_shreds {
+                Some(max_ledger_shreds) => {
+                    Self::find_slots_to_clean(&blockstore, root, max_ledger_shreds)
+                }
+                None => (false, 0, 0, 0),
+            };
 
         if slots_to_clean {
             let mut compact_first_slot = std::u64::MAX;
@@ -214,6 +264,74 @@ impl LedgerCleanupService {
         Ok(())
     }
 
+    fn cleanup_transaction_status(
+        blockstore: &Blockstore,
+        root: Slot,
+        cleanup: &mut TransactionStatusCleanup,
+    ) {
+        let retention = cleanup.retention;
+        if !retention.is_enabled() {
+            return;
+        }
+        let size_pre = match blockstore.transaction_status_size() {
+            Ok(size) => size,
+            Err(err) => {
+                warn!(
+                    "Unable to get the size of the transaction statuses: {:?}",
+                    err
+                );
+                return;
+            }
+        };
+        if let Some(size_at_last_purge) = cleanup.size_at_last_purge {
+            if size_pre < size_at_last_purge {
+                cleanup.size_at_last_purge = None;
+            }
+        }
+
+        let mut purge_time = Measure::start("purge_transaction_status");
+        let purge = |to_slot, reason: &str| match blockstore.purge_transaction_status(to_slot) {
+            Ok(Some(index)) => {
+                info!(
+                    "purged transaction status index {} up to slot {} for {}",
+                    index, to_slot, reason
+                );
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                error!("Error: {:?}; Couldn't purge transaction statuses", err);
+                false
+            }
+        };
+        let purged_for_slots = retention
+            .max_slots
+            .map(|max_slots| root > max_slots && purge(root - max_slots - 1, "slots"))
+            .unwrap_or(false);
+        let purged_for_size = !purged_for_slots
+            && cleanup.size_at_last_purge.is_none()
+            && retention
+                .max_bytes
+                .map(|max_bytes| size_pre > max_bytes && purge(root, "size"))
+                .unwrap_or(false);
+        if purged_for_size {
+            cleanup.size_at_last_purge = Some(size_pre);
+        }
+        purge_time.stop();
+
+        datapoint_info!(
+            "ledger_transaction_status_cleanup",
+            ("root", root, i64),
+            ("size", size_pre, i64),
+            ("purged_for_slots", purged_for_slots, bool),
+            ("purged_for_size", purged_for_size, bool),
+            ("purge_us", purge_time.as_us(), i64),
+        );
+        registry::namespace("blockstore")
+            .gauge("transaction_status_size")
+            .set(size_pre as i64);
+    }
+
     fn report_disk_metrics(
         pre: BlockstoreResult<u64>,
         post: BlockstoreResult<u64>,
@@ -227,6 +345,9 @@ impl LedgerCleanupService {
                 ("disk_utilization_delta", (pre as i64 - post as i64), i64),
                 ("total_shreds", total_shreds, i64),
             );
+            let namespace = registry::namespace("blockstore");
+            namespace.gauge("disk_utilization").set(post as i64);
+            namespace.gauge("total_shreds").set(total_shreds as i64);
         }
     }
 
@@ -239,6 +360,8 @@ mod tests {
     use super::*;
     use solana_ledger::blockstore::make_many_slot_entries;
     use solana_ledger::get_tmp_ledger_path;
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
+    use solana_transaction_status::TransactionStatusMeta;
     use std::sync::mpsc::channel;
 
     #[test]
@@ -258,7 +381,8 @@ mod tests {
         LedgerCleanupService::cleanup_ledger(
             &receiver,
             &blockstore,
-            5,
+            Some(5),
+            &mut TransactionStatusCleanup::default(),
             &mut last_purge_slot,
             10,
             &mut last_compaction_slot,
@@ -276,6 +400,59 @@ mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_cleanup_transaction_status() {
+        solana_logger::setup();
+        let blockstore_path = get_tmp_ledger_path!();
+        let blockstore = Blockstore::open(&blockstore_path).unwrap();
+        let (shreds, _) = make_many_slot_entries(0, 50, 5);
+        blockstore.insert_shreds(shreds, None, false).unwrap();
+        let signature = Signature::new(&[1u8; 64]);
+        blockstore
+            .write_transaction_status(
+                10,
+                signature,
+                vec![&Pubkey::new(&[2u8; 32])],
+                vec![],
+                &TransactionStatusMeta::default(),
+            )
+            .unwrap();
+        let blockstore = Arc::new(blockstore);
+        let (sender, receiver) = channel();
+
+        let mut transaction_status_cleanup =
+            TransactionStatusCleanup::new(TransactionStatusRetention {
+                max_slots: Some(20),
+                max_bytes: None,
+            });
+        let mut last_purge_slot = 0;
+        let mut last_compaction_slot = 0;
+        for root in &[30, 50] {
+            sender.send(*root).unwrap();
+            LedgerCleanupService::cleanup_ledger(
+                &receiver,
+                &blockstore,
+                None,
+                &mut transaction_status_cleanup,
+                &mut last_purge_slot,
+                10,
+                &mut last_compaction_slot,
+                10,
+            )
+            .unwrap();
+        }
+
+        // The transaction status of slot 10 is purged, the shreds are not
+        assert!(blockstore
+            .read_transaction_status((signature, 10))
+            .unwrap()
+            .is_none());
+        assert_eq!(blockstore.slot_meta_iterator(0).unwrap().count(), 50);
+
+        drop(blockstore);
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_cleanup_speed() {
         solana_logger::setup();
@@ -315,7 +492,8 @@ mod tests {
             LedgerCleanupService::cleanup_ledger(
                 &receiver,
                 &blockstore,
-                initial_slots,
+                Some(initial_slots),
+                &mut TransactionStatusCleanup::default(),
                 &mut last_purge_slot,
                 10,
                 &mut last_compaction_slot,
@@ -7,8 +7,11 @@
 //!
 
 pub mod accounts_hash_verifier;
+pub mod ancestor_hashes_service;
+pub mod banking_scheduler;
 pub mod banking_stage;
 pub mod bigtable_upload_service;
+pub mod bootstrap_rpc_service;
 pub mod broadcast_stage;
 pub mod cache_block_time_service;
 pub mod cluster_info_vote_listener;
@@ -36,17 +39,23 @@ pub mod epoch_slots;
 pub mod fetch_stage;
 pub mod fork_choice;
 pub mod gen_keys;
+pub mod gossip_compression;
+pub mod gossip_peer_stats;
 pub mod gossip_service;
 pub mod heaviest_subtree_fork_choice;
 pub mod ledger_cleanup_service;
+pub mod link_conditioner;
 pub mod local_vote_signer_service;
 pub mod non_circulating_supply;
 pub mod optimistic_confirmation_verifier;
 pub mod optimistically_confirmed_bank_tracker;
+pub mod peer_bandwidth;
 pub mod ping_pong;
+pub mod planned_hard_fork_service;
 pub mod poh_recorder;
 pub mod poh_service;
 pub mod progress_map;
+pub mod prometheus_service;
 pub mod pubkey_references;
 pub mod repair_response;
 pub mod repair_service;
@@ -69,16 +78,25 @@ pub mod sigverify;
 pub mod sigverify_shreds;
 pub mod sigverify_stage;
 pub mod snapshot_packager_service;
+pub mod supermajority_progress;
+pub mod telemetry_service;
 pub mod test_validator;
+pub mod tower_storage;
 pub mod tpu;
+pub mod tpu_qos_stage;
+pub mod tpu_tcp_stage;
+pub mod transaction_forwarder;
 pub mod transaction_status_service;
 pub mod tree_diff;
+pub mod turbine_tree;
 pub mod tvu;
 pub mod validator;
 pub mod verified_vote_packets;
+pub mod vote_account_monitor_service;
 pub mod vote_stake_tracker;
 pub mod weighted_shuffle;
 pub mod window_service;
+pub mod write_lock_qos;
 
 #[macro_use]
 extern crate log;
@@ -0,0 +1,200 @@
+//! The `link_conditioner` module degrades the links between nodes for tests: the packets a node
+//! receives on its gossip, TVU and TPU sockets can be dropped or delayed depending on the node
+//! which sent them.
+
+use solana_perf::packet::{Packet, Packets};
+use solana_sdk::pubkey::Pubkey;
+use solana_streamer::streamer::{PacketReceiver, PacketSender};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    net::SocketAddr,
+    sync::{
+        mpsc::{channel, RecvTimeoutError},
+        Arc,
+    },
+    thread::{sleep, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// How long the conditioning thread waits for packets when none are delayed
+const RECV_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LinkSocket {
+    Gossip,
+    /// Shreds, broadcast or retransmitted, including the forwarded ones
+    Tvu,
+    /// Transactions and votes, including the forwarded ones
+    Tpu,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkAction {
+    Deliver,
+    Drop,
+    Delay(Duration),
+}
+
+pub trait LinkConditioner: Debug + Send + Sync {
+    /// Called by every validator starting up with the addresses its packets are sent from, so
+    /// that the conditioner can tell which node a packet comes from
+    fn register_node(&self, _id: &Pubkey, _addrs: &[SocketAddr]) {}
+
+    /// Decides what happens to a packet received by the node `id` on `socket` from `from`
+    fn condition(&self, id: &Pubkey, socket: LinkSocket, from: &SocketAddr) -> LinkAction;
+}
+
+/// A conditioner applied to the packets received by the node `id`
+#[derive(Clone, Debug)]
+pub struct NodeLinkConditioner {
+    pub id: Pubkey,
+    pub conditioner: Arc<dyn LinkConditioner>,
+}
+
+/// Returns the sender the receivers of `socket` should send their packets to. With a
+/// conditioner, the packets go through a thread applying it before reaching `sender`; the thread
+/// exits once every clone of the returned sender is dropped.
+pub fn conditioned_sender(
+    link_conditioner: Option<&NodeLinkConditioner>,
+    socket: LinkSocket,
+    sender: PacketSender,
+) -> (PacketSender, Option<JoinHandle<()>>) {
+    match link_conditioner {
+        Some(link_conditioner) => {
+            let (conditioned_sender, receiver) = channel();
+            let link_conditioner = link_conditioner.clone();
+            let thread_hdl = Builder::new()
+                .name("solana-link-conditioner".to_string())
+                .spawn(move || condition_packets(&link_conditioner, socket, &receiver, &sender))
+                .unwrap();
+            (conditioned_sender, Some(thread_hdl))
+        }
+        None => (sender, None),
+    }
+}
+
+fn condition_packets(
+    link_conditioner: &NodeLinkConditioner,
+    socket: LinkSocket,
+    receiver: &PacketReceiver,
+    sender: &PacketSender,
+) {
+    // Keyed by release time, then arrival order so that packets delayed alike stay in order
+    let mut delayed: BTreeMap<(Instant, u64), Packet> = BTreeMap::new();
+    let mut num_delayed = 0;
+    let mut disconnected = false;
+    loop {
+        let timeout = delayed
+            .keys()
+            .next()
+            .map(|(release, _)| release.saturating_duration_since(Instant::now()))
+            .unwrap_or(RECV_TIMEOUT);
+        let mut deliver = vec![];
+        // The packets still delayed are released once the receivers are gone
+        if disconnected {
+            sleep(timeout);
+        } else {
+            match receiver.recv_timeout(timeout) {
+                Ok(packets) => {
+                    for packet in packets.packets.iter() {
+                        let action = link_conditioner.conditioner.condition(
+                            &link_conditioner.id,
+                            socket,
+                            &packet.meta.addr(),
+                        );
+                        match action {
+                            LinkAction::Deliver => deliver.push(packet.clone()),
+                            LinkAction::Drop => (),
+                            LinkAction::Delay(latency) => {
+                                delayed.insert(
+                                    (Instant::now() + latency, num_delayed),
+                                    packet.clone(),
+                                );
+                                num_delayed += 1;
+                            }
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => disconnected = true,
+            }
+        }
+
+        let now = Instant::now();
+        while let Some(key) = delayed.keys().next().cloned() {
+            if key.0 > now {
+                break;
+            }
+            deliver.extend(delayed.remove(&key));
+        }
+        if !deliver.is_empty() && sender.send(Packets::new(deliver)).is_err() {
+            break;
+        }
+        if disconnected && delayed.is_empty() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[derive(Debug)]
+    struct PortConditioner;
+
+    impl LinkConditioner for PortConditioner {
+        fn condition(&self, _id: &Pubkey, _socket: LinkSocket, from: &SocketAddr) -> LinkAction {
+            match from.port() {
+                1 => LinkAction::Deliver,
+                2 => LinkAction::Drop,
+                port => LinkAction::Delay(Duration::from_millis(port as u64)),
+            }
+        }
+    }
+
+    fn packet_from(port: u16) -> Packet {
+        let mut packet = Packet::default();
+        packet
+            .meta
+            .set_addr(&SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port));
+        packet
+    }
+
+    #[test]
+    fn test_conditioned_sender() {
+        let (sender, receiver) = channel();
+        let (unconditioned, thread_hdl) = conditioned_sender(None, LinkSocket::Tpu, sender);
+        assert!(thread_hdl.is_none());
+        unconditioned
+            .send(Packets::new(vec![packet_from(2)]))
+            .unwrap();
+        assert_eq!(receiver.recv().unwrap().packets.len(), 1);
+
+        let link_conditioner = NodeLinkConditioner {
+            id: Pubkey::new_unique(),
+            conditioner: Arc::new(PortConditioner),
+        };
+        let (conditioned, thread_hdl) =
+            conditioned_sender(Some(&link_conditioner), LinkSocket::Tpu, unconditioned);
+        let start = Instant::now();
+        conditioned
+            .send(Packets::new(vec![
+                packet_from(300),
+                packet_from(1),
+                packet_from(2),
+                packet_from(200),
+            ]))
+            .unwrap();
+        drop(conditioned);
+        let ports: Vec<Vec<u16>> = receiver
+            .iter()
+            .map(|packets| packets.packets.iter().map(|p| p.meta.port).collect())
+            .collect();
+        assert_eq!(ports, vec![vec![1], vec![200], vec![300]]);
+        assert!(start.elapsed() >= Duration::from_millis(300));
+        thread_hdl.unwrap().join().unwrap();
+    }
+}
@@ -4,8 +4,9 @@
 
 use crate::rpc_subscriptions::RpcSubscriptions;
 use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use solana_client::rpc_response::SlotUpdate;
 use solana_runtime::{bank::Bank, bank_forks::BankForks};
-use solana_sdk::clock::Slot;
+use solana_sdk::{clock::Slot, timing::timestamp};
 use std::{
     collections::HashSet,
     sync::{
@@ -112,6 +113,10 @@ impl OptimisticallyConfirmedBankTracker {
         debug!("received bank notification: {:?}", notification);
         match notification {
             BankNotification::OptimisticallyConfirmed(slot) => {
+                subscriptions.notify_slot_update(SlotUpdate::OptimisticConfirmation {
+                    slot,
+                    timestamp: timestamp(),
+                });
                 if let Some(bank) = bank_forks
                     .read()
                     .unwrap()
@@ -0,0 +1,217 @@
+//! The `peer_bandwidth` module tracks the bytes served to and received from each peer
+//! over repair and turbine retransmit, and maintains rolling contribution scores which
+//! can be used to prefer peers that reciprocate over leecher nodes. The shreds retransmitted
+//! to the leaves of a turbine tree are not counted as served, since leaves have no one to
+//! retransmit them to and could not reciprocate.
+
+use solana_sdk::{pubkey::Pubkey, timing::timestamp};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        RwLock,
+    },
+};
+
+toml_config::package_config! {
+    PEER_BANDWIDTH_WINDOW_MS: u64,
+    PEER_BANDWIDTH_DECAY: f64,
+    PEER_BANDWIDTH_LEECHER_SCORE: f64,
+    PEER_BANDWIDTH_LEECHER_MIN_BYTES: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerBandwidth {
+    /// Total bytes sent to the peer
+    pub bytes_served: u64,
+    /// Total bytes received from the peer
+    pub bytes_received: u64,
+    /// Exponentially decayed bytes sent per window
+    pub served_rate: f64,
+    /// Exponentially decayed bytes received per window
+    pub received_rate: f64,
+    window_served: u64,
+    window_received: u64,
+}
+
+impl PeerBandwidth {
+    /// Ratio of the bytes received from the peer to the bytes served to it.
+    /// Peers scoring above 1.0 contribute more than they take.
+    pub fn score(&self) -> f64 {
+        (self.received_rate + 1.0) / (self.served_rate + 1.0)
+    }
+
+    pub fn is_leecher(&self) -> bool {
+        self.served_rate >= CFG.PEER_BANDWIDTH_LEECHER_MIN_BYTES as f64
+            && self.score() < CFG.PEER_BANDWIDTH_LEECHER_SCORE
+    }
+
+    fn roll(&mut self) {
+        let decay = CFG.PEER_BANDWIDTH_DECAY;
+        self.served_rate = self.served_rate * decay + self.window_served as f64 * (1.0 - decay);
+        self.received_rate =
+            self.received_rate * decay + self.window_received as f64 * (1.0 - decay);
+        self.window_served = 0;
+        self.window_received = 0;
+    }
+}
+
+#[derive(Default)]
+pub struct PeerBandwidthTracker {
+    peers: RwLock<HashMap<Pubkey, PeerBandwidth>>,
+    // Maps the source ip of received packets back to the peer that sent them
+    peer_addrs: RwLock<HashMap<IpAddr, Pubkey>>,
+    last_roll_ms: AtomicU64,
+    prioritize_reciprocating_peers: AtomicBool,
+}
+
+impl PeerBandwidthTracker {
+    /// Adds bytes served to peers. Callers sum up the bytes of a whole batch of packets first,
+    /// so that the tracker is only locked once per batch.
+    pub fn record_served<I>(&self, bytes_served: I)
+    where
+        I: IntoIterator<Item = (Pubkey, u64)>,
+    {
+        let mut peers = self.peers.write().unwrap();
+        for (pubkey, bytes) in bytes_served {
+            let peer = peers.entry(pubkey).or_default();
+            peer.bytes_served += bytes;
+            peer.window_served += bytes;
+        }
+    }
+
+    pub fn record_received<I>(&self, bytes_received: I)
+    where
+        I: IntoIterator<Item = (IpAddr, u64)>,
+    {
+        let peer_addrs = self.peer_addrs.read().unwrap();
+        let mut peers = self.peers.write().unwrap();
+        for (addr, bytes) in bytes_received {
+            if let Some(pubkey) = peer_addrs.get(&addr) {
+                let peer = peers.entry(*pubkey).or_default();
+                peer.bytes_received += bytes;
+                peer.window_received += bytes;
+            }
+        }
+    }
+
+    pub fn set_peer_addrs<I>(&self, peer_addrs: I)
+    where
+        I: IntoIterator<Item = (IpAddr, Pubkey)>,
+    {
+        *self.peer_addrs.write().unwrap() = peer_addrs.into_iter().collect();
+    }
+
+    /// Folds the current window into the rolling rates if at least
+    /// `PEER_BANDWIDTH_WINDOW_MS` has passed since the last roll.
+    /// Returns true if the window was rolled.
+    pub fn maybe_roll(&self) -> bool {
+        let now = timestamp();
+        let last = self.last_roll_ms.load(Ordering::Acquire);
+        if now < last + CFG.PEER_BANDWIDTH_WINDOW_MS
+            || self
+                .last_roll_ms
+                .compare_exchange(last, now, Ordering::AcqRel, Ordering::Acquire)
+                .is_err()
+        {
+            return false;
+        }
+        self.roll();
+        true
+    }
+
+    fn roll(&self) {
+        let mut peers = self.peers.write().unwrap();
+        let mut window_served = 0;
+        let mut window_received = 0;
+        for peer in peers.values_mut() {
+            window_served += peer.window_served;
+            window_received += peer.window_received;
+            peer.roll();
+        }
+        // Forget peers we have not exchanged anything with for a while
+        peers.retain(|_, peer| peer.served_rate >= 1.0 || peer.received_rate >= 1.0);
+        let num_leechers = peers.values().filter(|peer| peer.is_leecher()).count();
+        datapoint_info!(
+            "peer_bandwidth",
+            ("num_peers", peers.len(), i64),
+            ("num_leechers", num_leechers, i64),
+            ("bytes_served", window_served, i64),
+            ("bytes_received", window_received, i64),
+        );
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<PeerBandwidth> {
+        self.peers.read().unwrap().get(pubkey).cloned()
+    }
+
+    pub fn peers(&self) -> Vec<(Pubkey, PeerBandwidth)> {
+        self.peers
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(pubkey, peer)| (*pubkey, peer.clone()))
+            .collect()
+    }
+
+    pub fn is_leecher(&self, pubkey: &Pubkey) -> bool {
+        self.peers
+            .read()
+            .unwrap()
+            .get(pubkey)
+            .map(PeerBandwidth::is_leecher)
+            .unwrap_or(false)
+    }
+
+    pub fn set_prioritize_reciprocating_peers(&self, enable: bool) {
+        self.prioritize_reciprocating_peers
+            .store(enable, Ordering::Relaxed);
+    }
+
+    /// If set, serving requests from leecher nodes is deprioritized under load
+    pub fn prioritize_reciprocating_peers(&self) -> bool {
+        self.prioritize_reciprocating_peers.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_peer_bandwidth_scores() {
+        let tracker = PeerBandwidthTracker::default();
+        let leecher = solana_sdk::pubkey::new_rand();
+        let seeder = solana_sdk::pubkey::new_rand();
+        let seeder_addr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        tracker.set_peer_addrs(vec![(seeder_addr, seeder)]);
+
+        let bytes = CFG.PEER_BANDWIDTH_LEECHER_MIN_BYTES * 100;
+        tracker.record_served(vec![(leecher, bytes), (seeder, bytes)]);
+        tracker.record_received(vec![
+            (seeder_addr, bytes),
+            (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), bytes),
+        ]);
+        tracker.roll();
+
+        let seeder_bandwidth = tracker.get(&seeder).unwrap();
+        assert_eq!(seeder_bandwidth.bytes_served, bytes);
+        assert_eq!(seeder_bandwidth.bytes_received, bytes);
+        assert!((seeder_bandwidth.score() - 1.0).abs() < f64::EPSILON);
+        assert!(!tracker.is_leecher(&seeder));
+
+        let leecher_bandwidth = tracker.get(&leecher).unwrap();
+        assert_eq!(leecher_bandwidth.bytes_received, 0);
+        assert!(tracker.is_leecher(&leecher));
+        assert_eq!(tracker.peers().len(), 2);
+
+        // Rates decay away once the peers go quiet
+        for _ in 0..1_000 {
+            tracker.roll();
+        }
+        assert!(tracker.peers().is_empty());
+        assert!(!tracker.is_leecher(&leecher));
+    }
+}
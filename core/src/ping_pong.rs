@@ -35,9 +35,12 @@ pub struct PingCache {
     pings: LruCache<(Pubkey, SocketAddr), Instant>,
     // Verified pong responses from remote nodes.
     pongs: LruCache<(Pubkey, SocketAddr), Instant>,
-    // Hash of ping tokens sent out to remote nodes,
+    // Hash of ping tokens sent out to remote nodes, and when they were sent,
     // pending a pong response back.
-    pending_cache: LruCache<Hash, (Pubkey, SocketAddr)>,
+    pending_cache: LruCache<Hash, ((Pubkey, SocketAddr), Instant)>,
+    // Smoothed round trip time of the pings to remote nodes, at their last
+    // address answering pings.
+    rtts: LruCache<Pubkey, (SocketAddr, Duration)>,
 }
 
 impl<T: Serialize> Ping<T> {
@@ -138,26 +141,52 @@ impl PingCache {
             pings: LruCache::new(cap),
             pongs: LruCache::new(cap),
             pending_cache: LruCache::new(cap),
+            rtts: LruCache::new(cap),
         }
     }
 
     /// Checks if the pong hash, pubkey and socket match a ping message sent
     /// out previously. If so records current timestamp for the remote node and
-    /// returns true.
+    /// returns true. The round trip time of the ping is recorded for the
+    /// remote node.
     /// Note: Does not verify the signature.
     pub fn add(&mut self, pong: &Pong, socket: SocketAddr, now: Instant) -> bool {
         let node = (pong.pubkey(), socket);
         match self.pending_cache.peek(&pong.hash) {
-            Some(value) if *value == node => {
+            Some((value, sent)) if *value == node => {
+                let rtt = now.saturating_duration_since(*sent);
                 self.pings.pop(&node);
                 self.pongs.put(node, now);
                 self.pending_cache.pop(&pong.hash);
+                self.record_rtt(node, rtt);
                 true
             }
             _ => false,
         }
     }
 
+    // Smooths the round trip times as TCP does, restarting from the sample if
+    // the remote node answers from another address.
+    fn record_rtt(&mut self, (pubkey, socket): (Pubkey, SocketAddr), sample: Duration) {
+        let rtt = match self.rtts.peek(&pubkey) {
+            Some((addr, rtt)) if *addr == socket => (*rtt * 7 + sample) / 8,
+            _ => sample,
+        };
+        self.rtts.put(pubkey, (socket, rtt));
+    }
+
+    /// Returns the smoothed round trip time of the pings to the remote node,
+    /// along with the address it last answered from.
+    pub fn rtt(&self, pubkey: &Pubkey) -> Option<(SocketAddr, Duration)> {
+        self.rtts.peek(pubkey).copied()
+    }
+
+    /// Returns the smoothed round trip time of the pings to every remote node
+    /// which answered one.
+    pub fn rtts(&self) -> impl Iterator<Item = (&Pubkey, Duration)> {
+        self.rtts.iter().map(|(pubkey, (_, rtt))| (pubkey, *rtt))
+    }
+
     /// Checks if the remote node has been pinged recently. If not, calls the
     /// given function to generates a new ping message, records current
     /// timestamp and hash of ping token, and returns the ping message.
@@ -179,7 +208,7 @@ impl PingCache {
                 let ping = pingf()?;
                 let hash = hash::hash(&serialize(&ping.token).ok()?);
                 self.pings.put(node, now);
-                self.pending_cache.put(hash, node);
+                self.pending_cache.put(hash, (node, now));
                 Some(ping)
             }
         }
@@ -231,6 +260,7 @@ impl PingCache {
             pings: LruCache::new(self.pings.cap()),
             pongs: LruCache::new(self.pongs.cap()),
             pending_cache: LruCache::new(self.pending_cache.cap()),
+            rtts: LruCache::new(self.rtts.cap()),
         };
         for (k, v) in self.pongs.iter().rev() {
             clone.pings.put(*k, *v);
@@ -241,6 +271,9 @@ impl PingCache {
         for (k, v) in self.pending_cache.iter().rev() {
             clone.pending_cache.put(*k, *v);
         }
+        for (k, v) in self.rtts.iter().rev() {
+            clone.rtts.put(*k, *v);
+        }
         clone
     }
 }
@@ -397,4 +430,49 @@ mod tests {
             assert_eq!(seen_nodes.insert(node), ping.is_some());
         }
     }
+
+    #[test]
+    fn test_ping_cache_rtt() {
+        let now = Instant::now();
+        let mut rng = rand::thread_rng();
+        let ttl = Duration::from_millis(256);
+        let mut cache = PingCache::new(ttl, /*cap=*/ 1000);
+        let this_node = Keypair::new();
+        let remote_node = Keypair::new();
+        let socket = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8001));
+        let other_socket = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 8002));
+        let node = (remote_node.pubkey(), socket);
+        let mut ping_pong = |cache: &mut PingCache, socket, sent: Instant, rtt: Duration| {
+            let pingf = || Ping::<Token>::new_rand(&mut rng, &this_node).ok();
+            let ping = cache.check(sent, (remote_node.pubkey(), socket), pingf).1;
+            let pong = Pong::new(&ping.unwrap(), &remote_node).unwrap();
+            assert!(cache.add(&pong, socket, sent + rtt));
+        };
+        assert_eq!(cache.rtt(&node.0), None);
+
+        ping_pong(&mut cache, socket, now, Duration::from_millis(80));
+        assert_eq!(
+            cache.rtt(&node.0),
+            Some((socket, Duration::from_millis(80)))
+        );
+
+        // Later samples are smoothed
+        let now = now + ttl;
+        ping_pong(&mut cache, socket, now, Duration::from_millis(160));
+        assert_eq!(
+            cache.rtt(&node.0),
+            Some((socket, Duration::from_millis(90)))
+        );
+        assert_eq!(
+            cache.rtts().collect::<Vec<_>>(),
+            vec![(&node.0, Duration::from_millis(90))]
+        );
+
+        // Unless the node answers from another address
+        ping_pong(&mut cache, other_socket, now, Duration::from_millis(20));
+        assert_eq!(
+            cache.rtt(&node.0),
+            Some((other_socket, Duration::from_millis(20)))
+        );
+    }
 }
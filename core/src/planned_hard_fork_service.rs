@@ -0,0 +1,189 @@
+//! The `planned_hard_fork_service` watches the hard forks operators advertise in gossip ahead of
+//! a coordinated restart, and warns when a supermajority of the stake plans one which this
+//! validator is not configured with: restarted without it, the validator would be left on the
+//! old shred version.
+
+use crate::{cluster_info::ClusterInfo, crds_value::PlannedHardFork};
+use solana_ledger::staking_utils;
+use solana_runtime::bank_forks::BankForks;
+use solana_sdk::{clock::Slot, pubkey::Pubkey};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{self, sleep, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const SLEEP_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct PlannedHardForkService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl PlannedHardForkService {
+    /// `planned_hard_fork` is the hard fork this validator advertises itself, if any
+    pub fn new(
+        planned_hard_fork: Option<Slot>,
+        cluster_info: &Arc<ClusterInfo>,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let exit = exit.clone();
+        let cluster_info = cluster_info.clone();
+        let bank_forks = bank_forks.clone();
+
+        info!("Starting PlannedHardFork service");
+        let thread_hdl = Builder::new()
+            .name("solana-planned-hard-fork".to_string())
+            .spawn(move || {
+                Self::run(planned_hard_fork, &cluster_info, &bank_forks, &exit);
+            })
+            .unwrap();
+
+        Self { thread_hdl }
+    }
+
+    fn run(
+        planned_hard_fork: Option<Slot>,
+        cluster_info: &ClusterInfo,
+        bank_forks: &RwLock<BankForks>,
+        exit: &AtomicBool,
+    ) {
+        // Each hard fork is only warned about once
+        let mut warned: HashSet<(Slot, u16)> = HashSet::new();
+        let mut last_check: Option<Instant> = None;
+        loop {
+            if exit.load(Ordering::Relaxed) {
+                break;
+            }
+            if last_check.map_or(true, |last_check| last_check.elapsed() >= CHECK_INTERVAL) {
+                last_check = Some(Instant::now());
+                let root_bank = bank_forks.read().unwrap().root_bank().clone();
+                let stakes = staking_utils::staked_nodes(&root_bank);
+                let mut known_hard_forks: Vec<Slot> = root_bank
+                    .hard_forks()
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .map(|(slot, _)| *slot)
+                    .collect();
+                known_hard_forks.extend(planned_hard_fork);
+                let unknown_hard_forks = Self::unknown_supermajority_hard_forks(
+                    &cluster_info.get_planned_hard_forks(),
+                    &stakes,
+                    &known_hard_forks,
+                );
+                let total_stake: u64 = stakes.values().sum();
+                for (slot, shred_version, stake) in unknown_hard_forks {
+                    if !warned.insert((slot, shred_version)) {
+                        continue;
+                    }
+                    warn!(
+                        "{:.1}% of the stake plans a hard fork at slot {} (shred version {}) \
+                         which this validator is not configured with",
+                        stake as f64 * 100.0 / total_stake as f64,
+                        slot,
+                        shred_version,
+                    );
+                    datapoint_warn!(
+                        "planned-hard-fork-unknown",
+                        ("slot", slot, i64),
+                        ("shred_version", shred_version, i64),
+                        ("stake", stake, i64),
+                        ("total_stake", total_stake, i64),
+                    );
+                }
+            }
+            sleep(SLEEP_INTERVAL);
+        }
+    }
+
+    /// Sums the stake advertising each planned (slot, shred version), and returns those backed
+    /// by a supermajority of the stake whose slot is not in `known_hard_forks`, with their stake
+    fn unknown_supermajority_hard_forks(
+        planned_hard_forks: &[PlannedHardFork],
+        stakes: &HashMap<Pubkey, u64>,
+        known_hard_forks: &[Slot],
+    ) -> Vec<(Slot, u16, u64)> {
+        let total_stake: u64 = stakes.values().sum();
+        let mut fork_stakes: HashMap<(Slot, u16), u64> = HashMap::new();
+        for hard_fork in planned_hard_forks {
+            let stake = stakes.get(&hard_fork.from).copied().unwrap_or_default();
+            if stake > 0 {
+                *fork_stakes
+                    .entry((hard_fork.slot, hard_fork.shred_version))
+                    .or_default() += stake;
+            }
+        }
+        let mut unknown_hard_forks: Vec<_> = fork_stakes
+            .into_iter()
+            .filter(|((slot, _), stake)| {
+                *stake as u128 * 3 >= total_stake as u128 * 2 && !known_hard_forks.contains(slot)
+            })
+            .map(|((slot, shred_version), stake)| (slot, shred_version, stake))
+            .collect();
+        unknown_hard_forks.sort_unstable();
+        unknown_hard_forks
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_supermajority_hard_forks() {
+        let nodes: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let stakes: HashMap<Pubkey, u64> = nodes.iter().map(|node| (*node, 25)).collect();
+        let unstaked = Pubkey::new_unique();
+        let planned =
+            |from: &Pubkey, slot, shred_version| PlannedHardFork::new(*from, slot, shred_version);
+
+        // Half of the stake is not a supermajority, unstaked nodes do not count
+        let mut planned_hard_forks = vec![
+            planned(&nodes[0], 100, 7),
+            planned(&nodes[1], 100, 7),
+            planned(&unstaked, 100, 7),
+        ];
+        assert!(PlannedHardForkService::unknown_supermajority_hard_forks(
+            &planned_hard_forks,
+            &stakes,
+            &[]
+        )
+        .is_empty());
+
+        // Nodes planning the same slot with another shred version are counted apart
+        planned_hard_forks.push(planned(&nodes[2], 100, 8));
+        assert!(PlannedHardForkService::unknown_supermajority_hard_forks(
+            &planned_hard_forks,
+            &stakes,
+            &[]
+        )
+        .is_empty());
+
+        planned_hard_forks.push(planned(&nodes[3], 100, 7));
+        assert_eq!(
+            PlannedHardForkService::unknown_supermajority_hard_forks(
+                &planned_hard_forks,
+                &stakes,
+                &[50]
+            ),
+            vec![(100, 7, 75)]
+        );
+        // Nothing to warn about once the hard fork is configured
+        assert!(PlannedHardForkService::unknown_supermajority_hard_forks(
+            &planned_hard_forks,
+            &stakes,
+            &[50, 100]
+        )
+        .is_empty());
+    }
+}
@@ -123,6 +123,25 @@ impl PohRecorder {
         self.leader_after_n_slots(1)
     }
 
+    /// Returns the distinct leaders of the `num_leaders` leader rotations starting `slots` slots
+    /// from now, in the order they are scheduled
+    pub fn upcoming_leaders(&self, slots: u64, num_leaders: usize) -> Vec<Pubkey> {
+        let first_slot = self.tick_height.saturating_sub(1) / self.ticks_per_slot + slots;
+        let num_slots = num_leaders as u64 * CLOCK_CFG.NUM_CONSECUTIVE_LEADER_SLOTS;
+        let mut leaders = Vec::with_capacity(num_leaders);
+        for slot in first_slot..first_slot + num_slots {
+            match self.leader_schedule_cache.slot_leader_at(slot, None) {
+                Some(leader) if !leaders.contains(&leader) => leaders.push(leader),
+                Some(_) => (),
+                None => break,
+            }
+            if leaders.len() == num_leaders {
+                break;
+            }
+        }
+        leaders
+    }
+
     pub fn bank(&self) -> Option<Arc<Bank>> {
         self.working_bank.clone().map(|w| w.bank)
     }
@@ -1384,6 +1403,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_upcoming_leaders() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&ledger_path)
+                .expect("Expected to be able to open database ledger");
+            let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(2);
+            let bank = Arc::new(Bank::new(&genesis_config));
+            let (poh_recorder, _entry_receiver) = PohRecorder::new(
+                0,
+                bank.last_blockhash(),
+                0,
+                None,
+                bank.ticks_per_slot(),
+                &Pubkey::default(),
+                &Arc::new(blockstore),
+                &Arc::new(LeaderScheduleCache::new_from_bank(&bank)),
+                &Arc::new(PohConfig::default()),
+            );
+            // The only staked node leads every rotation
+            let leader = poh_recorder.leader_after_n_slots(1).unwrap();
+            assert_eq!(poh_recorder.upcoming_leaders(1, 3), vec![leader]);
+            assert_eq!(poh_recorder.upcoming_leaders(1, 1), vec![leader]);
+            assert!(poh_recorder.upcoming_leaders(1, 0).is_empty());
+            // No leader schedule this far ahead
+            assert!(poh_recorder.upcoming_leaders(1_000_000_000, 3).is_empty());
+        }
+    }
+
     #[test]
     fn test_flush_virtual_ticks() {
         let ledger_path = get_tmp_ledger_path!();
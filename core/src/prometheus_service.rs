@@ -0,0 +1,123 @@
+//! The `prometheus_service` module serves the metrics registry over HTTP in the Prometheus text
+//! format, for operators scraping validators with Prometheus rather than collecting the
+//! datapoints submitted to InfluxDB.
+
+use crate::validator::ValidatorExit;
+use jsonrpc_core::MetaIoHandler;
+use jsonrpc_http_server::{hyper, RequestMiddleware, RequestMiddlewareAction, ServerBuilder};
+use solana_metrics::registry;
+use std::{
+    net::SocketAddr,
+    sync::{mpsc::channel, Arc, RwLock},
+    thread::{self, Builder, JoinHandle},
+};
+
+struct PrometheusRequestMiddleware;
+
+impl RequestMiddleware for PrometheusRequestMiddleware {
+    fn on_request(&self, request: hyper::Request<hyper::Body>) -> RequestMiddlewareAction {
+        let response = if request.uri().path() == "/metrics" {
+            hyper::Response::builder()
+                .status(hyper::StatusCode::OK)
+                .header(
+                    hyper::header::CONTENT_TYPE,
+                    "text/plain; version=0.0.4; charset=utf-8",
+                )
+                .body(hyper::Body::from(registry::dump_prometheus()))
+        } else {
+            hyper::Response::builder()
+                .status(hyper::StatusCode::NOT_FOUND)
+                .body(hyper::Body::empty())
+        };
+        RequestMiddlewareAction::Respond {
+            should_validate_hosts: false,
+            response: Box::new(jsonrpc_core::futures::future::ok(response.unwrap())),
+        }
+    }
+}
+
+pub struct PrometheusService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl PrometheusService {
+    pub fn new(
+        bind_address: SocketAddr,
+        validator_exit: &Arc<RwLock<Option<ValidatorExit>>>,
+    ) -> Self {
+        info!("prometheus metrics bound to {:?}", bind_address);
+        let (close_handle_sender, close_handle_receiver) = channel();
+        let thread_hdl = Builder::new()
+            .name("solana-prometheus".to_string())
+            .spawn(move || {
+                let server = ServerBuilder::new(MetaIoHandler::<()>::default())
+                    .threads(1)
+                    .request_middleware(PrometheusRequestMiddleware)
+                    .start_http(&bind_address);
+
+                let server = match server {
+                    Ok(server) => server,
+                    Err(err) => {
+                        warn!("Prometheus metrics service unavailable error: {:?}", err);
+                        return;
+                    }
+                };
+                close_handle_sender.send(server.close_handle()).unwrap();
+                server.wait();
+            })
+            .unwrap();
+
+        if let Ok(close_handle) = close_handle_receiver.recv() {
+            validator_exit
+                .write()
+                .unwrap()
+                .as_mut()
+                .unwrap()
+                .register_exit(Box::new(move || close_handle.close()));
+        }
+        Self { thread_hdl }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::create_validator_exit;
+    use std::{
+        io::{Read, Write},
+        net::{IpAddr, Ipv4Addr, TcpStream},
+        sync::atomic::AtomicBool,
+    };
+
+    #[test]
+    fn test_prometheus_service() {
+        registry::namespace("test_prometheus_service")
+            .gauge("gauge")
+            .set(42);
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let validator_exit = create_validator_exit(&exit);
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let bind_address = SocketAddr::new(
+            ip_addr,
+            solana_net_utils::find_available_port_in_range(ip_addr, (10000, 65535)).unwrap(),
+        );
+        let prometheus_service = PrometheusService::new(bind_address, &validator_exit);
+
+        let mut stream = TcpStream::connect(bind_address).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("\nsolana_test_prometheus_service_gauge 42\n"));
+
+        validator_exit.write().unwrap().take().unwrap().exit();
+        prometheus_service.join().unwrap();
+    }
+}
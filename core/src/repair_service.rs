@@ -7,7 +7,7 @@ use crate::{
     repair_weight::RepairWeight,
     repair_weighted_traversal::Contains,
     result::Result,
-    serve_repair::{RepairType, ServeRepair, DEFAULT_NONCE},
+    serve_repair::{RepairType, ServeRepair, DEFAULT_NONCE, MAX_SHRED_RANGE_REPAIR_RESPONSES},
 };
 use crossbeam_channel::{Receiver as CrossbeamReceiver, Sender as CrossbeamSender};
 use solana_ledger::{
@@ -69,6 +69,9 @@ pub struct RepairStats {
     pub shred: RepairStatsGroup,
     pub highest_shred: RepairStatsGroup,
     pub orphan: RepairStatsGroup,
+    pub shred_range: RepairStatsGroup,
+    /// Repairs requested by the `ProactiveRepairPolicy`, also counted in the groups above
+    pub proactive: u64,
     pub get_best_orphans_us: u64,
     pub get_best_shreds_us: u64,
 }
@@ -103,6 +106,86 @@ pub const MAX_REPAIR_PER_DUPLICATE: usize = 20;
 pub const MAX_DUPLICATE_WAIT_MS: usize = 10_000;
 pub const REPAIR_MS: u64 = 100;
 pub const MAX_ORPHANS: usize = 5;
+// Runs of at least this many consecutive missing shreds are repaired with a single ranged request,
+// split back into a request per shred for the peers not serving ranges
+pub const MIN_SHRED_RANGE_REPAIR_LENGTH: usize = 4;
+
+toml_config::package_config! {
+    PROACTIVE_REPAIR_GAP_MS: u64,
+}
+
+/// Repairs the slots whose missing data shreds have not been filled for longer than the gap
+/// timeout, ahead of the weighted repairs and without waiting out the turbine delay of the
+/// missing shreds. The runs of missing shreds are requested as `ShredRange` repairs. A zero
+/// timeout disables it.
+pub struct ProactiveRepairPolicy {
+    gap_timeout_ms: u64,
+    // Slot -> (consumed, timestamp the gap at consumed was first seen or last repaired)
+    gaps: HashMap<Slot, (u64, u64)>,
+}
+
+impl Default for ProactiveRepairPolicy {
+    fn default() -> Self {
+        Self::new(CFG.PROACTIVE_REPAIR_GAP_MS)
+    }
+}
+
+impl ProactiveRepairPolicy {
+    pub fn new(gap_timeout_ms: u64) -> Self {
+        Self {
+            gap_timeout_ms,
+            gaps: HashMap::new(),
+        }
+    }
+
+    /// Returns the repairs for the slots above the root whose gap has timed out by `now`, and
+    /// restarts the timeout of those slots
+    pub fn generate_repairs(
+        &mut self,
+        blockstore: &Blockstore,
+        root: Slot,
+        max_repairs: usize,
+        now: u64,
+    ) -> Vec<RepairType> {
+        if self.gap_timeout_ms == 0 {
+            return vec![];
+        }
+        let slot_metas = match blockstore.slot_meta_iterator(root + 1) {
+            Ok(slot_metas) => slot_metas,
+            Err(_) => return vec![],
+        };
+        let mut repairs = vec![];
+        let mut gaps = HashMap::new();
+        for (slot, slot_meta) in slot_metas {
+            if slot_meta.is_full() || slot_meta.consumed >= slot_meta.received {
+                continue;
+            }
+            let since = match self.gaps.get(&slot) {
+                Some((consumed, since)) if *consumed == slot_meta.consumed => *since,
+                _ => now,
+            };
+            if repairs.len() < max_repairs && now.saturating_sub(since) >= self.gap_timeout_ms {
+                // With no first shred timestamp, all of the holes are past the turbine delay
+                let missing_indexes = blockstore.find_missing_data_indexes(
+                    slot,
+                    0,
+                    slot_meta.consumed,
+                    slot_meta.received,
+                    max_repairs - repairs.len(),
+                );
+                repairs.extend(RepairService::coalesce_shred_repairs(
+                    slot,
+                    &missing_indexes,
+                ));
+                gaps.insert(slot, (slot_meta.consumed, now));
+            } else {
+                gaps.insert(slot, (slot_meta.consumed, since));
+            }
+        }
+        self.gaps = gaps;
+        repairs
+    }
+}
 
 pub struct RepairInfo {
     pub bank_forks: Arc<RwLock<BankForks>>,
@@ -177,6 +260,7 @@ impl RepairService {
         let id = cluster_info.id();
         let mut repair_stats = RepairStats::default();
         let mut repair_timing = RepairTiming::default();
+        let mut proactive_repair_policy = ProactiveRepairPolicy::default();
         let mut last_stats = Instant::now();
         let duplicate_slot_repair_statuses: HashMap<Slot, DuplicateSlotRepairStatus> =
             HashMap::new();
@@ -247,31 +331,48 @@ impl RepairService {
                     &repair_info.repair_validators,
                 );*/
 
-                repair_weight.get_best_weighted_repairs(
+                // Leave at least half of the repairs to the weighting heuristic
+                let mut repairs = proactive_repair_policy.generate_repairs(
+                    blockstore,
+                    new_root,
+                    MAX_REPAIR_LENGTH / 2,
+                    timestamp(),
+                );
+                repair_stats.proactive += repairs.len() as u64;
+                let proactive_repairs: HashSet<_> = repairs.iter().copied().collect();
+                let weighted_repairs = repair_weight.get_best_weighted_repairs(
                     blockstore,
                     root_bank.epoch_stakes_map(),
                     root_bank.epoch_schedule(),
                     MAX_ORPHANS,
-                    MAX_REPAIR_LENGTH,
+                    MAX_REPAIR_LENGTH - repairs.len(),
                     &duplicate_slot_repair_statuses,
                     Some(&mut repair_timing),
-                )
+                );
+                repairs.extend(
+                    weighted_repairs
+                        .into_iter()
+                        .filter(|repair| !proactive_repairs.contains(repair)),
+                );
+                repairs
             };
 
             let mut cache = HashMap::new();
             let mut send_repairs_elapsed = Measure::start("send_repairs_elapsed");
             repairs.into_iter().for_each(|repair_request| {
-                if let Ok((to, req)) = serve_repair.repair_request(
+                if let Ok((to, reqs)) = serve_repair.repair_request(
                     &cluster_slots,
                     repair_request,
                     &mut cache,
                     &mut repair_stats,
                     &repair_info.repair_validators,
                 ) {
-                    repair_socket.send_to(&req, to).unwrap_or_else(|e| {
-                        info!("{} repair req send_to({}) error {:?}", id, to, e);
-                        0
-                    });
+                    for req in reqs {
+                        repair_socket.send_to(&req, to).unwrap_or_else(|e| {
+                            info!("{} repair req send_to({}) error {:?}", id, to, e);
+                            0
+                        });
+                    }
                 }
             });
             send_repairs_elapsed.stop();
@@ -285,7 +386,8 @@ impl RepairService {
             if last_stats.elapsed().as_secs() > 2 {
                 let repair_total = repair_stats.shred.count
                     + repair_stats.highest_shred.count
-                    + repair_stats.orphan.count;
+                    + repair_stats.orphan.count
+                    + repair_stats.shred_range.count;
                 info!("repair_stats: {:?}", repair_stats);
                 if repair_total > 0 {
                     datapoint_info!(
@@ -294,6 +396,8 @@ impl RepairService {
                         ("shred-count", repair_stats.shred.count, i64),
                         ("highest-shred-count", repair_stats.highest_shred.count, i64),
                         ("orphan-count", repair_stats.orphan.count, i64),
+                        ("shred-range-count", repair_stats.shred_range.count, i64),
+                        ("proactive-count", repair_stats.proactive, i64),
                         ("repair-highest-slot", repair_stats.highest_shred.max, i64),
                         ("repair-orphan", repair_stats.orphan.max, i64),
                     );
@@ -378,12 +482,42 @@ impl RepairService {
                 slot_meta.received,
                 max_repairs,
             );
-            reqs.into_iter()
-                .map(|i| RepairType::Shred(slot, i))
-                .collect()
+            Self::coalesce_shred_repairs(slot, &reqs)
         }
     }
 
+    /// Turns runs of at least `MIN_SHRED_RANGE_REPAIR_LENGTH` consecutive missing indexes into
+    /// `ShredRange` repairs, each spanning at most `MAX_SHRED_RANGE_REPAIR_RESPONSES` shreds.
+    /// `missing_indexes` must be sorted.
+    fn coalesce_shred_repairs(slot: Slot, missing_indexes: &[u64]) -> Vec<RepairType> {
+        let mut repairs = vec![];
+        let mut start = 0;
+        while start < missing_indexes.len() {
+            let mut end = start + 1;
+            while end < missing_indexes.len()
+                && end - start < MAX_SHRED_RANGE_REPAIR_RESPONSES
+                && missing_indexes[end] == missing_indexes[end - 1] + 1
+            {
+                end += 1;
+            }
+            if end - start >= MIN_SHRED_RANGE_REPAIR_LENGTH {
+                repairs.push(RepairType::ShredRange(
+                    slot,
+                    missing_indexes[start],
+                    missing_indexes[end - 1] + 1,
+                ));
+            } else {
+                repairs.extend(
+                    missing_indexes[start..end]
+                        .iter()
+                        .map(|index| RepairType::Shred(slot, *index)),
+                );
+            }
+            start = end;
+        }
+        repairs
+    }
+
     /// Repairs any fork starting at the input slot
     pub fn generate_repairs_for_fork(
         blockstore: &Blockstore,
@@ -709,6 +843,53 @@ mod test {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    pub fn test_proactive_repair_policy() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let num_entries = max_ticks_per_n_shreds(5, None);
+            let (mut shreds, _) = make_slot_entries(1, 0, num_entries);
+            assert!(shreds.len() >= 5);
+            // Leave a gap at indexes 1 and 2
+            let missing: Vec<_> = shreds.drain(1..3).collect();
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+
+            let mut policy = ProactiveRepairPolicy::new(1_000);
+            let now = timestamp();
+            assert!(policy.generate_repairs(&blockstore, 0, 10, now).is_empty());
+            assert!(policy
+                .generate_repairs(&blockstore, 0, 10, now + 999)
+                .is_empty());
+            assert_eq!(
+                policy.generate_repairs(&blockstore, 0, 10, now + 1_000),
+                vec![RepairType::Shred(1, 1), RepairType::Shred(1, 2)]
+            );
+            // The timeout restarts once the gap is repaired
+            assert!(policy
+                .generate_repairs(&blockstore, 0, 10, now + 1_500)
+                .is_empty());
+            assert_eq!(
+                policy.generate_repairs(&blockstore, 0, 1, now + 2_000),
+                vec![RepairType::Shred(1, 1)]
+            );
+
+            // Slots at or below the root are left alone
+            assert!(policy
+                .generate_repairs(&blockstore, 1, 10, now + 5_000)
+                .is_empty());
+
+            blockstore.insert_shreds(missing, None, false).unwrap();
+            assert!(policy
+                .generate_repairs(&blockstore, 0, 10, now + 10_000)
+                .is_empty());
+            assert!(ProactiveRepairPolicy::new(0)
+                .generate_repairs(&blockstore, 0, 10, now)
+                .is_empty());
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     pub fn test_generate_repairs() {
         let blockstore_path = get_tmp_ledger_path!();
@@ -864,6 +1045,29 @@ mod test {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    pub fn test_coalesce_shred_repairs() {
+        let slot = 3;
+        assert!(RepairService::coalesce_shred_repairs(slot, &[]).is_empty());
+        let missing_indexes: Vec<u64> = vec![0, 1, 2, 4, 5, 6, 7, 9, 20]
+            .into_iter()
+            .chain(100..100 + MAX_SHRED_RANGE_REPAIR_RESPONSES as u64 + 1)
+            .collect();
+        assert_eq!(
+            RepairService::coalesce_shred_repairs(slot, &missing_indexes),
+            vec![
+                RepairType::Shred(slot, 0),
+                RepairType::Shred(slot, 1),
+                RepairType::Shred(slot, 2),
+                RepairType::ShredRange(slot, 4, 8),
+                RepairType::Shred(slot, 9),
+                RepairType::Shred(slot, 20),
+                RepairType::ShredRange(slot, 100, 100 + MAX_SHRED_RANGE_REPAIR_RESPONSES as u64),
+                RepairType::Shred(slot, 100 + MAX_SHRED_RANGE_REPAIR_RESPONSES as u64),
+            ]
+        );
+    }
+
     #[test]
     pub fn test_repair_range_highest() {
         let blockstore_path = get_tmp_ledger_path!();
@@ -8,7 +8,9 @@ use crate::{
     cluster_info_vote_listener::VoteTracker,
     cluster_slots::ClusterSlots,
     commitment_service::{AggregateCommitmentService, CommitmentAggregationData},
-    consensus::{ComputedBankState, Stake, SwitchForkDecision, Tower, VotedStakes},
+    consensus::{
+        ComputedBankState, Stake, SwitchForkDecision, Tower, VotedStakes, CFG as CONSENSUS_CFG,
+    },
     fork_choice::{ForkChoice, SelectVoteAndResetForkResult},
     heaviest_subtree_fork_choice::HeaviestSubtreeForkChoice,
     optimistically_confirmed_bank_tracker::{BankNotification, BankNotificationSender},
@@ -19,7 +21,9 @@ use crate::{
     result::Result,
     rewards_recorder_service::RewardsRecorderSender,
     rpc_subscriptions::RpcSubscriptions,
+    tower_storage::TowerStorage,
 };
+use solana_client::rpc_response::SlotUpdate;
 use solana_ledger::{
     block_error::BlockError,
     blockstore::Blockstore,
@@ -28,7 +32,10 @@ use solana_ledger::{
     leader_schedule_cache::LeaderScheduleCache,
 };
 use solana_measure::{measure::Measure, thread_mem_usage};
-use solana_metrics::inc_new_counter_info;
+use solana_metrics::{
+    inc_new_counter_info,
+    registry::{self, Gauge, Histogram},
+};
 use solana_runtime::{
     accounts_background_service::SnapshotRequestSender, bank::Bank, bank_forks::BankForks,
     commitment::BlockCommitmentCache, vote_sender_types::ReplayVoteSender,
@@ -112,10 +119,60 @@ pub struct ReplayStageConfig {
     pub rewards_recorder_sender: Option<RewardsRecorderSender>,
     pub cache_block_time_sender: Option<CacheBlockTimeSender>,
     pub bank_notification_sender: Option<BankNotificationSender>,
+    pub tower_storage: Arc<dyn TowerStorage>,
+}
+
+/// Replay loop timings and fork state, exported through the metrics registry. Unlike the
+/// `replay-loop-timing-stats` datapoint, the histograms are never reset
+struct ReplayMetrics {
+    compute_bank_stats_us: Arc<Histogram>,
+    select_vote_and_reset_forks_us: Arc<Histogram>,
+    start_leader_us: Arc<Histogram>,
+    reset_bank_us: Arc<Histogram>,
+    voting_us: Arc<Histogram>,
+    select_forks_us: Arc<Histogram>,
+    compute_slot_stats_us: Arc<Histogram>,
+    generate_new_bank_forks_us: Arc<Histogram>,
+    replay_active_banks_us: Arc<Histogram>,
+    reset_duplicate_slots_us: Arc<Histogram>,
+    bank_forks_banks: Arc<Gauge>,
+    bank_forks_depth: Arc<Gauge>,
+    root_slot: Arc<Gauge>,
+}
+
+impl Default for ReplayMetrics {
+    fn default() -> Self {
+        let namespace = registry::namespace("replay_stage");
+        Self {
+            compute_bank_stats_us: namespace.histogram("compute_bank_stats_us"),
+            select_vote_and_reset_forks_us: namespace.histogram("select_vote_and_reset_forks_us"),
+            start_leader_us: namespace.histogram("start_leader_us"),
+            reset_bank_us: namespace.histogram("reset_bank_us"),
+            voting_us: namespace.histogram("voting_us"),
+            select_forks_us: namespace.histogram("select_forks_us"),
+            compute_slot_stats_us: namespace.histogram("compute_slot_stats_us"),
+            generate_new_bank_forks_us: namespace.histogram("generate_new_bank_forks_us"),
+            replay_active_banks_us: namespace.histogram("replay_active_banks_us"),
+            reset_duplicate_slots_us: namespace.histogram("reset_duplicate_slots_us"),
+            bank_forks_banks: namespace.gauge("bank_forks_banks"),
+            bank_forks_depth: namespace.gauge("bank_forks_depth"),
+            root_slot: namespace.gauge("root_slot"),
+        }
+    }
+}
+
+impl ReplayMetrics {
+    fn record_bank_forks(&self, num_banks: usize, root: Slot, highest_slot: Slot) {
+        self.bank_forks_banks.set(num_banks as i64);
+        self.bank_forks_depth
+            .set(highest_slot.saturating_sub(root) as i64);
+        self.root_slot.set(root as i64);
+    }
 }
 
 #[derive(Default)]
 pub struct ReplayTiming {
+    metrics: ReplayMetrics,
     last_print: u64,
     compute_bank_stats_elapsed: u64,
     select_vote_and_reset_forks_elapsed: u64,
@@ -153,6 +210,31 @@ impl ReplayTiming {
         self.generate_new_bank_forks_elapsed += generate_new_bank_forks_elapsed;
         self.replay_active_banks_elapsed += replay_active_banks_elapsed;
         self.reset_duplicate_slots_elapsed += reset_duplicate_slots_elapsed;
+
+        let metrics = &self.metrics;
+        metrics
+            .compute_bank_stats_us
+            .record(compute_bank_stats_elapsed);
+        metrics
+            .select_vote_and_reset_forks_us
+            .record(select_vote_and_reset_forks_elapsed);
+        metrics.start_leader_us.record(start_leader_elapsed);
+        metrics.reset_bank_us.record(reset_bank_elapsed);
+        metrics.voting_us.record(voting_elapsed);
+        metrics.select_forks_us.record(select_forks_elapsed);
+        metrics
+            .compute_slot_stats_us
+            .record(compute_slot_stats_elapsed);
+        metrics
+            .generate_new_bank_forks_us
+            .record(generate_new_bank_forks_elapsed);
+        metrics
+            .replay_active_banks_us
+            .record(replay_active_banks_elapsed);
+        metrics
+            .reset_duplicate_slots_us
+            .record(reset_duplicate_slots_elapsed);
+
         let now = timestamp();
         let elapsed_ms = now - self.last_print;
         if elapsed_ms > 1000 {
@@ -244,6 +326,7 @@ impl ReplayStage {
             rewards_recorder_sender,
             cache_block_time_sender,
             bank_notification_sender,
+            tower_storage,
         } = config;
 
         trace!("replay stage");
@@ -315,6 +398,7 @@ impl ReplayStage {
                         &replay_vote_sender,
                         &bank_notification_sender,
                         &rewards_recorder_sender,
+                        &subscriptions,
                     );
                     replay_active_banks_time.stop();
                     Self::report_memory(&allocated, "replay_active_banks", start);
@@ -323,6 +407,11 @@ impl ReplayStage {
                     let mut ancestors = bank_forks.read().unwrap().ancestors();
                     let mut descendants = bank_forks.read().unwrap().descendants();
                     let forks_root = bank_forks.read().unwrap().root();
+                    replay_timing.metrics.record_bank_forks(
+                        ancestors.len(),
+                        forks_root,
+                        bank_forks.read().unwrap().highest_slot(),
+                    );
                     let start = allocated.get();
 
                     // Reset any duplicate slots that have been confirmed
@@ -337,6 +426,16 @@ impl ReplayStage {
                     );
                     reset_duplicate_slots_time.stop();
 
+                    Self::prune_forks(
+                        &tower,
+                        &poh_recorder,
+                        &mut ancestors,
+                        &mut descendants,
+                        &mut progress,
+                        &mut heaviest_subtree_fork_choice,
+                        &bank_forks,
+                    );
+
                     let mut collect_frozen_banks_time = Measure::start("frozen_banks");
                     let mut frozen_banks: Vec<_> = bank_forks
                         .read()
@@ -468,6 +567,7 @@ impl ReplayStage {
                             &mut heaviest_subtree_fork_choice,
                             &cache_block_time_sender,
                             &bank_notification_sender,
+                            tower_storage.as_ref(),
                         )?;
                     };
                     voting_time.stop();
@@ -754,6 +854,58 @@ impl ReplayStage {
         }
     }
 
+    // Prune the forks exceeding the limits of the `BankForks` pruning config. The forks of the
+    // last vote, of the heaviest slot and of the bank being produced are kept, and of the others
+    // only the ones the tower is locked out of, or with too little stake voting on them to ever
+    // pass the switch threshold, are pruned. The pruned slots are replayed again if they show up
+    // in the fork choice later.
+    fn prune_forks(
+        tower: &Tower,
+        poh_recorder: &Mutex<PohRecorder>,
+        ancestors: &mut HashMap<Slot, HashSet<Slot>>,
+        descendants: &mut HashMap<Slot, HashSet<Slot>>,
+        progress: &mut ProgressMap,
+        heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
+        bank_forks: &RwLock<BankForks>,
+    ) {
+        let mut protected_slots = vec![heaviest_subtree_fork_choice.best_overall_slot()];
+        protected_slots.extend(tower.last_voted_slot());
+        protected_slots.extend(poh_recorder.lock().unwrap().bank().map(|bank| bank.slot()));
+        let total_stake = bank_forks.read().unwrap().root_bank().total_epoch_stake();
+        let switch_threshold_stake =
+            (total_stake as f64 * CONSENSUS_CFG.SWITCH_FORK_THRESHOLD) as u64;
+        let is_locked_out =
+            |slot: &Slot| ancestors.contains_key(slot) && tower.is_locked_out(*slot, ancestors);
+        let can_prune = |slot: Slot| {
+            let below_switch_threshold = heaviest_subtree_fork_choice
+                .stake_voted_subtree(slot)
+                .map_or(true, |stake| stake <= switch_threshold_stake);
+            below_switch_threshold
+                || (is_locked_out(&slot)
+                    && descendants.get(&slot).map_or(true, |slot_descendants| {
+                        slot_descendants.iter().all(is_locked_out)
+                    }))
+        };
+        let pruned_slots = bank_forks
+            .write()
+            .unwrap()
+            .prune_forks(&protected_slots, can_prune);
+        if pruned_slots.is_empty() {
+            return;
+        }
+
+        for slot in &pruned_slots {
+            let _ = progress.remove(slot);
+            ancestors.remove(slot);
+            descendants.remove(slot);
+            heaviest_subtree_fork_choice.remove_subtree(*slot);
+        }
+        let pruned_slots: HashSet<Slot> = pruned_slots.into_iter().collect();
+        for slot_descendants in descendants.values_mut() {
+            slot_descendants.retain(|slot| !pruned_slots.contains(slot));
+        }
+    }
+
     // Purge given slot and all its descendants from the `ancestors` and
     // `descendants` structures so that they're consistent with `BankForks`
     // and the `progress` map.
@@ -981,6 +1133,7 @@ impl ReplayStage {
             transaction_status_sender,
             Some(replay_vote_sender),
             None,
+            None,
             verify_recyclers,
         );
         let tx_count_after = bank_progress.replay_progress.num_txs;
@@ -1036,6 +1189,7 @@ impl ReplayStage {
         heaviest_subtree_fork_choice: &mut HeaviestSubtreeForkChoice,
         cache_block_time_sender: &Option<CacheBlockTimeSender>,
         bank_notification_sender: &Option<BankNotificationSender>,
+        tower_storage: &dyn TowerStorage,
     ) -> Result<()> {
         if bank.is_empty() {
             inc_new_counter_info!("replay_stage-voted_empty_bank", 1);
@@ -1045,7 +1199,7 @@ impl ReplayStage {
         let new_root = tower.record_bank_vote(vote);
         let last_vote = tower.last_vote_and_timestamp();
 
-        if let Err(err) = tower.save(&cluster_info.keypair) {
+        if let Err(err) = tower.save(tower_storage, &cluster_info.keypair) {
             error!("Unable to save tower: {:?}", err);
             std::process::exit(1);
         }
@@ -1113,6 +1267,7 @@ impl ReplayStage {
         Self::push_vote(
             cluster_info,
             bank,
+            leader_schedule_cache,
             vote_account_pubkey,
             authorized_voter_keypairs,
             last_vote,
@@ -1122,9 +1277,11 @@ impl ReplayStage {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn push_vote(
         cluster_info: &ClusterInfo,
         bank: &Arc<Bank>,
+        leader_schedule_cache: &LeaderScheduleCache,
         vote_account_pubkey: &Pubkey,
         authorized_voter_keypairs: &[Arc<Keypair>],
         vote: Vote,
@@ -1134,6 +1291,10 @@ impl ReplayStage {
         if authorized_voter_keypairs.is_empty() {
             return;
         }
+        if cluster_info.is_duplicate_instance() {
+            warn!("Another instance of this validator is running.  Unable to vote");
+            return;
+        }
 
         let vote_state =
             if let Some((_, vote_account)) = bank.vote_accounts().get(vote_account_pubkey) {
@@ -1201,7 +1362,9 @@ impl ReplayStage {
         let blockhash = bank.last_blockhash();
         vote_tx.partial_sign(&[node_keypair.as_ref()], blockhash);
         vote_tx.partial_sign(&[authorized_voter_keypair.as_ref()], blockhash);
-        let _ = cluster_info.send_vote(&vote_tx);
+        // The vote lands in a block of the next leader
+        let next_leader = leader_schedule_cache.slot_leader_at(bank.slot() + 1, Some(bank));
+        let _ = cluster_info.send_vote(&vote_tx, next_leader.as_ref());
         cluster_info.push_vote(tower_index, vote_tx);
     }
 
@@ -1265,6 +1428,7 @@ impl ReplayStage {
         replay_vote_sender: &ReplayVoteSender,
         bank_notification_sender: &Option<BankNotificationSender>,
         rewards_recorder_sender: &Option<RewardsRecorderSender>,
+        subscriptions: &Arc<RpcSubscriptions>,
     ) -> bool {
         let mut did_complete_bank = false;
         let mut tx_count = 0;
@@ -1333,6 +1497,10 @@ impl ReplayStage {
                 did_complete_bank = true;
                 info!("bank frozen: {}", bank.slot());
                 bank.freeze();
+                subscriptions.notify_slot_update(SlotUpdate::Frozen {
+                    slot: bank.slot(),
+                    timestamp: timestamp(),
+                });
                 heaviest_subtree_fork_choice
                     .add_new_leaf_slot(bank.slot(), Some(bank.parent_slot()));
                 if let Some(sender) = bank_notification_sender {
@@ -1894,6 +2062,11 @@ impl ReplayStage {
         subscriptions: &Arc<RpcSubscriptions>,
     ) -> Bank {
         subscriptions.notify_slot(slot, parent.slot(), root_slot);
+        subscriptions.notify_slot_update(SlotUpdate::ReplayStarted {
+            slot,
+            parent: parent.slot(),
+            timestamp: timestamp(),
+        });
         Bank::new_from_parent(parent, leader, slot)
     }
 
@@ -1,7 +1,7 @@
 //! The `retransmit_stage` retransmits shreds between validators
 
 use crate::{
-    cluster_info::{compute_retransmit_peers, ClusterInfo, CFG as CLUSTER_CFG},
+    cluster_info::{ClusterInfo, CFG as CLUSTER_CFG},
     cluster_info_vote_listener::VerifiedVoteReceiver,
     cluster_slots::ClusterSlots,
     cluster_slots_service::ClusterSlotsService,
@@ -10,9 +10,12 @@ use crate::{
     repair_service::DuplicateSlotsResetSender,
     repair_service::RepairInfo,
     result::{Error, Result},
+    rpc_subscriptions::RpcSubscriptions,
+    turbine_tree::{self, RetransmitPeers, TurbineTree},
     window_service::{should_retransmit_and_persist, WindowService},
 };
 use crossbeam_channel::Receiver;
+use solana_client::rpc_response::SlotUpdate;
 use solana_ledger::{
     blockstore::{Blockstore, CompletedSlotsReceiver},
     leader_schedule_cache::LeaderScheduleCache,
@@ -24,14 +27,14 @@ use solana_perf::packet::Packets;
 use solana_runtime::bank_forks::BankForks;
 use solana_sdk::clock::{Epoch, Slot};
 use solana_sdk::epoch_schedule::EpochSchedule;
+use solana_sdk::feature_set;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::timing::timestamp;
 use solana_streamer::streamer::PacketReceiver;
 use std::{
-    cmp,
     collections::hash_set::HashSet,
-    collections::{BTreeMap, HashMap},
-    net::UdpSocket,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    net::{IpAddr, UdpSocket},
     sync::atomic::{AtomicBool, AtomicU64, Ordering},
     sync::mpsc::channel,
     sync::mpsc::RecvTimeoutError,
@@ -54,6 +57,7 @@ struct RetransmitStats {
     epoch_cache_update: AtomicU64,
     repair_total: AtomicU64,
     discard_total: AtomicU64,
+    unknown_leader_total: AtomicU64,
     retransmit_total: AtomicU64,
     last_ts: AtomicU64,
     compute_turbine_peers_total: AtomicU64,
@@ -68,6 +72,7 @@ fn update_retransmit_stats(
     total_packets: usize,
     retransmit_total: u64,
     discard_total: u64,
+    unknown_leader_total: u64,
     repair_total: u64,
     compute_turbine_peers_total: u64,
     peers_len: usize,
@@ -89,6 +94,9 @@ fn update_retransmit_stats(
     stats
         .discard_total
         .fetch_add(discard_total, Ordering::Relaxed);
+    stats
+        .unknown_leader_total
+        .fetch_add(unknown_leader_total, Ordering::Relaxed);
     stats
         .compute_turbine_peers_total
         .fetch_add(compute_turbine_peers_total, Ordering::Relaxed);
@@ -161,6 +169,11 @@ fn update_retransmit_stats(
                 stats.discard_total.swap(0, Ordering::Relaxed) as i64,
                 i64
             ),
+            (
+                "unknown_leader_total",
+                stats.unknown_leader_total.swap(0, Ordering::Relaxed) as i64,
+                i64
+            ),
         );
         let mut packets_by_slot = stats.packets_by_slot.lock().unwrap();
         info!("retransmit: packets_by_slot: {:?}", packets_by_slot);
@@ -192,10 +205,34 @@ fn update_retransmit_stats(
 struct EpochStakesCache {
     epoch: Epoch,
     stakes: Option<Arc<HashMap<Pubkey, u64>>>,
-    peers: Vec<ContactInfo>,
+    peers: HashMap<Pubkey, ContactInfo>,
+    peer_rtts: HashMap<Pubkey, Duration>,
+    turbine_tree: TurbineTree,
+    // Retransmit peers, including self, and their stakes for the trees shuffled with the seed
+    // of the shred, until the turbine_tree feature is active
+    sorted_peers: Vec<ContactInfo>,
     stakes_and_index: Vec<(u64, usize)>,
 }
 
+impl EpochStakesCache {
+    // Peers of `id` in the tree shuffled with the seed of the shred
+    fn seeded_retransmit_peers(&self, id: &Pubkey, seed: [u8; 32]) -> RetransmitPeers {
+        let (my_index, mut shuffled_stakes_and_index) = ClusterInfo::shuffle_peers_and_index(
+            id,
+            &self.sorted_peers,
+            &self.stakes_and_index,
+            seed,
+        );
+        shuffled_stakes_and_index.remove(my_index);
+        let shuffle = shuffled_stakes_and_index
+            .into_iter()
+            .map(|(_, index)| self.sorted_peers[index].id)
+            .collect();
+        turbine_tree::retransmit_peers_at_index(my_index, shuffle, CLUSTER_CFG.DATA_PLANE_FANOUT)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn retransmit(
     bank_forks: &Arc<RwLock<BankForks>>,
     leader_schedule_cache: &Arc<LeaderScheduleCache>,
@@ -206,6 +243,8 @@ fn retransmit(
     stats: &Arc<RetransmitStats>,
     epoch_stakes_cache: &Arc<RwLock<EpochStakesCache>>,
     last_peer_update: &Arc<AtomicU64>,
+    first_shreds_received: &Mutex<BTreeSet<Slot>>,
+    rpc_subscriptions: &Option<Arc<RpcSubscriptions>>,
 ) -> Result<()> {
     let timer = Duration::new(1, 0);
     let r_lock = r.lock().unwrap();
@@ -248,23 +287,45 @@ fn retransmit(
     {
         drop(r_epoch_stakes_cache);
         let mut w_epoch_stakes_cache = epoch_stakes_cache.write().unwrap();
-        let (peers, stakes_and_index) =
+        let peers: HashMap<_, _> = cluster_info
+            .retransmit_peers()
+            .into_iter()
+            .map(|peer| (peer.id, peer))
+            .collect();
+        w_epoch_stakes_cache.turbine_tree = TurbineTree::new(
+            peers
+                .keys()
+                .copied()
+                .chain(std::iter::once(cluster_info.id())),
+            w_epoch_stakes_cache.stakes.as_deref(),
+        );
+        let (sorted_peers, stakes_and_index) =
             cluster_info.sorted_retransmit_peers_and_stakes(w_epoch_stakes_cache.stakes.clone());
-        w_epoch_stakes_cache.peers = peers;
+        w_epoch_stakes_cache.sorted_peers = sorted_peers;
         w_epoch_stakes_cache.stakes_and_index = stakes_and_index;
+        w_epoch_stakes_cache.peers = peers;
+        w_epoch_stakes_cache.peer_rtts = cluster_info.peer_rtts();
         drop(w_epoch_stakes_cache);
         r_epoch_stakes_cache = epoch_stakes_cache.read().unwrap();
     }
-    let mut peers_len = 0;
+    let peers_len = r_epoch_stakes_cache.turbine_tree.len();
     epoch_cache_update.stop();
 
+    // Shreds of the slots from the activation of the turbine_tree feature on are retransmitted
+    // along the tree derived from their slot, index and leader
+    let turbine_tree_slot = r_bank
+        .feature_set
+        .activated_slot(&feature_set::turbine_tree::id());
     let my_id = cluster_info.id();
     let mut discard_total = 0;
+    let mut unknown_leader_total = 0;
     let mut repair_total = 0;
     let mut retransmit_total = 0;
     let mut compute_turbine_peers_total = 0;
     let mut packets_by_slot: HashMap<Slot, usize> = HashMap::new();
     let mut packets_by_source: HashMap<String, usize> = HashMap::new();
+    let mut bytes_received: HashMap<IpAddr, u64> = HashMap::new();
+    let mut bytes_served: HashMap<Pubkey, u64> = HashMap::new();
     for mut packets in packet_v {
         for packet in packets.packets.iter_mut() {
             // skip discarded packets and repair packets
@@ -273,37 +334,59 @@ fn retransmit(
                 discard_total += 1;
                 continue;
             }
+            *bytes_received.entry(packet.meta.addr().ip()).or_insert(0) += packet.meta.size as u64;
             if packet.meta.repair {
                 total_packets -= 1;
                 repair_total += 1;
                 continue;
             }
 
+            let leader =
+                leader_schedule_cache.slot_leader_at(packet.meta.slot, Some(r_bank.as_ref()));
             let mut compute_turbine_peers = Measure::start("turbine_start");
-            let (my_index, mut shuffled_stakes_and_index) = ClusterInfo::shuffle_peers_and_index(
-                &my_id,
-                &r_epoch_stakes_cache.peers,
-                &r_epoch_stakes_cache.stakes_and_index,
-                packet.meta.seed,
-            );
-            peers_len = cmp::max(peers_len, shuffled_stakes_and_index.len());
-            shuffled_stakes_and_index.remove(my_index);
-            // split off the indexes, we don't need the stakes anymore
-            let indexes = shuffled_stakes_and_index
-                .into_iter()
-                .map(|(_, index)| index)
-                .collect();
-
-            let (neighbors, children) =
-                compute_retransmit_peers(CLUSTER_CFG.DATA_PLANE_FANOUT, my_index, indexes);
-            let neighbors: Vec<_> = neighbors
-                .into_iter()
-                .map(|index| &r_epoch_stakes_cache.peers[index])
-                .collect();
-            let children: Vec<_> = children
-                .into_iter()
-                .map(|index| &r_epoch_stakes_cache.peers[index])
-                .collect();
+            let turbine_peers = if turbine_tree_slot.map_or(false, |slot| packet.meta.slot >= slot)
+            {
+                // The retransmit tree is only defined for shreds of a known leader
+                let turbine_peers = match (leader, turbine_tree::get_shred_index(packet)) {
+                    (Some(leader), Some(index)) => {
+                        r_epoch_stakes_cache.turbine_tree.get_retransmit_peers(
+                            &my_id,
+                            &leader,
+                            turbine_tree::shred_seed(packet.meta.slot, index, &leader),
+                            CLUSTER_CFG.DATA_PLANE_FANOUT,
+                        )
+                    }
+                    _ => None,
+                };
+                match turbine_peers {
+                    Some(turbine_peers) => turbine_peers,
+                    None => {
+                        if leader.is_none() {
+                            unknown_leader_total += 1;
+                        }
+                        total_packets -= 1;
+                        discard_total += 1;
+                        continue;
+                    }
+                }
+            } else {
+                r_epoch_stakes_cache.seeded_retransmit_peers(&my_id, packet.meta.seed)
+            };
+            // Nodes whose contact info is not known locally are skipped. The tree is the same
+            // for every node, but the shred goes to the peers with the lowest latency first
+            let contact_infos = |peers: Vec<Pubkey>| -> Vec<_> {
+                let mut peers: Vec<_> = peers
+                    .iter()
+                    .filter_map(|peer| r_epoch_stakes_cache.peers.get(peer))
+                    .collect();
+                peers.sort_by_key(|peer| {
+                    let rtt = r_epoch_stakes_cache.peer_rtts.get(&peer.id);
+                    (rtt.is_none(), rtt.copied())
+                });
+                peers
+            };
+            let neighbors = contact_infos(turbine_peers.neighbors);
+            let children = contact_infos(turbine_peers.children);
             compute_turbine_peers.stop();
             compute_turbine_peers_total += compute_turbine_peers.as_us();
 
@@ -312,8 +395,6 @@ fn retransmit(
                 .entry(packet.meta.addr().to_string())
                 .or_insert(0) += 1;
 
-            let leader =
-                leader_schedule_cache.slot_leader_at(packet.meta.slot, Some(r_bank.as_ref()));
             let mut retransmit_time = Measure::start("retransmit_to");
             if !packet.meta.forward {
                 ClusterInfo::retransmit_to(&neighbors, packet, leader, sock, true)?;
@@ -321,10 +402,30 @@ fn retransmit(
             } else {
                 ClusterInfo::retransmit_to(&children, packet, leader, sock, true)?;
             }
+            // Leaves of the tree never retransmit, so serving them does not count against
+            // their contribution
+            if !packet.meta.forward && !turbine_peers.neighbors_are_leaves {
+                record_served(&mut bytes_served, &neighbors, leader, packet.meta.size);
+            }
+            if !turbine_peers.children_are_leaves {
+                record_served(&mut bytes_served, &children, leader, packet.meta.size);
+            }
             retransmit_time.stop();
             retransmit_total += retransmit_time.as_us();
         }
     }
+    if let Some(rpc_subscriptions) = rpc_subscriptions {
+        notify_first_shreds_received(
+            packets_by_slot.keys().copied(),
+            bank_forks.read().unwrap().root(),
+            now,
+            first_shreds_received,
+            rpc_subscriptions,
+        );
+    }
+    let peer_bandwidth = cluster_info.peer_bandwidth();
+    peer_bandwidth.record_received(bytes_received);
+    peer_bandwidth.record_served(bytes_served);
     timer_start.stop();
     debug!(
         "retransmitted {} packets in {}ms retransmit_time: {}ms id: {}",
@@ -339,6 +440,7 @@ fn retransmit(
         total_packets,
         retransmit_total,
         discard_total,
+        unknown_leader_total,
         repair_total,
         compute_turbine_peers_total,
         peers_len,
@@ -351,6 +453,40 @@ fn retransmit(
     Ok(())
 }
 
+// Adds the bytes of a shred retransmitted to `peers` to the bytes served to them in this batch,
+// recorded into the peer bandwidth tracker once for the whole batch
+fn record_served(
+    bytes_served: &mut HashMap<Pubkey, u64>,
+    peers: &[&ContactInfo],
+    leader: Option<Pubkey>,
+    packet_size: usize,
+) {
+    for peer in peers.iter().filter(|peer| Some(peer.id) != leader) {
+        *bytes_served.entry(peer.id).or_insert(0) += packet_size as u64;
+    }
+}
+
+// Notifies the slots whose first shred was received, at `timestamp`. Slots at or below the root
+// are no longer tracked, as their shreds are not retransmitted anymore
+fn notify_first_shreds_received<I>(
+    slots: I,
+    root: Slot,
+    timestamp: u64,
+    first_shreds_received: &Mutex<BTreeSet<Slot>>,
+    rpc_subscriptions: &RpcSubscriptions,
+) where
+    I: Iterator<Item = Slot>,
+{
+    let mut first_shreds_received = first_shreds_received.lock().unwrap();
+    for slot in slots {
+        if slot > root && first_shreds_received.insert(slot) {
+            rpc_subscriptions
+                .notify_slot_update(SlotUpdate::FirstShredReceived { slot, timestamp });
+        }
+    }
+    *first_shreds_received = first_shreds_received.split_off(&(root + 1));
+}
+
 /// Service to retransmit messages from the leader or layer 1 to relevant peer nodes.
 /// See `cluster_info` for network layer definitions.
 /// # Arguments
@@ -359,14 +495,17 @@ fn retransmit(
 /// * `leader_schedule_cache` - The leader schedule to verify shreds
 /// * `cluster_info` - This structure needs to be updated and populated by the bank and via gossip.
 /// * `r` - Receive channel for shreds to be retransmitted to all the layer 1 nodes.
+/// * `rpc_subscriptions` - Notified of the first shred received of each slot.
 pub fn retransmitter(
     sockets: Arc<Vec<UdpSocket>>,
     bank_forks: Arc<RwLock<BankForks>>,
     leader_schedule_cache: &Arc<LeaderScheduleCache>,
     cluster_info: Arc<ClusterInfo>,
     r: Arc<Mutex<PacketReceiver>>,
+    rpc_subscriptions: Option<Arc<RpcSubscriptions>>,
 ) -> Vec<JoinHandle<()>> {
     let stats = Arc::new(RetransmitStats::default());
+    let first_shreds_received = Arc::new(Mutex::new(BTreeSet::new()));
     (0..sockets.len())
         .map(|s| {
             let sockets = sockets.clone();
@@ -375,6 +514,8 @@ pub fn retransmitter(
             let r = r.clone();
             let cluster_info = cluster_info.clone();
             let stats = stats.clone();
+            let first_shreds_received = first_shreds_received.clone();
+            let rpc_subscriptions = rpc_subscriptions.clone();
             let epoch_stakes_cache = Arc::new(RwLock::new(EpochStakesCache::default()));
             let last_peer_update = Arc::new(AtomicU64::new(0));
 
@@ -393,6 +534,8 @@ pub fn retransmitter(
                             &stats,
                             &epoch_stakes_cache,
                             &last_peer_update,
+                            &first_shreds_received,
+                            &rpc_subscriptions,
                         ) {
                             match e {
                                 Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
@@ -437,6 +580,7 @@ impl RetransmitStage {
         verified_vote_receiver: VerifiedVoteReceiver,
         repair_validators: Option<HashSet<Pubkey>>,
         completed_data_sets_sender: CompletedDataSetsSender,
+        rpc_subscriptions: Option<Arc<RpcSubscriptions>>,
     ) -> Self {
         let (retransmit_sender, retransmit_receiver) = channel();
 
@@ -447,6 +591,7 @@ impl RetransmitStage {
             leader_schedule_cache,
             cluster_info.clone(),
             retransmit_receiver,
+            rpc_subscriptions,
         );
 
         let leader_schedule_cache_clone = leader_schedule_cache.clone();
@@ -518,8 +663,9 @@ mod tests {
     use solana_ledger::blockstore_processor::{process_blockstore, ProcessOptions};
     use solana_ledger::create_new_tmp_ledger;
     use solana_ledger::genesis_utils::{create_genesis_config, GenesisConfigInfo};
+    use solana_ledger::shred::Shred;
     use solana_net_utils::find_available_port_in_range;
-    use solana_perf::packet::{Meta, Packet, Packets};
+    use solana_perf::packet::{Packet, Packets};
     use std::net::{IpAddr, Ipv4Addr};
 
     #[test]
@@ -562,10 +708,17 @@ mod tests {
             &leader_schedule_cache,
             cluster_info,
             Arc::new(Mutex::new(retransmit_receiver)),
+            None,
         );
         let _thread_hdls = vec![t_retransmit];
 
-        let packets = Packets::new(vec![Packet::default()]);
+        // The retransmit tree is derived from the slot, index and leader of the shred
+        let shred_packet = || {
+            let mut packet = Packet::default();
+            Shred::new_from_data(0, 0, 0, None, true, true, 0, 0, 0).copy_to_packet(&mut packet);
+            packet
+        };
+        let packets = Packets::new(vec![shred_packet()]);
         // it should send this over the sockets.
         retransmit_sender.send(packets).unwrap();
         let mut packets = Packets::new(vec![]);
@@ -573,16 +726,11 @@ mod tests {
         assert_eq!(packets.packets.len(), 1);
         assert_eq!(packets.packets[0].meta.repair, false);
 
-        let repair = Packet {
-            meta: Meta {
-                repair: true,
-                ..Meta::default()
-            },
-            ..Packet::default()
-        };
+        let mut repair = shred_packet();
+        repair.meta.repair = true;
 
         // send 1 repair and 1 "regular" packet so that we don't block forever on the recv_from
-        let packets = Packets::new(vec![repair, Packet::default()]);
+        let packets = Packets::new(vec![repair, shred_packet()]);
         retransmit_sender.send(packets).unwrap();
         let mut packets = Packets::new(vec![]);
         solana_streamer::packet::recv_from(&mut packets, &me_retransmit, 1).unwrap();
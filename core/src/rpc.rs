@@ -1,12 +1,14 @@
 //! The `rpc` module implements the Solana RPC interface.
 
 use crate::{
+    accounts_hash_verifier::AccountsHashVerification,
     cluster_info::ClusterInfo,
     contact_info::ContactInfo,
     non_circulating_supply::calculate_non_circulating_supply,
     optimistically_confirmed_bank_tracker::OptimisticallyConfirmedBank,
     rpc_health::*,
     send_transaction_service::{SendTransactionService, TransactionInfo},
+    supermajority_progress::SupermajorityProgress,
     validator::ValidatorExit,
 };
 use bincode::{config::Options, serialize};
@@ -25,23 +27,35 @@ use solana_client::{
     rpc_custom_error::RpcCustomError,
     rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
     rpc_request::{
-        TokenAccountsFilter, DELINQUENT_VALIDATOR_SLOT_DISTANCE, MAX_GET_CONFIRMED_BLOCKS_RANGE,
+        TokenAccountsFilter, DELINQUENT_VALIDATOR_SLOT_DISTANCE,
+        MAX_GET_ACCOUNT_LOCK_CONTENTION_LIMIT, MAX_GET_CONFIRMED_BLOCKS_RANGE,
         MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT,
-        MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS_SLOT_RANGE,
+        MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS_SLOT_RANGE, MAX_GET_LEADER_SLOTS_RANGE,
+        MAX_GET_PROGRAM_ACCOUNTS_LIMIT, MAX_GET_RECENT_TRANSACTION_LOGS_LIMIT,
         MAX_GET_SIGNATURE_STATUSES_QUERY_ITEMS, MAX_MULTIPLE_ACCOUNTS, NUM_LARGEST_ACCOUNTS,
+        NUM_MOST_CONTENDED_ACCOUNTS,
     },
     rpc_response::Response as RpcResponse,
     rpc_response::*,
 };
 use solana_faucet::faucet::request_airdrop_transaction;
-use solana_ledger::{blockstore::Blockstore, blockstore_db::BlockstoreError, get_tmp_ledger_path};
-use solana_metrics::inc_new_counter_info;
+use solana_ledger::{
+    blockstore::Blockstore, blockstore_db::BlockstoreError, get_tmp_ledger_path,
+    leader_schedule_utils, transaction_trace,
+};
+use solana_metrics::{
+    inc_new_counter_info,
+    registry::{self, MetricValue},
+};
 use solana_perf::packet::PACKET_DATA_SIZE;
 use solana_runtime::{
     accounts::AccountAddressFilter,
-    bank::Bank,
+    accounts_index::{AccountIndex, IndexKey},
+    bank::{Bank, TransactionSimulationDetails},
     bank_forks::BankForks,
     commitment::{BlockCommitmentArray, BlockCommitmentCache, CommitmentSlots},
+    epoch_rewards::NUM_EPOCH_REWARDS_PARTITIONS,
+    lock_contention,
 };
 use solana_sdk::{
     account::Account,
@@ -60,7 +74,8 @@ use solana_sdk::{
 };
 use solana_stake_program::stake_state::StakeState;
 use solana_transaction_status::{
-    EncodedConfirmedBlock, EncodedConfirmedTransaction, TransactionStatus, UiTransactionEncoding,
+    EncodedConfirmedBlock, EncodedConfirmedTransaction, InnerInstructions, Reward, RewardType,
+    TransactionStatus, UiInnerInstructions, UiTransactionEncoding,
 };
 use solana_vote_program::vote_state::{VoteState, MAX_LOCKOUT_HISTORY};
 use spl_token_v2_0::{
@@ -71,6 +86,7 @@ use std::{
     cmp::{max, min},
     collections::{HashMap, HashSet},
     net::SocketAddr,
+    path::PathBuf,
     str::FromStr,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -102,12 +118,21 @@ pub fn is_confirmed_rooted(
 pub struct JsonRpcConfig {
     pub enable_validator_exit: bool,
     pub enable_set_log_filter: bool,
+    pub enable_get_metrics: bool,
     pub enable_rpc_transaction_history: bool,
     pub identity_pubkey: Pubkey,
     pub faucet_addr: Option<SocketAddr>,
     pub health_check_slot_distance: u64,
+    pub health_checks: RpcHealthChecks,
     pub enable_bigtable_ledger_storage: bool,
     pub enable_bigtable_ledger_upload: bool,
+    pub bigtable_ledger_upload_starting_slot: Slot,
+    pub bigtable_ledger_upload_ending_slot: Option<Slot>, // None = keep uploading new blocks
+    pub historical_ledger_storage_url: Option<String>,    // None = BigTable
+    pub historical_ledger_storage_ca_certificate: Option<PathBuf>,
+    pub max_batch_size: Option<usize>,
+    pub batch_compute_budget_ms: Option<u64>,
+    pub max_signatures_for_address_limit: Option<usize>, // None = 1,000
 }
 
 #[derive(Clone)]
@@ -122,8 +147,10 @@ pub struct JsonRpcRequestProcessor {
     genesis_hash: Hash,
     transaction_sender: Arc<Mutex<Sender<TransactionInfo>>>,
     runtime_handle: runtime::Handle,
-    bigtable_ledger_storage: Option<solana_storage_bigtable::LedgerStorage>,
+    bigtable_ledger_storage: Option<Arc<dyn solana_storage_bigtable::HistoricalLedgerStorage>>,
     optimistically_confirmed_bank: Arc<RwLock<OptimisticallyConfirmedBank>>,
+    accounts_hash_verification: Arc<RwLock<AccountsHashVerification>>,
+    supermajority_progress: Arc<RwLock<Option<SupermajorityProgress>>>,
 }
 impl Metadata for JsonRpcRequestProcessor {}
 
@@ -190,6 +217,17 @@ impl JsonRpcRequestProcessor {
         })
     }
 
+    /// Returns the bank of `slot`, as long as it is frozen and still held by this node
+    fn frozen_bank_at_slot(&self, slot: Slot) -> Result<Arc<Bank>> {
+        self.bank_forks
+            .read()
+            .unwrap()
+            .get(slot)
+            .filter(|bank| bank.is_frozen())
+            .cloned()
+            .ok_or_else(|| RpcCustomError::BlockNotAvailable { slot }.into())
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         config: JsonRpcConfig,
@@ -201,8 +239,10 @@ impl JsonRpcRequestProcessor {
         cluster_info: Arc<ClusterInfo>,
         genesis_hash: Hash,
         runtime: &runtime::Runtime,
-        bigtable_ledger_storage: Option<solana_storage_bigtable::LedgerStorage>,
+        bigtable_ledger_storage: Option<Arc<dyn solana_storage_bigtable::HistoricalLedgerStorage>>,
         optimistically_confirmed_bank: Arc<RwLock<OptimisticallyConfirmedBank>>,
+        accounts_hash_verification: Arc<RwLock<AccountsHashVerification>>,
+        supermajority_progress: Arc<RwLock<Option<SupermajorityProgress>>>,
     ) -> (Self, Receiver<TransactionInfo>) {
         let (sender, receiver) = channel();
         (
@@ -219,6 +259,8 @@ impl JsonRpcRequestProcessor {
                 runtime_handle: runtime.handle().clone(),
                 bigtable_ledger_storage,
                 optimistically_confirmed_bank,
+                accounts_hash_verification,
+                supermajority_progress,
             },
             receiver,
         )
@@ -237,6 +279,15 @@ impl JsonRpcRequestProcessor {
         let tpu_address = cluster_info.my_contact_info().tpu;
         let (sender, receiver) = channel();
         SendTransactionService::new(tpu_address, &bank_forks, None, receiver);
+        let health = Arc::new(RpcHealth::new(
+            cluster_info.clone(),
+            bank_forks.clone(),
+            None,
+            None,
+            0,
+            RpcHealthChecks::default(),
+            exit.clone(),
+        ));
 
         Self {
             config: JsonRpcConfig::default(),
@@ -248,7 +299,7 @@ impl JsonRpcRequestProcessor {
             ))),
             blockstore,
             validator_exit: create_validator_exit(&exit),
-            health: Arc::new(RpcHealth::new(cluster_info.clone(), None, 0, exit.clone())),
+            health,
             cluster_info,
             genesis_hash,
             transaction_sender: Arc::new(Mutex::new(sender)),
@@ -257,6 +308,8 @@ impl JsonRpcRequestProcessor {
             optimistically_confirmed_bank: Arc::new(RwLock::new(OptimisticallyConfirmedBank {
                 bank: bank.clone(),
             })),
+            accounts_hash_verification: Arc::default(),
+            supermajority_progress: Arc::default(),
         }
     }
 
@@ -303,23 +356,36 @@ impl JsonRpcRequestProcessor {
             .get_minimum_balance_for_rent_exemption(data_len)
     }
 
+    /// Loads the accounts of `program_id` passing `filters`. When `limit` is set, only the page of
+    /// at most `limit` accounts following `cursor` is returned
     pub fn get_program_accounts(
         &self,
         program_id: &Pubkey,
         config: Option<RpcAccountInfoConfig>,
         filters: Vec<RpcFilterType>,
-    ) -> Result<Vec<RpcKeyedAccount>> {
+        limit: Option<usize>,
+        cursor: Option<Pubkey>,
+    ) -> Result<RpcProgramAccounts> {
         let config = config.unwrap_or_default();
         let bank = self.bank(config.commitment);
         let encoding = config.encoding.unwrap_or(UiAccountEncoding::Binary);
         let data_slice_config = config.data_slice;
         check_slice_and_encoding(&encoding, data_slice_config.is_some())?;
-        let keyed_accounts = get_filtered_program_accounts(&bank, program_id, filters);
-        let result =
+        let (keyed_accounts, next_cursor) = match limit {
+            Some(limit) => {
+                get_filtered_program_accounts_page(&bank, program_id, filters, cursor, limit)
+            }
+            None => (
+                get_filtered_program_accounts(&bank, program_id, filters).collect(),
+                None,
+            ),
+        };
+        let accounts =
             if program_id == &spl_token_id_v2_0() && encoding == UiAccountEncoding::JsonParsed {
-                get_parsed_token_accounts(bank, keyed_accounts).collect()
+                get_parsed_token_accounts(bank, keyed_accounts.into_iter()).collect()
             } else {
                 keyed_accounts
+                    .into_iter()
                     .map(|(pubkey, account)| RpcKeyedAccount {
                         pubkey: pubkey.to_string(),
                         account: UiAccount::encode(
@@ -332,7 +398,13 @@ impl JsonRpcRequestProcessor {
                     })
                     .collect()
             };
-        Ok(result)
+        Ok(match limit {
+            Some(_) => RpcProgramAccounts::Page(RpcKeyedAccountsPage {
+                accounts,
+                next_cursor,
+            }),
+            None => RpcProgramAccounts::Accounts(accounts),
+        })
     }
 
     pub fn get_inflation_governor(
@@ -364,6 +436,53 @@ impl JsonRpcRequestProcessor {
         *bank.epoch_schedule()
     }
 
+    pub fn get_leader_slots(&self, config: Option<RpcLeaderSlotsConfig>) -> Result<RpcLeaderSlots> {
+        let config = config.unwrap_or_default();
+        let bank = self.bank(config.commitment);
+        let identity = config.identity.map(verify_pubkey).transpose()?;
+        let epoch_schedule = bank.epoch_schedule();
+        let (start_slot, end_slot) = if let Some(epoch) = config.epoch {
+            if config.start_slot.is_some() || config.end_slot.is_some() {
+                return Err(Error::invalid_params(
+                    "Invalid param: epoch cannot be combined with a slot range".to_string(),
+                ));
+            }
+            (
+                epoch_schedule.get_first_slot_in_epoch(epoch),
+                epoch_schedule.get_last_slot_in_epoch(epoch),
+            )
+        } else {
+            let start_slot = config.start_slot.unwrap_or_else(|| bank.slot());
+            let end_slot = config.end_slot.unwrap_or_else(|| {
+                epoch_schedule.get_last_slot_in_epoch(epoch_schedule.get_epoch(start_slot))
+            });
+            (start_slot, end_slot)
+        };
+        if end_slot < start_slot {
+            return Err(Error::invalid_params(format!(
+                "Invalid param: end slot {} is before start slot {}",
+                end_slot, start_slot
+            )));
+        }
+        if end_slot - start_slot >= MAX_GET_LEADER_SLOTS_RANGE {
+            return Err(Error::invalid_params(format!(
+                "Slot range too large; max {}",
+                MAX_GET_LEADER_SLOTS_RANGE
+            )));
+        }
+
+        let mut leader_slots = RpcLeaderSlots::new();
+        for (slot, leader) in
+            leader_schedule_utils::slot_leaders(start_slot, end_slot, identity.as_ref(), &bank)
+        {
+            leader_slots
+                .entry(leader.to_string())
+                .or_insert_with(Vec::new)
+                .push(slot);
+        }
+        Ok(leader_slots)
+    }
+
     pub fn get_balance(
         &self,
         pubkey: &Pubkey,
@@ -509,6 +628,23 @@ impl JsonRpcRequestProcessor {
         )
     }
 
+    fn get_quarantined_accounts(
+        &self,
+        commitment: Option<CommitmentConfig>,
+    ) -> RpcResponse<Vec<RpcQuarantinedAccount>> {
+        let bank = self.bank(commitment);
+        new_response(
+            &bank,
+            bank.get_quarantined_accounts()
+                .into_iter()
+                .map(|(address, data_len)| RpcQuarantinedAccount {
+                    address: address.to_string(),
+                    data_len,
+                })
+                .collect(),
+        )
+    }
+
     fn get_supply(&self, commitment: Option<CommitmentConfig>) -> RpcResponse<RpcSupply> {
         let bank = self.bank(commitment);
         let non_circulating_supply = calculate_non_circulating_supply(&bank);
@@ -599,6 +735,102 @@ impl JsonRpcRequestProcessor {
         }
     }
 
+    pub fn get_accounts_hash_verification(&self) -> RpcAccountsHashVerification {
+        let verification = self.accounts_hash_verification.read().unwrap();
+        RpcAccountsHashVerification {
+            last_verified_slot: verification.last_verified.map(|(slot, _)| slot),
+            last_verified_hash: verification.last_verified.map(|(_, hash)| hash.to_string()),
+            mismatch_slot: verification.mismatch,
+        }
+    }
+
+    pub fn get_supermajority_progress(&self) -> Option<RpcSupermajorityProgress> {
+        let node = |identity: &Pubkey, stake: u64, bank_hash: Option<&Hash>| RpcSupermajorityNode {
+            identity: identity.to_string(),
+            stake,
+            bank_hash: bank_hash.map(|bank_hash| bank_hash.to_string()),
+        };
+        let progress = self.supermajority_progress.read().unwrap();
+        progress.as_ref().map(|progress| RpcSupermajorityProgress {
+            slot: progress.slot,
+            bank_hash: progress.bank_hash.to_string(),
+            total_stake: progress.total_stake,
+            online_stake: progress.online_stake,
+            stake_by_version: progress.stake_by_version.clone(),
+            stake_by_bank_hash: progress
+                .stake_by_bank_hash
+                .iter()
+                .map(|(bank_hash, stake)| (bank_hash.to_string(), *stake))
+                .collect(),
+            wrong_shred_version: progress
+                .wrong_shred_version
+                .iter()
+                .map(|(identity, stake)| node(identity, *stake, None))
+                .collect(),
+            offline: progress
+                .offline
+                .iter()
+                .map(|(identity, stake)| node(identity, *stake, None))
+                .collect(),
+            mismatched_bank_hash: progress
+                .mismatched_bank_hash
+                .iter()
+                .map(|(identity, stake, bank_hash)| node(identity, *stake, Some(bank_hash)))
+                .collect(),
+        })
+    }
+
+    pub fn get_metrics(&self) -> Result<Vec<RpcMetric>> {
+        if !self.config.enable_get_metrics {
+            return Err(Error::method_not_found());
+        }
+        Ok(registry::dump()
+            .into_iter()
+            .map(|metric| RpcMetric {
+                namespace: metric.namespace.to_string(),
+                instance: metric.instance,
+                name: metric.name.to_string(),
+                value: match metric.value {
+                    MetricValue::Counter(value) => RpcMetricValue::Counter { value },
+                    MetricValue::Gauge(value) => RpcMetricValue::Gauge { value },
+                    MetricValue::Histogram(snapshot) => RpcMetricValue::Histogram {
+                        count: snapshot.count,
+                        sum: snapshot.sum,
+                        min: snapshot.min,
+                        max: snapshot.max,
+                    },
+                },
+            })
+            .collect())
+    }
+
+    pub fn get_account_lock_contention(
+        &self,
+        limit: Option<usize>,
+    ) -> Result<Vec<RpcAccountLockContention>> {
+        let profiler = lock_contention::profiler();
+        if !profiler.is_enabled() {
+            return Err(Error::method_not_found());
+        }
+        let limit = limit.unwrap_or(NUM_MOST_CONTENDED_ACCOUNTS);
+        if limit == 0 || limit > MAX_GET_ACCOUNT_LOCK_CONTENTION_LIMIT {
+            return Err(Error::invalid_params(format!(
+                "Invalid limit; max {}",
+                MAX_GET_ACCOUNT_LOCK_CONTENTION_LIMIT
+            )));
+        }
+        Ok(profiler
+            .most_contended(limit)
+            .into_iter()
+            .map(|(pubkey, stats)| RpcAccountLockContention {
+                pubkey: pubkey.to_string(),
+                write_conflicts: stats.write_conflicts,
+                read_conflicts: stats.read_conflicts,
+                shared_readonly_locks: stats.shared_readonly_locks,
+            })
+            .collect())
+    }
+
     fn check_slot_cleaned_up<T>(
         &self,
         result: &std::result::Result<T, BlockstoreError>,
@@ -742,7 +974,9 @@ impl JsonRpcRequestProcessor {
                 .highest_confirmed_root()
         {
             let result = self.blockstore.get_block_time(slot);
-            if result.is_err() {
+            // Slots rooted before the block time was cached locally may still be found in
+            // BigTable
+            if result.as_ref().map_or(true, Option::is_none) {
                 if let Some(bigtable_ledger_storage) = &self.bigtable_ledger_storage {
                     return Ok(self
                         .runtime_handle
@@ -817,6 +1051,7 @@ impl JsonRpcRequestProcessor {
                             status: status_meta.status,
                             confirmations: None,
                             err,
+                            optimistically_confirmed: true,
                         }
                     })
                     .or_else(|| {
@@ -854,12 +1089,20 @@ impl JsonRpcRequestProcessor {
                 .get_confirmation_count(slot)
                 .or(Some(0))
         };
+        drop(r_block_commitment_cache);
+
+        let optimistically_confirmed = confirmations.is_none()
+            || self
+                .bank(Some(CommitmentConfig::single_gossip()))
+                .get_signature_status_slot(&signature)
+                .is_some();
         let err = status.clone().err();
         Some(TransactionStatus {
             slot,
             status,
             confirmations,
             err,
+            optimistically_confirmed,
         })
     }
 
@@ -930,6 +1173,8 @@ impl JsonRpcRequestProcessor {
         mut before: Option<Signature>,
         until: Option<Signature>,
         mut limit: usize,
+        min_slot: Option<Slot>,
+        max_slot: Option<Slot>,
     ) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
         if self.config.enable_rpc_transaction_history {
             let highest_confirmed_root = self
@@ -937,15 +1182,15 @@ impl JsonRpcRequestProcessor {
                 .read()
                 .unwrap()
                 .highest_confirmed_root();
+            let min_slot = min_slot.unwrap_or(0);
+            let max_slot = max_slot.map_or(highest_confirmed_root, |max_slot| {
+                max_slot.min(highest_confirmed_root)
+            });
 
             let mut results = self
                 .blockstore
                 .get_confirmed_signatures_for_address2(
-                    address,
-                    highest_confirmed_root,
-                    before,
-                    until,
-                    limit,
+                    address, max_slot, min_slot, before, until, limit,
                 )
                 .map_err(|err| Error::invalid_params(format!("{}", err)))?;
 
@@ -966,7 +1211,12 @@ impl JsonRpcRequestProcessor {
                     );
                     match bigtable_results {
                         Ok(bigtable_results) => {
-                            results.extend(bigtable_results.into_iter().map(|x| x.0));
+                            results.extend(
+                                bigtable_results
+                                    .into_iter()
+                                    .map(|x| x.0)
+                                    .filter(|x| (min_slot..=max_slot).contains(&x.slot)),
+                            );
                         }
                         Err(err) => {
                             warn!("{:?}", err);
@@ -981,6 +1231,24 @@ impl JsonRpcRequestProcessor {
         }
     }
 
+    pub fn get_recent_transaction_logs(
+        &self,
+        address: Option<Pubkey>,
+        limit: usize,
+        commitment: Option<CommitmentConfig>,
+    ) -> Vec<RpcLogsResponse> {
+        self.bank(commitment)
+            .get_recent_transaction_logs(address.as_ref(), limit)
+            .into_iter()
+            .map(|(slot, info)| RpcLogsResponse {
+                signature: info.signature.to_string(),
+                slot,
+                err: info.result.err(),
+                logs: info.log_messages,
+            })
+            .collect()
+    }
+
     pub fn get_first_available_block(&self) -> Slot {
         let slot = self
             .blockstore
@@ -1065,6 +1333,124 @@ impl JsonRpcRequestProcessor {
         })
     }
 
+    pub fn get_inflation_reward(
+        &self,
+        addresses: Vec<Pubkey>,
+        config: Option<RpcStakeConfig>,
+    ) -> Result<Vec<Option<RpcInflationReward>>> {
+        let config = config.unwrap_or_default();
+        let bank = self.bank(config.commitment);
+        let epoch = config
+            .epoch
+            .unwrap_or_else(|| bank.epoch().saturating_sub(1));
+        if epoch >= bank.epoch() {
+            return Err(Error::invalid_params(format!(
+                "Invalid param: epoch {:?} has not yet been rewarded",
+                epoch
+            )));
+        }
+
+        // The rewards of an epoch are paid by the first block of the next epoch, and the stake
+        // rewards may be spread over the blocks following it
+        let epoch_schedule = bank.epoch_schedule();
+        let first_slot = epoch_schedule.get_first_slot_in_epoch(epoch + 1);
+        let last_slot = epoch_schedule.get_last_slot_in_epoch(epoch + 1);
+        if !self.config.enable_rpc_transaction_history {
+            return Err(RpcCustomError::BlockNotAvailable { slot: first_slot }.into());
+        }
+        let first_available_block = self
+            .blockstore
+            .get_first_available_block()
+            .unwrap_or_default();
+        if first_available_block > first_slot {
+            return Err(RpcCustomError::BlockCleanedUp {
+                slot: first_slot,
+                first_available_block,
+            }
+            .into());
+        }
+        let highest_confirmed_root = self
+            .block_commitment_cache
+            .read()
+            .unwrap()
+            .highest_confirmed_root();
+        let num_reward_blocks = 1 + NUM_EPOCH_REWARDS_PARTITIONS as usize;
+        let slots: Vec<Slot> = self
+            .blockstore
+            .rooted_slot_iterator(first_slot)
+            .map_err(|_| Error::internal_error())?
+            .take_while(|slot| *slot <= last_slot.min(highest_confirmed_root))
+            .take(num_reward_blocks)
+            .collect();
+        if slots.is_empty() {
+            return Err(RpcCustomError::BlockNotAvailable { slot: first_slot }.into());
+        }
+
+        let mut rewards: HashMap<String, (Slot, Reward)> = HashMap::new();
+        for slot in slots {
+            let slot_rewards = self
+                .blockstore
+                .read_rewards(slot)
+                .map_err(|_| Error::internal_error())?
+                .unwrap_or_default();
+            for reward in slot_rewards {
+                if let Some(RewardType::Voting) | Some(RewardType::Staking) = reward.reward_type {
+                    rewards
+                        .entry(reward.pubkey.clone())
+                        .or_insert((slot, reward));
+                }
+            }
+        }
+
+        // The vote accounts and their stakes as of the rewarded epoch
+        let epoch_vote_accounts = bank.epoch_vote_accounts(epoch);
+        let commission = |vote_pubkey: &Pubkey| {
+            epoch_vote_accounts
+                .and_then(|vote_accounts| vote_accounts.get(vote_pubkey))
+                .and_then(|(_, account)| VoteState::from(account))
+                .map(|vote_state| vote_state.commission)
+        };
+        let stake_history = bank
+            .get_account(&stake_history::id())
+            .and_then(|account| solana_sdk::account::from_account::<StakeHistory>(&account));
+        Ok(addresses
+            .iter()
+            .map(|address| {
+                let (effective_slot, reward) = rewards.get(&address.to_string())?;
+                let (effective_stake, commission) = match reward.reward_type {
+                    Some(RewardType::Voting) => (
+                        epoch_vote_accounts
+                            .and_then(|vote_accounts| vote_accounts.get(address))
+                            .map(|(stake, _)| *stake),
+                        commission(address),
+                    ),
+                    _ => {
+                        // The stake accounts are not part of the epoch stakes: their stake for
+                        // the epoch is derived from their current delegation, unless it was
+                        // delegated anew since
+                        let delegation = bank
+                            .get_account(address)
+                            .and_then(|account| StakeState::delegation_from(&account))
+                            .filter(|delegation| delegation.activation_epoch <= epoch);
+                        (
+                            delegation
+                                .map(|delegation| delegation.stake(epoch, stake_history.as_ref())),
+                            delegation.and_then(|delegation| commission(&delegation.voter_pubkey)),
+                        )
+                    }
+                };
+                Some(RpcInflationReward {
+                    epoch,
+                    effective_slot: *effective_slot,
+                    amount: reward.lamports.max(0) as u64,
+                    post_balance: reward.post_balance,
+                    effective_stake,
+                    commission,
+                })
+            })
+            .collect())
+    }
+
     pub fn get_token_account_balance(
         &self,
         pubkey: &Pubkey,
@@ -1341,42 +1727,130 @@ fn get_encoded_account(
     encoding: UiAccountEncoding,
     data_slice: Option<UiDataSliceConfig>,
 ) -> Result<Option<UiAccount>> {
-    let mut response = None;
-    if let Some(account) = bank.get_account(pubkey) {
-        if account.owner == spl_token_id_v2_0() && encoding == UiAccountEncoding::JsonParsed {
-            response = Some(get_parsed_token_account(bank.clone(), pubkey, account));
-        } else if (encoding == UiAccountEncoding::Binary || encoding == UiAccountEncoding::Base58)
-            && account.data.len() > 128
-        {
-            let message = "Encoded binary (base 58) data should be less than 128 bytes, please use Base64 encoding.".to_string();
-            return Err(error::Error {
-                code: error::ErrorCode::InvalidRequest,
-                message,
-                data: None,
-            });
-        } else {
-            response = Some(UiAccount::encode(
-                pubkey, account, encoding, None, data_slice,
-            ));
-        }
+    bank.get_account(pubkey)
+        .map(|account| encode_account(bank, pubkey, account, encoding, data_slice))
+        .transpose()
+}
+
+fn encode_account(
+    bank: &Arc<Bank>,
+    pubkey: &Pubkey,
+    account: Account,
+    encoding: UiAccountEncoding,
+    data_slice: Option<UiDataSliceConfig>,
+) -> Result<UiAccount> {
+    if account.owner == spl_token_id_v2_0() && encoding == UiAccountEncoding::JsonParsed {
+        Ok(get_parsed_token_account(bank.clone(), pubkey, account))
+    } else if (encoding == UiAccountEncoding::Binary || encoding == UiAccountEncoding::Base58)
+        && account.data.len() > 128
+    {
+        let message = "Encoded binary (base 58) data should be less than 128 bytes, please use Base64 encoding.".to_string();
+        Err(error::Error {
+            code: error::ErrorCode::InvalidRequest,
+            message,
+            data: None,
+        })
+    } else {
+        Ok(UiAccount::encode(
+            pubkey, account, encoding, None, data_slice,
+        ))
     }
-    Ok(response)
 }
 
-/// Use a set of filters to get an iterator of keyed program accounts from a bank
+/// Use a set of filters to get an iterator of keyed program accounts from a bank, in pubkey order.
+/// The filters are evaluated while scanning the accounts
 fn get_filtered_program_accounts(
     bank: &Arc<Bank>,
     program_id: &Pubkey,
     filters: Vec<RpcFilterType>,
 ) -> impl Iterator<Item = (Pubkey, Account)> {
-    bank.get_program_accounts(&program_id)
-        .into_iter()
-        .filter(move |(_, account)| {
-            filters.iter().all(|filter_type| match filter_type {
-                RpcFilterType::DataSize(size) => account.data.len() as u64 == *size,
-                RpcFilterType::Memcmp(compare) => compare.bytes_match(&account.data),
-            })
+    let index_key = get_spl_token_mint_index_key(bank, program_id, &filters);
+    let filter = account_filter(filters);
+    let accounts = match index_key {
+        Some(index_key) => bank.get_filtered_indexed_accounts(&index_key, filter),
+        None => bank.get_filtered_program_accounts(program_id, filter),
+    };
+    accounts.into_iter()
+}
+
+/// Loads the page of at most `limit` keyed program accounts following `cursor` from a bank, along
+/// with the cursor of the next page if there are more accounts. The scan resumes from `cursor`
+/// and stops once the page is full, rather than going through all the accounts for every page.
+fn get_filtered_program_accounts_page(
+    bank: &Arc<Bank>,
+    program_id: &Pubkey,
+    filters: Vec<RpcFilterType>,
+    cursor: Option<Pubkey>,
+    limit: usize,
+) -> (Vec<(Pubkey, Account)>, Option<String>) {
+    let index_key = get_spl_token_mint_index_key(bank, program_id, &filters);
+    let filter = account_filter(filters);
+    // One more account tells whether there is a next page
+    let page_limit = limit.saturating_add(1);
+    let keyed_accounts = match index_key {
+        Some(index_key) => {
+            bank.get_filtered_indexed_accounts_page(&index_key, filter, cursor, page_limit)
+        }
+        None => bank.get_filtered_program_accounts_page(program_id, filter, cursor, page_limit),
+    };
+    paginate_keyed_accounts(keyed_accounts, limit)
+}
+
+fn account_filter(filters: Vec<RpcFilterType>) -> impl Fn(&Account) -> bool {
+    move |account: &Account| {
+        filters.iter().all(|filter_type| match filter_type {
+            RpcFilterType::DataSize(size) => account.data.len() as u64 == *size,
+            RpcFilterType::Memcmp(compare) => compare.bytes_match(&account.data),
         })
+    }
+}
+
+/// Truncates accounts in pubkey order to their first `limit` ones, returning them along with the
+/// cursor of the next page if there were more accounts. The cursor is the last pubkey of the page
+fn paginate_keyed_accounts(
+    mut page: Vec<(Pubkey, Account)>,
+    limit: usize,
+) -> (Vec<(Pubkey, Account)>, Option<String>) {
+    let next_cursor = if page.len() > limit {
+        page.truncate(limit);
+        page.last().map(|(pubkey, _)| pubkey.to_string())
+    } else {
+        None
+    };
+    (page, next_cursor)
+}
+
+/// The key of the token accounts of a mint in the secondary index, if `filters` select the token
+/// accounts of a single mint and the index is enabled
+fn get_spl_token_mint_index_key(
+    bank: &Bank,
+    program_id: &Pubkey,
+    filters: &[RpcFilterType],
+) -> Option<IndexKey> {
+    if *program_id != spl_token_id_v2_0()
+        || !bank.is_account_index_enabled(AccountIndex::SplTokenMint)
+    {
+        return None;
+    }
+    let token_account_len = TokenAccount::get_packed_len() as u64;
+    if !filters
+        .iter()
+        .any(|filter| matches!(filter, RpcFilterType::DataSize(size) if *size == token_account_len))
+    {
+        return None;
+    }
+    filters.iter().find_map(|filter| match filter {
+        RpcFilterType::Memcmp(Memcmp {
+            offset: 0,
+            bytes: MemcmpEncodedBytes::Binary(bytes),
+            ..
+        }) => bs58::decode(bytes)
+            .into_vec()
+            .ok()
+            .filter(|bytes| bytes.len() == std::mem::size_of::<Pubkey>())
+            .map(|bytes| IndexKey::SplTokenMint(Pubkey::new(&bytes))),
+        _ => None,
+    })
 }
 
 pub(crate) fn get_parsed_token_account(
@@ -1538,7 +2012,7 @@ pub trait RpcSol {
         meta: Self::Metadata,
         program_id_str: String,
         config: Option<RpcProgramAccountsConfig>,
-    ) -> Result<Vec<RpcKeyedAccount>>;
+    ) -> Result<RpcProgramAccounts>;
 
     #[rpc(meta, name = "getMinimumBalanceForRentExemption")]
     fn get_minimum_balance_for_rent_exemption(
@@ -1572,6 +2046,18 @@ pub trait RpcSol {
     #[rpc(meta, name = "getClusterNodes")]
     fn get_cluster_nodes(&self, meta: Self::Metadata) -> Result<Vec<RpcContactInfo>>;
 
+    #[rpc(meta, name = "getPeerBandwidth")]
+    fn get_peer_bandwidth(&self, meta: Self::Metadata) -> Result<Vec<RpcPeerBandwidth>>;
+
+    #[rpc(meta, name = "getGossipPeerStats")]
+    fn get_gossip_peer_stats(&self, meta: Self::Metadata) -> Result<Vec<RpcGossipPeerStats>>;
+
+    #[rpc(meta, name = "getAncestorHashesReports")]
+    fn get_ancestor_hashes_reports(
+        &self,
+        meta: Self::Metadata,
+    ) -> Result<Vec<RpcAncestorHashesReport>>;
+
     #[rpc(meta, name = "getRecentPerformanceSamples")]
     fn get_recent_performance_samples(
         &self,
@@ -1579,6 +2065,13 @@ pub trait RpcSol {
         limit: Option<usize>,
     ) -> Result<Vec<RpcPerfSample>>;
 
+    #[rpc(meta, name = "getRecentTransactionLogs")]
+    fn get_recent_transaction_logs(
+        &self,
+        meta: Self::Metadata,
+        config: Option<RpcTransactionLogsConfig>,
+    ) -> Result<Vec<RpcLogsResponse>>;
+
     #[rpc(meta, name = "getEpochInfo")]
     fn get_epoch_info(
         &self,
@@ -1586,6 +2079,13 @@ pub trait RpcSol {
         commitment: Option<CommitmentConfig>,
     ) -> Result<EpochInfo>;
 
+    #[rpc(meta, name = "getEpochRewardsDistribution")]
+    fn get_epoch_rewards_distribution(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Option<RpcEpochRewardsDistribution>>;
+
     #[rpc(meta, name = "getBlockCommitment")]
     fn get_block_commitment(
         &self,
@@ -1604,6 +2104,13 @@ pub trait RpcSol {
         commitment: Option<CommitmentConfig>,
     ) -> Result<Option<RpcLeaderSchedule>>;
 
+    #[rpc(meta, name = "getLeaderSlots")]
+    fn get_leader_slots(
+        &self,
+        meta: Self::Metadata,
+        config: Option<RpcLeaderSlotsConfig>,
+    ) -> Result<RpcLeaderSlots>;
+
     #[rpc(meta, name = "getRecentBlockhash")]
     fn get_recent_blockhash(
         &self,
@@ -1665,6 +2172,13 @@ pub trait RpcSol {
         config: Option<RpcLargestAccountsConfig>,
     ) -> Result<RpcResponse<Vec<RpcAccountBalance>>>;
 
+    #[rpc(meta, name = "getQuarantinedAccounts")]
+    fn get_quarantined_accounts(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<Vec<RpcQuarantinedAccount>>>;
+
     #[rpc(meta, name = "getSupply")]
     fn get_supply(
         &self,
@@ -1726,6 +2240,28 @@ pub trait RpcSol {
     #[rpc(meta, name = "setLogFilter")]
     fn set_log_filter(&self, _meta: Self::Metadata, filter: String) -> Result<()>;
 
+    #[rpc(meta, name = "getMetrics")]
+    fn get_metrics(&self, meta: Self::Metadata) -> Result<Vec<RpcMetric>>;
+
+    #[rpc(meta, name = "getAccountLockContention")]
+    fn get_account_lock_contention(
+        &self,
+        meta: Self::Metadata,
+        limit: Option<usize>,
+    ) -> Result<Vec<RpcAccountLockContention>>;
+
+    #[rpc(meta, name = "getAccountsHashVerification")]
+    fn get_accounts_hash_verification(
+        &self,
+        meta: Self::Metadata,
+    ) -> Result<RpcAccountsHashVerification>;
+
+    #[rpc(meta, name = "getSupermajorityProgress")]
+    fn get_supermajority_progress(
+        &self,
+        meta: Self::Metadata,
+    ) -> Result<Option<RpcSupermajorityProgress>>;
+
     #[rpc(meta, name = "getConfirmedBlock")]
     fn get_confirmed_block(
         &self,
@@ -1789,6 +2325,14 @@ pub trait RpcSol {
         config: Option<RpcStakeConfig>,
     ) -> Result<RpcStakeActivation>;
 
+    #[rpc(meta, name = "getInflationReward")]
+    fn get_inflation_reward(
+        &self,
+        meta: Self::Metadata,
+        address_strs: Vec<String>,
+        config: Option<RpcStakeConfig>,
+    ) -> Result<Vec<Option<RpcInflationReward>>>;
+
     // SPL Token-specific RPC endpoints
     // See https://github.com/solana-labs/solana-program-library/releases/tag/token-v2.0.0 for
     // program details
@@ -1841,11 +2385,15 @@ fn _send_transaction(
     transaction: Transaction,
     wire_transaction: Vec<u8>,
     last_valid_slot: Slot,
+    trace_id: Option<u64>,
 ) -> Result<String> {
     if transaction.signatures.is_empty() {
         return Err(RpcCustomError::TransactionSignatureVerificationFailure.into());
     }
     let signature = transaction.signatures[0];
+    if let Some(trace_id) = trace_id {
+        transaction_trace::tracer().start(signature, trace_id);
+    }
     let transaction_info = TransactionInfo::new(signature, wire_transaction, last_valid_slot);
     meta.transaction_sender
         .lock()
@@ -1926,24 +2474,45 @@ impl RpcSol for RpcSolImpl {
         meta: Self::Metadata,
         program_id_str: String,
         config: Option<RpcProgramAccountsConfig>,
-    ) -> Result<Vec<RpcKeyedAccount>> {
+    ) -> Result<RpcProgramAccounts> {
         debug!(
             "get_program_accounts rpc request received: {:?}",
             program_id_str
         );
         let program_id = verify_pubkey(program_id_str)?;
-        let (config, filters) = if let Some(config) = config {
+        let (config, filters, limit, cursor) = if let Some(config) = config {
             (
                 Some(config.account_config),
                 config.filters.unwrap_or_default(),
+                config.limit,
+                config.cursor,
             )
         } else {
-            (None, vec![])
+            (None, vec![], None, None)
         };
         for filter in &filters {
             verify_filter(filter)?;
         }
-        meta.get_program_accounts(&program_id, config, filters)
+        if let Some(limit) = limit {
+            if limit == 0 || limit > MAX_GET_PROGRAM_ACCOUNTS_LIMIT {
+                return Err(Error::invalid_params(format!(
+                    "Invalid limit; max {}",
+                    MAX_GET_PROGRAM_ACCOUNTS_LIMIT
+                )));
+            }
+        }
+        let cursor = match cursor {
+            Some(_) if limit.is_none() => {
+                return Err(Error::invalid_params("cursor requires limit".to_string()));
+            }
+            Some(cursor) => Some(
+                cursor
+                    .parse::<Pubkey>()
+                    .map_err(|_| Error::invalid_params("Invalid cursor".to_string()))?,
+            ),
+            None => None,
+        };
+        meta.get_program_accounts(&program_id, config, filters, limit, cursor)
     }
 
     fn get_inflation_governor(
@@ -2033,6 +2602,14 @@ impl RpcSol for RpcSolImpl {
                         } else {
                             (None, None)
                         };
+                    let validator_info =
+                        cluster_info
+                            .get_validator_info(&contact_info.id)
+                            .map(|info| RpcValidatorInfo {
+                                name: info.name,
+                                website: info.website,
+                                icon_url: info.icon_url,
+                            });
                     Some(RpcContactInfo {
                         pubkey: contact_info.id.to_string(),
                         gossip: Some(contact_info.gossip),
@@ -2040,6 +2617,7 @@ impl RpcSol for RpcSolImpl {
                         rpc: valid_address_or_none(&contact_info.rpc),
                         version,
                         feature_set,
+                        validator_info,
                     })
                 } else {
                     None // Exclude spy nodes
@@ -2048,35 +2626,139 @@ impl RpcSol for RpcSolImpl {
             .collect())
     }
 
-    fn get_epoch_info(
-        &self,
-        meta: Self::Metadata,
-        commitment: Option<CommitmentConfig>,
-    ) -> Result<EpochInfo> {
-        debug!("get_epoch_info rpc request received");
-        let bank = meta.bank(commitment);
-        Ok(bank.get_epoch_info())
-    }
-
-    fn get_block_commitment(
-        &self,
-        meta: Self::Metadata,
-        block: Slot,
-    ) -> Result<RpcBlockCommitment<BlockCommitmentArray>> {
-        debug!("get_block_commitment rpc request received");
-        Ok(meta.get_block_commitment(block))
+    fn get_peer_bandwidth(&self, meta: Self::Metadata) -> Result<Vec<RpcPeerBandwidth>> {
+        debug!("get_peer_bandwidth rpc request received");
+        Ok(meta
+            .cluster_info
+            .peer_bandwidth()
+            .peers()
+            .into_iter()
+            .map(|(pubkey, peer)| RpcPeerBandwidth {
+                pubkey: pubkey.to_string(),
+                bytes_served: peer.bytes_served,
+                bytes_received: peer.bytes_received,
+                score: peer.score(),
+                leecher: peer.is_leecher(),
+            })
+            .collect())
     }
 
-    fn get_genesis_hash(&self, meta: Self::Metadata) -> Result<String> {
-        debug!("get_genesis_hash rpc request received");
-        Ok(meta.genesis_hash.to_string())
+    fn get_gossip_peer_stats(&self, meta: Self::Metadata) -> Result<Vec<RpcGossipPeerStats>> {
+        debug!("get_gossip_peer_stats rpc request received");
+        Ok(meta
+            .cluster_info
+            .gossip_peer_stats()
+            .peers()
+            .into_iter()
+            .map(|(pubkey, peer)| RpcGossipPeerStats {
+                pubkey: pubkey.to_string(),
+                push_messages_received: peer.push_messages_received,
+                pull_requests_served: peer.pull_requests_served,
+                prunes_sent: peer.prunes_sent,
+                prunes_received: peer.prunes_received,
+                bytes_received: peer.bytes_received,
+                bytes_sent: peer.bytes_sent,
+                last_error: peer.last_error,
+                last_updated: peer.last_updated,
+            })
+            .collect())
     }
 
-    fn get_leader_schedule(
+    fn get_ancestor_hashes_reports(
         &self,
         meta: Self::Metadata,
-        slot: Option<Slot>,
-        commitment: Option<CommitmentConfig>,
+    ) -> Result<Vec<RpcAncestorHashesReport>> {
+        debug!("get_ancestor_hashes_reports rpc request received");
+        Ok(meta
+            .cluster_info
+            .ancestor_hashes_reports()
+            .reports()
+            .into_iter()
+            .map(|report| RpcAncestorHashesReport {
+                slot: report.slot,
+                divergent_slot: report.divergent_slot,
+                num_responses: report.num_responses,
+                num_agreeing: report.num_agreeing,
+                timestamp: report.timestamp,
+            })
+            .collect())
+    }
+
+    fn get_recent_transaction_logs(
+        &self,
+        meta: Self::Metadata,
+        config: Option<RpcTransactionLogsConfig>,
+    ) -> Result<Vec<RpcLogsResponse>> {
+        debug!("get_recent_transaction_logs rpc request received");
+        let config = config.unwrap_or_default();
+        let address = if let Some(mentions) = config.mentions {
+            Some(verify_pubkey(mentions)?)
+        } else {
+            None
+        };
+        let limit = config
+            .limit
+            .unwrap_or(MAX_GET_RECENT_TRANSACTION_LOGS_LIMIT);
+        if limit == 0 || limit > MAX_GET_RECENT_TRANSACTION_LOGS_LIMIT {
+            return Err(Error::invalid_params(format!(
+                "Invalid limit; max {}",
+                MAX_GET_RECENT_TRANSACTION_LOGS_LIMIT
+            )));
+        }
+        Ok(meta.get_recent_transaction_logs(address, limit, config.commitment))
+    }
+
+    fn get_epoch_info(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<EpochInfo> {
+        debug!("get_epoch_info rpc request received");
+        let bank = meta.bank(commitment);
+        Ok(bank.get_epoch_info())
+    }
+
+    fn get_epoch_rewards_distribution(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<Option<RpcEpochRewardsDistribution>> {
+        debug!("get_epoch_rewards_distribution rpc request received");
+        let bank = meta.bank(commitment);
+        Ok(bank
+            .epoch_rewards_distribution()
+            .map(|distribution| RpcEpochRewardsDistribution {
+                rewarded_epoch: distribution.rewarded_epoch,
+                calculation_slot: distribution.calculation_slot,
+                calculation_block_height: distribution.calculation_block_height,
+                end_block_height: distribution.end_block_height(),
+                num_partitions: distribution.num_partitions() as u64,
+                distributed_partitions: distribution.num_distributed_partitions(bank.block_height())
+                    as u64,
+                num_stake_accounts: distribution.num_stake_rewards() as u64,
+                total_stake_rewards: distribution.total_stake_rewards,
+            }))
+    }
+
+    fn get_block_commitment(
+        &self,
+        meta: Self::Metadata,
+        block: Slot,
+    ) -> Result<RpcBlockCommitment<BlockCommitmentArray>> {
+        debug!("get_block_commitment rpc request received");
+        Ok(meta.get_block_commitment(block))
+    }
+
+    fn get_genesis_hash(&self, meta: Self::Metadata) -> Result<String> {
+        debug!("get_genesis_hash rpc request received");
+        Ok(meta.genesis_hash.to_string())
+    }
+
+    fn get_leader_schedule(
+        &self,
+        meta: Self::Metadata,
+        slot: Option<Slot>,
+        commitment: Option<CommitmentConfig>,
     ) -> Result<Option<RpcLeaderSchedule>> {
         let bank = meta.bank(commitment);
         let slot = slot.unwrap_or_else(|| bank.slot());
@@ -2101,6 +2783,15 @@ impl RpcSol for RpcSolImpl {
         )
     }
 
+    fn get_leader_slots(
+        &self,
+        meta: Self::Metadata,
+        config: Option<RpcLeaderSlotsConfig>,
+    ) -> Result<RpcLeaderSlots> {
+        debug!("get_leader_slots rpc request received: {:?}", config);
+        meta.get_leader_slots(config)
+    }
+
     fn get_recent_blockhash(
         &self,
         meta: Self::Metadata,
@@ -2222,6 +2913,15 @@ impl RpcSol for RpcSolImpl {
         Ok(meta.get_largest_accounts(config))
     }
 
+    fn get_quarantined_accounts(
+        &self,
+        meta: Self::Metadata,
+        commitment: Option<CommitmentConfig>,
+    ) -> Result<RpcResponse<Vec<RpcQuarantinedAccount>>> {
+        debug!("get_quarantined_accounts rpc request received");
+        Ok(meta.get_quarantined_accounts(commitment))
+    }
+
     fn get_supply(
         &self,
         meta: Self::Metadata,
@@ -2270,7 +2970,7 @@ impl RpcSol for RpcSolImpl {
             Error::internal_error()
         })?;
 
-        _send_transaction(meta, transaction, wire_transaction, last_valid_slot)
+        _send_transaction(meta, transaction, wire_transaction, last_valid_slot, None)
     }
 
     fn send_transaction(
@@ -2307,13 +3007,21 @@ impl RpcSol for RpcSolImpl {
                     result: RpcSimulateTransactionResult {
                         err: Some(err),
                         logs: Some(logs),
+                        inner_instructions: None,
+                        accounts: None,
                     },
                 }
                 .into());
             }
         }
 
-        _send_transaction(meta, transaction, wire_transaction, last_valid_slot)
+        _send_transaction(
+            meta,
+            transaction,
+            wire_transaction,
+            last_valid_slot,
+            config.trace_id,
+        )
     }
 
     fn simulate_transaction(
@@ -2333,14 +3041,84 @@ impl RpcSol for RpcSolImpl {
             }
         }
 
-        let bank = &*meta.bank(config.commitment);
-        let (result, logs) = bank.simulate_transaction(transaction);
+        let (account_encoding, addresses) = match config.accounts {
+            Some(accounts_config) => {
+                if accounts_config.addresses.len() > MAX_MULTIPLE_ACCOUNTS {
+                    return Err(Error::invalid_params(format!(
+                        "Too many accounts provided; max {}",
+                        MAX_MULTIPLE_ACCOUNTS
+                    )));
+                }
+                let addresses = accounts_config
+                    .addresses
+                    .into_iter()
+                    .map(verify_pubkey)
+                    .collect::<Result<Vec<_>>>()?;
+                let encoding = accounts_config
+                    .encoding
+                    .unwrap_or(UiAccountEncoding::Base64);
+                (encoding, Some(addresses))
+            }
+            None => (UiAccountEncoding::Base64, None),
+        };
+
+        let bank = match config.slot {
+            Some(slot) => meta.frozen_bank_at_slot(slot)?,
+            None => meta.bank(config.commitment),
+        };
+        let TransactionSimulationDetails {
+            result,
+            logs,
+            inner_instructions,
+            accounts,
+        } = bank.simulate_transaction_with_details(
+            transaction,
+            config.inner_instructions,
+            addresses.as_deref().unwrap_or_default(),
+        );
+
+        let inner_instructions = inner_instructions.map(|inner_instructions| {
+            inner_instructions
+                .into_iter()
+                .enumerate()
+                .map(|(index, instructions)| InnerInstructions {
+                    index: index as u8,
+                    instructions,
+                })
+                .filter(|i| !i.instructions.is_empty())
+                .map(UiInnerInstructions::from)
+                .collect()
+        });
+        let accounts = match addresses {
+            Some(addresses) => Some(
+                addresses
+                    .iter()
+                    .zip(accounts)
+                    .map(|(address, account)| {
+                        account
+                            .map(|account| {
+                                encode_account(
+                                    &bank,
+                                    address,
+                                    account,
+                                    account_encoding.clone(),
+                                    None,
+                                )
+                            })
+                            .transpose()
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            None => None,
+        };
 
         Ok(new_response(
             &bank,
             RpcSimulateTransactionResult {
                 err: result.err(),
                 logs: Some(logs),
+                inner_instructions,
+                accounts,
             },
         ))
     }
@@ -2395,6 +3173,36 @@ impl RpcSol for RpcSolImpl {
         Ok(())
     }
 
+    fn get_metrics(&self, meta: Self::Metadata) -> Result<Vec<RpcMetric>> {
+        debug!("get_metrics rpc request received");
+        meta.get_metrics()
+    }
+
+    fn get_account_lock_contention(
+        &self,
+        meta: Self::Metadata,
+        limit: Option<usize>,
+    ) -> Result<Vec<RpcAccountLockContention>> {
+        debug!("get_account_lock_contention rpc request received");
+        meta.get_account_lock_contention(limit)
+    }
+
+    fn get_accounts_hash_verification(
+        &self,
+        meta: Self::Metadata,
+    ) -> Result<RpcAccountsHashVerification> {
+        debug!("get_accounts_hash_verification rpc request received");
+        Ok(meta.get_accounts_hash_verification())
+    }
+
+    fn get_supermajority_progress(
+        &self,
+        meta: Self::Metadata,
+    ) -> Result<Option<RpcSupermajorityProgress>> {
+        debug!("get_supermajority_progress rpc request received");
+        Ok(meta.get_supermajority_progress())
+    }
+
     fn get_confirmed_block(
         &self,
         meta: Self::Metadata,
@@ -2499,18 +3307,37 @@ impl RpcSol for RpcSolImpl {
         } else {
             None
         };
+        let max_limit = meta
+            .config
+            .max_signatures_for_address_limit
+            .unwrap_or(MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT);
         let limit = config
             .limit
-            .unwrap_or(MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT);
+            .unwrap_or_else(|| MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT.min(max_limit));
 
-        if limit == 0 || limit > MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT {
+        if limit == 0 || limit > max_limit {
             return Err(Error::invalid_params(format!(
                 "Invalid limit; max {}",
-                MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS2_LIMIT
+                max_limit
             )));
         }
+        if let (Some(min_slot), Some(max_slot)) = (config.min_slot, config.max_slot) {
+            if min_slot > max_slot {
+                return Err(Error::invalid_params(format!(
+                    "Invalid slot range; min slot {} is greater than max slot {}",
+                    min_slot, max_slot
+                )));
+            }
+        }
 
-        meta.get_confirmed_signatures_for_address2(address, before, until, limit)
+        meta.get_confirmed_signatures_for_address2(
+            address,
+            before,
+            until,
+            limit,
+            config.min_slot,
+            config.max_slot,
+        )
     }
 
     fn get_first_available_block(&self, meta: Self::Metadata) -> Result<Slot> {
@@ -2532,6 +3359,29 @@ impl RpcSol for RpcSolImpl {
         meta.get_stake_activation(&pubkey, config)
     }
 
+    fn get_inflation_reward(
+        &self,
+        meta: Self::Metadata,
+        address_strs: Vec<String>,
+        config: Option<RpcStakeConfig>,
+    ) -> Result<Vec<Option<RpcInflationReward>>> {
+        debug!(
+            "get_inflation_reward rpc request received: {:?}",
+            address_strs.len()
+        );
+        if address_strs.len() > MAX_MULTIPLE_ACCOUNTS {
+            return Err(Error::invalid_params(format!(
+                "Too many inputs provided; max {}",
+                MAX_MULTIPLE_ACCOUNTS
+            )));
+        }
+        let mut addresses: Vec<Pubkey> = vec![];
+        for address_str in address_strs {
+            addresses.push(verify_pubkey(address_str)?);
+        }
+        meta.get_inflation_reward(addresses, config)
+    }
+
     fn get_token_account_balance(
         &self,
         meta: Self::Metadata,
@@ -2677,6 +3527,7 @@ pub(crate) fn create_validator_exit(exit: &Arc<AtomicBool>) -> Arc<RwLock<Option
 pub mod tests {
     use super::*;
     use crate::{
+        ancestor_hashes_service::AncestorHashesReport,
         contact_info::ContactInfo,
         non_circulating_supply::non_circulating_accounts,
         optimistically_confirmed_bank_tracker::{
@@ -2861,6 +3712,8 @@ pub mod tests {
             &runtime::Runtime::new().unwrap(),
             None,
             OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+            Arc::default(),
+            Arc::default(),
         );
         SendTransactionService::new(tpu_address, &bank_forks, None, receiver);
 
@@ -2963,7 +3816,7 @@ pub mod tests {
             .expect("actual response deserialization");
 
         let expected = format!(
-            r#"{{"jsonrpc":"2.0","result":[{{"pubkey": "{}", "gossip": "127.0.0.1:1235", "tpu": "127.0.0.1:1234", "rpc": "127.0.0.1:{}", "version": null, "featureSet": null}}],"id":1}}"#,
+            r#"{{"jsonrpc":"2.0","result":[{{"pubkey": "{}", "gossip": "127.0.0.1:1235", "tpu": "127.0.0.1:1234", "rpc": "127.0.0.1:{}", "version": null, "featureSet": null, "validatorInfo": null}}],"id":1}}"#,
             leader_pubkey,
             rpc_port::DEFAULT_RPC_PORT
         );
@@ -3028,6 +3881,38 @@ pub mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_confirmed_signatures_for_address2_invalid_params() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let check = |params: &str, message: &str| {
+            let req = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"getConfirmedSignaturesForAddress2","params":["{}",{}]}}"#,
+                bob_pubkey, params
+            );
+            let res = io.handle_request_sync(&req, meta.clone());
+            let result: Response = serde_json::from_str(&res.expect("actual response"))
+                .expect("actual response deserialization");
+            let expected = json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32602,
+                    "message": message
+                },
+                "id": 1
+            });
+            let expected: Response =
+                serde_json::from_value(expected).expect("expected response deserialization");
+            assert_eq!(expected, result);
+        };
+        check(r#"{"limit":1001}"#, "Invalid limit; max 1000");
+        check(
+            r#"{"minSlot":5,"maxSlot":4}"#,
+            "Invalid slot range; min slot 5 is greater than max slot 4",
+        );
+    }
+
     #[test]
     fn test_rpc_get_slot_leader() {
         let bob_pubkey = solana_sdk::pubkey::new_rand();
@@ -3202,6 +4087,96 @@ pub mod tests {
         assert_eq!(largest_accounts.len(), 1);
     }
 
+    #[test]
+    fn test_rpc_get_peer_bandwidth() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+        let peer = solana_sdk::pubkey::new_rand();
+        meta.cluster_info
+            .peer_bandwidth()
+            .record_served(vec![(peer, 1_000)]);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getPeerBandwidth"}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        let peer_bandwidth: Vec<RpcPeerBandwidth> = serde_json::from_value(json["result"].clone())
+            .expect("actual response deserialization");
+        assert_eq!(peer_bandwidth.len(), 1);
+        assert_eq!(peer_bandwidth[0].pubkey, peer.to_string());
+        assert_eq!(peer_bandwidth[0].bytes_served, 1_000);
+        assert_eq!(peer_bandwidth[0].bytes_received, 0);
+        assert!(!peer_bandwidth[0].leecher);
+    }
+
+    #[test]
+    fn test_rpc_get_gossip_peer_stats() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+        let peer = solana_sdk::pubkey::new_rand();
+        meta.cluster_info
+            .gossip_peer_stats()
+            .update(vec![(peer, 1_000)], |stats, bytes| {
+                stats.push_messages_received += 1;
+                stats.bytes_received += bytes;
+            });
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getGossipPeerStats"}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        let peer_stats: Vec<RpcGossipPeerStats> = serde_json::from_value(json["result"].clone())
+            .expect("actual response deserialization");
+        assert_eq!(peer_stats.len(), 1);
+        assert_eq!(peer_stats[0].pubkey, peer.to_string());
+        assert_eq!(peer_stats[0].push_messages_received, 1);
+        assert_eq!(peer_stats[0].bytes_received, 1_000);
+        assert_eq!(peer_stats[0].bytes_sent, 0);
+        assert_eq!(peer_stats[0].last_error, None);
+    }
+
+    #[test]
+    fn test_rpc_get_ancestor_hashes_reports() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+        meta.cluster_info
+            .ancestor_hashes_reports()
+            .insert(AncestorHashesReport {
+                slot: 7,
+                divergent_slot: Some(5),
+                num_responses: 3,
+                num_agreeing: 2,
+                timestamp: 42,
+            });
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getAncestorHashesReports"}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        let reports: Vec<RpcAncestorHashesReport> = serde_json::from_value(json["result"].clone())
+            .expect("actual response deserialization");
+        assert_eq!(
+            reports,
+            vec![RpcAncestorHashesReport {
+                slot: 7,
+                divergent_slot: Some(5),
+                num_responses: 3,
+                num_agreeing: 2,
+                timestamp: 42,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_get_quarantined_accounts() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getQuarantinedAccounts"}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        let quarantined_accounts: Vec<RpcQuarantinedAccount> =
+            serde_json::from_value(json["result"]["value"].clone())
+                .expect("actual response deserialization");
+        assert!(quarantined_accounts.is_empty());
+    }
+
     #[test]
     fn test_rpc_get_minimum_balance_for_rent_exemption() {
         let bob_pubkey = solana_sdk::pubkey::new_rand();
@@ -3281,6 +4256,109 @@ pub mod tests {
         assert_eq!(inflation_rate, expected_inflation_rate);
     }
 
+    #[test]
+    fn test_rpc_get_epoch_rewards_distribution() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        // No rewards are being distributed in the first epoch
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getEpochRewardsDistribution"}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        let distribution: Option<RpcEpochRewardsDistribution> =
+            serde_json::from_value(json["result"].clone())
+                .expect("actual response deserialization");
+        assert_eq!(distribution, None);
+    }
+
+    #[test]
+    fn test_rpc_get_inflation_reward() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler {
+            io,
+            meta,
+            bank,
+            block_commitment_cache,
+            leader_vote_keypair,
+            ..
+        } = start_rpc_handler_with_tx_and_blockstore(
+            &bob_pubkey,
+            vec![1, TEST_SLOTS_PER_EPOCH, TEST_SLOTS_PER_EPOCH + 1],
+        );
+        block_commitment_cache
+            .write()
+            .unwrap()
+            .set_highest_confirmed_root(TEST_SLOTS_PER_EPOCH + 1);
+
+        let vote_pubkey = leader_vote_keypair.pubkey();
+        let stake_pubkey = solana_sdk::pubkey::new_rand();
+        let reward = |pubkey: &Pubkey, lamports, reward_type| Reward {
+            pubkey: pubkey.to_string(),
+            lamports,
+            post_balance: 100,
+            reward_type: Some(reward_type),
+        };
+        meta.blockstore
+            .write_rewards(
+                TEST_SLOTS_PER_EPOCH,
+                vec![
+                    reward(&vote_pubkey, 10, RewardType::Voting),
+                    reward(&bob_pubkey, -1, RewardType::Rent),
+                ],
+            )
+            .unwrap();
+        // Stake rewards may be paid by the blocks following the first one of the epoch
+        meta.blockstore
+            .write_rewards(
+                TEST_SLOTS_PER_EPOCH + 1,
+                vec![reward(&stake_pubkey, 20, RewardType::Staking)],
+            )
+            .unwrap();
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getInflationReward","params":[["{}","{}","{}"]]}}"#,
+            vote_pubkey, stake_pubkey, bob_pubkey
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        let rewards: Vec<Option<RpcInflationReward>> =
+            serde_json::from_value(json["result"].clone())
+                .expect("actual response deserialization");
+        let vote_account = bank.get_account(&vote_pubkey).unwrap();
+        assert_eq!(
+            rewards,
+            vec![
+                Some(RpcInflationReward {
+                    epoch: 0,
+                    effective_slot: TEST_SLOTS_PER_EPOCH,
+                    amount: 10,
+                    post_balance: 100,
+                    effective_stake: Some(bank.epoch_vote_accounts(0).unwrap()[&vote_pubkey].0),
+                    commission: Some(VoteState::from(&vote_account).unwrap().commission),
+                }),
+                // The stake account is not known to the bank
+                Some(RpcInflationReward {
+                    epoch: 0,
+                    effective_slot: TEST_SLOTS_PER_EPOCH + 1,
+                    amount: 20,
+                    post_balance: 100,
+                    effective_stake: None,
+                    commission: None,
+                }),
+                None,
+            ]
+        );
+
+        // The current epoch has not been rewarded yet
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getInflationReward","params":[["{}"],{{"epoch":1}}]}}"#,
+            vote_pubkey
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        assert_eq!(json["error"]["code"], -32602);
+    }
+
     #[test]
     fn test_rpc_get_epoch_schedule() {
         let bob_pubkey = solana_sdk::pubkey::new_rand();
@@ -3359,6 +4437,57 @@ pub mod tests {
         assert_eq!(schedule, None);
     }
 
+    #[test]
+    fn test_rpc_get_leader_slots() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, bank, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+        let leader = bank.collector_id().to_string();
+
+        let get_leader_slots = |config: &str| {
+            let req = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"getLeaderSlots","params":[{}]}}"#,
+                config
+            );
+            let res = io.handle_request_sync(&req, meta.clone());
+            serde_json::from_str::<Value>(&res.expect("actual response"))
+                .expect("actual response deserialization")
+        };
+
+        // Slots are absolute, across epochs
+        let json = get_leader_slots(&format!(
+            r#"{{"startSlot":{},"endSlot":{}}}"#,
+            TEST_SLOTS_PER_EPOCH - 1,
+            TEST_SLOTS_PER_EPOCH + 1
+        ));
+        let leader_slots: RpcLeaderSlots = serde_json::from_value(json["result"].clone()).unwrap();
+        assert_eq!(leader_slots.len(), 1);
+        assert_eq!(
+            leader_slots[&leader],
+            vec![
+                TEST_SLOTS_PER_EPOCH - 1,
+                TEST_SLOTS_PER_EPOCH,
+                TEST_SLOTS_PER_EPOCH + 1
+            ]
+        );
+
+        let json = get_leader_slots(r#"{"epoch":0}"#);
+        let leader_slots: RpcLeaderSlots = serde_json::from_value(json["result"].clone()).unwrap();
+        assert_eq!(
+            leader_slots[&leader],
+            (0..TEST_SLOTS_PER_EPOCH).collect::<Vec<_>>()
+        );
+
+        // Only the slots of the identity are returned
+        let json = get_leader_slots(&format!(r#"{{"epoch":0,"identity":"{}"}}"#, bob_pubkey));
+        let leader_slots: RpcLeaderSlots = serde_json::from_value(json["result"].clone()).unwrap();
+        assert!(leader_slots.is_empty());
+
+        let json = get_leader_slots(r#"{"epoch":0,"startSlot":1}"#);
+        assert_eq!(json["error"]["code"], -32602);
+        let json = get_leader_slots(r#"{"startSlot":2,"endSlot":1}"#);
+        assert_eq!(json["error"]["code"], -32602);
+    }
+
     #[test]
     fn test_rpc_get_account_info() {
         let bob_pubkey = solana_sdk::pubkey::new_rand();
@@ -3751,11 +4880,88 @@ pub mod tests {
             system_program::id(),
             bs58::encode(vec![1]).into_string(),
         ); // Filter on Initialized and non-matching data size
-        let res = io.handle_request_sync(&req, meta);
+        let res = io.handle_request_sync(&req, meta.clone());
         let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
         let accounts: Vec<RpcKeyedAccount> = serde_json::from_value(json["result"].clone())
             .expect("actual response deserialization");
         assert_eq!(accounts.len(), 0);
+
+        // Test pagination; page through the nonce accounts one at a time
+        let mut nonce_pubkeys = vec![nonce_keypair0.pubkey(), nonce_keypair1.pubkey()];
+        nonce_pubkeys.sort();
+        let get_page = |cursor: Option<String>| {
+            let req = format!(
+                r#"{{
+                    "jsonrpc":"2.0",
+                    "id":1,
+                    "method":"getProgramAccounts",
+                    "params":["{}",{{"filters": [
+                        {{
+                            "dataSize": {}
+                        }}
+                    ], "limit": 1{}}}]
+                }}"#,
+                system_program::id(),
+                nonce::State::size(),
+                cursor
+                    .map(|cursor| format!(r#", "cursor": "{}""#, cursor))
+                    .unwrap_or_default(),
+            );
+            let res = io.handle_request_sync(&req, meta.clone());
+            let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+            serde_json::from_value::<RpcKeyedAccountsPage>(json["result"].clone())
+                .expect("actual response deserialization")
+        };
+        let page = get_page(None);
+        assert_eq!(page.accounts.len(), 1);
+        assert_eq!(page.accounts[0].pubkey, nonce_pubkeys[0].to_string());
+        assert_eq!(page.next_cursor, Some(nonce_pubkeys[0].to_string()));
+        let page = get_page(page.next_cursor);
+        assert_eq!(page.accounts.len(), 1);
+        assert_eq!(page.accounts[0].pubkey, nonce_pubkeys[1].to_string());
+        let page = get_page(page.next_cursor);
+        assert!(page.accounts.is_empty());
+        assert_eq!(page.next_cursor, None);
+
+        // A limit above the maximum is rejected
+        let req = format!(
+            r#"{{
+                "jsonrpc":"2.0",
+                "id":1,
+                "method":"getProgramAccounts",
+                "params":["{}",{{"limit": {}}}]
+            }}"#,
+            system_program::id(),
+            MAX_GET_PROGRAM_ACCOUNTS_LIMIT + 1,
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        assert!(json["error"].is_object());
+    }
+
+    #[test]
+    fn test_paginate_keyed_accounts() {
+        let mut keyed_accounts: Vec<_> = (0..5)
+            .map(|_| (solana_sdk::pubkey::new_rand(), Account::default()))
+            .collect();
+        keyed_accounts.sort_by_key(|(pubkey, _)| *pubkey);
+        let pubkeys: Vec<_> = keyed_accounts.iter().map(|(pubkey, _)| *pubkey).collect();
+        let page_pubkeys = |page: &[(Pubkey, Account)]| -> Vec<Pubkey> {
+            page.iter().map(|(pubkey, _)| *pubkey).collect()
+        };
+
+        let (page, next_cursor) = paginate_keyed_accounts(keyed_accounts.clone(), 2);
+        assert_eq!(page_pubkeys(&page), pubkeys[..2].to_vec());
+        assert_eq!(next_cursor, Some(pubkeys[1].to_string()));
+
+        let (page, next_cursor) = paginate_keyed_accounts(keyed_accounts[4..].to_vec(), 2);
+        assert_eq!(page_pubkeys(&page), pubkeys[4..].to_vec());
+        assert_eq!(next_cursor, None);
+
+        // No cursor when the last page is exactly full
+        let (page, next_cursor) = paginate_keyed_accounts(keyed_accounts, 5);
+        assert_eq!(page.len(), 5);
+        assert_eq!(next_cursor, None);
     }
 
     #[test]
@@ -3787,7 +4993,7 @@ pub mod tests {
             "jsonrpc": "2.0",
             "result": {
                 "context":{"slot":0},
-                "value":{"err":null, "logs":[]}
+                "value":{"accounts": null, "err":null, "innerInstructions": null, "logs":[]}
             },
             "id": 1,
         });
@@ -3828,7 +5034,7 @@ pub mod tests {
             "jsonrpc": "2.0",
             "result": {
                 "context":{"slot":0},
-                "value":{"err":null, "logs":[]}
+                "value":{"accounts": null, "err":null, "innerInstructions": null, "logs":[]}
             },
             "id": 1,
         });
@@ -3843,12 +5049,12 @@ pub mod tests {
             r#"{{"jsonrpc":"2.0","id":1,"method":"simulateTransaction","params":["{}"]}}"#,
             tx_serialized_encoded,
         );
-        let res = io.handle_request_sync(&req, meta);
+        let res = io.handle_request_sync(&req, meta.clone());
         let expected = json!({
             "jsonrpc": "2.0",
             "result": {
                 "context":{"slot":0},
-                "value":{"err":null, "logs":[]}
+                "value":{"accounts": null, "err":null, "innerInstructions": null, "logs":[]}
             },
             "id": 1,
         });
@@ -3857,6 +5063,45 @@ pub mod tests {
         let result: Response = serde_json::from_str(&res.expect("actual response"))
             .expect("actual response deserialization");
         assert_eq!(expected, result);
+
+        // Simulate against a specific slot, returning inner instructions and account states
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"simulateTransaction","params":["{}", {{"slot": 0, "innerInstructions": true, "accounts": {{"addresses": ["{}"]}}}}]}}"#,
+            tx_serialized_encoded, bob_pubkey,
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let json: Value = serde_json::from_str(&res.unwrap()).unwrap();
+        let result: RpcSimulateTransactionResult =
+            serde_json::from_value(json["result"]["value"].clone())
+                .expect("actual response deserialization");
+        assert_eq!(result.err, None);
+        assert_eq!(result.inner_instructions, Some(vec![]));
+        let accounts = result.accounts.unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(
+            accounts[0].as_ref().unwrap().lamports,
+            bank.get_balance(&bob_pubkey) + 1234
+        );
+
+        // Slots that are not held by the node are rejected
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"simulateTransaction","params":["{}", {{"slot": 42}}]}}"#,
+            tx_serialized_encoded,
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let expected = json!({
+            "jsonrpc":"2.0",
+            "error": {
+                "code": -32004,
+                "message": "Block not available for slot 42"
+            },
+            "id":1
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
     }
 
     #[test]
@@ -4013,6 +5258,7 @@ pub mod tests {
         let result = result.as_ref().unwrap();
         assert_eq!(expected_res, result.status);
         assert_eq!(None, result.confirmations);
+        assert!(result.optimistically_confirmed);
 
         // Test getSignatureStatus request on unprocessed tx
         let tx = system_transaction::transfer(&alice, &bob_pubkey, 10, blockhash);
@@ -4253,6 +5499,8 @@ pub mod tests {
             &runtime::Runtime::new().unwrap(),
             None,
             OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+            Arc::default(),
+            Arc::default(),
         );
         SendTransactionService::new(tpu_address, &bank_forks, None, receiver);
 
@@ -4449,6 +5697,8 @@ pub mod tests {
             &runtime::Runtime::new().unwrap(),
             None,
             OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+            Arc::default(),
+            Arc::default(),
         );
         SendTransactionService::new(tpu_address, &bank_forks, None, receiver);
         assert_eq!(request_processor.validator_exit(), false);
@@ -4479,12 +5729,189 @@ pub mod tests {
             &runtime::Runtime::new().unwrap(),
             None,
             OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+            Arc::default(),
+            Arc::default(),
         );
         SendTransactionService::new(tpu_address, &bank_forks, None, receiver);
         assert_eq!(request_processor.validator_exit(), true);
         assert_eq!(exit.load(Ordering::Relaxed), true);
     }
 
+    #[test]
+    fn test_rpc_request_processor_get_metrics() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let ledger_path = get_tmp_ledger_path!();
+        let blockstore = Arc::new(Blockstore::open(&ledger_path).unwrap());
+        let bank_forks = new_bank_forks().0;
+        let cluster_info = Arc::new(ClusterInfo::default());
+        let new_request_processor = |enable_get_metrics| {
+            let config = JsonRpcConfig {
+                enable_get_metrics,
+                ..JsonRpcConfig::default()
+            };
+            JsonRpcRequestProcessor::new(
+                config,
+                bank_forks.clone(),
+                Arc::new(RwLock::new(BlockCommitmentCache::default())),
+                blockstore.clone(),
+                create_validator_exit(&exit),
+                RpcHealth::stub(),
+                cluster_info.clone(),
+                Hash::default(),
+                &runtime::Runtime::new().unwrap(),
+                None,
+                OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+                Arc::default(),
+                Arc::default(),
+            )
+            .0
+        };
+
+        assert_eq!(
+            new_request_processor(false).get_metrics(),
+            Err(Error::method_not_found())
+        );
+        let metrics = new_request_processor(true).get_metrics().unwrap();
+        let instance = cluster_info.id().to_string();
+        assert!(metrics.iter().any(|metric| {
+            metric.namespace == "cluster_info_stats"
+                && metric.instance.as_ref() == Some(&instance)
+                && metric.name == "table_size"
+        }));
+    }
+
+    #[test]
+    fn test_rpc_get_accounts_hash_verification() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+        *meta.accounts_hash_verification.write().unwrap() = AccountsHashVerification {
+            last_verified: Some((5, Hash::default())),
+            mismatch: None,
+        };
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getAccountsHashVerification"}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "lastVerifiedSlot": 5,
+                "lastVerifiedHash": Hash::default().to_string(),
+                "mismatchSlot": null,
+            },
+            "id": 1
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
+    #[test]
+    fn test_rpc_get_account_lock_contention() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        lock_contention::enable_lock_contention_profiler();
+        let profiler = lock_contention::profiler();
+        let hot_pubkey = solana_sdk::pubkey::new_rand();
+        profiler.record_conflict(&hot_pubkey, lock_contention::LockConflict::WriteLocked);
+        profiler.record_conflict(&hot_pubkey, lock_contention::LockConflict::ReadLocked);
+        profiler.record_shared_readonly_lock(&hot_pubkey);
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getAccountLockContention","params":[{}]}}"#,
+            MAX_GET_ACCOUNT_LOCK_CONTENTION_LIMIT
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        let contention: Vec<RpcAccountLockContention> = if let Response::Single(res) = result {
+            if let Output::Success(res) = res {
+                serde_json::from_value(res.result).unwrap()
+            } else {
+                panic!("Expected success");
+            }
+        } else {
+            panic!("Expected single response");
+        };
+        assert!(contention.contains(&RpcAccountLockContention {
+            pubkey: hot_pubkey.to_string(),
+            write_conflicts: 1,
+            read_conflicts: 1,
+            shared_readonly_locks: 1,
+        }));
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getAccountLockContention","params":[0]}"#;
+        let res = io.handle_request_sync(&req, meta);
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_matches!(result, Response::Single(Output::Failure(_)));
+    }
+
+    #[test]
+    fn test_rpc_get_supermajority_progress() {
+        let bob_pubkey = solana_sdk::pubkey::new_rand();
+        let RpcHandler { io, meta, .. } = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getSupermajorityProgress"}"#;
+        let res = io.handle_request_sync(&req, meta.clone());
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": null,
+            "id": 1
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+
+        let bank_hash = Hash::new_unique();
+        let other_bank_hash = Hash::new_unique();
+        *meta.supermajority_progress.write().unwrap() = Some(SupermajorityProgress {
+            slot: 5,
+            bank_hash,
+            total_stake: 30,
+            online_stake: 20,
+            stake_by_version: vec![("1.5.0".to_string(), 20)].into_iter().collect(),
+            stake_by_bank_hash: vec![(bank_hash, 10), (other_bank_hash, 10)]
+                .into_iter()
+                .collect(),
+            wrong_shred_version: vec![],
+            offline: vec![(bob_pubkey, 10)],
+            mismatched_bank_hash: vec![(bob_pubkey, 10, other_bank_hash)],
+        });
+        let res = io.handle_request_sync(&req, meta);
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": {
+                "slot": 5,
+                "bankHash": bank_hash.to_string(),
+                "totalStake": 30,
+                "onlineStake": 20,
+                "stakeByVersion": {"1.5.0": 20},
+                "stakeByBankHash": {
+                    bank_hash.to_string(): 10,
+                    other_bank_hash.to_string(): 10,
+                },
+                "wrongShredVersion": [],
+                "offline": [{"identity": bob_pubkey.to_string(), "stake": 10}],
+                "mismatchedBankHash": [{
+                    "identity": bob_pubkey.to_string(),
+                    "stake": 10,
+                    "bankHash": other_bank_hash.to_string(),
+                }],
+            },
+            "id": 1
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
     #[test]
     fn test_rpc_get_identity() {
         let bob_pubkey = solana_sdk::pubkey::new_rand();
@@ -4568,6 +5995,8 @@ pub mod tests {
             &runtime::Runtime::new().unwrap(),
             None,
             OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+            Arc::default(),
+            Arc::default(),
         );
         SendTransactionService::new(tpu_address, &bank_forks, None, receiver);
         assert_eq!(
@@ -5748,6 +7177,8 @@ pub mod tests {
             &runtime::Runtime::new().unwrap(),
             None,
             optimistically_confirmed_bank.clone(),
+            Arc::default(),
+            Arc::default(),
         );
 
         let mut io = MetaIoHandler::default();
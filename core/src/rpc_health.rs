@@ -1,21 +1,55 @@
 use crate::cluster_info::ClusterInfo;
-use solana_sdk::pubkey::Pubkey;
+use solana_runtime::{
+    bank_forks::{BankForks, SnapshotConfig},
+    snapshot_utils,
+};
+use solana_sdk::{pubkey::Pubkey, timing::timestamp};
 use std::{
     collections::HashSet,
     sync::atomic::{AtomicBool, Ordering},
-    sync::Arc,
+    sync::{Arc, RwLock},
 };
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(PartialEq, Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum RpcHealthStatus {
     Ok,
-    Behind, // Validator is behind its trusted validators
+    Behind,    // Validator is behind its trusted validators
+    Unhealthy, // One of the checks configured by the operator failed
+}
+
+/// Checks which, when set, must pass for the validator to be healthy, beside being within
+/// `health_check_slot_distance` of its trusted validators
+#[derive(Debug, Default, Clone)]
+pub struct RpcHealthChecks {
+    pub max_vote_age_ms: Option<u64>,
+    pub min_gossip_peers: Option<usize>,
+    pub max_snapshot_age_slots: Option<u64>,
+}
+
+/// Detailed health of the validator
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcHealthReport {
+    pub status: RpcHealthStatus,
+    /// Distance to the latest account hash slot of the trusted validators
+    pub behind_by_slots: Option<u64>,
+    /// Milliseconds since the validator last voted
+    pub last_vote_age_ms: Option<u64>,
+    pub gossip_peers: Option<usize>,
+    /// Slots between the root and the latest snapshot archive
+    pub snapshot_age_slots: Option<u64>,
+    /// Why the validator is not healthy
+    pub causes: Vec<String>,
 }
 
 pub struct RpcHealth {
     cluster_info: Arc<ClusterInfo>,
+    bank_forks: Arc<RwLock<BankForks>>,
+    snapshot_config: Option<SnapshotConfig>,
     trusted_validators: Option<HashSet<Pubkey>>,
     health_check_slot_distance: u64,
+    health_checks: RpcHealthChecks,
     override_health_check: Arc<AtomicBool>,
     #[cfg(test)]
     stub_health_status: std::sync::RwLock<Option<RpcHealthStatus>>,
@@ -24,14 +58,20 @@ pub struct RpcHealth {
 impl RpcHealth {
     pub fn new(
         cluster_info: Arc<ClusterInfo>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        snapshot_config: Option<SnapshotConfig>,
         trusted_validators: Option<HashSet<Pubkey>>,
         health_check_slot_distance: u64,
+        health_checks: RpcHealthChecks,
         override_health_check: Arc<AtomicBool>,
     ) -> Self {
         Self {
             cluster_info,
+            bank_forks,
+            snapshot_config,
             trusted_validators,
             health_check_slot_distance,
+            health_checks,
             override_health_check,
             #[cfg(test)]
             stub_health_status: std::sync::RwLock::new(None),
@@ -39,74 +79,156 @@ impl RpcHealth {
     }
 
     pub fn check(&self) -> RpcHealthStatus {
-        #[cfg(test)]
-        {
-            if let Some(stub_health_status) = *self.stub_health_status.read().unwrap() {
-                return stub_health_status;
-            }
-        }
+        self.evaluate(false).status
+    }
 
-        if self.override_health_check.load(Ordering::Relaxed) {
-            RpcHealthStatus::Ok
-        } else if let Some(trusted_validators) = &self.trusted_validators {
-            let (latest_account_hash_slot, latest_trusted_validator_account_hash_slot) = {
-                (
-                    self.cluster_info
-                        .get_accounts_hash_for_node(&self.cluster_info.id(), |hashes| {
-                            hashes
-                                .iter()
-                                .max_by(|a, b| a.0.cmp(&b.0))
-                                .map(|slot_hash| slot_hash.0)
-                        })
-                        .flatten()
-                        .unwrap_or(0),
-                    trusted_validators
-                        .iter()
-                        .map(|trusted_validator| {
-                            self.cluster_info
-                                .get_accounts_hash_for_node(&trusted_validator, |hashes| {
-                                    hashes
-                                        .iter()
-                                        .max_by(|a, b| a.0.cmp(&b.0))
-                                        .map(|slot_hash| slot_hash.0)
-                                })
-                                .flatten()
-                                .unwrap_or(0)
-                        })
-                        .max()
-                        .unwrap_or(0),
-                )
-            };
+    /// Reports the health of the validator along with every value it is checked against
+    pub fn report(&self) -> RpcHealthReport {
+        self.evaluate(true)
+    }
+
+    // Computes the values the configured checks gate on, or all of them if `detailed`
+    fn evaluate(&self, detailed: bool) -> RpcHealthReport {
+        let mut report = RpcHealthReport {
+            status: RpcHealthStatus::Ok,
+            behind_by_slots: None,
+            last_vote_age_ms: None,
+            gossip_peers: None,
+            snapshot_age_slots: None,
+            causes: vec![],
+        };
+        let checks = &self.health_checks;
+
+        if let Some(trusted_validators) = &self.trusted_validators {
+            let (latest_account_hash_slot, latest_trusted_validator_account_hash_slot) =
+                self.latest_account_hash_slots(trusted_validators);
+            report.behind_by_slots = Some(
+                latest_trusted_validator_account_hash_slot.saturating_sub(latest_account_hash_slot),
+            );
 
             // This validator is considered healthy if its latest account hash slot is within
             // `health_check_slot_distance` of the latest trusted validator's account hash slot
-            if latest_account_hash_slot > 0
+            if !(latest_account_hash_slot > 0
                 && latest_trusted_validator_account_hash_slot > 0
                 && latest_account_hash_slot
                     > latest_trusted_validator_account_hash_slot
-                        .saturating_sub(self.health_check_slot_distance)
+                        .saturating_sub(self.health_check_slot_distance))
             {
-                RpcHealthStatus::Ok
-            } else {
                 warn!(
                     "health check: me={}, latest trusted_validator={}",
                     latest_account_hash_slot, latest_trusted_validator_account_hash_slot
                 );
-                RpcHealthStatus::Behind
+                report.status = RpcHealthStatus::Behind;
+                report.causes.push(format!(
+                    "account hash slot {} is not within {} slots of the trusted validators' {}",
+                    latest_account_hash_slot,
+                    self.health_check_slot_distance,
+                    latest_trusted_validator_account_hash_slot
+                ));
+            }
+        }
+
+        if detailed || checks.max_vote_age_ms.is_some() {
+            report.last_vote_age_ms = self
+                .cluster_info
+                .my_last_vote_wallclock()
+                .map(|wallclock| timestamp().saturating_sub(wallclock));
+            if let Some(max_vote_age_ms) = checks.max_vote_age_ms {
+                match report.last_vote_age_ms {
+                    Some(age_ms) if age_ms <= max_vote_age_ms => (),
+                    Some(age_ms) => report.causes.push(format!(
+                        "last vote is {}ms old, more than {}ms",
+                        age_ms, max_vote_age_ms
+                    )),
+                    None => report.causes.push("no vote found in gossip".to_string()),
+                }
+            }
+        }
+
+        if detailed || checks.min_gossip_peers.is_some() {
+            let gossip_peers = self.cluster_info.gossip_peers().len();
+            report.gossip_peers = Some(gossip_peers);
+            if let Some(min_gossip_peers) = checks.min_gossip_peers {
+                if gossip_peers < min_gossip_peers {
+                    report.causes.push(format!(
+                        "{} gossip peers, less than {}",
+                        gossip_peers, min_gossip_peers
+                    ));
+                }
+            }
+        }
+
+        if detailed || checks.max_snapshot_age_slots.is_some() {
+            report.snapshot_age_slots = self.snapshot_age_slots();
+            if let Some(max_snapshot_age_slots) = checks.max_snapshot_age_slots {
+                match report.snapshot_age_slots {
+                    Some(age_slots) if age_slots <= max_snapshot_age_slots => (),
+                    Some(age_slots) => report.causes.push(format!(
+                        "latest snapshot is {} slots old, more than {}",
+                        age_slots, max_snapshot_age_slots
+                    )),
+                    None => report.causes.push("no snapshot archive found".to_string()),
+                }
+            }
+        }
+
+        if report.status == RpcHealthStatus::Ok && !report.causes.is_empty() {
+            report.status = RpcHealthStatus::Unhealthy;
+        }
+        if self.override_health_check.load(Ordering::Relaxed) {
+            report.status = RpcHealthStatus::Ok;
+        }
+        #[cfg(test)]
+        {
+            if let Some(stub_health_status) = *self.stub_health_status.read().unwrap() {
+                report.status = stub_health_status;
             }
-        } else {
-            // No trusted validator point of reference available, so this validator is healthy
-            // because it's running
-            RpcHealthStatus::Ok
         }
+        report
+    }
+
+    // Returns the latest account hash slots of this validator and of its trusted validators
+    fn latest_account_hash_slots(&self, trusted_validators: &HashSet<Pubkey>) -> (u64, u64) {
+        let latest_account_hash_slot = |pubkey: &Pubkey| {
+            self.cluster_info
+                .get_accounts_hash_for_node(pubkey, |hashes| {
+                    hashes
+                        .iter()
+                        .max_by(|a, b| a.0.cmp(&b.0))
+                        .map(|slot_hash| slot_hash.0)
+                })
+                .flatten()
+                .unwrap_or(0)
+        };
+        (
+            latest_account_hash_slot(&self.cluster_info.id()),
+            trusted_validators
+                .iter()
+                .map(latest_account_hash_slot)
+                .max()
+                .unwrap_or(0),
+        )
+    }
+
+    fn snapshot_age_slots(&self) -> Option<u64> {
+        let snapshot_config = self.snapshot_config.as_ref()?;
+        let (_, (snapshot_slot, _, _)) = snapshot_utils::get_highest_snapshot_archive_path(
+            &snapshot_config.snapshot_package_output_path,
+        )?;
+        let root = self.bank_forks.read().unwrap().root();
+        Some(root.saturating_sub(snapshot_slot))
     }
 
     #[cfg(test)]
     pub(crate) fn stub() -> Arc<Self> {
+        use solana_runtime::bank::Bank;
         Arc::new(Self::new(
             Arc::new(ClusterInfo::default()),
+            Arc::new(RwLock::new(BankForks::new(Bank::default()))),
+            None,
             None,
             42,
+            RpcHealthChecks::default(),
             Arc::new(AtomicBool::new(false)),
         ))
     }
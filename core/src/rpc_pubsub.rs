@@ -6,8 +6,14 @@ use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::{typed::Subscriber, Session, SubscriptionId};
 use solana_account_decoder::UiAccount;
 use solana_client::{
-    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSignatureSubscribeConfig},
-    rpc_response::{Response as RpcResponse, RpcKeyedAccount, RpcSignatureResult, SlotInfo},
+    rpc_config::{
+        RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSignatureSubscribeConfig,
+        RpcTransactionLogsSubscribeConfig,
+    },
+    rpc_response::{
+        Response as RpcResponse, RpcKeyedAccount, RpcLogsResponse, RpcSignatureResult, SlotInfo,
+        SlotUpdate,
+    },
 };
 #[cfg(test)]
 use solana_runtime::bank_forks::BankForks;
@@ -75,6 +81,24 @@ pub trait RpcSolPubSub {
     fn program_unsubscribe(&self, meta: Option<Self::Metadata>, id: SubscriptionId)
         -> Result<bool>;
 
+    // Get notification of the logs of every transaction a bank reaching the commitment
+    // processed, or only of those which mention a given address
+    #[pubsub(subscription = "logsNotification", subscribe, name = "logsSubscribe")]
+    fn logs_subscribe(
+        &self,
+        meta: Self::Metadata,
+        subscriber: Subscriber<RpcResponse<RpcLogsResponse>>,
+        config: Option<RpcTransactionLogsSubscribeConfig>,
+    );
+
+    // Unsubscribe from logs notification subscription.
+    #[pubsub(
+        subscription = "logsNotification",
+        unsubscribe,
+        name = "logsUnsubscribe"
+    )]
+    fn logs_unsubscribe(&self, meta: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool>;
+
     // Get notification when signature is verified
     // Accepts signature parameter as base-58 encoded string
     #[pubsub(
@@ -114,6 +138,26 @@ pub trait RpcSolPubSub {
     )]
     fn slot_unsubscribe(&self, meta: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool>;
 
+    // Get notification of each step in the processing of slots
+    #[pubsub(
+        subscription = "slotsUpdatesNotification",
+        subscribe,
+        name = "slotsUpdatesSubscribe"
+    )]
+    fn slots_updates_subscribe(&self, meta: Self::Metadata, subscriber: Subscriber<SlotUpdate>);
+
+    // Unsubscribe from slots updates notification subscription.
+    #[pubsub(
+        subscription = "slotsUpdatesNotification",
+        unsubscribe,
+        name = "slotsUpdatesUnsubscribe"
+    )]
+    fn slots_updates_unsubscribe(
+        &self,
+        meta: Option<Self::Metadata>,
+        id: SubscriptionId,
+    ) -> Result<bool>;
+
     // Get notification when vote is encountered
     #[pubsub(subscription = "voteNotification", subscribe, name = "voteSubscribe")]
     fn vote_subscribe(&self, meta: Self::Metadata, subscriber: Subscriber<RpcVote>);
@@ -241,6 +285,39 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         }
     }
 
+    fn logs_subscribe(
+        &self,
+        _meta: Self::Metadata,
+        subscriber: Subscriber<RpcResponse<RpcLogsResponse>>,
+        config: Option<RpcTransactionLogsSubscribeConfig>,
+    ) {
+        let address = match config.as_ref().and_then(|config| config.mentions.as_ref()) {
+            Some(mentions) => match param::<Pubkey>(mentions, "mentions") {
+                Ok(address) => Some(address),
+                Err(e) => return subscriber.reject(e).unwrap(),
+            },
+            None => None,
+        };
+        let id = self.uid.fetch_add(1, atomic::Ordering::Relaxed);
+        let sub_id = SubscriptionId::Number(id as u64);
+        info!("logs_subscribe: mentions={:?} id={:?}", address, sub_id);
+        self.subscriptions
+            .add_logs_subscription(address, config, sub_id, subscriber)
+    }
+
+    fn logs_unsubscribe(&self, _meta: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool> {
+        info!("logs_unsubscribe: id={:?}", id);
+        if self.subscriptions.remove_logs_subscription(&id) {
+            Ok(true)
+        } else {
+            Err(Error {
+                code: ErrorCode::InvalidParams,
+                message: "Invalid Request: Subscription id does not exist".into(),
+                data: None,
+            })
+        }
+    }
+
     fn signature_subscribe(
         &self,
         _meta: Self::Metadata,
@@ -306,6 +383,32 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         }
     }
 
+    fn slots_updates_subscribe(&self, _meta: Self::Metadata, subscriber: Subscriber<SlotUpdate>) {
+        info!("slots_updates_subscribe");
+        let id = self.uid.fetch_add(1, atomic::Ordering::Relaxed);
+        let sub_id = SubscriptionId::Number(id as u64);
+        info!("slots_updates_subscribe: id={:?}", sub_id);
+        self.subscriptions
+            .add_slot_update_subscription(sub_id, subscriber);
+    }
+
+    fn slots_updates_unsubscribe(
+        &self,
+        _meta: Option<Self::Metadata>,
+        id: SubscriptionId,
+    ) -> Result<bool> {
+        info!("slots_updates_unsubscribe");
+        if self.subscriptions.remove_slot_update_subscription(&id) {
+            Ok(true)
+        } else {
+            Err(Error {
+                code: ErrorCode::InvalidParams,
+                message: "Invalid Request: Subscription id does not exist".into(),
+                data: None,
+            })
+        }
+    }
+
     fn vote_subscribe(&self, _meta: Self::Metadata, subscriber: Subscriber<RpcVote>) {
         info!("vote_subscribe");
         let id = self.uid.fetch_add(1, atomic::Ordering::Relaxed);
@@ -1047,10 +1150,17 @@ mod tests {
         .unwrap();
 
         let (response, _) = robust_poll_or_panic(receiver);
-        assert_eq!(
-            response,
-            r#"{"jsonrpc":"2.0","method":"voteNotification","params":{"result":{"hash":"11111111111111111111111111111111","slots":[1,2],"timestamp":null},"subscription":0}}"#
-        );
+        let notification: serde_json::Value = serde_json::from_str(&response).unwrap();
+        let vote: RpcVote =
+            serde_json::from_value(notification["params"]["result"].clone()).unwrap();
+        let vote_pubkeys: Vec<_> = validator_voting_keypairs
+            .iter()
+            .map(|keypairs| keypairs.vote_keypair.pubkey().to_string())
+            .collect();
+        assert!(vote_pubkeys.contains(&vote.vote_pubkey));
+        assert_eq!(vote.hash, "11111111111111111111111111111111");
+        assert_eq!(vote.slots, vec![1, 2]);
+        assert_eq!(vote.timestamp, None);
     }
 
     #[test]
@@ -1,22 +1,34 @@
 //! The `rpc_service` module implements the Solana JSON RPC service.
 
 use crate::{
+    accounts_hash_verifier::AccountsHashVerification,
     bigtable_upload_service::BigTableUploadService,
     cluster_info::ClusterInfo,
     optimistically_confirmed_bank_tracker::OptimisticallyConfirmedBank,
     poh_recorder::PohRecorder,
     rpc::*,
     rpc_health::*,
-    send_transaction_service::{LeaderInfo, SendTransactionService},
+    send_transaction_service::{LeaderInfo, SendTransactionService, TransactionQueueFile},
+    supermajority_progress::SupermajorityProgress,
     validator::ValidatorExit,
 };
-use jsonrpc_core::MetaIoHandler;
+use jsonrpc_core::{
+    futures::{
+        executor::{self, Notify, NotifyHandle},
+        future::{self, Either},
+        sync::oneshot,
+        Async, Future,
+    },
+    Call, Error, MetaIoHandler, Metadata, Middleware, Output, Request, Response, Version,
+};
 use jsonrpc_http_server::{
     hyper, AccessControlAllowOrigin, CloseHandle, DomainsValidation, RequestMiddleware,
     RequestMiddlewareAction, ServerBuilder,
 };
+use rayon::{ThreadPool, ThreadPoolBuilder};
 use regex::Regex;
-use solana_ledger::blockstore::Blockstore;
+use solana_client::rpc_custom_error::RpcCustomError;
+use solana_ledger::{bigtable_upload::UploadCheckpoint, blockstore::Blockstore};
 use solana_runtime::{
     bank_forks::{BankForks, SnapshotConfig},
     commitment::BlockCommitmentCache,
@@ -24,17 +36,19 @@ use solana_runtime::{
 };
 use solana_sdk::{hash::Hash, native_token::lamports_to_sol, pubkey::Pubkey};
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     net::SocketAddr,
     path::{Path, PathBuf},
     sync::atomic::{AtomicBool, Ordering},
     sync::{mpsc::channel, Arc, Mutex, RwLock},
     thread::{self, Builder, JoinHandle},
+    time::Instant,
 };
 use tokio::runtime;
 
 pub struct JsonRpcService {
     thread_hdl: JoinHandle<()>,
+    send_transaction_service: SendTransactionService,
 
     #[cfg(test)]
     pub request_processor: JsonRpcRequestProcessor, // Used only by test_rpc_new()...
@@ -143,10 +157,17 @@ impl RpcRequestMiddleware {
         let response = match self.health.check() {
             RpcHealthStatus::Ok => "ok",
             RpcHealthStatus::Behind => "behind",
+            RpcHealthStatus::Unhealthy => "unhealthy",
         };
         info!("health check: {}", response);
         response
     }
+
+    fn health_report(&self) -> String {
+        let report = self.health.report();
+        info!("health report: {:?}", report);
+        serde_json::to_string(&report).unwrap()
+    }
 }
 
 impl RequestMiddleware for RpcRequestMiddleware {
@@ -193,12 +214,19 @@ impl RequestMiddleware for RpcRequestMiddleware {
         } else if self.is_file_get_path(request.uri().path()) {
             self.process_file_get(request.uri().path())
         } else if request.uri().path() == "/health" {
+            // The plain verdict is kept for load balancers, the report is asked for with
+            // `/health?details`
+            let body = if request.uri().query() == Some("details") {
+                hyper::Body::from(self.health_report())
+            } else {
+                hyper::Body::from(self.health_check())
+            };
             RequestMiddlewareAction::Respond {
                 should_validate_hosts: true,
                 response: Box::new(jsonrpc_core::futures::future::ok(
                     hyper::Response::builder()
                         .status(hyper::StatusCode::OK)
-                        .body(hyper::Body::from(self.health_check()))
+                        .body(body)
                         .unwrap(),
                 )),
             }
@@ -211,6 +239,182 @@ impl RequestMiddleware for RpcRequestMiddleware {
     }
 }
 
+/// Executes the calls of a batched request concurrently, rejecting batches with more than
+/// `max_batch_size` calls. Once the calls of a batch have spent `batch_compute_budget_ms` executing,
+/// its remaining calls fail individually
+struct RpcBatchMiddleware {
+    max_batch_size: Option<usize>,
+    batch_compute_budget_ms: Option<u64>,
+    thread_pool: Arc<ThreadPool>,
+}
+
+impl RpcBatchMiddleware {
+    fn new(max_batch_size: Option<usize>, batch_compute_budget_ms: Option<u64>) -> Self {
+        let thread_pool = ThreadPoolBuilder::new()
+            .num_threads(num_cpus::get())
+            .thread_name(|ix| format!("rpc-batch-{}", ix))
+            .build()
+            .unwrap();
+        Self {
+            max_batch_size,
+            batch_compute_budget_ms,
+            thread_pool: Arc::new(thread_pool),
+        }
+    }
+}
+
+/// Execution time of the calls of a batch. The calls still executing are charged for the time
+/// they have run so far, so the budget also limits the calls dispatched while others run.
+struct BatchComputeBudget {
+    budget_us: u64,
+    // Time spent by the finished calls, and start of the executing calls by index
+    spent: Mutex<(u64, HashMap<usize, Instant>)>,
+}
+
+impl BatchComputeBudget {
+    fn new(budget_ms: u64) -> Self {
+        Self {
+            budget_us: budget_ms.saturating_mul(1_000),
+            spent: Mutex::default(),
+        }
+    }
+
+    /// Starts charging call `ix`, unless the budget is spent
+    fn start(&self, ix: usize) -> bool {
+        let mut spent = self.spent.lock().unwrap();
+        let (spent_us, executing) = &mut *spent;
+        let charged_us = executing
+            .values()
+            .map(|start| start.elapsed().as_micros() as u64)
+            .fold(*spent_us, u64::saturating_add);
+        if charged_us >= self.budget_us {
+            return false;
+        }
+        executing.insert(ix, Instant::now());
+        true
+    }
+
+    fn finish(&self, ix: usize) {
+        let mut spent = self.spent.lock().unwrap();
+        let (spent_us, executing) = &mut *spent;
+        if let Some(start) = executing.remove(&ix) {
+            *spent_us = spent_us.saturating_add(start.elapsed().as_micros() as u64);
+        }
+    }
+}
+
+// Wakeups of call futures polled on the thread pool are dropped: the futures still pending are
+// handed back to the server, whose next poll registers its own task
+struct NoopNotify;
+
+impl Notify for NoopNotify {
+    fn notify(&self, _id: usize) {}
+}
+
+type CallOutputFuture = Box<dyn Future<Item = Option<Output>, Error = ()> + Send>;
+
+fn call_output(response: Option<Response>) -> Option<Output> {
+    match response {
+        Some(Response::Single(output)) => Some(output),
+        _ => None,
+    }
+}
+
+fn call_failure(call: &Call, error: Error) -> Option<Output> {
+    match call {
+        Call::MethodCall(method_call) => Some(Output::from(
+            Err(error),
+            method_call.id.clone(),
+            method_call.jsonrpc,
+        )),
+        Call::Notification(_) => None,
+        Call::Invalid { id } => Some(Output::invalid_request(id.clone(), None)),
+    }
+}
+
+impl<M: Metadata> Middleware<M> for RpcBatchMiddleware {
+    type Future = Box<dyn Future<Item = Option<Response>, Error = ()> + Send>;
+    type CallFuture = jsonrpc_core::middleware::NoopCallFuture;
+
+    fn on_request<F, X>(&self, request: Request, meta: M, next: F) -> Either<Self::Future, X>
+    where
+        F: Fn(Request, M) -> X + Send + Sync,
+        X: Future<Item = Option<Response>, Error = ()> + Send + 'static,
+    {
+        let calls = match request {
+            Request::Batch(calls) => calls,
+            request => return Either::B(next(request, meta)),
+        };
+        if let Some(max_batch_size) = self.max_batch_size {
+            if calls.len() > max_batch_size {
+                let error = Error::invalid_params(format!(
+                    "Too many requests in batch; max {}",
+                    max_batch_size
+                ));
+                return Either::A(Box::new(future::ok(Some(Response::from(
+                    error,
+                    Some(Version::V2),
+                )))));
+            }
+        }
+
+        let budget = self
+            .batch_compute_budget_ms
+            .map(|budget_ms| Arc::new(BatchComputeBudget::new(budget_ms)));
+        let outputs: Vec<_> = calls
+            .into_iter()
+            .enumerate()
+            .map(|(ix, call)| {
+                let (sender, receiver) = oneshot::channel::<CallOutputFuture>();
+                // Only builds the future of the call, which is first polled on the thread pool
+                let response = next(Request::Single(call.clone()), meta.clone());
+                let budget = budget.clone();
+                let batch_compute_budget_ms = self.batch_compute_budget_ms;
+                self.thread_pool.spawn(move || {
+                    if let Some(budget) = &budget {
+                        if !budget.start(ix) {
+                            let budget_ms = batch_compute_budget_ms.unwrap();
+                            let output = call_failure(
+                                &call,
+                                RpcCustomError::BatchComputeBudgetExceeded { budget_ms }.into(),
+                            );
+                            let _ = sender.send(Box::new(future::ok(output)));
+                            return;
+                        }
+                    }
+                    // Synchronous methods complete on the first poll. The others are left to
+                    // complete on the server instead of blocking a thread of the pool.
+                    let mut response = executor::spawn(response);
+                    let output: CallOutputFuture = match response
+                        .poll_future_notify(&NotifyHandle::from(Arc::new(NoopNotify)), 0)
+                    {
+                        Ok(Async::Ready(response)) => Box::new(future::ok(call_output(response))),
+                        Ok(Async::NotReady) => Box::new(response.into_inner().map(call_output)),
+                        Err(()) => Box::new(future::ok(None)),
+                    };
+                    if let Some(budget) = &budget {
+                        budget.finish(ix);
+                    }
+                    let _ = sender.send(output);
+                });
+                receiver.then(|output| match output {
+                    Ok(output) => Either::A(output),
+                    Err(_) => Either::B(future::ok(None)),
+                })
+            })
+            .collect();
+
+        Either::A(Box::new(future::join_all(outputs).map(|outputs| {
+            let outputs: Vec<_> = outputs.into_iter().flatten().collect();
+            if outputs.is_empty() {
+                None
+            } else {
+                Some(Response::Batch(outputs))
+            }
+        })))
+    }
+}
+
 fn process_rest(bank_forks: &Arc<RwLock<BankForks>>, path: &str) -> Option<String> {
     match path {
         "/v0/circulating-supply" => {
@@ -251,14 +455,19 @@ impl JsonRpcService {
         trusted_validators: Option<HashSet<Pubkey>>,
         override_health_check: Arc<AtomicBool>,
         optimistically_confirmed_bank: Arc<RwLock<OptimisticallyConfirmedBank>>,
+        accounts_hash_verification: Arc<RwLock<AccountsHashVerification>>,
+        supermajority_progress: Arc<RwLock<Option<SupermajorityProgress>>>,
     ) -> Self {
         info!("rpc bound to {:?}", rpc_addr);
         info!("rpc configuration: {:?}", config);
 
         let health = Arc::new(RpcHealth::new(
             cluster_info.clone(),
+            bank_forks.clone(),
+            snapshot_config.clone(),
             trusted_validators,
             config.health_check_slot_distance,
+            config.health_checks.clone(),
             override_health_check,
         ));
 
@@ -275,17 +484,22 @@ impl JsonRpcService {
         let (bigtable_ledger_storage, _bigtable_ledger_upload_service) =
             if config.enable_bigtable_ledger_storage || config.enable_bigtable_ledger_upload {
                 runtime
-                    .block_on(solana_storage_bigtable::LedgerStorage::new(
+                    .block_on(solana_storage_bigtable::connect_historical_ledger_storage(
+                        config.historical_ledger_storage_url.as_deref(),
+                        config.historical_ledger_storage_ca_certificate.as_deref(),
                         !config.enable_bigtable_ledger_upload,
                     ))
                     .map(|bigtable_ledger_storage| {
-                        info!("BigTable ledger storage initialized");
+                        info!("Historical ledger storage initialized");
 
                         let bigtable_ledger_upload_service = Arc::new(BigTableUploadService::new(
                             runtime.handle().clone(),
                             bigtable_ledger_storage.clone(),
                             blockstore.clone(),
                             block_commitment_cache.clone(),
+                            config.bigtable_ledger_upload_starting_slot,
+                            config.bigtable_ledger_upload_ending_slot,
+                            UploadCheckpoint::new(ledger_path.join("bigtable_upload_checkpoint")),
                             exit_bigtable_ledger_upload_service.clone(),
                         ));
 
@@ -295,13 +509,16 @@ impl JsonRpcService {
                         )
                     })
                     .unwrap_or_else(|err| {
-                        error!("Failed to initialize BigTable ledger storage: {:?}", err);
+                        error!("Failed to initialize historical ledger storage: {:?}", err);
                         (None, None)
                     })
             } else {
                 (None, None)
             };
 
+        let max_batch_size = config.max_batch_size;
+        let batch_compute_budget_ms = config.batch_compute_budget_ms;
+
         let (request_processor, receiver) = JsonRpcRequestProcessor::new(
             config,
             bank_forks.clone(),
@@ -314,16 +531,21 @@ impl JsonRpcService {
             &runtime,
             bigtable_ledger_storage,
             optimistically_confirmed_bank,
+            accounts_hash_verification,
+            supermajority_progress,
         );
 
         let leader_info =
             poh_recorder.map(|recorder| LeaderInfo::new(cluster_info.clone(), recorder));
-        let _send_transaction_service = Arc::new(SendTransactionService::new(
+        let exit_send_transaction_service = Arc::new(AtomicBool::new(false));
+        let send_transaction_service = SendTransactionService::new_with_queue_file(
             tpu_address,
             &bank_forks,
             leader_info,
             receiver,
-        ));
+            TransactionQueueFile::new(ledger_path.join("send_transaction_queue")),
+            &exit_send_transaction_service,
+        );
 
         #[cfg(test)]
         let test_request_processor = request_processor.clone();
@@ -334,7 +556,10 @@ impl JsonRpcService {
         let thread_hdl = Builder::new()
             .name("solana-jsonrpc".to_string())
             .spawn(move || {
-                let mut io = MetaIoHandler::default();
+                let mut io = MetaIoHandler::with_middleware(RpcBatchMiddleware::new(
+                    max_batch_size,
+                    batch_compute_budget_ms,
+                ));
                 let rpc = RpcSolImpl;
                 io.extend_with(rpc.to_delegate());
 
@@ -371,6 +596,7 @@ impl JsonRpcService {
                 close_handle_sender.send(server.close_handle()).unwrap();
                 server.wait();
                 exit_bigtable_ledger_upload_service.store(true, Ordering::Relaxed);
+                exit_send_transaction_service.store(true, Ordering::Relaxed);
             })
             .unwrap();
 
@@ -383,6 +609,7 @@ impl JsonRpcService {
             .register_exit(Box::new(move || close_handle_.close()));
         Self {
             thread_hdl,
+            send_transaction_service,
             runtime,
             #[cfg(test)]
             request_processor: test_request_processor,
@@ -398,7 +625,8 @@ impl JsonRpcService {
 
     pub fn join(self) -> thread::Result<()> {
         self.runtime.shutdown_background();
-        self.thread_hdl.join()
+        self.thread_hdl.join()?;
+        self.send_transaction_service.join()
     }
 }
 
@@ -406,6 +634,7 @@ impl JsonRpcService {
 mod tests {
     use super::*;
     use crate::{
+        contact_info::ContactInfo,
         crds_value::{CrdsData, CrdsValue, SnapshotHash},
         rpc::create_validator_exit,
     };
@@ -414,9 +643,11 @@ mod tests {
         get_tmp_ledger_path,
     };
     use solana_runtime::{
-        bank::Bank, bank_forks::CompressionType, snapshot_utils::SnapshotVersion,
+        bank::Bank,
+        bank_forks::{CompressionOptions, CompressionType, SnapshotThrottleOptions},
+        snapshot_utils::SnapshotVersion,
     };
-    use solana_sdk::{genesis_config::ClusterType, signature::Signer};
+    use solana_sdk::{genesis_config::ClusterType, signature::Signer, timing::timestamp};
     use std::net::{IpAddr, Ipv4Addr};
 
     #[test]
@@ -456,6 +687,8 @@ mod tests {
             None,
             Arc::new(AtomicBool::new(false)),
             optimistically_confirmed_bank,
+            Arc::default(),
+            Arc::default(),
         );
         let thread = rpc_service.thread_hdl.thread();
         assert_eq!(thread.name().unwrap(), "solana-jsonrpc");
@@ -507,6 +740,8 @@ mod tests {
                 snapshot_package_output_path: PathBuf::from("/"),
                 snapshot_path: PathBuf::from("/"),
                 compression: CompressionType::Bzip2,
+                compression_options: CompressionOptions::default(),
+                throttle_options: SnapshotThrottleOptions::default(),
                 snapshot_version: SnapshotVersion::default(),
             }),
             bank_forks,
@@ -556,14 +791,18 @@ mod tests {
             solana_sdk::pubkey::new_rand(),
         ];
 
+        let bank_forks = create_bank_forks();
         let health = Arc::new(RpcHealth::new(
             cluster_info.clone(),
+            bank_forks.clone(),
+            None,
             Some(trusted_validators.clone().into_iter().collect()),
             health_check_slot_distance,
+            RpcHealthChecks::default(),
             override_health_check.clone(),
         ));
 
-        let rm = RpcRequestMiddleware::new(PathBuf::from("/"), None, create_bank_forks(), health);
+        let rm = RpcRequestMiddleware::new(PathBuf::from("/"), None, bank_forks, health);
 
         // No account hashes for this node or any trusted validators == "behind"
         assert_eq!(rm.health_check(), "behind");
@@ -628,4 +867,106 @@ mod tests {
             .unwrap();
         assert_eq!(rm.health_check(), "behind");
     }
+
+    #[test]
+    fn test_health_check_with_configured_checks() {
+        let cluster_info = Arc::new(ClusterInfo::default());
+        let override_health_check = Arc::new(AtomicBool::new(false));
+        let bank_forks = create_bank_forks();
+        let health = Arc::new(RpcHealth::new(
+            cluster_info.clone(),
+            bank_forks.clone(),
+            None,
+            None,
+            150,
+            RpcHealthChecks {
+                min_gossip_peers: Some(1),
+                ..RpcHealthChecks::default()
+            },
+            override_health_check.clone(),
+        ));
+        let rm = RpcRequestMiddleware::new(PathBuf::from("/"), None, bank_forks, health);
+
+        // The node has no gossip peers
+        assert_eq!(rm.health_check(), "unhealthy");
+        let report: serde_json::Value = serde_json::from_str(&rm.health_report()).unwrap();
+        assert_eq!(report["status"], "unhealthy");
+        assert_eq!(report["gossipPeers"], 0);
+        assert_eq!(report["causes"].as_array().unwrap().len(), 1);
+        // Values which do not gate the verdict are reported too
+        assert_eq!(report["behindBySlots"], serde_json::Value::Null);
+        assert_eq!(report["lastVoteAgeMs"], serde_json::Value::Null);
+
+        cluster_info.insert_info(ContactInfo::new_localhost(
+            &solana_sdk::pubkey::new_rand(),
+            timestamp(),
+        ));
+        assert_eq!(rm.health_check(), "ok");
+        let report: serde_json::Value = serde_json::from_str(&rm.health_report()).unwrap();
+        assert_eq!(report["status"], "ok");
+        assert!(report["causes"].as_array().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rpc_batch_middleware() {
+        let new_io = |max_batch_size, batch_compute_budget_ms| {
+            let mut io = MetaIoHandler::<(), _>::with_middleware(RpcBatchMiddleware::new(
+                max_batch_size,
+                batch_compute_budget_ms,
+            ));
+            io.add_method("ping", |_params| {
+                Ok(serde_json::Value::String("pong".to_string()))
+            });
+            io
+        };
+        let batch = r#"[
+            {"jsonrpc":"2.0","id":1,"method":"ping"},
+            {"jsonrpc":"2.0","id":2,"method":"ping"},
+            {"jsonrpc":"2.0","id":3,"method":"missing"}
+        ]"#;
+        let handle_batch = |io: &MetaIoHandler<(), RpcBatchMiddleware>| -> serde_json::Value {
+            serde_json::from_str(&io.handle_request_sync(batch, ()).unwrap()).unwrap()
+        };
+
+        // Each call of the batch is answered individually, in order
+        let response = handle_batch(&new_io(Some(3), None));
+        assert_eq!(response[0]["id"], 1);
+        assert_eq!(response[0]["result"], "pong");
+        assert_eq!(response[1]["id"], 2);
+        assert_eq!(response[1]["result"], "pong");
+        assert_eq!(response[2]["id"], 3);
+        assert_eq!(response[2]["error"]["code"], -32601);
+
+        // Batches over the size limit are rejected as a whole
+        let response = handle_batch(&new_io(Some(2), None));
+        assert_eq!(response["error"]["code"], -32602);
+
+        // Calls started once the compute budget is spent fail individually
+        let response = handle_batch(&new_io(None, Some(0)));
+        let outputs = response.as_array().unwrap();
+        assert_eq!(outputs.len(), 3);
+        for output in outputs {
+            assert_eq!(
+                output["error"]["code"],
+                solana_client::rpc_custom_error::JSON_RPC_SERVER_ERROR_BATCH_COMPUTE_BUDGET_EXCEEDED
+            );
+        }
+
+        // Calls still executing are charged
+        let budget = BatchComputeBudget::new(1);
+        assert!(budget.start(0));
+        thread::sleep(std::time::Duration::from_millis(2));
+        assert!(!budget.start(1));
+        budget.finish(0);
+        assert!(!budget.start(2));
+
+        // Single requests are not affected
+        let io = new_io(Some(0), Some(0));
+        let response: serde_json::Value = serde_json::from_str(
+            &io.handle_request_sync(r#"{"jsonrpc":"2.0","id":1,"method":"ping"}"#, ())
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(response["result"], "pong");
+    }
 }
@@ -13,16 +13,19 @@ use jsonrpc_pubsub::{
 use serde::Serialize;
 use solana_account_decoder::{parse_token::spl_token_id_v2_0, UiAccount, UiAccountEncoding};
 use solana_client::{
-    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSignatureSubscribeConfig},
+    rpc_config::{
+        RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSignatureSubscribeConfig,
+        RpcTransactionLogsSubscribeConfig,
+    },
     rpc_filter::RpcFilterType,
     rpc_response::{
         ProcessedSignatureResult, ReceivedSignatureResult, Response, RpcKeyedAccount,
-        RpcResponseContext, RpcSignatureResult, SlotInfo,
+        RpcLogsResponse, RpcResponseContext, RpcSignatureResult, SlotInfo, SlotUpdate,
     },
 };
 use solana_measure::measure::Measure;
 use solana_runtime::{
-    bank::Bank,
+    bank::{Bank, TransactionLogInfo},
     bank_forks::BankForks,
     commitment::{BlockCommitmentCache, CommitmentSlots},
 };
@@ -32,6 +35,7 @@ use solana_sdk::{
     commitment_config::{CommitmentConfig, CommitmentLevel},
     pubkey::Pubkey,
     signature::Signature,
+    timing::timestamp,
     transaction,
 };
 use solana_vote_program::vote_state::Vote;
@@ -42,6 +46,7 @@ use std::sync::{
 use std::thread::{Builder, JoinHandle};
 use std::time::Duration;
 use std::{
+    cell::RefCell,
     collections::{HashMap, HashSet},
     iter,
     sync::{Arc, Mutex, RwLock},
@@ -54,7 +59,9 @@ const RECEIVE_DELAY_MILLIS: u64 = 100;
 
 // A more human-friendly version of Vote, with the bank state signature base58 encoded.
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct RpcVote {
+    pub vote_pubkey: String,
     pub slots: Vec<Slot>,
     pub hash: String,
     pub timestamp: Option<UnixTimestamp>,
@@ -62,7 +69,8 @@ pub struct RpcVote {
 
 enum NotificationEntry {
     Slot(SlotInfo),
-    Vote(Vote),
+    SlotUpdate(SlotUpdate),
+    Vote((Pubkey, Vote)),
     Root(Slot),
     Bank(CommitmentSlots),
     Gossip(Slot),
@@ -75,6 +83,9 @@ impl std::fmt::Debug for NotificationEntry {
             NotificationEntry::Root(root) => write!(f, "Root({})", root),
             NotificationEntry::Vote(vote) => write!(f, "Vote({:?})", vote),
             NotificationEntry::Slot(slot_info) => write!(f, "Slot({:?})", slot_info),
+            NotificationEntry::SlotUpdate(slot_update) => {
+                write!(f, "SlotUpdate({:?})", slot_update)
+            }
             NotificationEntry::Bank(commitment_slots) => {
                 write!(f, "Bank({{slot: {:?}}})", commitment_slots.slot)
             }
@@ -115,7 +126,15 @@ type RpcSignatureSubscriptions = RwLock<
         HashMap<SubscriptionId, SubscriptionData<Response<RpcSignatureResult>, bool>>,
     >,
 >;
+// Keyed by the address the transactions must mention, None for every transaction
+type RpcLogsSubscriptions = RwLock<
+    HashMap<
+        Option<Pubkey>,
+        HashMap<SubscriptionId, SubscriptionData<Response<RpcLogsResponse>, ()>>,
+    >,
+>;
 type RpcSlotSubscriptions = RwLock<HashMap<SubscriptionId, Sink<SlotInfo>>>;
+type RpcSlotUpdateSubscriptions = RwLock<HashMap<SubscriptionId, Sink<SlotUpdate>>>;
 type RpcVoteSubscriptions = RwLock<HashMap<SubscriptionId, Sink<RpcVote>>>;
 type RpcRootSubscriptions = RwLock<HashMap<SubscriptionId, Sink<Slot>>>;
 
@@ -293,6 +312,33 @@ fn filter_signature_result(
     )
 }
 
+/// The accounts modified by each bank, grouped by owner. Each bank is scanned once per
+/// notification, however many programs are subscribed to
+#[derive(Default)]
+struct ModifiedProgramAccounts(RefCell<HashMap<Slot, AccountsByOwner>>);
+type AccountsByOwner = HashMap<Pubkey, Vec<(Pubkey, Account)>>;
+
+impl ModifiedProgramAccounts {
+    fn get(&self, bank: &Bank, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.0
+            .borrow_mut()
+            .entry(bank.slot())
+            .or_insert_with(|| {
+                let mut accounts_by_owner = AccountsByOwner::new();
+                for (pubkey, account) in bank.get_all_accounts_modified_since_parent() {
+                    accounts_by_owner
+                        .entry(account.owner)
+                        .or_default()
+                        .push((pubkey, account));
+                }
+                accounts_by_owner
+            })
+            .get(program_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
 fn filter_program_results(
     accounts: Vec<(Pubkey, Account)>,
     program_id: &Pubkey,
@@ -327,15 +373,43 @@ fn filter_program_results(
     (accounts, last_notified_slot)
 }
 
+fn filter_logs_results(
+    logs: Vec<TransactionLogInfo>,
+    _address: &Option<Pubkey>,
+    last_notified_slot: Slot,
+    _config: Option<()>,
+    bank: Option<Arc<Bank>>,
+) -> (Box<dyn Iterator<Item = RpcLogsResponse>>, Slot) {
+    match bank {
+        // The logs of a bank are only notified once, oldest first
+        Some(bank) if bank.slot() != last_notified_slot => {
+            let slot = bank.slot();
+            (
+                Box::new(logs.into_iter().rev().map(move |info| RpcLogsResponse {
+                    signature: info.signature.to_string(),
+                    slot,
+                    err: info.result.err(),
+                    logs: info.log_messages,
+                })),
+                slot,
+            )
+        }
+        _ => (Box::new(iter::empty()), last_notified_slot),
+    }
+}
+
 #[derive(Clone)]
 struct Subscriptions {
     account_subscriptions: Arc<RpcAccountSubscriptions>,
     program_subscriptions: Arc<RpcProgramSubscriptions>,
     signature_subscriptions: Arc<RpcSignatureSubscriptions>,
+    logs_subscriptions: Arc<RpcLogsSubscriptions>,
     gossip_account_subscriptions: Arc<RpcAccountSubscriptions>,
     gossip_program_subscriptions: Arc<RpcProgramSubscriptions>,
     gossip_signature_subscriptions: Arc<RpcSignatureSubscriptions>,
+    gossip_logs_subscriptions: Arc<RpcLogsSubscriptions>,
     slot_subscriptions: Arc<RpcSlotSubscriptions>,
+    slot_update_subscriptions: Arc<RpcSlotUpdateSubscriptions>,
     vote_subscriptions: Arc<RpcVoteSubscriptions>,
     root_subscriptions: Arc<RpcRootSubscriptions>,
 }
@@ -374,10 +448,13 @@ impl RpcSubscriptions {
         let account_subscriptions = Arc::new(RpcAccountSubscriptions::default());
         let program_subscriptions = Arc::new(RpcProgramSubscriptions::default());
         let signature_subscriptions = Arc::new(RpcSignatureSubscriptions::default());
+        let logs_subscriptions = Arc::new(RpcLogsSubscriptions::default());
         let gossip_account_subscriptions = Arc::new(RpcAccountSubscriptions::default());
         let gossip_program_subscriptions = Arc::new(RpcProgramSubscriptions::default());
         let gossip_signature_subscriptions = Arc::new(RpcSignatureSubscriptions::default());
+        let gossip_logs_subscriptions = Arc::new(RpcLogsSubscriptions::default());
         let slot_subscriptions = Arc::new(RpcSlotSubscriptions::default());
+        let slot_update_subscriptions = Arc::new(RpcSlotUpdateSubscriptions::default());
         let vote_subscriptions = Arc::new(RpcVoteSubscriptions::default());
         let root_subscriptions = Arc::new(RpcRootSubscriptions::default());
         let notification_sender = Arc::new(Mutex::new(notification_sender));
@@ -389,10 +466,13 @@ impl RpcSubscriptions {
             account_subscriptions,
             program_subscriptions,
             signature_subscriptions,
+            logs_subscriptions,
             gossip_account_subscriptions,
             gossip_program_subscriptions,
             gossip_signature_subscriptions,
+            gossip_logs_subscriptions,
             slot_subscriptions,
+            slot_update_subscriptions,
             vote_subscriptions,
             root_subscriptions,
         };
@@ -464,6 +544,7 @@ impl RpcSubscriptions {
         program_id: &Pubkey,
         bank_forks: &Arc<RwLock<BankForks>>,
         program_subscriptions: Arc<RpcProgramSubscriptions>,
+        modified_accounts: &ModifiedProgramAccounts,
         notifier: &RpcNotifier,
         commitment_slots: &CommitmentSlots,
     ) -> HashSet<SubscriptionId> {
@@ -473,7 +554,7 @@ impl RpcSubscriptions {
             program_id,
             bank_forks,
             commitment_slots,
-            Bank::get_program_accounts_modified_since_parent,
+            |bank, program_id| modified_accounts.get(bank, program_id),
             filter_program_results,
             notifier,
         )
@@ -631,6 +712,50 @@ impl RpcSubscriptions {
         }
     }
 
+    pub fn add_logs_subscription(
+        &self,
+        address: Option<Pubkey>,
+        config: Option<RpcTransactionLogsSubscribeConfig>,
+        sub_id: SubscriptionId,
+        subscriber: Subscriber<Response<RpcLogsResponse>>,
+    ) {
+        let commitment = config.and_then(|config| config.commitment);
+        let commitment_level = commitment
+            .unwrap_or_else(CommitmentConfig::recent)
+            .commitment;
+        let mut subscriptions = if commitment_level == CommitmentLevel::SingleGossip {
+            self.subscriptions
+                .gossip_logs_subscriptions
+                .write()
+                .unwrap()
+        } else {
+            self.subscriptions.logs_subscriptions.write().unwrap()
+        };
+        add_subscription(
+            &mut subscriptions,
+            address,
+            commitment,
+            sub_id,
+            subscriber,
+            0,
+            None,
+        );
+    }
+
+    pub fn remove_logs_subscription(&self, id: &SubscriptionId) -> bool {
+        let mut subscriptions = self.subscriptions.logs_subscriptions.write().unwrap();
+        if remove_subscription(&mut subscriptions, id) {
+            true
+        } else {
+            let mut subscriptions = self
+                .subscriptions
+                .gossip_logs_subscriptions
+                .write()
+                .unwrap();
+            remove_subscription(&mut subscriptions, id)
+        }
+    }
+
     pub fn add_signature_subscription(
         &self,
         signature: Signature,
@@ -706,6 +831,33 @@ impl RpcSubscriptions {
         self.enqueue_notification(NotificationEntry::Slot(SlotInfo { slot, parent, root }));
     }
 
+    pub fn add_slot_update_subscription(
+        &self,
+        sub_id: SubscriptionId,
+        subscriber: Subscriber<SlotUpdate>,
+    ) {
+        let sink = subscriber.assign_id(sub_id.clone()).unwrap();
+        let mut subscriptions = self
+            .subscriptions
+            .slot_update_subscriptions
+            .write()
+            .unwrap();
+        subscriptions.insert(sub_id, sink);
+    }
+
+    pub fn remove_slot_update_subscription(&self, id: &SubscriptionId) -> bool {
+        let mut subscriptions = self
+            .subscriptions
+            .slot_update_subscriptions
+            .write()
+            .unwrap();
+        subscriptions.remove(id).is_some()
+    }
+
+    pub fn notify_slot_update(&self, slot_update: SlotUpdate) {
+        self.enqueue_notification(NotificationEntry::SlotUpdate(slot_update));
+    }
+
     pub fn notify_signatures_received(&self, slot_signatures: (Slot, Vec<Signature>)) {
         self.enqueue_notification(NotificationEntry::SignaturesReceived(slot_signatures));
     }
@@ -721,8 +873,8 @@ impl RpcSubscriptions {
         subscriptions.remove(id).is_some()
     }
 
-    pub fn notify_vote(&self, vote: &Vote) {
-        self.enqueue_notification(NotificationEntry::Vote(vote.clone()));
+    pub fn notify_vote(&self, vote_pubkey: &Pubkey, vote: &Vote) {
+        self.enqueue_notification(NotificationEntry::Vote((*vote_pubkey, vote.clone())));
     }
 
     pub fn add_root_subscription(&self, sub_id: SubscriptionId, subscriber: Subscriber<Slot>) {
@@ -738,7 +890,12 @@ impl RpcSubscriptions {
 
     pub fn notify_roots(&self, mut rooted_slots: Vec<Slot>) {
         rooted_slots.sort();
+        let now = timestamp();
         rooted_slots.into_iter().for_each(|root| {
+            self.enqueue_notification(NotificationEntry::SlotUpdate(SlotUpdate::Root {
+                slot: root,
+                timestamp: now,
+            }));
             self.enqueue_notification(NotificationEntry::Root(root));
         });
     }
@@ -780,15 +937,23 @@ impl RpcSubscriptions {
                             notifier.notify(slot_info, sink);
                         }
                     }
+                    NotificationEntry::SlotUpdate(slot_update) => {
+                        debug!("slot update notify: {:?}", slot_update);
+                        let subscriptions = subscriptions.slot_update_subscriptions.read().unwrap();
+                        for (_, sink) in subscriptions.iter() {
+                            notifier.notify(slot_update, sink);
+                        }
+                    }
                     // These notifications are only triggered by votes observed on gossip,
                     // unlike `NotificationEntry::Gossip`, which also accounts for slots seen
                     // in VoteState's from bank states built in ReplayStage.
-                    NotificationEntry::Vote(ref vote_info) => {
+                    NotificationEntry::Vote((vote_pubkey, ref vote_info)) => {
                         debug!("vote notify: {:?}", vote_info);
                         let subscriptions = subscriptions.vote_subscriptions.read().unwrap();
                         for (_, sink) in subscriptions.iter() {
                             notifier.notify(
                                 RpcVote {
+                                    vote_pubkey: vote_pubkey.to_string(),
                                     slots: vote_info.slots.clone(),
                                     hash: bs58::encode(vote_info.hash).into_string(),
                                     timestamp: vote_info.timestamp,
@@ -813,7 +978,13 @@ impl RpcSubscriptions {
                             &commitment_slots,
                             &notifier,
                             "bank",
-                        )
+                        );
+                        RpcSubscriptions::notify_logs(
+                            &subscriptions.logs_subscriptions,
+                            &bank_forks,
+                            &commitment_slots,
+                            &notifier,
+                        );
                     }
                     NotificationEntry::Gossip(slot) => {
                         Self::process_gossip_notification(
@@ -861,6 +1032,32 @@ impl RpcSubscriptions {
             &notifier,
             "gossip",
         );
+        RpcSubscriptions::notify_logs(
+            &subscriptions.gossip_logs_subscriptions,
+            bank_forks,
+            &commitment_slots,
+            &notifier,
+        );
+    }
+
+    fn notify_logs(
+        logs_subscriptions: &Arc<RpcLogsSubscriptions>,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        commitment_slots: &CommitmentSlots,
+        notifier: &RpcNotifier,
+    ) {
+        let subscriptions = logs_subscriptions.read().unwrap();
+        for address in subscriptions.keys() {
+            check_commitment_and_notify(
+                &subscriptions,
+                address,
+                bank_forks,
+                commitment_slots,
+                |bank, address| bank.get_transaction_logs(address.as_ref()),
+                filter_logs_results,
+                notifier,
+            );
+        }
     }
 
     fn notify_accounts_programs_signatures(
@@ -895,12 +1092,14 @@ impl RpcSubscriptions {
             let subs = program_subscriptions.read().unwrap();
             subs.keys().cloned().collect()
         };
+        let modified_accounts = ModifiedProgramAccounts::default();
         let mut num_programs_notified = 0;
         for program_id in &programs {
             num_programs_notified += Self::check_program(
                 program_id,
                 bank_forks,
                 program_subscriptions.clone(),
+                &modified_accounts,
                 &notifier,
                 &commitment_slots,
             )
@@ -1008,6 +1207,7 @@ pub(crate) mod tests {
     use jsonrpc_pubsub::typed::Subscriber;
     use serial_test_derive::serial;
     use solana_runtime::{
+        bank::TransactionLogCollectorConfig,
         commitment::BlockCommitment,
         genesis_utils::{create_genesis_config, GenesisConfigInfo},
     };
@@ -1223,6 +1423,149 @@ pub(crate) mod tests {
             .contains_key(&solana_stake_program::id()));
     }
 
+    #[test]
+    #[serial]
+    fn test_check_logs_subscribe() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100);
+        let bank = Bank::new(&genesis_config);
+        bank.set_transaction_log_collector_config(TransactionLogCollectorConfig {
+            max_logs_per_bank: 10,
+            include_votes: false,
+        });
+        let blockhash = bank.last_blockhash();
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(bank)));
+        let bank0 = bank_forks.read().unwrap().get(0).unwrap().clone();
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        bank_forks.write().unwrap().insert(bank1);
+        let alice = solana_sdk::pubkey::new_rand();
+        let bob = solana_sdk::pubkey::new_rand();
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let subscriptions = RpcSubscriptions::new(
+            &exit,
+            bank_forks.clone(),
+            Arc::new(RwLock::new(BlockCommitmentCache::new_for_tests_with_slots(
+                1, 1,
+            ))),
+            OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks),
+        );
+        let (subscriber, _id_receiver, transport_receiver) =
+            Subscriber::new_test("logsNotification");
+        let sub_id = SubscriptionId::Number(0 as u64);
+        subscriptions.add_logs_subscription(
+            Some(alice),
+            Some(RpcTransactionLogsSubscribeConfig {
+                mentions: Some(alice.to_string()),
+                commitment: Some(CommitmentConfig::recent()),
+            }),
+            sub_id.clone(),
+            subscriber,
+        );
+        assert!(subscriptions
+            .subscriptions
+            .logs_subscriptions
+            .read()
+            .unwrap()
+            .contains_key(&Some(alice)));
+
+        let to_bob = system_transaction::transfer(&mint_keypair, &bob, 1, blockhash);
+        let to_alice = system_transaction::transfer(&mint_keypair, &alice, 1, blockhash);
+        let bank1 = bank_forks.read().unwrap().get(1).unwrap().clone();
+        bank1.process_transaction(&to_bob).unwrap();
+        bank1.process_transaction(&to_alice).unwrap();
+
+        let mut commitment_slots = CommitmentSlots::default();
+        commitment_slots.slot = 1;
+        subscriptions.notify_subscribers(commitment_slots);
+        let (response, _) = robust_poll_or_panic(transport_receiver);
+        let expected = json!({
+           "jsonrpc": "2.0",
+           "method": "logsNotification",
+           "params": {
+               "result": {
+                   "context": { "slot": 1 },
+                   "value": {
+                       "signature": to_alice.signatures[0].to_string(),
+                       "slot": 1,
+                       "err": null,
+                       "logs": [],
+                    },
+               },
+               "subscription": 0,
+           }
+        });
+        assert_eq!(serde_json::to_string(&expected).unwrap(), response);
+
+        // The logs of a bank are only notified once, oldest first
+        let logs = bank1.get_transaction_logs(None);
+        let (results, slot) =
+            filter_logs_results(logs.clone(), &None, 0, None, Some(bank1.clone()));
+        assert_eq!(
+            results.map(|result| result.signature).collect::<Vec<_>>(),
+            vec![
+                to_bob.signatures[0].to_string(),
+                to_alice.signatures[0].to_string()
+            ]
+        );
+        assert_eq!(slot, 1);
+        let (results, slot) = filter_logs_results(logs, &None, 1, None, Some(bank1));
+        assert_eq!(results.count(), 0);
+        assert_eq!(slot, 1);
+
+        subscriptions.remove_logs_subscription(&sub_id);
+        assert!(!subscriptions
+            .subscriptions
+            .logs_subscriptions
+            .read()
+            .unwrap()
+            .contains_key(&Some(alice)));
+    }
+
+    #[test]
+    fn test_modified_program_accounts() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100);
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        let blockhash = bank0.last_blockhash();
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        for owner in &[solana_stake_program::id(), solana_vote_program::id()] {
+            let tx = system_transaction::create_account(
+                &mint_keypair,
+                &Keypair::new(),
+                blockhash,
+                1,
+                16,
+                owner,
+            );
+            bank1.process_transaction(&tx).unwrap();
+        }
+
+        let modified_accounts = ModifiedProgramAccounts::default();
+        for owner in &[
+            solana_sdk::system_program::id(),
+            solana_stake_program::id(),
+            solana_vote_program::id(),
+        ] {
+            let accounts = modified_accounts.get(&bank1, owner);
+            assert_eq!(accounts.len(), 1);
+            assert_eq!(
+                accounts,
+                bank1.get_program_accounts_modified_since_parent(owner)
+            );
+        }
+        assert!(modified_accounts
+            .get(&bank1, &solana_sdk::pubkey::new_rand())
+            .is_empty());
+        assert_eq!(modified_accounts.0.borrow().len(), 1);
+    }
+
     #[test]
     #[serial]
     fn test_check_signature_subscribe() {
@@ -1532,6 +1875,57 @@ pub(crate) mod tests {
             .contains_key(&sub_id));
     }
 
+    #[test]
+    #[serial]
+    fn test_check_slot_update_subscribe() {
+        let (subscriber, _id_receiver, mut transport_receiver) =
+            Subscriber::new_test("slotsUpdatesNotification");
+        let sub_id = SubscriptionId::Number(0 as u64);
+        let exit = Arc::new(AtomicBool::new(false));
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank = Bank::new(&genesis_config);
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(bank)));
+        let optimistically_confirmed_bank =
+            OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks);
+        let subscriptions = RpcSubscriptions::new(
+            &exit,
+            bank_forks,
+            Arc::new(RwLock::new(BlockCommitmentCache::new_for_tests())),
+            optimistically_confirmed_bank,
+        );
+        subscriptions.add_slot_update_subscription(sub_id.clone(), subscriber);
+
+        subscriptions.notify_slot_update(SlotUpdate::FirstShredReceived {
+            slot: 1,
+            timestamp: 42,
+        });
+        let (response, receiver) = robust_poll_or_panic(transport_receiver);
+        transport_receiver = receiver;
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "method": "slotsUpdatesNotification",
+            "params": {
+                "result": {"type": "firstShredReceived", "slot": 1, "timestamp": 42},
+                "subscription": 0,
+            },
+        });
+        assert_eq!(
+            expected,
+            serde_json::from_str::<serde_json::Value>(&response).unwrap()
+        );
+
+        // Roots are also streamed as slot updates
+        subscriptions.notify_roots(vec![1]);
+        let (response, _receiver) = robust_poll_or_panic(transport_receiver);
+        let notification: serde_json::Value = serde_json::from_str(&response).unwrap();
+        let slot_update: SlotUpdate =
+            serde_json::from_value(notification["params"]["result"].clone()).unwrap();
+        assert!(matches!(slot_update, SlotUpdate::Root { slot: 1, .. }));
+
+        assert!(subscriptions.remove_slot_update_subscription(&sub_id));
+        assert!(!subscriptions.remove_slot_update_subscription(&sub_id));
+    }
+
     #[test]
     #[serial]
     fn test_add_and_remove_subscription() {
@@ -9,9 +9,25 @@ use std::{
     time::{Duration, Instant},
 };
 
-const SAMPLE_INTERVAL: u64 = 60;
 const SLEEP_INTERVAL: u64 = 500;
 
+#[derive(Clone, Debug)]
+pub struct SamplePerformanceConfig {
+    /// Time between samples
+    pub sample_interval: Duration,
+    /// Number of most recent samples kept in the blockstore, across restarts
+    pub max_samples: usize,
+}
+
+impl Default for SamplePerformanceConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval: Duration::from_secs(60),
+            max_samples: 720,
+        }
+    }
+}
+
 pub struct SamplePerformanceSnapshot {
     pub num_transactions: u64,
     pub num_slots: u64,
@@ -24,6 +40,7 @@ pub struct SamplePerformanceService {
 impl SamplePerformanceService {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(
+        config: SamplePerformanceConfig,
         bank_forks: &Arc<RwLock<BankForks>>,
         blockstore: &Arc<Blockstore>,
         exit: &Arc<AtomicBool>,
@@ -36,7 +53,7 @@ impl SamplePerformanceService {
         let thread_hdl = Builder::new()
             .name("sample-performance".to_string())
             .spawn(move || {
-                Self::run(bank_forks, &blockstore, exit);
+                Self::run(&config, bank_forks, &blockstore, exit);
             })
             .unwrap();
 
@@ -44,6 +61,7 @@ impl SamplePerformanceService {
     }
 
     pub fn run(
+        config: &SamplePerformanceConfig,
         bank_forks: Arc<RwLock<BankForks>>,
         blockstore: &Arc<Blockstore>,
         exit: Arc<AtomicBool>,
@@ -66,7 +84,7 @@ impl SamplePerformanceService {
 
             let elapsed = now.elapsed();
 
-            if elapsed.as_secs() >= SAMPLE_INTERVAL {
+            if elapsed >= config.sample_interval {
                 now = Instant::now();
                 let bank_forks = bank_forks.read().unwrap();
                 let bank = bank_forks.root_bank().clone();
@@ -87,6 +105,9 @@ impl SamplePerformanceService {
                 if let Err(e) = blockstore.write_perf_sample(highest_slot, &perf_sample) {
                     error!("write_perf_sample failed: slot {:?} {:?}", highest_slot, e);
                 }
+                if let Err(e) = blockstore.purge_old_perf_samples(config.max_samples) {
+                    error!("purge_old_perf_samples failed: {:?}", e);
+                }
 
                 sample_snapshot = SamplePerformanceSnapshot {
                     num_transactions: bank.transaction_count(),
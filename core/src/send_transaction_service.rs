@@ -1,15 +1,24 @@
+//! The `send_transaction_service` sends the transactions submitted over RPC to the current and
+//! upcoming leaders, and keeps rebroadcasting them until they are rooted, fail or expire. The
+//! queue of pending transactions is persisted to disk, so that a restart of the node does not
+//! drop them.
+
 // TODO: Merge this implementation with the one at `banks-server/src/send_transaction_service.rs`
 use crate::cluster_info::ClusterInfo;
 use crate::poh_recorder::PohRecorder;
 use log::*;
-use solana_metrics::{datapoint_warn, inc_new_counter_info};
+use solana_ledger::transaction_trace::{self, TraceEvent};
+use solana_metrics::{datapoint_warn, inc_new_counter_info, registry};
 use solana_runtime::{bank::Bank, bank_forks::BankForks};
 use solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Signature};
 use std::sync::Mutex;
 use std::{
     collections::HashMap,
+    fs,
     net::{SocketAddr, UdpSocket},
+    path::PathBuf,
     sync::{
+        atomic::{AtomicBool, Ordering},
         mpsc::{Receiver, RecvTimeoutError},
         Arc, RwLock,
     },
@@ -20,10 +29,18 @@ use std::{
 /// Maximum size of the transaction queue
 const MAX_TRANSACTION_QUEUE_SIZE: usize = 10_000; // This seems like a lot but maybe it needs to be bigger one day
 
+/// Minimum interval between two writes of the transaction queue to disk
+const QUEUE_STORE_INTERVAL: Duration = Duration::from_secs(1);
+
+toml_config::package_config! {
+    SEND_TRANSACTION_NUM_LEADERS: usize,
+}
+
 pub struct SendTransactionService {
     thread: JoinHandle<()>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TransactionInfo {
     pub signature: Signature,
     pub wire_transaction: Vec<u8>,
@@ -64,12 +81,54 @@ impl LeaderInfo {
             .collect();
     }
 
-    pub fn get_leader_tpu(&self) -> Option<&SocketAddr> {
+    /// TPU addresses of the current leader and of the next `SEND_TRANSACTION_NUM_LEADERS - 1`
+    /// leaders, which are known
+    pub fn get_leader_tpus(&self) -> Vec<&SocketAddr> {
         self.poh_recorder
             .lock()
             .unwrap()
-            .leader_after_n_slots(0)
-            .and_then(|leader| self.recent_peers.get(&leader))
+            .upcoming_leaders(0, CFG.SEND_TRANSACTION_NUM_LEADERS.max(1))
+            .iter()
+            .filter_map(|leader| self.recent_peers.get(leader))
+            .collect()
+    }
+}
+
+/// File the queue of pending transactions is persisted to
+pub struct TransactionQueueFile {
+    path: PathBuf,
+}
+
+impl TransactionQueueFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn load(&self) -> Vec<TransactionInfo> {
+        let contents = match fs::read(&self.path) {
+            Ok(contents) => contents,
+            Err(_) => return vec![],
+        };
+        bincode::deserialize(&contents).unwrap_or_else(|err| {
+            warn!(
+                "Ignoring malformed transaction queue {:?}: {}",
+                self.path, err
+            );
+            vec![]
+        })
+    }
+
+    pub fn store(&self, transactions: &HashMap<Signature, TransactionInfo>) {
+        let transactions: Vec<_> = transactions.values().collect();
+        // Write to a temporary file first so that a crash never leaves a truncated queue
+        let tmp_path = self.path.with_extension("tmp");
+        let result = bincode::serialize(&transactions)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+            .and_then(|contents| fs::write(&tmp_path, contents))
+            .and_then(|()| fs::rename(&tmp_path, &self.path));
+        if let Err(err) = result {
+            warn!("Failed to store transaction queue {:?}: {}", self.path, err);
+        }
     }
 }
 
@@ -89,7 +148,36 @@ impl SendTransactionService {
         leader_info: Option<LeaderInfo>,
         receiver: Receiver<TransactionInfo>,
     ) -> Self {
-        let thread = Self::retry_thread(tpu_address, receiver, bank_forks.clone(), leader_info);
+        let thread = Self::retry_thread(
+            tpu_address,
+            receiver,
+            bank_forks.clone(),
+            leader_info,
+            None,
+            Arc::new(AtomicBool::new(false)),
+        );
+        Self { thread }
+    }
+
+    /// The pending transactions are persisted to `queue_file`, and the ones it holds are sent
+    /// again on startup. The queue is stored one last time when the service exits, once `exit`
+    /// is set or the sender is dropped
+    pub fn new_with_queue_file(
+        tpu_address: SocketAddr,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        leader_info: Option<LeaderInfo>,
+        receiver: Receiver<TransactionInfo>,
+        queue_file: TransactionQueueFile,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let thread = Self::retry_thread(
+            tpu_address,
+            receiver,
+            bank_forks.clone(),
+            leader_info,
+            Some(queue_file),
+            exit.clone(),
+        );
         Self { thread }
     }
 
@@ -98,9 +186,21 @@ impl SendTransactionService {
         receiver: Receiver<TransactionInfo>,
         bank_forks: Arc<RwLock<BankForks>>,
         mut leader_info: Option<LeaderInfo>,
+        queue_file: Option<TransactionQueueFile>,
+        exit: Arc<AtomicBool>,
     ) -> JoinHandle<()> {
         let mut last_status_check = Instant::now();
-        let mut transactions = HashMap::new();
+        let mut last_queue_store = Instant::now();
+        let mut queue_changed = false;
+        let mut transactions: HashMap<_, _> = queue_file
+            .iter()
+            .flat_map(TransactionQueueFile::load)
+            .map(|transaction_info| (transaction_info.signature, transaction_info))
+            .collect();
+        if !transactions.is_empty() {
+            info!("Loaded {} pending transactions", transactions.len());
+        }
+        let queue_depth = registry::namespace("send_transaction_service").gauge("queue_depth");
         let send_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
 
         if let Some(leader_info) = leader_info.as_mut() {
@@ -109,50 +209,75 @@ impl SendTransactionService {
 
         Builder::new()
             .name("send-tx-sv2".to_string())
-            .spawn(move || loop {
-                match receiver.recv_timeout(Duration::from_secs(1)) {
-                    Err(RecvTimeoutError::Disconnected) => break,
-                    Err(RecvTimeoutError::Timeout) => {}
-                    Ok(transaction_info) => {
-                        let address = leader_info
-                            .as_ref()
-                            .and_then(|leader_info| leader_info.get_leader_tpu())
-                            .unwrap_or(&tpu_address);
-                        Self::send_transaction(
-                            &send_socket,
-                            address,
-                            &transaction_info.wire_transaction,
-                        );
-                        if transactions.len() < MAX_TRANSACTION_QUEUE_SIZE {
-                            transactions.insert(transaction_info.signature, transaction_info);
-                        } else {
-                            datapoint_warn!("send_transaction_service-queue-overflow");
+            .spawn(move || {
+                loop {
+                    if exit.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    match receiver.recv_timeout(Duration::from_secs(1)) {
+                        Err(RecvTimeoutError::Disconnected) => break,
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Ok(transaction_info) => {
+                            Self::send_to_leaders(
+                                &send_socket,
+                                &tpu_address,
+                                &leader_info,
+                                &transaction_info.wire_transaction,
+                            );
+                            let tracer = transaction_trace::tracer();
+                            tracer.record(&transaction_info.signature, TraceEvent::Forwarded, None);
+                            if transactions.len() < MAX_TRANSACTION_QUEUE_SIZE {
+                                transactions.insert(transaction_info.signature, transaction_info);
+                                queue_changed = true;
+                            } else {
+                                datapoint_warn!("send_transaction_service-queue-overflow");
+                                tracer.record(
+                                    &transaction_info.signature,
+                                    TraceEvent::Dropped,
+                                    None,
+                                );
+                            }
                         }
                     }
-                }
 
-                if Instant::now().duration_since(last_status_check).as_secs() >= 5 {
-                    if !transactions.is_empty() {
-                        datapoint_info!(
-                            "send_transaction_service-queue-size",
-                            ("len", transactions.len(), i64)
-                        );
-                        let bank_forks = bank_forks.read().unwrap();
-                        let root_bank = bank_forks.root_bank();
-                        let working_bank = bank_forks.working_bank();
+                    if Instant::now().duration_since(last_status_check).as_secs() >= 5 {
+                        if !transactions.is_empty() {
+                            datapoint_info!(
+                                "send_transaction_service-queue-size",
+                                ("len", transactions.len(), i64)
+                            );
+                            let bank_forks = bank_forks.read().unwrap();
+                            let root_bank = bank_forks.root_bank();
+                            let working_bank = bank_forks.working_bank();
+
+                            let result = Self::process_transactions(
+                                &working_bank,
+                                &root_bank,
+                                &send_socket,
+                                &tpu_address,
+                                &mut transactions,
+                                &leader_info,
+                            );
+                            queue_changed |= result.rooted + result.expired + result.failed > 0;
+                        }
+                        last_status_check = Instant::now();
+                        if let Some(leader_info) = leader_info.as_mut() {
+                            leader_info.refresh_recent_peers();
+                        }
+                    }
 
-                        let _result = Self::process_transactions(
-                            &working_bank,
-                            &root_bank,
-                            &send_socket,
-                            &tpu_address,
-                            &mut transactions,
-                            &leader_info,
-                        );
+                    queue_depth.set(transactions.len() as i64);
+                    if queue_changed && last_queue_store.elapsed() >= QUEUE_STORE_INTERVAL {
+                        if let Some(queue_file) = &queue_file {
+                            queue_file.store(&transactions);
+                        }
+                        queue_changed = false;
+                        last_queue_store = Instant::now();
                     }
-                    last_status_check = Instant::now();
-                    if let Some(leader_info) = leader_info.as_mut() {
-                        leader_info.refresh_recent_peers();
+                }
+                if queue_changed {
+                    if let Some(queue_file) = &queue_file {
+                        queue_file.store(&transactions);
                     }
                 }
             })
@@ -168,17 +293,20 @@ impl SendTransactionService {
         leader_info: &Option<LeaderInfo>,
     ) -> ProcessTransactionsResult {
         let mut result = ProcessTransactionsResult::default();
+        let tracer = transaction_trace::tracer();
 
         transactions.retain(|signature, transaction_info| {
             if root_bank.has_signature(signature) {
                 info!("Transaction is rooted: {}", signature);
                 result.rooted += 1;
                 inc_new_counter_info!("send_transaction_service-rooted", 1);
+                tracer.record(signature, TraceEvent::Confirmed, Some(root_bank.slot()));
                 false
             } else if transaction_info.last_valid_slot < root_bank.slot() {
                 info!("Dropping expired transaction: {}", signature);
                 result.expired += 1;
                 inc_new_counter_info!("send_transaction_service-expired", 1);
+                tracer.record(signature, TraceEvent::Dropped, None);
                 false
             } else {
                 match working_bank.get_signature_status_slot(signature) {
@@ -188,21 +316,21 @@ impl SendTransactionService {
                         info!("Retrying transaction: {}", signature);
                         result.retried += 1;
                         inc_new_counter_info!("send_transaction_service-retry", 1);
-                        Self::send_transaction(
+                        Self::send_to_leaders(
                             &send_socket,
-                            leader_info
-                                .as_ref()
-                                .and_then(|leader_info| leader_info.get_leader_tpu())
-                                .unwrap_or(&tpu_address),
+                            &tpu_address,
+                            leader_info,
                             &transaction_info.wire_transaction,
                         );
+                        tracer.record(signature, TraceEvent::Forwarded, None);
                         true
                     }
-                    Some((_slot, status)) => {
+                    Some((slot, status)) => {
                         if status.is_err() {
                             info!("Dropping failed transaction: {}", signature);
                             result.failed += 1;
                             inc_new_counter_info!("send_transaction_service-failed", 1);
+                            tracer.record(signature, TraceEvent::Dropped, Some(slot));
                             false
                         } else {
                             result.retained += 1;
@@ -216,6 +344,26 @@ impl SendTransactionService {
         result
     }
 
+    /// Sends the transaction to the current and upcoming leaders, or to `tpu_address` if none
+    /// of them is known
+    fn send_to_leaders(
+        send_socket: &UdpSocket,
+        tpu_address: &SocketAddr,
+        leader_info: &Option<LeaderInfo>,
+        wire_transaction: &[u8],
+    ) {
+        let leader_tpus = leader_info
+            .as_ref()
+            .map(LeaderInfo::get_leader_tpus)
+            .unwrap_or_default();
+        if leader_tpus.is_empty() {
+            Self::send_transaction(send_socket, tpu_address, wire_transaction);
+        }
+        for leader_tpu in leader_tpus {
+            Self::send_transaction(send_socket, leader_tpu, wire_transaction);
+        }
+    }
+
     fn send_transaction(
         send_socket: &UdpSocket,
         tpu_address: &SocketAddr,
@@ -254,6 +402,59 @@ mod test {
         send_tranaction_service.join().unwrap();
     }
 
+    #[test]
+    fn service_exit_stores_queue() {
+        let tpu_address = "127.0.0.1:0".parse().unwrap();
+        let bank = Bank::default();
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(bank)));
+        let (sender, receiver) = channel();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let queue_path = temp_dir.path().join("queue");
+        let exit = Arc::new(AtomicBool::new(false));
+
+        let send_tranaction_service = SendTransactionService::new_with_queue_file(
+            tpu_address,
+            &bank_forks,
+            None,
+            receiver,
+            TransactionQueueFile::new(queue_path.clone()),
+            &exit,
+        );
+        let signature = Signature::new(&[1u8; 64]);
+        sender
+            .send(TransactionInfo::new(signature, vec![1, 2, 3], 42))
+            .unwrap();
+        // The queue is stored on exit, without waiting for the store interval
+        drop(sender);
+        send_tranaction_service.join().unwrap();
+        let loaded = TransactionQueueFile::new(queue_path).load();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].signature, signature);
+    }
+
+    #[test]
+    fn transaction_queue_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let queue_file = TransactionQueueFile::new(temp_dir.path().join("queue"));
+        assert!(queue_file.load().is_empty());
+
+        let signature = Signature::new(&[1u8; 64]);
+        let mut transactions = HashMap::new();
+        transactions.insert(
+            signature,
+            TransactionInfo::new(signature, vec![1, 2, 3], 42),
+        );
+        queue_file.store(&transactions);
+        let loaded = queue_file.load();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].signature, signature);
+        assert_eq!(loaded[0].wire_transaction, vec![1, 2, 3]);
+        assert_eq!(loaded[0].last_valid_slot, 42);
+
+        fs::write(temp_dir.path().join("queue"), b"garbage").unwrap();
+        assert!(queue_file.load().is_empty());
+    }
+
     #[test]
     fn process_transactions() {
         solana_logger::setup();
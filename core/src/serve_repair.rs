@@ -2,6 +2,7 @@ use crate::{
     cluster_info::{ClusterInfo, ClusterInfoError},
     cluster_slots::ClusterSlots,
     contact_info::ContactInfo,
+    crds_value::CAPABILITY_REPAIR_SHRED_RANGE,
     repair_response,
     repair_service::RepairStats,
     result::{Error, Result},
@@ -12,9 +13,12 @@ use solana_ledger::{blockstore::Blockstore, shred::Nonce};
 use solana_measure::measure::Measure;
 use solana_measure::thread_mem_usage;
 use solana_metrics::{datapoint_debug, inc_new_counter_debug};
-use solana_perf::packet::{limited_deserialize, Packets, PacketsRecycler};
+use solana_perf::packet::{
+    limited_deserialize, Packet, Packets, PacketsRecycler, PACKET_DATA_SIZE,
+};
 use solana_sdk::{
     clock::Slot,
+    hash::Hash,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
     timing::duration_as_ms,
@@ -31,6 +35,15 @@ use std::{
 
 /// the number of slots to respond with when responding to `Orphan` requests
 pub const MAX_ORPHAN_REPAIR_RESPONSES: usize = 10;
+/// the maximum number of bytes to respond with when responding to `WindowIndexRange` requests
+pub const MAX_SHRED_RANGE_REPAIR_RESPONSE_BYTES: usize = 16 * PACKET_DATA_SIZE;
+/// the maximum number of shreds spanned by a `ShredRange` repair, each shred taking a full packet
+/// in the response
+pub const MAX_SHRED_RANGE_REPAIR_RESPONSES: usize =
+    MAX_SHRED_RANGE_REPAIR_RESPONSE_BYTES / PACKET_DATA_SIZE;
+/// the number of ancestors to respond with when responding to `AncestorHashes` requests, so that
+/// the response fits in a single packet
+pub const MAX_ANCESTOR_HASHES_RESPONSES: usize = 24;
 pub const DEFAULT_NONCE: u32 = 42;
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash, PartialEq, Eq)]
@@ -38,6 +51,8 @@ pub enum RepairType {
     Orphan(Slot),
     HighestShred(Slot, u64),
     Shred(Slot, u64),
+    /// Data shreds of the slot in `[from_index, to_index)`
+    ShredRange(Slot, u64, u64),
 }
 
 impl RepairType {
@@ -46,6 +61,7 @@ impl RepairType {
             RepairType::Orphan(slot) => *slot,
             RepairType::HighestShred(slot, _) => *slot,
             RepairType::Shred(slot, _) => *slot,
+            RepairType::ShredRange(slot, _, _) => *slot,
         }
     }
 }
@@ -59,6 +75,9 @@ pub struct ServeRepairStats {
     pub window_index: usize,
     pub highest_window_index: usize,
     pub orphan: usize,
+    pub window_index_range: usize,
+    pub ancestor_hashes: usize,
+    pub leecher_dropped: usize,
 }
 
 /// Window protocol messages
@@ -70,6 +89,16 @@ pub enum RepairProtocol {
     WindowIndexWithNonce(ContactInfo, u64, u64, Nonce),
     HighestWindowIndexWithNonce(ContactInfo, u64, u64, Nonce),
     OrphanWithNonce(ContactInfo, u64, Nonce),
+    WindowIndexRangeWithNonce(ContactInfo, u64, u64, u64, Nonce),
+    AncestorHashesWithNonce(ContactInfo, u64, Nonce),
+}
+
+/// Response to `AncestorHashes` requests: the hash of the last entry of the requested slot and
+/// of its ancestors, from the highest slot down, skipping slots which are not full
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct AncestorHashesResponse {
+    pub nonce: Nonce,
+    pub hashes: Vec<(Slot, Hash)>,
 }
 
 #[derive(Clone)]
@@ -115,9 +144,12 @@ impl ServeRepair {
             RepairProtocol::WindowIndexWithNonce(ref from, _, _, _) => from,
             RepairProtocol::HighestWindowIndexWithNonce(ref from, _, _, _) => from,
             RepairProtocol::OrphanWithNonce(ref from, _, _) => from,
+            RepairProtocol::WindowIndexRangeWithNonce(ref from, _, _, _, _) => from,
+            RepairProtocol::AncestorHashesWithNonce(ref from, _, _) => from,
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handle_repair(
         me: &Arc<RwLock<Self>>,
         recycler: &PacketsRecycler,
@@ -125,6 +157,8 @@ impl ServeRepair {
         blockstore: Option<&Arc<Blockstore>>,
         request: RepairProtocol,
         stats: &mut ServeRepairStats,
+        overloaded: bool,
+        bytes_served: &mut HashMap<Pubkey, u64>,
     ) -> Option<Packets> {
         let now = Instant::now();
 
@@ -135,6 +169,17 @@ impl ServeRepair {
             stats.self_repair += 1;
             return None;
         }
+        if overloaded {
+            // Under load, stop serving peers which take without giving back
+            let me = me.read().unwrap();
+            let peer_bandwidth = me.cluster_info.peer_bandwidth();
+            if peer_bandwidth.prioritize_reciprocating_peers()
+                && peer_bandwidth.is_leecher(&from.id)
+            {
+                stats.leecher_dropped += 1;
+                return None;
+            }
+        }
 
         let (res, label) = {
             match &request {
@@ -182,10 +227,45 @@ impl ServeRepair {
                         "OrphanWithNonce",
                     )
                 }
+                RepairProtocol::WindowIndexRangeWithNonce(_, slot, from_index, to_index, nonce) => {
+                    stats.window_index_range += 1;
+                    (
+                        Self::run_window_range_request(
+                            recycler,
+                            &from_addr,
+                            blockstore,
+                            *slot,
+                            *from_index,
+                            *to_index,
+                            MAX_SHRED_RANGE_REPAIR_RESPONSE_BYTES,
+                            *nonce,
+                        ),
+                        "WindowIndexRangeWithNonce",
+                    )
+                }
+                RepairProtocol::AncestorHashesWithNonce(_, slot, nonce) => {
+                    stats.ancestor_hashes += 1;
+                    (
+                        Self::run_ancestor_hashes(
+                            recycler,
+                            &from_addr,
+                            blockstore,
+                            *slot,
+                            MAX_ANCESTOR_HASHES_RESPONSES,
+                            *nonce,
+                        ),
+                        "AncestorHashesWithNonce",
+                    )
+                }
                 _ => (None, "Unsupported repair type"),
             }
         };
 
+        if let Some(packets) = &res {
+            let bytes: usize = packets.packets.iter().map(|p| p.meta.size).sum();
+            *bytes_served.entry(from.id).or_default() += bytes as u64;
+        }
+
         trace!("{}: received repair request: {:?}", my_id, request);
         Self::report_time_spent(label, &now.elapsed(), "");
         res
@@ -226,11 +306,28 @@ impl ServeRepair {
 
         stats.dropped_packets += dropped_packets;
         stats.total_packets += total_packets;
+        let overloaded = dropped_packets > 0;
 
         let mut time = Measure::start("repair::handle_packets");
+        // The bytes served are recorded once for the whole batch of requests
+        let mut bytes_served = HashMap::new();
         for reqs in reqs_v {
-            Self::handle_packets(obj, &recycler, blockstore, reqs, response_sender, stats);
+            Self::handle_packets(
+                obj,
+                &recycler,
+                blockstore,
+                reqs,
+                response_sender,
+                stats,
+                overloaded,
+                &mut bytes_served,
+            );
         }
+        obj.read()
+            .unwrap()
+            .cluster_info
+            .peer_bandwidth()
+            .record_served(bytes_served);
         time.stop();
         if total_packets >= *max_packets {
             if time.as_ms() > 1000 {
@@ -266,6 +363,15 @@ impl ServeRepair {
             stats.highest_window_index
         );
         inc_new_counter_debug!("serve_repair-request-orphan", stats.orphan);
+        inc_new_counter_debug!(
+            "serve_repair-request-window-index-range",
+            stats.window_index_range
+        );
+        inc_new_counter_debug!(
+            "serve_repair-request-ancestor-hashes",
+            stats.ancestor_hashes
+        );
+        inc_new_counter_info!("serve_repair-leecher-dropped", stats.leecher_dropped);
 
         *stats = ServeRepairStats::default();
     }
@@ -312,6 +418,7 @@ impl ServeRepair {
             .unwrap()
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn handle_packets(
         me: &Arc<RwLock<Self>>,
         recycler: &PacketsRecycler,
@@ -319,6 +426,8 @@ impl ServeRepair {
         packets: Packets,
         response_sender: &PacketSender,
         stats: &mut ServeRepairStats,
+        overloaded: bool,
+        bytes_served: &mut HashMap<Pubkey, u64>,
     ) {
         // iter over the packets
         let allocated = thread_mem_usage::Allocatedp::default();
@@ -329,8 +438,16 @@ impl ServeRepair {
                 .into_iter()
                 .for_each(|request| {
                     stats.processed += 1;
-                    let rsp =
-                        Self::handle_repair(me, recycler, &from_addr, blockstore, request, stats);
+                    let rsp = Self::handle_repair(
+                        me,
+                        recycler,
+                        &from_addr,
+                        blockstore,
+                        request,
+                        stats,
+                        overloaded,
+                        bytes_served,
+                    );
                     if let Some(rsp) = rsp {
                         let _ignore_disconnect = response_sender.send(rsp);
                     }
@@ -370,6 +487,30 @@ impl ServeRepair {
         Ok(out)
     }
 
+    fn window_index_range_request_bytes(
+        &self,
+        slot: Slot,
+        from_index: u64,
+        to_index: u64,
+        nonce: Nonce,
+    ) -> Result<Vec<u8>> {
+        let req = RepairProtocol::WindowIndexRangeWithNonce(
+            self.my_info.clone(),
+            slot,
+            from_index,
+            to_index,
+            nonce,
+        );
+        let out = serialize(&req)?;
+        Ok(out)
+    }
+
+    pub fn ancestor_hashes_request_bytes(&self, slot: Slot, nonce: Nonce) -> Result<Vec<u8>> {
+        let req = RepairProtocol::AncestorHashesWithNonce(self.my_info.clone(), slot, nonce);
+        let out = serialize(&req)?;
+        Ok(out)
+    }
+
     fn orphan_bytes(&self, slot: Slot, nonce: Nonce) -> Result<Vec<u8>> {
         let req = RepairProtocol::OrphanWithNonce(self.my_info.clone(), slot, nonce);
         let out = serialize(&req)?;
@@ -383,7 +524,7 @@ impl ServeRepair {
         cache: &mut RepairCache,
         repair_stats: &mut RepairStats,
         repair_validators: &Option<HashSet<Pubkey>>,
-    ) -> Result<(SocketAddr, Vec<u8>)> {
+    ) -> Result<(SocketAddr, Vec<Vec<u8>>)> {
         // find a peer that appears to be accepting replication and has the desired slot, as indicated
         // by a valid tvu port location
         let slot = repair_request.slot();
@@ -392,22 +533,49 @@ impl ServeRepair {
             if repair_peers.is_empty() {
                 return Err(ClusterInfoError::NoPeers.into());
             }
-            let weights = cluster_slots.compute_weights(slot, &repair_peers);
+            let mut weights = cluster_slots.compute_weights(slot, &repair_peers);
+            self.cluster_info
+                .prefer_low_latency_peers(&repair_peers, &mut weights);
             cache.insert(slot, (repair_peers, weights));
         }
         let (repair_peers, weights) = cache.get(&slot).unwrap();
         let n = weighted_best(&weights, solana_sdk::pubkey::new_rand().to_bytes());
         let addr = repair_peers[n].serve_repair; // send the request to the peer's serve_repair port
         let repair_peer_id = repair_peers[n].id;
-        let out = self.map_repair_request(
-            &repair_request,
-            &repair_peer_id,
-            repair_stats,
-            DEFAULT_NONCE,
-        )?;
+        let serves_shred_range = matches!(repair_request, RepairType::ShredRange(..))
+            && self
+                .cluster_info
+                .node_has_capability(&repair_peer_id, CAPABILITY_REPAIR_SHRED_RANGE);
+        let out = Self::split_repair_request(repair_request, serves_shred_range)
+            .iter()
+            .map(|repair_request| {
+                self.map_repair_request(
+                    repair_request,
+                    &repair_peer_id,
+                    repair_stats,
+                    DEFAULT_NONCE,
+                )
+            })
+            .collect::<Result<_>>()?;
         Ok((addr, out))
     }
 
+    // Peers not advertising `CAPABILITY_REPAIR_SHRED_RANGE` would drop a `ShredRange` request, so
+    // they are sent a `Shred` request per index instead
+    fn split_repair_request(
+        repair_request: RepairType,
+        serves_shred_range: bool,
+    ) -> Vec<RepairType> {
+        match repair_request {
+            RepairType::ShredRange(slot, from_index, to_index) if !serves_shred_range => {
+                (from_index..to_index)
+                    .map(|shred_index| RepairType::Shred(slot, shred_index))
+                    .collect()
+            }
+            _ => vec![repair_request],
+        }
+    }
+
     pub fn repair_request_duplicate_compute_best_peer(
         &self,
         slot: Slot,
@@ -418,7 +586,9 @@ impl ServeRepair {
         if repair_peers.is_empty() {
             return Err(ClusterInfoError::NoPeers.into());
         }
-        let weights = cluster_slots.compute_weights_exclude_noncomplete(slot, &repair_peers);
+        let mut weights = cluster_slots.compute_weights_exclude_noncomplete(slot, &repair_peers);
+        self.cluster_info
+            .prefer_low_latency_peers(&repair_peers, &mut weights);
         let n = weighted_best(&weights, solana_sdk::pubkey::new_rand().to_bytes());
         Ok((repair_peers[n].id, repair_peers[n].serve_repair))
     }
@@ -447,6 +617,12 @@ impl ServeRepair {
                 repair_stats.orphan.update(repair_peer_id, *slot, 0);
                 Ok(self.orphan_bytes(*slot, nonce)?)
             }
+            RepairType::ShredRange(slot, from_index, to_index) => {
+                repair_stats
+                    .shred_range
+                    .update(repair_peer_id, *slot, to_index.saturating_sub(1));
+                Ok(self.window_index_range_request_bytes(*slot, *from_index, *to_index, nonce)?)
+            }
         }
     }
 
@@ -542,6 +718,109 @@ impl ServeRepair {
         None
     }
 
+    fn run_window_range_request(
+        recycler: &PacketsRecycler,
+        from_addr: &SocketAddr,
+        blockstore: Option<&Arc<Blockstore>>,
+        slot: Slot,
+        from_index: u64,
+        to_index: u64,
+        max_response_bytes: usize,
+        nonce: Nonce,
+    ) -> Option<Packets> {
+        let blockstore = blockstore?;
+        let max_responses = max_response_bytes / PACKET_DATA_SIZE;
+        let to_index = to_index.min(from_index.saturating_add(max_responses as u64));
+        let mut response_bytes = 0;
+        let packets: Vec<_> = (from_index..to_index)
+            .filter_map(|shred_index| {
+                repair_response::repair_response_packet(
+                    blockstore,
+                    slot,
+                    shred_index,
+                    from_addr,
+                    nonce,
+                )
+            })
+            .take_while(|packet| {
+                response_bytes += packet.meta.size;
+                response_bytes <= max_response_bytes
+            })
+            .collect();
+        if packets.is_empty() {
+            inc_new_counter_debug!("serve_repair-window-range-request-fail", 1);
+            return None;
+        }
+        inc_new_counter_debug!("serve_repair-window-range-request-ledger", packets.len());
+        Some(Packets::new_with_recycler_data(
+            recycler,
+            "run_window_range_request",
+            packets,
+        ))
+    }
+
+    /// Returns the hash of the last entry of `slot` and of up to `max_ancestors - 1` of its
+    /// ancestors, see `AncestorHashesResponse`
+    pub fn get_ancestor_hashes(
+        blockstore: &Blockstore,
+        mut slot: Slot,
+        max_ancestors: usize,
+    ) -> Vec<(Slot, Hash)> {
+        let mut hashes = vec![];
+        while hashes.len() < max_ancestors {
+            let meta = match blockstore.meta(slot) {
+                Ok(Some(meta)) => meta,
+                _ => break,
+            };
+            if meta.is_full() {
+                let last_index = meta.completed_data_indexes.last().copied();
+                let start_index = meta
+                    .completed_data_indexes
+                    .iter()
+                    .rev()
+                    .nth(1)
+                    .map(|index| index + 1)
+                    .unwrap_or_default();
+                let hash = last_index.and_then(|last_index| {
+                    blockstore
+                        .get_entries_in_data_block(slot, start_index, last_index, None)
+                        .ok()?
+                        .last()
+                        .map(|entry| entry.hash)
+                });
+                match hash {
+                    Some(hash) => hashes.push((slot, hash)),
+                    None => break,
+                }
+            }
+            if !meta.is_parent_set() || meta.parent_slot >= slot {
+                break;
+            }
+            slot = meta.parent_slot;
+        }
+        hashes
+    }
+
+    fn run_ancestor_hashes(
+        recycler: &PacketsRecycler,
+        from_addr: &SocketAddr,
+        blockstore: Option<&Arc<Blockstore>>,
+        slot: Slot,
+        max_responses: usize,
+        nonce: Nonce,
+    ) -> Option<Packets> {
+        let hashes = Self::get_ancestor_hashes(blockstore?, slot, max_responses);
+        if hashes.is_empty() {
+            return None;
+        }
+        let packet = Packet::from_data(from_addr, AncestorHashesResponse { nonce, hashes });
+        Some(Packets::new_with_recycler_data(
+            recycler,
+            "run_ancestor_hashes",
+            vec![packet],
+        ))
+    }
+
     fn run_orphan(
         recycler: &PacketsRecycler,
         from_addr: &SocketAddr,
@@ -788,6 +1067,19 @@ mod tests {
             .unwrap();
         assert_eq!(nxt.serve_repair, serve_repair_addr);
         assert_eq!(rv.0, nxt.serve_repair);
+        assert_eq!(rv.1.len(), 1);
+
+        // The peer does not advertise serving ranges, so it is sent a request per shred
+        let rv = serve_repair
+            .repair_request(
+                &cluster_slots,
+                RepairType::ShredRange(0, 2, 6),
+                &mut HashMap::new(),
+                &mut RepairStats::default(),
+                &None,
+            )
+            .unwrap();
+        assert_eq!(rv.1.len(), 4);
 
         let serve_repair_addr2 = socketaddr!([127, 0, 0, 2], 1243);
         let nxt = ContactInfo {
@@ -829,6 +1121,162 @@ mod tests {
         assert!(one && two);
     }
 
+    #[test]
+    fn test_split_repair_request() {
+        let range = RepairType::ShredRange(3, 5, 8);
+        assert_eq!(ServeRepair::split_repair_request(range, true), vec![range]);
+        assert_eq!(
+            ServeRepair::split_repair_request(range, false),
+            vec![
+                RepairType::Shred(3, 5),
+                RepairType::Shred(3, 6),
+                RepairType::Shred(3, 7)
+            ]
+        );
+        let shred = RepairType::Shred(3, 5);
+        assert_eq!(ServeRepair::split_repair_request(shred, false), vec![shred]);
+    }
+
+    #[test]
+    fn test_run_window_range_request() {
+        solana_logger::setup();
+        let recycler = PacketsRecycler::default();
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Arc::new(Blockstore::open(&ledger_path).unwrap());
+            let slot = 2;
+            let nonce = 9;
+            let run_window_range_request = |from_index, to_index, max_responses| {
+                ServeRepair::run_window_range_request(
+                    &recycler,
+                    &socketaddr_any!(),
+                    Some(&blockstore),
+                    slot,
+                    from_index,
+                    to_index,
+                    max_responses,
+                    nonce,
+                )
+                .map(|packets| {
+                    packets
+                        .packets
+                        .into_iter()
+                        .map(|packet| {
+                            assert_eq!(repair_response::nonce(&packet.data[..]).unwrap(), nonce);
+                            let shred =
+                                Shred::new_from_serialized_shred(packet.data.to_vec()).unwrap();
+                            assert_eq!(shred.slot(), slot);
+                            shred.index()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            };
+            assert_eq!(
+                run_window_range_request(0, 8, MAX_SHRED_RANGE_REPAIR_RESPONSE_BYTES),
+                None
+            );
+
+            let (mut shreds, _) = make_many_slot_entries(slot, 1, 200);
+            assert!(shreds.len() >= 8);
+            // Leave a hole at indexes 3 and 4
+            shreds.retain(|shred| shred.index() != 3 && shred.index() != 4);
+            blockstore
+                .insert_shreds(shreds, None, false)
+                .expect("Expect successful ledger write");
+
+            assert_eq!(
+                run_window_range_request(1, 8, MAX_SHRED_RANGE_REPAIR_RESPONSE_BYTES),
+                Some(vec![1, 2, 5, 6, 7])
+            );
+            // The response is capped to the shreds fitting in the byte budget
+            assert_eq!(
+                run_window_range_request(0, 100, 3 * PACKET_DATA_SIZE),
+                Some(vec![0, 1, 2])
+            );
+            assert_eq!(
+                run_window_range_request(0, 100, 3 * PACKET_DATA_SIZE - 1),
+                Some(vec![0, 1])
+            );
+            assert_eq!(
+                run_window_range_request(3, 5, MAX_SHRED_RANGE_REPAIR_RESPONSE_BYTES),
+                None
+            );
+            assert_eq!(
+                run_window_range_request(5, 5, MAX_SHRED_RANGE_REPAIR_RESPONSE_BYTES),
+                None
+            );
+        }
+        Blockstore::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_run_ancestor_hashes() {
+        solana_logger::setup();
+        let recycler = PacketsRecycler::default();
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Arc::new(Blockstore::open(&ledger_path).unwrap());
+            let nonce = 9;
+            let rv = ServeRepair::run_ancestor_hashes(
+                &recycler,
+                &socketaddr_any!(),
+                Some(&blockstore),
+                3,
+                MAX_ANCESTOR_HASHES_RESPONSES,
+                nonce,
+            );
+            assert!(rv.is_none());
+
+            // Create slots 1, 2 and 3 chained to each other, and leave slot 2 incomplete
+            let entries_per_slot = 200;
+            let (mut shreds, entries) = make_many_slot_entries(1, 3, entries_per_slot);
+            let slot_hash = |slot: Slot| {
+                let last_entry = slot * entries_per_slot - 1;
+                (slot, entries[last_entry as usize].hash)
+            };
+            shreds.retain(|shred| shred.slot() != 2 || !shred.last_in_slot());
+            assert!(shreds.iter().any(|shred| shred.slot() == 2));
+            blockstore
+                .insert_shreds(shreds, None, false)
+                .expect("Expect successful ledger write");
+
+            assert_eq!(
+                ServeRepair::get_ancestor_hashes(&blockstore, 3, MAX_ANCESTOR_HASHES_RESPONSES),
+                vec![slot_hash(3), slot_hash(1)]
+            );
+            assert_eq!(
+                ServeRepair::get_ancestor_hashes(&blockstore, 3, 1),
+                vec![slot_hash(3)]
+            );
+            assert_eq!(
+                ServeRepair::get_ancestor_hashes(&blockstore, 2, MAX_ANCESTOR_HASHES_RESPONSES),
+                vec![slot_hash(1)]
+            );
+
+            let rv = ServeRepair::run_ancestor_hashes(
+                &recycler,
+                &socketaddr_any!(),
+                Some(&blockstore),
+                3,
+                MAX_ANCESTOR_HASHES_RESPONSES,
+                nonce,
+            )
+            .expect("packets");
+            assert_eq!(rv.packets.len(), 1);
+            let packet = &rv.packets[0];
+            let response: AncestorHashesResponse =
+                limited_deserialize(&packet.data[..packet.meta.size]).unwrap();
+            assert_eq!(
+                response,
+                AncestorHashesResponse {
+                    nonce,
+                    hashes: vec![slot_hash(3), slot_hash(1)],
+                }
+            );
+        }
+        Blockstore::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_run_orphan() {
         run_orphan(2, 3, 9);
@@ -1,5 +1,6 @@
 //! The `shred_fetch_stage` pulls shreds from UDP sockets and sends it to a channel.
 
+use crate::link_conditioner::{conditioned_sender, LinkSocket, NodeLinkConditioner};
 use bv::BitVec;
 use solana_ledger::blockstore::CFG as BLOCKSTORE_CFG;
 use solana_ledger::shred::{
@@ -215,15 +216,18 @@ impl ShredFetchStage {
         repair_socket: Arc<UdpSocket>,
         sender: &PacketSender,
         bank_forks: Option<Arc<RwLock<BankForks>>>,
+        link_conditioner: Option<&NodeLinkConditioner>,
         exit: &Arc<AtomicBool>,
     ) -> Self {
         let recycler: PacketsRecycler = Recycler::warmed(100, 1024);
 
+        let (tvu_sender, tvu_conditioner_hdl) =
+            conditioned_sender(link_conditioner, LinkSocket::Tvu, sender.clone());
         let tvu_threads = sockets.into_iter().map(|socket| {
             streamer::receiver(
                 socket,
                 &exit,
-                sender.clone(),
+                tvu_sender.clone(),
                 recycler.clone(),
                 "shred_fetch_stage",
             )
@@ -232,7 +236,7 @@ impl ShredFetchStage {
         let (tvu_forwards_threads, fwd_thread_hdl) = Self::packet_modifier(
             forward_sockets,
             &exit,
-            sender.clone(),
+            tvu_sender.clone(),
             recycler.clone(),
             bank_forks.clone(),
             "shred_fetch_tvu_forwards",
@@ -255,6 +259,7 @@ impl ShredFetchStage {
         thread_hdls.extend(repair_receiver.into_iter());
         thread_hdls.push(fwd_thread_hdl);
         thread_hdls.push(repair_handler);
+        thread_hdls.extend(tvu_conditioner_hdl);
 
         Self { thread_hdls }
     }
@@ -5,34 +5,198 @@
 //!
 
 use crate::sigverify_stage::SigVerifier;
+use solana_metrics::registry::{self, Counter};
 use solana_perf::cuda_runtime::PinnedVec;
-use solana_perf::packet::Packets;
+use solana_perf::packet::{limited_deserialize, Packet, Packets};
+use solana_perf::perf_libs;
 use solana_perf::recycler::Recycler;
 use solana_perf::sigverify;
 pub use solana_perf::sigverify::{
     batch_size, ed25519_verify_cpu, ed25519_verify_disabled, init, TxOffset,
 };
+use solana_runtime::bloom::Bloom;
+use solana_sdk::hash::{hash, Hash};
+use solana_sdk::transaction::Transaction;
+use std::sync::{Arc, Mutex};
+
+toml_config::package_config! {
+    SIGVERIFY_DEDUP_CAPACITY: usize,
+    SIGVERIFY_DEDUP_FALSE_RATE: f64,
+    SIGVERIFY_GPU_THRESHOLD: usize,
+}
+
+/// Remembers the recent transactions to drop the repeated submissions before they are verified
+/// again. Transactions are told apart by the hash of the whole packet rather than by their
+/// signature alone, which anyone seeing the transaction could otherwise send first along with a
+/// corrupted message to have the genuine transaction dropped.
+///
+/// Two generations of filters are kept, the newer one sized for `capacity` transactions with
+/// the given false positive rate. Once full, it becomes the older one, so that transactions are
+/// remembered for at least `capacity` transactions.
+pub struct PacketDeduper {
+    capacity: usize,
+    false_rate: f64,
+    filters: Mutex<DedupFilters>,
+}
+
+struct DedupFilters {
+    current: Bloom<Hash>,
+    previous: Bloom<Hash>,
+    num_current: usize,
+}
+
+impl Default for PacketDeduper {
+    fn default() -> Self {
+        Self::new(CFG.SIGVERIFY_DEDUP_CAPACITY, CFG.SIGVERIFY_DEDUP_FALSE_RATE)
+    }
+}
+
+impl PacketDeduper {
+    pub fn new(capacity: usize, false_rate: f64) -> Self {
+        let filters = DedupFilters {
+            current: Self::new_filter(capacity, false_rate),
+            previous: Self::new_filter(capacity, false_rate),
+            num_current: 0,
+        };
+        Self {
+            capacity,
+            false_rate,
+            filters: Mutex::new(filters),
+        }
+    }
+
+    fn new_filter(capacity: usize, false_rate: f64) -> Bloom<Hash> {
+        Bloom::random(capacity, false_rate, std::usize::MAX)
+    }
+
+    /// Drops the packets already seen, and returns how many were dropped
+    pub fn dedup_packets(&self, batches: &mut Vec<Packets>) -> usize {
+        let mut filters = self.filters.lock().unwrap();
+        let mut num_dropped = 0;
+        for packets in batches.iter_mut() {
+            let keep: Vec<bool> = packets
+                .packets
+                .iter()
+                .map(|packet| !self.check_and_add(&mut filters, packet))
+                .collect();
+            let num_kept = keep.iter().filter(|keep| **keep).count();
+            if num_kept < keep.len() {
+                num_dropped += keep.len() - num_kept;
+                *packets = Packets::new(
+                    packets
+                        .packets
+                        .iter()
+                        .zip(keep)
+                        .filter(|(_, keep)| *keep)
+                        .map(|(packet, _)| packet.clone())
+                        .collect(),
+                );
+            }
+        }
+        batches.retain(|packets| !packets.packets.is_empty());
+        num_dropped
+    }
+
+    /// Whether the packet was seen before; remembers it otherwise
+    fn check_and_add(&self, filters: &mut DedupFilters, packet: &Packet) -> bool {
+        let key = hash(&packet.data[..packet.meta.size]);
+        if filters.current.contains(&key) || filters.previous.contains(&key) {
+            return true;
+        }
+        if filters.num_current >= self.capacity {
+            let current = Self::new_filter(self.capacity, self.false_rate);
+            filters.previous = std::mem::replace(&mut filters.current, current);
+            filters.num_current = 0;
+        }
+        filters.current.add(&key);
+        filters.num_current += 1;
+        false
+    }
+}
 
 #[derive(Clone)]
 pub struct TransactionSigVerifier {
     recycler: Recycler<TxOffset>,
     recycler_out: Recycler<PinnedVec<u8>>,
+    deduper: Option<Arc<PacketDeduper>>,
+    votes_only: bool,
+    gpu_threshold: usize,
+    packets: Arc<Counter>,
+    failed_packets: Arc<Counter>,
+    duplicate_packets: Arc<Counter>,
+    non_vote_packets: Arc<Counter>,
+    gpu_batches: Arc<Counter>,
+    cpu_batches: Arc<Counter>,
 }
 
 impl Default for TransactionSigVerifier {
     fn default() -> Self {
+        Self::new(CFG.SIGVERIFY_DEDUP_CAPACITY > 0)
+    }
+}
+
+impl TransactionSigVerifier {
+    /// With `dedup`, repeated submissions are dropped, see `PacketDeduper`
+    pub fn new(dedup: bool) -> Self {
+        Self::new_with_namespace(dedup, false, "tpu_sigverify")
+    }
+
+    /// Verifier for the packets received on the tpu_vote port, which drops anything other than
+    /// vote transactions before verifying, so that the port can't be used to skip the queue
+    pub fn new_vote_verifier() -> Self {
+        Self::new_with_namespace(CFG.SIGVERIFY_DEDUP_CAPACITY > 0, true, "tpu_vote_sigverify")
+    }
+
+    fn new_with_namespace(dedup: bool, votes_only: bool, namespace: &str) -> Self {
         init();
+        let namespace = registry::namespace(namespace);
         Self {
             recycler: Recycler::warmed(50, 4096),
             recycler_out: Recycler::warmed(50, 4096),
+            deduper: if dedup {
+                Some(Arc::new(PacketDeduper::default()))
+            } else {
+                None
+            },
+            votes_only,
+            gpu_threshold: CFG.SIGVERIFY_GPU_THRESHOLD,
+            packets: namespace.counter("packets"),
+            failed_packets: namespace.counter("failed_packets"),
+            duplicate_packets: namespace.counter("duplicate_packets"),
+            non_vote_packets: namespace.counter("non_vote_packets"),
+            gpu_batches: namespace.counter("gpu_batches"),
+            cpu_batches: namespace.counter("cpu_batches"),
         }
     }
 }
 
 impl SigVerifier for TransactionSigVerifier {
     fn verify_batch(&self, mut batch: Vec<Packets>) -> Vec<Packets> {
-        let r = sigverify::ed25519_verify(&batch, &self.recycler, &self.recycler_out);
+        // Repeated submissions are dropped before spending any time verifying them
+        if let Some(deduper) = &self.deduper {
+            let num_duplicates = deduper.dedup_packets(&mut batch);
+            self.duplicate_packets.add(num_duplicates as u64);
+        }
+        if self.votes_only {
+            let num_non_votes = retain_votes(&mut batch);
+            self.non_vote_packets.add(num_non_votes as u64);
+        }
+        if perf_libs::api().is_some() && batch_size(&batch) >= self.gpu_threshold {
+            self.gpu_batches.inc();
+        } else {
+            self.cpu_batches.inc();
+        }
+        let r = sigverify::ed25519_verify_with_gpu_threshold(
+            &batch,
+            &self.recycler,
+            &self.recycler_out,
+            self.gpu_threshold,
+        );
         mark_disabled(&mut batch, &r);
+        let total: usize = r.iter().map(Vec::len).sum();
+        let failed = r.iter().flatten().filter(|v| **v == 0).count();
+        self.packets.add(total as u64);
+        self.failed_packets.add(failed as u64);
         batch
     }
 }
@@ -46,10 +210,46 @@ pub fn mark_disabled(batches: &mut Vec<Packets>, r: &[Vec<u8>]) {
     });
 }
 
+/// Drops the packets which aren't vote transactions, and returns how many were dropped
+pub fn retain_votes(batches: &mut Vec<Packets>) -> usize {
+    let mut num_dropped = 0;
+    for packets in batches.iter_mut() {
+        let num_packets = packets.packets.len();
+        if packets.packets.iter().all(is_vote_packet) {
+            continue;
+        }
+        *packets = Packets::new(
+            packets
+                .packets
+                .iter()
+                .filter(|packet| is_vote_packet(packet))
+                .cloned()
+                .collect(),
+        );
+        num_dropped += num_packets - packets.packets.len();
+    }
+    batches.retain(|packets| !packets.packets.is_empty());
+    num_dropped
+}
+
+/// Whether the packet holds a transaction whose instructions all invoke the vote program
+fn is_vote_packet(packet: &Packet) -> bool {
+    match limited_deserialize::<Transaction>(&packet.data[..packet.meta.size]) {
+        Ok(tx) => {
+            let message = &tx.message;
+            !message.instructions.is_empty()
+                && message.instructions.iter().all(|ix| {
+                    message.account_keys.get(ix.program_id_index as usize)
+                        == Some(&solana_vote_program::id())
+                })
+        }
+        Err(_) => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use solana_perf::packet::Packet;
 
     #[test]
     fn test_mark_disabled() {
@@ -61,4 +261,66 @@ mod tests {
         mark_disabled(&mut batches, &[vec![1]]);
         assert_eq!(batches[0].packets[0].meta.discard, false);
     }
+
+    #[test]
+    fn test_packet_deduper() {
+        let packet = |byte: u8| {
+            let mut packet = Packet::default();
+            packet.data[0] = byte;
+            packet.meta.size = 1;
+            packet
+        };
+        let deduper = PacketDeduper::new(2, 0.001);
+        let mut batches = vec![
+            Packets::new(vec![packet(1), packet(2), packet(1)]),
+            Packets::new(vec![packet(2)]),
+        ];
+        assert_eq!(deduper.dedup_packets(&mut batches), 2);
+        assert_eq!(batches.len(), 1);
+        let data: Vec<u8> = batches[0].packets.iter().map(|p| p.data[0]).collect();
+        assert_eq!(data, vec![1, 2]);
+
+        // Packets are remembered for at least `capacity` packets
+        let mut batches = vec![Packets::new(vec![packet(3), packet(4), packet(2)])];
+        assert_eq!(deduper.dedup_packets(&mut batches), 1);
+        let mut batches = vec![Packets::new(vec![packet(5), packet(1), packet(4)])];
+        assert_eq!(deduper.dedup_packets(&mut batches), 1);
+        assert_eq!(batches[0].packets.len(), 2);
+    }
+
+    #[test]
+    fn test_retain_votes() {
+        use solana_sdk::{
+            signature::{Keypair, Signer},
+            system_transaction,
+        };
+        use solana_vote_program::vote_transaction;
+
+        let keypair = Keypair::new();
+        let vote_tx = vote_transaction::new_vote_transaction(
+            vec![1],
+            Hash::default(),
+            Hash::default(),
+            &keypair,
+            &keypair,
+            &keypair,
+            None,
+        );
+        let transfer_tx =
+            system_transaction::transfer(&keypair, &keypair.pubkey(), 1, Hash::default());
+        let addr = "127.0.0.1:8001".parse().unwrap();
+        let packet = |tx: &Transaction| Packet::from_data(&addr, tx);
+        let mut garbage = Packet::default();
+        garbage.meta.size = 10;
+        let mut batches = vec![
+            Packets::new(vec![packet(&vote_tx), packet(&transfer_tx), garbage]),
+            Packets::new(vec![packet(&transfer_tx)]),
+            Packets::new(vec![packet(&vote_tx)]),
+        ];
+        assert_eq!(retain_votes(&mut batches), 3);
+        assert_eq!(batches.len(), 2);
+        assert!(batches
+            .iter()
+            .all(|packets| packets.packets.len() == 1 && is_vote_packet(&packets.packets[0])));
+    }
 }
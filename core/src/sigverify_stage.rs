@@ -8,7 +8,10 @@
 use crate::sigverify;
 use crossbeam_channel::{SendError, Sender as CrossbeamSender};
 use solana_measure::measure::Measure;
-use solana_metrics::datapoint_debug;
+use solana_metrics::{
+    datapoint_debug,
+    registry::{self, Gauge, Histogram},
+};
 use solana_perf::packet::Packets;
 use solana_perf::perf_libs;
 use solana_sdk::timing;
@@ -19,6 +22,7 @@ use std::thread::{self, Builder, JoinHandle};
 use thiserror::Error;
 
 toml_config::package_config! {
+    RECV_BATCH_MIN: usize,
     RECV_BATCH_MAX_CPU: usize,
     RECV_BATCH_MAX_GPU: usize,
 }
@@ -34,6 +38,54 @@ pub enum SigVerifyServiceError {
 
 type Result<T> = std::result::Result<T, SigVerifyServiceError>;
 
+/// Adapts the number of packets a verifier takes off the queue at once to the depth of the
+/// queue: batches grow while packets queue up faster than they are verified, to make the most
+/// of the GPU, and shrink back as the queue drains, so that during quiet periods packets are
+/// verified as they come instead of in large batches.
+struct BatchSizer {
+    min: usize,
+    max: usize,
+    limit: usize,
+}
+
+impl BatchSizer {
+    fn new(min: usize, max: usize) -> Self {
+        let min = min.min(max);
+        Self {
+            min,
+            max,
+            limit: min,
+        }
+    }
+
+    /// Adapts the limit after taking `len` packets off the queue with the current limit
+    fn update(&mut self, len: usize) {
+        if len >= self.limit {
+            self.limit = (self.limit * 2).min(self.max);
+        } else if len < self.limit / 4 {
+            self.limit = (self.limit / 2).max(self.min);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SigVerifyStageStats {
+    batch_packets: Arc<Histogram>,
+    batch_latency_us: Arc<Histogram>,
+    batch_limit: Arc<Gauge>,
+}
+
+impl Default for SigVerifyStageStats {
+    fn default() -> Self {
+        let namespace = registry::namespace("sigverify_stage");
+        Self {
+            batch_packets: namespace.histogram("batch_packets"),
+            batch_latency_us: namespace.histogram("batch_latency_us"),
+            batch_limit: namespace.gauge("batch_limit"),
+        }
+    }
+}
+
 pub struct SigVerifyStage {
     thread_hdls: Vec<JoinHandle<()>>,
 }
@@ -69,15 +121,16 @@ impl SigVerifyStage {
         sendr: &CrossbeamSender<Vec<Packets>>,
         id: usize,
         verifier: &T,
+        batch_sizer: &mut BatchSizer,
+        stats: &SigVerifyStageStats,
     ) -> Result<()> {
         let (batch, len, recv_time) = streamer::recv_batch(
             &recvr.lock().expect("'recvr' lock in fn verifier"),
-            if perf_libs::api().is_some() {
-                CFG.RECV_BATCH_MAX_GPU
-            } else {
-                CFG.RECV_BATCH_MAX_CPU
-            },
+            batch_sizer.limit,
         )?;
+        batch_sizer.update(len);
+        stats.batch_packets.record(len as u64);
+        stats.batch_limit.set(batch_sizer.limit as i64);
 
         let mut verify_batch_time = Measure::start("sigverify_batch_time");
         let batch_len = batch.len();
@@ -95,6 +148,7 @@ impl SigVerifyStage {
         }
 
         verify_batch_time.stop();
+        stats.batch_latency_us.record(verify_batch_time.as_us());
 
         debug!(
             "@{:?} verifier: done. batches: {} total verify time: {:?} id: {} verified: {} v/s {}",
@@ -126,19 +180,35 @@ impl SigVerifyStage {
         let verifier = verifier.clone();
         Builder::new()
             .name(format!("solana-verifier-{}", id))
-            .spawn(move || loop {
-                if let Err(e) = Self::verifier(&packet_receiver, &verified_sender, id, &verifier) {
-                    match e {
-                        SigVerifyServiceError::StreamerError(StreamerError::RecvTimeoutError(
-                            RecvTimeoutError::Disconnected,
-                        )) => break,
-                        SigVerifyServiceError::StreamerError(StreamerError::RecvTimeoutError(
-                            RecvTimeoutError::Timeout,
-                        )) => (),
-                        SigVerifyServiceError::SendError(_) => {
-                            break;
+            .spawn(move || {
+                let max_batch = if perf_libs::api().is_some() {
+                    CFG.RECV_BATCH_MAX_GPU
+                } else {
+                    CFG.RECV_BATCH_MAX_CPU
+                };
+                let mut batch_sizer = BatchSizer::new(CFG.RECV_BATCH_MIN, max_batch);
+                let stats = SigVerifyStageStats::default();
+                loop {
+                    if let Err(e) = Self::verifier(
+                        &packet_receiver,
+                        &verified_sender,
+                        id,
+                        &verifier,
+                        &mut batch_sizer,
+                        &stats,
+                    ) {
+                        match e {
+                            SigVerifyServiceError::StreamerError(
+                                StreamerError::RecvTimeoutError(RecvTimeoutError::Disconnected),
+                            ) => break,
+                            SigVerifyServiceError::StreamerError(
+                                StreamerError::RecvTimeoutError(RecvTimeoutError::Timeout),
+                            ) => (),
+                            SigVerifyServiceError::SendError(_) => {
+                                break;
+                            }
+                            _ => error!("{:?}", e),
                         }
-                        _ => error!("{:?}", e),
                     }
                 }
             })
@@ -165,3 +235,31 @@ impl SigVerifyStage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_sizer() {
+        let mut batch_sizer = BatchSizer::new(100, 1000);
+        assert_eq!(batch_sizer.limit, 100);
+        // Packets queue up, the batches grow up to the maximum
+        batch_sizer.update(100);
+        assert_eq!(batch_sizer.limit, 200);
+        for _ in 0..4 {
+            batch_sizer.update(batch_sizer.limit);
+        }
+        assert_eq!(batch_sizer.limit, 1000);
+        // The queue keeps up, the limit stays
+        batch_sizer.update(400);
+        assert_eq!(batch_sizer.limit, 1000);
+        // The queue drains, the batches shrink down to the minimum
+        batch_sizer.update(10);
+        assert_eq!(batch_sizer.limit, 500);
+        for _ in 0..4 {
+            batch_sizer.update(0);
+        }
+        assert_eq!(batch_sizer.limit, 100);
+    }
+}
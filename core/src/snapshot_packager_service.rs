@@ -1,11 +1,18 @@
-use crate::cluster_info::{ClusterInfo, CFG as CLUSTER_CFG};
-use solana_runtime::{snapshot_package::AccountsPackageReceiver, snapshot_utils};
+use crate::{
+    cluster_info::{ClusterInfo, CFG as CLUSTER_CFG},
+    poh_recorder::PohRecorder,
+};
+use solana_runtime::{
+    bank_forks::{CompressionOptions, SnapshotThrottleOptions},
+    snapshot_package::AccountsPackageReceiver,
+    snapshot_utils::{self, ArchiveThrottle},
+};
 use solana_sdk::{clock::Slot, hash::Hash};
 use std::{
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc::RecvTimeoutError,
-        Arc,
+        Arc, Mutex,
     },
     thread::{self, Builder, JoinHandle},
     time::Duration,
@@ -21,6 +28,9 @@ impl SnapshotPackagerService {
         starting_snapshot_hash: Option<(Slot, Hash)>,
         exit: &Arc<AtomicBool>,
         cluster_info: &Arc<ClusterInfo>,
+        compression_options: CompressionOptions,
+        throttle_options: SnapshotThrottleOptions,
+        poh_recorder: Option<Arc<Mutex<PohRecorder>>>,
     ) -> Self {
         let exit = exit.clone();
         let cluster_info = cluster_info.clone();
@@ -28,6 +38,27 @@ impl SnapshotPackagerService {
         let t_snapshot_packager = Builder::new()
             .name("solana-snapshot-packager".to_string())
             .spawn(move || {
+                if throttle_options.low_priority {
+                    if let Err(err) = snapshot_utils::set_thread_low_priority() {
+                        warn!("Unable to lower the snapshot packager priority: {}", err);
+                    }
+                }
+                // Producing a block is when replay can least afford to wait on IO
+                let is_leader = || {
+                    poh_recorder
+                        .as_ref()
+                        .map(|poh_recorder| poh_recorder.lock().unwrap().has_bank())
+                        .unwrap_or(false)
+                };
+                let throttle = ArchiveThrottle {
+                    max_write_bytes_per_sec: throttle_options.max_write_bytes_per_sec,
+                    is_paused: if throttle_options.pause_during_leader_slots {
+                        Some(&is_leader)
+                    } else {
+                        None
+                    },
+                };
+
                 let mut hashes = vec![];
                 if let Some(starting_snapshot_hash) = starting_snapshot_hash {
                     hashes.push(starting_snapshot_hash);
@@ -46,9 +77,11 @@ impl SnapshotPackagerService {
                             {
                                 snapshot_package = new_snapshot_package;
                             }
-                            if let Err(err) =
-                                snapshot_utils::archive_snapshot_package(&snapshot_package)
-                            {
+                            if let Err(err) = snapshot_utils::archive_snapshot_package_with_options(
+                                &snapshot_package,
+                                &compression_options,
+                                &throttle,
+                            ) {
                                 warn!("Failed to create snapshot archive: {}", err);
                             } else {
                                 hashes.push((snapshot_package.root, snapshot_package.hash));
@@ -0,0 +1,236 @@
+//! The `supermajority_progress` module summarizes, while a validator waits for a supermajority
+//! of the stake to restart a cluster with `--wait-for-supermajority`, which of the staked nodes
+//! are already in gossip, their versions and the bank hashes they restart from.
+
+use crate::cluster_info::ClusterInfo;
+use solana_runtime::bank::Bank;
+use solana_sdk::{clock::Slot, hash::Hash, pubkey::Pubkey};
+use solana_vote_program::vote_state::VoteState;
+use std::collections::HashMap;
+
+/// Stake observed in gossip at the slot the cluster restarts from
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SupermajorityProgress {
+    pub slot: Slot,
+    pub bank_hash: Hash,
+    pub total_stake: u64,
+    /// Stake of the nodes in gossip with the shred version of this node
+    pub online_stake: u64,
+    pub stake_by_version: HashMap<String, u64>,
+    /// Stake of the online nodes by the bank hash they advertised for `slot`
+    pub stake_by_bank_hash: HashMap<Hash, u64>,
+    pub wrong_shred_version: Vec<(Pubkey, u64)>,
+    /// Staked nodes not yet in gossip
+    pub offline: Vec<(Pubkey, u64)>,
+    /// Online nodes restarting from another bank hash than this node
+    pub mismatched_bank_hash: Vec<(Pubkey, u64, Hash)>,
+}
+
+impl SupermajorityProgress {
+    pub fn new(bank: &Bank, cluster_info: &ClusterInfo) -> Self {
+        let mut progress = Self {
+            slot: bank.slot(),
+            bank_hash: bank.hash(),
+            ..Self::default()
+        };
+        let all_tvu_peers = cluster_info.all_tvu_peers();
+        let my_shred_version = cluster_info.my_shred_version();
+        let my_id = cluster_info.id();
+
+        for (activated_stake, vote_account) in bank.vote_accounts().values() {
+            let activated_stake = *activated_stake;
+            let node_pubkey = VoteState::from(&vote_account)
+                .unwrap_or_default()
+                .node_pubkey;
+            progress.total_stake += activated_stake;
+
+            if activated_stake == 0 {
+                continue;
+            }
+
+            let (version, bank_hash) = if node_pubkey == my_id {
+                // This node is online
+                (solana_version::Version::default(), Some(progress.bank_hash))
+            } else if let Some(peer) = all_tvu_peers.iter().find(|peer| peer.id == node_pubkey) {
+                if peer.shred_version != my_shred_version {
+                    progress
+                        .wrong_shred_version
+                        .push((node_pubkey, activated_stake));
+                    continue;
+                }
+                trace!(
+                    "observed {} in gossip, (activated_stake={})",
+                    node_pubkey,
+                    activated_stake
+                );
+                let bank_hash = cluster_info
+                    .get_restart_bank_hash(&node_pubkey)
+                    .filter(|(slot, _)| *slot == progress.slot)
+                    .map(|(_, bank_hash)| bank_hash);
+                (
+                    cluster_info
+                        .get_node_version(&node_pubkey)
+                        .unwrap_or_default(),
+                    bank_hash,
+                )
+            } else {
+                progress.offline.push((node_pubkey, activated_stake));
+                continue;
+            };
+
+            progress.online_stake += activated_stake;
+            *progress
+                .stake_by_version
+                .entry(version.to_string())
+                .or_default() += activated_stake;
+            if let Some(bank_hash) = bank_hash {
+                *progress.stake_by_bank_hash.entry(bank_hash).or_default() += activated_stake;
+                if bank_hash != progress.bank_hash {
+                    progress
+                        .mismatched_bank_hash
+                        .push((node_pubkey, activated_stake, bank_hash));
+                }
+            }
+        }
+        progress
+    }
+
+    pub fn online_stake_percent(&self) -> u64 {
+        self.stake_percent(self.online_stake)
+    }
+
+    fn stake_percent(&self, stake: u64) -> u64 {
+        if self.total_stake == 0 {
+            return 0;
+        }
+        stake * 100 / self.total_stake
+    }
+
+    pub fn log(&self) {
+        info!(
+            "{}% of active stake visible in gossip",
+            self.online_stake_percent()
+        );
+        for (version, stake) in &self.stake_by_version {
+            info!("    {}% - version {}", self.stake_percent(*stake), version);
+        }
+        for (bank_hash, stake) in &self.stake_by_bank_hash {
+            info!(
+                "    {}% - bank hash {} at slot {}",
+                self.stake_percent(*stake),
+                bank_hash,
+                self.slot
+            );
+        }
+
+        if !self.wrong_shred_version.is_empty() {
+            let stake = self
+                .wrong_shred_version
+                .iter()
+                .map(|(_, stake)| stake)
+                .sum();
+            info!(
+                "{}% of active stake has the wrong shred version in gossip",
+                self.stake_percent(stake)
+            );
+            for (identity, stake) in &self.wrong_shred_version {
+                info!("    {}% - {}", self.stake_percent(*stake), identity);
+            }
+        }
+
+        if !self.offline.is_empty() {
+            let stake = self.offline.iter().map(|(_, stake)| stake).sum();
+            info!(
+                "{}% of active stake is not visible in gossip",
+                self.stake_percent(stake)
+            );
+            for (identity, stake) in &self.offline {
+                info!("    {}% - {}", self.stake_percent(*stake), identity);
+            }
+        }
+
+        for (identity, stake, bank_hash) in &self.mismatched_bank_hash {
+            warn!(
+                "{} ({}% of active stake) restarts from bank hash {} at slot {}, expected {}",
+                identity,
+                self.stake_percent(*stake),
+                bank_hash,
+                self.slot,
+                self.bank_hash
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        contact_info::ContactInfo,
+        crds_value::{CrdsData, CrdsValue, RestartBankHash},
+    };
+    use solana_runtime::genesis_utils::{
+        create_genesis_config_with_vote_accounts, ValidatorVoteKeypairs,
+    };
+    use solana_sdk::{
+        signature::{Keypair, Signer},
+        timing::timestamp,
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_supermajority_progress() {
+        let validator_keypairs: Vec<_> =
+            (0..3).map(|_| ValidatorVoteKeypairs::new_rand()).collect();
+        let genesis_config = create_genesis_config_with_vote_accounts(
+            10_000,
+            &validator_keypairs,
+            vec![100; validator_keypairs.len()],
+        )
+        .genesis_config;
+        let bank = Bank::new(&genesis_config);
+        let node_keypair =
+            Arc::new(Keypair::from_bytes(&validator_keypairs[0].node_keypair.to_bytes()).unwrap());
+        let cluster_info = ClusterInfo::new(
+            ContactInfo::new_localhost(&node_keypair.pubkey(), timestamp()),
+            node_keypair,
+        );
+
+        // The second node is in gossip, restarting from another bank hash
+        let peer = validator_keypairs[1].node_keypair.pubkey();
+        let other_bank_hash = Hash::new_unique();
+        cluster_info.insert_info(ContactInfo::new_localhost(&peer, timestamp()));
+        let restart_bank_hash = CrdsValue::new_unsigned(CrdsData::RestartBankHash(
+            RestartBankHash::new(peer, 0, other_bank_hash),
+        ));
+        cluster_info
+            .gossip
+            .write()
+            .unwrap()
+            .crds
+            .insert(restart_bank_hash, timestamp())
+            .unwrap();
+
+        let progress = SupermajorityProgress::new(&bank, &cluster_info);
+        assert_eq!(progress.slot, 0);
+        assert_eq!(progress.bank_hash, bank.hash());
+        assert_eq!(progress.total_stake, 300);
+        assert_eq!(progress.online_stake, 200);
+        assert_eq!(progress.online_stake_percent(), 66);
+        assert_eq!(progress.stake_by_bank_hash.get(&bank.hash()), Some(&100));
+        assert_eq!(
+            progress.stake_by_bank_hash.get(&other_bank_hash),
+            Some(&100)
+        );
+        assert_eq!(
+            progress.offline,
+            vec![(validator_keypairs[2].node_keypair.pubkey(), 100)]
+        );
+        assert!(progress.wrong_shred_version.is_empty());
+        assert_eq!(
+            progress.mismatched_bank_hash,
+            vec![(peer, 100, other_bank_hash)]
+        );
+        progress.log();
+    }
+}
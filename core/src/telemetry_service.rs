@@ -0,0 +1,274 @@
+//! The `telemetry_service` periodically submits anonymized performance aggregates to an
+//! operator-configured endpoint. It is only started if the operator opts in.
+//!
+//! A report contains nothing but the following, and in particular no identity, vote account or
+//! network address:
+//! * the software version
+//! * a coarse hardware class: operating system, and the number of cpus and amount of memory
+//!   rounded down to a power of two
+//! * percentiles of the replay lag, the number of slots the highest replayed bank is behind the
+//!   highest slot received, sampled every second
+//! * the number of slots and skipped slots rooted during the report interval
+
+use serde::Serialize;
+use solana_ledger::blockstore::Blockstore;
+use solana_runtime::bank_forks::BankForks;
+use solana_sdk::clock::Slot;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{self, sleep, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+use systemstat::{Platform, System};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct TelemetryConfig {
+    pub endpoint: String,
+    pub report_interval: Duration,
+    /// Log the reports instead of submitting them, to review what would be sent
+    pub dry_run: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HardwareClass {
+    pub os: String,
+    pub cpus: u64,
+    pub memory_gb: u64,
+}
+
+impl HardwareClass {
+    pub fn new(os: &str, cpus: u64, memory_bytes: u64) -> Self {
+        Self {
+            os: os.to_string(),
+            cpus: round_down_to_power_of_two(cpus),
+            memory_gb: round_down_to_power_of_two(memory_bytes >> 30),
+        }
+    }
+
+    fn detect() -> Self {
+        let memory_bytes = System::new()
+            .memory()
+            .map(|memory| memory.total.as_u64())
+            .unwrap_or_default();
+        Self::new(std::env::consts::OS, num_cpus::get() as u64, memory_bytes)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TelemetryReport {
+    pub version: String,
+    pub hardware_class: HardwareClass,
+    pub interval_secs: u64,
+    pub replay_lag_p50: u64,
+    pub replay_lag_p90: u64,
+    pub replay_lag_p99: u64,
+    pub rooted_slots: u64,
+    pub skipped_slots: u64,
+    pub skipped_slot_rate: f64,
+}
+
+impl TelemetryReport {
+    pub fn new(
+        hardware_class: HardwareClass,
+        interval: Duration,
+        mut replay_lags: Vec<u64>,
+        rooted_slots: u64,
+        slots: u64,
+    ) -> Self {
+        replay_lags.sort_unstable();
+        let skipped_slots = slots.saturating_sub(rooted_slots);
+        Self {
+            version: solana_version::version!().to_string(),
+            hardware_class,
+            interval_secs: interval.as_secs(),
+            replay_lag_p50: percentile(&replay_lags, 50),
+            replay_lag_p90: percentile(&replay_lags, 90),
+            replay_lag_p99: percentile(&replay_lags, 99),
+            rooted_slots,
+            skipped_slots,
+            skipped_slot_rate: if slots == 0 {
+                0.0
+            } else {
+                skipped_slots as f64 / slots as f64
+            },
+        }
+    }
+}
+
+fn round_down_to_power_of_two(value: u64) -> u64 {
+    if value == 0 {
+        0
+    } else {
+        1 << (63 - value.leading_zeros())
+    }
+}
+
+// `sorted` must be sorted in ascending order
+fn percentile(sorted: &[u64], percentile: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let index = (sorted.len() * percentile / 100).min(sorted.len() - 1);
+    sorted[index]
+}
+
+pub struct TelemetryService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl TelemetryService {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        config: TelemetryConfig,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        blockstore: &Arc<Blockstore>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let exit = exit.clone();
+        let blockstore = blockstore.clone();
+        let bank_forks = bank_forks.clone();
+
+        info!(
+            "Starting Telemetry service, reporting to {}{}",
+            config.endpoint,
+            if config.dry_run { " (dry run)" } else { "" }
+        );
+        let thread_hdl = Builder::new()
+            .name("telemetry".to_string())
+            .spawn(move || {
+                Self::run(&config, &bank_forks, &blockstore, &exit);
+            })
+            .unwrap();
+
+        Self { thread_hdl }
+    }
+
+    fn run(
+        config: &TelemetryConfig,
+        bank_forks: &RwLock<BankForks>,
+        blockstore: &Blockstore,
+        exit: &AtomicBool,
+    ) {
+        let hardware_class = HardwareClass::detect();
+        let client = reqwest::blocking::Client::new();
+        let mut replay_lags = vec![];
+        let mut last_root = bank_forks.read().unwrap().root();
+        let mut last_report = Instant::now();
+        loop {
+            if exit.load(Ordering::Relaxed) {
+                break;
+            }
+
+            let highest_replayed_slot = bank_forks.read().unwrap().highest_slot();
+            if let Ok(Some(highest_slot)) = blockstore.highest_slot() {
+                replay_lags.push(highest_slot.saturating_sub(highest_replayed_slot));
+            }
+
+            if last_report.elapsed() >= config.report_interval {
+                last_report = Instant::now();
+                let root = bank_forks.read().unwrap().root();
+                let report = TelemetryReport::new(
+                    hardware_class.clone(),
+                    config.report_interval,
+                    std::mem::take(&mut replay_lags),
+                    Self::count_rooted_slots(blockstore, last_root, root),
+                    root.saturating_sub(last_root),
+                );
+                last_root = root;
+                Self::submit(config, &client, &report);
+            }
+            sleep(SAMPLE_INTERVAL);
+        }
+    }
+
+    // Number of rooted slots in (`start`, `end`]
+    fn count_rooted_slots(blockstore: &Blockstore, start: Slot, end: Slot) -> u64 {
+        blockstore
+            .rooted_slot_iterator(start + 1)
+            .map(|slots| slots.take_while(|slot| *slot <= end).count() as u64)
+            .unwrap_or_default()
+    }
+
+    fn submit(
+        config: &TelemetryConfig,
+        client: &reqwest::blocking::Client,
+        report: &TelemetryReport,
+    ) {
+        if config.dry_run {
+            info!("Telemetry report (not submitted): {:?}", report);
+            return;
+        }
+        let result = client
+            .post(&config.endpoint)
+            .timeout(SUBMIT_TIMEOUT)
+            .json(report)
+            .send()
+            .and_then(|response| response.error_for_status());
+        if let Err(err) = result {
+            warn!(
+                "Failed to submit telemetry report to {}: {}",
+                config.endpoint, err
+            );
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_ledger::get_tmp_ledger_path;
+
+    #[test]
+    fn test_telemetry_report() {
+        let hardware_class = HardwareClass::new("linux", 24, 200 << 30);
+        assert_eq!(hardware_class.cpus, 16);
+        assert_eq!(hardware_class.memory_gb, 128);
+        assert_eq!(HardwareClass::new("linux", 0, 0).cpus, 0);
+
+        let report = TelemetryReport::new(
+            hardware_class,
+            Duration::from_secs(60),
+            (0..100).rev().collect(),
+            75,
+            100,
+        );
+        assert_eq!(report.replay_lag_p50, 50);
+        assert_eq!(report.replay_lag_p90, 90);
+        assert_eq!(report.replay_lag_p99, 99);
+        assert_eq!(report.skipped_slots, 25);
+        assert!((report.skipped_slot_rate - 0.25).abs() < f64::EPSILON);
+
+        let report =
+            TelemetryReport::new(report.hardware_class, Duration::from_secs(60), vec![], 0, 0);
+        assert_eq!(report.replay_lag_p99, 0);
+        assert!(report.skipped_slot_rate.abs() < f64::EPSILON);
+
+        // Nothing but the aggregates is reported
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json.as_object().unwrap().len(), 9);
+    }
+
+    #[test]
+    fn test_count_rooted_slots() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            blockstore.set_roots(&[1, 2, 4, 7, 9]).unwrap();
+            assert_eq!(TelemetryService::count_rooted_slots(&blockstore, 1, 7), 3);
+            assert_eq!(TelemetryService::count_rooted_slots(&blockstore, 9, 20), 0);
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+}
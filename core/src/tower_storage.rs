@@ -0,0 +1,268 @@
+//! The `tower_storage` module persists the tower of a validator: to a file, by default in the
+//! ledger directory, or to an etcd cluster shared by the machines a validator fails over between.
+
+use crate::consensus::{Result, SavedTower, TowerError};
+use solana_sdk::{pubkey::Pubkey, timing::timestamp};
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use tokio::runtime::Runtime;
+
+pub trait TowerStorage: fmt::Debug + Sync + Send {
+    fn load(&self, node_pubkey: &Pubkey) -> Result<SavedTower>;
+    fn store(&self, node_pubkey: &Pubkey, saved_tower: &SavedTower) -> Result<()>;
+}
+
+/// How a tower file is made durable before the validator votes with it. The new tower is always
+/// written to a temporary file renamed over the previous one, so that a crash of the validator
+/// never leaves a torn tower behind; a power failure may still, unless the writes are synced.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TowerSyncMode {
+    /// Nothing is synced, which keeps syncing out of the voting path
+    None,
+    /// The temporary file is synced before the rename, and the directory after
+    Fsync,
+    /// The temporary file is written with `O_DSYNC`, syncing its data but not its metadata, and
+    /// the directory is synced after the rename
+    Dsync,
+}
+
+impl Default for TowerSyncMode {
+    fn default() -> Self {
+        TowerSyncMode::None
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FileTowerStorage {
+    pub tower_path: PathBuf,
+    pub sync_mode: TowerSyncMode,
+}
+
+impl FileTowerStorage {
+    pub fn new(tower_path: PathBuf, sync_mode: TowerSyncMode) -> Self {
+        Self {
+            tower_path,
+            sync_mode,
+        }
+    }
+
+    pub fn filename(&self, node_pubkey: &Pubkey) -> PathBuf {
+        self.tower_path
+            .join(format!("tower-{}", node_pubkey))
+            .with_extension("bin")
+    }
+
+    fn tmp_filename(&self, node_pubkey: &Pubkey) -> PathBuf {
+        self.filename(node_pubkey).with_extension("bin.new")
+    }
+
+    fn create_tmp_file(&self, path: &Path) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        // overwrite anything if exists
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            if self.sync_mode == TowerSyncMode::Dsync {
+                options.custom_flags(libc::O_DSYNC);
+            }
+        }
+        options.open(path)
+    }
+}
+
+impl TowerStorage for FileTowerStorage {
+    fn load(&self, node_pubkey: &Pubkey) -> Result<SavedTower> {
+        // Ensure to create the directory here, because load() precedes store() always
+        fs::create_dir_all(&self.tower_path)?;
+
+        let file = File::open(self.filename(node_pubkey))?;
+        let mut stream = BufReader::new(file);
+        bincode::deserialize_from(&mut stream).map_err(|e| e.into())
+    }
+
+    fn store(&self, node_pubkey: &Pubkey, saved_tower: &SavedTower) -> Result<()> {
+        let filename = self.filename(node_pubkey);
+        let new_filename = self.tmp_filename(node_pubkey);
+        let data = bincode::serialize(saved_tower)?;
+        {
+            let mut file = self.create_tmp_file(&new_filename)?;
+            file.write_all(&data)?;
+            match self.sync_mode {
+                TowerSyncMode::None => (),
+                TowerSyncMode::Fsync => file.sync_all()?,
+                TowerSyncMode::Dsync => {
+                    // O_DSYNC is only set on unix, elsewhere the data is synced here
+                    if cfg!(not(unix)) {
+                        file.sync_data()?;
+                    }
+                }
+            }
+        }
+        fs::rename(&new_filename, &filename)?;
+        // The rename itself only survives a power failure once the directory is synced
+        #[cfg(unix)]
+        {
+            if self.sync_mode != TowerSyncMode::None {
+                File::open(&self.tower_path)?.sync_all()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Keeps the tower of a validator in etcd, under `<identity>/tower`. A validator claims the
+/// tower when loading it, by writing its instance id to `<identity>/instance`; a tower is only
+/// loaded or stored while the claim holds, so that once a standby machine takes over, the
+/// instance it replaced can no longer overwrite the tower.
+pub struct EtcdTowerStorage {
+    client: Mutex<etcd_client::Client>,
+    runtime: Mutex<Runtime>,
+    instance_id: [u8; 8],
+}
+
+impl fmt::Debug for EtcdTowerStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EtcdTowerStorage")
+            .field("instance_id", &self.instance_id)
+            .finish()
+    }
+}
+
+impl EtcdTowerStorage {
+    pub fn new<E: AsRef<str>, S: AsRef<[E]>>(endpoints: S) -> Result<Self> {
+        let mut runtime = tokio::runtime::Builder::new()
+            .basic_scheduler()
+            .enable_all()
+            .thread_name("solana-etcd-tower")
+            .build()?;
+        let client = runtime
+            .block_on(etcd_client::Client::connect(endpoints, None))
+            .map_err(Self::etcd_to_tower_error)?;
+        Ok(Self {
+            client: Mutex::new(client),
+            runtime: Mutex::new(runtime),
+            instance_id: timestamp().to_le_bytes(),
+        })
+    }
+
+    fn keys(node_pubkey: &Pubkey) -> (String, String) {
+        (
+            format!("{}/instance", node_pubkey),
+            format!("{}/tower", node_pubkey),
+        )
+    }
+
+    fn etcd_to_tower_error(err: etcd_client::Error) -> TowerError {
+        TowerError::IOError(io::Error::new(io::ErrorKind::Other, err.to_string()))
+    }
+
+    fn lost_instance_error(node_pubkey: &Pubkey) -> TowerError {
+        TowerError::IOError(io::Error::new(
+            io::ErrorKind::Other,
+            format!(
+                "another instance of {} claimed the tower in etcd",
+                node_pubkey
+            ),
+        ))
+    }
+
+    fn txn(&self, txn: etcd_client::Txn) -> Result<etcd_client::TxnResponse> {
+        let mut client = self.client.lock().unwrap();
+        self.runtime
+            .lock()
+            .unwrap()
+            .block_on(client.txn(txn))
+            .map_err(Self::etcd_to_tower_error)
+    }
+
+    fn while_claimed(&self, instance_key: String) -> etcd_client::Txn {
+        etcd_client::Txn::new().when(vec![etcd_client::Compare::value(
+            instance_key,
+            etcd_client::CompareOp::Equal,
+            self.instance_id,
+        )])
+    }
+}
+
+impl TowerStorage for EtcdTowerStorage {
+    fn load(&self, node_pubkey: &Pubkey) -> Result<SavedTower> {
+        let (instance_key, tower_key) = Self::keys(node_pubkey);
+        let claim = etcd_client::Txn::new().and_then(vec![etcd_client::TxnOp::put(
+            instance_key.clone(),
+            self.instance_id,
+            None,
+        )]);
+        self.txn(claim)?;
+
+        let response = self.txn(
+            self.while_claimed(instance_key)
+                .and_then(vec![etcd_client::TxnOp::get(tower_key, None)]),
+        )?;
+        if !response.succeeded() {
+            return Err(Self::lost_instance_error(node_pubkey));
+        }
+        for op_response in response.op_responses() {
+            if let etcd_client::TxnOpResponse::Get(get_response) = op_response {
+                if let Some(kv) = get_response.kvs().first() {
+                    return bincode::deserialize(kv.value()).map_err(|e| e.into());
+                }
+            }
+        }
+        // Reported like a missing tower file, the validator has never voted from etcd
+        Err(TowerError::IOError(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no tower for {} in etcd", node_pubkey),
+        )))
+    }
+
+    fn store(&self, node_pubkey: &Pubkey, saved_tower: &SavedTower) -> Result<()> {
+        let (instance_key, tower_key) = Self::keys(node_pubkey);
+        let put = etcd_client::TxnOp::put(tower_key, bincode::serialize(saved_tower)?, None);
+        let response = self.txn(self.while_claimed(instance_key).and_then(vec![put]))?;
+        if !response.succeeded() {
+            return Err(Self::lost_instance_error(node_pubkey));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::Tower;
+    use solana_sdk::signature::{Keypair, Signer};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_file_tower_storage() {
+        let identity_keypair = Arc::new(Keypair::new());
+        let tower = Tower::new_with_key(&identity_keypair.pubkey());
+        for sync_mode in &[
+            TowerSyncMode::None,
+            TowerSyncMode::Fsync,
+            TowerSyncMode::Dsync,
+        ] {
+            let dir = TempDir::new().unwrap();
+            let tower_storage = FileTowerStorage::new(dir.path().join("tower"), *sync_mode);
+            assert!(tower_storage
+                .load(&identity_keypair.pubkey())
+                .unwrap_err()
+                .is_file_missing());
+
+            tower.save(&tower_storage, &identity_keypair).unwrap();
+            assert!(tower_storage.filename(&identity_keypair.pubkey()).exists());
+            assert!(!tower_storage
+                .tmp_filename(&identity_keypair.pubkey())
+                .exists());
+            let restored = Tower::restore(&tower_storage, &identity_keypair.pubkey()).unwrap();
+            assert_eq!(restored, tower);
+        }
+    }
+}
@@ -7,11 +7,14 @@ use crate::{
     cluster_info::ClusterInfo,
     cluster_info_vote_listener::{ClusterInfoVoteListener, VerifiedVoteSender, VoteTracker},
     fetch_stage::FetchStage,
+    link_conditioner::NodeLinkConditioner,
     optimistically_confirmed_bank_tracker::BankNotificationSender,
     poh_recorder::{PohRecorder, WorkingBankEntry},
     rpc_subscriptions::RpcSubscriptions,
     sigverify::TransactionSigVerifier,
     sigverify_stage::SigVerifyStage,
+    tpu_qos_stage::TpuQosStage,
+    tpu_tcp_stage::TpuTcpStage,
 };
 use crossbeam_channel::unbounded;
 use solana_ledger::{blockstore::Blockstore, blockstore_processor::TransactionStatusSender};
@@ -20,7 +23,7 @@ use solana_runtime::{
     vote_sender_types::{ReplayVoteReceiver, ReplayVoteSender},
 };
 use std::{
-    net::UdpSocket,
+    net::{TcpListener, UdpSocket},
     sync::{
         atomic::AtomicBool,
         mpsc::{channel, Receiver},
@@ -31,7 +34,10 @@ use std::{
 
 pub struct Tpu {
     fetch_stage: FetchStage,
+    tpu_tcp_stage: Option<TpuTcpStage>,
+    tpu_qos_stage: Option<TpuQosStage>,
     sigverify_stage: SigVerifyStage,
+    vote_sigverify_stage: SigVerifyStage,
     banking_stage: BankingStage,
     cluster_info_vote_listener: ClusterInfoVoteListener,
     broadcast_stage: BroadcastStage,
@@ -45,7 +51,9 @@ impl Tpu {
         entry_receiver: Receiver<WorkingBankEntry>,
         retransmit_slots_receiver: RetransmitSlotsReceiver,
         transactions_sockets: Vec<UdpSocket>,
+        tpu_tcp_listener: Option<TcpListener>,
         tpu_forwards_sockets: Vec<UdpSocket>,
+        tpu_vote_sockets: Vec<UdpSocket>,
         broadcast_sockets: Vec<UdpSocket>,
         subscriptions: &Arc<RpcSubscriptions>,
         transaction_status_sender: Option<TransactionStatusSender>,
@@ -59,15 +67,50 @@ impl Tpu {
         replay_vote_receiver: ReplayVoteReceiver,
         replay_vote_sender: ReplayVoteSender,
         bank_notification_sender: Option<BankNotificationSender>,
+        link_conditioner: Option<&NodeLinkConditioner>,
+        tpu_qos: bool,
     ) -> Self {
         let (packet_sender, packet_receiver) = channel();
+        // Votes have their own port and sigverify lane, bypassing QoS, so that they aren't held
+        // up behind a flood of other transactions
+        let (vote_packet_sender, vote_packet_receiver) = channel();
+        // With QoS, the packets are shared out by stake before reaching sigverify. The packets
+        // received over UDP and TCP go through separate channels, as only the source addresses
+        // of the latter can be trusted.
+        let (packet_sender, tcp_packet_sender, tpu_qos_stage) = if tpu_qos {
+            let (qos_sender, qos_receiver) = channel();
+            let (qos_tcp_sender, qos_tcp_receiver) = channel();
+            let tpu_qos_stage = TpuQosStage::new(
+                qos_receiver,
+                qos_tcp_receiver,
+                packet_sender,
+                cluster_info.clone(),
+                bank_forks.clone(),
+                &exit,
+            );
+            (qos_sender, qos_tcp_sender, Some(tpu_qos_stage))
+        } else {
+            (packet_sender.clone(), packet_sender, None)
+        };
         let fetch_stage = FetchStage::new_with_sender(
             transactions_sockets,
             tpu_forwards_sockets,
+            tpu_vote_sockets,
             &exit,
             &packet_sender,
+            &vote_packet_sender,
             &poh_recorder,
+            link_conditioner,
         );
+        let tpu_tcp_stage = tpu_tcp_listener.map(|listener| {
+            TpuTcpStage::new(
+                listener,
+                tcp_packet_sender,
+                cluster_info.clone(),
+                bank_forks.clone(),
+                &exit,
+            )
+        });
         let (verified_sender, verified_receiver) = unbounded();
 
         let sigverify_stage = {
@@ -76,6 +119,17 @@ impl Tpu {
         };
 
         let (verified_vote_packets_sender, verified_vote_packets_receiver) = unbounded();
+
+        // Verified votes go straight to the banking stage's vote thread, alongside the gossip votes
+        let vote_sigverify_stage = {
+            let verifier = TransactionSigVerifier::new_vote_verifier();
+            SigVerifyStage::new(
+                vote_packet_receiver,
+                verified_vote_packets_sender.clone(),
+                verifier,
+            )
+        };
+
         let cluster_info_vote_listener = ClusterInfoVoteListener::new(
             &exit,
             cluster_info.clone(),
@@ -111,7 +165,10 @@ impl Tpu {
 
         Self {
             fetch_stage,
+            tpu_tcp_stage,
+            tpu_qos_stage,
             sigverify_stage,
+            vote_sigverify_stage,
             banking_stage,
             cluster_info_vote_listener,
             broadcast_stage,
@@ -121,7 +178,14 @@ impl Tpu {
     pub fn join(self) -> thread::Result<()> {
         let mut results = vec![];
         results.push(self.fetch_stage.join());
+        if let Some(tpu_tcp_stage) = self.tpu_tcp_stage {
+            results.push(tpu_tcp_stage.join());
+        }
+        if let Some(tpu_qos_stage) = self.tpu_qos_stage {
+            results.push(tpu_qos_stage.join());
+        }
         results.push(self.sigverify_stage.join());
+        results.push(self.vote_sigverify_stage.join());
         results.push(self.cluster_info_vote_listener.join());
         results.push(self.banking_stage.join());
         let broadcast_result = self.broadcast_stage.join();
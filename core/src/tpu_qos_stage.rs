@@ -0,0 +1,370 @@
+//! The `tpu_qos_stage` sits between the `fetch_stage` and sigverify, and shares the packets
+//! sigverify can take between their senders by stake, so that during spam storms staked
+//! validators and RPC relayers are not crowded out by floods of anonymous packets.
+//!
+//! Senders are identified by their IP address, matched against the gossip address of the staked
+//! nodes; gossip only keeps the addresses nodes answered pings from. Only the packets received
+//! over TCP are credited with the stake of their sender, as the TCP handshake proves their source
+//! address; the source address of a UDP packet can be forged, so UDP packets are all treated as
+//! unstaked. Within each window, packets all go through as long as the window budget is not used
+//! up. Past it, each staked sender may still send its reserved share of the budget, proportional
+//! to its stake, and the unstaked senders together share `TPU_QOS_UNSTAKED_SHARE` of it; the
+//! packets beyond are dropped.
+
+use crate::{cluster_info::ClusterInfo, tpu_tcp_stage::TpuTcpStage};
+use crossbeam_channel::{unbounded, RecvTimeoutError, Sender};
+use solana_metrics::registry::{self, Counter};
+use solana_perf::packet::Packets;
+use solana_runtime::bank_forks::BankForks;
+use solana_streamer::streamer::{PacketReceiver, PacketSender};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, RwLock,
+    },
+    thread::{self, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+toml_config::package_config! {
+    TPU_QOS_MAX_PACKETS_PER_SECOND: u64,
+    TPU_QOS_UNSTAKED_SHARE: f64,
+}
+
+const QOS_WINDOW: Duration = Duration::from_millis(100);
+const STAKES_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+const RECV_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Admits the packets of each window by sender, see the module documentation
+struct QosScheduler {
+    max_window_packets: u64,
+    unstaked_share: f64,
+    stakes: HashMap<IpAddr, u64>,
+    total_stake: u64,
+    num_packets: u64,
+    num_staked_packets: HashMap<IpAddr, u64>,
+    num_unstaked_packets: u64,
+}
+
+impl QosScheduler {
+    fn new(max_window_packets: u64, unstaked_share: f64) -> Self {
+        Self {
+            max_window_packets,
+            unstaked_share,
+            stakes: HashMap::new(),
+            total_stake: 0,
+            num_packets: 0,
+            num_staked_packets: HashMap::new(),
+            num_unstaked_packets: 0,
+        }
+    }
+
+    fn set_stakes(&mut self, stakes: HashMap<IpAddr, u64>) {
+        self.total_stake = stakes.values().sum();
+        self.stakes = stakes;
+    }
+
+    /// Starts a new window. Returns whether the window ending was congested.
+    fn start_window(&mut self) -> bool {
+        let congested = self.num_packets > self.max_window_packets;
+        self.num_packets = 0;
+        self.num_staked_packets.clear();
+        self.num_unstaked_packets = 0;
+        congested
+    }
+
+    fn staked_allowance(&self, stake: u64) -> u64 {
+        let staked_packets = self.max_window_packets as f64 * (1.0 - self.unstaked_share);
+        (staked_packets * stake as f64 / self.total_stake.max(1) as f64) as u64
+    }
+
+    fn unstaked_allowance(&self) -> u64 {
+        (self.max_window_packets as f64 * self.unstaked_share) as u64
+    }
+
+    /// Whether the packet from `ip` goes through, and if not whether its sender is staked. The
+    /// sender is only looked up if `authenticated`, that is if `ip` is known not to be forged.
+    fn admit(&mut self, ip: &IpAddr, authenticated: bool) -> Result<(), bool> {
+        self.num_packets += 1;
+        let stake = if authenticated {
+            self.stakes.get(ip)
+        } else {
+            None
+        };
+        let (within_allowance, staked) = match stake {
+            Some(stake) => {
+                let allowance = self.staked_allowance(*stake);
+                let num_packets = self.num_staked_packets.entry(*ip).or_default();
+                *num_packets += 1;
+                (*num_packets <= allowance, true)
+            }
+            None => {
+                self.num_unstaked_packets += 1;
+                (
+                    self.num_unstaked_packets <= self.unstaked_allowance(),
+                    false,
+                )
+            }
+        };
+        if self.num_packets <= self.max_window_packets || within_allowance {
+            Ok(())
+        } else {
+            Err(staked)
+        }
+    }
+}
+
+struct TpuQosStats {
+    packets: Arc<Counter>,
+    dropped_staked: Arc<Counter>,
+    dropped_unstaked: Arc<Counter>,
+    congested_windows: Arc<Counter>,
+}
+
+impl Default for TpuQosStats {
+    fn default() -> Self {
+        let namespace = registry::namespace("tpu_qos");
+        Self {
+            packets: namespace.counter("packets"),
+            dropped_staked: namespace.counter("dropped_staked"),
+            dropped_unstaked: namespace.counter("dropped_unstaked"),
+            congested_windows: namespace.counter("congested_windows"),
+        }
+    }
+}
+
+pub struct TpuQosStage {
+    thread_hdls: Vec<JoinHandle<()>>,
+}
+
+impl TpuQosStage {
+    /// `receiver` carries the packets received over UDP, and `tcp_receiver` the ones received
+    /// over TCP
+    pub fn new(
+        receiver: PacketReceiver,
+        tcp_receiver: PacketReceiver,
+        sender: PacketSender,
+        cluster_info: Arc<ClusterInfo>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let (tagged_sender, tagged_receiver) = unbounded();
+        let udp_thread = Self::spawn_forward(
+            "solana-tpu-qos-udp",
+            receiver,
+            tagged_sender.clone(),
+            false,
+            exit,
+        );
+        let tcp_thread = Self::spawn_forward(
+            "solana-tpu-qos-tcp",
+            tcp_receiver,
+            tagged_sender,
+            true,
+            exit,
+        );
+        let exit = exit.clone();
+        let qos_thread = Builder::new()
+            .name("solana-tpu-qos".to_string())
+            .spawn(move || {
+                let max_window_packets =
+                    CFG.TPU_QOS_MAX_PACKETS_PER_SECOND * QOS_WINDOW.as_millis() as u64 / 1000;
+                let mut scheduler =
+                    QosScheduler::new(max_window_packets, CFG.TPU_QOS_UNSTAKED_SHARE);
+                Self::run(
+                    &tagged_receiver,
+                    &sender,
+                    &cluster_info,
+                    &bank_forks,
+                    &mut scheduler,
+                    &exit,
+                )
+            })
+            .unwrap();
+        Self {
+            thread_hdls: vec![udp_thread, tcp_thread, qos_thread],
+        }
+    }
+
+    /// Forwards the packets of `receiver` to the QoS thread, tagged with whether their source
+    /// addresses are `authenticated`
+    fn spawn_forward(
+        name: &str,
+        receiver: PacketReceiver,
+        sender: Sender<(Packets, bool)>,
+        authenticated: bool,
+        exit: &Arc<AtomicBool>,
+    ) -> JoinHandle<()> {
+        let exit = exit.clone();
+        Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                while !exit.load(Ordering::Relaxed) {
+                    let packets = match receiver.recv_timeout(RECV_TIMEOUT) {
+                        Ok(packets) => packets,
+                        Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    };
+                    if sender.send((packets, authenticated)).is_err() {
+                        break;
+                    }
+                }
+            })
+            .unwrap()
+    }
+
+    fn run(
+        receiver: &crossbeam_channel::Receiver<(Packets, bool)>,
+        sender: &PacketSender,
+        cluster_info: &ClusterInfo,
+        bank_forks: &RwLock<BankForks>,
+        scheduler: &mut QosScheduler,
+        exit: &AtomicBool,
+    ) {
+        let stats = TpuQosStats::default();
+        let mut window_start = Instant::now();
+        let mut last_stakes_refresh: Option<Instant> = None;
+        while !exit.load(Ordering::Relaxed) {
+            let (mut packets, authenticated) = match receiver.recv_timeout(RECV_TIMEOUT) {
+                Ok(packets) => packets,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if last_stakes_refresh.map_or(true, |last| last.elapsed() >= STAKES_REFRESH_INTERVAL) {
+                scheduler.set_stakes(TpuTcpStage::stakes_by_ip(cluster_info, bank_forks));
+                last_stakes_refresh = Some(Instant::now());
+            }
+            if window_start.elapsed() >= QOS_WINDOW {
+                if scheduler.start_window() {
+                    stats.congested_windows.inc();
+                }
+                window_start = Instant::now();
+            }
+            stats.packets.add(packets.packets.len() as u64);
+            let admitted: Vec<bool> = packets
+                .packets
+                .iter()
+                .map(
+                    |packet| match scheduler.admit(&packet.meta.addr().ip(), authenticated) {
+                        Ok(()) => true,
+                        Err(true) => {
+                            stats.dropped_staked.inc();
+                            false
+                        }
+                        Err(false) => {
+                            stats.dropped_unstaked.inc();
+                            false
+                        }
+                    },
+                )
+                .collect();
+            // The batch is only copied when some of its packets are dropped
+            if admitted.iter().any(|admitted| !admitted) {
+                if !admitted.iter().any(|admitted| *admitted) {
+                    continue;
+                }
+                packets = Packets::new(
+                    packets
+                        .packets
+                        .iter()
+                        .zip(&admitted)
+                        .filter(|(_, admitted)| **admitted)
+                        .map(|(packet, _)| packet.clone())
+                        .collect(),
+                );
+            }
+            if sender.send(packets).is_err() {
+                break;
+            }
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        for thread_hdl in self.thread_hdls {
+            thread_hdl.join()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_ledger::genesis_utils::create_genesis_config;
+    use solana_perf::packet::Packet;
+    use solana_runtime::bank::Bank;
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::mpsc::channel,
+    };
+
+    fn ip(i: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, i))
+    }
+
+    #[test]
+    fn test_qos_scheduler() {
+        let mut scheduler = QosScheduler::new(100, 0.2);
+        scheduler.set_stakes(vec![(ip(1), 3), (ip(2), 1)].into_iter().collect());
+        assert_eq!(scheduler.staked_allowance(3), 60);
+        assert_eq!(scheduler.staked_allowance(1), 20);
+        assert_eq!(scheduler.unstaked_allowance(), 20);
+
+        // Unstaked senders flood the window, past the budget their share is dropped
+        let num_admitted = (0..200)
+            .filter(|i| scheduler.admit(&ip(10 + i % 5), true).is_ok())
+            .count();
+        assert_eq!(num_admitted, 100);
+        assert_eq!(scheduler.admit(&ip(10), true), Err(false));
+        // Staked senders still get their share
+        let num_admitted = (0..100)
+            .filter(|_| scheduler.admit(&ip(1), true).is_ok())
+            .count();
+        assert_eq!(num_admitted, 60);
+        assert_eq!(scheduler.admit(&ip(1), true), Err(true));
+        assert_eq!(scheduler.admit(&ip(2), true), Ok(()));
+        // Packets claiming a staked source address over UDP count as unstaked
+        assert_eq!(scheduler.admit(&ip(2), false), Err(false));
+
+        // Within the budget of a new window, everything goes through
+        assert!(scheduler.start_window());
+        assert!((0..100).all(|_| scheduler.admit(&ip(10), true).is_ok()));
+        assert!(!scheduler.start_window());
+    }
+
+    #[test]
+    fn test_tpu_qos_stage() {
+        let genesis_config = create_genesis_config(10_000).genesis_config;
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(Bank::new(&genesis_config))));
+        let cluster_info = Arc::new(ClusterInfo::default());
+        let exit = Arc::new(AtomicBool::new(false));
+        let (qos_sender, qos_receiver) = channel();
+        let (qos_tcp_sender, qos_tcp_receiver) = channel();
+        let (sender, receiver) = channel();
+        let tpu_qos_stage = TpuQosStage::new(
+            qos_receiver,
+            qos_tcp_receiver,
+            sender,
+            cluster_info,
+            bank_forks,
+            &exit,
+        );
+
+        let mut packet = Packet::default();
+        packet.meta.set_addr(&SocketAddr::new(ip(1), 8000));
+        qos_sender
+            .send(Packets::new(vec![packet.clone(); 10]))
+            .unwrap();
+        let packets = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(packets.packets.len(), 10);
+        qos_tcp_sender.send(Packets::new(vec![packet; 5])).unwrap();
+        let packets = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(packets.packets.len(), 5);
+
+        drop(qos_sender);
+        drop(qos_tcp_sender);
+        tpu_qos_stage.join().unwrap();
+    }
+}
@@ -0,0 +1,469 @@
+//! The `tpu_tcp_stage` accepts transactions over TCP on the TPU port, next to the UDP sockets of
+//! the `fetch_stage`, so that clients behind NATs or lossy links can submit transactions without
+//! them being silently dropped. Each transaction is framed by its length, as a little-endian
+//! u16, and forwarded to sigverify like the packets received over UDP.
+//!
+//! Connections are accepted by stake, looked up from the gossip address of the nodes: once the
+//! table is full, a new connection evicts the least staked one if it has more stake, and
+//! unstaked connections are capped separately. Each connection is limited in the number of
+//! transactions it may send per second, by not reading from it any further until the next
+//! second, and is closed once idle.
+
+use crate::cluster_info::ClusterInfo;
+use solana_ledger::staking_utils;
+use solana_metrics::registry::{self, Counter};
+use solana_perf::packet::{Packet, Packets, NUM_RCVMMSGS, PACKET_DATA_SIZE};
+use solana_runtime::bank_forks::BankForks;
+use solana_streamer::streamer::PacketSender;
+use std::{
+    collections::HashMap,
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex, RwLock,
+    },
+    thread::{self, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream},
+    runtime,
+    sync::oneshot,
+    time::{delay_for, timeout},
+};
+
+toml_config::package_config! {
+    TPU_TCP_MAX_CONNECTIONS: usize,
+    TPU_TCP_MAX_UNSTAKED_CONNECTIONS: usize,
+    TPU_TCP_MAX_CONNECTIONS_PER_IP: usize,
+    TPU_TCP_MAX_PACKETS_PER_SECOND: u64,
+    TPU_TCP_IDLE_TIMEOUT_MS: u64,
+}
+
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(1);
+const STAKES_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+struct Connection {
+    ip: IpAddr,
+    stake: u64,
+    evict: oneshot::Sender<()>,
+}
+
+struct ConnectionTable {
+    max_connections: usize,
+    max_unstaked_connections: usize,
+    max_connections_per_ip: usize,
+    next_id: u64,
+    connections: HashMap<u64, Connection>,
+}
+
+impl Default for ConnectionTable {
+    fn default() -> Self {
+        Self::new(
+            CFG.TPU_TCP_MAX_CONNECTIONS,
+            CFG.TPU_TCP_MAX_UNSTAKED_CONNECTIONS,
+            CFG.TPU_TCP_MAX_CONNECTIONS_PER_IP,
+        )
+    }
+}
+
+impl ConnectionTable {
+    fn new(
+        max_connections: usize,
+        max_unstaked_connections: usize,
+        max_connections_per_ip: usize,
+    ) -> Self {
+        Self {
+            max_connections,
+            max_unstaked_connections,
+            max_connections_per_ip,
+            next_id: 0,
+            connections: HashMap::new(),
+        }
+    }
+
+    /// Admits a connection from `ip`, evicting the least staked connection if the table is full
+    /// and it has less stake. Returns the id of the connection and the receiver signaled when
+    /// the connection is evicted, or None if the connection is rejected.
+    fn try_add(&mut self, ip: IpAddr, stake: u64) -> Option<(u64, oneshot::Receiver<()>)> {
+        let num_ip_connections = self
+            .connections
+            .values()
+            .filter(|connection| connection.ip == ip)
+            .count();
+        if num_ip_connections >= self.max_connections_per_ip {
+            return None;
+        }
+        if stake == 0 {
+            let num_unstaked = self
+                .connections
+                .values()
+                .filter(|connection| connection.stake == 0)
+                .count();
+            if num_unstaked >= self.max_unstaked_connections {
+                return None;
+            }
+        }
+        if self.connections.len() >= self.max_connections {
+            let (id, lowest_stake) = self
+                .connections
+                .iter()
+                .map(|(id, connection)| (*id, connection.stake))
+                .min_by_key(|(_, stake)| *stake)?;
+            if lowest_stake >= stake {
+                return None;
+            }
+            let connection = self.connections.remove(&id).unwrap();
+            let _ = connection.evict.send(());
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let (evict, evicted) = oneshot::channel();
+        self.connections.insert(id, Connection { ip, stake, evict });
+        Some((id, evicted))
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.connections.remove(&id);
+    }
+
+    fn len(&self) -> usize {
+        self.connections.len()
+    }
+}
+
+#[derive(Clone)]
+struct TpuTcpStats {
+    accepted: Arc<Counter>,
+    rejected: Arc<Counter>,
+    evicted: Arc<Counter>,
+    packets: Arc<Counter>,
+    rate_limited: Arc<Counter>,
+    invalid_frames: Arc<Counter>,
+}
+
+impl Default for TpuTcpStats {
+    fn default() -> Self {
+        let namespace = registry::namespace("tpu_tcp");
+        Self {
+            accepted: namespace.counter("accepted"),
+            rejected: namespace.counter("rejected"),
+            evicted: namespace.counter("evicted"),
+            packets: namespace.counter("packets"),
+            rate_limited: namespace.counter("rate_limited"),
+            invalid_frames: namespace.counter("invalid_frames"),
+        }
+    }
+}
+
+pub struct TpuTcpStage {
+    thread_hdls: Vec<JoinHandle<()>>,
+}
+
+impl TpuTcpStage {
+    pub fn new(
+        listener: std::net::TcpListener,
+        sender: PacketSender,
+        cluster_info: Arc<ClusterInfo>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let (packet_sender, packet_receiver) = channel();
+        let batch_exit = exit.clone();
+        let batch_thread = Builder::new()
+            .name("solana-tpu-tcp-batch".to_string())
+            .spawn(move || Self::batch_packets(packet_receiver, sender, &batch_exit))
+            .unwrap();
+        let exit = exit.clone();
+        let accept_thread = Builder::new()
+            .name("solana-tpu-tcp".to_string())
+            .spawn(move || {
+                let mut runtime = runtime::Builder::new()
+                    .threaded_scheduler()
+                    .core_threads(2)
+                    .thread_name("tpu-tcp-runtime")
+                    .enable_all()
+                    .build()
+                    .expect("Runtime");
+                runtime.block_on(Self::run(
+                    listener,
+                    packet_sender,
+                    cluster_info,
+                    bank_forks,
+                    exit,
+                ));
+            })
+            .unwrap();
+        Self {
+            thread_hdls: vec![batch_thread, accept_thread],
+        }
+    }
+
+    async fn run(
+        listener: std::net::TcpListener,
+        packet_sender: Sender<Packet>,
+        cluster_info: Arc<ClusterInfo>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        exit: Arc<AtomicBool>,
+    ) {
+        let mut listener = match listener
+            .set_nonblocking(true)
+            .and_then(|_| TcpListener::from_std(listener))
+        {
+            Ok(listener) => listener,
+            Err(err) => {
+                warn!("TPU TCP listener unavailable: {:?}", err);
+                return;
+            }
+        };
+        info!("TPU TCP listening on {:?}", listener.local_addr());
+        let table = Arc::new(Mutex::new(ConnectionTable::default()));
+        let stats = TpuTcpStats::default();
+        let mut stakes = HashMap::new();
+        let mut last_stakes_refresh: Option<Instant> = None;
+        while !exit.load(Ordering::Relaxed) {
+            let (stream, addr) = match timeout(ACCEPT_TIMEOUT, listener.accept()).await {
+                Ok(Ok(accepted)) => accepted,
+                Ok(Err(err)) => {
+                    debug!("TPU TCP accept error: {:?}", err);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            if last_stakes_refresh.map_or(true, |last| last.elapsed() >= STAKES_REFRESH_INTERVAL) {
+                stakes = Self::stakes_by_ip(&cluster_info, &bank_forks);
+                last_stakes_refresh = Some(Instant::now());
+            }
+            let stake = stakes.get(&addr.ip()).copied().unwrap_or_default();
+            let admitted = {
+                let mut table = table.lock().unwrap();
+                let num_connections = table.len();
+                let admitted = table.try_add(addr.ip(), stake);
+                if admitted.is_some() && table.len() == num_connections {
+                    stats.evicted.inc();
+                }
+                admitted
+            };
+            let (id, evicted) = match admitted {
+                Some(admitted) => admitted,
+                None => {
+                    stats.rejected.inc();
+                    continue;
+                }
+            };
+            stats.accepted.inc();
+            let table = table.clone();
+            let packet_sender = packet_sender.clone();
+            let stats = stats.clone();
+            let exit = exit.clone();
+            tokio::spawn(async move {
+                Self::handle_connection(stream, addr, evicted, packet_sender, &stats, &exit).await;
+                table.lock().unwrap().remove(id);
+            });
+        }
+    }
+
+    async fn handle_connection(
+        mut stream: TcpStream,
+        addr: SocketAddr,
+        mut evicted: oneshot::Receiver<()>,
+        packet_sender: Sender<Packet>,
+        stats: &TpuTcpStats,
+        exit: &AtomicBool,
+    ) {
+        let idle_timeout = Duration::from_millis(CFG.TPU_TCP_IDLE_TIMEOUT_MS);
+        let mut window_start = Instant::now();
+        let mut window_packets = 0;
+        while !exit.load(Ordering::Relaxed) {
+            let packet = tokio::select! {
+                _ = &mut evicted => break,
+                packet = timeout(idle_timeout, read_packet(&mut stream, &addr)) => packet,
+            };
+            let packet = match packet {
+                Ok(Ok(Some(packet))) => packet,
+                Ok(Err(err)) if err.kind() == io::ErrorKind::InvalidData => {
+                    stats.invalid_frames.inc();
+                    break;
+                }
+                // Closed by the client, timed out or failed
+                _ => break,
+            };
+            if window_start.elapsed() >= RATE_LIMIT_WINDOW {
+                window_start = Instant::now();
+                window_packets = 0;
+            }
+            window_packets += 1;
+            if window_packets > CFG.TPU_TCP_MAX_PACKETS_PER_SECOND {
+                // Stop reading, so that the client is slowed down by TCP flow control
+                stats.rate_limited.inc();
+                delay_for(
+                    RATE_LIMIT_WINDOW
+                        .checked_sub(window_start.elapsed())
+                        .unwrap_or_default(),
+                )
+                .await;
+                window_start = Instant::now();
+                window_packets = 1;
+            }
+            stats.packets.inc();
+            if packet_sender.send(packet).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Forwards the packets received over all connections to sigverify, in batches
+    fn batch_packets(receiver: Receiver<Packet>, sender: PacketSender, exit: &AtomicBool) {
+        while !exit.load(Ordering::Relaxed) {
+            let packet = match receiver.recv_timeout(Duration::from_secs(1)) {
+                Ok(packet) => packet,
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            let mut packets = Packets::default();
+            packets.packets.push(packet);
+            while packets.packets.len() < NUM_RCVMMSGS {
+                match receiver.try_recv() {
+                    Ok(packet) => packets.packets.push(packet),
+                    Err(_) => break,
+                }
+            }
+            if sender.send(packets).is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Stake of the nodes by the IP address of their gossip socket
+    pub(crate) fn stakes_by_ip(
+        cluster_info: &ClusterInfo,
+        bank_forks: &RwLock<BankForks>,
+    ) -> HashMap<IpAddr, u64> {
+        let stakes = staking_utils::staked_nodes(&bank_forks.read().unwrap().root_bank());
+        let mut stakes_by_ip = HashMap::new();
+        for node in cluster_info.all_tvu_peers() {
+            if let Some(stake) = stakes.get(&node.id) {
+                let entry = stakes_by_ip.entry(node.gossip.ip()).or_default();
+                *entry = (*entry).max(*stake);
+            }
+        }
+        stakes_by_ip
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        for thread_hdl in self.thread_hdls {
+            thread_hdl.join()?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a length-prefixed transaction. Returns None once the client closed the connection.
+async fn read_packet(stream: &mut TcpStream, addr: &SocketAddr) -> io::Result<Option<Packet>> {
+    let mut size = [0u8; 2];
+    match stream.read_exact(&mut size).await {
+        Ok(_) => (),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let size = u16::from_le_bytes(size) as usize;
+    if size == 0 || size > PACKET_DATA_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid transaction size: {}", size),
+        ));
+    }
+    let mut packet = Packet::default();
+    stream.read_exact(&mut packet.data[..size]).await?;
+    packet.meta.size = size;
+    packet.meta.set_addr(addr);
+    Ok(Some(packet))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_ledger::genesis_utils::create_genesis_config;
+    use solana_runtime::bank::Bank;
+    use std::{
+        io::Write,
+        net::{Ipv4Addr, TcpStream as StdTcpStream},
+    };
+
+    #[test]
+    fn test_connection_table() {
+        let ip = |i: u8| IpAddr::V4(Ipv4Addr::new(10, 0, 0, i));
+        let mut table = ConnectionTable::new(4, 1, 2);
+        let (unstaked, _) = table.try_add(ip(1), 0).unwrap();
+        // Unstaked connections are capped
+        assert!(table.try_add(ip(2), 0).is_none());
+        table.remove(unstaked);
+        let (_, mut unstaked_evicted) = table.try_add(ip(2), 0).unwrap();
+        // So are connections per IP
+        let (_, mut low_stake_evicted) = table.try_add(ip(3), 10).unwrap();
+        let (_, _) = table.try_add(ip(3), 20).unwrap();
+        assert!(table.try_add(ip(3), 30).is_none());
+        let (_, _) = table.try_add(ip(4), 30).unwrap();
+        assert_eq!(table.len(), 4);
+
+        // A new connection evicts the least staked one, only if it has more stake
+        assert!(table.try_add(ip(5), 0).is_none());
+        let (_, _) = table.try_add(ip(5), 5).unwrap();
+        assert_eq!(table.len(), 4);
+        assert_eq!(unstaked_evicted.try_recv(), Ok(()));
+        let (_, _) = table.try_add(ip(6), 15).unwrap();
+        assert_eq!(table.len(), 4);
+        assert_eq!(
+            low_stake_evicted.try_recv(),
+            Err(oneshot::error::TryRecvError::Empty)
+        );
+        assert!(table.try_add(ip(6), 1).is_none());
+    }
+
+    #[test]
+    fn test_tpu_tcp_stage() {
+        solana_logger::setup();
+        let genesis_config = create_genesis_config(10_000).genesis_config;
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(Bank::new(&genesis_config))));
+        let cluster_info = Arc::new(ClusterInfo::default());
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let exit = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = channel();
+        let tpu_tcp_stage = TpuTcpStage::new(listener, sender, cluster_info, bank_forks, &exit);
+
+        let mut stream = StdTcpStream::connect(addr).unwrap();
+        for data in &[vec![1u8; 100], vec![2u8; PACKET_DATA_SIZE]] {
+            stream
+                .write_all(&(data.len() as u16).to_le_bytes())
+                .unwrap();
+            stream.write_all(data).unwrap();
+        }
+        let mut packets = vec![];
+        while packets.len() < 2 {
+            let batch = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            packets.extend(batch.packets.iter().cloned());
+        }
+        assert_eq!(packets[0].meta.size, 100);
+        assert_eq!(&packets[0].data[..100], &[1u8; 100][..]);
+        assert_eq!(packets[1].meta.size, PACKET_DATA_SIZE);
+        assert_eq!(
+            packets[1].meta.addr().ip(),
+            stream.local_addr().unwrap().ip()
+        );
+
+        // Oversized frames close the connection
+        stream
+            .write_all(&(PACKET_DATA_SIZE as u16 + 1).to_le_bytes())
+            .unwrap();
+        stream.write_all(&[0u8; 8]).unwrap();
+        assert!(receiver.recv_timeout(Duration::from_millis(500)).is_err());
+
+        exit.store(true, Ordering::Relaxed);
+        tpu_tcp_stage.join().unwrap();
+    }
+}
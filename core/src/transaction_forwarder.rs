@@ -0,0 +1,229 @@
+//! The `transaction_forwarder` module forwards the transactions buffered by a banking thread to
+//! the next `FORWARD_TRANSACTIONS_NUM_LEADERS` scheduled leaders. The first leader is sent the
+//! transactions right away and each following leader `FORWARD_TRANSACTIONS_LEADER_STAGGER_MS`
+//! after the previous one, so that transactions submitted near a slot boundary still land with
+//! the next leader. A transaction recently forwarded to a leader is not forwarded to it again.
+
+use crate::cluster_info::ClusterInfo;
+use solana_perf::packet::Packet;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet, VecDeque},
+    hash::{Hash, Hasher},
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+toml_config::package_config! {
+    FORWARD_TRANSACTIONS_NUM_LEADERS: usize,
+    FORWARD_TRANSACTIONS_LEADER_STAGGER_MS: u64,
+}
+
+// Number of (leader, transaction) pairs remembered to deduplicate forwarded transactions
+const MAX_RECENTLY_FORWARDED: usize = 200_000;
+
+struct PendingForward {
+    send_at: Instant,
+    addr: SocketAddr,
+    packets: Vec<Vec<u8>>,
+}
+
+pub struct TransactionForwarder {
+    socket: UdpSocket,
+    stagger: Duration,
+    // Transactions due to be sent to the later leaders
+    pending: Vec<PendingForward>,
+    recently_forwarded: HashSet<(Pubkey, u64)>,
+    recently_forwarded_order: VecDeque<(Pubkey, u64)>,
+}
+
+impl TransactionForwarder {
+    pub fn new(socket: UdpSocket) -> Self {
+        Self::new_with_stagger(
+            socket,
+            Duration::from_millis(CFG.FORWARD_TRANSACTIONS_LEADER_STAGGER_MS),
+        )
+    }
+
+    pub fn new_with_stagger(socket: UdpSocket, stagger: Duration) -> Self {
+        Self {
+            socket,
+            stagger,
+            pending: Vec::new(),
+            recently_forwarded: HashSet::new(),
+            recently_forwarded_order: VecDeque::new(),
+        }
+    }
+
+    /// Number of upcoming leaders the transactions are forwarded to
+    pub fn num_leaders() -> usize {
+        CFG.FORWARD_TRANSACTIONS_NUM_LEADERS.max(1)
+    }
+
+    /// Forwards `packets` to the tpu_forwards address of `leaders`, in order. Returns false,
+    /// without forwarding anything, if the contact info of none of the leaders is known.
+    pub fn forward(
+        &mut self,
+        cluster_info: &ClusterInfo,
+        leaders: &[Pubkey],
+        packets: &[&Packet],
+    ) -> bool {
+        let mut addrs: Vec<(Pubkey, SocketAddr)> = Vec::with_capacity(leaders.len());
+        for leader in leaders {
+            if let Some(addr) =
+                cluster_info.lookup_contact_info(leader, |leader| leader.tpu_forwards)
+            {
+                if addrs.iter().all(|(_, other)| *other != addr) {
+                    addrs.push((*leader, addr));
+                }
+            }
+        }
+        if addrs.is_empty() {
+            return false;
+        }
+        let hashes: Vec<u64> = packets.iter().map(|packet| packet_hash(packet)).collect();
+        let now = Instant::now();
+        for (i, (leader, addr)) in addrs.into_iter().enumerate() {
+            let packets: Vec<&Packet> = packets
+                .iter()
+                .zip(&hashes)
+                .filter(|(_, hash)| self.insert_recently_forwarded(leader, **hash))
+                .map(|(packet, _)| *packet)
+                .collect();
+            inc_new_counter_info!("banking_stage-forwarded_packets", packets.len());
+            inc_new_counter_info!(
+                "banking_stage-duplicate_forwarded_packets",
+                hashes.len() - packets.len()
+            );
+            if packets.is_empty() {
+                continue;
+            }
+            if i == 0 {
+                for packet in packets {
+                    let _ = self.socket.send_to(&packet.data[..packet.meta.size], &addr);
+                }
+            } else {
+                self.pending.push(PendingForward {
+                    send_at: now + self.stagger * i as u32,
+                    addr,
+                    packets: packets
+                        .iter()
+                        .map(|packet| packet.data[..packet.meta.size].to_vec())
+                        .collect(),
+                });
+            }
+        }
+        true
+    }
+
+    /// Sends the forwarded transactions which are due to the later leaders
+    pub fn send_pending(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let socket = &self.socket;
+        self.pending.retain(|pending| {
+            if pending.send_at > now {
+                return true;
+            }
+            for data in &pending.packets {
+                let _ = socket.send_to(data, &pending.addr);
+            }
+            false
+        });
+    }
+
+    // Returns false if the packet was recently forwarded to the leader
+    fn insert_recently_forwarded(&mut self, leader: Pubkey, hash: u64) -> bool {
+        if !self.recently_forwarded.insert((leader, hash)) {
+            return false;
+        }
+        self.recently_forwarded_order.push_back((leader, hash));
+        if self.recently_forwarded_order.len() > MAX_RECENTLY_FORWARDED {
+            let oldest = self.recently_forwarded_order.pop_front().unwrap();
+            self.recently_forwarded.remove(&oldest);
+        }
+        true
+    }
+}
+
+fn packet_hash(packet: &Packet) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    packet.data[..packet.meta.size].hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contact_info::ContactInfo;
+    use solana_sdk::timing::timestamp;
+    use std::thread::sleep;
+
+    fn new_packet(data: &[u8]) -> Packet {
+        let mut packet = Packet::default();
+        packet.data[..data.len()].copy_from_slice(data);
+        packet.meta.size = data.len();
+        packet
+    }
+
+    fn recv_all(socket: &UdpSocket) -> Vec<Vec<u8>> {
+        let mut buf = [0u8; 64];
+        let mut received = vec![];
+        while let Ok(size) = socket.recv(&mut buf) {
+            received.push(buf[..size].to_vec());
+        }
+        received
+    }
+
+    #[test]
+    fn test_forward_to_upcoming_leaders() {
+        let cluster_info = ClusterInfo::new_with_invalid_keypair(ContactInfo::new_localhost(
+            &solana_sdk::pubkey::new_rand(),
+            timestamp(),
+        ));
+        let leaders: Vec<_> = std::iter::repeat_with(solana_sdk::pubkey::new_rand)
+            .take(3)
+            .collect();
+        let sockets: Vec<_> = leaders
+            .iter()
+            .take(2)
+            .map(|leader| {
+                let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+                socket
+                    .set_read_timeout(Some(Duration::from_millis(100)))
+                    .unwrap();
+                let mut contact_info = ContactInfo::new_localhost(leader, timestamp());
+                contact_info.tpu_forwards = socket.local_addr().unwrap();
+                cluster_info.insert_info(contact_info);
+                socket
+            })
+            .collect();
+
+        let mut forwarder = TransactionForwarder::new_with_stagger(
+            UdpSocket::bind("127.0.0.1:0").unwrap(),
+            Duration::from_millis(200),
+        );
+        // The contact info of the third leader is not known
+        assert!(!forwarder.forward(&cluster_info, &leaders[2..], &[&new_packet(&[1])]));
+
+        let packets = vec![new_packet(&[1]), new_packet(&[2])];
+        let packets: Vec<_> = packets.iter().collect();
+        assert!(forwarder.forward(&cluster_info, &leaders, &packets));
+        assert_eq!(recv_all(&sockets[0]), vec![vec![1], vec![2]]);
+        // The second leader is sent the transactions later
+        forwarder.send_pending();
+        assert!(recv_all(&sockets[1]).is_empty());
+        sleep(Duration::from_millis(200));
+        forwarder.send_pending();
+        assert!(forwarder.pending.is_empty());
+        assert_eq!(recv_all(&sockets[1]), vec![vec![1], vec![2]]);
+
+        // Transactions already forwarded to a leader are not forwarded again
+        let packets = vec![new_packet(&[2]), new_packet(&[3])];
+        let packets: Vec<_> = packets.iter().collect();
+        assert!(forwarder.forward(&cluster_info, &leaders[..1], &packets));
+        assert_eq!(recv_all(&sockets[0]), vec![vec![3]]);
+    }
+}
@@ -0,0 +1,270 @@
+//! The `turbine_tree` module derives the retransmit tree of a shred deterministically. The nodes
+//! of the tree are sorted by stake and pubkey and shuffled with a seed derived from the
+//! (slot, shred index, leader) of the shred, so that all nodes derive the same neighbors and
+//! children for the shred regardless of the order in which they learned about their peers.
+//! The leader broadcasts the shred to the root of the same tree.
+//!
+//! Until the `turbine_tree` feature is active, shreds are broadcast to a stake weighted peer and
+//! retransmitted along the trees shuffled with the seed of the shred, see
+//! `retransmit_peers_at_index`.
+
+use crate::{
+    cluster_info::{compute_retransmit_peers, ClusterInfo},
+    weighted_shuffle::weighted_shuffle,
+};
+use solana_ledger::shred::{OFFSET_OF_SHRED_INDEX, SIZE_OF_SHRED_INDEX};
+use solana_perf::packet::{limited_deserialize, Packet};
+use solana_sdk::{clock::Slot, hash::hashv, pubkey::Pubkey};
+use std::collections::{HashMap, HashSet};
+
+/// Seed of the shuffle of the retransmit tree of a shred
+pub fn shred_seed(slot: Slot, index: u32, leader: &Pubkey) -> [u8; 32] {
+    hashv(&[&slot.to_le_bytes(), &index.to_le_bytes(), leader.as_ref()]).to_bytes()
+}
+
+/// Reads the index of the shred in `packet`
+pub fn get_shred_index(packet: &Packet) -> Option<u32> {
+    let index_end = OFFSET_OF_SHRED_INDEX + SIZE_OF_SHRED_INDEX;
+    if index_end > packet.meta.size {
+        return None;
+    }
+    limited_deserialize(&packet.data[OFFSET_OF_SHRED_INDEX..index_end]).ok()
+}
+
+/// Peers a node retransmits a shred to
+#[derive(Debug, PartialEq)]
+pub struct RetransmitPeers<T = Pubkey> {
+    pub neighbors: Vec<T>,
+    pub children: Vec<T>,
+    /// Whether the neighbors are in the last layer of the tree, so that they have no children to
+    /// retransmit the shred to
+    pub neighbors_are_leaves: bool,
+    /// Whether the children are in the last layer of the tree
+    pub children_are_leaves: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct TurbineTree {
+    // Stake and pubkey of the nodes of the tree, in descending order
+    nodes: Vec<(u64, Pubkey)>,
+}
+
+impl TurbineTree {
+    /// The tree holds `nodes` and every staked node of `stakes`, whether or not its contact info
+    /// is known locally, so that nodes with a partial view of the cluster still agree on the
+    /// position of the staked nodes.
+    pub fn new<I>(nodes: I, stakes: Option<&HashMap<Pubkey, u64>>) -> Self
+    where
+        I: IntoIterator<Item = Pubkey>,
+    {
+        let staked_nodes = stakes
+            .into_iter()
+            .flatten()
+            .filter(|(_, stake)| **stake > 0)
+            .map(|(node, _)| *node);
+        let nodes: HashSet<_> = nodes.into_iter().chain(staked_nodes).collect();
+        // For stake weighted shuffle a valid weight is at least 1
+        let stake = |node: &Pubkey| {
+            stakes
+                .and_then(|stakes| stakes.get(node))
+                .copied()
+                .unwrap_or_default()
+                .max(1)
+        };
+        let mut nodes: Vec<_> = nodes.into_iter().map(|node| (stake(&node), node)).collect();
+        nodes.sort_unstable_by(|a, b| b.cmp(a));
+        Self { nodes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // The nodes of the tree of the shred seeded by `seed`, in shuffled order. The leader of the
+    // slot is left out of the tree, since it already has the shred.
+    fn shuffle(&self, leader: &Pubkey, seed: [u8; 32]) -> Vec<Pubkey> {
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node != leader)
+            .collect();
+        let weights = nodes.iter().map(|(stake, _)| *stake).collect();
+        weighted_shuffle(weights, seed)
+            .into_iter()
+            .map(|index| nodes[index].1)
+            .collect()
+    }
+
+    /// Returns the peers of `id` in the retransmit tree of the shred seeded by `seed`, see
+    /// `shred_seed`, or None if `id` is not a node of the tree.
+    pub fn get_retransmit_peers(
+        &self,
+        id: &Pubkey,
+        leader: &Pubkey,
+        seed: [u8; 32],
+        fanout: usize,
+    ) -> Option<RetransmitPeers> {
+        let mut shuffle = self.shuffle(leader, seed);
+        let my_index = shuffle.iter().position(|node| node == id)?;
+        shuffle.remove(my_index);
+        Some(retransmit_peers_at_index(my_index, shuffle, fanout))
+    }
+
+    /// Returns the node the leader broadcasts the shred seeded by `seed` to: the root of its
+    /// retransmit tree, or else the first node of the tree for which `is_known` holds, which
+    /// retransmits the shred to the first layer as the root would.
+    pub fn get_broadcast_peer<F>(
+        &self,
+        leader: &Pubkey,
+        seed: [u8; 32],
+        is_known: F,
+    ) -> Option<Pubkey>
+    where
+        F: Fn(&Pubkey) -> bool,
+    {
+        self.shuffle(leader, seed).into_iter().find(is_known)
+    }
+}
+
+/// Peers of the node at `my_index` of a retransmit tree, given the other nodes of the tree in
+/// shuffled order
+pub fn retransmit_peers_at_index<T: Copy>(
+    my_index: usize,
+    shuffle: Vec<T>,
+    fanout: usize,
+) -> RetransmitPeers<T> {
+    let (num_layers, layer_indices) = ClusterInfo::describe_data_plane(shuffle.len(), fanout);
+    let my_layer = layer_indices
+        .iter()
+        .rposition(|layer_start| *layer_start <= my_index)
+        .unwrap_or_default();
+    let indexes = (0..shuffle.len()).collect();
+    let (neighbors, children) = compute_retransmit_peers(fanout, my_index, indexes);
+    let nodes = |indexes: Vec<usize>| indexes.into_iter().map(|index| shuffle[index]).collect();
+    RetransmitPeers {
+        neighbors: nodes(neighbors),
+        children: nodes(children),
+        neighbors_are_leaves: my_layer + 1 >= num_layers,
+        children_are_leaves: my_layer + 2 >= num_layers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_ledger::shred::Shred;
+
+    #[test]
+    fn test_get_shred_index() {
+        let shred = Shred::new_from_data(5, 42, 0, None, true, true, 0, 0, 0);
+        let mut packet = Packet::default();
+        shred.copy_to_packet(&mut packet);
+        assert_eq!(get_shred_index(&packet), Some(42));
+        packet.meta.size = OFFSET_OF_SHRED_INDEX;
+        assert_eq!(get_shred_index(&packet), None);
+    }
+
+    #[test]
+    fn test_retransmit_peers_are_deterministic() {
+        let nodes: Vec<_> = std::iter::repeat_with(solana_sdk::pubkey::new_rand)
+            .take(200)
+            .collect();
+        let stakes: HashMap<_, _> = nodes
+            .iter()
+            .take(50)
+            .enumerate()
+            .map(|(i, node)| (*node, i as u64 + 1))
+            .collect();
+        let leader = nodes[10];
+        let tree = TurbineTree::new(nodes.iter().copied(), Some(&stakes));
+        assert_eq!(tree.len(), nodes.len());
+
+        // Another node which learned about its peers in a different order, and does not know
+        // the contact infos of some staked nodes
+        let other_tree = TurbineTree::new(nodes.iter().skip(20).rev().copied(), Some(&stakes));
+        assert_eq!(other_tree.len(), nodes.len());
+
+        for index in 0..10 {
+            let seed = shred_seed(7, index, &leader);
+            for node in &nodes {
+                let peers = tree.get_retransmit_peers(node, &leader, seed, 10);
+                assert_eq!(
+                    peers,
+                    other_tree.get_retransmit_peers(node, &leader, seed, 10)
+                );
+                if *node == leader {
+                    assert_eq!(peers, None);
+                } else {
+                    let peers = peers.unwrap();
+                    assert!(!peers.neighbors.contains(&leader));
+                    assert!(!peers.children.contains(&leader));
+                    // Nodes of the last layer have no children
+                    if peers.neighbors_are_leaves {
+                        assert!(peers.children.is_empty());
+                        assert!(peers.children_are_leaves);
+                    }
+                }
+            }
+        }
+        let unknown = solana_sdk::pubkey::new_rand();
+        assert_eq!(
+            tree.get_retransmit_peers(&unknown, &leader, shred_seed(7, 0, &leader), 10),
+            None
+        );
+    }
+
+    #[test]
+    fn test_broadcast_peer() {
+        let nodes: Vec<_> = std::iter::repeat_with(solana_sdk::pubkey::new_rand)
+            .take(200)
+            .collect();
+        let stakes: HashMap<_, _> = nodes
+            .iter()
+            .take(50)
+            .enumerate()
+            .map(|(i, node)| (*node, i as u64 + 1))
+            .collect();
+        let leader = nodes[10];
+        let tree = TurbineTree::new(nodes.iter().copied(), Some(&stakes));
+        let other_tree = TurbineTree::new(nodes.iter().skip(20).rev().copied(), Some(&stakes));
+        for index in 0..10 {
+            let seed = shred_seed(7, index, &leader);
+            let root = tree.get_broadcast_peer(&leader, seed, |_| true).unwrap();
+            assert_ne!(root, leader);
+            assert_eq!(
+                other_tree.get_broadcast_peer(&leader, seed, |_| true),
+                Some(root)
+            );
+            // The shred goes to the next node of the first layer if the contact info of the
+            // root is not known
+            let other = tree
+                .get_broadcast_peer(&leader, seed, |node| *node != root)
+                .unwrap();
+            assert_ne!(other, root);
+            let peers = tree.get_retransmit_peers(&root, &leader, seed, 10).unwrap();
+            assert!(peers.neighbors.contains(&other));
+            assert_eq!(tree.get_broadcast_peer(&leader, seed, |_| false), None);
+        }
+    }
+
+    #[test]
+    fn test_retransmit_peers_at_index() {
+        let shuffle: Vec<u64> = (0..30).collect();
+        let peers = retransmit_peers_at_index(0, shuffle.clone(), 10);
+        let (neighbors, children) = compute_retransmit_peers(10, 0, shuffle);
+        assert_eq!(
+            peers.neighbors,
+            neighbors.iter().map(|x| *x as u64).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            peers.children,
+            children.iter().map(|x| *x as u64).collect::<Vec<_>>()
+        );
+        assert!(!peers.neighbors_are_leaves);
+        assert!(peers.children_are_leaves);
+    }
+}
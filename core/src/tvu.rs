@@ -2,7 +2,8 @@
 //! validation pipeline in software.
 
 use crate::{
-    accounts_hash_verifier::AccountsHashVerifier,
+    accounts_hash_verifier::{AccountsHashVerification, AccountsHashVerifier},
+    ancestor_hashes_service::AncestorHashesService,
     broadcast_stage::RetransmitSlotsSender,
     cache_block_time_service::CacheBlockTimeSender,
     cluster_info::ClusterInfo,
@@ -10,7 +11,8 @@ use crate::{
     cluster_slots::ClusterSlots,
     completed_data_sets_service::CompletedDataSetsSender,
     consensus::Tower,
-    ledger_cleanup_service::LedgerCleanupService,
+    ledger_cleanup_service::{LedgerCleanupService, TransactionStatusRetention},
+    link_conditioner::NodeLinkConditioner,
     optimistically_confirmed_bank_tracker::BankNotificationSender,
     poh_recorder::PohRecorder,
     replay_stage::{ReplayStage, ReplayStageConfig},
@@ -20,6 +22,7 @@ use crate::{
     shred_fetch_stage::ShredFetchStage,
     sigverify_shreds::ShredSigVerifier,
     sigverify_stage::SigVerifyStage,
+    tower_storage::TowerStorage,
 };
 use crossbeam_channel::unbounded;
 use solana_ledger::{
@@ -28,7 +31,9 @@ use solana_ledger::{
     leader_schedule_cache::LeaderScheduleCache,
 };
 use solana_runtime::{
-    accounts_background_service::{AccountsBackgroundService, SnapshotRequestHandler},
+    accounts_background_service::{
+        AccountsBackgroundConfig, AccountsBackgroundService, SnapshotRequestHandler,
+    },
     bank_forks::{BankForks, SnapshotConfig},
     commitment::BlockCommitmentCache,
     snapshot_package::AccountsPackageSender,
@@ -57,6 +62,7 @@ pub struct Tvu {
     ledger_cleanup_service: Option<LedgerCleanupService>,
     accounts_background_service: AccountsBackgroundService,
     accounts_hash_verifier: AccountsHashVerifier,
+    ancestor_hashes_service: AncestorHashesService,
 }
 
 pub struct Sockets {
@@ -64,16 +70,21 @@ pub struct Sockets {
     pub repair: UdpSocket,
     pub retransmit: Vec<UdpSocket>,
     pub forwards: Vec<UdpSocket>,
+    pub ancestor_hashes_requests: UdpSocket,
 }
 
 #[derive(Default)]
 pub struct TvuConfig {
     pub max_ledger_shreds: Option<u64>,
+    pub transaction_status_retention: TransactionStatusRetention,
     pub shred_version: u16,
     pub halt_on_trusted_validators_accounts_hash_mismatch: bool,
     pub trusted_validators: Option<HashSet<Pubkey>>,
     pub repair_validators: Option<HashSet<Pubkey>>,
     pub accounts_hash_fault_injection_slots: u64,
+    pub accounts_background_config: AccountsBackgroundConfig,
+    pub link_conditioner: Option<NodeLinkConditioner>,
+    pub accounts_hash_verification: Arc<RwLock<AccountsHashVerification>>,
 }
 
 impl Tvu {
@@ -95,6 +106,7 @@ impl Tvu {
         subscriptions: &Arc<RpcSubscriptions>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         tower: Tower,
+        tower_storage: Arc<dyn TowerStorage>,
         leader_schedule_cache: &Arc<LeaderScheduleCache>,
         exit: &Arc<AtomicBool>,
         completed_slots_receiver: CompletedSlotsReceiver,
@@ -119,6 +131,7 @@ impl Tvu {
             fetch: fetch_sockets,
             retransmit: retransmit_sockets,
             forwards: tvu_forward_sockets,
+            ancestor_hashes_requests: ancestor_hashes_socket,
         } = sockets;
 
         let (fetch_sender, fetch_receiver) = channel();
@@ -133,6 +146,7 @@ impl Tvu {
             repair_socket.clone(),
             &fetch_sender,
             Some(bank_forks.clone()),
+            tvu_config.link_conditioner.as_ref(),
             &exit,
         );
 
@@ -163,6 +177,15 @@ impl Tvu {
             verified_vote_receiver,
             tvu_config.repair_validators,
             completed_data_sets_sender,
+            Some(subscriptions.clone()),
+        );
+
+        let ancestor_hashes_service = AncestorHashesService::new(
+            blockstore.clone(),
+            cluster_info.clone(),
+            ancestor_hashes_socket,
+            tvu_config.trusted_validators.clone(),
+            exit,
         );
 
         let (ledger_cleanup_slot_sender, ledger_cleanup_slot_receiver) = channel();
@@ -190,6 +213,7 @@ impl Tvu {
             tvu_config.halt_on_trusted_validators_accounts_hash_mismatch,
             tvu_config.accounts_hash_fault_injection_slots,
             snapshot_interval_slots,
+            tvu_config.accounts_hash_verification.clone(),
         );
 
         let (snapshot_request_sender, snapshot_request_handler) = {
@@ -222,6 +246,7 @@ impl Tvu {
             rewards_recorder_sender,
             cache_block_time_sender,
             bank_notification_sender,
+            tower_storage,
         };
 
         let replay_stage = ReplayStage::new(
@@ -239,17 +264,26 @@ impl Tvu {
             replay_vote_sender,
         );
 
-        let ledger_cleanup_service = tvu_config.max_ledger_shreds.map(|max_ledger_shreds| {
-            LedgerCleanupService::new(
+        let ledger_cleanup_service = if tvu_config.max_ledger_shreds.is_some()
+            || tvu_config.transaction_status_retention.is_enabled()
+        {
+            Some(LedgerCleanupService::new(
                 ledger_cleanup_slot_receiver,
                 blockstore.clone(),
-                max_ledger_shreds,
+                tvu_config.max_ledger_shreds,
+                tvu_config.transaction_status_retention,
                 &exit,
-            )
-        });
+            ))
+        } else {
+            None
+        };
 
-        let accounts_background_service =
-            AccountsBackgroundService::new(bank_forks.clone(), &exit, snapshot_request_handler);
+        let accounts_background_service = AccountsBackgroundService::new(
+            bank_forks.clone(),
+            &exit,
+            snapshot_request_handler,
+            tvu_config.accounts_background_config,
+        );
 
         Tvu {
             fetch_stage,
@@ -259,6 +293,7 @@ impl Tvu {
             ledger_cleanup_service,
             accounts_background_service,
             accounts_hash_verifier,
+            ancestor_hashes_service,
         }
     }
 
@@ -272,6 +307,7 @@ impl Tvu {
         self.accounts_background_service.join()?;
         self.replay_stage.join()?;
         self.accounts_hash_verifier.join()?;
+        self.ancestor_hashes_service.join()?;
         Ok(())
     }
 }
@@ -283,6 +319,7 @@ pub mod tests {
         banking_stage::create_test_recorder,
         cluster_info::{ClusterInfo, Node},
         optimistically_confirmed_bank_tracker::OptimisticallyConfirmedBank,
+        tower_storage::{FileTowerStorage, TowerSyncMode},
     };
     use serial_test_derive::serial;
     use solana_ledger::{
@@ -344,6 +381,7 @@ pub mod tests {
                     retransmit: target1.sockets.retransmit_sockets,
                     fetch: target1.sockets.tvu,
                     forwards: target1.sockets.tvu_forwards,
+                    ancestor_hashes_requests: target1.sockets.ancestor_hashes_requests,
                 }
             },
             blockstore,
@@ -356,6 +394,10 @@ pub mod tests {
             )),
             &poh_recorder,
             tower,
+            Arc::new(FileTowerStorage::new(
+                blockstore_path.clone(),
+                TowerSyncMode::None,
+            )),
             &leader_schedule_cache,
             &exit,
             completed_slots_receiver,
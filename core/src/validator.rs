@@ -1,55 +1,79 @@
 //! The `validator` module hosts all the validator microservices.
 
 use crate::{
+    accounts_hash_verifier::AccountsHashVerification,
+    bootstrap_rpc_service::{BootstrapProgress, BootstrapRpcService},
     broadcast_stage::BroadcastStageType,
     cache_block_time_service::{CacheBlockTimeSender, CacheBlockTimeService},
-    cluster_info::{ClusterInfo, Node},
+    cluster_info::{ClusterInfo, Node, CRDS_CACHE_FILE},
     cluster_info_vote_listener::VoteTracker,
     completed_data_sets_service::CompletedDataSetsService,
     consensus::{reconcile_blockstore_roots_with_tower, Tower},
     contact_info::ContactInfo,
+    crds_gossip_push::PushConfig,
+    crds_value::{
+        CAPABILITY_GOSSIP_COMPRESSION, CAPABILITY_NETWORK_NAMESPACE, CAPABILITY_REPAIR_SHRED_RANGE,
+        CAPABILITY_RPC_METHODS, CAPABILITY_SNAPSHOTS, CAPABILITY_TPU_TCP, CAPABILITY_TPU_VOTE,
+    },
+    gossip_compression::GOSSIP_COMPRESSION_CODEC,
     gossip_service::GossipService,
+    ledger_cleanup_service::TransactionStatusRetention,
+    link_conditioner::{LinkConditioner, NodeLinkConditioner},
     optimistically_confirmed_bank_tracker::{
         OptimisticallyConfirmedBank, OptimisticallyConfirmedBankTracker,
     },
+    planned_hard_fork_service::PlannedHardForkService,
     poh_recorder::{PohRecorder, CFG as POH_CFG},
     poh_service::PohService,
+    prometheus_service::PrometheusService,
     rewards_recorder_service::{RewardsRecorderSender, RewardsRecorderService},
     rpc::JsonRpcConfig,
     rpc_pubsub_service::{PubSubConfig, PubSubService},
     rpc_service::JsonRpcService,
     rpc_subscriptions::RpcSubscriptions,
-    sample_performance_service::SamplePerformanceService,
+    sample_performance_service::{SamplePerformanceConfig, SamplePerformanceService},
     serve_repair::ServeRepair,
     serve_repair_service::ServeRepairService,
     sigverify,
     snapshot_packager_service::SnapshotPackagerService,
+    supermajority_progress::SupermajorityProgress,
+    telemetry_service::{TelemetryConfig, TelemetryService},
+    tower_storage::{FileTowerStorage, TowerStorage, TowerSyncMode},
     tpu::Tpu,
     transaction_status_service::TransactionStatusService,
     tvu::{Sockets, Tvu, TvuConfig},
+    vote_account_monitor_service::{VoteAccountMonitorConfig, VoteAccountMonitorService},
 };
 use crossbeam_channel::{bounded, unbounded};
 use rand::{thread_rng, Rng};
 use solana_banks_server::rpc_banks_service::RpcBanksService;
+use solana_client::rpc_response::RpcReplayProgress;
 use solana_ledger::{
     bank_forks_utils,
-    blockstore::{Blockstore, BlockstoreSignals, CompletedSlotsReceiver, PurgeType},
-    blockstore_db::BlockstoreRecoveryMode,
-    blockstore_processor::{self, TransactionStatusSender},
+    blockstore::{
+        Blockstore, BlockstoreSignals, CompletedSlotsReceiver, PurgeType, ShredWriteCoalescing,
+    },
+    blockstore_db::{BlockstoreRecoveryMode, CompactionSchedule, WalPolicy},
+    blockstore_processor::{self, ReplayProgress, TransactionStatusSender},
     leader_schedule::FixedSchedule,
     leader_schedule_cache::LeaderScheduleCache,
+    replay_thread_pool::ThreadPoolConfig,
 };
 use solana_measure::measure::Measure;
-use solana_metrics::datapoint_info;
+use solana_metrics::{datapoint_info, datapoint_warn};
 use solana_runtime::{
-    bank::Bank,
-    bank_forks::{BankForks, SnapshotConfig},
+    accounts_background_service::AccountsBackgroundConfig,
+    accounts_index::AccountIndexes,
+    append_vec::AppendVecBackend,
+    bank::{Bank, TransactionLogCollectorConfig},
+    bank_forks::{BankForks, PruningConfig, SnapshotConfig},
     commitment::BlockCommitmentCache,
     hardened_unpack::{open_genesis_config, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE},
 };
 use solana_sdk::{
     clock::Slot,
     epoch_schedule::CFG as EPOCH_CFG,
+    feature_set,
     genesis_config::GenesisConfig,
     hash::Hash,
     pubkey::Pubkey,
@@ -59,18 +83,19 @@ use solana_sdk::{
 };
 use solana_vote_program::vote_state::VoteState;
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     net::SocketAddr,
     path::{Path, PathBuf},
     process,
     sync::atomic::{AtomicBool, Ordering},
     sync::mpsc::Receiver,
     sync::{mpsc::channel, Arc, Mutex, RwLock},
-    thread::{sleep, Result},
-    time::Duration,
+    thread::{sleep, Builder, Result},
+    time::{Duration, Instant},
 };
 
 const MAX_COMPLETED_DATA_SETS_IN_CHANNEL: usize = 100_000;
+const REPLAY_PROGRESS_REPORT_INTERVAL: Duration = Duration::from_secs(10);
 
 #[derive(Clone, Debug)]
 pub struct ValidatorConfig {
@@ -78,6 +103,10 @@ pub struct ValidatorConfig {
     pub expected_genesis_hash: Option<Hash>,
     pub expected_bank_hash: Option<Hash>,
     pub expected_shred_version: Option<u16>,
+    // Label of the network advertised in gossip, peers advertising another one are ignored
+    pub network_namespace: Option<String>,
+    // Print the shred version and what it was computed from, then exit
+    pub print_shred_version: bool,
     pub voting_disabled: bool,
     pub account_paths: Vec<PathBuf>,
     pub rpc_config: JsonRpcConfig,
@@ -85,25 +114,52 @@ pub struct ValidatorConfig {
     pub pubsub_config: PubSubConfig,
     pub snapshot_config: Option<SnapshotConfig>,
     pub max_ledger_shreds: Option<u64>,
+    pub transaction_status_retention: TransactionStatusRetention,
+    pub transaction_log_collector_config: TransactionLogCollectorConfig,
     pub broadcast_stage_type: BroadcastStageType,
     pub enable_partition: Option<Arc<AtomicBool>>,
+    pub link_conditioner: Option<Arc<dyn LinkConditioner>>,
     pub fixed_leader_schedule: Option<FixedSchedule>,
     pub wait_for_supermajority: Option<Slot>,
     pub new_hard_forks: Option<Vec<Slot>>,
+    // Hard fork advertised in gossip ahead of a coordinated restart
+    pub planned_hard_fork: Option<Slot>,
     pub trusted_validators: Option<HashSet<Pubkey>>, // None = trust all
     pub repair_validators: Option<HashSet<Pubkey>>,  // None = repair from all
     pub gossip_validators: Option<HashSet<Pubkey>>,  // None = gossip with all
     pub halt_on_trusted_validators_accounts_hash_mismatch: bool,
     pub accounts_hash_fault_injection_slots: u64, // 0 = no fault injection
+    pub accounts_background_config: AccountsBackgroundConfig,
     pub frozen_accounts: Vec<Pubkey>,
     pub no_rocksdb_compaction: bool,
+    pub rocksdb_compaction_schedule: CompactionSchedule,
+    pub rocksdb_wal_policy: WalPolicy,
     pub accounts_hash_interval_slots: u64,
     pub max_genesis_archive_unpacked_size: u64,
     pub wal_recovery_mode: Option<BlockstoreRecoveryMode>,
+    pub shred_write_coalescing: ShredWriteCoalescing,
     pub poh_verify: bool, // Perform PoH verification during blockstore processing at boo
     pub cuda: bool,
+    pub replay_thread_pool: Option<ThreadPoolConfig>, // None = default size, unpinned
+    pub poh_verify_thread_pool: Option<ThreadPoolConfig>, // None = default size, unpinned
     pub require_tower: bool,
+    pub tower_storage: Option<Arc<dyn TowerStorage>>, // None = tower file in the ledger directory
     pub debug_keys: Option<Arc<HashSet<Pubkey>>>,
+    pub account_indexes: AccountIndexes,
+    pub append_vec_backend: AppendVecBackend,
+    pub gossip_cache: bool,
+    pub prioritize_reciprocating_peers: bool,
+    pub tpu_tcp: bool,
+    pub gossip_compression: bool,
+    pub tpu_qos: bool,
+    pub bank_forks_pruning: PruningConfig,
+    pub vote_account_monitor: VoteAccountMonitorConfig,
+    pub telemetry: Option<TelemetryConfig>, // None = telemetry disabled
+    pub sample_performance: SamplePerformanceConfig,
+    pub prometheus_bind_address: Option<SocketAddr>, // None = no Prometheus metrics endpoint
+    pub gossip_push: PushConfig,
+    // (name, website, icon URL) published in gossip, None = no info published
+    pub validator_info: Option<(String, Option<String>, Option<String>)>,
 }
 
 impl Default for ValidatorConfig {
@@ -113,8 +169,12 @@ impl Default for ValidatorConfig {
             expected_genesis_hash: None,
             expected_bank_hash: None,
             expected_shred_version: None,
+            network_namespace: None,
+            print_shred_version: false,
             voting_disabled: false,
             max_ledger_shreds: None,
+            transaction_status_retention: TransactionStatusRetention::default(),
+            transaction_log_collector_config: TransactionLogCollectorConfig::default(),
             account_paths: Vec::new(),
             rpc_config: JsonRpcConfig::default(),
             rpc_addrs: None,
@@ -122,23 +182,46 @@ impl Default for ValidatorConfig {
             snapshot_config: None,
             broadcast_stage_type: BroadcastStageType::Standard,
             enable_partition: None,
+            link_conditioner: None,
             fixed_leader_schedule: None,
             wait_for_supermajority: None,
             new_hard_forks: None,
+            planned_hard_fork: None,
             trusted_validators: None,
             repair_validators: None,
             gossip_validators: None,
             halt_on_trusted_validators_accounts_hash_mismatch: false,
             accounts_hash_fault_injection_slots: 0,
+            accounts_background_config: AccountsBackgroundConfig::default(),
             frozen_accounts: vec![],
             no_rocksdb_compaction: false,
+            rocksdb_compaction_schedule: CompactionSchedule::default(),
+            rocksdb_wal_policy: WalPolicy::default(),
             accounts_hash_interval_slots: std::u64::MAX,
             max_genesis_archive_unpacked_size: MAX_GENESIS_ARCHIVE_UNPACKED_SIZE,
             wal_recovery_mode: None,
+            shred_write_coalescing: ShredWriteCoalescing::default(),
             poh_verify: true,
             cuda: false,
+            replay_thread_pool: None,
+            poh_verify_thread_pool: None,
             require_tower: false,
+            tower_storage: None,
             debug_keys: None,
+            account_indexes: AccountIndexes::default(),
+            append_vec_backend: AppendVecBackend::default(),
+            gossip_cache: false,
+            prioritize_reciprocating_peers: false,
+            tpu_tcp: false,
+            gossip_compression: false,
+            tpu_qos: false,
+            bank_forks_pruning: PruningConfig::default(),
+            vote_account_monitor: VoteAccountMonitorConfig::default(),
+            telemetry: None,
+            sample_performance: SamplePerformanceConfig::default(),
+            prometheus_bind_address: None,
+            gossip_push: PushConfig::default(),
+            validator_info: None,
         }
     }
 }
@@ -185,6 +268,10 @@ pub struct Validator {
     rewards_recorder_service: Option<RewardsRecorderService>,
     cache_block_time_service: Option<CacheBlockTimeService>,
     sample_performance_service: Option<SamplePerformanceService>,
+    vote_account_monitor_service: Option<VoteAccountMonitorService>,
+    telemetry_service: Option<TelemetryService>,
+    prometheus_service: Option<PrometheusService>,
+    planned_hard_fork_service: PlannedHardForkService,
     gossip_service: GossipService,
     serve_repair_service: ServeRepairService,
     completed_data_sets_service: CompletedDataSetsService,
@@ -265,6 +352,12 @@ impl Validator {
         let validator_exit = Arc::new(RwLock::new(Some(validator_exit)));
 
         let (replay_vote_sender, replay_vote_receiver) = unbounded();
+        let tower_storage = config.tower_storage.clone().unwrap_or_else(|| {
+            Arc::new(FileTowerStorage::new(
+                ledger_path.to_path_buf(),
+                TowerSyncMode::None,
+            ))
+        });
         let (
             genesis_config,
             bank_forks,
@@ -287,17 +380,25 @@ impl Validator {
             vote_account,
             config,
             ledger_path,
+            tower_storage.as_ref(),
             config.poh_verify,
             &exit,
         );
 
         let leader_schedule_cache = Arc::new(leader_schedule_cache);
         let bank = bank_forks.working_bank();
+        if config.rpc_addrs.is_some() {
+            // The config is shared by every bank of `bank_forks` and their descendants
+            bank.set_transaction_log_collector_config(
+                config.transaction_log_collector_config.clone(),
+            );
+        }
         let bank_forks = Arc::new(RwLock::new(bank_forks));
 
         let sample_performance_service =
             if config.rpc_addrs.is_some() && config.rpc_config.enable_rpc_transaction_history {
                 Some(SamplePerformanceService::new(
+                    config.sample_performance.clone(),
                     &bank_forks,
                     &blockstore,
                     &exit,
@@ -306,6 +407,28 @@ impl Validator {
                 None
             };
 
+        let vote_account_monitor_service = if config.voting_disabled {
+            None
+        } else {
+            Some(VoteAccountMonitorService::new(
+                id,
+                *vote_account,
+                config.vote_account_monitor.clone(),
+                &bank_forks,
+                &validator_exit,
+                &exit,
+            ))
+        };
+
+        let telemetry_service = config
+            .telemetry
+            .clone()
+            .map(|telemetry| TelemetryService::new(telemetry, &bank_forks, &blockstore, &exit));
+
+        let prometheus_service = config
+            .prometheus_bind_address
+            .map(|bind_address| PrometheusService::new(bind_address, &validator_exit));
+
         info!("Starting validator with working bank slot {}", bank.slot());
         {
             let hard_forks: Vec<_> = bank.hard_forks().read().unwrap().iter().copied().collect();
@@ -319,29 +442,114 @@ impl Validator {
             &genesis_config.hash(),
             Some(&bank.hard_forks().read().unwrap()),
         );
+        // The shred version is derived from the genesis hash and the hard forks, in order
+        let shred_version_inputs = format!(
+            "genesis hash: {}, hard forks (slot, count): {:?}",
+            genesis_config.hash(),
+            bank.hard_forks().read().unwrap().iter().collect::<Vec<_>>(),
+        );
+
+        if config.print_shred_version {
+            println!("Shred version: {}", node.info.shred_version);
+            println!("Computed from {}", shred_version_inputs);
+            println!(
+                "Network namespace: {}",
+                config.network_namespace.as_deref().unwrap_or("none")
+            );
+            process::exit(0);
+        }
 
         Self::print_node_info(&node);
 
         if let Some(expected_shred_version) = config.expected_shred_version {
             if expected_shred_version != node.info.shred_version {
                 error!(
-                    "shred version mismatch: expected {} found: {}",
-                    expected_shred_version, node.info.shred_version,
+                    "shred version mismatch: expected {} found: {}, computed from {}",
+                    expected_shred_version, node.info.shred_version, shred_version_inputs,
                 );
                 process::exit(1);
             }
         }
 
+        // Otherwise the tpu-tcp capability would be advertised with no listener behind it
+        if config.tpu_tcp && node.sockets.tpu_tcp.is_none() {
+            error!(
+                "failed to bind the TCP listener of the TPU on port {}",
+                node.info.tpu.port()
+            );
+            process::exit(1);
+        }
+
         let cluster_info = Arc::new(ClusterInfo::new(
             node.info.clone(),
             identity_keypair.clone(),
         ));
+        if config.gossip_cache {
+            cluster_info.enable_crds_cache(ledger_path.join(CRDS_CACHE_FILE));
+        }
+        cluster_info
+            .peer_bandwidth()
+            .set_prioritize_reciprocating_peers(config.prioritize_reciprocating_peers);
+        cluster_info
+            .set_push_config(config.gossip_push)
+            .expect("invalid gossip push config");
+        cluster_info.set_network_namespace(config.network_namespace.clone());
+        let capabilities = node_capabilities(config, &node);
+        cluster_info.push_once_active(
+            feature_set::gossip_node_capabilities::id(),
+            move |cluster_info| cluster_info.push_node_capabilities(capabilities),
+        );
+        if let Some((name, website, icon_url)) = config.validator_info.clone() {
+            cluster_info.push_once_active(
+                feature_set::gossip_validator_info::id(),
+                move |cluster_info| cluster_info.push_validator_info(name, website, icon_url),
+            );
+        }
+        if let Some(slot) = config.planned_hard_fork {
+            let mut hard_forks = bank.hard_forks().read().unwrap().clone();
+            hard_forks.register(slot);
+            let shred_version = compute_shred_version(&genesis_config.hash(), Some(&hard_forks));
+            info!(
+                "Planned hard fork at slot {}, shred version {}",
+                slot, shred_version
+            );
+            cluster_info.push_once_active(
+                feature_set::gossip_planned_hard_fork::id(),
+                move |cluster_info| cluster_info.push_planned_hard_fork(slot, shred_version),
+            );
+        }
+        let planned_hard_fork_service = PlannedHardForkService::new(
+            config.planned_hard_fork,
+            &cluster_info,
+            &bank_forks,
+            &exit,
+        );
+        let link_conditioner = config.link_conditioner.as_ref().map(|conditioner| {
+            let mut send_addrs = vec![
+                node.sockets.gossip.local_addr().unwrap(),
+                cluster_info.vote_socket_addr(),
+            ];
+            send_addrs.extend(
+                node.sockets
+                    .broadcast
+                    .iter()
+                    .chain(&node.sockets.retransmit_sockets)
+                    .map(|socket| socket.local_addr().unwrap()),
+            );
+            conditioner.register_node(&id, &send_addrs);
+            NodeLinkConditioner {
+                id,
+                conditioner: conditioner.clone(),
+            }
+        });
         let mut block_commitment_cache = BlockCommitmentCache::default();
         block_commitment_cache.initialize_slots(bank.slot());
         let block_commitment_cache = Arc::new(RwLock::new(block_commitment_cache));
 
         let optimistically_confirmed_bank =
             OptimisticallyConfirmedBank::locked_from_bank_forks_root(&bank_forks);
+        let accounts_hash_verification = Arc::<RwLock<AccountsHashVerification>>::default();
+        let supermajority_progress = Arc::<RwLock<Option<SupermajorityProgress>>>::default();
 
         let subscriptions = Arc::new(RpcSubscriptions::new(
             &exit,
@@ -421,6 +629,8 @@ impl Validator {
                             config.trusted_validators.clone(),
                             rpc_override_health_check.clone(),
                             optimistically_confirmed_bank.clone(),
+                            accounts_hash_verification.clone(),
+                            supermajority_progress.clone(),
                         ),
                         pubsub_service: PubSubService::new(
                             config.pubsub_config.clone(),
@@ -465,11 +675,12 @@ impl Validator {
 
         let ip_echo_server = solana_net_utils::ip_echo_server(node.sockets.ip_echo.unwrap());
 
-        let gossip_service = GossipService::new(
+        let gossip_service = GossipService::new_with_link_conditioner(
             &cluster_info,
             Some(bank_forks.clone()),
             node.sockets.gossip,
             config.gossip_validators.clone(),
+            link_conditioner.as_ref(),
             &exit,
         );
 
@@ -491,8 +702,15 @@ impl Validator {
             if let Some(snapshot_config) = config.snapshot_config.clone() {
                 // Start a snapshot packaging service
                 let (sender, receiver) = channel();
-                let snapshot_packager_service =
-                    SnapshotPackagerService::new(receiver, snapshot_hash, &exit, &cluster_info);
+                let snapshot_packager_service = SnapshotPackagerService::new(
+                    receiver,
+                    snapshot_hash,
+                    &exit,
+                    &cluster_info,
+                    snapshot_config.compression_options.clone(),
+                    snapshot_config.throttle_options.clone(),
+                    Some(poh_recorder.clone()),
+                );
                 (
                     Some(snapshot_packager_service),
                     Some((snapshot_config, sender)),
@@ -501,7 +719,13 @@ impl Validator {
                 (None, None)
             };
 
-        if wait_for_supermajority(config, &bank, &cluster_info, rpc_override_health_check) {
+        if wait_for_supermajority(
+            config,
+            &bank,
+            &cluster_info,
+            rpc_override_health_check,
+            &supermajority_progress,
+        ) {
             std::process::exit(1);
         }
 
@@ -545,12 +769,18 @@ impl Validator {
                     .iter()
                     .map(|s| s.try_clone().expect("Failed to clone TVU forwards Sockets"))
                     .collect(),
+                ancestor_hashes_requests: node
+                    .sockets
+                    .ancestor_hashes_requests
+                    .try_clone()
+                    .expect("Failed to clone ancestor hashes requests socket"),
             },
             blockstore.clone(),
             ledger_signal_receiver,
             &subscriptions,
             &poh_recorder,
             tower,
+            tower_storage,
             &leader_schedule_cache,
             &exit,
             completed_slots_receiver,
@@ -568,12 +798,16 @@ impl Validator {
             bank_notification_sender.clone(),
             TvuConfig {
                 max_ledger_shreds: config.max_ledger_shreds,
+                transaction_status_retention: config.transaction_status_retention,
                 halt_on_trusted_validators_accounts_hash_mismatch: config
                     .halt_on_trusted_validators_accounts_hash_mismatch,
                 shred_version: node.info.shred_version,
                 trusted_validators: config.trusted_validators.clone(),
                 repair_validators: config.repair_validators.clone(),
                 accounts_hash_fault_injection_slots: config.accounts_hash_fault_injection_slots,
+                accounts_background_config: config.accounts_background_config,
+                link_conditioner: link_conditioner.clone(),
+                accounts_hash_verification,
             },
         );
 
@@ -583,7 +817,13 @@ impl Validator {
             entry_receiver,
             retransmit_slots_receiver,
             node.sockets.tpu,
+            if config.tpu_tcp {
+                node.sockets.tpu_tcp
+            } else {
+                None
+            },
             node.sockets.tpu_forwards,
+            node.sockets.tpu_vote,
             node.sockets.broadcast,
             &subscriptions,
             transaction_status_sender,
@@ -597,6 +837,8 @@ impl Validator {
             replay_vote_receiver,
             replay_vote_sender,
             bank_notification_sender,
+            link_conditioner.as_ref(),
+            config.tpu_qos,
         );
 
         datapoint_info!("validator-new", ("id", id.to_string(), String));
@@ -609,6 +851,10 @@ impl Validator {
             rewards_recorder_service,
             cache_block_time_service,
             sample_performance_service,
+            vote_account_monitor_service,
+            telemetry_service,
+            prometheus_service,
+            planned_hard_fork_service,
             snapshot_packager_service,
             completed_data_sets_service,
             tpu,
@@ -688,6 +934,20 @@ impl Validator {
             sample_performance_service.join()?;
         }
 
+        if let Some(vote_account_monitor_service) = self.vote_account_monitor_service {
+            vote_account_monitor_service.join()?;
+        }
+
+        if let Some(telemetry_service) = self.telemetry_service {
+            telemetry_service.join()?;
+        }
+
+        if let Some(prometheus_service) = self.prometheus_service {
+            prometheus_service.join()?;
+        }
+
+        self.planned_hard_fork_service.join()?;
+
         if let Some(s) = self.snapshot_packager_service {
             s.join()?;
         }
@@ -717,7 +977,6 @@ fn post_process_restored_tower(
     validator_identity: &Pubkey,
     vote_account: &Pubkey,
     config: &ValidatorConfig,
-    ledger_path: &Path,
     bank_forks: &BankForks,
 ) -> Tower {
     restored_tower
@@ -762,7 +1021,6 @@ fn post_process_restored_tower(
 
             Tower::new_from_bankforks(
                 &bank_forks,
-                &ledger_path,
                 &validator_identity,
                 &vote_account,
             )
@@ -775,6 +1033,7 @@ fn new_banks_from_ledger(
     vote_account: &Pubkey,
     config: &ValidatorConfig,
     ledger_path: &Path,
+    tower_storage: &dyn TowerStorage,
     poh_verify: bool,
     exit: &Arc<AtomicBool>,
 ) -> (
@@ -817,8 +1076,11 @@ fn new_banks_from_ledger(
     } = Blockstore::open_with_signal(ledger_path, config.wal_recovery_mode.clone())
         .expect("Failed to open ledger database");
     blockstore.set_no_compaction(config.no_rocksdb_compaction);
+    blockstore.set_compaction_schedule(config.rocksdb_compaction_schedule.clone());
+    blockstore.set_wal_policy(config.rocksdb_wal_policy.clone());
+    blockstore.set_shred_write_coalescing(config.shred_write_coalescing);
 
-    let restored_tower = Tower::restore(ledger_path, &validator_identity);
+    let restored_tower = Tower::restore(tower_storage, &validator_identity);
     if let Ok(tower) = &restored_tower {
         reconcile_blockstore_roots_with_tower(&tower, &blockstore).unwrap_or_else(|err| {
             error!("Failed to reconcile blockstore with tower: {:?}", err);
@@ -826,22 +1088,45 @@ fn new_banks_from_ledger(
         });
     }
 
+    let (replay_progress_sender, replay_progress_receiver) = unbounded();
+    let highest_slot = blockstore.highest_slot().ok().flatten().unwrap_or_default();
+    // The JSON RPC service only starts once the ledger is loaded, until then the RPC port serves
+    // the replay progress
+    let bootstrap_progress = Arc::<BootstrapProgress>::default();
+    let bootstrap_rpc_service = config
+        .rpc_addrs
+        .map(|(rpc_addr, _, _)| BootstrapRpcService::new(rpc_addr, bootstrap_progress.clone()));
+    let replay_progress_thread = Builder::new()
+        .name("solana-replay-progress".to_string())
+        .spawn(move || {
+            report_replay_progress(replay_progress_receiver, highest_slot, &bootstrap_progress)
+        })
+        .unwrap();
+
     let process_options = blockstore_processor::ProcessOptions {
         poh_verify,
         dev_halt_at_slot: config.dev_halt_at_slot,
         new_hard_forks: config.new_hard_forks.clone(),
         frozen_accounts: config.frozen_accounts.clone(),
         debug_keys: config.debug_keys.clone(),
+        account_indexes: config.account_indexes.clone(),
+        append_vec_backend: config.append_vec_backend.clone(),
+        replay_thread_pool: config.replay_thread_pool.clone(),
+        poh_verify_thread_pool: config.poh_verify_thread_pool.clone(),
+        replay_progress_sender: Some(replay_progress_sender),
         ..blockstore_processor::ProcessOptions::default()
     };
 
     let blockstore = Arc::new(blockstore);
-    let transaction_history_services =
-        if config.rpc_addrs.is_some() && config.rpc_config.enable_rpc_transaction_history {
-            initialize_rpc_transaction_history_services(blockstore.clone(), exit)
-        } else {
-            TransactionHistoryServices::default()
-        };
+    let transaction_history_services = if config.rpc_addrs.is_some() {
+        initialize_rpc_transaction_history_services(
+            blockstore.clone(),
+            exit,
+            config.rpc_config.enable_rpc_transaction_history,
+        )
+    } else {
+        TransactionHistoryServices::default()
+    };
 
     let (mut bank_forks, mut leader_schedule_cache, snapshot_hash) = bank_forks_utils::load(
         &genesis_config,
@@ -857,13 +1142,17 @@ fn new_banks_from_ledger(
         error!("Failed to load ledger: {:?}", err);
         process::exit(1);
     });
+    // The progress sender was dropped along with the process options
+    replay_progress_thread.join().unwrap();
+    if let Some(bootstrap_rpc_service) = bootstrap_rpc_service {
+        bootstrap_rpc_service.close().unwrap();
+    }
 
     let tower = post_process_restored_tower(
         restored_tower,
         &validator_identity,
         &vote_account,
         &config,
-        &ledger_path,
         &bank_forks,
     );
 
@@ -873,6 +1162,7 @@ fn new_banks_from_ledger(
 
     bank_forks.set_snapshot_config(config.snapshot_config.clone());
     bank_forks.set_accounts_hash_interval_slots(config.accounts_hash_interval_slots);
+    bank_forks.set_pruning_config(config.bank_forks_pruning.clone());
 
     (
         genesis_config,
@@ -887,6 +1177,64 @@ fn new_banks_from_ledger(
     )
 }
 
+// Logs the progress of processing the ledger, and an estimate of the time left until
+// `highest_slot` is reached, until the sender is dropped
+fn report_replay_progress(
+    receiver: crossbeam_channel::Receiver<ReplayProgress>,
+    highest_slot: Slot,
+    bootstrap_progress: &BootstrapProgress,
+) {
+    let start = Instant::now();
+    let mut last_report = Instant::now();
+    let mut first_slot = None;
+    for progress in receiver.iter() {
+        let first_slot = *first_slot.get_or_insert(progress.slot);
+        let time_left = replay_time_left(start.elapsed(), first_slot, progress.slot, highest_slot);
+        *bootstrap_progress.replay.write().unwrap() = Some(RpcReplayProgress {
+            slot: progress.slot,
+            highest_slot,
+            num_entries: progress.num_entries,
+            num_txs: progress.num_txs,
+            slot_replay_ms: progress.elapsed.as_millis() as u64,
+            estimated_remaining_secs: time_left.as_secs(),
+        });
+        if last_report.elapsed() < REPLAY_PROGRESS_REPORT_INTERVAL {
+            continue;
+        }
+        last_report = Instant::now();
+        info!(
+            "catching up at slot {} ({} entries, {} txs in {}ms), {} slots behind {}, \
+             estimated time left: {}s",
+            progress.slot,
+            progress.num_entries,
+            progress.num_txs,
+            progress.elapsed.as_millis(),
+            highest_slot.saturating_sub(progress.slot),
+            highest_slot,
+            time_left.as_secs(),
+        );
+        datapoint_info!(
+            "replay-progress",
+            ("slot", progress.slot, i64),
+            ("highest_slot", highest_slot, i64),
+            ("time_left_secs", time_left.as_secs(), i64),
+        );
+    }
+}
+
+// Extrapolates the time left to replay up to `highest_slot` from the rate at which the slots
+// since `first_slot` were replayed
+fn replay_time_left(
+    elapsed: Duration,
+    first_slot: Slot,
+    slot: Slot,
+    highest_slot: Slot,
+) -> Duration {
+    let slots_replayed = slot.saturating_sub(first_slot) + 1;
+    let slots_left = highest_slot.saturating_sub(slot);
+    elapsed.mul_f64(slots_left as f64 / slots_replayed as f64)
+}
+
 fn backup_and_clear_blockstore(ledger_path: &Path, start_slot: Slot, shred_version: u16) {
     use std::time::Instant;
     let blockstore = Blockstore::open(ledger_path).unwrap();
@@ -947,10 +1295,74 @@ fn backup_and_clear_blockstore(ledger_path: &Path, start_slot: Slot, shred_versi
     drop(blockstore);
 }
 
+// Optional capabilities advertised in gossip, for peers to pick the nodes serving what they need
+fn node_capabilities(config: &ValidatorConfig, node: &Node) -> BTreeMap<String, String> {
+    let mut capabilities = BTreeMap::new();
+    capabilities.insert(CAPABILITY_REPAIR_SHRED_RANGE.to_string(), String::new());
+    if config.tpu_tcp {
+        capabilities.insert(CAPABILITY_TPU_TCP.to_string(), String::new());
+    }
+    if ContactInfo::is_valid_address(&node.info.tpu) {
+        if let Some(socket) = node.sockets.tpu_vote.first() {
+            capabilities.insert(
+                CAPABILITY_TPU_VOTE.to_string(),
+                socket.local_addr().unwrap().port().to_string(),
+            );
+        }
+    }
+    if let Some(network_namespace) = &config.network_namespace {
+        capabilities.insert(
+            CAPABILITY_NETWORK_NAMESPACE.to_string(),
+            network_namespace.clone(),
+        );
+    }
+    if config.gossip_compression {
+        capabilities.insert(
+            CAPABILITY_GOSSIP_COMPRESSION.to_string(),
+            GOSSIP_COMPRESSION_CODEC.clone(),
+        );
+    }
+    if config.rpc_addrs.is_some() {
+        if config.snapshot_config.is_some() {
+            capabilities.insert(CAPABILITY_SNAPSHOTS.to_string(), String::new());
+        }
+        let mut rpc_methods = vec![];
+        if config.rpc_config.enable_rpc_transaction_history {
+            rpc_methods.push("transaction-history");
+        }
+        if config.rpc_config.enable_bigtable_ledger_storage {
+            rpc_methods.push("bigtable-ledger");
+        }
+        if config.rpc_config.enable_get_metrics {
+            rpc_methods.push("metrics");
+        }
+        capabilities.insert(CAPABILITY_RPC_METHODS.to_string(), rpc_methods.join(","));
+    }
+    capabilities
+}
+
 fn initialize_rpc_transaction_history_services(
     blockstore: Arc<Blockstore>,
     exit: &Arc<AtomicBool>,
+    enable_rpc_transaction_history: bool,
 ) -> TransactionHistoryServices {
+    // Block times are cached for every rooted slot, so that getBlockTime does not depend on
+    // the transaction history being recorded
+    let (cache_block_time_sender, cache_block_time_receiver) = unbounded();
+    let cache_block_time_sender = Some(cache_block_time_sender);
+    let cache_block_time_service = Some(CacheBlockTimeService::new(
+        cache_block_time_receiver,
+        blockstore.clone(),
+        exit,
+    ));
+    if !enable_rpc_transaction_history {
+        return TransactionHistoryServices {
+            cache_block_time_sender,
+            cache_block_time_service,
+            ..TransactionHistoryServices::default()
+        };
+    }
+
     let (transaction_status_sender, transaction_status_receiver) = unbounded();
     let transaction_status_sender = Some(transaction_status_sender);
     let transaction_status_service = Some(TransactionStatusService::new(
@@ -963,17 +1375,10 @@ fn initialize_rpc_transaction_history_services(
     let rewards_recorder_sender = Some(rewards_recorder_sender);
     let rewards_recorder_service = Some(RewardsRecorderService::new(
         rewards_receiver,
-        blockstore.clone(),
-        exit,
-    ));
-
-    let (cache_block_time_sender, cache_block_time_receiver) = unbounded();
-    let cache_block_time_sender = Some(cache_block_time_sender);
-    let cache_block_time_service = Some(CacheBlockTimeService::new(
-        cache_block_time_receiver,
         blockstore,
         exit,
     ));
+
     TransactionHistoryServices {
         transaction_status_sender,
         transaction_status_service,
@@ -990,6 +1395,7 @@ fn wait_for_supermajority(
     bank: &Bank,
     cluster_info: &ClusterInfo,
     rpc_override_health_check: Arc<AtomicBool>,
+    supermajority_progress: &RwLock<Option<SupermajorityProgress>>,
 ) -> bool {
     if let Some(wait_for_supermajority) = config.wait_for_supermajority {
         match wait_for_supermajority.cmp(&bank.slot()) {
@@ -1019,8 +1425,37 @@ fn wait_for_supermajority(
         "Waiting for 80% of activated stake at slot {} to be in gossip...",
         bank.slot()
     );
+    // Let the other nodes compare the bank they restart from with this one, once they all
+    // deserialize the RestartBankHash variant
+    if bank
+        .feature_set
+        .is_active(&feature_set::gossip_restart_bank_hash::id())
+    {
+        cluster_info.push_restart_bank_hash(bank.slot(), bank.hash());
+    }
+    let mut reported_mismatches = HashSet::new();
     for i in 1.. {
-        let gossip_stake_percent = get_stake_percent_in_gossip(&bank, &cluster_info, i % 10 == 0);
+        let progress = SupermajorityProgress::new(&bank, &cluster_info);
+        if i % 10 == 0 {
+            progress.log();
+        }
+        for (identity, stake, bank_hash) in &progress.mismatched_bank_hash {
+            if reported_mismatches.insert((*identity, *bank_hash)) {
+                warn!(
+                    "{} restarts from bank hash {} at slot {}, this node from {}",
+                    identity, bank_hash, progress.slot, progress.bank_hash
+                );
+                datapoint_warn!(
+                    "wait-for-supermajority-bank-hash-mismatch",
+                    ("identity", identity.to_string(), String),
+                    ("stake", *stake, i64),
+                    ("slot", progress.slot, i64),
+                    ("bank_hash", bank_hash.to_string(), String),
+                );
+            }
+        }
+        let gossip_stake_percent = progress.online_stake_percent();
+        *supermajority_progress.write().unwrap() = Some(progress);
 
         if gossip_stake_percent >= 80 {
             break;
@@ -1032,6 +1467,7 @@ fn wait_for_supermajority(
         sleep(Duration::new(1, 0));
     }
     rpc_override_health_check.store(false, Ordering::Relaxed);
+    *supermajority_progress.write().unwrap() = None;
     false
 }
 
@@ -1066,88 +1502,6 @@ unsafe fn check_avx() {
     }
 }
 
-// Get the activated stake percentage (based on the provided bank) that is visible in gossip
-fn get_stake_percent_in_gossip(bank: &Bank, cluster_info: &ClusterInfo, log: bool) -> u64 {
-    let mut online_stake = 0;
-    let mut wrong_shred_stake = 0;
-    let mut wrong_shred_nodes = vec![];
-    let mut offline_stake = 0;
-    let mut offline_nodes = vec![];
-
-    let mut total_activated_stake = 0;
-    let all_tvu_peers = cluster_info.all_tvu_peers();
-    let my_shred_version = cluster_info.my_shred_version();
-    let my_id = cluster_info.id();
-
-    for (activated_stake, vote_account) in bank.vote_accounts().values() {
-        let vote_state = VoteState::from(&vote_account).unwrap_or_default();
-        total_activated_stake += activated_stake;
-
-        if *activated_stake == 0 {
-            continue;
-        }
-
-        if let Some(peer) = all_tvu_peers
-            .iter()
-            .find(|peer| peer.id == vote_state.node_pubkey)
-        {
-            if peer.shred_version == my_shred_version {
-                trace!(
-                    "observed {} in gossip, (activated_stake={})",
-                    vote_state.node_pubkey,
-                    activated_stake
-                );
-                online_stake += activated_stake;
-            } else {
-                wrong_shred_stake += activated_stake;
-                wrong_shred_nodes.push((*activated_stake, vote_state.node_pubkey));
-            }
-        } else if vote_state.node_pubkey == my_id {
-            online_stake += activated_stake; // This node is online
-        } else {
-            offline_stake += activated_stake;
-            offline_nodes.push((*activated_stake, vote_state.node_pubkey));
-        }
-    }
-
-    if log {
-        info!(
-            "{}% of active stake visible in gossip",
-            online_stake * 100 / total_activated_stake
-        );
-
-        if !wrong_shred_nodes.is_empty() {
-            info!(
-                "{}% of active stake has the wrong shred version in gossip",
-                wrong_shred_stake * 100 / total_activated_stake,
-            );
-            for (stake, identity) in wrong_shred_nodes {
-                info!(
-                    "    {}% - {}",
-                    stake * 100 / total_activated_stake,
-                    identity
-                );
-            }
-        }
-
-        if !offline_nodes.is_empty() {
-            info!(
-                "{}% of active stake is not visible in gossip",
-                offline_stake * 100 / total_activated_stake
-            );
-            for (stake, identity) in offline_nodes {
-                info!(
-                    "    {}% - {}",
-                    stake * 100 / total_activated_stake,
-                    identity
-                );
-            }
-        }
-    }
-
-    online_stake * 100 / total_activated_stake
-}
-
 // Cleanup anything that looks like an accounts append-vec
 fn cleanup_accounts_path(account_path: &std::path::Path) {
     if std::fs::remove_dir_all(account_path).is_err() {
@@ -1164,6 +1518,23 @@ mod tests {
     use solana_ledger::{create_new_tmp_ledger, genesis_utils::create_genesis_config_with_leader};
     use std::fs::remove_dir_all;
 
+    #[test]
+    fn test_replay_time_left() {
+        let elapsed = Duration::from_secs(10);
+        assert_eq!(
+            replay_time_left(elapsed, 100, 109, 129),
+            Duration::from_secs(20)
+        );
+        assert_eq!(
+            replay_time_left(elapsed, 100, 100, 100),
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            replay_time_left(elapsed, 100, 130, 129),
+            Duration::from_secs(0)
+        );
+    }
+
     #[test]
     fn validator_exit() {
         solana_logger::setup();
@@ -1300,11 +1671,13 @@ mod tests {
         let bank = Arc::new(Bank::new(&genesis_config));
         let mut config = ValidatorConfig::default();
         let rpc_override_health_check = Arc::new(AtomicBool::new(false));
+        let supermajority_progress = RwLock::default();
         assert!(!wait_for_supermajority(
             &config,
             &bank,
             &cluster_info,
-            rpc_override_health_check.clone()
+            rpc_override_health_check.clone(),
+            &supermajority_progress,
         ));
 
         // bank=0, wait=1, should fail
@@ -1313,7 +1686,8 @@ mod tests {
             &config,
             &bank,
             &cluster_info,
-            rpc_override_health_check.clone()
+            rpc_override_health_check.clone(),
+            &supermajority_progress,
         ));
 
         // bank=1, wait=0, should pass, bank is past the wait slot
@@ -1323,7 +1697,8 @@ mod tests {
             &config,
             &bank,
             &cluster_info,
-            rpc_override_health_check.clone()
+            rpc_override_health_check.clone(),
+            &supermajority_progress,
         ));
 
         // bank=1, wait=1, equal, but bad hash provided
@@ -1333,7 +1708,9 @@ mod tests {
             &config,
             &bank,
             &cluster_info,
-            rpc_override_health_check
+            rpc_override_health_check,
+            &supermajority_progress,
         ));
+        assert!(supermajority_progress.read().unwrap().is_none());
     }
 }
@@ -0,0 +1,250 @@
+//! The `vote_account_monitor_service` periodically checks the validator's identity balance and
+//! vote account state in the root bank, so that operators notice a drained fee account before
+//! the validator stops voting.
+
+use crate::validator::ValidatorExit;
+use solana_runtime::{bank::Bank, bank_forks::BankForks};
+use solana_sdk::{clock::Slot, native_token::lamports_to_sol, pubkey::Pubkey};
+use solana_vote_program::vote_state::VoteState;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    thread::{self, sleep, Builder, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const SLEEP_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Debug, Default)]
+pub struct VoteAccountMonitorConfig {
+    /// Warn once the identity balance falls below this many lamports
+    pub min_identity_balance: Option<u64>,
+    /// Exit the validator instead of only warning when the identity balance is too low
+    pub exit_on_low_identity_balance: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct VoteAccountStatus {
+    pub identity_balance: u64,
+    pub vote_account_balance: u64,
+    pub root_slot: Option<Slot>,
+    pub last_vote_slot: Option<Slot>,
+    pub credits: u64,
+    /// Reasons the validator will not be able to vote with this vote account
+    pub problems: Vec<String>,
+}
+
+impl VoteAccountStatus {
+    pub fn new(bank: &Bank, identity_pubkey: &Pubkey, vote_account_address: &Pubkey) -> Self {
+        let mut status = Self {
+            identity_balance: bank.get_balance(identity_pubkey),
+            ..Self::default()
+        };
+        let vote_account = match bank.get_account(vote_account_address) {
+            Some(vote_account) => vote_account,
+            None => {
+                status.problems.push(format!(
+                    "vote account does not exist: {}",
+                    vote_account_address
+                ));
+                return status;
+            }
+        };
+        status.vote_account_balance = vote_account.lamports;
+
+        if vote_account.owner != solana_vote_program::id() {
+            status.problems.push(format!(
+                "not a vote account (owned by {}): {}",
+                vote_account.owner, vote_account_address
+            ));
+            return status;
+        }
+        match VoteState::from(&vote_account) {
+            Some(vote_state) => {
+                status.root_slot = vote_state.root_slot;
+                status.last_vote_slot = vote_state.last_voted_slot();
+                status.credits = vote_state.credits();
+                if vote_state.authorized_voters().is_empty() {
+                    status
+                        .problems
+                        .push("vote account not yet initialized".to_string());
+                }
+                if vote_state.node_pubkey != *identity_pubkey {
+                    status.problems.push(format!(
+                        "vote account's identity ({}) does not match the validator's identity ({})",
+                        vote_state.node_pubkey, identity_pubkey
+                    ));
+                }
+            }
+            None => status.problems.push(format!(
+                "invalid vote account data for {}",
+                vote_account_address
+            )),
+        }
+        status
+    }
+}
+
+pub struct VoteAccountMonitorService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl VoteAccountMonitorService {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new(
+        identity_pubkey: Pubkey,
+        vote_account_address: Pubkey,
+        config: VoteAccountMonitorConfig,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        validator_exit: &Arc<RwLock<Option<ValidatorExit>>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let exit = exit.clone();
+        let validator_exit = validator_exit.clone();
+        let bank_forks = bank_forks.clone();
+
+        info!("Starting VoteAccountMonitor service");
+        let thread_hdl = Builder::new()
+            .name("vote-account-monitor".to_string())
+            .spawn(move || {
+                Self::run(
+                    &identity_pubkey,
+                    &vote_account_address,
+                    &config,
+                    &bank_forks,
+                    &validator_exit,
+                    &exit,
+                );
+            })
+            .unwrap();
+
+        Self { thread_hdl }
+    }
+
+    fn run(
+        identity_pubkey: &Pubkey,
+        vote_account_address: &Pubkey,
+        config: &VoteAccountMonitorConfig,
+        bank_forks: &RwLock<BankForks>,
+        validator_exit: &RwLock<Option<ValidatorExit>>,
+        exit: &AtomicBool,
+    ) {
+        let mut last_check: Option<Instant> = None;
+        loop {
+            if exit.load(Ordering::Relaxed) {
+                break;
+            }
+            if last_check.map_or(true, |last_check| last_check.elapsed() >= CHECK_INTERVAL) {
+                last_check = Some(Instant::now());
+                let bank = bank_forks.read().unwrap().root_bank().clone();
+                let status = VoteAccountStatus::new(&bank, identity_pubkey, vote_account_address);
+                Self::report(bank.slot(), &status);
+                if Self::check_identity_balance(identity_pubkey, config, &status) {
+                    error!("Exiting due to low identity balance");
+                    if let Some(validator_exit) = validator_exit.write().unwrap().take() {
+                        validator_exit.exit();
+                    }
+                    break;
+                }
+            }
+            sleep(SLEEP_INTERVAL);
+        }
+    }
+
+    fn report(slot: Slot, status: &VoteAccountStatus) {
+        datapoint_info!(
+            "vote-account-monitor",
+            ("slot", slot, i64),
+            ("identity_balance", status.identity_balance, i64),
+            ("vote_account_balance", status.vote_account_balance, i64),
+            ("root_slot", status.root_slot.unwrap_or_default(), i64),
+            (
+                "last_vote_slot",
+                status.last_vote_slot.unwrap_or_default(),
+                i64
+            ),
+            ("credits", status.credits, i64),
+            ("problems", status.problems.len(), i64),
+        );
+        for problem in &status.problems {
+            warn!("Vote account problem: {}", problem);
+        }
+    }
+
+    // Returns true if the validator should exit
+    fn check_identity_balance(
+        identity_pubkey: &Pubkey,
+        config: &VoteAccountMonitorConfig,
+        status: &VoteAccountStatus,
+    ) -> bool {
+        match config.min_identity_balance {
+            Some(min_identity_balance) if status.identity_balance < min_identity_balance => {
+                warn!(
+                    "Identity account {} balance is low: ◎{} (minimum ◎{})",
+                    identity_pubkey,
+                    lamports_to_sol(status.identity_balance),
+                    lamports_to_sol(min_identity_balance),
+                );
+                config.exit_on_low_identity_balance
+            }
+            _ => false,
+        }
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_runtime::genesis_utils::{create_genesis_config_with_leader, GenesisConfigInfo};
+    use solana_sdk::signature::Signer;
+
+    #[test]
+    fn test_vote_account_status() {
+        let identity_pubkey = solana_sdk::pubkey::new_rand();
+        let GenesisConfigInfo {
+            genesis_config,
+            voting_keypair,
+            ..
+        } = create_genesis_config_with_leader(10_000, &identity_pubkey, 100);
+        let bank = Bank::new(&genesis_config);
+        let vote_account_address = voting_keypair.pubkey();
+
+        let status = VoteAccountStatus::new(&bank, &identity_pubkey, &vote_account_address);
+        assert!(status.problems.is_empty(), "{:?}", status.problems);
+        assert_eq!(status.identity_balance, bank.get_balance(&identity_pubkey));
+        assert!(status.vote_account_balance > 0);
+
+        let other_identity = solana_sdk::pubkey::new_rand();
+        let status = VoteAccountStatus::new(&bank, &other_identity, &vote_account_address);
+        assert_eq!(status.identity_balance, 0);
+        assert_eq!(status.problems.len(), 1);
+
+        let config = VoteAccountMonitorConfig {
+            min_identity_balance: Some(1),
+            exit_on_low_identity_balance: true,
+        };
+        assert!(VoteAccountMonitorService::check_identity_balance(
+            &other_identity,
+            &config,
+            &status
+        ));
+        assert!(!VoteAccountMonitorService::check_identity_balance(
+            &other_identity,
+            &VoteAccountMonitorConfig::default(),
+            &status
+        ));
+
+        let status = VoteAccountStatus::new(&bank, &identity_pubkey, &other_identity);
+        assert_eq!(
+            status.problems,
+            vec![format!("vote account does not exist: {}", other_identity)]
+        );
+    }
+}
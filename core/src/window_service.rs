@@ -131,9 +131,26 @@ where
 {
     let timer = Duration::from_millis(200);
     let (mut shreds, mut repair_infos) = shred_receiver.recv_timeout(timer)?;
-    while let Ok((more_shreds, more_repair_infos)) = shred_receiver.try_recv() {
-        shreds.extend(more_shreds);
-        repair_infos.extend(more_repair_infos);
+    // Coalesce the received shreds into one write, until either the batch is full or the
+    // flush interval has passed since the first of them was received
+    let coalescing = blockstore.shred_write_coalescing();
+    let flush_deadline = Instant::now() + coalescing.flush_interval;
+    loop {
+        while let Ok((more_shreds, more_repair_infos)) = shred_receiver.try_recv() {
+            shreds.extend(more_shreds);
+            repair_infos.extend(more_repair_infos);
+        }
+        let now = Instant::now();
+        if shreds.len() >= coalescing.batch_size || now >= flush_deadline {
+            break;
+        }
+        match shred_receiver.recv_timeout(flush_deadline - now) {
+            Ok((more_shreds, more_repair_infos)) => {
+                shreds.extend(more_shreds);
+                repair_infos.extend(more_repair_infos);
+            }
+            Err(_) => break,
+        }
     }
 
     assert_eq!(shreds.len(), repair_infos.len());
@@ -142,8 +159,10 @@ where
     repair_infos.retain(|repair_info| verify_repair(&repair_info));
     assert_eq!(shreds.len(), repair_infos.len());
 
+    let is_repaired = repair_infos.iter().map(Option::is_some).collect();
     let (completed_data_sets, inserted_indices) = blockstore.insert_shreds_handle_duplicate(
         shreds,
+        is_repaired,
         Some(leader_schedule_cache),
         false,
         &handle_duplicate,
@@ -0,0 +1,126 @@
+//! The `write_lock_qos` module budgets the time block production spends on the transactions
+//! writing each account. The time the banking stage takes to process a batch is split evenly
+//! among its transactions and charged to the accounts they write; once an account has been
+//! charged `MAX_WRITABLE_ACCOUNT_PROCESSING_US` in a slot, further transactions writing it are
+//! deferred to a later slot, so that a hot account cannot monopolize block space.
+//!
+//! Unlike the limits of the `cost_model`, the budget is measured rather than estimated, and is
+//! only applied by the leader.
+
+use solana_sdk::{clock::Slot, pubkey::Pubkey, sanitize::Sanitize, transaction::Transaction};
+use std::collections::HashMap;
+
+toml_config::package_config! {
+    MAX_WRITABLE_ACCOUNT_PROCESSING_US: u64,
+}
+
+#[derive(Debug)]
+pub struct WriteLockQos {
+    account_limit_us: u64,
+    slot: Slot,
+    account_times_us: HashMap<Pubkey, u64>,
+}
+
+impl Default for WriteLockQos {
+    fn default() -> Self {
+        Self::new(CFG.MAX_WRITABLE_ACCOUNT_PROCESSING_US)
+    }
+}
+
+impl WriteLockQos {
+    pub fn new(account_limit_us: u64) -> Self {
+        Self {
+            account_limit_us,
+            slot: 0,
+            account_times_us: HashMap::new(),
+        }
+    }
+
+    /// Starts budgeting the block of `slot`, unless it already is the budgeted one
+    pub fn reset_if_new_bank(&mut self, slot: Slot) {
+        if slot != self.slot {
+            self.slot = slot;
+            self.account_times_us.clear();
+        }
+    }
+
+    /// Returns the first account written by `transaction` which used up its budget
+    pub fn over_budget_account(&self, transaction: &Transaction) -> Option<Pubkey> {
+        writable_accounts(transaction)
+            .find(|account| {
+                self.account_times_us
+                    .get(account)
+                    .map_or(false, |time_us| *time_us >= self.account_limit_us)
+            })
+            .copied()
+    }
+
+    /// Charges the accounts written by `transactions` for the `elapsed_us` taken to process
+    /// them in the block of `slot`
+    pub fn record(&mut self, slot: Slot, transactions: &[Transaction], elapsed_us: u64) {
+        if transactions.is_empty() {
+            return;
+        }
+        self.reset_if_new_bank(slot);
+        let time_us = elapsed_us / transactions.len() as u64;
+        for transaction in transactions {
+            for account in writable_accounts(transaction) {
+                *self.account_times_us.entry(*account).or_default() += time_us;
+            }
+        }
+    }
+}
+
+fn writable_accounts(transaction: &Transaction) -> impl Iterator<Item = &Pubkey> {
+    let message = &transaction.message;
+    // Transactions which fail to sanitize are rejected before locking any account
+    let num_accounts = if transaction.sanitize().is_ok() {
+        message.account_keys.len()
+    } else {
+        0
+    };
+    message.account_keys[..num_accounts]
+        .iter()
+        .enumerate()
+        .filter(move |(i, _)| message.is_writable(*i))
+        .map(|(_, account)| account)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        hash::Hash,
+        signature::{Keypair, Signer},
+        system_transaction,
+    };
+
+    #[test]
+    fn test_write_lock_qos() {
+        let keypair = Keypair::new();
+        let hot_account = solana_sdk::pubkey::new_rand();
+        let transfer = |to: &Pubkey| system_transaction::transfer(&keypair, to, 1, Hash::default());
+        let hot_transactions = vec![transfer(&hot_account), transfer(&hot_account)];
+        let mut qos = WriteLockQos::new(100);
+
+        qos.record(1, &hot_transactions, 80);
+        assert_eq!(qos.over_budget_account(&hot_transactions[0]), None);
+        qos.record(1, &hot_transactions[..1], 40);
+        // The fee payer is charged as well
+        assert_eq!(
+            qos.over_budget_account(&transfer(&solana_sdk::pubkey::new_rand())),
+            Some(keypair.pubkey())
+        );
+        let other_transaction = system_transaction::transfer(
+            &Keypair::new(),
+            &solana_sdk::pubkey::new_rand(),
+            1,
+            Hash::default(),
+        );
+        assert_eq!(qos.over_budget_account(&other_transaction), None);
+
+        // The budget is per slot
+        qos.record(2, &[other_transaction], 10);
+        assert_eq!(qos.over_budget_account(&hot_transactions[0]), None);
+    }
+}
@@ -44,8 +44,11 @@ mod tests {
     };
     use solana_runtime::{
         accounts_background_service::SnapshotRequestHandler,
+        append_vec::AppendVecBackend,
         bank::{Bank, BankSlotDelta},
-        bank_forks::{BankForks, CompressionType, SnapshotConfig},
+        bank_forks::{
+            BankForks, CompressionOptions, CompressionType, SnapshotConfig, SnapshotThrottleOptions,
+        },
         genesis_utils::{create_genesis_config, GenesisConfigInfo},
         snapshot_utils,
         snapshot_utils::SnapshotVersion,
@@ -59,7 +62,10 @@ mod tests {
         signature::{Keypair, Signer},
         system_transaction,
     };
-    use std::{fs, path::PathBuf, sync::atomic::AtomicBool, sync::mpsc::channel, sync::Arc};
+    use std::{
+        collections::HashSet, fs, path::PathBuf, sync::atomic::AtomicBool, sync::mpsc::channel,
+        sync::Arc,
+    };
     use tempfile::TempDir;
 
     DEFINE_SNAPSHOT_VERSION_PARAMETERIZED_TEST_FUNCTIONS!(V1_2_0, Development, V1_2_0_Development);
@@ -93,6 +99,8 @@ mod tests {
                 &[],
                 None,
                 None,
+                HashSet::new(),
+                AppendVecBackend::default(),
             );
             bank0.freeze();
             let mut bank_forks = BankForks::new(bank0);
@@ -103,6 +111,8 @@ mod tests {
                 snapshot_package_output_path: PathBuf::from(snapshot_output_path.path()),
                 snapshot_path: PathBuf::from(snapshot_dir.path()),
                 compression: CompressionType::Bzip2,
+                compression_options: CompressionOptions::default(),
+                throttle_options: SnapshotThrottleOptions::default(),
                 snapshot_version,
             };
             bank_forks.set_snapshot_config(Some(snapshot_config.clone()));
@@ -148,6 +158,8 @@ mod tests {
             old_genesis_config,
             None,
             None,
+            HashSet::new(),
+            AppendVecBackend::default(),
         )
         .unwrap();
 
@@ -393,8 +405,15 @@ mod tests {
 
         let cluster_info = Arc::new(ClusterInfo::new_with_invalid_keypair(ContactInfo::default()));
 
-        let snapshot_packager_service =
-            SnapshotPackagerService::new(receiver, None, &exit, &cluster_info);
+        let snapshot_packager_service = SnapshotPackagerService::new(
+            receiver,
+            None,
+            &exit,
+            &cluster_info,
+            CompressionOptions::default(),
+            SnapshotThrottleOptions::default(),
+            None,
+        );
 
         // Close the channel so that the package service will exit after reading all the
         // packages off the channel
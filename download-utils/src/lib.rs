@@ -9,11 +9,54 @@ use std::io;
 use std::io::Read;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 static TRUCK: Emoji = Emoji("🚚 ", "");
 static SPARKLE: Emoji = Emoji("✨ ", "");
 
+/// How often the progress of a download is reported to its callback
+const DOWNLOAD_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// How often the progress of a download is logged, when there is no terminal to draw it in
+const DOWNLOAD_PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Progress of a download, reported periodically and once completed
+#[derive(Clone, Debug, PartialEq)]
+pub struct DownloadProgressRecord {
+    pub elapsed_time: Duration,
+    pub downloaded_bytes: u64,
+    /// Size of the download, `None` if the server did not report it
+    pub total_bytes: Option<u64>,
+    /// Average rate since the download started
+    pub bytes_per_second: u64,
+    /// `None` if the size of the download or the rate is unknown
+    pub estimated_remaining_time: Option<Duration>,
+}
+
+impl DownloadProgressRecord {
+    fn new(elapsed_time: Duration, downloaded_bytes: u64, total_bytes: Option<u64>) -> Self {
+        let bytes_per_second = if elapsed_time.as_millis() > 0 {
+            (downloaded_bytes as u128 * 1000 / elapsed_time.as_millis()) as u64
+        } else {
+            0
+        };
+        let estimated_remaining_time = match total_bytes {
+            Some(total_bytes) if bytes_per_second > 0 => Some(Duration::from_secs(
+                total_bytes.saturating_sub(downloaded_bytes) / bytes_per_second,
+            )),
+            _ => None,
+        };
+        Self {
+            elapsed_time,
+            downloaded_bytes,
+            total_bytes,
+            bytes_per_second,
+            estimated_remaining_time,
+        }
+    }
+}
+
+pub type DownloadProgressCallback<'a> = Box<dyn FnMut(&DownloadProgressRecord) + 'a>;
+
 /// Creates a new process bar for processing that will take an unknown amount of time
 fn new_spinner_progress_bar() -> ProgressBar {
     let progress_bar = ProgressBar::new(42);
@@ -23,7 +66,13 @@ fn new_spinner_progress_bar() -> ProgressBar {
     progress_bar
 }
 
-pub fn download_file(url: &str, destination_file: &Path) -> Result<(), String> {
+/// Downloads `url` to `destination_file`, drawing a progress bar when run in a terminal and
+/// reporting the progress to `progress_callback`
+pub fn download_file(
+    url: &str,
+    destination_file: &Path,
+    progress_callback: &mut Option<DownloadProgressCallback<'_>>,
+) -> Result<(), String> {
     if destination_file.is_file() {
         return Err(format!("{:?} already exists", destination_file));
     }
@@ -33,7 +82,12 @@ pub fn download_file(url: &str, destination_file: &Path) -> Result<(), String> {
 
     let temp_destination_file = destination_file.with_extension(".tmp");
 
-    let progress_bar = new_spinner_progress_bar();
+    let use_progress_bar = console::user_attended();
+    let progress_bar = if use_progress_bar {
+        new_spinner_progress_bar()
+    } else {
+        ProgressBar::hidden()
+    };
     progress_bar.set_message(&format!("{}Downloading {}...", TRUCK, url));
 
     let response = reqwest::blocking::Client::new()
@@ -66,23 +120,77 @@ pub fn download_file(url: &str, destination_file: &Path) -> Result<(), String> {
             .progress_chars("=> "),
     );
 
-    struct DownloadProgress<R> {
+    struct DownloadProgress<'a, 'b, R> {
         progress_bar: ProgressBar,
         response: R,
+        url: &'a str,
+        total_bytes: Option<u64>,
+        downloaded_bytes: u64,
+        start: Instant,
+        last_report: Instant,
+        last_log: Instant,
+        use_progress_bar: bool,
+        callback: &'a mut Option<DownloadProgressCallback<'b>>,
     }
 
-    impl<R: Read> Read for DownloadProgress<R> {
+    impl<'a, 'b, R> DownloadProgress<'a, 'b, R> {
+        fn report(&mut self) {
+            let record = DownloadProgressRecord::new(
+                self.start.elapsed(),
+                self.downloaded_bytes,
+                self.total_bytes,
+            );
+            if !self.use_progress_bar && self.last_log.elapsed() >= DOWNLOAD_PROGRESS_LOG_INTERVAL {
+                info!(
+                    "Downloading {}: {} of {} bytes, {} bytes/s, ETA {}",
+                    self.url,
+                    record.downloaded_bytes,
+                    record
+                        .total_bytes
+                        .map_or_else(|| "?".to_string(), |total| total.to_string()),
+                    record.bytes_per_second,
+                    record
+                        .estimated_remaining_time
+                        .map_or_else(|| "?".to_string(), |eta| format!("{:?}", eta)),
+                );
+                self.last_log = Instant::now();
+            }
+            if let Some(callback) = self.callback.as_mut() {
+                callback(&record);
+            }
+            self.last_report = Instant::now();
+        }
+    }
+
+    impl<'a, 'b, R: Read> Read for DownloadProgress<'a, 'b, R> {
         fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
             self.response.read(buf).map(|n| {
                 self.progress_bar.inc(n as u64);
+                self.downloaded_bytes += n as u64;
+                if self.last_report.elapsed() >= DOWNLOAD_PROGRESS_INTERVAL {
+                    self.report();
+                }
                 n
             })
         }
     }
 
+    let now = Instant::now();
     let mut source = DownloadProgress {
         progress_bar,
         response,
+        url,
+        total_bytes: if download_size > 0 {
+            Some(download_size)
+        } else {
+            None
+        },
+        downloaded_bytes: 0,
+        start: download_start,
+        last_report: now,
+        last_log: now,
+        use_progress_bar,
+        callback: progress_callback,
     };
 
     File::create(&temp_destination_file)
@@ -90,6 +198,7 @@ pub fn download_file(url: &str, destination_file: &Path) -> Result<(), String> {
         .map_err(|err| format!("Unable to write {:?}: {:?}", temp_destination_file, err))?;
 
     source.progress_bar.finish_and_clear();
+    source.report();
     info!(
         "  {}{}",
         SPARKLE,
@@ -119,6 +228,7 @@ pub fn download_genesis_if_missing(
         download_file(
             &format!("http://{}/{}", rpc_addr, "genesis.tar.bz2"),
             &tmp_genesis_package,
+            &mut None,
         )?;
 
         Ok(tmp_genesis_package)
@@ -131,6 +241,7 @@ pub fn download_snapshot(
     rpc_addr: &SocketAddr,
     ledger_path: &Path,
     desired_snapshot_hash: (Slot, Hash),
+    progress_callback: &mut Option<DownloadProgressCallback<'_>>,
 ) -> Result<(), String> {
     // Remove all snapshot not matching the desired hash
     let snapshot_packages = snapshot_utils::get_snapshot_archives(ledger_path);
@@ -171,6 +282,7 @@ pub fn download_snapshot(
                         .unwrap()
                 ),
                 &desired_snapshot_package,
+                progress_callback,
             )
             .is_ok()
             {
@@ -2,14 +2,17 @@ use clap::{crate_description, crate_name, App, Arg};
 use solana_clap_utils::input_parsers::{lamports_of_sol, value_of};
 use solana_faucet::{
     faucet::{run_faucet, Faucet, FAUCET_PORT},
+    request_limits::{AccessTokens, RequestLimits, RequestLimitsConfig, LIMITS_WINDOW},
     socketaddr,
 };
 use solana_sdk::signature::read_keypair_file;
 use std::{
     error,
     net::{Ipv4Addr, SocketAddr},
+    path::Path,
     sync::{Arc, Mutex},
     thread,
+    time::Duration,
 };
 
 fn main() -> Result<(), Box<dyn error::Error>> {
@@ -49,6 +52,48 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                 .takes_value(true)
                 .help("Request limit for a single request, in SOL"),
         )
+        .arg(
+            Arg::with_name("limits_window")
+                .long("limits-window")
+                .value_name("SECS")
+                .takes_value(true)
+                .help("Time window over which to limit requests per IP and per recipient [default: one day]"),
+        )
+        .arg(
+            Arg::with_name("per_ip_cap")
+                .long("per-ip-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Request limit for a single IP per limits window, in SOL"),
+        )
+        .arg(
+            Arg::with_name("per_ip_request_cap")
+                .long("per-ip-request-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Maximum number of requests from a single IP per limits window"),
+        )
+        .arg(
+            Arg::with_name("per_pubkey_cap")
+                .long("per-pubkey-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Request limit for a single recipient per limits window, in SOL"),
+        )
+        .arg(
+            Arg::with_name("limits_file")
+                .long("limits-file")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("File keeping the per-IP and per-recipient usage across restarts"),
+        )
+        .arg(
+            Arg::with_name("access_token_file")
+                .long("access-token-file")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Only grant requests bearing one of the access tokens listed in this file, one per line"),
+        )
         .get_matches();
 
     let mint_keypair = read_keypair_file(matches.value_of("keypair").unwrap())
@@ -57,15 +102,25 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     let time_slice = value_of(&matches, "slice");
     let per_time_cap = lamports_of_sol(&matches, "per_time_cap");
     let per_request_cap = lamports_of_sol(&matches, "per_request_cap");
+    let limits_config = RequestLimitsConfig {
+        window: Duration::from_secs(value_of(&matches, "limits_window").unwrap_or(LIMITS_WINDOW)),
+        per_ip_cap: lamports_of_sol(&matches, "per_ip_cap"),
+        per_ip_request_cap: value_of(&matches, "per_ip_request_cap"),
+        per_pubkey_cap: lamports_of_sol(&matches, "per_pubkey_cap"),
+    };
+    let request_limits = match matches.value_of("limits_file") {
+        Some(path) => RequestLimits::load(limits_config, Path::new(path))?,
+        None => RequestLimits::new(limits_config),
+    };
 
     let faucet_addr = socketaddr!(0, FAUCET_PORT);
 
-    let faucet = Arc::new(Mutex::new(Faucet::new(
-        mint_keypair,
-        time_slice,
-        per_time_cap,
-        per_request_cap,
-    )));
+    let mut faucet = Faucet::new(mint_keypair, time_slice, per_time_cap, per_request_cap);
+    faucet.set_request_limits(request_limits);
+    if let Some(path) = matches.value_of("access_token_file") {
+        faucet.set_request_verifier(Box::new(AccessTokens::load(Path::new(path))?));
+    }
+    let faucet = Arc::new(Mutex::new(faucet));
 
     let faucet1 = faucet.clone();
     thread::spawn(move || loop {
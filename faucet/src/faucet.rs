@@ -1,15 +1,16 @@
 //! The `faucet` module provides an object for launching a Solana Faucet,
 //! which is the custodian of any remaining lamports in a mint.
 //! The Solana Faucet builds and send airdrop transactions,
-//! checking requests against a request cap for a given time time_slice
-//! and (to come) an IP rate limit.
+//! checking requests against a request cap for a given time time_slice,
+//! per-IP and per-recipient limits, and an optional request verifier.
 
+use crate::request_limits::{RequestLimits, RequestVerifier};
 use bincode::{deserialize, serialize};
 use byteorder::{ByteOrder, LittleEndian};
 use bytes::{Bytes, BytesMut};
 use log::*;
 use serde_derive::{Deserialize, Serialize};
-use solana_metrics::datapoint_info;
+use solana_metrics::{datapoint_info, datapoint_warn};
 use solana_sdk::{
     hash::Hash,
     message::Message,
@@ -17,9 +18,11 @@ use solana_sdk::{
     pubkey::Pubkey,
     signature::{Keypair, Signer},
     system_instruction,
+    timing::timestamp,
     transaction::Transaction,
 };
 use std::{
+    fmt,
     io::{self, Error, ErrorKind},
     net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream},
     sync::{mpsc::Sender, Arc, Mutex},
@@ -49,13 +52,50 @@ pub const REQUEST_CAP: u64 = solana_sdk::native_token::LAMPORTS_PER_SOL * 10_000
 pub const FAUCET_PORT: u16 = 9900;
 pub const FAUCET_PORT_STR: &str = "9900";
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone)]
 pub enum FaucetRequest {
     GetAirdrop {
         lamports: u64,
         to: Pubkey,
         blockhash: Hash,
     },
+    /// Airdrop request bearing a token for the faucet's request verifier
+    GetAirdropWithToken {
+        lamports: u64,
+        to: Pubkey,
+        blockhash: Hash,
+        token: String,
+    },
+}
+
+// Requests are logged, so keep the access token out of their debug output
+impl fmt::Debug for FaucetRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FaucetRequest::GetAirdrop {
+                lamports,
+                to,
+                blockhash,
+            } => f
+                .debug_struct("GetAirdrop")
+                .field("lamports", lamports)
+                .field("to", to)
+                .field("blockhash", blockhash)
+                .finish(),
+            FaucetRequest::GetAirdropWithToken {
+                lamports,
+                to,
+                blockhash,
+                ..
+            } => f
+                .debug_struct("GetAirdropWithToken")
+                .field("lamports", lamports)
+                .field("to", to)
+                .field("blockhash", blockhash)
+                .field("token", &"<redacted>")
+                .finish(),
+        }
+    }
 }
 
 pub struct Faucet {
@@ -65,6 +105,8 @@ pub struct Faucet {
     per_time_cap: u64,
     per_request_cap: Option<u64>,
     pub request_current: u64,
+    request_limits: RequestLimits,
+    request_verifier: Option<Box<dyn RequestVerifier>>,
 }
 
 impl Faucet {
@@ -83,9 +125,19 @@ impl Faucet {
             per_time_cap,
             per_request_cap,
             request_current: 0,
+            request_limits: RequestLimits::default(),
+            request_verifier: None,
         }
     }
 
+    pub fn set_request_limits(&mut self, request_limits: RequestLimits) {
+        self.request_limits = request_limits;
+    }
+
+    pub fn set_request_verifier(&mut self, request_verifier: Box<dyn RequestVerifier>) {
+        self.request_verifier = Some(request_verifier);
+    }
+
     pub fn check_time_request_limit(&mut self, request_amount: u64) -> bool {
         self.request_current
             .checked_add(request_amount)
@@ -108,49 +160,79 @@ impl Faucet {
     pub fn build_airdrop_transaction(
         &mut self,
         req: FaucetRequest,
+        ip: IpAddr,
     ) -> Result<Transaction, io::Error> {
         trace!("build_airdrop_transaction: {:?}", req);
-        match req {
+        let (lamports, to, blockhash, token) = match req {
             FaucetRequest::GetAirdrop {
                 lamports,
                 to,
                 blockhash,
-            } => {
-                if let Some(cap) = self.per_request_cap {
-                    if lamports > cap {
-                        return Err(Error::new(
-                            ErrorKind::Other,
-                            format!("request too large; req: {} cap: {}", lamports, cap),
-                        ));
-                    }
-                }
-                if self.check_time_request_limit(lamports) {
-                    self.request_current = self.request_current.saturating_add(lamports);
-                    datapoint_info!(
-                        "faucet-airdrop",
-                        ("request_amount", lamports, i64),
-                        ("request_current", self.request_current, i64)
-                    );
-                    info!("Requesting airdrop of {} to {:?}", lamports, to);
-
-                    let mint_pubkey = self.mint_keypair.pubkey();
-                    let create_instruction =
-                        system_instruction::transfer(&mint_pubkey, &to, lamports);
-                    let message = Message::new(&[create_instruction], Some(&mint_pubkey));
-                    Ok(Transaction::new(&[&self.mint_keypair], message, blockhash))
-                } else {
-                    Err(Error::new(
-                        ErrorKind::Other,
-                        format!(
-                            "token limit reached; req: {} current: {} cap: {}",
-                            lamports, self.request_current, self.per_time_cap
-                        ),
-                    ))
-                }
+            } => (lamports, to, blockhash, None),
+            FaucetRequest::GetAirdropWithToken {
+                lamports,
+                to,
+                blockhash,
+                token,
+            } => (lamports, to, blockhash, Some(token)),
+        };
+        let deny = |reason: &str, message: String| {
+            datapoint_warn!(
+                "faucet-airdrop-denied",
+                ("reason", reason.to_string(), String),
+                ("request_amount", lamports, i64)
+            );
+            Err(Error::new(ErrorKind::Other, message))
+        };
+        if let Some(cap) = self.per_request_cap {
+            if lamports > cap {
+                return deny(
+                    "per_request_cap",
+                    format!("request too large; req: {} cap: {}", lamports, cap),
+                );
+            }
+        }
+        if let Some(request_verifier) = &self.request_verifier {
+            if let Err(err) = request_verifier.verify(&ip, &to, lamports, token.as_deref()) {
+                return deny("verifier", format!("request rejected: {}", err));
             }
         }
+        if !self.check_time_request_limit(lamports) {
+            return deny(
+                "per_time_cap",
+                format!(
+                    "token limit reached; req: {} current: {} cap: {}",
+                    lamports, self.request_current, self.per_time_cap
+                ),
+            );
+        }
+        let now = timestamp();
+        if let Err(err) = self.request_limits.check(&ip, &to, lamports, now) {
+            return deny(err.reason(), err.to_string());
+        }
+
+        self.request_current = self.request_current.saturating_add(lamports);
+        if let Err(err) = self.request_limits.record(ip, to, lamports, now) {
+            warn!("Failed to save the faucet limits: {:?}", err);
+        }
+        datapoint_info!(
+            "faucet-airdrop",
+            ("request_amount", lamports, i64),
+            ("request_current", self.request_current, i64)
+        );
+        info!("Requesting airdrop of {} to {:?}", lamports, to);
+
+        let mint_pubkey = self.mint_keypair.pubkey();
+        let create_instruction = system_instruction::transfer(&mint_pubkey, &to, lamports);
+        let message = Message::new(&[create_instruction], Some(&mint_pubkey));
+        Ok(Transaction::new(&[&self.mint_keypair], message, blockhash))
     }
-    pub fn process_faucet_request(&mut self, bytes: &BytesMut) -> Result<Bytes, io::Error> {
+
+    pub fn process_faucet_request(
+        &mut self,
+        bytes: &BytesMut,
+        ip: IpAddr,
+    ) -> Result<Bytes, io::Error> {
         let req: FaucetRequest = deserialize(bytes).map_err(|err| {
             io::Error::new(
                 io::ErrorKind::Other,
@@ -159,7 +241,7 @@ impl Faucet {
         })?;
 
         info!("Airdrop transaction requested...{:?}", req);
-        let res = self.build_airdrop_transaction(req);
+        let res = self.build_airdrop_transaction(req, ip);
         match res {
             Ok(tx) => {
                 let response_vec = bincode::serialize(&tx).map_err(|err| {
@@ -201,14 +283,38 @@ pub fn request_airdrop_transaction(
         "request_airdrop_transaction: faucet_addr={} id={} lamports={} blockhash={}",
         faucet_addr, id, lamports, blockhash
     );
-
-    let mut stream = TcpStream::connect_timeout(faucet_addr, Duration::new(3, 0))?;
-    stream.set_read_timeout(Some(Duration::new(10, 0)))?;
     let req = FaucetRequest::GetAirdrop {
         lamports,
         blockhash,
         to: *id,
     };
+    send_faucet_request(faucet_addr, req)
+}
+
+/// Requests an airdrop from a faucet verifying the requests, passing it `token`
+pub fn request_airdrop_transaction_with_token(
+    faucet_addr: &SocketAddr,
+    id: &Pubkey,
+    lamports: u64,
+    blockhash: Hash,
+    token: &str,
+) -> Result<Transaction, Error> {
+    info!(
+        "request_airdrop_transaction_with_token: faucet_addr={} id={} lamports={} blockhash={}",
+        faucet_addr, id, lamports, blockhash
+    );
+    let req = FaucetRequest::GetAirdropWithToken {
+        lamports,
+        blockhash,
+        to: *id,
+        token: token.to_string(),
+    };
+    send_faucet_request(faucet_addr, req)
+}
+
+fn send_faucet_request(faucet_addr: &SocketAddr, req: FaucetRequest) -> Result<Transaction, Error> {
+    let mut stream = TcpStream::connect_timeout(faucet_addr, Duration::new(3, 0))?;
+    stream.set_read_timeout(Some(Duration::new(10, 0)))?;
     let req = serialize(&req).expect("serialize faucet request");
     stream.write_all(&req)?;
 
@@ -285,11 +391,15 @@ pub fn run_faucet(
         .map_err(|e| debug!("failed to accept socket; error = {:?}", e))
         .for_each(move |socket| {
             let faucet2 = faucet.clone();
+            let ip = socket
+                .peer_addr()
+                .map(|addr| addr.ip())
+                .unwrap_or_else(|_| IpAddr::V4(Ipv4Addr::UNSPECIFIED));
             let framed = BytesCodec::new().framed(socket);
             let (writer, reader) = framed.split();
 
             let processor = reader.and_then(move |bytes| {
-                match faucet2.lock().unwrap().process_faucet_request(&bytes) {
+                match faucet2.lock().unwrap().process_faucet_request(&bytes, ip) {
                     Ok(response_bytes) => {
                         trace!("Airdrop response_bytes: {:?}", response_bytes.to_vec());
                         Ok(response_bytes)
@@ -316,6 +426,7 @@ pub fn run_faucet(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::request_limits::{AccessTokens, RequestLimitsConfig};
     use bytes::BufMut;
     use solana_sdk::system_instruction::SystemInstruction;
     use std::time::Duration;
@@ -390,7 +501,10 @@ mod tests {
         let mint_pubkey = mint.pubkey();
         let mut faucet = Faucet::new(mint, None, None, None);
 
-        let tx = faucet.build_airdrop_transaction(request).unwrap();
+        let ip = "127.0.0.1".parse().unwrap();
+        let tx = faucet
+            .build_airdrop_transaction(request.clone(), ip)
+            .unwrap();
         let message = tx.message();
 
         assert_eq!(tx.signatures.len(), 1);
@@ -407,14 +521,48 @@ mod tests {
         // Test per-time request cap
         let mint = Keypair::new();
         faucet = Faucet::new(mint, None, Some(1), None);
-        let tx = faucet.build_airdrop_transaction(request);
+        let tx = faucet.build_airdrop_transaction(request.clone(), ip);
         assert!(tx.is_err());
 
         // Test per-request cap
         let mint = Keypair::new();
         faucet = Faucet::new(mint, None, None, Some(1));
-        let tx = faucet.build_airdrop_transaction(request);
+        let tx = faucet.build_airdrop_transaction(request.clone(), ip);
         assert!(tx.is_err());
+
+        // Test per-IP cap
+        let mint = Keypair::new();
+        faucet = Faucet::new(mint, None, None, None);
+        faucet.set_request_limits(RequestLimits::new(RequestLimitsConfig {
+            per_ip_cap: Some(3),
+            ..RequestLimitsConfig::default()
+        }));
+        assert!(faucet
+            .build_airdrop_transaction(request.clone(), ip)
+            .is_ok());
+        assert!(faucet
+            .build_airdrop_transaction(request.clone(), ip)
+            .is_err());
+        let other_ip = "127.0.0.2".parse().unwrap();
+        assert!(faucet
+            .build_airdrop_transaction(request.clone(), other_ip)
+            .is_ok());
+
+        // Test request verifier
+        let mint = Keypair::new();
+        faucet = Faucet::new(mint, None, None, None);
+        faucet.set_request_verifier(Box::new(AccessTokens::new(
+            vec!["secret".to_string()].into_iter().collect(),
+        )));
+        assert!(faucet.build_airdrop_transaction(request, ip).is_err());
+        let request = FaucetRequest::GetAirdropWithToken {
+            lamports: 2,
+            to,
+            blockhash,
+            token: "secret".to_string(),
+        };
+        assert!(!format!("{:?}", request).contains("secret"));
+        assert!(faucet.build_airdrop_transaction(request, ip).is_ok());
     }
 
     #[test]
@@ -441,12 +589,13 @@ mod tests {
         expected_vec_with_length.extend_from_slice(&expected_bytes);
 
         let mut faucet = Faucet::new(keypair, None, None, None);
-        let response = faucet.process_faucet_request(&bytes);
+        let ip = "127.0.0.1".parse().unwrap();
+        let response = faucet.process_faucet_request(&bytes, ip);
         let response_vec = response.unwrap().to_vec();
         assert_eq!(expected_vec_with_length, response_vec);
 
         let mut bad_bytes = BytesMut::with_capacity(9);
         bad_bytes.put("bad bytes");
-        assert!(faucet.process_faucet_request(&bad_bytes).is_err());
+        assert!(faucet.process_faucet_request(&bad_bytes, ip).is_err());
     }
 }
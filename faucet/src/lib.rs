@@ -1,2 +1,3 @@
 pub mod faucet;
 pub mod faucet_mock;
+pub mod request_limits;
@@ -0,0 +1,383 @@
+//! The `request_limits` module caps the lamports airdropped to each source IP and to each
+//! recipient over a time window. The accounting can be kept in a file, so that restarting the
+//! faucet does not reset it.
+
+use bincode::{deserialize, serialize};
+use serde_derive::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::{self, Error, ErrorKind},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const LIMITS_WINDOW: u64 = 24 * 60 * 60;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RequestLimitsConfig {
+    /// Period over which the caps apply
+    pub window: Duration,
+    /// Lamports granted to requests from a single IP per window
+    pub per_ip_cap: Option<u64>,
+    /// Number of requests granted from a single IP per window
+    pub per_ip_request_cap: Option<u64>,
+    /// Lamports granted to a single recipient per window
+    pub per_pubkey_cap: Option<u64>,
+}
+
+impl Default for RequestLimitsConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(LIMITS_WINDOW),
+            per_ip_cap: None,
+            per_ip_request_cap: None,
+            per_pubkey_cap: None,
+        }
+    }
+}
+
+/// What was granted to an IP or pubkey in its current window
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Usage {
+    /// Timestamp the window started at, in ms
+    pub window_start: u64,
+    pub lamports: u64,
+    pub requests: u64,
+}
+
+impl Usage {
+    fn is_expired(&self, window: Duration, now: u64) -> bool {
+        now.saturating_sub(self.window_start) >= window.as_millis() as u64
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LimitExceeded {
+    Ip { lamports: u64, cap: u64 },
+    IpRequests { requests: u64, cap: u64 },
+    Pubkey { lamports: u64, cap: u64 },
+}
+
+impl LimitExceeded {
+    /// Short name of the limit, for metrics
+    pub fn reason(&self) -> &'static str {
+        match self {
+            LimitExceeded::Ip { .. } => "per_ip_cap",
+            LimitExceeded::IpRequests { .. } => "per_ip_request_cap",
+            LimitExceeded::Pubkey { .. } => "per_pubkey_cap",
+        }
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitExceeded::Ip { lamports, cap } => {
+                write!(f, "IP limit reached; req total: {} cap: {}", lamports, cap)
+            }
+            LimitExceeded::IpRequests { requests, cap } => write!(
+                f,
+                "IP request limit reached; requests: {} cap: {}",
+                requests, cap
+            ),
+            LimitExceeded::Pubkey { lamports, cap } => write!(
+                f,
+                "recipient limit reached; req total: {} cap: {}",
+                lamports, cap
+            ),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct UsageLedger {
+    ips: HashMap<IpAddr, Usage>,
+    pubkeys: HashMap<Pubkey, Usage>,
+}
+
+pub struct RequestLimits {
+    config: RequestLimitsConfig,
+    ledger: UsageLedger,
+    path: Option<PathBuf>,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self::new(RequestLimitsConfig::default())
+    }
+}
+
+impl RequestLimits {
+    /// Limits kept in memory only
+    pub fn new(config: RequestLimitsConfig) -> Self {
+        Self {
+            config,
+            ledger: UsageLedger::default(),
+            path: None,
+        }
+    }
+
+    /// Limits persisted to `path`, starting from the usage saved there if it exists
+    pub fn load(config: RequestLimitsConfig, path: &Path) -> io::Result<Self> {
+        let ledger = if path.exists() {
+            deserialize(&fs::read(path)?).map_err(|err| {
+                Error::new(
+                    ErrorKind::Other,
+                    format!("invalid faucet limits file {:?}: {:?}", path, err),
+                )
+            })?
+        } else {
+            UsageLedger::default()
+        };
+        Ok(Self {
+            config,
+            ledger,
+            path: Some(path.to_path_buf()),
+        })
+    }
+
+    pub fn config(&self) -> &RequestLimitsConfig {
+        &self.config
+    }
+
+    /// Usage of `ip` in its window current at `now`
+    pub fn ip_usage(&self, ip: &IpAddr, now: u64) -> Usage {
+        self.current_usage(self.ledger.ips.get(ip), now)
+    }
+
+    /// Usage of `pubkey` in its window current at `now`
+    pub fn pubkey_usage(&self, pubkey: &Pubkey, now: u64) -> Usage {
+        self.current_usage(self.ledger.pubkeys.get(pubkey), now)
+    }
+
+    fn current_usage(&self, usage: Option<&Usage>, now: u64) -> Usage {
+        match usage {
+            Some(usage) if !usage.is_expired(self.config.window, now) => *usage,
+            _ => Usage {
+                window_start: now,
+                ..Usage::default()
+            },
+        }
+    }
+
+    /// Checks whether granting `lamports` to `to`, requested from `ip`, stays within the caps
+    pub fn check(
+        &self,
+        ip: &IpAddr,
+        to: &Pubkey,
+        lamports: u64,
+        now: u64,
+    ) -> Result<(), LimitExceeded> {
+        let ip_usage = self.ip_usage(ip, now);
+        if let Some(cap) = self.config.per_ip_request_cap {
+            if ip_usage.requests >= cap {
+                return Err(LimitExceeded::IpRequests {
+                    requests: ip_usage.requests,
+                    cap,
+                });
+            }
+        }
+        let within = |used: u64, cap: Option<u64>| {
+            cap.map(|cap| {
+                used.checked_add(lamports)
+                    .map(|total| total <= cap)
+                    .unwrap_or(false)
+            })
+            .unwrap_or(true)
+        };
+        if !within(ip_usage.lamports, self.config.per_ip_cap) {
+            return Err(LimitExceeded::Ip {
+                lamports: ip_usage.lamports.saturating_add(lamports),
+                cap: self.config.per_ip_cap.unwrap(),
+            });
+        }
+        let pubkey_usage = self.pubkey_usage(to, now);
+        if !within(pubkey_usage.lamports, self.config.per_pubkey_cap) {
+            return Err(LimitExceeded::Pubkey {
+                lamports: pubkey_usage.lamports.saturating_add(lamports),
+                cap: self.config.per_pubkey_cap.unwrap(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Accounts for `lamports` granted to `to`, and saves the usage if persisted
+    pub fn record(&mut self, ip: IpAddr, to: Pubkey, lamports: u64, now: u64) -> io::Result<()> {
+        let mut ip_usage = self.ip_usage(&ip, now);
+        ip_usage.lamports = ip_usage.lamports.saturating_add(lamports);
+        ip_usage.requests += 1;
+        let mut pubkey_usage = self.pubkey_usage(&to, now);
+        pubkey_usage.lamports = pubkey_usage.lamports.saturating_add(lamports);
+        pubkey_usage.requests += 1;
+        self.ledger.ips.insert(ip, ip_usage);
+        self.ledger.pubkeys.insert(to, pubkey_usage);
+        self.save(now)
+    }
+
+    fn save(&mut self, now: u64) -> io::Result<()> {
+        let window = self.config.window;
+        self.ledger
+            .ips
+            .retain(|_, usage| !usage.is_expired(window, now));
+        self.ledger
+            .pubkeys
+            .retain(|_, usage| !usage.is_expired(window, now));
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let data = serialize(&self.ledger)
+            .map_err(|err| Error::new(ErrorKind::Other, format!("{:?}", err)))?;
+        // Write a new file and move it over the old one, so that a crash does not leave a
+        // truncated file behind
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+/// Checks airdrop requests before they are granted, e.g. against a captcha service. The token is
+/// the one the requester sent along, if any.
+pub trait RequestVerifier: Send {
+    fn verify(
+        &self,
+        ip: &IpAddr,
+        to: &Pubkey,
+        lamports: u64,
+        token: Option<&str>,
+    ) -> Result<(), String>;
+}
+
+/// Only grants requests bearing one of the given access tokens
+pub struct AccessTokens(HashSet<String>);
+
+impl AccessTokens {
+    pub fn new(tokens: HashSet<String>) -> Self {
+        Self(tokens)
+    }
+
+    /// Reads the tokens from a file, one per line
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let tokens = fs::read_to_string(path)?
+            .lines()
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(str::to_string)
+            .collect();
+        Ok(Self(tokens))
+    }
+}
+
+impl RequestVerifier for AccessTokens {
+    fn verify(
+        &self,
+        _ip: &IpAddr,
+        _to: &Pubkey,
+        _lamports: u64,
+        token: Option<&str>,
+    ) -> Result<(), String> {
+        match token {
+            Some(token) if self.0.contains(token) => Ok(()),
+            Some(_) => Err("invalid access token".to_string()),
+            None => Err("missing access token".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_limits() {
+        let config = RequestLimitsConfig {
+            window: Duration::from_secs(10),
+            per_ip_cap: Some(100),
+            per_ip_request_cap: Some(3),
+            per_pubkey_cap: Some(50),
+        };
+        let mut limits = RequestLimits::new(config);
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let other_ip: IpAddr = "127.0.0.2".parse().unwrap();
+        let to = solana_sdk::pubkey::new_rand();
+        let other_to = solana_sdk::pubkey::new_rand();
+
+        assert_eq!(limits.check(&ip, &to, 40, 0), Ok(()));
+        limits.record(ip, to, 40, 0).unwrap();
+        assert_eq!(
+            limits.check(&other_ip, &to, 20, 1_000),
+            Err(LimitExceeded::Pubkey {
+                lamports: 60,
+                cap: 50
+            })
+        );
+        assert_eq!(
+            limits.check(&ip, &other_to, 70, 1_000),
+            Err(LimitExceeded::Ip {
+                lamports: 110,
+                cap: 100
+            })
+        );
+        limits.record(ip, other_to, 10, 1_000).unwrap();
+        limits.record(ip, other_to, 10, 1_000).unwrap();
+        assert_eq!(
+            limits.check(&ip, &other_to, 1, 2_000),
+            Err(LimitExceeded::IpRequests {
+                requests: 3,
+                cap: 3
+            })
+        );
+        assert_eq!(limits.ip_usage(&ip, 2_000).lamports, 60);
+        assert_eq!(limits.pubkey_usage(&other_to, 2_000).lamports, 20);
+
+        // The window is over
+        assert_eq!(limits.check(&ip, &to, 50, 10_000), Ok(()));
+        assert_eq!(
+            limits.ip_usage(&ip, 10_000),
+            Usage {
+                window_start: 10_000,
+                lamports: 0,
+                requests: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_request_limits_persistence() {
+        let ledger_dir = std::env::temp_dir().join(format!(
+            "test_request_limits_persistence-{}",
+            solana_sdk::pubkey::new_rand()
+        ));
+        fs::create_dir_all(&ledger_dir).unwrap();
+        let path = ledger_dir.join("limits");
+        let config = RequestLimitsConfig {
+            per_pubkey_cap: Some(10),
+            ..RequestLimitsConfig::default()
+        };
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let to = solana_sdk::pubkey::new_rand();
+
+        let mut limits = RequestLimits::load(config.clone(), &path).unwrap();
+        limits.record(ip, to, 10, 0).unwrap();
+        drop(limits);
+
+        let limits = RequestLimits::load(config, &path).unwrap();
+        assert_eq!(limits.pubkey_usage(&to, 1).lamports, 10);
+        assert_eq!(limits.ip_usage(&ip, 1).requests, 1);
+        assert!(limits.check(&ip, &to, 1, 1).is_err());
+        fs::remove_dir_all(ledger_dir).unwrap();
+    }
+
+    #[test]
+    fn test_access_tokens() {
+        let tokens = AccessTokens::new(vec!["secret".to_string()].into_iter().collect());
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let to = solana_sdk::pubkey::new_rand();
+        assert!(tokens.verify(&ip, &to, 1, Some("secret")).is_ok());
+        assert!(tokens.verify(&ip, &to, 1, Some("guess")).is_err());
+        assert!(tokens.verify(&ip, &to, 1, None).is_err());
+    }
+}
@@ -0,0 +1,271 @@
+//! The `genesis_builder` module assembles a `GenesisConfig` programmatically, for tools and tests
+//! setting up private clusters without going through the `solana-genesis` command line.
+
+use solana_sdk::{
+    account::Account,
+    clock::UnixTimestamp,
+    epoch_schedule::EpochSchedule,
+    fee_calculator::FeeRateGovernor,
+    genesis_config::{ClusterType, GenesisConfig},
+    inflation::Inflation,
+    poh_config::PohConfig,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use solana_stake_program::stake_state;
+use solana_vote_program::vote_state::{self, VoteState};
+use std::{fs::File, io, io::Read, path::Path};
+
+struct BootstrapValidator {
+    identity_pubkey: Pubkey,
+    vote_pubkey: Pubkey,
+    stake_pubkey: Pubkey,
+    stake_authority: Option<Pubkey>,
+    lamports: u64,
+    stake_lamports: u64,
+}
+
+struct BpfProgram {
+    address: Pubkey,
+    loader: Pubkey,
+    data: Vec<u8>,
+}
+
+/// Builds a `GenesisConfig`, starting from `GenesisConfig::default()`. The accounts depending on
+/// the rent, those of the bootstrap validators and BPF programs, are created by `build()` with
+/// the final rent.
+#[derive(Default)]
+pub struct GenesisConfigBuilder {
+    genesis_config: GenesisConfig,
+    bootstrap_validators: Vec<BootstrapValidator>,
+    bpf_programs: Vec<BpfProgram>,
+}
+
+impl GenesisConfigBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cluster_type(mut self, cluster_type: ClusterType) -> Self {
+        self.genesis_config.cluster_type = cluster_type;
+        self
+    }
+
+    pub fn creation_time(mut self, creation_time: UnixTimestamp) -> Self {
+        self.genesis_config.creation_time = creation_time;
+        self
+    }
+
+    pub fn ticks_per_slot(mut self, ticks_per_slot: u64) -> Self {
+        self.genesis_config.ticks_per_slot = ticks_per_slot;
+        self
+    }
+
+    pub fn poh_config(mut self, poh_config: PohConfig) -> Self {
+        self.genesis_config.poh_config = poh_config;
+        self
+    }
+
+    pub fn fee_rate_governor(mut self, fee_rate_governor: FeeRateGovernor) -> Self {
+        self.genesis_config.fee_rate_governor = fee_rate_governor;
+        self
+    }
+
+    pub fn rent(mut self, rent: Rent) -> Self {
+        self.genesis_config.rent = rent;
+        self
+    }
+
+    pub fn inflation(mut self, inflation: Inflation) -> Self {
+        self.genesis_config.inflation = inflation;
+        self
+    }
+
+    /// Epochs of `slots_per_epoch` slots, starting with short epochs that double in length up to
+    /// it if `enable_warmup_epochs`
+    pub fn epoch_schedule(mut self, slots_per_epoch: u64, enable_warmup_epochs: bool) -> Self {
+        self.genesis_config.epoch_schedule =
+            EpochSchedule::custom(slots_per_epoch, slots_per_epoch, enable_warmup_epochs);
+        self
+    }
+
+    pub fn native_instruction_processor(mut self, name: String, program_id: Pubkey) -> Self {
+        self.genesis_config
+            .add_native_instruction_processor(name, program_id);
+        self
+    }
+
+    pub fn account(mut self, pubkey: Pubkey, account: Account) -> Self {
+        self.genesis_config.add_account(pubkey, account);
+        self
+    }
+
+    /// Adds a validator staked from genesis: its identity account holding `lamports`, its vote
+    /// account, and a stake account of `stake_lamports` delegated to it, managed by
+    /// `stake_authority` or else the identity
+    pub fn bootstrap_validator(
+        mut self,
+        identity_pubkey: Pubkey,
+        vote_pubkey: Pubkey,
+        stake_pubkey: Pubkey,
+        stake_authority: Option<Pubkey>,
+        lamports: u64,
+        stake_lamports: u64,
+    ) -> Self {
+        self.bootstrap_validators.push(BootstrapValidator {
+            identity_pubkey,
+            vote_pubkey,
+            stake_pubkey,
+            stake_authority,
+            lamports,
+            stake_lamports,
+        });
+        self
+    }
+
+    /// Installs the program `data` as an executable account at `address`, owned by `loader`
+    pub fn bpf_program(mut self, address: Pubkey, loader: Pubkey, data: Vec<u8>) -> Self {
+        self.bpf_programs.push(BpfProgram {
+            address,
+            loader,
+            data,
+        });
+        self
+    }
+
+    /// Installs the program read from the shared object at `path`
+    pub fn bpf_program_from_file(
+        self,
+        address: Pubkey,
+        loader: Pubkey,
+        path: &Path,
+    ) -> io::Result<Self> {
+        let mut data = vec![];
+        File::open(path).and_then(|mut file| file.read_to_end(&mut data))?;
+        Ok(self.bpf_program(address, loader, data))
+    }
+
+    pub fn build(self) -> GenesisConfig {
+        let mut genesis_config = self.genesis_config;
+        let rent = genesis_config.rent;
+        for validator in self.bootstrap_validators {
+            genesis_config.add_account(
+                validator.identity_pubkey,
+                Account::new(validator.lamports, 0, &system_program::id()),
+            );
+            let vote_account = vote_state::create_account_with_authorized(
+                &validator.identity_pubkey,
+                &validator.identity_pubkey,
+                &validator.identity_pubkey,
+                100,
+                VoteState::get_rent_exempt_reserve(&rent).max(1),
+            );
+            genesis_config.add_account(
+                validator.stake_pubkey,
+                stake_state::create_account(
+                    validator
+                        .stake_authority
+                        .as_ref()
+                        .unwrap_or(&validator.identity_pubkey),
+                    &validator.vote_pubkey,
+                    &vote_account,
+                    &rent,
+                    validator.stake_lamports,
+                ),
+            );
+            genesis_config.add_account(validator.vote_pubkey, vote_account);
+        }
+        for program in self.bpf_programs {
+            add_bpf_program(
+                &mut genesis_config,
+                program.address,
+                program.loader,
+                program.data,
+            );
+        }
+        genesis_config
+    }
+}
+
+/// Installs the program `data` as a rent-exempt executable account at `address`, owned by
+/// `loader`
+pub fn add_bpf_program(
+    genesis_config: &mut GenesisConfig,
+    address: Pubkey,
+    loader: Pubkey,
+    data: Vec<u8>,
+) {
+    genesis_config.add_account(
+        address,
+        Account {
+            lamports: genesis_config.rent.minimum_balance(data.len()),
+            data,
+            executable: true,
+            owner: loader,
+            rent_epoch: 0,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::bpf_loader;
+
+    #[test]
+    fn test_genesis_config_builder() {
+        let identity_pubkey = solana_sdk::pubkey::new_rand();
+        let vote_pubkey = solana_sdk::pubkey::new_rand();
+        let stake_pubkey = solana_sdk::pubkey::new_rand();
+        let program_address = solana_sdk::pubkey::new_rand();
+        let rent = Rent {
+            lamports_per_byte_year: 10,
+            ..Rent::default()
+        };
+        let mut inflation = Inflation::new_fixed(0.05);
+        inflation.foundation = 0.01;
+
+        let genesis_config = GenesisConfigBuilder::new()
+            .cluster_type(ClusterType::Testnet)
+            .creation_time(42)
+            .ticks_per_slot(8)
+            .inflation(inflation)
+            .epoch_schedule(256, true)
+            // The program accounts are funded with the rent set afterwards
+            .bpf_program(program_address, bpf_loader::id(), vec![1; 100])
+            .rent(rent)
+            .bootstrap_validator(
+                identity_pubkey,
+                vote_pubkey,
+                stake_pubkey,
+                None,
+                500,
+                1_000_000,
+            )
+            .build();
+
+        assert_eq!(genesis_config.cluster_type, ClusterType::Testnet);
+        assert_eq!(genesis_config.creation_time, 42);
+        assert_eq!(genesis_config.ticks_per_slot, 8);
+        assert_eq!(genesis_config.inflation, inflation);
+        assert_eq!(genesis_config.epoch_schedule.slots_per_epoch, 256);
+        assert!(genesis_config.epoch_schedule.warmup);
+
+        let program = &genesis_config.accounts[&program_address];
+        assert!(program.executable);
+        assert_eq!(program.owner, bpf_loader::id());
+        assert_eq!(program.data, vec![1; 100]);
+        assert_eq!(program.lamports, rent.minimum_balance(100));
+
+        assert_eq!(genesis_config.accounts[&identity_pubkey].lamports, 500);
+        assert_eq!(
+            genesis_config.accounts[&vote_pubkey].owner,
+            solana_vote_program::id()
+        );
+        let stake_account = &genesis_config.accounts[&stake_pubkey];
+        assert_eq!(stake_account.lamports, 1_000_000);
+        let stake = stake_state::StakeState::stake_from(stake_account).unwrap();
+        assert_eq!(stake.delegation.voter_pubkey, vote_pubkey);
+    }
+}
@@ -1,5 +1,6 @@
 pub mod address_generator;
 pub mod genesis_accounts;
+pub mod genesis_builder;
 pub mod stakes;
 pub mod unlocks;
 
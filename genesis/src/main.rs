@@ -10,9 +10,15 @@ extern crate solana_vest_program;
 use clap::{crate_description, crate_name, value_t, value_t_or_exit, App, Arg, ArgMatches};
 use solana_clap_utils::{
     input_parsers::{cluster_type_of, pubkey_of, pubkeys_of, unix_timestamp_from_rfc3339_datetime},
-    input_validators::{is_pubkey_or_keypair, is_rfc3339_datetime, is_valid_percentage},
+    input_validators::{
+        is_parsable, is_pubkey_or_keypair, is_rfc3339_datetime, is_valid_percentage,
+    },
+};
+use solana_genesis::{
+    genesis_accounts::add_genesis_accounts,
+    genesis_builder::{add_bpf_program, GenesisConfigBuilder},
+    Base64Account,
 };
-use solana_genesis::{genesis_accounts::add_genesis_accounts, Base64Account};
 use solana_ledger::{
     blockstore::create_new_ledger, blockstore_db::AccessType, poh::compute_hashes_per_tick,
 };
@@ -20,9 +26,9 @@ use solana_runtime::hardened_unpack::MAX_GENESIS_ARCHIVE_UNPACKED_SIZE;
 use solana_sdk::{
     account::Account,
     clock,
-    epoch_schedule::EpochSchedule,
     fee_calculator::FeeRateGovernor,
     genesis_config::{ClusterType, GenesisConfig},
+    inflation::Inflation,
     native_token::sol_to_lamports,
     poh_config::PohConfig,
     pubkey::Pubkey,
@@ -30,8 +36,8 @@ use solana_sdk::{
     signature::{Keypair, Signer},
     system_program, timing,
 };
-use solana_stake_program::stake_state::{self, StakeState};
-use solana_vote_program::vote_state::{self, VoteState};
+use solana_stake_program::stake_state::StakeState;
+use solana_vote_program::vote_state::VoteState;
 use std::{
     collections::HashMap,
     error,
@@ -362,6 +368,55 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                 .multiple(true)
                 .help("Install a BPF program at the given address"),
         )
+        .arg(
+            Arg::with_name("inflation")
+                .long("inflation")
+                .value_name("SCHEDULE")
+                .takes_value(true)
+                .possible_values(&["default", "pico", "none"])
+                .default_value("default")
+                .help("Base inflation schedule, adjusted by the --inflation-* arguments"),
+        )
+        .arg(
+            Arg::with_name("inflation_initial")
+                .long("inflation-initial")
+                .value_name("RATE")
+                .takes_value(true)
+                .validator(is_parsable::<f64>)
+                .help("Initial yearly inflation rate"),
+        )
+        .arg(
+            Arg::with_name("inflation_terminal")
+                .long("inflation-terminal")
+                .value_name("RATE")
+                .takes_value(true)
+                .validator(is_parsable::<f64>)
+                .help("Yearly inflation rate the inflation tapers down to"),
+        )
+        .arg(
+            Arg::with_name("inflation_taper")
+                .long("inflation-taper")
+                .value_name("RATE")
+                .takes_value(true)
+                .validator(is_parsable::<f64>)
+                .help("Rate per year at which the inflation is lowered until reaching the terminal rate"),
+        )
+        .arg(
+            Arg::with_name("inflation_foundation")
+                .long("inflation-foundation")
+                .value_name("RATE")
+                .takes_value(true)
+                .validator(is_parsable::<f64>)
+                .help("Share of the inflation allocated to the foundation"),
+        )
+        .arg(
+            Arg::with_name("inflation_foundation_term")
+                .long("inflation-foundation-term")
+                .value_name("YEARS")
+                .takes_value(true)
+                .validator(is_parsable::<f64>)
+                .help("Duration of the foundation's inflation allocation"),
+        )
         .get_matches();
 
     let faucet_lamports = value_t!(matches, "faucet_lamports", u64).unwrap_or(0);
@@ -464,82 +519,71 @@ fn main() -> Result<(), Box<dyn error::Error>> {
             }
         }
     };
-    let epoch_schedule = EpochSchedule::custom(
-        slots_per_epoch,
-        slots_per_epoch,
-        matches.is_present("enable_warmup_epochs"),
-    );
+    let mut inflation = match matches.value_of("inflation").unwrap() {
+        "pico" => Inflation::pico(),
+        "none" => Inflation::new_disabled(),
+        _ => Inflation::default(),
+    };
+    if let Ok(initial) = value_t!(matches, "inflation_initial", f64) {
+        inflation.initial = initial;
+    }
+    if let Ok(terminal) = value_t!(matches, "inflation_terminal", f64) {
+        inflation.terminal = terminal;
+    }
+    if let Ok(taper) = value_t!(matches, "inflation_taper", f64) {
+        inflation.taper = taper;
+    }
+    if let Ok(foundation) = value_t!(matches, "inflation_foundation", f64) {
+        inflation.foundation = foundation;
+    }
+    if let Ok(foundation_term) = value_t!(matches, "inflation_foundation_term", f64) {
+        inflation.foundation_term = foundation_term;
+    }
 
-    let native_instruction_processors = if cluster_type == ClusterType::Development {
-        vec![
+    let mut genesis_config_builder = GenesisConfigBuilder::new()
+        .cluster_type(cluster_type)
+        .ticks_per_slot(ticks_per_slot)
+        .epoch_schedule(slots_per_epoch, matches.is_present("enable_warmup_epochs"))
+        .fee_rate_governor(fee_rate_governor)
+        .rent(rent)
+        .inflation(inflation)
+        .poh_config(poh_config);
+
+    if cluster_type == ClusterType::Development {
+        for (name, program_id) in vec![
             solana_vest_program!(),
             solana_budget_program!(),
             solana_exchange_program!(),
-        ]
-    } else {
-        vec![]
-    };
-
-    let mut genesis_config = GenesisConfig {
-        native_instruction_processors,
-        ticks_per_slot,
-        epoch_schedule,
-        fee_rate_governor,
-        rent,
-        poh_config,
-        cluster_type,
-        ..GenesisConfig::default()
-    };
-
-    let mut bootstrap_validator_pubkeys_iter = bootstrap_validator_pubkeys.iter();
-    loop {
-        let identity_pubkey = match bootstrap_validator_pubkeys_iter.next() {
-            None => break,
-            Some(identity_pubkey) => identity_pubkey,
-        };
-        let vote_pubkey = bootstrap_validator_pubkeys_iter.next().unwrap();
-        let stake_pubkey = bootstrap_validator_pubkeys_iter.next().unwrap();
-
-        genesis_config.add_account(
-            *identity_pubkey,
-            Account::new(bootstrap_validator_lamports, 0, &system_program::id()),
-        );
-
-        let vote_account = vote_state::create_account_with_authorized(
-            &identity_pubkey,
-            &identity_pubkey,
-            &identity_pubkey,
-            100,
-            VoteState::get_rent_exempt_reserve(&rent).max(1),
-        );
+        ] {
+            genesis_config_builder =
+                genesis_config_builder.native_instruction_processor(name, program_id);
+        }
+    }
 
-        genesis_config.add_account(
-            *stake_pubkey,
-            stake_state::create_account(
-                bootstrap_stake_authorized_pubkey
-                    .as_ref()
-                    .unwrap_or(&identity_pubkey),
-                &vote_pubkey,
-                &vote_account,
-                &rent,
-                bootstrap_validator_stake_lamports,
-            ),
+    for pubkeys in bootstrap_validator_pubkeys.chunks(3) {
+        genesis_config_builder = genesis_config_builder.bootstrap_validator(
+            pubkeys[0],
+            pubkeys[1],
+            pubkeys[2],
+            bootstrap_stake_authorized_pubkey,
+            bootstrap_validator_lamports,
+            bootstrap_validator_stake_lamports,
         );
-
-        genesis_config.add_account(*vote_pubkey, vote_account);
     }
 
     if let Some(creation_time) = unix_timestamp_from_rfc3339_datetime(&matches, "creation_time") {
-        genesis_config.creation_time = creation_time;
+        genesis_config_builder = genesis_config_builder.creation_time(creation_time);
     }
 
     if let Some(faucet_pubkey) = faucet_pubkey {
-        genesis_config.add_account(
+        genesis_config_builder = genesis_config_builder.account(
             faucet_pubkey,
             Account::new(faucet_lamports, 0, &system_program::id()),
         );
     }
 
+    let mut genesis_config = genesis_config_builder.build();
+
     solana_stake_program::add_genesis_accounts(&mut genesis_config);
     if genesis_config.cluster_type == ClusterType::Development {
         solana_runtime::genesis_utils::activate_all_features(&mut genesis_config);
@@ -584,16 +628,7 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                             eprintln!("Error: failed to read {}: {}", program, err);
                             process::exit(1);
                         });
-                    genesis_config.add_account(
-                        address,
-                        Account {
-                            lamports: genesis_config.rent.minimum_balance(program_data.len()),
-                            data: program_data,
-                            executable: true,
-                            owner: loader,
-                            rent_epoch: 0,
-                        },
-                    );
+                    add_bpf_program(&mut genesis_config, address, loader, program_data);
                 }
                 _ => unreachable!(),
             }
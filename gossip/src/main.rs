@@ -4,20 +4,60 @@ use clap::{
     crate_description, crate_name, value_t, value_t_or_exit, App, AppSettings, Arg, ArgMatches,
     SubCommand,
 };
+use serde_json::{json, Map, Value};
 use solana_clap_utils::{
-    input_parsers::keypair_of,
-    input_validators::{is_keypair_or_ask_keyword, is_port, is_pubkey},
+    input_parsers::{keypair_of, pubkeys_of},
+    input_validators::{is_keypair_or_ask_keyword, is_port, is_pubkey, is_url},
 };
 use solana_client::rpc_client::RpcClient;
-use solana_core::{contact_info::ContactInfo, gossip_service::discover};
-use solana_sdk::pubkey::Pubkey;
+use solana_core::{
+    cluster_info::ClusterInfo,
+    contact_info::ContactInfo,
+    crds_value::{CrdsData, CrdsValue, CrdsValueLabel},
+    gossip_service::{
+        discover, discover_rpc_nodes, get_rpc_peers, make_gossip_node, RpcNodeFilter,
+    },
+};
+use solana_sdk::{
+    hash::Hash, native_token::lamports_to_sol, pubkey::Pubkey, signature::Keypair,
+    timing::timestamp,
+};
+use solana_vote_program::vote_transaction::parse_vote_transaction;
 use std::{
+    collections::{HashMap, HashSet},
     error,
     net::{IpAddr, Ipv4Addr, SocketAddr},
     process::exit,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
+const CRDS_VALUE_TYPES: &[&str] = &[
+    "contact-info",
+    "vote",
+    "lowest-slot",
+    "snapshot-hashes",
+    "accounts-hashes",
+    "epoch-slots",
+    "legacy-version",
+    "version",
+    "node-capabilities",
+    "validator-info",
+    "node-instance",
+    "planned-hard-fork",
+    "restart-bank-hash",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum OutputFormat {
+    Human,
+    Json,
+}
+
 fn parse_matches() -> ArgMatches<'static> {
     let shred_version_arg = Arg::with_name("shred_version")
         .long("shred-version")
@@ -56,6 +96,23 @@ fn parse_matches() -> ArgMatches<'static> {
                         .conflicts_with("all")
                         .help("Return any RPC URL"),
                 )
+                .arg(
+                    Arg::with_name("num_nodes")
+                        .long("num-nodes")
+                        .value_name("NUM")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("With --all, wait for at least NUM RPC nodes to be visible"),
+                )
+                .arg(
+                    Arg::with_name("min_version")
+                        .long("min-version")
+                        .value_name("MAJOR.MINOR.PATCH")
+                        .takes_value(true)
+                        .validator(|value| parse_version(&value).map(|_| ()))
+                        .help("With --any or --all, only return the RPC URLs of nodes running \
+                               at least this software version"),
+                )
                 .arg(
                     Arg::with_name("timeout")
                         .long("timeout")
@@ -139,6 +196,85 @@ fn parse_matches() -> ArgMatches<'static> {
                         .value_name("SECONDS")
                         .takes_value(true)
                         .help("Maximum time to wait in seconds [default: wait forever]"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .long("watch")
+                        .takes_value(false)
+                        .conflicts_with_all(&["num_nodes", "num_nodes_exactly", "node_pubkey"])
+                        .help("Stream the gossip values as they are received, until --timeout \
+                               if provided"),
+                )
+                .arg(
+                    Arg::with_name("filter_pubkey")
+                        .long("filter-pubkey")
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .multiple(true)
+                        .validator(is_pubkey)
+                        .help("Only output the nodes, or with --watch the gossip values, \
+                               of these pubkeys"),
+                )
+                .arg(
+                    Arg::with_name("value_type")
+                        .long("value-type")
+                        .value_name("TYPE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .possible_values(CRDS_VALUE_TYPES)
+                        .requires("watch")
+                        .help("With --watch, only output the gossip values of these types"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["human", "json"])
+                        .default_value("human")
+                        .help("Output format. With --watch, json outputs one object per line"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("version-census")
+                .about("Show the stake-weighted distribution of the software versions of the cluster")
+                .setting(AppSettings::DisableVersion)
+                .arg(
+                    Arg::with_name("entrypoint")
+                        .short("n")
+                        .long("entrypoint")
+                        .value_name("HOST:PORT")
+                        .takes_value(true)
+                        .required(true)
+                        .validator(solana_net_utils::is_host_port)
+                        .help("Rendezvous with the cluster at this entry point"),
+                )
+                .arg(
+                    Arg::with_name("json_rpc_url")
+                        .long("url")
+                        .value_name("URL")
+                        .takes_value(true)
+                        .validator(is_url)
+                        .help("JSON RPC URL to fetch the stakes from \
+                               [default: an RPC node found through gossip]"),
+                )
+                .arg(
+                    Arg::with_name("timeout")
+                        .long("timeout")
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .default_value("15")
+                        .help("Time to spend gathering the versions from gossip, in seconds"),
+                )
+                .arg(&shred_version_arg)
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(&["human", "json"])
+                        .default_value("human")
+                        .help("Output format"),
                 ),
         )
         .subcommand(
@@ -231,6 +367,171 @@ fn process_spy_results(
     }
 }
 
+/// Filter on the gossip values to output
+struct CrdsValueFilter {
+    pubkeys: HashSet<Pubkey>,
+    value_types: HashSet<String>,
+}
+
+impl CrdsValueFilter {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        Self {
+            pubkeys: pubkeys_of(matches, "filter_pubkey")
+                .unwrap_or_default()
+                .into_iter()
+                .collect(),
+            value_types: matches
+                .values_of("value_type")
+                .map(|values| values.map(str::to_string).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn matches_pubkey(&self, pubkey: &Pubkey) -> bool {
+        self.pubkeys.is_empty() || self.pubkeys.contains(pubkey)
+    }
+
+    fn matches(&self, value: &CrdsValue) -> bool {
+        self.matches_pubkey(&value.pubkey())
+            && (self.value_types.is_empty()
+                || self.value_types.contains(crds_value_type(&value.data)))
+    }
+}
+
+fn crds_value_type(data: &CrdsData) -> &'static str {
+    match data {
+        CrdsData::ContactInfo(_) => "contact-info",
+        CrdsData::Vote(_, _) => "vote",
+        CrdsData::LowestSlot(_, _) => "lowest-slot",
+        CrdsData::SnapshotHashes(_) => "snapshot-hashes",
+        CrdsData::AccountsHashes(_) => "accounts-hashes",
+        CrdsData::EpochSlots(_, _) => "epoch-slots",
+        CrdsData::LegacyVersion(_) => "legacy-version",
+        CrdsData::Version(_) => "version",
+        CrdsData::NodeCapabilities(_) => "node-capabilities",
+        CrdsData::ValidatorInfo(_) => "validator-info",
+        CrdsData::NodeInstance(_) => "node-instance",
+        CrdsData::PlannedHardFork(_) => "planned-hard-fork",
+        CrdsData::RestartBankHash(_) => "restart-bank-hash",
+        CrdsData::EpochSlotsShard(_, _) => "epoch-slots-shard",
+    }
+}
+
+fn contact_info_details(contact_info: &ContactInfo) -> Map<String, Value> {
+    let mut details = Map::new();
+    details.insert("gossip".to_string(), json!(contact_info.gossip.to_string()));
+    details.insert("tpu".to_string(), json!(contact_info.tpu.to_string()));
+    details.insert("rpc".to_string(), json!(contact_info.rpc.to_string()));
+    details.insert(
+        "shredVersion".to_string(),
+        json!(contact_info.shred_version),
+    );
+    details
+}
+
+/// The most telling fields of the gossip value, depending on its type
+fn crds_value_details(value: &CrdsValue) -> Map<String, Value> {
+    let mut details = Map::new();
+    match &value.data {
+        CrdsData::ContactInfo(contact_info) => {
+            details = contact_info_details(contact_info);
+        }
+        CrdsData::Vote(_, vote) => {
+            if let Some((_, vote, _)) = parse_vote_transaction(&vote.transaction) {
+                details.insert("slot".to_string(), json!(vote.slots.last()));
+            }
+        }
+        CrdsData::SnapshotHashes(snapshot_hash) | CrdsData::AccountsHashes(snapshot_hash) => {
+            if let Some((slot, hash)) = snapshot_hash.hashes.last() {
+                details.insert("slot".to_string(), json!(slot));
+                details.insert("hash".to_string(), json!(hash.to_string()));
+            }
+        }
+        CrdsData::LowestSlot(_, lowest_slot) => {
+            details.insert("lowest".to_string(), json!(lowest_slot.lowest));
+        }
+        CrdsData::Version(version) => {
+            details.insert("version".to_string(), json!(version.version.to_string()));
+        }
+        _ => (),
+    }
+    details
+}
+
+fn print_crds_value(value: &CrdsValue, output: OutputFormat) {
+    let value_type = crds_value_type(&value.data);
+    let details = crds_value_details(value);
+    match output {
+        OutputFormat::Json => {
+            let mut object = Map::new();
+            object.insert("type".to_string(), json!(value_type));
+            object.insert("pubkey".to_string(), json!(value.pubkey().to_string()));
+            object.insert("wallclock".to_string(), json!(value.wallclock()));
+            object.insert("details".to_string(), Value::Object(details));
+            println!("{}", Value::Object(object));
+        }
+        OutputFormat::Human => {
+            let details: Vec<_> = details
+                .iter()
+                .map(|(key, value)| match value {
+                    Value::String(value) => format!("{}={}", key, value),
+                    value => format!("{}={}", key, value),
+                })
+                .collect();
+            println!(
+                "{:<18} {:<44} {}",
+                value_type,
+                value.pubkey(),
+                details.join(" ")
+            );
+        }
+    }
+}
+
+/// Outputs the gossip values matching `filter` as they are inserted or updated
+fn watch_gossip(
+    cluster_info: &ClusterInfo,
+    filter: &CrdsValueFilter,
+    output: OutputFormat,
+    timeout: Option<u64>,
+) {
+    let start = Instant::now();
+    // Hashes of the values output so far
+    let mut seen: HashMap<CrdsValueLabel, Hash> = HashMap::new();
+    let mut last_update = 0;
+    loop {
+        if let Some(secs) = timeout {
+            if start.elapsed() >= Duration::from_secs(secs) {
+                break;
+            }
+        }
+        let now = timestamp();
+        let updated: Vec<_> = {
+            let gossip = cluster_info.gossip.read().unwrap();
+            gossip
+                .crds
+                .table
+                .iter()
+                .filter(|(label, value)| {
+                    value.local_timestamp >= last_update
+                        && seen.get(*label) != Some(&value.value_hash)
+                        && filter.matches(&value.value)
+                })
+                .map(|(label, value)| (label.clone(), value.value_hash, value.value.clone()))
+                .collect()
+        };
+        // Values updated during this same millisecond are checked again on the next round
+        last_update = now;
+        for (label, value_hash, value) in updated {
+            print_crds_value(&value, output);
+            seen.insert(label, value_hash);
+        }
+        sleep(Duration::from_millis(
+            solana_core::cluster_info::CFG.GOSSIP_SLEEP_MILLIS,
+        ));
+    }
+}
+
 fn process_spy(matches: &ArgMatches) -> std::io::Result<()> {
     let num_nodes_exactly = matches
         .value_of("num_nodes_exactly")
@@ -263,7 +564,27 @@ fn process_spy(matches: &ArgMatches) -> std::io::Result<()> {
         }),
     );
 
-    let (_all_peers, validators) = discover(
+    let filter = CrdsValueFilter::from_matches(matches);
+    let output = match matches.value_of("output") {
+        Some("json") => OutputFormat::Json,
+        _ => OutputFormat::Human,
+    };
+
+    if matches.is_present("watch") {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (_gossip_service, ip_echo, cluster_info) = make_gossip_node(
+            identity_keypair.unwrap_or_else(|| Arc::new(Keypair::new())),
+            entrypoint_addr.as_ref(),
+            &exit,
+            Some(&gossip_addr),
+            shred_version,
+        );
+        let _ip_echo_server = ip_echo.map(solana_net_utils::ip_echo_server);
+        watch_gossip(&cluster_info, &filter, output, timeout);
+        return Ok(());
+    }
+
+    let (all_peers, validators) = discover(
         identity_keypair,
         entrypoint_addr.as_ref(),
         num_nodes,
@@ -274,6 +595,19 @@ fn process_spy(matches: &ArgMatches) -> std::io::Result<()> {
         shred_version,
     )?;
 
+    if output == OutputFormat::Json {
+        let nodes: Vec<_> = all_peers
+            .iter()
+            .filter(|contact_info| filter.matches_pubkey(&contact_info.id))
+            .map(|contact_info| {
+                let mut node = contact_info_details(contact_info);
+                node.insert("pubkey".to_string(), json!(contact_info.id.to_string()));
+                Value::Object(node)
+            })
+            .collect();
+        println!("{}", Value::Array(nodes));
+    }
+
     process_spy_results(timeout, validators, num_nodes, num_nodes_exactly, pubkey);
 
     Ok(())
@@ -288,34 +622,70 @@ fn parse_entrypoint(matches: &ArgMatches) -> Option<SocketAddr> {
     })
 }
 
+fn parse_version(value: &str) -> Result<(u16, u16, u16), String> {
+    let parts = value
+        .split('.')
+        .map(|part| part.parse::<u16>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| format!("invalid version {}: {}", value, err))?;
+    match parts[..] {
+        [major, minor, patch] => Ok((major, minor, patch)),
+        _ => Err(format!(
+            "invalid version {}: expected MAJOR.MINOR.PATCH",
+            value
+        )),
+    }
+}
+
 fn process_rpc_url(matches: &ArgMatches) -> std::io::Result<()> {
     let any = matches.is_present("any");
     let all = matches.is_present("all");
     let entrypoint_addr = parse_entrypoint(&matches);
     let timeout = value_t_or_exit!(matches, "timeout", u64);
     let shred_version = value_t_or_exit!(matches, "shred_version", u16);
-    let (_all_peers, validators) = discover(
-        None,
-        entrypoint_addr.as_ref(),
-        Some(1),
-        Some(timeout),
-        None,
-        entrypoint_addr.as_ref(),
-        None,
-        shred_version,
-    )?;
 
-    let rpc_addrs: Vec<_> = validators
-        .iter()
-        .filter_map(|contact_info| {
-            if (any || all || Some(contact_info.gossip) == entrypoint_addr)
-                && ContactInfo::is_valid_address(&contact_info.rpc)
-            {
-                return Some(contact_info.rpc);
-            }
-            None
-        })
-        .collect();
+    let rpc_addrs: Vec<_> = if any || all {
+        let entrypoint_addr = entrypoint_addr.expect("entrypoint is required");
+        let filter = RpcNodeFilter {
+            shred_version,
+            min_version: matches
+                .value_of("min_version")
+                .map(|value| parse_version(value).unwrap()),
+        };
+        let num_nodes = if all {
+            value_t_or_exit!(matches, "num_nodes", usize)
+        } else {
+            1
+        };
+        discover_rpc_nodes(
+            &entrypoint_addr,
+            num_nodes,
+            &filter,
+            Duration::from_secs(timeout),
+        )?
+        .into_iter()
+        .map(|contact_info| contact_info.rpc)
+        .collect()
+    } else {
+        let (_all_peers, validators) = discover(
+            None,
+            entrypoint_addr.as_ref(),
+            Some(1),
+            Some(timeout),
+            None,
+            entrypoint_addr.as_ref(),
+            None,
+            shred_version,
+        )?;
+        validators
+            .iter()
+            .filter(|contact_info| {
+                Some(contact_info.gossip) == entrypoint_addr
+                    && ContactInfo::is_valid_address(&contact_info.rpc)
+            })
+            .map(|contact_info| contact_info.rpc)
+            .collect()
+    };
 
     if rpc_addrs.is_empty() {
         eprintln!("No RPC URL found");
@@ -332,6 +702,127 @@ fn process_rpc_url(matches: &ArgMatches) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Number of nodes and stake running a software version
+#[derive(Default)]
+struct VersionCensusEntry {
+    version: String,
+    num_nodes: usize,
+    stake: u64,
+}
+
+fn process_version_census(matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
+    let entrypoint_addr = parse_entrypoint(&matches);
+    let timeout = value_t_or_exit!(matches, "timeout", u64);
+    let shred_version = value_t_or_exit!(matches, "shred_version", u16);
+
+    let exit = Arc::new(AtomicBool::new(false));
+    let (gossip_service, _ip_echo, cluster_info) = make_gossip_node(
+        Arc::new(Keypair::new()),
+        entrypoint_addr.as_ref(),
+        &exit,
+        None,
+        shred_version,
+    );
+    sleep(Duration::from_secs(timeout));
+
+    let rpc_client = match matches.value_of("json_rpc_url") {
+        Some(json_rpc_url) => RpcClient::new(json_rpc_url.to_string()),
+        None => {
+            let filter = RpcNodeFilter {
+                shred_version,
+                min_version: None,
+            };
+            let rpc_peers = get_rpc_peers(&cluster_info, &filter);
+            let rpc_peer = rpc_peers.first().ok_or("No RPC node found, use --url")?;
+            RpcClient::new_socket(rpc_peer.rpc)
+        }
+    };
+    let vote_accounts = rpc_client.get_vote_accounts()?;
+    let mut stakes: HashMap<Pubkey, u64> = HashMap::new();
+    for vote_account in vote_accounts
+        .current
+        .iter()
+        .chain(vote_accounts.delinquent.iter())
+    {
+        let node_pubkey = vote_account.node_pubkey.parse::<Pubkey>()?;
+        *stakes.entry(node_pubkey).or_default() += vote_account.activated_stake;
+    }
+    let total_stake: u64 = stakes.values().sum();
+
+    // The staked nodes which are not visible in gossip are counted as unknown versions
+    let mut node_pubkeys: HashSet<Pubkey> = stakes.keys().cloned().collect();
+    node_pubkeys.extend(
+        cluster_info
+            .all_peers()
+            .into_iter()
+            .map(|(contact_info, _)| contact_info.id),
+    );
+    let mut census: HashMap<String, VersionCensusEntry> = HashMap::new();
+    for node_pubkey in node_pubkeys {
+        let version = cluster_info
+            .get_node_version(&node_pubkey)
+            .map(|version| format!("{}.{}.{}", version.major, version.minor, version.patch))
+            .unwrap_or_else(|| "unknown".to_string());
+        let entry = census
+            .entry(version.clone())
+            .or_insert_with(|| VersionCensusEntry {
+                version,
+                ..VersionCensusEntry::default()
+            });
+        entry.num_nodes += 1;
+        entry.stake += stakes.get(&node_pubkey).cloned().unwrap_or_default();
+    }
+    exit.store(true, Ordering::Relaxed);
+    gossip_service.join().unwrap();
+
+    let mut census: Vec<_> = census.into_iter().map(|(_, entry)| entry).collect();
+    census.sort_by(|a, b| {
+        b.stake
+            .cmp(&a.stake)
+            .then_with(|| b.num_nodes.cmp(&a.num_nodes))
+    });
+    let stake_percent = |stake: u64| stake as f64 * 100.0 / total_stake.max(1) as f64;
+
+    if matches.value_of("output") == Some("json") {
+        let versions: Vec<_> = census
+            .iter()
+            .map(|entry| {
+                json!({
+                    "version": entry.version,
+                    "nodes": entry.num_nodes,
+                    "stake": entry.stake,
+                    "stakePercent": stake_percent(entry.stake),
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            json!({ "totalStake": total_stake, "versions": versions })
+        );
+    } else {
+        println!(
+            "{:<12} {:>8} {:>24} {:>8}",
+            "Version", "Nodes", "Stake (SOL)", "Stake"
+        );
+        for entry in &census {
+            println!(
+                "{:<12} {:>8} {:>24.2} {:>7.2}%",
+                entry.version,
+                entry.num_nodes,
+                lamports_to_sol(entry.stake),
+                stake_percent(entry.stake)
+            );
+        }
+        println!(
+            "Total: {} nodes, {} SOL staked",
+            census.iter().map(|entry| entry.num_nodes).sum::<usize>(),
+            lamports_to_sol(total_stake)
+        );
+    }
+
+    Ok(())
+}
+
 fn process_stop(matches: &ArgMatches) -> Result<(), Box<dyn error::Error>> {
     let entrypoint_addr = parse_entrypoint(&matches);
     let pubkey = matches
@@ -385,6 +876,9 @@ fn main() -> Result<(), Box<dyn error::Error>> {
         ("stop", Some(matches)) => {
             process_stop(matches)?;
         }
+        ("version-census", Some(matches)) => {
+            process_version_census(matches)?;
+        }
         _ => unreachable!(),
     }
 
@@ -5,11 +5,13 @@ use solana_clap_utils::{
     input_validators::{is_slot, is_valid_pubkey},
 };
 use solana_cli_output::display::println_transaction;
-use solana_ledger::{blockstore::Blockstore, blockstore_db::AccessType};
+use solana_ledger::{
+    bigtable_upload::UploadCheckpoint, blockstore::Blockstore, blockstore_db::AccessType,
+};
 use solana_sdk::{clock::Slot, pubkey::Pubkey, signature::Signature};
 use solana_transaction_status::ConfirmedBlock;
 use std::{
-    path::Path,
+    path::{Path, PathBuf},
     process::exit,
     result::Result,
     sync::{atomic::AtomicBool, Arc},
@@ -20,6 +22,7 @@ async fn upload(
     starting_slot: Slot,
     ending_slot: Option<Slot>,
     allow_missing_metadata: bool,
+    checkpoint: Option<UploadCheckpoint>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let bigtable = solana_storage_bigtable::LedgerStorage::new(false)
         .await
@@ -27,10 +30,11 @@ async fn upload(
 
     solana_ledger::bigtable_upload::upload_confirmed_blocks(
         Arc::new(blockstore),
-        bigtable,
+        Arc::new(bigtable),
         starting_slot,
         ending_slot,
         allow_missing_metadata,
+        checkpoint.as_ref(),
         Arc::new(AtomicBool::new(false)),
     )
     .await
@@ -243,6 +247,16 @@ impl BigTableSubCommand for App<'_, '_> {
                                 .long("allow-missing-metadata")
                                 .takes_value(false)
                                 .help("Don't panic if transaction metadata is missing"),
+                        )
+                        .arg(
+                            Arg::with_name("checkpoint_file")
+                                .long("checkpoint-file")
+                                .value_name("PATH")
+                                .takes_value(true)
+                                .help(
+                                    "Record upload progress in this file, and resume from it \
+                                     when the upload is restarted",
+                                ),
                         ),
                 )
                 .subcommand(
@@ -385,6 +399,9 @@ pub fn bigtable_process_command(ledger_path: &Path, matches: &ArgMatches<'_>) {
             let starting_slot = value_t!(arg_matches, "starting_slot", Slot).unwrap_or(0);
             let ending_slot = value_t!(arg_matches, "ending_slot", Slot).ok();
             let allow_missing_metadata = arg_matches.is_present("allow_missing_metadata");
+            let checkpoint = arg_matches
+                .value_of("checkpoint_file")
+                .map(|path| UploadCheckpoint::new(PathBuf::from(path)));
             let blockstore =
                 crate::open_blockstore(&ledger_path, AccessType::TryPrimaryThenSecondary, None);
 
@@ -393,6 +410,7 @@ pub fn bigtable_process_command(ledger_path: &Path, matches: &ArgMatches<'_>) {
                 starting_slot,
                 ending_slot,
                 allow_missing_metadata,
+                checkpoint,
             ))
         }
         ("first-available-block", Some(_arg_matches)) => runtime.block_on(first_available_block()),
@@ -0,0 +1,358 @@
+/// The `browse` subcommand
+use clap::{value_t, App, Arg, ArgMatches, SubCommand};
+use solana_clap_utils::input_validators::is_slot;
+use solana_cli_output::display::println_transaction;
+use solana_ledger::{blockstore::Blockstore, blockstore_db::AccessType, entry::Entry};
+use solana_sdk::{clock::Slot, signature::Signature};
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+    process::exit,
+    str::FromStr,
+    time::Duration,
+};
+
+const DEFAULT_SLOT_LIST_LENGTH: usize = 20;
+// How often to pick up the writes of a running validator, when browsing its ledger
+const CATCH_UP_INTERVAL: Duration = Duration::from_secs(1);
+
+const HELP: &str = "\
+Commands:
+  slots [SLOT] [COUNT]  List COUNT slots starting at SLOT [default: the current slot]
+  slot SLOT             Select SLOT and show a summary of it
+  next, prev            Select the next slot, or the parent of the current slot
+  entries               List the entries of the current slot
+  entry INDEX           Show the transactions, with their statuses and logs, of an entry
+  tx SIGNATURE          Show a transaction processed in a rooted slot
+  help                  Show this message
+  quit                  Exit";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Slots(Option<Slot>, usize),
+    Slot(Slot),
+    Next,
+    Prev,
+    Entries,
+    Entry(usize),
+    Transaction(Signature),
+    Help,
+    Quit,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let args: Vec<_> = words.collect();
+        let parse_arg = |index: usize, name: &str| -> Result<Option<u64>, String> {
+            args.get(index)
+                .map(|arg| {
+                    arg.parse()
+                        .map_err(|_| format!("Invalid {}: {}", name, arg))
+                })
+                .transpose()
+        };
+        let required = |arg: Option<u64>, name: &str| {
+            arg.ok_or_else(|| format!("Missing {}, see `help`", name))
+        };
+        match command {
+            "slots" | "ls" => Ok(Command::Slots(
+                parse_arg(0, "slot")?,
+                parse_arg(1, "count")?.map_or(DEFAULT_SLOT_LIST_LENGTH, |count| count as usize),
+            )),
+            "slot" | "s" => Ok(Command::Slot(required(parse_arg(0, "slot")?, "SLOT")?)),
+            "next" | "n" => Ok(Command::Next),
+            "prev" | "p" => Ok(Command::Prev),
+            "entries" | "e" => Ok(Command::Entries),
+            "entry" => Ok(Command::Entry(
+                required(parse_arg(0, "entry index")?, "INDEX")? as usize,
+            )),
+            "tx" | "t" => args
+                .get(0)
+                .ok_or_else(|| "Missing SIGNATURE, see `help`".to_string())?
+                .parse()
+                .map(Command::Transaction)
+                .map_err(|_| format!("Invalid signature: {}", args[0])),
+            "help" | "h" | "?" => Ok(Command::Help),
+            "quit" | "q" | "exit" => Ok(Command::Quit),
+            _ => Err(format!("Unknown command: {}, see `help`", command)),
+        }
+    }
+}
+
+struct Browser {
+    blockstore: Blockstore,
+    slot: Slot,
+    // Entries of `slot`, loaded on demand
+    entries: Option<Vec<Entry>>,
+}
+
+impl Browser {
+    fn process_command(&mut self, command: Command) -> Result<(), String> {
+        match command {
+            Command::Slots(starting_slot, count) => {
+                self.list_slots(starting_slot.unwrap_or(self.slot), count)
+            }
+            Command::Slot(slot) => self.select_slot(slot),
+            Command::Next => {
+                let next_slot = self
+                    .blockstore
+                    .meta(self.slot)
+                    .map_err(|err| format!("Failed to load slot {}: {:?}", self.slot, err))?
+                    .and_then(|meta| meta.next_slots.iter().min().copied())
+                    .ok_or_else(|| format!("Slot {} has no known child", self.slot))?;
+                self.select_slot(next_slot)
+            }
+            Command::Prev => {
+                let parent_slot = self
+                    .blockstore
+                    .meta(self.slot)
+                    .map_err(|err| format!("Failed to load slot {}: {:?}", self.slot, err))?
+                    .map(|meta| meta.parent_slot)
+                    .filter(|parent_slot| *parent_slot != std::u64::MAX)
+                    .ok_or_else(|| format!("Slot {} has no known parent", self.slot))?;
+                self.select_slot(parent_slot)
+            }
+            Command::Entries => {
+                for (index, entry) in self.entries()?.iter().enumerate() {
+                    println!(
+                        "  Entry {} - num_hashes: {}, hash: {}, transactions: {}",
+                        index,
+                        entry.num_hashes,
+                        entry.hash,
+                        entry.transactions.len()
+                    );
+                }
+                Ok(())
+            }
+            Command::Entry(index) => {
+                let slot = self.slot;
+                let entry = self
+                    .entries()?
+                    .get(index)
+                    .cloned()
+                    .ok_or_else(|| format!("Slot {} has no entry {}", slot, index))?;
+                self.print_entry(index, &entry);
+                Ok(())
+            }
+            Command::Transaction(signature) => {
+                let confirmed_transaction = self
+                    .blockstore
+                    .get_confirmed_transaction(signature)
+                    .map_err(|err| format!("Failed to load transaction: {:?}", err))?
+                    .ok_or_else(|| {
+                        format!("Transaction {} not found in a rooted slot", signature)
+                    })?;
+                println!("Slot {}", confirmed_transaction.slot);
+                let meta = confirmed_transaction.transaction.meta;
+                println_transaction(
+                    &confirmed_transaction.transaction.transaction,
+                    &meta.clone().map(|meta| meta.into()),
+                    "  ",
+                );
+                print_log_messages(meta.and_then(|meta| meta.log_messages), "  ");
+                Ok(())
+            }
+            Command::Help => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            Command::Quit => Ok(()),
+        }
+    }
+
+    fn list_slots(&self, starting_slot: Slot, count: usize) -> Result<(), String> {
+        let slot_meta_iterator = self
+            .blockstore
+            .slot_meta_iterator(starting_slot)
+            .map_err(|err| format!("Failed to iterate slots: {:?}", err))?;
+        for (slot, meta) in slot_meta_iterator.take(count) {
+            println!(
+                "{}Slot {} - parent: {}, shreds: {}{}{}{}",
+                if slot == self.slot { "* " } else { "  " },
+                slot,
+                meta.parent_slot,
+                meta.consumed,
+                if meta.is_full() { ", full" } else { "" },
+                if self.blockstore.is_root(slot) {
+                    ", root"
+                } else {
+                    ""
+                },
+                if self.blockstore.is_dead(slot) {
+                    ", dead"
+                } else {
+                    ""
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn select_slot(&mut self, slot: Slot) -> Result<(), String> {
+        let meta = self
+            .blockstore
+            .meta(slot)
+            .map_err(|err| format!("Failed to load slot {}: {:?}", slot, err))?
+            .ok_or_else(|| format!("Slot {} is not in the ledger", slot))?;
+        self.slot = slot;
+        self.entries = None;
+
+        println!("Slot {}", slot);
+        println!(
+            "  Parent: {}, children: {:?}",
+            meta.parent_slot, meta.next_slots
+        );
+        println!(
+            "  Shreds: {}, full: {}, root: {}, dead: {}",
+            meta.consumed,
+            meta.is_full(),
+            self.blockstore.is_root(slot),
+            self.blockstore.is_dead(slot)
+        );
+        if let Ok(Some(block_time)) = self.blockstore.get_block_time(slot) {
+            println!("  Block time: {}", block_time);
+        }
+        if let Ok(entries) = self.entries() {
+            let transactions: usize = entries.iter().map(|entry| entry.transactions.len()).sum();
+            println!(
+                "  Entries: {}, transactions: {}",
+                entries.len(),
+                transactions
+            );
+        }
+        if let Ok(Some(rewards)) = self.blockstore.read_rewards(slot) {
+            println!("  Rewards: {}", rewards.len());
+        }
+        Ok(())
+    }
+
+    fn entries(&mut self) -> Result<&Vec<Entry>, String> {
+        if self.entries.is_none() {
+            let (entries, _num_shreds, _is_full) = self
+                .blockstore
+                .get_slot_entries_with_shred_info(self.slot, 0, true)
+                .map_err(|err| {
+                    format!("Failed to load entries for slot {}: {:?}", self.slot, err)
+                })?;
+            self.entries = Some(entries);
+        }
+        Ok(self.entries.as_ref().unwrap())
+    }
+
+    fn print_entry(&self, index: usize, entry: &Entry) {
+        println!(
+            "Entry {} - num_hashes: {}, hash: {}, transactions: {}",
+            index,
+            entry.num_hashes,
+            entry.hash,
+            entry.transactions.len()
+        );
+        for (transaction_index, transaction) in entry.transactions.iter().enumerate() {
+            println!("  Transaction {}", transaction_index);
+            let transaction_status = self
+                .blockstore
+                .read_transaction_status((transaction.signatures[0], self.slot))
+                .unwrap_or_else(|err| {
+                    eprintln!(
+                        "Failed to read transaction status for {} at slot {}: {}",
+                        transaction.signatures[0], self.slot, err
+                    );
+                    None
+                });
+            println_transaction(
+                &transaction,
+                &transaction_status.clone().map(|meta| meta.into()),
+                "    ",
+            );
+            print_log_messages(
+                transaction_status.and_then(|meta| meta.log_messages),
+                "    ",
+            );
+        }
+    }
+}
+
+fn print_log_messages(log_messages: Option<Vec<String>>, prefix: &str) {
+    if let Some(log_messages) = log_messages {
+        println!("{}Log Messages:", prefix);
+        for log_message in log_messages {
+            println!("{}  {}", prefix, log_message);
+        }
+    }
+}
+
+pub trait BrowseSubCommand {
+    fn browse_subcommand(self) -> Self;
+}
+
+impl BrowseSubCommand for App<'_, '_> {
+    fn browse_subcommand(self) -> Self {
+        self.subcommand(
+            SubCommand::with_name("browse")
+                .about("Interactively browse the slots, entries and transactions of the ledger")
+                .arg(
+                    Arg::with_name("starting_slot")
+                        .index(1)
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .validator(is_slot)
+                        .help("Start browsing at this slot [default: the last root]"),
+                ),
+        )
+    }
+}
+
+pub fn browse_process_command(ledger_path: &Path, matches: &ArgMatches<'_>) {
+    let blockstore =
+        crate::open_blockstore(&ledger_path, AccessType::TryPrimaryThenSecondary, None);
+    let slot = value_t!(matches, "starting_slot", Slot).unwrap_or_else(|_| blockstore.last_root());
+    let mut browser = Browser {
+        blockstore,
+        slot,
+        entries: None,
+    };
+    if let Err(err) = browser.select_slot(slot) {
+        eprintln!("{}", err);
+    }
+    println!("Type `help` for a list of commands");
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("slot {}> ", browser.slot);
+        io::stdout().flush().expect("flush stdout");
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            Some(Err(err)) => {
+                eprintln!("Failed to read command: {}", err);
+                exit(1);
+            }
+            None => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match browser
+            .blockstore
+            .maybe_catch_up_with_primary(CATCH_UP_INTERVAL)
+        {
+            // Entries of the current slot may have been added
+            Ok(true) => browser.entries = None,
+            Ok(false) => (),
+            Err(err) => eprintln!("Failed to catch up with the validator: {:?}", err),
+        }
+        match line.parse() {
+            Ok(Command::Quit) => break,
+            Ok(command) => {
+                if let Err(err) = browser.process_command(command) {
+                    eprintln!("{}", err);
+                }
+            }
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+}
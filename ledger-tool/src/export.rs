@@ -0,0 +1,536 @@
+//! The `export` module streams the rooted blocks of a blockstore into files analytics tools can
+//! ingest: Parquet tables of the transactions, instructions and balance changes, or a CAR archive
+//! holding each block as a JSON document.
+
+use log::*;
+use parquet::{
+    basic::Compression,
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    errors::ParquetError,
+    file::{
+        metadata::KeyValue,
+        properties::WriterProperties,
+        writer::{FileWriter, RowGroupWriter, SerializedFileWriter},
+    },
+    schema::parser::parse_message_type,
+};
+use serde_json::json;
+use solana_ledger::blockstore::Blockstore;
+use solana_sdk::{clock::Slot, hash::hash, instruction::CompiledInstruction, message::Message};
+use solana_transaction_status::{ConfirmedBlock, UiTransactionEncoding};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    str::FromStr,
+    sync::Arc,
+};
+
+/// Version of the layout of the exported tables and documents, bumped on any change to them.
+/// Stored in the key-value metadata of the Parquet files and in the manifest of the CAR archives.
+pub const EXPORT_SCHEMA_VERSION: u32 = 1;
+const SCHEMA_VERSION_KEY: &str = "solana.export.schema_version";
+const ROWS_PER_ROW_GROUP: usize = 100_000;
+
+const TRANSACTIONS_SCHEMA: &str = "
+message transactions {
+    REQUIRED INT64 slot;
+    OPTIONAL INT64 block_time;
+    REQUIRED INT32 transaction_index;
+    REQUIRED BYTE_ARRAY signature (UTF8);
+    REQUIRED BYTE_ARRAY fee_payer (UTF8);
+    OPTIONAL INT64 fee;
+    OPTIONAL BYTE_ARRAY error (UTF8);
+    REQUIRED INT32 num_instructions;
+}
+";
+
+const INSTRUCTIONS_SCHEMA: &str = "
+message instructions {
+    REQUIRED INT64 slot;
+    REQUIRED INT32 transaction_index;
+    REQUIRED BYTE_ARRAY signature (UTF8);
+    REQUIRED INT32 instruction_index;
+    OPTIONAL INT32 inner_instruction_index;
+    REQUIRED BYTE_ARRAY program_id (UTF8);
+    REQUIRED BYTE_ARRAY accounts (UTF8);
+    REQUIRED BYTE_ARRAY data;
+}
+";
+
+const BALANCES_SCHEMA: &str = "
+message balances {
+    REQUIRED INT64 slot;
+    REQUIRED INT32 transaction_index;
+    REQUIRED BYTE_ARRAY signature (UTF8);
+    REQUIRED BYTE_ARRAY account (UTF8);
+    REQUIRED INT64 pre_balance;
+    REQUIRED INT64 post_balance;
+}
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    /// `transactions.parquet`, `instructions.parquet` and `balances.parquet`
+    Parquet,
+    /// `blocks.car`
+    Car,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string {
+            "parquet" => Ok(ExportFormat::Parquet),
+            "car" => Ok(ExportFormat::Car),
+            _ => Err(format!(
+                "invalid export format {}: expected parquet or car",
+                string
+            )),
+        }
+    }
+}
+
+/// Exports the rooted blocks from `start_slot` up to `end_slot` into `output_directory`,
+/// returning the number of blocks exported
+pub fn export_blocks(
+    blockstore: &Blockstore,
+    start_slot: Slot,
+    end_slot: Option<Slot>,
+    format: ExportFormat,
+    output_directory: &Path,
+) -> Result<usize, String> {
+    fs::create_dir_all(output_directory)
+        .map_err(|err| format!("Unable to create {:?}: {}", output_directory, err))?;
+    let mut sink: Box<dyn BlockSink> = match format {
+        ExportFormat::Parquet => Box::new(ParquetSink::create(output_directory)?),
+        ExportFormat::Car => Box::new(CarSink::create(
+            &output_directory.join("blocks.car"),
+            start_slot,
+            end_slot,
+        )?),
+    };
+
+    let slots = blockstore
+        .rooted_slot_iterator(start_slot)
+        .map_err(|err| format!("Unable to read the roots from {}: {}", start_slot, err))?;
+    let mut exported = 0;
+    for slot in slots {
+        if end_slot.map(|end_slot| slot > end_slot).unwrap_or(false) {
+            break;
+        }
+        match blockstore.get_confirmed_block(slot) {
+            Ok(block) => {
+                sink.write_block(slot, block)?;
+                exported += 1;
+            }
+            Err(err) => warn!("Skipping slot {}: {:?}", slot, err),
+        }
+    }
+    sink.finish()?;
+    Ok(exported)
+}
+
+trait BlockSink {
+    fn write_block(&mut self, slot: Slot, block: ConfirmedBlock) -> Result<(), String>;
+    fn finish(self: Box<Self>) -> Result<(), String>;
+}
+
+fn parquet_error(err: ParquetError) -> String {
+    format!("Parquet error: {}", err)
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Int32(i32),
+    Int64(i64),
+    Bytes(Vec<u8>),
+    Null,
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Int32(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int64(value)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Value::Int64(value as i64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(value: usize) -> Self {
+        Value::Int32(value as i32)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::Bytes(value.into_bytes())
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(value: Vec<u8>) -> Self {
+        Value::Bytes(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+/// Buffers the rows of a Parquet file, writing them out a row group at a time
+struct TableWriter {
+    writer: SerializedFileWriter<File>,
+    columns: Vec<Vec<Value>>,
+}
+
+impl TableWriter {
+    fn create(path: &Path, message_type: &str) -> Result<Self, String> {
+        let schema = parse_message_type(message_type).map_err(parquet_error)?;
+        let num_columns = schema.get_fields().len();
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_key_value_metadata(Some(vec![KeyValue::new(
+                SCHEMA_VERSION_KEY.to_string(),
+                EXPORT_SCHEMA_VERSION.to_string(),
+            )]))
+            .build();
+        let file =
+            File::create(path).map_err(|err| format!("Unable to create {:?}: {}", path, err))?;
+        let writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(properties))
+            .map_err(parquet_error)?;
+        Ok(Self {
+            writer,
+            columns: vec![vec![]; num_columns],
+        })
+    }
+
+    fn push(&mut self, row: Vec<Value>) -> Result<(), String> {
+        assert_eq!(row.len(), self.columns.len());
+        for (column, value) in self.columns.iter_mut().zip(row) {
+            column.push(value);
+        }
+        if self.columns[0].len() >= ROWS_PER_ROW_GROUP {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if self.columns[0].is_empty() {
+            return Ok(());
+        }
+        let mut row_group = self.writer.next_row_group().map_err(parquet_error)?;
+        let mut columns = self.columns.iter_mut();
+        while let Some(mut column_writer) = row_group.next_column().map_err(parquet_error)? {
+            let values = std::mem::take(columns.next().unwrap());
+            write_column(&mut column_writer, values)?;
+            row_group
+                .close_column(column_writer)
+                .map_err(parquet_error)?;
+        }
+        self.writer
+            .close_row_group(row_group)
+            .map_err(parquet_error)
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        self.flush()?;
+        self.writer.close().map_err(parquet_error)
+    }
+}
+
+fn write_column(column_writer: &mut ColumnWriter, values: Vec<Value>) -> Result<(), String> {
+    // Nulls are left out of the values, and only marked by a definition level of 0
+    let def_levels: Vec<i16> = values
+        .iter()
+        .map(|value| match value {
+            Value::Null => 0,
+            _ => 1,
+        })
+        .collect();
+    let result = match column_writer {
+        ColumnWriter::Int32ColumnWriter(writer) => {
+            let values: Vec<i32> = values
+                .into_iter()
+                .filter_map(|value| match value {
+                    Value::Int32(value) => Some(value),
+                    _ => None,
+                })
+                .collect();
+            writer.write_batch(&values, Some(&def_levels), None)
+        }
+        ColumnWriter::Int64ColumnWriter(writer) => {
+            let values: Vec<i64> = values
+                .into_iter()
+                .filter_map(|value| match value {
+                    Value::Int64(value) => Some(value),
+                    _ => None,
+                })
+                .collect();
+            writer.write_batch(&values, Some(&def_levels), None)
+        }
+        ColumnWriter::ByteArrayColumnWriter(writer) => {
+            let values: Vec<ByteArray> = values
+                .into_iter()
+                .filter_map(|value| match value {
+                    Value::Bytes(value) => Some(ByteArray::from(value)),
+                    _ => None,
+                })
+                .collect();
+            writer.write_batch(&values, Some(&def_levels), None)
+        }
+        _ => return Err("Unsupported Parquet column type".to_string()),
+    };
+    result.map(|_| ()).map_err(parquet_error)
+}
+
+struct ParquetSink {
+    transactions: TableWriter,
+    instructions: TableWriter,
+    balances: TableWriter,
+}
+
+impl ParquetSink {
+    fn create(output_directory: &Path) -> Result<Self, String> {
+        Ok(Self {
+            transactions: TableWriter::create(
+                &output_directory.join("transactions.parquet"),
+                TRANSACTIONS_SCHEMA,
+            )?,
+            instructions: TableWriter::create(
+                &output_directory.join("instructions.parquet"),
+                INSTRUCTIONS_SCHEMA,
+            )?,
+            balances: TableWriter::create(
+                &output_directory.join("balances.parquet"),
+                BALANCES_SCHEMA,
+            )?,
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_instruction(
+        &mut self,
+        slot: Slot,
+        transaction_index: usize,
+        signature: &str,
+        message: &Message,
+        instruction_index: usize,
+        inner_instruction_index: Option<usize>,
+        instruction: &CompiledInstruction,
+    ) -> Result<(), String> {
+        let account_key = |index: u8| {
+            message
+                .account_keys
+                .get(index as usize)
+                .map(|key| key.to_string())
+                .unwrap_or_default()
+        };
+        let accounts: Vec<String> = instruction
+            .accounts
+            .iter()
+            .map(|index| account_key(*index))
+            .collect();
+        self.instructions.push(vec![
+            slot.into(),
+            transaction_index.into(),
+            signature.to_string().into(),
+            instruction_index.into(),
+            inner_instruction_index.into(),
+            account_key(instruction.program_id_index).into(),
+            accounts.join(",").into(),
+            instruction.data.clone().into(),
+        ])
+    }
+}
+
+impl BlockSink for ParquetSink {
+    fn write_block(&mut self, slot: Slot, block: ConfirmedBlock) -> Result<(), String> {
+        for (transaction_index, transaction_with_meta) in block.transactions.iter().enumerate() {
+            let transaction = &transaction_with_meta.transaction;
+            let message = &transaction.message;
+            let signature = transaction
+                .signatures
+                .get(0)
+                .map(|signature| signature.to_string())
+                .unwrap_or_default();
+            let meta = transaction_with_meta.meta.as_ref();
+            self.transactions.push(vec![
+                slot.into(),
+                block.block_time.into(),
+                transaction_index.into(),
+                signature.clone().into(),
+                message
+                    .account_keys
+                    .get(0)
+                    .map(|key| key.to_string())
+                    .unwrap_or_default()
+                    .into(),
+                meta.map(|meta| meta.fee).into(),
+                meta.and_then(|meta| meta.status.as_ref().err())
+                    .map(|err| err.to_string())
+                    .into(),
+                message.instructions.len().into(),
+            ])?;
+
+            for (instruction_index, instruction) in message.instructions.iter().enumerate() {
+                self.write_instruction(
+                    slot,
+                    transaction_index,
+                    &signature,
+                    message,
+                    instruction_index,
+                    None,
+                    instruction,
+                )?;
+            }
+            let inner_instructions = meta.and_then(|meta| meta.inner_instructions.as_ref());
+            for inner_instructions in inner_instructions.into_iter().flatten() {
+                for (inner_index, instruction) in inner_instructions.instructions.iter().enumerate()
+                {
+                    self.write_instruction(
+                        slot,
+                        transaction_index,
+                        &signature,
+                        message,
+                        inner_instructions.index as usize,
+                        Some(inner_index),
+                        instruction,
+                    )?;
+                }
+            }
+
+            // Only the accounts whose balance changed
+            if let Some(meta) = meta {
+                let balances = meta.pre_balances.iter().zip(&meta.post_balances);
+                for (account, (pre_balance, post_balance)) in
+                    message.account_keys.iter().zip(balances)
+                {
+                    if pre_balance != post_balance {
+                        self.balances.push(vec![
+                            slot.into(),
+                            transaction_index.into(),
+                            signature.clone().into(),
+                            account.to_string().into(),
+                            (*pre_balance).into(),
+                            (*post_balance).into(),
+                        ])?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        let ParquetSink {
+            transactions,
+            instructions,
+            balances,
+        } = *self;
+        transactions.finish()?;
+        instructions.finish()?;
+        balances.finish()
+    }
+}
+
+// CIDv1 of the raw codec, with a sha2-256 multihash
+const CID_VERSION: u8 = 0x01;
+const RAW_CODEC: u8 = 0x55;
+const SHA2_256_CODE: u8 = 0x12;
+const SHA2_256_LEN: u8 = 32;
+
+fn cid(data: &[u8]) -> Vec<u8> {
+    let mut cid = vec![CID_VERSION, RAW_CODEC, SHA2_256_CODE, SHA2_256_LEN];
+    cid.extend_from_slice(hash(data).as_ref());
+    cid
+}
+
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> std::io::Result<()> {
+    while value >= 0x80 {
+        writer.write_all(&[(value as u8) | 0x80])?;
+        value >>= 7;
+    }
+    writer.write_all(&[value as u8])
+}
+
+/// Writes a CARv1 archive: a manifest block, which is the root of the archive, followed by one
+/// block per slot holding the JSON encoded block
+struct CarSink {
+    writer: BufWriter<File>,
+}
+
+impl CarSink {
+    fn create(path: &Path, start_slot: Slot, end_slot: Option<Slot>) -> Result<Self, String> {
+        let file =
+            File::create(path).map_err(|err| format!("Unable to create {:?}: {}", path, err))?;
+        let mut sink = Self {
+            writer: BufWriter::new(file),
+        };
+        let manifest = json!({
+            "schemaVersion": EXPORT_SCHEMA_VERSION,
+            "startSlot": start_slot,
+            "endSlot": end_slot,
+            "encoding": "json",
+        })
+        .to_string()
+        .into_bytes();
+
+        // DAG-CBOR encoding of {"roots": [manifest CID], "version": 1}
+        let root = cid(&manifest);
+        let mut header = vec![0xa2, 0x65];
+        header.extend_from_slice(b"roots");
+        // An array of one CID, tag 42 over the CID bytes prefixed with a 0 byte
+        header.extend_from_slice(&[0x81, 0xd8, 0x2a, 0x58, root.len() as u8 + 1, 0x00]);
+        header.extend_from_slice(&root);
+        header.push(0x67);
+        header.extend_from_slice(b"version");
+        header.push(0x01);
+
+        write_varint(&mut sink.writer, header.len() as u64)
+            .and_then(|()| sink.writer.write_all(&header))
+            .map_err(|err| format!("Unable to write to {:?}: {}", path, err))?;
+        sink.write_data(&manifest)?;
+        Ok(sink)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), String> {
+        let cid = cid(data);
+        write_varint(&mut self.writer, (cid.len() + data.len()) as u64)
+            .and_then(|()| self.writer.write_all(&cid))
+            .and_then(|()| self.writer.write_all(data))
+            .map_err(|err| format!("Unable to write block: {}", err))
+    }
+}
+
+impl BlockSink for CarSink {
+    fn write_block(&mut self, slot: Slot, block: ConfirmedBlock) -> Result<(), String> {
+        let document = json!({
+            "schemaVersion": EXPORT_SCHEMA_VERSION,
+            "slot": slot,
+            "block": block.encode(UiTransactionEncoding::Json),
+        });
+        self.write_data(document.to_string().as_bytes())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), String> {
+        self.writer
+            .flush()
+            .map_err(|err| format!("Unable to write block: {}", err))
+    }
+}
@@ -15,13 +15,16 @@ use solana_ledger::{
     ancestor_iterator::AncestorIterator,
     bank_forks_utils,
     blockstore::{create_new_ledger, Blockstore, PurgeType},
-    blockstore_db::{self, AccessType, BlockstoreRecoveryMode, Column, Database},
+    blockstore_db::{self, AccessType, BlockstoreError, BlockstoreRecoveryMode, Column, Database},
     blockstore_processor::ProcessOptions,
     rooted_slot_iterator::RootedSlotIterator,
+    write_trace::WriteTraceSink,
 };
 use solana_runtime::{
     bank::Bank,
-    bank_forks::{BankForks, CompressionType, SnapshotConfig},
+    bank_forks::{
+        BankForks, CompressionOptions, CompressionType, SnapshotConfig, SnapshotThrottleOptions,
+    },
     hardened_unpack::{open_genesis_config, MAX_GENESIS_ARCHIVE_UNPACKED_SIZE},
     snapshot_utils,
     snapshot_utils::SnapshotVersion,
@@ -57,6 +60,10 @@ use std::{
 
 mod bigtable;
 use bigtable::*;
+mod browse;
+use browse::*;
+mod export;
+mod minimized_snapshot;
 
 #[derive(PartialEq)]
 enum LedgerOutputMethod {
@@ -619,6 +626,7 @@ fn open_blockstore(
 ) -> Blockstore {
     match Blockstore::open_with_access_type(ledger_path, access_type, wal_recovery_mode) {
         Ok(blockstore) => blockstore,
+        Err(BlockstoreError::PrimaryInUse) => exit_primary_in_use(ledger_path),
         Err(err) => {
             eprintln!("Failed to open ledger at {:?}: {:?}", ledger_path, err);
             exit(1);
@@ -629,6 +637,7 @@ fn open_blockstore(
 fn open_database(ledger_path: &Path, access_type: AccessType) -> Database {
     match Database::open(&ledger_path.join("rocksdb"), access_type, None) {
         Ok(database) => database,
+        Err(BlockstoreError::PrimaryInUse) => exit_primary_in_use(ledger_path),
         Err(err) => {
             eprintln!("Unable to read the Ledger rocksdb: {:?}", err);
             exit(1);
@@ -636,6 +645,16 @@ fn open_database(ledger_path: &Path, access_type: AccessType) -> Database {
     }
 }
 
+// Commands that modify the ledger need exclusive access to it
+fn exit_primary_in_use(ledger_path: &Path) -> ! {
+    eprintln!(
+        "Ledger at {:?} is in use, likely by a running validator. Refusing to modify it, stop \
+         the validator first",
+        ledger_path
+    );
+    exit(1);
+}
+
 // This function is duplicated in validator/src/main.rs...
 fn hardforks_of(matches: &ArgMatches<'_>, name: &str) -> Option<Vec<Slot>> {
     if matches.is_present(name) {
@@ -670,6 +689,8 @@ fn load_bank_forks(
             snapshot_package_output_path,
             snapshot_path,
             compression: CompressionType::Bzip2,
+            compression_options: CompressionOptions::default(),
+            throttle_options: SnapshotThrottleOptions::default(),
             snapshot_version: SnapshotVersion::default(),
         })
     };
@@ -827,6 +848,7 @@ fn main() {
                 .help("Use DIR for ledger location"),
         )
         .bigtable_subcommand()
+        .browse_subcommand()
         .subcommand(
             SubCommand::with_name("print")
             .about("Print the ledger")
@@ -987,6 +1009,37 @@ fn main() {
             .arg(&starting_slot_arg)
             .arg(&allow_dead_slots_arg)
         )
+        .subcommand(
+            SubCommand::with_name("export")
+            .about("Export the rooted blocks to files for analytics")
+            .arg(&starting_slot_arg)
+            .arg(
+                Arg::with_name("ending_slot")
+                    .long("ending-slot")
+                    .value_name("SLOT")
+                    .validator(is_slot)
+                    .takes_value(true)
+                    .help("Last slot to export [default: the highest root]"),
+            )
+            .arg(
+                Arg::with_name("format")
+                    .long("format")
+                    .value_name("FORMAT")
+                    .takes_value(true)
+                    .possible_values(&["parquet", "car"])
+                    .default_value("parquet")
+                    .help("Parquet tables of the transactions, instructions and balance \
+                           changes, or a CAR archive of the JSON encoded blocks"),
+            )
+            .arg(
+                Arg::with_name("output_directory")
+                    .index(1)
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Output directory for the exported files"),
+            )
+        )
         .subcommand(
             SubCommand::with_name("verify")
             .about("Verify the ledger")
@@ -1007,6 +1060,23 @@ fn main() {
                     .takes_value(false)
                     .help("After verifying the ledger, print some information about the account stores."),
             )
+            .arg(
+                Arg::with_name("checkpoint_dir")
+                    .long("checkpoint-dir")
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .help("Resume from the latest checkpoint in this directory, if any \
+                           and ahead of the latest snapshot. With --halt-at-slot, also \
+                           write a checkpoint of the root processing halts with into it"),
+            )
+            .arg(
+                Arg::with_name("trace_writes")
+                    .long("trace-writes")
+                    .value_name("FILE")
+                    .takes_value(true)
+                    .help("Write the state of the accounts each replayed transaction may \
+                           write, before and after it executes, to this file"),
+            )
         ).subcommand(
             SubCommand::with_name("graph")
             .about("Create a Graphviz rendering of the ledger")
@@ -1140,6 +1210,54 @@ fn main() {
                     .takes_value(false)
                     .help("Remove all existing stake accounts from the new snapshot.")
             )
+        ).subcommand(
+            SubCommand::with_name("create-minimized-snapshot")
+            .about("Create a snapshot and ledger holding only what is needed to replay a range \
+                    of slots")
+            .arg(&no_snapshot_arg)
+            .arg(&account_paths_arg)
+            .arg(&hard_forks_arg)
+            .arg(&max_genesis_archive_unpacked_size_arg)
+            .arg(&snapshot_version_arg)
+            .arg(
+                Arg::with_name("start_slot")
+                    .long("start-slot")
+                    .value_name("SLOT")
+                    .validator(is_slot)
+                    .takes_value(true)
+                    .required(true)
+                    .help("Slot at which to create the snapshot"),
+            )
+            .arg(
+                Arg::with_name("end_slot")
+                    .long("end-slot")
+                    .value_name("SLOT")
+                    .validator(is_slot)
+                    .takes_value(true)
+                    .required(true)
+                    .help("Last slot to replay from the snapshot"),
+            )
+            .arg(
+                Arg::with_name("output_directory")
+                    .index(1)
+                    .value_name("DIR")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Output directory for the genesis, the snapshot and the ledger of \
+                           the slots, which can be passed to --ledger"),
+            )
+        ).subcommand(
+            SubCommand::with_name("verify-snapshot")
+            .about("Verify the integrity of a snapshot archive before trusting it")
+            .arg(&max_genesis_archive_unpacked_size_arg)
+            .arg(
+                Arg::with_name("snapshot_archive")
+                    .index(1)
+                    .value_name("ARCHIVE")
+                    .takes_value(true)
+                    .required(true)
+                    .help("Snapshot archive to verify, named snapshot-<SLOT>-<HASH>.tar.<EXT>"),
+            )
         ).subcommand(
             SubCommand::with_name("accounts")
             .about("Print account contents after processing in the ledger")
@@ -1265,6 +1383,19 @@ fn main() {
             SubCommand::with_name("analyze-storage")
                 .about("Output statistics in JSON format about all column families in the ledger rocksDB")
         )
+        .subcommand(
+            SubCommand::with_name("compact")
+                .about("Manually compact column families of the ledger rocksDB")
+                .arg(
+                    Arg::with_name("columns")
+                        .long("column")
+                        .value_name("COLUMN")
+                        .takes_value(true)
+                        .multiple(true)
+                        .help("Compact only this column family. May be specified multiple times \
+                               [default: all column families]"),
+                )
+        )
         .get_matches();
 
     info!("{} {}", crate_name!(), solana_version::version!());
@@ -1295,6 +1426,7 @@ fn main() {
 
     match matches.subcommand() {
         ("bigtable", Some(arg_matches)) => bigtable_process_command(&ledger_path, arg_matches),
+        ("browse", Some(arg_matches)) => browse_process_command(&ledger_path, arg_matches),
         ("print", Some(arg_matches)) => {
             let starting_slot = value_t_or_exit!(arg_matches, "starting_slot", Slot);
             let num_slots = value_t!(arg_matches, "num_slots", Slot).ok();
@@ -1466,6 +1598,36 @@ fn main() {
                 true,
             );
         }
+        ("export", Some(arg_matches)) => {
+            let starting_slot = value_t_or_exit!(arg_matches, "starting_slot", Slot);
+            let ending_slot = value_t!(arg_matches, "ending_slot", Slot).ok();
+            let format = value_t_or_exit!(arg_matches, "format", export::ExportFormat);
+            let output_directory =
+                PathBuf::from(value_t_or_exit!(arg_matches, "output_directory", String));
+            let blockstore = open_blockstore(
+                &ledger_path,
+                AccessType::TryPrimaryThenSecondary,
+                wal_recovery_mode,
+            );
+            match export::export_blocks(
+                &blockstore,
+                starting_slot,
+                ending_slot,
+                format,
+                &output_directory,
+            ) {
+                Ok(exported) => println!(
+                    "Exported {} blocks to {:?} with schema version {}",
+                    exported,
+                    output_directory,
+                    export::EXPORT_SCHEMA_VERSION
+                ),
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    exit(1);
+                }
+            }
+        }
         ("dead-slots", Some(arg_matches)) => {
             let blockstore = open_blockstore(
                 &ledger_path,
@@ -1557,10 +1719,22 @@ fn main() {
             }
         }
         ("verify", Some(arg_matches)) => {
+            let write_trace_sink = value_t!(arg_matches, "trace_writes", String)
+                .ok()
+                .map(|path| {
+                    WriteTraceSink::create_file(Path::new(&path)).unwrap_or_else(|err| {
+                        eprintln!("Unable to create {}: {}", path, err);
+                        exit(1);
+                    })
+                });
             let process_options = ProcessOptions {
                 dev_halt_at_slot: value_t!(arg_matches, "halt_at_slot", Slot).ok(),
                 new_hard_forks: hardforks_of(arg_matches, "hard_forks"),
                 poh_verify: !arg_matches.is_present("skip_poh_verify"),
+                checkpoint_path: value_t!(arg_matches, "checkpoint_dir", String)
+                    .ok()
+                    .map(PathBuf::from),
+                trace_writes: write_trace_sink.clone(),
                 ..ProcessOptions::default()
             };
             let print_accounts_stats = arg_matches.is_present("print_accounts_stats");
@@ -1569,7 +1743,7 @@ fn main() {
                 open_genesis_config_by(&ledger_path, arg_matches).hash()
             );
 
-            let (bank_forks, _, _) = load_bank_forks(
+            let load_result = load_bank_forks(
                 arg_matches,
                 &ledger_path,
                 &open_genesis_config_by(&ledger_path, arg_matches),
@@ -1577,8 +1751,14 @@ fn main() {
                 AccessType::TryPrimaryThenSecondary,
                 wal_recovery_mode,
                 snapshot_archive_path,
-            )
-            .unwrap_or_else(|err| {
+            );
+            // The traces leading to a failure are the ones worth keeping
+            if let Some(write_trace_sink) = write_trace_sink {
+                if let Err(err) = write_trace_sink.flush() {
+                    eprintln!("Unable to write the transaction traces: {}", err);
+                }
+            }
+            let (bank_forks, _, _) = load_result.unwrap_or_else(|err| {
                 eprintln!("Ledger verification failed: {:?}", err);
                 exit(1);
             });
@@ -1893,6 +2073,161 @@ fn main() {
                 }
             }
         }
+        ("create-minimized-snapshot", Some(arg_matches)) => {
+            let start_slot = value_t_or_exit!(arg_matches, "start_slot", Slot);
+            let end_slot = value_t_or_exit!(arg_matches, "end_slot", Slot);
+            let output_directory =
+                PathBuf::from(value_t_or_exit!(arg_matches, "output_directory", String));
+            if end_slot <= start_slot {
+                eprintln!("Error: --end-slot must be greater than --start-slot");
+                exit(1);
+            }
+            let snapshot_version =
+                arg_matches
+                    .value_of("snapshot_version")
+                    .map_or(SnapshotVersion::default(), |s| {
+                        s.parse::<SnapshotVersion>().unwrap_or_else(|e| {
+                            eprintln!("Error: {}", e);
+                            exit(1)
+                        })
+                    });
+            let process_options = ProcessOptions {
+                dev_halt_at_slot: Some(start_slot),
+                new_hard_forks: hardforks_of(arg_matches, "hard_forks"),
+                poh_verify: false,
+                ..ProcessOptions::default()
+            };
+            let genesis_config = open_genesis_config_by(&ledger_path, arg_matches);
+            let (bank_forks, leader_schedule_cache, _snapshot_hash) = load_bank_forks(
+                arg_matches,
+                &ledger_path,
+                &genesis_config,
+                process_options,
+                AccessType::TryPrimaryThenSecondary,
+                wal_recovery_mode,
+                snapshot_archive_path,
+            )
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to load ledger: {:?}", err);
+                exit(1);
+            });
+            let bank = bank_forks
+                .get(start_slot)
+                .unwrap_or_else(|| {
+                    eprintln!("Error: Slot {} is not available", start_slot);
+                    exit(1);
+                })
+                .clone();
+            assert!(bank.is_complete());
+
+            let blockstore = open_blockstore(
+                &ledger_path,
+                AccessType::TryPrimaryThenSecondary,
+                wal_recovery_mode,
+            );
+            let accounts = minimized_snapshot::accounts_to_keep(
+                &blockstore,
+                &bank,
+                &leader_schedule_cache,
+                end_slot,
+            )
+            .unwrap_or_else(|err| {
+                eprintln!("Error: {}", err);
+                exit(1);
+            });
+            let removed = minimized_snapshot::remove_accounts(&bank, &accounts);
+            println!(
+                "Keeping {} accounts referenced by slots {} to {}, removed {}",
+                accounts.len(),
+                start_slot + 1,
+                end_slot,
+                removed
+            );
+
+            bank.set_capitalization();
+            bank.squash();
+            bank.clean_accounts(false);
+            bank.shrink_all_slots();
+            bank.update_accounts_hash();
+            bank.rehash();
+
+            if let Err(err) = genesis_config.write(&output_directory) {
+                eprintln!("Unable to write genesis to {:?}: {}", output_directory, err);
+                exit(1);
+            }
+            let target = open_blockstore(&output_directory, AccessType::PrimaryOnly, None);
+            let copied = minimized_snapshot::copy_slots(&blockstore, &target, start_slot, end_slot)
+                .unwrap_or_else(|err| {
+                    eprintln!("Error: {}", err);
+                    exit(1);
+                });
+            println!("Copied {} slots to {:?}", copied, output_directory);
+
+            let temp_dir = tempfile::tempdir_in(&output_directory).unwrap_or_else(|err| {
+                eprintln!("Unable to create temporary directory: {}", err);
+                exit(1);
+            });
+            let storages: Vec<_> = bank.get_snapshot_storages();
+            snapshot_utils::add_snapshot(&temp_dir, &bank, &storages, snapshot_version)
+                .and_then(|slot_snapshot_paths| {
+                    snapshot_utils::package_snapshot(
+                        &bank,
+                        &slot_snapshot_paths,
+                        &temp_dir,
+                        bank.src.slot_deltas(&bank.src.roots()),
+                        &output_directory,
+                        storages,
+                        CompressionType::Zstd,
+                        snapshot_version,
+                    )
+                })
+                .and_then(|package| {
+                    snapshot_utils::archive_snapshot_package(&package).map(|ok| {
+                        println!(
+                            "Successfully created minimized snapshot for slot {}, hash {}: {:?}",
+                            bank.slot(),
+                            bank.hash(),
+                            package.tar_output_file
+                        );
+                        ok
+                    })
+                })
+                .unwrap_or_else(|err| {
+                    eprintln!("Unable to create snapshot archive: {}", err);
+                    exit(1);
+                });
+        }
+        ("verify-snapshot", Some(arg_matches)) => {
+            let snapshot_archive =
+                PathBuf::from(value_t_or_exit!(arg_matches, "snapshot_archive", String));
+            let genesis_config = open_genesis_config_by(&ledger_path, arg_matches);
+
+            // Unpack next to the archive, which is expected to have room for its contents
+            let unpack_path = snapshot_archive
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."));
+            match snapshot_utils::verify_snapshot_archive(
+                &snapshot_archive,
+                unpack_path,
+                &genesis_config,
+                Some(&solana_ledger::builtins::get(genesis_config.cluster_type)),
+            ) {
+                Ok((slot, bank_hash, accounts_hash)) => {
+                    println!(
+                        "Snapshot archive {:?} verified: slot {}, bank hash {}, accounts hash {}",
+                        snapshot_archive, slot, bank_hash, accounts_hash
+                    );
+                }
+                Err(err) => {
+                    eprintln!(
+                        "Snapshot archive {:?} failed to verify: {}",
+                        snapshot_archive, err
+                    );
+                    exit(1);
+                }
+            }
+        }
         ("accounts", Some(arg_matches)) => {
             let dev_halt_at_slot = value_t!(arg_matches, "halt_at_slot", Slot).ok();
             let process_options = ProcessOptions {
@@ -2294,6 +2629,21 @@ fn main() {
                 }
             }
         }
+        ("compact", Some(arg_matches)) => {
+            let columns: Vec<&str> = match arg_matches.values_of("columns") {
+                Some(columns) => columns.collect(),
+                None => Database::columns(),
+            };
+            let database = open_database(&ledger_path, AccessType::PrimaryOnly);
+            for column in columns {
+                println!("Compacting {}...", column);
+                if let Err(err) = database.compact_column(column) {
+                    eprintln!("Unable to compact {}: {:?}", column, err);
+                    exit(1);
+                }
+            }
+            println!("Ok.");
+        }
         ("", _) => {
             eprintln!("{}", matches.usage());
             exit(1);
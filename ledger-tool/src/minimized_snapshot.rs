@@ -0,0 +1,97 @@
+//! The `minimized_snapshot` module cuts the state of a bank down to the accounts needed to replay
+//! a range of slots after it, so that the processing of those slots can be reproduced from a
+//! small snapshot and ledger instead of a copy of the whole ledger.
+
+use solana_ledger::{blockstore::Blockstore, leader_schedule_cache::LeaderScheduleCache};
+use solana_runtime::bank::Bank;
+use solana_sdk::{clock::Slot, feature, native_loader, pubkey::Pubkey, sysvar};
+use std::collections::HashSet;
+
+/// Returns the accounts needed to replay the slots after `bank` up to `end_slot`: those the
+/// transactions of the slots reference, the leaders collecting their fees, and the sysvar,
+/// builtin program, feature, vote and stake accounts the banks read on their own
+pub fn accounts_to_keep(
+    blockstore: &Blockstore,
+    bank: &Bank,
+    leader_schedule_cache: &LeaderScheduleCache,
+    end_slot: Slot,
+) -> Result<HashSet<Pubkey>, String> {
+    let mut accounts = HashSet::new();
+    let slots = blockstore
+        .slot_meta_iterator(bank.slot() + 1)
+        .map_err(|err| format!("Unable to read the slots after {}: {}", bank.slot(), err))?;
+    for (slot, _meta) in slots {
+        if slot > end_slot {
+            break;
+        }
+        let entries = blockstore
+            .get_slot_entries(slot, 0)
+            .map_err(|err| format!("Unable to read the entries of slot {}: {}", slot, err))?;
+        for transaction in entries.into_iter().flat_map(|entry| entry.transactions) {
+            accounts.extend(transaction.message.account_keys);
+        }
+        if let Some(leader) = leader_schedule_cache.slot_leader_at(slot, Some(bank)) {
+            accounts.insert(leader);
+        }
+    }
+
+    for owner in &[
+        sysvar::id(),
+        native_loader::id(),
+        feature::id(),
+        solana_vote_program::id(),
+        solana_stake_program::id(),
+    ] {
+        accounts.extend(
+            bank.get_program_accounts(owner)
+                .into_iter()
+                .map(|(pubkey, _account)| pubkey),
+        );
+    }
+    Ok(accounts)
+}
+
+/// Removes the accounts of `bank` which are not in `keep`, returning how many were removed
+pub fn remove_accounts(bank: &Bank, keep: &HashSet<Pubkey>) -> usize {
+    let mut removed = 0;
+    for (pubkey, mut account, _slot) in bank.get_all_accounts_with_modified_slots() {
+        if !keep.contains(&pubkey) {
+            account.lamports = 0;
+            bank.store_account(&pubkey, &account);
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// Copies the shreds of the slots from `start_slot` to `end_slot` into `target`, along with
+/// which of them are roots, returning the number of slots copied
+pub fn copy_slots(
+    source: &Blockstore,
+    target: &Blockstore,
+    start_slot: Slot,
+    end_slot: Slot,
+) -> Result<usize, String> {
+    let mut copied = 0;
+    let slots = source
+        .slot_meta_iterator(start_slot)
+        .map_err(|err| format!("Unable to read the slots from {}: {}", start_slot, err))?;
+    for (slot, _meta) in slots {
+        if slot > end_slot {
+            break;
+        }
+        let shreds = source
+            .get_data_shreds_for_slot(slot, 0)
+            .map_err(|err| format!("Unable to read the shreds of slot {}: {}", slot, err))?;
+        target
+            .insert_shreds(shreds, None, true)
+            .map_err(|err| format!("Unable to insert the shreds of slot {}: {}", slot, err))?;
+        if source.is_root(slot) {
+            target
+                .set_roots(&[slot])
+                .map_err(|err| format!("Unable to set slot {} as a root: {}", slot, err))?;
+        }
+        copied += 1;
+    }
+    Ok(copied)
+}
@@ -1,8 +1,10 @@
 use assert_cmd::prelude::*;
 use solana_ledger::create_new_tmp_ledger;
 use solana_ledger::genesis_utils::create_genesis_config;
+use std::io::Write;
 use std::process::Command;
 use std::process::Output;
+use std::process::Stdio;
 
 fn run_ledger_tool(args: &[&str]) -> Output {
     Command::cargo_bin(env!("CARGO_PKG_NAME"))
@@ -47,3 +49,57 @@ fn nominal() {
     assert!(output.status.success());
     assert_eq!(count_newlines(&output.stdout), ticks + meta_lines);
 }
+
+#[test]
+fn browse() {
+    let genesis_config = create_genesis_config(100).genesis_config;
+    let (ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_config);
+    let ledger_path = ledger_path.to_str().unwrap();
+
+    let mut child = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .args(&["-l", &ledger_path, "browse", "0"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"slots\nentries\nentry 0\nbogus\nquit\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("* Slot 0"));
+    assert!(stdout.contains("Entry 0 - num_hashes"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown command: bogus"));
+}
+
+#[test]
+fn export() {
+    let genesis_config = create_genesis_config(100).genesis_config;
+    let (ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_config);
+    let output_directory = ledger_path.join("export");
+    let ledger_path = ledger_path.to_str().unwrap();
+    let output_path = output_directory.to_str().unwrap();
+
+    let output = run_ledger_tool(&["-l", &ledger_path, "export", output_path]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Exported 1 blocks"));
+    for table in &["transactions", "instructions", "balances"] {
+        let file = std::fs::read(output_directory.join(format!("{}.parquet", table))).unwrap();
+        assert!(file.starts_with(b"PAR1") && file.ends_with(b"PAR1"));
+    }
+
+    let output = run_ledger_tool(&["-l", &ledger_path, "export", "--format", "car", output_path]);
+    assert!(output.status.success());
+    let car = std::fs::read(output_directory.join("blocks.car")).unwrap();
+    // The header is a length prefixed map of the roots and the version
+    let header_len = car[0] as usize;
+    assert_eq!(car[1], 0xa2);
+    assert!(car[..=header_len].ends_with(b"version\x01"));
+}
@@ -9,11 +9,15 @@ use crate::{
 };
 use log::*;
 use solana_runtime::{
-    bank_forks::{BankForks, SnapshotConfig},
+    bank_forks::{BankForks, CompressionType, SnapshotConfig},
     snapshot_utils,
 };
 use solana_sdk::{clock::Slot, genesis_config::GenesisConfig, hash::Hash};
-use std::{fs, path::PathBuf, process, result};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process, result,
+};
 
 pub type LoadResult = result::Result<
     (BankForks, LeaderScheduleCache, Option<(Slot, Hash)>),
@@ -37,7 +41,7 @@ pub fn load(
     process_options: ProcessOptions,
     transaction_status_sender: Option<TransactionStatusSender>,
 ) -> LoadResult {
-    if let Some(snapshot_config) = snapshot_config.as_ref() {
+    let snapshot_archive = if let Some(snapshot_config) = snapshot_config.as_ref() {
         info!(
             "Initializing snapshot path: {:?}",
             snapshot_config.snapshot_path
@@ -46,58 +50,50 @@ pub fn load(
         fs::create_dir_all(&snapshot_config.snapshot_path)
             .expect("Couldn't create snapshot directory");
 
-        match snapshot_utils::get_highest_snapshot_archive_path(
+        let archive = snapshot_utils::get_highest_snapshot_archive_path(
             &snapshot_config.snapshot_package_output_path,
-        ) {
-            Some((archive_filename, (archive_slot, archive_snapshot_hash, compression))) => {
-                info!("Loading snapshot package: {:?}", archive_filename);
-                // Fail hard here if snapshot fails to load, don't silently continue
-
-                if account_paths.is_empty() {
-                    error!("Account paths not present when booting from snapshot");
-                    process::exit(1);
-                }
-
-                let deserialized_bank = snapshot_utils::bank_from_archive(
-                    &account_paths,
-                    &process_options.frozen_accounts,
-                    &snapshot_config.snapshot_path,
-                    &archive_filename,
-                    compression,
-                    genesis_config,
-                    process_options.debug_keys.clone(),
-                    Some(&crate::builtins::get(genesis_config.cluster_type)),
-                )
-                .expect("Load from snapshot failed");
-
-                let deserialized_snapshot_hash = (
-                    deserialized_bank.slot(),
-                    deserialized_bank.get_accounts_hash(),
-                );
-
-                if deserialized_snapshot_hash != (archive_slot, archive_snapshot_hash) {
-                    error!(
-                        "Snapshot has mismatch:\narchive: {:?}\ndeserialized: {:?}",
-                        archive_snapshot_hash, deserialized_snapshot_hash
-                    );
-                    process::exit(1);
-                }
-
-                return to_loadresult(
-                    blockstore_processor::process_blockstore_from_root(
-                        blockstore,
-                        deserialized_bank,
-                        &process_options,
-                        &VerifyRecyclers::default(),
-                        transaction_status_sender,
-                    ),
-                    Some(deserialized_snapshot_hash),
-                );
-            }
-            None => info!("No snapshot package available"),
+        );
+        if archive.is_none() {
+            info!("No snapshot package available");
         }
+        archive
     } else {
         info!("Snapshots disabled");
+        None
+    };
+
+    // A checkpoint is only resumed from if it is ahead of the latest snapshot
+    let snapshot_slot = snapshot_archive.as_ref().map(|(_, (slot, _, _))| *slot);
+    if let Some(checkpoint_path) = process_options.checkpoint_path.as_ref() {
+        if let Some(archive) = snapshot_utils::get_highest_snapshot_archive_path(checkpoint_path)
+            .filter(|(_, (checkpoint_slot, _, _))| Some(*checkpoint_slot) > snapshot_slot)
+        {
+            info!("Resuming from checkpoint: {:?}", archive.0);
+            // Keep the unpacked checkpoint apart from the archives
+            let unpack_dir = tempfile::tempdir_in(checkpoint_path)
+                .expect("Couldn't create checkpoint unpack directory");
+            return load_from_snapshot(
+                genesis_config,
+                blockstore,
+                account_paths,
+                unpack_dir.path(),
+                archive,
+                &process_options,
+                transaction_status_sender,
+            );
+        }
+    }
+
+    if let (Some(snapshot_config), Some(archive)) = (snapshot_config, snapshot_archive) {
+        return load_from_snapshot(
+            genesis_config,
+            blockstore,
+            account_paths,
+            &snapshot_config.snapshot_path,
+            archive,
+            &process_options,
+            transaction_status_sender,
+        );
     }
 
     info!("Processing ledger from genesis");
@@ -111,3 +107,66 @@ pub fn load(
         None,
     )
 }
+
+fn load_from_snapshot(
+    genesis_config: &GenesisConfig,
+    blockstore: &Blockstore,
+    account_paths: Vec<PathBuf>,
+    snapshot_path: &Path,
+    archive: (PathBuf, (Slot, Hash, CompressionType)),
+    process_options: &ProcessOptions,
+    transaction_status_sender: Option<TransactionStatusSender>,
+) -> LoadResult {
+    let (archive_filename, (archive_slot, archive_snapshot_hash, compression)) = archive;
+    info!("Loading snapshot package: {:?}", archive_filename);
+    // Fail hard here if snapshot fails to load, don't silently continue
+
+    if account_paths.is_empty() {
+        error!("Account paths not present when booting from snapshot");
+        process::exit(1);
+    }
+
+    let deserialized_bank = snapshot_utils::bank_from_archive(
+        &account_paths,
+        &process_options.frozen_accounts,
+        &snapshot_path.to_path_buf(),
+        &archive_filename,
+        compression,
+        genesis_config,
+        process_options.debug_keys.clone(),
+        Some(&crate::builtins::get(genesis_config.cluster_type)),
+        process_options.account_indexes.clone(),
+        process_options.append_vec_backend.clone(),
+    )
+    .unwrap_or_else(|err| {
+        error!(
+            "Failed to load snapshot archive {:?}: {}",
+            archive_filename, err
+        );
+        process::exit(1);
+    });
+
+    let deserialized_snapshot_hash = (
+        deserialized_bank.slot(),
+        deserialized_bank.get_accounts_hash(),
+    );
+
+    if deserialized_snapshot_hash != (archive_slot, archive_snapshot_hash) {
+        error!(
+            "Snapshot has mismatch:\narchive: {:?}\ndeserialized: {:?}",
+            archive_snapshot_hash, deserialized_snapshot_hash
+        );
+        process::exit(1);
+    }
+
+    to_loadresult(
+        blockstore_processor::process_blockstore_from_root(
+            blockstore,
+            deserialized_bank,
+            process_options,
+            &VerifyRecyclers::default(),
+            transaction_status_sender,
+        ),
+        Some(deserialized_snapshot_hash),
+    )
+}
@@ -4,6 +4,8 @@ use solana_measure::measure::Measure;
 use solana_sdk::clock::Slot;
 use std::{
     collections::HashSet,
+    fs,
+    path::PathBuf,
     result::Result,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -21,16 +23,73 @@ toml_config::derived_values! {
     BLOCK_READ_AHEAD_DEPTH: usize = CFG.NUM_BLOCKS_TO_UPLOAD_IN_PARALLEL * 2;
 }
 
+/// Persists the range of slots whose blocks are known to be in the historical storage, so that an
+/// interrupted upload resumes where it left off rather than rescanning the storage from the start
+pub struct UploadCheckpoint {
+    path: PathBuf,
+}
+
+impl UploadCheckpoint {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// Return the (first, last) slots of the uploaded range, if any
+    pub fn load(&self) -> Option<(Slot, Slot)> {
+        let contents = fs::read_to_string(&self.path).ok()?;
+        let mut slots = contents.split_whitespace().map(|slot| slot.parse().ok());
+        match (slots.next(), slots.next()) {
+            (Some(Some(first_slot)), Some(Some(last_slot))) => Some((first_slot, last_slot)),
+            _ => {
+                warn!("Ignoring malformed upload checkpoint {:?}", self.path);
+                None
+            }
+        }
+    }
+
+    pub fn store(&self, first_slot: Slot, last_slot: Slot) {
+        // Write to a temporary file first so that a crash never leaves a truncated checkpoint
+        let tmp_path = self.path.with_extension("tmp");
+        let result = fs::write(&tmp_path, format!("{} {}\n", first_slot, last_slot))
+            .and_then(|()| fs::rename(&tmp_path, &self.path));
+        if let Err(err) = result {
+            warn!("Failed to store upload checkpoint {:?}: {}", self.path, err);
+        }
+    }
+}
+
 pub async fn upload_confirmed_blocks(
     blockstore: Arc<Blockstore>,
-    bigtable: solana_storage_bigtable::LedgerStorage,
+    bigtable: Arc<dyn solana_storage_bigtable::HistoricalLedgerStorage>,
     starting_slot: Slot,
     ending_slot: Option<Slot>,
     allow_missing_metadata: bool,
+    checkpoint: Option<&UploadCheckpoint>,
     exit: Arc<AtomicBool>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let mut measure = Measure::start("entire upload");
 
+    // Skip over the blocks a previous upload already stored, if it covered `starting_slot`
+    let uploaded_range = checkpoint.and_then(UploadCheckpoint::load);
+    let (checkpoint_first_slot, starting_slot) = match uploaded_range {
+        Some((first_slot, last_slot))
+            if first_slot <= starting_slot && starting_slot <= last_slot.saturating_add(1) =>
+        {
+            info!(
+                "Resuming upload after slot {}, slots {} to {} are already uploaded",
+                last_slot, first_slot, last_slot
+            );
+            (first_slot, last_slot.saturating_add(1))
+        }
+        _ => (starting_slot, starting_slot),
+    };
+    if let Some(ending_slot) = ending_slot {
+        if starting_slot > ending_slot {
+            info!("No blocks need to be uploaded to bigtable");
+            return Ok(());
+        }
+    }
+
     info!("Loading ledger slots starting at {}...", starting_slot);
     let blockstore_slots: Vec<_> = blockstore
         .slot_meta_iterator(starting_slot)
@@ -113,8 +172,12 @@ pub async fn upload_confirmed_blocks(
         blocks_to_upload
     };
 
+    let last_blockstore_slot = *blockstore_slots.last().unwrap();
     if blocks_to_upload.is_empty() {
         info!("No blocks need to be uploaded to bigtable");
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.store(checkpoint_first_slot, last_blockstore_slot);
+        }
         return Ok(());
     }
     info!(
@@ -177,6 +240,7 @@ pub async fn upload_confirmed_blocks(
 
         let mut measure_upload = Measure::start("Upload");
         let mut num_blocks = blocks.len();
+        let last_slot = blocks.last().map(|(slot, _)| *slot);
         info!("Preparing the next {} blocks for upload", num_blocks);
 
         let uploads = blocks.into_iter().filter_map(|(slot, block)| match block {
@@ -209,13 +273,44 @@ pub async fn upload_confirmed_blocks(
 
         measure_upload.stop();
         info!("{} for {} blocks", measure_upload, num_blocks);
+
+        // Every block up to this chunk is stored only as long as no upload has failed yet
+        if let (Some(checkpoint), Some(last_slot), 0) = (checkpoint, last_slot, failures) {
+            checkpoint.store(checkpoint_first_slot, last_slot);
+        }
     }
 
     measure.stop();
     info!("{}", measure);
+    if failures == 0 && !exit.load(Ordering::Relaxed) {
+        if let Some(checkpoint) = checkpoint {
+            checkpoint.store(checkpoint_first_slot, last_blockstore_slot);
+        }
+    }
     if failures > 0 {
         Err(format!("Incomplete upload, {} operations failed", failures).into())
     } else {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_checkpoint() {
+        let checkpoint_dir = tempfile::TempDir::new().unwrap();
+        let path = checkpoint_dir.path().join("checkpoint");
+        let checkpoint = UploadCheckpoint::new(path.clone());
+        assert_eq!(checkpoint.load(), None);
+
+        checkpoint.store(10, 42);
+        assert_eq!(checkpoint.load(), Some((10, 42)));
+        checkpoint.store(10, 100);
+        assert_eq!(UploadCheckpoint::new(path.clone()).load(), Some((10, 100)));
+
+        fs::write(&path, "garbage").unwrap();
+        assert_eq!(checkpoint.load(), None);
+    }
+}
@@ -26,4 +26,8 @@ pub enum BlockError {
     /// that each block has the same number of hashes
     #[error("trailing entry")]
     TrailingEntry,
+
+    /// The transactions of a block must not exceed the limits of the cost model
+    #[error("cost limit exceeded")]
+    ExceedsCostLimit,
 }
@@ -4,8 +4,8 @@
 pub use crate::{blockstore_db::BlockstoreError, blockstore_meta::SlotMeta};
 use crate::{
     blockstore_db::{
-        columns as cf, AccessType, BlockstoreRecoveryMode, Column, Database, IteratorDirection,
-        IteratorMode, LedgerColumn, Result, WriteBatch,
+        columns as cf, AccessType, BlockstoreRecoveryMode, Column, CompactionSchedule, Database,
+        IteratorDirection, IteratorMode, LedgerColumn, Result, WalPolicy, WriteBatch,
     },
     blockstore_meta::*,
     entry::{create_ticks, Entry},
@@ -55,7 +55,7 @@ use std::{
         mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
         Arc, Mutex, RwLock,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 use trees::{Tree, TreeWalk};
@@ -144,6 +144,10 @@ pub struct Blockstore {
     pub completed_slots_senders: Vec<SyncSender<Vec<Slot>>>,
     pub lowest_cleanup_slot: Arc<RwLock<u64>>,
     no_compaction: bool,
+    last_catch_up: Mutex<Instant>,
+    shred_write_coalescing: RwLock<ShredWriteCoalescing>,
+    // Stats of the slots inserted into since the last root
+    slot_recovery_stats: Mutex<HashMap<Slot, SlotRecoveryStats>>,
 }
 
 pub struct IndexMetaWorkingSetEntry {
@@ -179,6 +183,32 @@ pub struct BlockstoreInsertionMetrics {
     pub num_recovered_failed_invalid: usize,
     pub num_recovered_exists: usize,
     pub index_meta_time: u64,
+    pub num_write_batches: u64,
+    /// Bytes written to the database, including the metadata updated along with the shreds
+    pub write_batch_bytes: u64,
+    /// Bytes of the payloads of the inserted shreds
+    pub shred_payload_bytes: u64,
+}
+
+/// How the data shreds of a slot inserted into the blockstore were obtained
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SlotRecoveryStats {
+    /// Data shreds received over turbine
+    pub num_turbine: usize,
+    /// Data shreds received in response to repair requests
+    pub num_repaired: usize,
+    /// Data shreds recovered from the coding shreds of the slot
+    pub num_recovered: usize,
+}
+
+/// How the window service coalesces received shreds into fewer, larger blockstore writes. The
+/// default writes whatever shreds are pending as soon as any are received.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ShredWriteCoalescing {
+    /// Write once this many shreds are pending
+    pub batch_size: usize,
+    /// Longest time to wait for `batch_size` shreds before writing the pending ones
+    pub flush_interval: Duration,
 }
 
 impl SlotMetaWorkingSetEntry {
@@ -238,8 +268,21 @@ impl BlockstoreInsertionMetrics {
                 self.num_recovered_exists as i64,
                 i64
             ),
+            ("num_write_batches", self.num_write_batches as i64, i64),
+            ("write_batch_bytes", self.write_batch_bytes as i64, i64),
+            ("shred_payload_bytes", self.shred_payload_bytes as i64, i64),
+            ("write_amplification", self.write_amplification(), f64),
         );
     }
+
+    /// Ratio of the bytes written to the database to the shred payload bytes inserted
+    pub fn write_amplification(&self) -> f64 {
+        if self.shred_payload_bytes == 0 {
+            0.0
+        } else {
+            self.write_batch_bytes as f64 / self.shred_payload_bytes as f64
+        }
+    }
 }
 
 impl Blockstore {
@@ -252,6 +295,12 @@ impl Blockstore {
         Self::do_open(ledger_path, AccessType::PrimaryOnly, None)
     }
 
+    /// Opens a Ledger as a read-only secondary instance, which can be used while a validator has
+    /// the Ledger open. Call `try_catch_up_with_primary()` to see the validator's latest writes.
+    pub fn open_read_only(ledger_path: &Path) -> Result<Blockstore> {
+        Self::do_open(ledger_path, AccessType::Secondary, None)
+    }
+
     pub fn open_with_access_type(
         ledger_path: &Path,
         access_type: AccessType,
@@ -300,13 +349,7 @@ impl Blockstore {
 
         let db = Arc::new(db);
 
-        // Get max root or 0 if it doesn't exist
-        let max_root = db
-            .iter::<cf::Root>(IteratorMode::End)?
-            .next()
-            .map(|(slot, _)| slot)
-            .unwrap_or(0);
-        let last_root = Arc::new(RwLock::new(max_root));
+        let last_root = Arc::new(RwLock::new(Self::max_root(&db)?));
 
         // Get active transaction-status index or 0
         let active_transaction_status_index = db
@@ -349,13 +392,47 @@ impl Blockstore {
             last_root,
             lowest_cleanup_slot: Arc::new(RwLock::new(0)),
             no_compaction: false,
+            last_catch_up: Mutex::new(Instant::now()),
+            shred_write_coalescing: RwLock::default(),
+            slot_recovery_stats: Mutex::default(),
         };
-        if initialize_transaction_status_index {
+        if initialize_transaction_status_index && blockstore.is_primary_access() {
             blockstore.initialize_transaction_status_index()?;
         }
         Ok(blockstore)
     }
 
+    // Get max root or 0 if it doesn't exist
+    fn max_root(db: &Database) -> Result<Slot> {
+        Ok(db
+            .iter::<cf::Root>(IteratorMode::End)?
+            .next()
+            .map(|(slot, _)| slot)
+            .unwrap_or(0))
+    }
+
+    /// Catches a read-only Blockstore up with the writes of the validator since it was opened,
+    /// or since the last call
+    pub fn try_catch_up_with_primary(&self) -> Result<()> {
+        if self.is_primary_access() {
+            return Ok(());
+        }
+        self.db.try_catch_up_with_primary()?;
+        *self.last_root.write().unwrap() = Self::max_root(&self.db)?;
+        *self.last_catch_up.lock().unwrap() = Instant::now();
+        Ok(())
+    }
+
+    /// Catches a read-only Blockstore up with the validator if it has not done so for `interval`,
+    /// so that long-running tools can follow the ledger as it grows. Returns true if it caught up
+    pub fn maybe_catch_up_with_primary(&self, interval: Duration) -> Result<bool> {
+        if self.is_primary_access() || self.last_catch_up.lock().unwrap().elapsed() < interval {
+            return Ok(false);
+        }
+        self.try_catch_up_with_primary()?;
+        Ok(true)
+    }
+
     pub fn open_with_signal(
         ledger_path: &Path,
         recovery_mode: Option<BlockstoreRecoveryMode>,
@@ -425,6 +502,30 @@ impl Blockstore {
         self.no_compaction = no_compaction;
     }
 
+    pub fn set_compaction_schedule(&self, schedule: CompactionSchedule) {
+        self.db.set_compaction_schedule(schedule);
+    }
+
+    pub fn set_wal_policy(&self, policy: WalPolicy) {
+        self.db.set_wal_policy(policy);
+    }
+
+    pub fn set_shred_write_coalescing(&self, coalescing: ShredWriteCoalescing) {
+        *self.shred_write_coalescing.write().unwrap() = coalescing;
+    }
+
+    pub fn shred_write_coalescing(&self) -> ShredWriteCoalescing {
+        *self.shred_write_coalescing.read().unwrap()
+    }
+
+    pub fn run_scheduled_compactions(&self) -> usize {
+        self.db.run_scheduled_compactions()
+    }
+
+    pub fn compact_column(&self, column: &str) -> Result<()> {
+        self.db.compact_column(column)
+    }
+
     pub fn destroy(ledger_path: &Path) -> Result<()> {
         // Database::destroy() fails if the path doesn't exist
         fs::create_dir_all(ledger_path)?;
@@ -514,6 +615,15 @@ impl Blockstore {
         Ok(slot_iterator.take_while(move |((shred_slot, _), _)| *shred_slot == slot))
     }
 
+    pub fn highest_slot(&self) -> Result<Option<Slot>> {
+        let highest_slot = self
+            .db
+            .iter::<cf::SlotMeta>(IteratorMode::End)?
+            .next()
+            .map(|(slot, _)| slot);
+        Ok(highest_slot)
+    }
+
     pub fn rooted_slot_iterator<'a>(
         &'a self,
         slot: Slot,
@@ -743,6 +853,7 @@ impl Blockstore {
     pub fn insert_shreds_handle_duplicate<F>(
         &self,
         shreds: Vec<Shred>,
+        is_repaired: Vec<bool>,
         leader_schedule: Option<&Arc<LeaderScheduleCache>>,
         is_trusted: bool,
         handle_duplicate: &F,
@@ -751,6 +862,7 @@ impl Blockstore {
     where
         F: Fn(Shred),
     {
+        assert_eq!(shreds.len(), is_repaired.len());
         let mut total_start = Measure::start("Total elapsed");
         let mut start = Measure::start("Blockstore lock");
         let _lock = self.insert_shreds_lock.lock().unwrap();
@@ -772,6 +884,7 @@ impl Blockstore {
         let mut index_meta_time = 0;
         let mut newly_completed_data_sets: Vec<CompletedDataSetInfo> = vec![];
         let mut inserted_indices = Vec::new();
+        let mut recovery_stats: HashMap<Slot, SlotRecoveryStats> = HashMap::new();
         shreds.into_iter().enumerate().for_each(|(i, shred)| {
             if shred.is_data() {
                 let shred_slot = shred.slot();
@@ -797,6 +910,12 @@ impl Blockstore {
                     ));
                     inserted_indices.push(i);
                     num_inserted += 1;
+                    let stats = recovery_stats.entry(shred_slot).or_default();
+                    if is_repaired[i] {
+                        stats.num_repaired += 1;
+                    } else {
+                        stats.num_turbine += 1;
+                    }
                 }
             } else if shred.is_code() {
                 self.check_cache_coding_shred(
@@ -865,6 +984,7 @@ impl Blockstore {
                                     ),
                                 );
                                 num_recovered_inserted += 1;
+                                recovery_stats.entry(shred_slot).or_default().num_recovered += 1;
                             }
                         }
                     } else {
@@ -876,15 +996,22 @@ impl Blockstore {
         start.stop();
         let shred_recovery_elapsed = start.as_us();
 
+        let mut shred_payload_bytes: usize = just_inserted_data_shreds
+            .values()
+            .map(|shred| shred.payload.len())
+            .sum();
         just_inserted_coding_shreds
             .into_iter()
             .for_each(|((_, _), shred)| {
-                self.check_insert_coding_shred(
+                let payload_len = shred.payload.len();
+                if self.check_insert_coding_shred(
                     shred,
                     &mut index_working_set,
                     &mut write_batch,
                     &mut index_meta_time,
-                );
+                ) {
+                    shred_payload_bytes += payload_len;
+                }
                 num_inserted += 1;
             });
 
@@ -901,6 +1028,16 @@ impl Blockstore {
             &self.completed_slots_senders,
             &mut write_batch,
         )?;
+        let full_slots: Vec<Slot> = slot_meta_working_set
+            .iter()
+            .filter(|(_, entry)| {
+                is_newly_completed_slot(
+                    &RefCell::borrow(&*entry.new_slot_meta),
+                    &entry.old_slot_meta,
+                )
+            })
+            .map(|(slot, _)| *slot)
+            .collect();
 
         for ((slot, set_index), erasure_meta) in erasure_metas {
             write_batch.put::<cf::ErasureMeta>((slot, set_index), &erasure_meta)?;
@@ -915,6 +1052,7 @@ impl Blockstore {
         let commit_working_sets_elapsed = start.as_us();
 
         let mut start = Measure::start("Write Batch");
+        let write_batch_bytes = write_batch.size_in_bytes();
         self.db.write(write_batch)?;
         start.stop();
         let write_batch_elapsed = start.as_us();
@@ -926,6 +1064,8 @@ impl Blockstore {
             newly_completed_slots,
         )?;
 
+        self.update_slot_recovery_stats(recovery_stats, &full_slots);
+
         total_start.stop();
 
         metrics.num_shreds += num_shreds;
@@ -943,10 +1083,44 @@ impl Blockstore {
         metrics.num_recovered_failed_invalid = num_recovered_failed_invalid;
         metrics.num_recovered_exists = num_recovered_exists;
         metrics.index_meta_time += index_meta_time;
+        metrics.num_write_batches += 1;
+        metrics.write_batch_bytes += write_batch_bytes as u64;
+        metrics.shred_payload_bytes += shred_payload_bytes as u64;
 
         Ok((newly_completed_data_sets, inserted_indices))
     }
 
+    fn update_slot_recovery_stats(
+        &self,
+        updates: HashMap<Slot, SlotRecoveryStats>,
+        full_slots: &[Slot],
+    ) {
+        let mut slot_recovery_stats = self.slot_recovery_stats.lock().unwrap();
+        for (slot, update) in updates {
+            let stats = slot_recovery_stats.entry(slot).or_default();
+            stats.num_turbine += update.num_turbine;
+            stats.num_repaired += update.num_repaired;
+            stats.num_recovered += update.num_recovered;
+        }
+        for slot in full_slots {
+            if let Some(stats) = slot_recovery_stats.get(slot) {
+                datapoint_info!(
+                    "blockstore-slot-recovery",
+                    ("slot", *slot as i64, i64),
+                    ("num_turbine", stats.num_turbine as i64, i64),
+                    ("num_repaired", stats.num_repaired as i64, i64),
+                    ("num_recovered", stats.num_recovered as i64, i64),
+                );
+            }
+        }
+    }
+
+    /// How the data shreds inserted into the slot were obtained, for the slots inserted into
+    /// since the last root
+    pub fn slot_recovery_stats(&self, slot: Slot) -> Option<SlotRecoveryStats> {
+        self.slot_recovery_stats.lock().unwrap().get(&slot).copied()
+    }
+
     pub fn clear_unconfirmed_slot(&self, slot: Slot) {
         let _lock = self.insert_shreds_lock.lock().unwrap();
         if let Some(mut slot_meta) = self
@@ -977,8 +1151,10 @@ impl Blockstore {
         leader_schedule: Option<&Arc<LeaderScheduleCache>>,
         is_trusted: bool,
     ) -> Result<(Vec<CompletedDataSetInfo>, Vec<usize>)> {
+        let is_repaired = vec![false; shreds.len()];
         self.insert_shreds_handle_duplicate(
             shreds,
+            is_repaired,
             leader_schedule,
             is_trusted,
             &|_| {},
@@ -1999,10 +2175,14 @@ impl Blockstore {
             .map(|signatures| signatures.iter().map(|(_, signature)| *signature).collect())
     }
 
+    /// Returns the confirmed signatures for `address`, newest first, from `highest_slot` down to
+    /// `lowest_slot`. The `before` and `until` signatures narrow the slots further, letting
+    /// callers page through the history of the address
     pub fn get_confirmed_signatures_for_address2(
         &self,
         address: Pubkey,
-        highest_confirmed_root: Slot,
+        highest_slot: Slot,
+        lowest_slot: Slot,
         before: Option<Signature>,
         until: Option<Signature>,
         limit: usize,
@@ -2021,11 +2201,12 @@ impl Blockstore {
         // be excluded from the results.
         let mut get_before_slot_timer = Measure::start("get_before_slot_timer");
         let (slot, mut before_excluded_signatures) = match before {
-            None => (highest_confirmed_root, None),
+            None => (highest_slot, None),
             Some(before) => {
                 let transaction_status = self.get_transaction_status(before)?;
                 match transaction_status {
                     None => return Ok(vec![]),
+                    Some((slot, _)) if slot > highest_slot => (highest_slot, None),
                     Some((slot, _)) => {
                         let confirmed_block = self.get_confirmed_block(slot).map_err(|err| {
                             BlockstoreError::IO(IOError::new(
@@ -2071,11 +2252,11 @@ impl Blockstore {
         // `until` signature
         let mut get_until_slot_timer = Measure::start("get_until_slot_timer");
         let (lowest_slot, until_excluded_signatures) = match until {
-            None => (0, HashSet::new()),
+            None => (lowest_slot, HashSet::new()),
             Some(until) => {
                 let transaction_status = self.get_transaction_status(until)?;
                 match transaction_status {
-                    None => (0, HashSet::new()),
+                    None => (lowest_slot, HashSet::new()),
                     Some((slot, _)) => {
                         let confirmed_block = self.get_confirmed_block(slot).map_err(|err| {
                             BlockstoreError::IO(IOError::new(
@@ -2107,12 +2288,18 @@ impl Blockstore {
                             slot_signatures = slot_signatures.split_off(pos);
                         }
 
-                        (slot, slot_signatures.into_iter().collect::<HashSet<_>>())
+                        (
+                            slot.max(lowest_slot),
+                            slot_signatures.into_iter().collect::<HashSet<_>>(),
+                        )
                     }
                 }
             }
         };
         get_until_slot_timer.stop();
+        if slot < lowest_slot {
+            return Ok(vec![]);
+        }
 
         // Fetch the list of signatures that affect the given address
         let first_available_block = self.get_first_available_block()?;
@@ -2325,6 +2512,27 @@ impl Blockstore {
         self.perf_samples_cf.put(index, perf_sample)
     }
 
+    /// Deletes all but the `max_samples` most recent performance samples. Samples are not purged
+    /// along with the slots they are keyed by, so that they survive ledger cleanup and restarts.
+    pub fn purge_old_perf_samples(&self, max_samples: usize) -> Result<()> {
+        let oldest_retained_slot = self
+            .db
+            .iter::<cf::PerfSamples>(IteratorMode::End)?
+            .take(max_samples)
+            .last()
+            .map(|(slot, _)| slot);
+        if let Some(oldest_retained_slot) = oldest_retained_slot {
+            let mut write_batch = self.db.batch()?;
+            self.db.delete_range_cf::<cf::PerfSamples>(
+                &mut write_batch,
+                0,
+                oldest_retained_slot,
+            )?;
+            self.db.write(write_batch)?;
+        }
+        Ok(())
+    }
+
     /// Returns the entry vector for the slot starting with `shred_start_index`
     pub fn get_slot_entries(&self, slot: Slot, shred_start_index: u64) -> Result<Vec<Entry>> {
         self.get_slot_entries_with_shred_info(slot, shred_start_index, false)
@@ -2338,16 +2546,42 @@ impl Blockstore {
         slot: Slot,
         start_index: u64,
         allow_dead_slots: bool,
+    ) -> Result<(Vec<Entry>, u64, bool)> {
+        self.get_slot_entries_with_shred_info_limited(
+            slot,
+            start_index,
+            std::u64::MAX,
+            allow_dead_slots,
+        )
+    }
+
+    /// Like `get_slot_entries_with_shred_info`, but stops loading completed data blocks once
+    /// `max_shreds` shreds have been loaded. At least one data block is loaded if there is any,
+    /// and the returned `slot_full` is only true if the last data block of the slot was loaded.
+    pub fn get_slot_entries_with_shred_info_limited(
+        &self,
+        slot: Slot,
+        start_index: u64,
+        max_shreds: u64,
+        allow_dead_slots: bool,
     ) -> Result<(Vec<Entry>, u64, bool)> {
         if self.is_dead(slot) && !allow_dead_slots {
             return Err(BlockstoreError::DeadSlot);
         }
 
-        let (completed_ranges, slot_meta) = self.get_completed_ranges(slot, start_index)?;
+        let (mut completed_ranges, slot_meta) = self.get_completed_ranges(slot, start_index)?;
         if completed_ranges.is_empty() {
             return Ok((vec![], 0, false));
         }
         let slot_meta = slot_meta.unwrap();
+        let num_ranges = completed_ranges.len();
+        let num_limited_ranges = completed_ranges
+            .iter()
+            .position(|(_, end_index)| u64::from(*end_index) - start_index + 1 > max_shreds)
+            .unwrap_or(num_ranges)
+            .max(1);
+        completed_ranges.truncate(num_limited_ranges);
+        let slot_full = slot_meta.is_full() && num_limited_ranges == num_ranges;
         let num_shreds = completed_ranges
             .last()
             .map(|(_, end_index)| u64::from(*end_index) - start_index + 1)
@@ -2370,7 +2604,7 @@ impl Blockstore {
         });
 
         let entries: Vec<Entry> = entries?.into_iter().flatten().collect();
-        Ok((entries, num_shreds, slot_meta.is_full()))
+        Ok((entries, num_shreds, slot_full))
     }
 
     fn get_completed_ranges(
@@ -2572,6 +2806,11 @@ impl Blockstore {
             *last_root = 0;
         }
         *last_root = cmp::max(*rooted_slots.iter().max().unwrap(), *last_root);
+        let root = *last_root;
+        self.slot_recovery_stats
+            .lock()
+            .unwrap()
+            .retain(|slot, _| *slot > root);
         Ok(())
     }
 
@@ -3453,6 +3692,7 @@ fn adjust_ulimit_nofile() -> Result<()> {
 pub mod tests {
     use super::*;
     use crate::{
+        blockstore_db::{ColumnName, WalMode},
         blockstore_processor::fill_blockstore_slot_with_ticks,
         entry::{next_entry, next_entry_mut},
         genesis_utils::{create_genesis_config, GenesisConfigInfo},
@@ -3932,6 +4172,47 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_get_slot_entries_with_shred_info_limited() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let shredder = Shredder::new(1, 0, 0.0, Arc::new(Keypair::new()), 0, 0).unwrap();
+            let entries = create_ticks(6, 0, Hash::default());
+            // Three data blocks of two entries each
+            let mut shreds = vec![];
+            for (i, block) in entries.chunks(2).enumerate() {
+                let (mut data_shreds, _, _) =
+                    shredder.entries_to_shreds(block, i == 2, shreds.len() as u32);
+                shreds.append(&mut data_shreds);
+            }
+            let num_shreds = shreds.len() as u64;
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+
+            assert_eq!(
+                blockstore
+                    .get_slot_entries_with_shred_info(1, 0, false)
+                    .unwrap(),
+                (entries.clone(), num_shreds, true)
+            );
+
+            // At least one data block is loaded, even if it exceeds the limit
+            let (first_block, first_block_shreds, slot_full) = blockstore
+                .get_slot_entries_with_shred_info_limited(1, 0, 0, false)
+                .unwrap();
+            assert_eq!(first_block, entries[..2].to_vec());
+            assert!(!slot_full);
+
+            let (rest, rest_shreds, slot_full) = blockstore
+                .get_slot_entries_with_shred_info_limited(1, first_block_shreds, num_shreds, false)
+                .unwrap();
+            assert_eq!(rest, entries[2..].to_vec());
+            assert_eq!(first_block_shreds + rest_shreds, num_shreds);
+            assert!(slot_full);
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     // This test seems to be unnecessary with introduction of data shreds. There are no
     // guarantees that a particular shred index contains a complete entry
     #[test]
@@ -5300,6 +5581,108 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_insert_shreds_wal_policy() {
+        let mut policy = WalPolicy::default();
+        assert!(policy
+            .set_mode("no_such_column", WalMode::Disabled)
+            .is_err());
+        policy
+            .set_mode(cf::ShredData::NAME, WalMode::Disabled)
+            .unwrap();
+        policy.set_mode(cf::SlotMeta::NAME, WalMode::Sync).unwrap();
+        assert_eq!(policy.mode(cf::ShredData::NAME), WalMode::Disabled);
+        assert_eq!(policy.mode(cf::Index::NAME), WalMode::Async);
+        // Batches mixing columns are written with the strictest of their modes
+        assert_eq!(WalMode::Disabled.strictest(WalMode::Async), WalMode::Async);
+        assert_eq!(WalMode::Async.strictest(WalMode::Sync), WalMode::Sync);
+        assert_eq!(
+            WalMode::Disabled.strictest(WalMode::Disabled),
+            WalMode::Disabled
+        );
+        assert_eq!("disabled".parse(), Ok(WalMode::Disabled));
+        assert!("never".parse::<WalMode>().is_err());
+
+        let (shreds, entries) = make_slot_entries(0, 0, 20);
+        let num_shreds = shreds.len();
+        let payload_bytes: usize = shreds.iter().map(|shred| shred.payload.len()).sum();
+        let is_repaired = vec![false; num_shreds];
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            blockstore.set_wal_policy(policy);
+            let mut metrics = BlockstoreInsertionMetrics::default();
+            blockstore
+                .insert_shreds_handle_duplicate(
+                    shreds,
+                    is_repaired,
+                    None,
+                    false,
+                    &|_| {},
+                    &mut metrics,
+                )
+                .unwrap();
+            assert_eq!(blockstore.get_slot_entries(0, 0).unwrap(), entries);
+            assert!(blockstore.meta(0).unwrap().unwrap().is_full());
+
+            assert_eq!(metrics.num_shreds, num_shreds);
+            assert_eq!(metrics.num_write_batches, 1);
+            assert_eq!(metrics.shred_payload_bytes, payload_bytes as u64);
+            // The metadata is written along with the shred payloads
+            assert!(metrics.write_batch_bytes > metrics.shred_payload_bytes);
+            assert!(metrics.write_amplification() > 1.0);
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_slot_recovery_stats() {
+        let (mut shreds, _) = make_slot_entries(1, 0, 20);
+        let num_shreds = shreds.len();
+        assert!(num_shreds > 1);
+        let last_shred = shreds.pop().unwrap();
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let is_repaired = vec![false; shreds.len()];
+            blockstore
+                .insert_shreds_handle_duplicate(
+                    shreds,
+                    is_repaired,
+                    None,
+                    false,
+                    &|_| {},
+                    &mut BlockstoreInsertionMetrics::default(),
+                )
+                .unwrap();
+            blockstore
+                .insert_shreds_handle_duplicate(
+                    vec![last_shred.clone(), last_shred],
+                    vec![true, true],
+                    None,
+                    false,
+                    &|_| {},
+                    &mut BlockstoreInsertionMetrics::default(),
+                )
+                .unwrap();
+            assert!(blockstore.is_full(1));
+            // The duplicate of the repaired shred is not counted
+            assert_eq!(
+                blockstore.slot_recovery_stats(1),
+                Some(SlotRecoveryStats {
+                    num_turbine: num_shreds - 1,
+                    num_repaired: 1,
+                    num_recovered: 0,
+                })
+            );
+            assert_eq!(blockstore.slot_recovery_stats(2), None);
+
+            blockstore.set_roots(&[1]).unwrap();
+            assert_eq!(blockstore.slot_recovery_stats(1), None);
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     pub fn test_insert_multiple_is_last() {
         let (shreds, _) = make_slot_entries(0, 0, 20);
@@ -5377,6 +5760,57 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_open_read_only() {
+        let blockstore_path = get_tmp_ledger_path!();
+        let blockstore = Blockstore::open(&blockstore_path).unwrap();
+        let (shreds, _) = make_slot_entries(1, 0, 10);
+        blockstore.insert_shreds(shreds, None, false).unwrap();
+        blockstore.set_roots(&[0, 1]).unwrap();
+
+        let read_only = Blockstore::open_read_only(&blockstore_path).unwrap();
+        assert!(!read_only.is_primary_access());
+        assert_eq!(read_only.last_root(), 1);
+        assert!(read_only.is_full(1));
+        assert_matches!(read_only.set_roots(&[2]), Err(BlockstoreError::ReadOnly));
+
+        // Writes of the primary become visible after catching up
+        let (shreds, _) = make_slot_entries(2, 1, 10);
+        blockstore.insert_shreds(shreds, None, false).unwrap();
+        blockstore.set_roots(&[2]).unwrap();
+        read_only.try_catch_up_with_primary().unwrap();
+        assert_eq!(read_only.last_root(), 2);
+        assert!(read_only.is_full(2));
+
+        assert!(!read_only
+            .maybe_catch_up_with_primary(Duration::from_secs(60))
+            .unwrap());
+        assert!(read_only
+            .maybe_catch_up_with_primary(Duration::from_secs(0))
+            .unwrap());
+        assert!(!blockstore
+            .maybe_catch_up_with_primary(Duration::from_secs(0))
+            .unwrap());
+
+        // The validator holds the ledger, it cannot be opened for writing
+        assert_matches!(
+            Blockstore::open(&blockstore_path),
+            Err(BlockstoreError::PrimaryInUse)
+        );
+
+        drop(read_only);
+        // The directory of the secondary instance is removed once it is closed
+        assert!(!fs::read_dir(blockstore_path.join(BLOCKSTORE_DIRECTORY))
+            .unwrap()
+            .any(|entry| entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with("solana-secondary")));
+        drop(blockstore);
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_iter_bounds() {
         let blockstore_path = get_tmp_ledger_path!();
@@ -6595,6 +7029,7 @@ pub mod tests {
                 .get_confirmed_signatures_for_address2(
                     address0,
                     highest_confirmed_root,
+                    0,
                     None,
                     None,
                     usize::MAX,
@@ -6607,6 +7042,7 @@ pub mod tests {
                 .get_confirmed_signatures_for_address2(
                     address1,
                     highest_confirmed_root,
+                    0,
                     None,
                     None,
                     usize::MAX,
@@ -6622,6 +7058,7 @@ pub mod tests {
                     .get_confirmed_signatures_for_address2(
                         address0,
                         highest_confirmed_root,
+                        0,
                         if i == 0 {
                             None
                         } else {
@@ -6640,6 +7077,7 @@ pub mod tests {
                     .get_confirmed_signatures_for_address2(
                         address0,
                         highest_confirmed_root,
+                        0,
                         if i == 0 {
                             None
                         } else {
@@ -6661,6 +7099,7 @@ pub mod tests {
                 .get_confirmed_signatures_for_address2(
                     address0,
                     highest_confirmed_root,
+                    0,
                     Some(all0[all0.len() - 1].signature),
                     None,
                     1,
@@ -6672,6 +7111,7 @@ pub mod tests {
                 .get_confirmed_signatures_for_address2(
                     address0,
                     highest_confirmed_root,
+                    0,
                     None,
                     Some(all0[0].signature),
                     2,
@@ -6686,6 +7126,7 @@ pub mod tests {
                     .get_confirmed_signatures_for_address2(
                         address0,
                         highest_confirmed_root,
+                        0,
                         if i == 0 {
                             None
                         } else {
@@ -6708,6 +7149,7 @@ pub mod tests {
                     .get_confirmed_signatures_for_address2(
                         address1,
                         highest_confirmed_root,
+                        0,
                         if i == 0 {
                             None
                         } else {
@@ -6729,6 +7171,7 @@ pub mod tests {
                 .get_confirmed_signatures_for_address2(
                     address0,
                     highest_confirmed_root,
+                    0,
                     Some(all1[0].signature),
                     None,
                     usize::MAX,
@@ -6742,12 +7185,68 @@ pub mod tests {
                 .get_confirmed_signatures_for_address2(
                     address0,
                     highest_confirmed_root,
+                    0,
                     Some(all1[0].signature),
                     Some(all1[4].signature),
                     usize::MAX,
                 )
                 .unwrap();
             assert!(results2.len() < results.len());
+
+            // Fetch the signatures of address 0 in slots 4 to 6, across both primary indexes
+            let in_range: Vec<_> = all0
+                .iter()
+                .filter(|result| (4..=6).contains(&result.slot))
+                .cloned()
+                .collect();
+            assert_eq!(in_range.len(), 6);
+            let results = blockstore
+                .get_confirmed_signatures_for_address2(address0, 6, 4, None, None, usize::MAX)
+                .unwrap();
+            assert_eq!(results, in_range);
+
+            // A `before` signature above the range starts at the top of the range...
+            let results = blockstore
+                .get_confirmed_signatures_for_address2(
+                    address0,
+                    6,
+                    4,
+                    Some(all0[0].signature),
+                    None,
+                    usize::MAX,
+                )
+                .unwrap();
+            assert_eq!(results, in_range);
+
+            // ...while one below it leaves nothing to return
+            assert!(blockstore
+                .get_confirmed_signatures_for_address2(
+                    address0,
+                    highest_confirmed_root,
+                    4,
+                    Some(all0[all0.len() - 1].signature),
+                    None,
+                    usize::MAX,
+                )
+                .unwrap()
+                .is_empty());
+
+            // Pages within the range join up to the whole range
+            let page = blockstore
+                .get_confirmed_signatures_for_address2(address0, 6, 4, None, None, 4)
+                .unwrap();
+            assert_eq!(page, in_range[..4]);
+            let page = blockstore
+                .get_confirmed_signatures_for_address2(
+                    address0,
+                    6,
+                    4,
+                    Some(page[3].signature),
+                    None,
+                    4,
+                )
+                .unwrap();
+            assert_eq!(page, in_range[4..]);
         }
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
@@ -6854,6 +7353,42 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_purge_old_perf_samples() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let perf_sample = PerfSample {
+                num_transactions: 1000,
+                num_slots: 50,
+                sample_period_secs: 60,
+            };
+            for slot in 1..=10 {
+                blockstore
+                    .write_perf_sample(slot * 50, &perf_sample)
+                    .unwrap();
+            }
+
+            // Samples outlive the slots they are keyed by
+            blockstore.purge_slots(0, 500, PurgeType::Exact);
+            assert_eq!(blockstore.get_recent_perf_samples(20).unwrap().len(), 10);
+
+            blockstore.purge_old_perf_samples(4).unwrap();
+            let slots: Vec<_> = blockstore
+                .get_recent_perf_samples(20)
+                .unwrap()
+                .into_iter()
+                .map(|(slot, _)| slot)
+                .collect();
+            assert_eq!(slots, vec![500, 450, 400, 350]);
+
+            // Fewer samples than the limit
+            blockstore.purge_old_perf_samples(10).unwrap();
+            assert_eq!(blockstore.get_recent_perf_samples(20).unwrap().len(), 4);
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_lowest_slot() {
         let blockstore_path = get_tmp_ledger_path!();
@@ -141,11 +141,8 @@ impl Blockstore {
             & self
                 .db
                 .delete_range_cf::<cf::Blocktime>(&mut write_batch, from_slot, to_slot)
-                .is_ok()
-            & self
-                .db
-                .delete_range_cf::<cf::PerfSamples>(&mut write_batch, from_slot, to_slot)
                 .is_ok();
+        // PerfSamples are retained by count instead, see `Blockstore::purge_old_perf_samples`
         let mut w_active_transaction_status_index =
             self.active_transaction_status_index.write().unwrap();
         match purge_type {
@@ -176,6 +173,48 @@ impl Blockstore {
         Ok(columns_purged)
     }
 
+    /// Purges the transaction statuses and address signatures, without touching the other
+    /// columns, so that they can be retained for less time than the shreds. They are purged a
+    /// primary index at a time: the frozen index is purged if it only holds slots up to
+    /// `to_slot`, and returned. Without a frozen index, the active one is frozen instead.
+    ///
+    /// The primary index is toggled the same way, and under the same lock, as by the purges of
+    /// the shreds with `PurgeType::PrimaryIndex`
+    pub fn purge_transaction_status(&self, to_slot: Slot) -> Result<Option<u64>> {
+        let mut write_batch = self.db.batch()?;
+        let purged_index = {
+            let mut w_active_transaction_status_index =
+                self.active_transaction_status_index.write().unwrap();
+            let mut columns_purged = true;
+            let purged_index = self.purge_special_columns_with_primary_index(
+                &mut write_batch,
+                &mut columns_purged,
+                &mut w_active_transaction_status_index,
+                to_slot.saturating_add(1),
+            )?;
+            if !columns_purged {
+                return Err(BlockstoreError::IO(IOError::new(
+                    ErrorKind::Other,
+                    "failed to purge the transaction status columns",
+                )));
+            }
+            self.db.write(write_batch)?;
+            purged_index
+        };
+        if let Some(index) = purged_index {
+            // The size of the columns only reflects the purge once they are compacted
+            self.transaction_status_cf.compact_range(index, index + 1)?;
+            self.address_signatures_cf.compact_range(index, index + 1)?;
+        }
+        Ok(purged_index)
+    }
+
+    /// Estimated size of the transaction statuses and address signatures
+    pub fn transaction_status_size(&self) -> Result<u64> {
+        Ok(self.transaction_status_cf.live_data_size()?
+            + self.address_signatures_cf.live_data_size()?)
+    }
+
     pub fn compact_storage(&self, from_slot: Slot, to_slot: Slot) -> Result<bool> {
         if self.no_compaction {
             info!("compact_storage: compaction disabled");
@@ -183,67 +222,28 @@ impl Blockstore {
         }
         info!("compact_storage: from {} to {}", from_slot, to_slot);
         let mut compact_timer = Measure::start("compact_range");
-        let result = self
-            .meta_cf
-            .compact_range(from_slot, to_slot)
-            .unwrap_or(false)
-            && self
-                .db
+        // Every column is attempted, so that a column deferred to its compaction window does
+        // not hold back the others
+        let results = [
+            self.meta_cf.compact_range(from_slot, to_slot),
+            self.db
                 .column::<cf::Root>()
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .data_shred_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .code_shred_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .dead_slots_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .duplicate_slots_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .erasure_meta_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .orphans_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .index_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .transaction_status_cf
-                .compact_range(0, 2)
-                .unwrap_or(false)
-            && self
-                .address_signatures_cf
-                .compact_range(0, 2)
-                .unwrap_or(false)
-            && self
-                .transaction_status_index_cf
-                .compact_range(0, 2)
-                .unwrap_or(false)
-            && self
-                .rewards_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .blocktime_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false)
-            && self
-                .perf_samples_cf
-                .compact_range(from_slot, to_slot)
-                .unwrap_or(false);
+                .compact_range(from_slot, to_slot),
+            self.data_shred_cf.compact_range(from_slot, to_slot),
+            self.code_shred_cf.compact_range(from_slot, to_slot),
+            self.dead_slots_cf.compact_range(from_slot, to_slot),
+            self.duplicate_slots_cf.compact_range(from_slot, to_slot),
+            self.erasure_meta_cf.compact_range(from_slot, to_slot),
+            self.orphans_cf.compact_range(from_slot, to_slot),
+            self.index_cf.compact_range(from_slot, to_slot),
+            self.transaction_status_cf.compact_range(0, 2),
+            self.address_signatures_cf.compact_range(0, 2),
+            self.transaction_status_index_cf.compact_range(0, 2),
+            self.rewards_cf.compact_range(from_slot, to_slot),
+            self.blocktime_cf.compact_range(from_slot, to_slot),
+            self.perf_samples_cf.compact_range(from_slot, to_slot),
+        ];
+        let result = results.iter().all(|result| matches!(result, Ok(true)));
         compact_timer.stop();
         if !result {
             info!("compact_storage incomplete");
@@ -310,12 +310,13 @@ impl Blockstore {
         columns_purged: &mut bool,
         w_active_transaction_status_index: &mut u64,
         to_slot: Slot,
-    ) -> Result<()> {
-        if let Some(index) = self.toggle_transaction_status_index(
+    ) -> Result<Option<u64>> {
+        let purged_index = self.toggle_transaction_status_index(
             write_batch,
             w_active_transaction_status_index,
             to_slot,
-        )? {
+        )?;
+        if let Some(index) = purged_index {
             *columns_purged &= self
                 .db
                 .delete_range_cf::<cf::TransactionStatus>(write_batch, index, index + 1)
@@ -325,14 +326,18 @@ impl Blockstore {
                     .delete_range_cf::<cf::AddressSignatures>(write_batch, index, index + 1)
                     .is_ok();
         }
-        Ok(())
+        Ok(purged_index)
     }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::{blockstore::tests::make_slot_entries_with_transactions, get_tmp_ledger_path};
+    use crate::{
+        blockstore::tests::make_slot_entries_with_transactions,
+        blockstore_db::{ColumnName, CompactionSchedule, CompactionWindow},
+        get_tmp_ledger_path,
+    };
 
     // check that all columns are either empty or start at `min_slot`
     fn test_all_empty_or_min(blockstore: &Blockstore, min_slot: Slot) {
@@ -455,6 +460,63 @@ pub mod tests {
         Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_compaction_schedule() {
+        assert_eq!("always".parse(), Ok(CompactionWindow::Always));
+        assert_eq!("never".parse(), Ok(CompactionWindow::Never));
+        let window: CompactionWindow = "2-6".parse().unwrap();
+        assert_eq!(window, CompactionWindow::Hours { start: 2, end: 6 });
+        assert!(window.is_open_at(2));
+        assert!(window.is_open_at(5));
+        assert!(!window.is_open_at(6));
+        assert!(!window.is_open_at(1));
+        let window: CompactionWindow = "22-24".parse().unwrap();
+        assert!(window.is_open_at(23));
+        assert!(!window.is_open_at(0));
+        assert!("22-4".parse::<CompactionWindow>().is_err());
+        assert!("4-4".parse::<CompactionWindow>().is_err());
+        assert!("2-25".parse::<CompactionWindow>().is_err());
+        assert!("2".parse::<CompactionWindow>().is_err());
+
+        let mut schedule = CompactionSchedule::default();
+        assert!(schedule
+            .set_window("no_such_column", CompactionWindow::Never)
+            .is_err());
+        schedule
+            .set_window(cf::TransactionStatus::NAME, CompactionWindow::Never)
+            .unwrap();
+        assert_eq!(
+            schedule.window(cf::TransactionStatus::NAME),
+            CompactionWindow::Never
+        );
+        assert_eq!(
+            schedule.window(cf::SlotMeta::NAME),
+            CompactionWindow::Always
+        );
+
+        let blockstore_path = get_tmp_ledger_path!();
+        let blockstore = Blockstore::open(&blockstore_path).unwrap();
+        let (shreds, _) = make_many_slot_entries(0, 10, 5);
+        blockstore.insert_shreds(shreds, None, false).unwrap();
+        blockstore.set_compaction_schedule(schedule);
+
+        // The transaction status columns are deferred, the others are compacted
+        assert!(!blockstore.compact_storage(0, 5).unwrap());
+        assert_eq!(blockstore.run_scheduled_compactions(), 0);
+        blockstore.set_compaction_schedule(CompactionSchedule::default());
+        assert_eq!(blockstore.run_scheduled_compactions(), 1);
+        assert_eq!(blockstore.run_scheduled_compactions(), 0);
+        assert!(blockstore.compact_storage(0, 5).unwrap());
+
+        blockstore
+            .compact_column(cf::TransactionStatus::NAME)
+            .unwrap();
+        assert!(blockstore.compact_column("no_such_column").is_err());
+
+        drop(blockstore);
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     fn test_purge_huge() {
         let blockstore_path = get_tmp_ledger_path!();
@@ -789,6 +851,52 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_purge_transaction_status_only() {
+        let blockstore_path = get_tmp_ledger_path!();
+        {
+            let blockstore = Blockstore::open(&blockstore_path).unwrap();
+            let (shreds, _) = make_many_slot_entries(0, 12, 1);
+            blockstore.insert_shreds(shreds, None, false).unwrap();
+            let slot = 10;
+            let signature = Signature::new(&[1u8; 64]);
+            blockstore
+                .write_transaction_status(
+                    slot,
+                    signature,
+                    vec![&Pubkey::new(&[2u8; 32])],
+                    vec![&Pubkey::new(&[3u8; 32])],
+                    &TransactionStatusMeta::default(),
+                )
+                .unwrap();
+
+            // The first purge freezes index 0, which holds slots up to 10
+            assert_eq!(blockstore.purge_transaction_status(20).unwrap(), None);
+            assert_eq!(blockstore.purge_transaction_status(9).unwrap(), None);
+            assert!(blockstore
+                .read_transaction_status((signature, slot))
+                .unwrap()
+                .is_some());
+            assert!(blockstore.transaction_status_size().is_ok());
+
+            assert_eq!(blockstore.purge_transaction_status(10).unwrap(), Some(0));
+            assert!(blockstore
+                .read_transaction_status((signature, slot))
+                .unwrap()
+                .is_none());
+            assert!(blockstore
+                .db
+                .iter::<cf::AddressSignatures>(IteratorMode::Start)
+                .unwrap()
+                .next()
+                .is_none());
+            // Shreds are retained
+            assert!(blockstore.meta(0).unwrap().is_some());
+            assert!(blockstore.meta(slot).unwrap().is_some());
+        }
+        Blockstore::destroy(&blockstore_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     #[allow(clippy::cognitive_complexity)]
     fn test_purge_transaction_status_exact() {
@@ -1,15 +1,17 @@
 use crate::blockstore_meta;
 use bincode::{deserialize, serialize};
 use byteorder::{BigEndian, ByteOrder};
+use chrono::{Timelike, Utc};
 use log::*;
 use prost::Message;
 pub use rocksdb::Direction as IteratorDirection;
 use rocksdb::{
     self, ColumnFamily, ColumnFamilyDescriptor, DBIterator, DBRawIterator, DBRecoveryMode,
-    IteratorMode as RocksIteratorMode, Options, WriteBatch as RWriteBatch, DB,
+    IteratorMode as RocksIteratorMode, Options, WriteBatch as RWriteBatch, WriteOptions, DB,
 };
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use solana_measure::measure::Measure;
 use solana_runtime::hardened_unpack::UnpackError;
 use solana_sdk::{
     clock::{Slot, UnixTimestamp},
@@ -18,7 +20,15 @@ use solana_sdk::{
 };
 use solana_storage_proto::convert::generated;
 use solana_transaction_status::TransactionStatusMeta;
-use std::{collections::HashMap, fs, marker::PhantomData, path::Path, sync::Arc};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, RwLock},
+};
 use thiserror::Error;
 
 const MAX_WRITE_BUFFER_SIZE: u64 = 256 * 1024 * 1024; // 256MB
@@ -75,6 +85,9 @@ pub enum BlockstoreError {
     NoVoteTimestampsInRange,
     ProtobufEncodeError(#[from] prost::EncodeError),
     ProtobufDecodeError(#[from] prost::DecodeError),
+    UnknownColumn(String),
+    ReadOnly,
+    PrimaryInUse,
 }
 pub type Result<T> = std::result::Result<T, BlockstoreError>;
 
@@ -156,6 +169,7 @@ pub enum AccessType {
     PrimaryOnly,
     PrimaryOnlyForMaintenance, // this indicates no compaction
     TryPrimaryThenSecondary,
+    Secondary, // read-only, alongside a running validator
 }
 
 #[derive(Debug, PartialEq)]
@@ -200,10 +214,245 @@ impl Into<DBRecoveryMode> for BlockstoreRecoveryMode {
     }
 }
 
+/// When a column family may be compacted. Compactions requested outside of the window are
+/// deferred until it opens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompactionWindow {
+    Always,
+    Never,
+    /// From hour `start` up to, but not including, hour `end` (UTC), with `start < end <= 24`
+    Hours {
+        start: u32,
+        end: u32,
+    },
+}
+
+impl Default for CompactionWindow {
+    fn default() -> Self {
+        CompactionWindow::Always
+    }
+}
+
+impl CompactionWindow {
+    pub fn is_open_at(&self, hour: u32) -> bool {
+        match *self {
+            CompactionWindow::Always => true,
+            CompactionWindow::Never => false,
+            CompactionWindow::Hours { start, end } => hour >= start && hour < end,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.is_open_at(Utc::now().hour())
+    }
+}
+
+impl FromStr for CompactionWindow {
+    type Err = String;
+
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "always" => Ok(CompactionWindow::Always),
+            "never" => Ok(CompactionWindow::Never),
+            _ => {
+                let mut hours = string
+                    .splitn(2, '-')
+                    .map(|hour| hour.parse::<u32>().ok().filter(|hour| *hour <= 24));
+                match (hours.next().flatten(), hours.next().flatten()) {
+                    (Some(start), Some(end)) if start < end => {
+                        Ok(CompactionWindow::Hours { start, end })
+                    }
+                    _ => Err(format!(
+                        "invalid compaction window {}: expected always, never or START-END hours \
+                         with START < END <= 24",
+                        string
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Per column family compaction windows. Columns without a window are always compacted.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CompactionSchedule {
+    windows: HashMap<&'static str, CompactionWindow>,
+}
+
+impl CompactionSchedule {
+    pub fn set_window(&mut self, column: &str, window: CompactionWindow) -> Result<()> {
+        let column = Rocks::columns()
+            .into_iter()
+            .find(|name| *name == column)
+            .ok_or_else(|| BlockstoreError::UnknownColumn(column.to_string()))?;
+        self.windows.insert(column, window);
+        Ok(())
+    }
+
+    pub fn window(&self, column: &str) -> CompactionWindow {
+        self.windows.get(column).copied().unwrap_or_default()
+    }
+}
+
+/// How batched writes to a column family use the write-ahead log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalMode {
+    /// Written to the WAL without waiting for it to reach the disk
+    Async,
+    /// Written to the WAL and synced to the disk before the write returns
+    Sync,
+    /// Not written to the WAL. Writes not yet flushed from the memtable are lost on a crash.
+    Disabled,
+}
+
+impl Default for WalMode {
+    fn default() -> Self {
+        WalMode::Async
+    }
+}
+
+impl FromStr for WalMode {
+    type Err = String;
+
+    fn from_str(string: &str) -> std::result::Result<Self, Self::Err> {
+        match string {
+            "async" => Ok(WalMode::Async),
+            "sync" => Ok(WalMode::Sync),
+            "disabled" => Ok(WalMode::Disabled),
+            _ => Err(format!(
+                "invalid WAL mode {}: expected async, sync or disabled",
+                string
+            )),
+        }
+    }
+}
+
+impl WalMode {
+    fn write_options(self) -> WriteOptions {
+        let mut write_options = WriteOptions::default();
+        match self {
+            WalMode::Async => (),
+            WalMode::Sync => write_options.set_sync(true),
+            WalMode::Disabled => write_options.disable_wal(true),
+        }
+        write_options
+    }
+
+    /// The mode of the two with the strongest durability
+    pub fn strictest(self, other: WalMode) -> WalMode {
+        match (self, other) {
+            (WalMode::Sync, _) | (_, WalMode::Sync) => WalMode::Sync,
+            (WalMode::Async, _) | (_, WalMode::Async) => WalMode::Async,
+            (WalMode::Disabled, WalMode::Disabled) => WalMode::Disabled,
+        }
+    }
+}
+
+/// Per column family WAL modes of batched writes. Columns without a mode use `WalMode::Async`.
+/// A batch is written atomically, with the strictest mode of the columns it writes.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WalPolicy {
+    modes: HashMap<&'static str, WalMode>,
+}
+
+impl WalPolicy {
+    pub fn set_mode(&mut self, column: &str, mode: WalMode) -> Result<()> {
+        let column = Rocks::columns()
+            .into_iter()
+            .find(|name| *name == column)
+            .ok_or_else(|| BlockstoreError::UnknownColumn(column.to_string()))?;
+        self.modes.insert(column, mode);
+        Ok(())
+    }
+
+    pub fn mode(&self, column: &str) -> WalMode {
+        self.modes.get(column).copied().unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Default)]
+struct CompactionScheduler {
+    schedule: RwLock<CompactionSchedule>,
+    // Key ranges of the compactions deferred until the column's window opens
+    pending: Mutex<HashMap<&'static str, (Vec<u8>, Vec<u8>)>>,
+}
+
+impl CompactionScheduler {
+    fn is_open(&self, column: &str) -> bool {
+        self.schedule.read().unwrap().window(column).is_open()
+    }
+
+    fn defer(&self, column: &'static str, from: Vec<u8>, to: Vec<u8>) {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.entry(column) {
+            Entry::Occupied(mut entry) => {
+                let (start, end) = entry.get_mut();
+                if from < *start {
+                    *start = from;
+                }
+                if to > *end {
+                    *end = to;
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((from, to));
+            }
+        }
+    }
+
+    fn take_ready(&self) -> Vec<(&'static str, (Vec<u8>, Vec<u8>))> {
+        let mut pending = self.pending.lock().unwrap();
+        let ready: Vec<_> = pending
+            .keys()
+            .filter(|column| self.is_open(column))
+            .copied()
+            .collect();
+        ready
+            .into_iter()
+            .filter_map(|column| pending.remove(column).map(|range| (column, range)))
+            .collect()
+    }
+}
+
+// The database is closed before its secondary directory, if any, is removed
 #[derive(Debug)]
-struct Rocks(rocksdb::DB, ActualAccessType);
+struct Rocks(
+    ManuallyDrop<rocksdb::DB>,
+    ActualAccessType,
+    // Directory of the secondary instance to remove once closed
+    Option<PathBuf>,
+);
+
+impl Drop for Rocks {
+    fn drop(&mut self) {
+        if self.is_primary_access() {
+            PRIMARY_PATHS
+                .lock()
+                .unwrap()
+                .remove(&canonical_path(self.0.path()));
+        }
+        // Safety: the database is not used past this point
+        unsafe { ManuallyDrop::drop(&mut self.0) };
+        if let Some(secondary_path) = &self.2 {
+            if let Err(err) = fs::remove_dir_all(secondary_path) {
+                warn!(
+                    "Unable to remove the secondary directory {:?}: {}",
+                    secondary_path, err
+                );
+            }
+        }
+    }
+}
 
 impl Rocks {
+    fn new_primary(db: rocksdb::DB) -> Self {
+        PRIMARY_PATHS
+            .lock()
+            .unwrap()
+            .insert(canonical_path(db.path()));
+        Rocks(ManuallyDrop::new(db), ActualAccessType::Primary, None)
+    }
+
     fn open(
         path: &Path,
         access_type: AccessType,
@@ -281,15 +530,18 @@ impl Rocks {
 
         // Open the database
         let db = match access_type {
-            AccessType::PrimaryOnly | AccessType::PrimaryOnlyForMaintenance => Rocks(
-                DB::open_cf_descriptors(&db_options, path, cfs.into_iter().map(|c| c.1))?,
-                ActualAccessType::Primary,
-            ),
+            AccessType::PrimaryOnly | AccessType::PrimaryOnlyForMaintenance => {
+                match DB::open_cf_descriptors(&db_options, path, cfs.into_iter().map(|c| c.1)) {
+                    Ok(db) => Rocks::new_primary(db),
+                    Err(_) if is_locked(path) => return Err(BlockstoreError::PrimaryInUse),
+                    Err(err) => return Err(err.into()),
+                }
+            }
             AccessType::TryPrimaryThenSecondary => {
                 let names: Vec<_> = cfs.iter().map(|c| c.0).collect();
 
                 match DB::open_cf_descriptors(&db_options, path, cfs.into_iter().map(|c| c.1)) {
-                    Ok(db) => Rocks(db, ActualAccessType::Primary),
+                    Ok(db) => Rocks::new_primary(db),
                     Err(err) => {
                         let secondary_path = path.join("solana-secondary");
 
@@ -297,22 +549,60 @@ impl Rocks {
                         warn!("Trying as secondary at : {:?}", secondary_path);
                         warn!("This active secondary db use may temporarily cause the performance of another db use (like by validator) to degrade");
 
-                        // This is needed according to https://github.com/facebook/rocksdb/wiki/Secondary-instance
-                        db_options.set_max_open_files(-1);
-
-                        Rocks(
-                            DB::open_cf_as_secondary(&db_options, path, &secondary_path, names)?,
-                            ActualAccessType::Secondary,
-                        )
+                        Self::open_secondary(db_options, path, &secondary_path, names, false)?
                     }
                 }
             }
+            AccessType::Secondary => {
+                let names: Vec<_> = cfs.iter().map(|c| c.0).collect();
+                // Each secondary instance needs its own directory for its info logs, so that
+                // several read-only processes can follow the same primary
+                let secondary_path = path.join(format!("solana-secondary-{}", std::process::id()));
+                info!("Opening as secondary at : {:?}", secondary_path);
+
+                Self::open_secondary(db_options, path, &secondary_path, names, true)?
+            }
         };
 
         Ok(db)
     }
 
-    fn columns(&self) -> Vec<&'static str> {
+    // The secondary directory is removed when the database is dropped if `remove_on_drop`
+    fn open_secondary(
+        mut db_options: Options,
+        path: &Path,
+        secondary_path: &Path,
+        names: Vec<&str>,
+        remove_on_drop: bool,
+    ) -> Result<Rocks> {
+        // This is needed according to https://github.com/facebook/rocksdb/wiki/Secondary-instance
+        db_options.set_max_open_files(-1);
+
+        Ok(Rocks(
+            ManuallyDrop::new(DB::open_cf_as_secondary(
+                &db_options,
+                path,
+                secondary_path,
+                names,
+            )?),
+            ActualAccessType::Secondary,
+            if remove_on_drop {
+                Some(secondary_path.to_path_buf())
+            } else {
+                None
+            },
+        ))
+    }
+
+    fn try_catch_up_with_primary(&self) -> Result<()> {
+        if self.is_primary_access() {
+            return Ok(());
+        }
+        self.0.try_catch_up_with_primary()?;
+        Ok(())
+    }
+
+    fn columns() -> Vec<&'static str> {
         use columns::{
             AddressSignatures, Blocktime, DeadSlots, DuplicateSlots, ErasureMeta, Index, Orphans,
             PerfSamples, Rewards, Root, ShredCode, ShredData, SlotMeta, TransactionStatus,
@@ -356,6 +646,7 @@ impl Rocks {
     }
 
     fn put_cf(&self, cf: &ColumnFamily, key: &[u8], value: &[u8]) -> Result<()> {
+        self.check_writable()?;
         self.0.put_cf(cf, key, value)?;
         Ok(())
     }
@@ -391,14 +682,72 @@ impl Rocks {
         Ok(RWriteBatch::default())
     }
 
-    fn write(&self, batch: RWriteBatch) -> Result<()> {
-        self.0.write(batch)?;
+    fn write(&self, batch: RWriteBatch, wal_mode: WalMode) -> Result<()> {
+        self.check_writable()?;
+        self.0.write_opt(batch, &wal_mode.write_options())?;
         Ok(())
     }
 
     fn is_primary_access(&self) -> bool {
         self.1 == ActualAccessType::Primary
     }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.is_primary_access() {
+            Ok(())
+        } else {
+            Err(BlockstoreError::ReadOnly)
+        }
+    }
+}
+
+lazy_static! {
+    // Databases opened as primary by this process. RocksDB tracks the lock files it holds apart
+    // from the file locks, which do not conflict within a process
+    static ref PRIMARY_PATHS: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+}
+
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+// RocksDB fails to open a database as primary if another instance, in this or another process,
+// holds the lock file of the database
+fn is_locked(path: &Path) -> bool {
+    if PRIMARY_PATHS
+        .lock()
+        .unwrap()
+        .contains(&canonical_path(path))
+    {
+        return true;
+    }
+    is_lock_file_held(&path.join("LOCK"))
+}
+
+// Whether another process holds the lock RocksDB takes on `lock_path`. Must not be called for a
+// lock file held by this process: closing the file would release its lock
+#[cfg(unix)]
+fn is_lock_file_held(lock_path: &Path) -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    let file = match fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(lock_path)
+    {
+        Ok(file) => file,
+        Err(_) => return false,
+    };
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    let result = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) };
+    result == 0 && lock.l_type != libc::F_UNLCK as libc::c_short
+}
+
+#[cfg(not(unix))]
+fn is_lock_file_held(_lock_path: &Path) -> bool {
+    false
 }
 
 pub trait Column {
@@ -717,6 +1066,8 @@ impl TypedColumn for columns::ErasureMeta {
 pub struct Database {
     backend: Arc<Rocks>,
     path: Arc<Path>,
+    compaction: Arc<CompactionScheduler>,
+    wal_policy: Arc<RwLock<WalPolicy>>,
 }
 
 #[derive(Debug, Clone)]
@@ -725,11 +1076,15 @@ where
     C: Column,
 {
     backend: Arc<Rocks>,
+    compaction: Arc<CompactionScheduler>,
     column: PhantomData<C>,
 }
 
 pub struct WriteBatch<'a> {
     write_batch: RWriteBatch,
+    // Strictest WAL mode of the columns written so far
+    wal_mode: Option<WalMode>,
+    wal_policy: WalPolicy,
     map: HashMap<&'static str, &'a ColumnFamily>,
 }
 
@@ -744,9 +1099,15 @@ impl Database {
         Ok(Database {
             backend,
             path: Arc::from(path),
+            compaction: Arc::default(),
+            wal_policy: Arc::default(),
         })
     }
 
+    pub fn columns() -> Vec<&'static str> {
+        Rocks::columns()
+    }
+
     pub fn destroy(path: &Path) -> Result<()> {
         Rocks::destroy(path)?;
 
@@ -792,6 +1153,7 @@ impl Database {
     {
         LedgerColumn {
             backend: Arc::clone(&self.backend),
+            compaction: Arc::clone(&self.compaction),
             column: PhantomData,
         }
     }
@@ -803,18 +1165,23 @@ impl Database {
 
     pub fn batch(&self) -> Result<WriteBatch> {
         let write_batch = self.backend.batch()?;
-        let map = self
-            .backend
-            .columns()
+        let map = Rocks::columns()
             .into_iter()
             .map(|desc| (desc, self.backend.cf_handle(desc)))
             .collect();
 
-        Ok(WriteBatch { write_batch, map })
+        Ok(WriteBatch {
+            write_batch,
+            wal_mode: None,
+            wal_policy: self.wal_policy(),
+            map,
+        })
     }
 
+    /// Writes the batch atomically, with the strictest WAL mode of the columns it writes
     pub fn write(&self, batch: WriteBatch) -> Result<()> {
-        self.backend.write(batch.write_batch)
+        self.backend
+            .write(batch.write_batch, batch.wal_mode.unwrap_or_default())
     }
 
     pub fn storage_size(&self) -> Result<u64> {
@@ -835,6 +1202,61 @@ impl Database {
     pub fn is_primary_access(&self) -> bool {
         self.backend.is_primary_access()
     }
+
+    /// Makes the writes of the primary instance visible to a secondary instance. A no-op for
+    /// the primary instance.
+    pub fn try_catch_up_with_primary(&self) -> Result<()> {
+        self.backend.try_catch_up_with_primary()
+    }
+
+    pub fn set_compaction_schedule(&self, schedule: CompactionSchedule) {
+        *self.compaction.schedule.write().unwrap() = schedule;
+    }
+
+    pub fn compaction_schedule(&self) -> CompactionSchedule {
+        self.compaction.schedule.read().unwrap().clone()
+    }
+
+    /// Sets the WAL modes of the batches created from now on
+    pub fn set_wal_policy(&self, policy: WalPolicy) {
+        *self.wal_policy.write().unwrap() = policy;
+    }
+
+    pub fn wal_policy(&self) -> WalPolicy {
+        self.wal_policy.read().unwrap().clone()
+    }
+
+    /// Runs the deferred compactions of the columns whose window is now open, returning the
+    /// number of columns compacted
+    pub fn run_scheduled_compactions(&self) -> usize {
+        let ready = self.compaction.take_ready();
+        for (column, (from, to)) in &ready {
+            info!("running deferred compaction of {}", column);
+            let mut compact_timer = Measure::start("compact_range");
+            self.backend
+                .0
+                .compact_range_cf(self.backend.cf_handle(column), Some(from), Some(to));
+            compact_timer.stop();
+            datapoint_info!(
+                "blockstore-scheduled-compact",
+                ("column", column.to_string(), String),
+                ("compact_range_us", compact_timer.as_us() as i64, i64),
+            );
+        }
+        ready.len()
+    }
+
+    /// Compacts the whole column family now, regardless of its compaction window
+    pub fn compact_column(&self, column: &str) -> Result<()> {
+        if !Rocks::columns().iter().any(|name| *name == column) {
+            return Err(BlockstoreError::UnknownColumn(column.to_string()));
+        }
+        let cf = self.backend.cf_handle(column);
+        self.backend
+            .0
+            .compact_range_cf::<&[u8], &[u8]>(cf, None, None);
+        Ok(())
+    }
 }
 
 impl<C> LedgerColumn<C>
@@ -888,14 +1310,20 @@ where
         Ok(end)
     }
 
+    /// Returns false if the compaction was deferred until the column's compaction window opens
     pub fn compact_range(&self, from: Slot, to: Slot) -> Result<bool>
     where
         C::Index: PartialOrd + Copy,
     {
-        let cf = self.handle();
-        let from = Some(C::key(C::as_index(from)));
-        let to = Some(C::key(C::as_index(to)));
-        self.backend.0.compact_range_cf(cf, from, to);
+        let from = C::key(C::as_index(from));
+        let to = C::key(C::as_index(to));
+        if !self.compaction.is_open(C::NAME) {
+            self.compaction.defer(C::NAME, from, to);
+            return Ok(false);
+        }
+        self.backend
+            .0
+            .compact_range_cf(self.handle(), Some(from), Some(to));
         Ok(true)
     }
 
@@ -904,6 +1332,16 @@ where
         self.backend.cf_handle(C::NAME)
     }
 
+    /// Estimated size of the live data of the column, which excludes purged data only once it
+    /// is compacted
+    pub fn live_data_size(&self) -> Result<u64> {
+        Ok(self
+            .backend
+            .0
+            .property_int_value_cf(self.handle(), "rocksdb.estimate-live-data-size")?
+            .unwrap_or(0))
+    }
+
     #[cfg(test)]
     pub fn is_empty(&self) -> Result<bool> {
         let mut iter = self.backend.raw_iterator_cf(self.handle())?;
@@ -974,13 +1412,14 @@ where
 
 impl<'a> WriteBatch<'a> {
     pub fn put_bytes<C: Column + ColumnName>(&mut self, key: C::Index, bytes: &[u8]) -> Result<()> {
-        self.write_batch
-            .put_cf(self.get_cf::<C>(), &C::key(key), bytes);
+        let cf = self.get_cf::<C>();
+        self.write_batch::<C>().put_cf(cf, &C::key(key), bytes);
         Ok(())
     }
 
     pub fn delete<C: Column + ColumnName>(&mut self, key: C::Index) -> Result<()> {
-        self.write_batch.delete_cf(self.get_cf::<C>(), &C::key(key));
+        let cf = self.get_cf::<C>();
+        self.write_batch::<C>().delete_cf(cf, &C::key(key));
         Ok(())
     }
 
@@ -990,23 +1429,38 @@ impl<'a> WriteBatch<'a> {
         value: &C::Type,
     ) -> Result<()> {
         let serialized_value = serialize(&value)?;
-        self.write_batch
-            .put_cf(self.get_cf::<C>(), &C::key(key), &serialized_value);
+        let cf = self.get_cf::<C>();
+        self.write_batch::<C>()
+            .put_cf(cf, &C::key(key), &serialized_value);
         Ok(())
     }
 
+    /// Size of the serialized batch, in bytes
+    pub fn size_in_bytes(&self) -> usize {
+        self.write_batch.size_in_bytes()
+    }
+
     #[inline]
     fn get_cf<C: Column + ColumnName>(&self) -> &'a ColumnFamily {
         self.map[C::NAME]
     }
 
-    pub fn delete_range_cf<C: Column>(
+    fn write_batch<C: Column + ColumnName>(&mut self) -> &mut RWriteBatch {
+        let wal_mode = self.wal_policy.mode(C::NAME);
+        self.wal_mode = Some(
+            self.wal_mode
+                .map_or(wal_mode, |batch_mode| batch_mode.strictest(wal_mode)),
+        );
+        &mut self.write_batch
+    }
+
+    pub fn delete_range_cf<C: Column + ColumnName>(
         &mut self,
         cf: &ColumnFamily,
         from: C::Index,
         to: C::Index,
     ) -> Result<()> {
-        self.write_batch
+        self.write_batch::<C>()
             .delete_range_cf(cf, C::key(from), C::key(to));
         Ok(())
     }
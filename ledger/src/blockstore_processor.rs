@@ -3,25 +3,33 @@ use crate::{
     blockstore::Blockstore,
     blockstore_db::BlockstoreError,
     blockstore_meta::SlotMeta,
-    entry::{create_ticks, Entry, EntrySlice, EntryVerificationStatus, VerifyRecyclers},
+    cost_model::{self, CostTracker},
+    entry::{self, create_ticks, Entry, EntrySlice, EntryVerificationStatus, VerifyRecyclers},
     leader_schedule_cache::LeaderScheduleCache,
+    replay_thread_pool::{self, ThreadPoolConfig},
+    transaction_trace::{self, TraceEvent},
+    write_trace::{self, TransactionWrites, WriteTraceSink},
 };
-use crossbeam_channel::Sender;
+use crossbeam_channel::{bounded, Sender};
 use itertools::Itertools;
 use log::*;
 use rand::{seq::SliceRandom, thread_rng};
 use rayon::{prelude::*, ThreadPool};
 use solana_measure::{measure::Measure, thread_mem_usage};
 use solana_metrics::{datapoint_error, inc_new_counter_debug};
-use solana_rayon_threadlimit::get_thread_count;
 use solana_runtime::{
+    accounts::AccountLockSet,
+    accounts_index::AccountIndexes,
+    append_vec::AppendVecBackend,
     bank::{
         Bank, InnerInstructionsList, TransactionBalancesSet, TransactionLogMessages,
         TransactionProcessResult, TransactionResults,
     },
-    bank_forks::BankForks,
+    bank_forks::{BankForks, CompressionType},
     bank_utils,
     commitment::CFG as COMMITMENT_CFG,
+    lock_contention::{self, LockConflict},
+    snapshot_utils::{self, SnapshotError},
     transaction_batch::TransactionBatch,
     transaction_utils::OrderedIterator,
     vote_sender_types::ReplayVoteSender,
@@ -29,6 +37,7 @@ use solana_runtime::{
 use solana_sdk::{
     account::Account,
     clock::{Slot, MAX_PROCESSING_AGE},
+    feature_set,
     genesis_config::GenesisConfig,
     hash::Hash,
     pubkey::Pubkey,
@@ -40,22 +49,24 @@ use solana_vote_program::vote_state::VoteState;
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    fs, mem,
+    path::{Path, PathBuf},
     result,
     sync::Arc,
     time::{Duration, Instant},
 };
 use thiserror::Error;
 
+toml_config::package_config! {
+    REPLAY_ENTRY_BATCH_SHREDS: u64,
+    REPLAY_ENTRY_BATCH_CHANNEL_SIZE: usize,
+}
+
 pub type BlockstoreProcessorResult =
     result::Result<(BankForks, LeaderScheduleCache), BlockstoreProcessorError>;
 
-thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> = RefCell::new(rayon::ThreadPoolBuilder::new()
-                    .num_threads(get_thread_count())
-                    .thread_name(|ix| format!("blockstore_processor_{}", ix))
-                    .build()
-                    .unwrap())
-);
+thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> =
+    RefCell::new(replay_thread_pool::replay_config().build("blockstore_processor")));
 
 fn first_err(results: &[Result<()>]) -> Result<()> {
     for r in results {
@@ -97,12 +108,27 @@ fn get_first_error(
     first_err
 }
 
+// Executes `batch`, returning the writes of its transactions in their order in the entry if
+// `trace_writes` is set
 fn execute_batch(
     batch: &TransactionBatch,
     bank: &Arc<Bank>,
+    trace_writes: bool,
     transaction_status_sender: Option<TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
-) -> Result<()> {
+) -> (Result<()>, Vec<TransactionWrites>) {
+    let pre_writes: Option<Vec<_>> = if trace_writes {
+        Some(
+            batch
+                .transactions()
+                .iter()
+                .map(|transaction| write_trace::load_pre_writes(bank, transaction))
+                .collect(),
+        )
+    } else {
+        None
+    };
+
     let (tx_results, balances, inner_instructions, transaction_logs) =
         batch.bank().load_execute_and_commit_transactions(
             batch,
@@ -113,6 +139,14 @@ fn execute_batch(
         );
 
     bank_utils::find_and_send_votes(batch.transactions(), &tx_results, replay_vote_sender);
+    transaction_trace::tracer().record_all(
+        batch
+            .transactions()
+            .iter()
+            .filter_map(|tx| tx.signatures.first()),
+        TraceEvent::Replayed,
+        Some(bank.slot()),
+    );
 
     let TransactionResults {
         fee_collection_results,
@@ -120,6 +154,28 @@ fn execute_batch(
         ..
     } = tx_results;
 
+    let transaction_writes = match pre_writes {
+        Some(pre_writes) => {
+            let mut results = vec![Ok(()); pre_writes.len()];
+            for ((result, _), (i, _)) in processing_results.iter().zip(OrderedIterator::new(
+                batch.transactions(),
+                batch.iteration_order(),
+            )) {
+                results[i] = result.clone();
+            }
+            batch
+                .transactions()
+                .iter()
+                .zip(pre_writes)
+                .zip(results)
+                .map(|((transaction, writes), result)| {
+                    write_trace::load_post_writes(bank, transaction, writes, result)
+                })
+                .collect()
+        }
+        None => vec![],
+    };
+
     if let Some(sender) = transaction_status_sender {
         send_transaction_status_batch(
             bank.clone(),
@@ -134,39 +190,204 @@ fn execute_batch(
     }
 
     let first_err = get_first_error(batch, fee_collection_results);
-    first_err.map(|(result, _)| result).unwrap_or(Ok(()))
+    (
+        first_err.map(|(result, _)| result).unwrap_or(Ok(())),
+        transaction_writes,
+    )
 }
 
 fn execute_batches(
     bank: &Arc<Bank>,
     batches: &[TransactionBatch],
     entry_callback: Option<&ProcessCallback>,
+    write_trace_sink: Option<&WriteTraceSink>,
     transaction_status_sender: Option<TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
-) -> Result<()> {
+) -> Vec<Result<()>> {
     inc_new_counter_debug!("bank-par_execute_entries-count", batches.len());
-    let results: Vec<Result<()>> = PAR_THREAD_POOL.with(|thread_pool| {
-        thread_pool.borrow().install(|| {
-            batches
-                .into_par_iter()
-                .map_with(transaction_status_sender, |sender, batch| {
-                    let result = execute_batch(batch, bank, sender.clone(), replay_vote_sender);
-                    if let Some(entry_callback) = entry_callback {
-                        entry_callback(bank);
-                    }
-                    result
-                })
-                .collect()
-        })
-    });
+    let (results, transaction_writes): (Vec<Result<()>>, Vec<_>) =
+        PAR_THREAD_POOL.with(|thread_pool| {
+            thread_pool.borrow().install(|| {
+                batches
+                    .into_par_iter()
+                    .map_with(transaction_status_sender, |sender, batch| {
+                        let result = execute_batch(
+                            batch,
+                            bank,
+                            write_trace_sink.is_some(),
+                            sender.clone(),
+                            replay_vote_sender,
+                        );
+                        if let Some(entry_callback) = entry_callback {
+                            entry_callback(bank);
+                        }
+                        result
+                    })
+                    .unzip()
+            })
+        });
+
+    // Batches are emitted in order once all are executed, so traces do not depend on scheduling
+    if let Some(write_trace_sink) = write_trace_sink {
+        for writes in transaction_writes.into_iter().flatten() {
+            write_trace_sink.emit(writes);
+        }
+    }
 
-    first_err(&results)
+    results
+}
+
+/// Accounts locked by a set of transactions
+#[derive(Default)]
+struct AccountLocks {
+    writable: HashSet<Pubkey>,
+    readonly: HashSet<Pubkey>,
+}
+
+impl AccountLocks {
+    fn conflicts_with(&self, lock_set: &AccountLockSet) -> bool {
+        lock_set
+            .writable
+            .iter()
+            .any(|key| self.writable.contains(key) || self.readonly.contains(key))
+            || lock_set
+                .readonly
+                .iter()
+                .any(|key| self.writable.contains(key))
+    }
+
+    fn insert(&mut self, lock_set: &AccountLockSet) {
+        self.writable.extend(lock_set.writable.iter().cloned());
+        self.readonly.extend(lock_set.readonly.iter().cloned());
+    }
+
+    fn clear(&mut self) {
+        self.writable.clear();
+        self.readonly.clear();
+    }
+}
+
+// Records the accounts of `lock_set` held in `held`, which defer its transaction
+fn record_lock_conflicts(lock_set: &AccountLockSet, held: &[&AccountLocks]) {
+    let profiler = lock_contention::profiler();
+    for key in &lock_set.writable {
+        if held.iter().any(|locks| locks.writable.contains(key)) {
+            profiler.record_conflict(key, LockConflict::WriteLocked);
+        } else if held.iter().any(|locks| locks.readonly.contains(key)) {
+            profiler.record_conflict(key, LockConflict::ReadLocked);
+        }
+    }
+    for key in &lock_set.readonly {
+        if held.iter().any(|locks| locks.writable.contains(key)) {
+            profiler.record_conflict(key, LockConflict::WriteLocked);
+        }
+    }
+}
+
+/// Schedules consecutive entries into rounds of entries that do not conflict with each other. An
+/// entry is deferred to a later round if one of its transactions conflicts with a transaction of
+/// the current round, or with a transaction of an entry deferred before it, so transactions
+/// locking the same account execute in their order in the entries, while later entries that do
+/// not conflict execute concurrently, ahead of the deferred ones. Entries are never split, and the
+/// entries after a failed one are dropped, see `cancel_after`, so no transaction of an entry
+/// conflicting with a failed earlier entry is executed.
+struct TransactionScheduler<'a> {
+    entries: &'a [Entry],
+    /// Indexes of the entries of the current round, in entry order
+    round: Vec<usize>,
+    round_locks: AccountLocks,
+    /// Indexes and account locks of the deferred entries, in entry order
+    deferred: Vec<(usize, Vec<AccountLockSet>)>,
+    deferred_locks: AccountLocks,
+}
+
+impl<'a> TransactionScheduler<'a> {
+    fn new(entries: &'a [Entry]) -> Self {
+        Self {
+            entries,
+            round: vec![],
+            round_locks: AccountLocks::default(),
+            deferred: vec![],
+            deferred_locks: AccountLocks::default(),
+        }
+    }
+
+    /// Schedules `entries[entry_index]`. Fails without scheduling it if a transaction can not be
+    /// locked, or if the entry conflicts with itself
+    fn schedule_entry(&mut self, entry_index: usize) -> Result<()> {
+        let lock_sets = self.entries[entry_index]
+            .transactions
+            .iter()
+            .map(AccountLockSet::try_from_transaction)
+            .collect::<Result<Vec<_>>>()?;
+        let mut entry_locks = AccountLocks::default();
+        for lock_set in &lock_sets {
+            if entry_locks.conflicts_with(lock_set) {
+                return Err(TransactionError::AccountInUse);
+            }
+            entry_locks.insert(lock_set);
+        }
+        self.schedule(entry_index, lock_sets);
+        Ok(())
+    }
+
+    fn schedule(&mut self, entry_index: usize, lock_sets: Vec<AccountLockSet>) {
+        if lock_sets.iter().any(|lock_set| {
+            self.round_locks.conflicts_with(lock_set)
+                || self.deferred_locks.conflicts_with(lock_set)
+        }) {
+            if lock_contention::profiler().is_enabled() {
+                for lock_set in &lock_sets {
+                    record_lock_conflicts(lock_set, &[&self.round_locks, &self.deferred_locks]);
+                }
+            }
+            for lock_set in &lock_sets {
+                self.deferred_locks.insert(lock_set);
+            }
+            self.deferred.push((entry_index, lock_sets));
+            return;
+        }
+        for lock_set in &lock_sets {
+            self.round_locks.insert(lock_set);
+        }
+        self.round.push(entry_index);
+    }
+
+    /// Takes the indexes and transactions of the entries of the current round, and schedules the
+    /// deferred entries into the next one
+    fn take_round(&mut self) -> Vec<(usize, &'a [Transaction])> {
+        let entries = self.entries;
+        let round = mem::take(&mut self.round)
+            .into_iter()
+            .map(|entry_index| (entry_index, &entries[entry_index].transactions[..]))
+            .collect();
+        self.round_locks.clear();
+        self.deferred_locks.clear();
+        for (entry_index, lock_sets) in mem::take(&mut self.deferred) {
+            self.schedule(entry_index, lock_sets);
+        }
+        round
+    }
+
+    /// Drops the scheduled entries after `entries[entry_index]`. The locks of the dropped entries
+    /// are released when the next round is taken
+    fn cancel_after(&mut self, entry_index: usize) {
+        self.round.retain(|i| *i < entry_index);
+        self.deferred.retain(|(i, _)| *i < entry_index);
+    }
+
+    /// The first deferred entry is always scheduled in the next round, so there is nothing left
+    /// to execute once the current round is empty
+    fn is_empty(&self) -> bool {
+        self.round.is_empty()
+    }
 }
 
 /// Process an ordered list of entries in parallel
-/// 1. In order lock accounts for each entry while the lock succeeds, up to a Tick entry
-/// 2. Process the locked group in parallel
-/// 3. Register the `Tick` if it's available
+/// 1. Schedule each entry, up to a Tick entry at a block boundary, into rounds of entries that do
+///    not conflict with each other, see `TransactionScheduler`
+/// 2. Process each round in parallel, in order
+/// 3. Register the `Tick`s if they're available
 /// 4. Update the leader scheduler, goto 1
 pub fn process_entries(
     bank: &Arc<Bank>,
@@ -180,37 +401,92 @@ pub fn process_entries(
         entries,
         randomize,
         None,
+        None,
         transaction_status_sender,
         replay_vote_sender,
     )
 }
 
+// Executes the entries scheduled in `scheduler`, one round at a time. Once an entry fails, the
+// entries after it are not executed, as when processing the entries one at a time
+fn execute_scheduled(
+    bank: &Arc<Bank>,
+    scheduler: &mut TransactionScheduler,
+    randomize: bool,
+    entry_callback: Option<&ProcessCallback>,
+    write_trace_sink: Option<&WriteTraceSink>,
+    transaction_status_sender: Option<TransactionStatusSender>,
+    replay_vote_sender: Option<&ReplayVoteSender>,
+) -> Result<()> {
+    let mut result = Ok(());
+    while !scheduler.is_empty() {
+        let round = scheduler.take_round();
+        let batches = round
+            .iter()
+            .map(|(_, transactions)| {
+                let iteration_order = if randomize {
+                    let mut iteration_order: Vec<usize> = (0..transactions.len()).collect();
+                    iteration_order.shuffle(&mut thread_rng());
+                    Some(iteration_order)
+                } else {
+                    None
+                };
+                let batch = bank.prepare_batch(transactions, iteration_order);
+                // Transactions of a round do not conflict, this only fails if the accounts are
+                // locked outside of replay
+                first_err(batch.lock_results())?;
+                Ok(batch)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let results = execute_batches(
+            bank,
+            &batches,
+            entry_callback,
+            write_trace_sink,
+            transaction_status_sender.clone(),
+            replay_vote_sender,
+        );
+        if let Some((entry_index, err)) = round
+            .iter()
+            .zip(results)
+            .find_map(|((entry_index, _), result)| result.err().map(|err| (*entry_index, err)))
+        {
+            // Only entries before the failed one are left, so a later failure is an earlier entry
+            scheduler.cancel_after(entry_index);
+            result = Err(err);
+        }
+    }
+    result
+}
+
 fn process_entries_with_callback(
     bank: &Arc<Bank>,
     entries: &[Entry],
     randomize: bool,
     entry_callback: Option<&ProcessCallback>,
+    write_trace_sink: Option<&WriteTraceSink>,
     transaction_status_sender: Option<TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
 ) -> Result<()> {
-    // accumulator for entries that can be processed in parallel
-    let mut batches = vec![];
+    // schedules the transactions of the entries that can be processed in parallel
+    let mut scheduler = TransactionScheduler::new(entries);
     let mut tick_hashes = vec![];
-    for entry in entries {
+    for (entry_index, entry) in entries.iter().enumerate() {
         if entry.is_tick() {
             // If it's a tick, save it for later
             tick_hashes.push(entry.hash);
             if bank.is_block_boundary(bank.tick_height() + tick_hashes.len() as u64) {
                 // If it's a tick that will cause a new blockhash to be created,
-                // execute the group and register the tick
-                execute_batches(
+                // execute the scheduled transactions and register the tick
+                execute_scheduled(
                     bank,
-                    &batches,
+                    &mut scheduler,
+                    randomize,
                     entry_callback,
+                    write_trace_sink,
                     transaction_status_sender.clone(),
                     replay_vote_sender,
                 )?;
-                batches.clear();
                 for hash in &tick_hashes {
                     bank.register_tick(hash);
                 }
@@ -218,62 +494,39 @@ fn process_entries_with_callback(
             }
             continue;
         }
-        // else loop on processing the entry
-        loop {
-            let iteration_order = if randomize {
-                let mut iteration_order: Vec<usize> = (0..entry.transactions.len()).collect();
-                iteration_order.shuffle(&mut thread_rng());
-                Some(iteration_order)
-            } else {
-                None
-            };
-
-            // try to lock the accounts
-            let batch = bank.prepare_batch(&entry.transactions, iteration_order);
-
-            let first_lock_err = first_err(batch.lock_results());
-
-            // if locking worked
-            if first_lock_err.is_ok() {
-                batches.push(batch);
-                // done with this entry
-                break;
-            }
-            // else we failed to lock, 2 possible reasons
-            if batches.is_empty() {
-                // An entry has account lock conflicts with *itself*, which should not happen
-                // if generated by a properly functioning leader
-                datapoint_error!(
-                    "validator_process_entry_error",
-                    (
-                        "error",
-                        format!(
-                            "Lock accounts error, entry conflicts with itself, txs: {:?}",
-                            entry.transactions
-                        ),
-                        String
-                    )
-                );
-                // bail
-                first_lock_err?;
-            } else {
-                // else we have an entry that conflicts with a prior entry
-                // execute the current queue and try to process this entry again
-                execute_batches(
-                    bank,
-                    &batches,
-                    entry_callback,
-                    transaction_status_sender.clone(),
-                    replay_vote_sender,
-                )?;
-                batches.clear();
-            }
+        if let Err(err) = scheduler.schedule_entry(entry_index) {
+            // An entry has account lock conflicts with *itself*, or invalid transactions, which
+            // should not happen if generated by a properly functioning leader
+            datapoint_error!(
+                "validator_process_entry_error",
+                (
+                    "error",
+                    format!(
+                        "Lock accounts error: {:?}, txs: {:?}",
+                        err, entry.transactions
+                    ),
+                    String
+                )
+            );
+            // execute the prior entries, as when processing the entries one at a time, and bail
+            execute_scheduled(
+                bank,
+                &mut scheduler,
+                randomize,
+                entry_callback,
+                write_trace_sink,
+                transaction_status_sender,
+                replay_vote_sender,
+            )?;
+            return Err(err);
         }
     }
-    execute_batches(
+    execute_scheduled(
         bank,
-        &batches,
+        &mut scheduler,
+        randomize,
         entry_callback,
+        write_trace_sink,
         transaction_status_sender,
         replay_vote_sender,
     )?;
@@ -305,11 +558,26 @@ pub enum BlockstoreProcessorError {
 
     #[error("root bank with mismatched capitalization at {0}")]
     RootBankWithMismatchedCapitalization(Slot),
+
+    #[error("failed to write checkpoint")]
+    FailedToWriteCheckpoint(#[from] SnapshotError),
 }
 
 /// Callback for accessing bank state while processing the blockstore
 pub type ProcessCallback = Arc<dyn Fn(&Bank) + Sync + Send>;
 
+/// Sent by `process_blockstore` for every slot replayed from the blockstore
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayProgress {
+    pub slot: Slot,
+    pub num_entries: usize,
+    pub num_txs: usize,
+    /// Time spent replaying the slot
+    pub elapsed: Duration,
+}
+
+pub type ReplayProgressSender = Sender<ReplayProgress>;
+
 #[derive(Default, Clone)]
 pub struct ProcessOptions {
     pub poh_verify: bool,
@@ -317,9 +585,23 @@ pub struct ProcessOptions {
     pub dev_halt_at_slot: Option<Slot>,
     pub entry_callback: Option<ProcessCallback>,
     pub override_num_threads: Option<usize>,
+    /// Thread pools executing transactions, and verifying entries. Unlike `override_num_threads`,
+    /// these also apply to the replay stage once the ledger is processed
+    pub replay_thread_pool: Option<ThreadPoolConfig>,
+    pub poh_verify_thread_pool: Option<ThreadPoolConfig>,
     pub new_hard_forks: Option<Vec<Slot>>,
     pub frozen_accounts: Vec<Pubkey>,
     pub debug_keys: Option<Arc<HashSet<Pubkey>>>,
+    pub account_indexes: AccountIndexes,
+    pub append_vec_backend: AppendVecBackend,
+    pub replay_progress_sender: Option<ReplayProgressSender>,
+    /// Directory holding checkpoints. When replay halts at `dev_halt_at_slot`, a snapshot archive
+    /// of the root bank is written there, and `bank_forks_utils::load` resumes from the latest
+    /// checkpoint in it, unless a snapshot of a higher slot is available
+    pub checkpoint_path: Option<PathBuf>,
+    /// Receives the state of the accounts each replayed transaction may write, before and after
+    /// it executes
+    pub trace_writes: Option<WriteTraceSink>,
 }
 
 pub fn process_blockstore(
@@ -328,14 +610,7 @@ pub fn process_blockstore(
     account_paths: Vec<PathBuf>,
     opts: ProcessOptions,
 ) -> BlockstoreProcessorResult {
-    if let Some(num_threads) = opts.override_num_threads {
-        PAR_THREAD_POOL.with(|pool| {
-            *pool.borrow_mut() = rayon::ThreadPoolBuilder::new()
-                .num_threads(num_threads)
-                .build()
-                .unwrap()
-        });
-    }
+    configure_thread_pools(&opts);
 
     // Setup bank for slot 0
     let bank0 = Bank::new_with_paths(
@@ -344,6 +619,8 @@ pub fn process_blockstore(
         &opts.frozen_accounts,
         opts.debug_keys.clone(),
         Some(&crate::builtins::get(genesis_config.cluster_type)),
+        opts.account_indexes.clone(),
+        opts.append_vec_backend.clone(),
     );
     let bank0 = Arc::new(bank0);
     info!("processing ledger for slot 0...");
@@ -352,6 +629,49 @@ pub fn process_blockstore(
     do_process_blockstore_from_root(blockstore, bank0, &opts, &recyclers, None)
 }
 
+// Writes a snapshot archive of the root bank replay halted with. Only a rooted bank is
+// checkpointed, as the checkpoint is resumed from as a root
+fn write_checkpoint(
+    bank: &Bank,
+    checkpoint_path: &Path,
+) -> result::Result<(), BlockstoreProcessorError> {
+    fs::create_dir_all(checkpoint_path).map_err(SnapshotError::from)?;
+    let archive_path = snapshot_utils::bank_to_snapshot_archive(
+        checkpoint_path,
+        bank,
+        None,
+        checkpoint_path,
+        CompressionType::Zstd,
+    )?;
+    info!(
+        "checkpoint of slot {} written to {:?}",
+        bank.slot(),
+        archive_path
+    );
+    Ok(())
+}
+
+// Applies the thread pool options to the pools of the current thread, and to the pools the
+// replay stage builds later
+fn configure_thread_pools(opts: &ProcessOptions) {
+    if let Some(config) = &opts.replay_thread_pool {
+        replay_thread_pool::set_replay_config(config.clone());
+        PAR_THREAD_POOL.with(|pool| *pool.borrow_mut() = config.build("blockstore_processor"));
+    }
+    if let Some(config) = &opts.poh_verify_thread_pool {
+        replay_thread_pool::set_poh_verify_config(config.clone());
+        entry::rebuild_thread_pool();
+    }
+    if let Some(num_threads) = opts.override_num_threads {
+        PAR_THREAD_POOL.with(|pool| {
+            *pool.borrow_mut() = rayon::ThreadPoolBuilder::new()
+                .num_threads(num_threads)
+                .build()
+                .unwrap()
+        });
+    }
+}
+
 // Process blockstore from a known root bank
 pub(crate) fn process_blockstore_from_root(
     blockstore: &Blockstore,
@@ -360,6 +680,7 @@ pub(crate) fn process_blockstore_from_root(
     recyclers: &VerifyRecyclers,
     transaction_status_sender: Option<TransactionStatusSender>,
 ) -> BlockstoreProcessorResult {
+    configure_thread_pools(opts);
     do_process_blockstore_from_root(
         blockstore,
         Arc::new(bank),
@@ -407,7 +728,10 @@ fn do_process_blockstore_from_root(
             .set_roots(&[start_slot])
             .expect("Couldn't set root slot on startup");
     } else if !blockstore.is_root(start_slot) {
-        panic!("starting slot isn't root and can't update due to being secondary blockstore access: {}", start_slot);
+        warn!(
+            "starting slot {} isn't root and can't be marked as root due to read-only blockstore access",
+            start_slot
+        );
     }
 
     if let Ok(metas) = blockstore.slot_meta_iterator(start_slot) {
@@ -451,6 +775,17 @@ fn do_process_blockstore_from_root(
     }
     let bank_forks = BankForks::new_from_banks(&initial_forks, root);
 
+    if let (Some(dev_halt_at_slot), Some(checkpoint_path)) =
+        (opts.dev_halt_at_slot, opts.checkpoint_path.as_ref())
+    {
+        if initial_forks
+            .iter()
+            .any(|bank| bank.slot() >= dev_halt_at_slot)
+        {
+            write_checkpoint(bank_forks.root_bank(), checkpoint_path)?;
+        }
+    }
+
     info!(
         "ledger processed in {}ms. {} MB allocated. root={}, {} fork{} at {}, with {} frozen bank{}",
         duration_as_ms(&now.elapsed()),
@@ -526,6 +861,22 @@ pub fn verify_ticks(
     Ok(())
 }
 
+// Adds the cost of the transactions of `entries` to the cost of their block
+fn track_block_cost(
+    cost_tracker: &mut CostTracker,
+    entries: &[Entry],
+) -> std::result::Result<(), BlockError> {
+    for transaction in entries.iter().flat_map(|entry| &entry.transactions) {
+        cost_tracker
+            .try_add(&cost_model::transaction_cost(transaction))
+            .map_err(|err| {
+                debug!("{:?}: {}", transaction.signatures.first(), err);
+                BlockError::ExceedsCostLimit
+            })?;
+    }
+    Ok(())
+}
+
 fn confirm_full_slot(
     blockstore: &Blockstore,
     bank: &Arc<Bank>,
@@ -546,6 +897,7 @@ fn confirm_full_slot(
         transaction_status_sender,
         replay_vote_sender,
         opts.entry_callback.as_ref(),
+        opts.trace_writes.as_ref(),
         recyclers,
     )?;
 
@@ -587,6 +939,7 @@ pub struct ConfirmationProgress {
     pub num_shreds: u64,
     pub num_entries: usize,
     pub num_txs: usize,
+    pub cost_tracker: CostTracker,
 }
 
 impl ConfirmationProgress {
@@ -598,6 +951,13 @@ impl ConfirmationProgress {
     }
 }
 
+/// Replays the entries of `bank`'s slot that are in the blockstore but were not replayed yet.
+///
+/// Entries are replayed as a pipeline: a fetch thread loads batches of up to
+/// `REPLAY_ENTRY_BATCH_SHREDS` shreds from the blockstore, while earlier batches are verified and
+/// executed. Only a bounded number of fetched batches are buffered, so large slots are never
+/// held in memory at once.
+#[allow(clippy::too_many_arguments)]
 pub fn confirm_slot(
     blockstore: &Blockstore,
     bank: &Arc<Bank>,
@@ -607,24 +967,105 @@ pub fn confirm_slot(
     transaction_status_sender: Option<TransactionStatusSender>,
     replay_vote_sender: Option<&ReplayVoteSender>,
     entry_callback: Option<&ProcessCallback>,
+    write_trace_sink: Option<&WriteTraceSink>,
     recyclers: &VerifyRecyclers,
 ) -> result::Result<(), BlockstoreProcessorError> {
     let slot = bank.slot();
+    let start_index = progress.num_shreds;
+    let (sender, receiver) = bounded(CFG.REPLAY_ENTRY_BATCH_CHANNEL_SIZE);
+
+    crossbeam_utils::thread::scope(|scope| {
+        scope
+            .builder()
+            .name("solana-replay-fetch".to_string())
+            .spawn(move |_| fetch_entry_batches(blockstore, slot, start_index, sender))
+            .unwrap();
+
+        // Dropping `receiver` on an early return stops the fetch thread
+        for (load_elapsed_us, load_result) in receiver {
+            let (entries, num_shreds, slot_full) = match load_result {
+                Ok(batch) => {
+                    timing.fetch_elapsed += load_elapsed_us;
+                    batch
+                }
+                Err(err) => {
+                    timing.fetch_fail_elapsed += load_elapsed_us;
+                    return Err(BlockstoreProcessorError::FailedToLoadEntries(err));
+                }
+            };
+            confirm_entries(
+                bank,
+                entries,
+                num_shreds,
+                slot_full,
+                timing,
+                progress,
+                skip_verification,
+                transaction_status_sender.clone(),
+                replay_vote_sender,
+                entry_callback,
+                write_trace_sink,
+                recyclers,
+            )?;
+        }
+        Ok(())
+    })
+    .unwrap()
+}
 
-    let (entries, num_shreds, slot_full) = {
+type EntryBatch = (
+    u64,
+    result::Result<(Vec<Entry>, u64, bool), BlockstoreError>,
+);
+
+// Sends batches of entries of `slot`, starting at shred `start_index`, along with the time it took
+// to load each batch, until the loaded shreds are exhausted or loading fails
+fn fetch_entry_batches(
+    blockstore: &Blockstore,
+    slot: Slot,
+    mut start_index: u64,
+    sender: Sender<EntryBatch>,
+) {
+    loop {
         let mut load_elapsed = Measure::start("load_elapsed");
-        let load_result = blockstore
-            .get_slot_entries_with_shred_info(slot, progress.num_shreds, false)
-            .map_err(BlockstoreProcessorError::FailedToLoadEntries);
+        let load_result = blockstore.get_slot_entries_with_shred_info_limited(
+            slot,
+            start_index,
+            CFG.REPLAY_ENTRY_BATCH_SHREDS,
+            false,
+        );
         load_elapsed.stop();
-        if load_result.is_err() {
-            timing.fetch_fail_elapsed += load_elapsed.as_us();
-        } else {
-            timing.fetch_elapsed += load_elapsed.as_us();
+        let done = match &load_result {
+            // Nothing more to replay until more shreds arrive
+            Ok((_, 0, _)) => break,
+            Ok((_, num_shreds, slot_full)) => {
+                start_index += num_shreds;
+                *slot_full
+            }
+            Err(_) => true,
+        };
+        if sender.send((load_elapsed.as_us(), load_result)).is_err() || done {
+            break;
         }
-        load_result
-    }?;
+    }
+}
 
+#[allow(clippy::too_many_arguments)]
+fn confirm_entries(
+    bank: &Arc<Bank>,
+    entries: Vec<Entry>,
+    num_shreds: u64,
+    slot_full: bool,
+    timing: &mut ConfirmationTiming,
+    progress: &mut ConfirmationProgress,
+    skip_verification: bool,
+    transaction_status_sender: Option<TransactionStatusSender>,
+    replay_vote_sender: Option<&ReplayVoteSender>,
+    entry_callback: Option<&ProcessCallback>,
+    write_trace_sink: Option<&WriteTraceSink>,
+    recyclers: &VerifyRecyclers,
+) -> result::Result<(), BlockstoreProcessorError> {
+    let slot = bank.slot();
     let num_entries = entries.len();
     let num_txs = entries.iter().map(|e| e.transactions.len()).sum::<usize>();
     trace!(
@@ -652,6 +1093,20 @@ pub fn confirm_slot(
             );
             err
         })?;
+        if bank
+            .feature_set
+            .is_active(&feature_set::block_cost_limits::id())
+        {
+            track_block_cost(&mut progress.cost_tracker, &entries).map_err(|err| {
+                warn!(
+                    "{:#?}, slot: {}, block cost: {}",
+                    err,
+                    slot,
+                    progress.cost_tracker.block_cost()
+                );
+                err
+            })?;
+        }
     }
 
     let verifier = if !skip_verification {
@@ -676,6 +1131,7 @@ pub fn confirm_slot(
         &entries,
         true,
         entry_callback,
+        write_trace_sink,
         transaction_status_sender,
         replay_vote_sender,
     )
@@ -843,6 +1299,7 @@ fn load_frozen_forks(
         let initial_allocation = allocated.get();
 
         let mut progress = ConfirmationProgress::new(last_entry_hash);
+        let replay_start = Instant::now();
 
         if process_single_slot(
             blockstore,
@@ -858,6 +1315,15 @@ fn load_frozen_forks(
             continue;
         }
         txs += progress.num_txs;
+        if let Some(sender) = &opts.replay_progress_sender {
+            // The receiver is only informed, replay goes on without it
+            let _ = sender.send(ReplayProgress {
+                slot,
+                num_entries: progress.num_entries,
+                num_txs: progress.num_txs,
+                elapsed: replay_start.elapsed(),
+            });
+        }
 
         // Block must be frozen by this point, otherwise `process_single_slot` would
         // have errored above
@@ -1007,7 +1473,16 @@ fn process_single_slot(
 ) -> result::Result<(), BlockstoreProcessorError> {
     // Mark corrupt slots as dead so validators don't replay this slot and
     // see DuplicateSignature errors later in ReplayStage
-    confirm_full_slot(blockstore, bank, opts, recyclers, progress, transaction_status_sender, replay_vote_sender).map_err(|err| {
+    confirm_full_slot(
+        blockstore,
+        bank,
+        opts,
+        recyclers,
+        progress,
+        transaction_status_sender,
+        replay_vote_sender,
+    )
+    .map_err(|err| {
         let slot = bank.slot();
         warn!("slot {} failed to verify: {}", slot, err);
         if blockstore.is_primary_access() {
@@ -1015,7 +1490,10 @@ fn process_single_slot(
                 .set_dead_slot(slot)
                 .expect("Failed to mark slot as dead in blockstore");
         } else if !blockstore.is_dead(slot) {
-            panic!("Failed slot isn't dead and can't update due to being secondary blockstore access: {}", slot);
+            warn!(
+                "failed slot {} can't be marked as dead due to read-only blockstore access",
+                slot
+            );
         }
         err
     })?;
@@ -1104,20 +1582,22 @@ pub mod tests {
         genesis_utils::{
             create_genesis_config, create_genesis_config_with_leader, GenesisConfigInfo,
         },
+        shred::Shredder,
     };
     use crossbeam_channel::unbounded;
     use matches::assert_matches;
-    use rand::{thread_rng, Rng};
+    use rand::{seq::SliceRandom, thread_rng, Rng};
     use solana_runtime::genesis_utils::{
         self, create_genesis_config_with_vote_accounts, ValidatorVoteKeypairs,
     };
     use solana_sdk::{
         epoch_schedule::EpochSchedule,
         hash::Hash,
+        instruction::{AccountMeta, Instruction},
         pubkey::Pubkey,
         signature::{Keypair, Signer},
-        system_instruction::SystemError,
-        system_transaction,
+        system_instruction::{SystemError, SystemInstruction},
+        system_program, system_transaction,
         transaction::{Transaction, TransactionError},
     };
     use solana_vote_program::{
@@ -1883,6 +2363,62 @@ pub mod tests {
         assert_eq!(bank.last_blockhash(), last_blockhash);
     }
 
+    #[test]
+    fn test_process_ledger_in_entry_batches() {
+        solana_logger::setup();
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10_000);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, mut last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockhash = last_entry_hash;
+
+        // Enough transactions that slot 1 spans several batches of shreds
+        let num_txs = 1_000;
+        let mut entries: Vec<_> = (0..num_txs)
+            .map(|_| {
+                let tx = system_transaction::transfer(
+                    &mint_keypair,
+                    &solana_sdk::pubkey::new_rand(),
+                    1,
+                    blockhash,
+                );
+                next_entry_mut(&mut last_entry_hash, 1, vec![tx])
+            })
+            .collect();
+        entries.extend(create_ticks(ticks_per_slot, 0, last_entry_hash));
+
+        // Shred the slot into several data blocks, so that it can be fetched in batches
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let shredder = Shredder::new(1, 0, 0.0, Arc::new(Keypair::new()), 0, 0).unwrap();
+        let block_size = 128;
+        let num_blocks = entries.chunks(block_size).len();
+        let mut shreds = vec![];
+        for (i, block) in entries.chunks(block_size).enumerate() {
+            let is_last_in_slot = i == num_blocks - 1;
+            let (mut data_shreds, _, _) =
+                shredder.entries_to_shreds(block, is_last_in_slot, shreds.len() as u32);
+            shreds.append(&mut data_shreds);
+        }
+        assert!(shreds.len() as u64 > CFG.REPLAY_ENTRY_BATCH_SHREDS);
+        blockstore.insert_shreds(shreds, None, false).unwrap();
+
+        let opts = ProcessOptions {
+            poh_verify: true,
+            ..ProcessOptions::default()
+        };
+        let (bank_forks, _leader_schedule) =
+            process_blockstore(&genesis_config, &blockstore, Vec::new(), opts).unwrap();
+
+        assert_eq!(frozen_bank_slots(&bank_forks), vec![0, 1]);
+        let bank = bank_forks[1].clone();
+        assert_eq!(bank.transaction_count(), num_txs);
+        assert_eq!(bank.tick_height(), 2 * ticks_per_slot);
+        assert_eq!(bank.last_blockhash(), entries.last().unwrap().hash);
+    }
+
     #[test]
     fn test_process_ledger_with_one_tick_per_slot() {
         let GenesisConfigInfo {
@@ -1920,6 +2456,134 @@ pub mod tests {
         });
     }
 
+    #[test]
+    fn test_process_ledger_options_thread_pools() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(123);
+        let (ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_config);
+
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let config = ThreadPoolConfig {
+            num_threads: Some(2),
+            cores: vec![],
+        };
+        let opts = ProcessOptions {
+            replay_thread_pool: Some(config.clone()),
+            ..ProcessOptions::default()
+        };
+        process_blockstore(&genesis_config, &blockstore, Vec::new(), opts).unwrap();
+        PAR_THREAD_POOL.with(|pool| {
+            assert_eq!(pool.borrow().current_num_threads(), 2);
+        });
+        // Pools built later by other threads use the same config
+        assert_eq!(replay_thread_pool::replay_config(), config);
+        assert_eq!(
+            replay_thread_pool::poh_verify_config(),
+            ThreadPoolConfig::default()
+        );
+        replay_thread_pool::set_replay_config(ThreadPoolConfig::default());
+    }
+
+    #[test]
+    fn test_process_ledger_replay_progress() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(123);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 1, 0, blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 2, 1, blockhash);
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let opts = ProcessOptions {
+            replay_progress_sender: Some(sender),
+            ..ProcessOptions::default()
+        };
+        process_blockstore(&genesis_config, &blockstore, Vec::new(), opts).unwrap();
+        let progress: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            progress
+                .iter()
+                .map(|progress| progress.slot)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+        for progress in progress {
+            assert_eq!(progress.num_entries, ticks_per_slot as usize);
+            assert_eq!(progress.num_txs, 0);
+        }
+    }
+
+    #[test]
+    fn test_track_block_cost() {
+        let keypair = Keypair::new();
+        let tx = |to: &Pubkey| system_transaction::transfer(&keypair, to, 1, Hash::default());
+        let to = solana_sdk::pubkey::new_rand();
+        let tx_cost = cost_model::transaction_cost(&tx(&to)).sum();
+        let entry_1 = next_entry(&Hash::default(), 1, vec![tx(&to), tx(&to)]);
+        let entry_2 = next_entry(&entry_1.hash, 1, vec![tx(&to)]);
+
+        // Every transaction writes the payer account
+        let mut cost_tracker = CostTracker::new(10 * tx_cost, 2 * tx_cost);
+        assert_eq!(track_block_cost(&mut cost_tracker, &[entry_1]), Ok(()));
+        assert_eq!(
+            track_block_cost(&mut cost_tracker, &[entry_2]),
+            Err(BlockError::ExceedsCostLimit)
+        );
+    }
+
+    #[test]
+    fn test_process_ledger_halt_checkpoint() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(123);
+        let ticks_per_slot = genesis_config.ticks_per_slot;
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore = Blockstore::open(&ledger_path).unwrap();
+        let blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 1, 0, blockhash);
+        let blockhash =
+            fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 2, 1, blockhash);
+        fill_blockstore_slot_with_ticks(&blockstore, ticks_per_slot, 3, 2, blockhash);
+        blockstore.set_roots(&[0, 1]).unwrap();
+
+        let checkpoint_dir = tempfile::TempDir::new().unwrap();
+        let opts = ProcessOptions {
+            dev_halt_at_slot: Some(2),
+            checkpoint_path: Some(checkpoint_dir.path().to_path_buf()),
+            ..ProcessOptions::default()
+        };
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let account_paths = vec![accounts_dir.path().to_path_buf()];
+        let (bank_forks, _leader_schedule) =
+            process_blockstore(&genesis_config, &blockstore, account_paths, opts.clone()).unwrap();
+        let root_bank_hash = bank_forks[1].hash();
+        assert_eq!(bank_forks.working_bank().slot(), 2);
+        assert!(bank_forks.get(3).is_none());
+        // The root is checkpointed, not the unrooted bank replay halted at
+        let (_archive, (slot, _hash, _compression)) =
+            snapshot_utils::get_highest_snapshot_archive_path(checkpoint_dir.path()).unwrap();
+        assert_eq!(slot, 1);
+        drop(bank_forks);
+
+        // Replay resumes from the checkpointed root
+        let opts = ProcessOptions {
+            dev_halt_at_slot: None,
+            ..opts
+        };
+        let accounts_dir = tempfile::TempDir::new().unwrap();
+        let account_paths = vec![accounts_dir.path().to_path_buf()];
+        let (bank_forks, _leader_schedule, snapshot_hash) = crate::bank_forks_utils::load(
+            &genesis_config,
+            &blockstore,
+            account_paths,
+            None,
+            opts,
+            None,
+        )
+        .unwrap();
+        assert_eq!(snapshot_hash.map(|(slot, _hash)| slot), Some(1));
+        assert_eq!(bank_forks[1].hash(), root_bank_hash);
+        assert_eq!(bank_forks.working_bank().slot(), 3);
+    }
+
     #[test]
     fn test_process_ledger_options_full_leader_cache() {
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(123);
@@ -1995,6 +2659,80 @@ pub mod tests {
         assert_eq!(*callback_counter.write().unwrap(), 2);
     }
 
+    #[test]
+    fn test_process_ledger_options_trace_writes() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(100);
+        let (ledger_path, last_entry_hash) = create_new_tmp_ledger!(&genesis_config);
+        let blockstore =
+            Blockstore::open(&ledger_path).expect("Expected to successfully open database ledger");
+        let blockhash = genesis_config.hash();
+        let keypairs = [Keypair::new(), Keypair::new(), Keypair::new()];
+
+        // Transactions of an entry which do not conflict are traced in the order of the entry
+        let transactions: Vec<_> = keypairs
+            .iter()
+            .map(|keypair| {
+                system_transaction::transfer(&mint_keypair, &keypair.pubkey(), 1, blockhash)
+            })
+            .collect();
+        let entry_1 = next_entry(&last_entry_hash, 1, vec![transactions[0].clone()]);
+        let entry_2 = next_entry(&entry_1.hash, 1, transactions[1..].to_vec());
+        let mut entries = vec![entry_1, entry_2];
+        entries.extend(create_ticks(
+            genesis_config.ticks_per_slot,
+            0,
+            last_entry_hash,
+        ));
+        blockstore
+            .write_entries(
+                1,
+                0,
+                0,
+                genesis_config.ticks_per_slot,
+                None,
+                true,
+                &Arc::new(Keypair::new()),
+                entries,
+                0,
+            )
+            .unwrap();
+
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let opts = ProcessOptions {
+            trace_writes: Some(WriteTraceSink::Channel(sender)),
+            ..ProcessOptions::default()
+        };
+        process_blockstore(&genesis_config, &blockstore, Vec::new(), opts).unwrap();
+
+        let traces: Vec<_> = receiver.try_iter().collect();
+        assert_eq!(
+            traces
+                .iter()
+                .map(|writes| writes.signature)
+                .collect::<Vec<_>>(),
+            transactions
+                .iter()
+                .map(|transaction| transaction.signatures[0])
+                .collect::<Vec<_>>()
+        );
+        let lamports = |account: &Option<Account>| account.as_ref().map(|a| a.lamports);
+        let mut mint_lamports = 100;
+        for (writes, keypair) in traces.iter().zip(keypairs.iter()) {
+            assert_eq!(writes.slot, 1);
+            assert_eq!(writes.result, Ok(()));
+            assert_eq!(writes.writes[0].pubkey, mint_keypair.pubkey());
+            assert_eq!(lamports(&writes.writes[0].pre), Some(mint_lamports));
+            mint_lamports -= 1;
+            assert_eq!(lamports(&writes.writes[0].post), Some(mint_lamports));
+            assert_eq!(writes.writes[1].pubkey, keypair.pubkey());
+            assert_eq!(writes.writes[1].pre, None);
+        }
+    }
+
     #[test]
     fn test_process_entries_tick() {
         let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(1000);
@@ -2291,6 +3029,300 @@ pub mod tests {
         assert_eq!(bank.get_balance(&keypair3.pubkey()), 2);
     }
 
+    #[test]
+    fn test_transaction_scheduler() {
+        let keypairs: Vec<_> = (0..6).map(|_| Keypair::new()).collect();
+        let transfer = |from: usize, to: usize| {
+            system_transaction::transfer(
+                &keypairs[from],
+                &keypairs[to].pubkey(),
+                1,
+                Hash::default(),
+            )
+        };
+        let entries = vec![
+            next_entry(&Hash::default(), 1, vec![transfer(0, 1)]),
+            // The first transaction conflicts with the first entry, so the whole entry is deferred
+            next_entry(&Hash::default(), 1, vec![transfer(1, 2), transfer(3, 4)]),
+            // Does not conflict with the first round, but with the deferred entry
+            next_entry(&Hash::default(), 1, vec![transfer(2, 5)]),
+        ];
+        let mut scheduler = TransactionScheduler::new(&entries);
+        for entry_index in 0..entries.len() {
+            scheduler.schedule_entry(entry_index).unwrap();
+        }
+
+        for entry_index in 0..entries.len() {
+            assert_eq!(
+                scheduler.take_round(),
+                vec![(entry_index, &entries[entry_index].transactions[..])]
+            );
+        }
+        assert!(scheduler.is_empty());
+        assert!(scheduler.take_round().is_empty());
+
+        // An entry conflicting with itself is not scheduled
+        let entries = vec![next_entry(
+            &Hash::default(),
+            1,
+            vec![transfer(0, 1), transfer(1, 2)],
+        )];
+        let mut scheduler = TransactionScheduler::new(&entries);
+        assert_eq!(
+            scheduler.schedule_entry(0),
+            Err(TransactionError::AccountInUse)
+        );
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_transaction_scheduler_cross_entry() {
+        let keypairs: Vec<_> = (0..6).map(|_| Keypair::new()).collect();
+        let transfer = |from: usize, to: usize| {
+            system_transaction::transfer(
+                &keypairs[from],
+                &keypairs[to].pubkey(),
+                1,
+                Hash::default(),
+            )
+        };
+        let entries = vec![
+            next_entry(&Hash::default(), 1, vec![transfer(0, 1)]),
+            // Conflicts with the first entry
+            next_entry(&Hash::default(), 1, vec![transfer(1, 2)]),
+            // Does not conflict with the entries before it, so runs ahead of the deferred one
+            next_entry(&Hash::default(), 1, vec![transfer(3, 4)]),
+            // Conflicts with the third entry
+            next_entry(&Hash::default(), 1, vec![transfer(4, 5)]),
+        ];
+        let schedule = || {
+            let mut scheduler = TransactionScheduler::new(&entries);
+            for entry_index in 0..entries.len() {
+                scheduler.schedule_entry(entry_index).unwrap();
+            }
+            scheduler
+        };
+
+        let mut scheduler = schedule();
+        assert_eq!(
+            scheduler.take_round(),
+            vec![
+                (0, &entries[0].transactions[..]),
+                (2, &entries[2].transactions[..])
+            ]
+        );
+        assert_eq!(
+            scheduler.take_round(),
+            vec![
+                (1, &entries[1].transactions[..]),
+                (3, &entries[3].transactions[..])
+            ]
+        );
+        assert!(scheduler.is_empty());
+
+        // The entries after a failed one are dropped, the ones before it are still executed
+        let mut scheduler = schedule();
+        scheduler.take_round();
+        scheduler.cancel_after(2);
+        assert_eq!(
+            scheduler.take_round(),
+            vec![(1, &entries[1].transactions[..])]
+        );
+        assert!(scheduler.is_empty());
+
+        let mut scheduler = schedule();
+        scheduler.take_round();
+        scheduler.cancel_after(0);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_process_entries_parallel_across_entries() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(1000);
+        let bank = Arc::new(Bank::new(&genesis_config));
+        let keypair1 = Keypair::new();
+        let keypair2 = Keypair::new();
+        let keypair3 = Keypair::new();
+        let keypair4 = Keypair::new();
+
+        // fund: put 4 in each of 1, 2 and 4
+        assert_matches!(bank.transfer(4, &mint_keypair, &keypair1.pubkey()), Ok(_));
+        assert_matches!(bank.transfer(4, &mint_keypair, &keypair2.pubkey()), Ok(_));
+        assert_matches!(bank.transfer(4, &mint_keypair, &keypair4.pubkey()), Ok(_));
+
+        let entry_1_to_mint_and_error = next_entry(
+            &bank.last_blockhash(),
+            1,
+            vec![
+                system_transaction::transfer(
+                    &keypair1,
+                    &mint_keypair.pubkey(),
+                    1,
+                    bank.last_blockhash(),
+                ),
+                system_transaction::transfer(
+                    &keypair4,
+                    &keypair4.pubkey(),
+                    1,
+                    Hash::default(), // Should cause a transaction failure with BlockhashNotFound
+                ),
+            ],
+        );
+        // Conflicts with the first entry, so it is deferred and never executed
+        let entry_1_to_mint = next_entry(
+            &entry_1_to_mint_and_error.hash,
+            1,
+            vec![system_transaction::transfer(
+                &keypair1,
+                &mint_keypair.pubkey(),
+                2,
+                bank.last_blockhash(),
+            )],
+        );
+        // Does not conflict with the entries before it, so it executes with the first entry
+        let entry_2_to_3 = next_entry(
+            &entry_1_to_mint.hash,
+            1,
+            vec![system_transaction::transfer(
+                &keypair2,
+                &keypair3.pubkey(),
+                2,
+                bank.last_blockhash(),
+            )],
+        );
+
+        assert!(process_entries(
+            &bank,
+            &[entry_1_to_mint_and_error, entry_1_to_mint, entry_2_to_3],
+            false,
+            None,
+            None,
+        )
+        .is_err());
+
+        assert_eq!(bank.get_balance(&keypair1.pubkey()), 3);
+        assert_eq!(bank.get_balance(&keypair2.pubkey()), 2);
+        assert_eq!(bank.get_balance(&keypair3.pubkey()), 2);
+    }
+
+    #[test]
+    fn test_transaction_scheduler_readonly() {
+        let keys: Vec<_> = (0..3).map(|_| solana_sdk::pubkey::new_rand()).collect();
+        let mut locks = AccountLocks::default();
+        locks.insert(&AccountLockSet::new(vec![keys[0]], vec![keys[1]]));
+        // Readonly locks only conflict with writable ones
+        assert!(!locks.conflicts_with(&AccountLockSet::new(vec![], vec![keys[1]])));
+        assert!(!locks.conflicts_with(&AccountLockSet::new(vec![keys[2]], vec![])));
+        assert!(locks.conflicts_with(&AccountLockSet::new(vec![], vec![keys[0]])));
+        assert!(locks.conflicts_with(&AccountLockSet::new(vec![keys[1]], vec![])));
+        locks.clear();
+        assert!(!locks.conflicts_with(&AccountLockSet::new(vec![keys[0]], vec![keys[1]])));
+    }
+
+    // Random transfers between a few accounts, some of them also reading another account and
+    // some failing for insufficient funds, in entries which do not conflict with themselves
+    fn random_transfer_entries(
+        rng: &mut impl Rng,
+        keypairs: &[Keypair],
+        blockhash: Hash,
+        num_entries: usize,
+    ) -> Vec<Entry> {
+        let mut signatures = HashSet::new();
+        let mut hash = blockhash;
+        (0..num_entries)
+            .map(|_| {
+                let mut indexes: Vec<_> = (0..keypairs.len()).collect();
+                indexes.shuffle(rng);
+                let num_transactions = rng.gen_range(1, keypairs.len() / 3 + 1);
+                let (writable, readonly) = indexes.split_at(num_transactions * 2);
+                let transactions = writable
+                    .chunks(2)
+                    .map(|pair| {
+                        let from = &keypairs[pair[0]];
+                        let mut account_metas = vec![
+                            AccountMeta::new(from.pubkey(), true),
+                            AccountMeta::new(keypairs[pair[1]].pubkey(), false),
+                        ];
+                        if rng.gen_bool(0.3) {
+                            let readonly = readonly.choose(rng).unwrap();
+                            account_metas.push(AccountMeta::new_readonly(
+                                keypairs[*readonly].pubkey(),
+                                false,
+                            ));
+                        }
+                        let instruction = Instruction::new(
+                            system_program::id(),
+                            &SystemInstruction::Transfer {
+                                lamports: rng.gen_range(1, 50) * 2,
+                            },
+                            account_metas,
+                        );
+                        Transaction::new_signed_with_payer(
+                            &[instruction],
+                            Some(&from.pubkey()),
+                            &[from],
+                            blockhash,
+                        )
+                    })
+                    .filter(|transaction| signatures.insert(transaction.signatures[0]))
+                    .collect();
+                next_entry_mut(&mut hash, 1, transactions)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_process_entries_fuzz_against_sequential() {
+        solana_logger::setup();
+        let mut rng = thread_rng();
+        for _ in 0..50 {
+            let GenesisConfigInfo {
+                genesis_config,
+                mint_keypair,
+                ..
+            } = create_genesis_config(1_000_000);
+            let bank = Arc::new(Bank::new(&genesis_config));
+            let sequential_bank = Bank::new(&genesis_config);
+
+            let keypairs: Vec<_> = (0..rng.gen_range(3, 12)).map(|_| Keypair::new()).collect();
+            // Balances stay odd with even transfers, so fee payers are never emptied
+            for keypair in &keypairs {
+                let lamports = rng.gen_range(0, 100) * 2 + 1;
+                bank.transfer(lamports, &mint_keypair, &keypair.pubkey())
+                    .unwrap();
+                sequential_bank
+                    .transfer(lamports, &mint_keypair, &keypair.pubkey())
+                    .unwrap();
+            }
+
+            let num_entries = rng.gen_range(1, 32);
+            let entries =
+                random_transfer_entries(&mut rng, &keypairs, bank.last_blockhash(), num_entries);
+            process_entries(&bank, &entries, rng.gen(), None, None).unwrap();
+            for transaction in entries.iter().flat_map(|entry| &entry.transactions) {
+                let _ = sequential_bank.process_transaction(transaction);
+            }
+
+            for keypair in &keypairs {
+                assert_eq!(
+                    bank.get_balance(&keypair.pubkey()),
+                    sequential_bank.get_balance(&keypair.pubkey())
+                );
+            }
+            assert_eq!(
+                bank.transaction_count(),
+                sequential_bank.transaction_count()
+            );
+            bank.freeze();
+            sequential_bank.freeze();
+            assert_eq!(bank.hash(), sequential_bank.hash());
+        }
+    }
+
     #[test]
     fn test_process_entries_2_entries_par() {
         let GenesisConfigInfo {
@@ -2821,7 +3853,7 @@ pub mod tests {
         let entry = next_entry(&new_blockhash, 1, vec![tx]);
         entries.push(entry);
 
-        process_entries_with_callback(&bank0, &entries, true, None, None, None).unwrap();
+        process_entries_with_callback(&bank0, &entries, true, None, None, None, None).unwrap();
         assert_eq!(bank0.get_balance(&keypair.pubkey()), 1)
     }
 
@@ -2829,7 +3861,15 @@ pub mod tests {
         genesis_config: &GenesisConfig,
         account_paths: Vec<PathBuf>,
     ) -> EpochSchedule {
-        let bank = Bank::new_with_paths(&genesis_config, account_paths, &[], None, None);
+        let bank = Bank::new_with_paths(
+            &genesis_config,
+            account_paths,
+            &[],
+            None,
+            None,
+            AccountIndexes::default(),
+            AppendVecBackend::default(),
+        );
         *bank.epoch_schedule()
     }
 
@@ -0,0 +1,252 @@
+//! The `cost_model` module assigns transactions a cost, an estimate of the compute their execution
+//! takes and of the write locks they hold, and tracks the cost of the transactions of a block
+//! against a per-block limit and a per-writable-account limit.
+//!
+//! The banking stage stops packing transactions into a block once a limit is reached, and once
+//! the `block_cost_limits` feature is active replay rejects blocks exceeding the limits, so that a
+//! pathological block cannot stall replay on every validator. Every validator must agree on the
+//! costs and limits, so they are constants rather than configuration.
+
+use solana_sdk::{
+    clock::Slot, pubkey::Pubkey, sanitize::Sanitize, secp256k1_program, system_program,
+    transaction::Transaction,
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Cost of each signature of a transaction
+pub const SIGNATURE_COST: u64 = 100;
+/// Cost of each writable account of a transaction
+pub const WRITE_LOCK_COST: u64 = 20;
+/// Estimated cost of an instruction of a builtin program
+pub const BUILTIN_INSTRUCTION_COST: u64 = 200;
+/// Estimated cost of an instruction of any other program
+pub const PROGRAM_INSTRUCTION_COST: u64 = 200_000;
+/// Maximum cost of the transactions of a block
+pub const MAX_BLOCK_COST: u64 = 48_000_000;
+/// Maximum cost of the transactions of a block writing any one account
+pub const MAX_WRITABLE_ACCOUNT_COST: u64 = 12_000_000;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TransactionCost {
+    /// Cost of verifying the signatures and of locking the writable accounts
+    pub account_access_cost: u64,
+    /// Estimated cost of executing the instructions
+    pub execution_cost: u64,
+    pub writable_accounts: Vec<Pubkey>,
+}
+
+impl TransactionCost {
+    pub fn sum(&self) -> u64 {
+        self.account_access_cost + self.execution_cost
+    }
+}
+
+fn is_builtin_program(program_id: &Pubkey) -> bool {
+    *program_id == system_program::id()
+        || *program_id == solana_vote_program::id()
+        || *program_id == solana_stake_program::id()
+        || *program_id == secp256k1_program::id()
+}
+
+pub fn transaction_cost(transaction: &Transaction) -> TransactionCost {
+    let signature_cost = SIGNATURE_COST * transaction.signatures.len() as u64;
+    if transaction.sanitize().is_err() {
+        // Rejected before executing, only the signatures are verified
+        return TransactionCost {
+            account_access_cost: signature_cost,
+            ..TransactionCost::default()
+        };
+    }
+
+    let message = &transaction.message;
+    let writable_accounts: Vec<_> = message
+        .account_keys
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| message.is_writable(*i))
+        .map(|(_, key)| *key)
+        .collect();
+    let execution_cost = message
+        .instructions
+        .iter()
+        .map(|instruction| {
+            let program_id = &message.account_keys[instruction.program_id_index as usize];
+            if is_builtin_program(program_id) {
+                BUILTIN_INSTRUCTION_COST
+            } else {
+                PROGRAM_INSTRUCTION_COST
+            }
+        })
+        .sum();
+    TransactionCost {
+        account_access_cost: signature_cost + WRITE_LOCK_COST * writable_accounts.len() as u64,
+        execution_cost,
+        writable_accounts,
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CostTrackerError {
+    #[error("would exceed the block cost limit")]
+    WouldExceedBlockLimit,
+
+    #[error("would exceed the cost limit of writable account {0}")]
+    WouldExceedAccountLimit(Pubkey),
+}
+
+/// The cost of the transactions of a block, in total and per writable account
+#[derive(Debug)]
+pub struct CostTracker {
+    block_limit: u64,
+    account_limit: u64,
+    slot: Slot,
+    block_cost: u64,
+    account_costs: HashMap<Pubkey, u64>,
+}
+
+impl Default for CostTracker {
+    fn default() -> Self {
+        Self::new(MAX_BLOCK_COST, MAX_WRITABLE_ACCOUNT_COST)
+    }
+}
+
+impl CostTracker {
+    pub fn new(block_limit: u64, account_limit: u64) -> Self {
+        Self {
+            block_limit,
+            account_limit,
+            slot: 0,
+            block_cost: 0,
+            account_costs: HashMap::new(),
+        }
+    }
+
+    /// Starts tracking the block of `slot`, unless it already is the tracked one
+    pub fn reset_if_new_bank(&mut self, slot: Slot) {
+        if slot != self.slot {
+            self.slot = slot;
+            self.block_cost = 0;
+            self.account_costs.clear();
+        }
+    }
+
+    pub fn slot(&self) -> Slot {
+        self.slot
+    }
+
+    pub fn block_cost(&self) -> u64 {
+        self.block_cost
+    }
+
+    pub fn would_fit(&self, cost: &TransactionCost) -> Result<(), CostTrackerError> {
+        let sum = cost.sum();
+        if self.block_cost + sum > self.block_limit {
+            return Err(CostTrackerError::WouldExceedBlockLimit);
+        }
+        for account in &cost.writable_accounts {
+            let account_cost = self.account_costs.get(account).copied().unwrap_or_default();
+            if account_cost + sum > self.account_limit {
+                return Err(CostTrackerError::WouldExceedAccountLimit(*account));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn try_add(&mut self, cost: &TransactionCost) -> Result<(), CostTrackerError> {
+        self.would_fit(cost)?;
+        let sum = cost.sum();
+        self.block_cost += sum;
+        for account in &cost.writable_accounts {
+            *self.account_costs.entry(*account).or_default() += sum;
+        }
+        Ok(())
+    }
+
+    /// Gives back the cost of a transaction that was added but not included in the block
+    pub fn remove(&mut self, cost: &TransactionCost) {
+        let sum = cost.sum();
+        self.block_cost = self.block_cost.saturating_sub(sum);
+        for account in &cost.writable_accounts {
+            if let Some(account_cost) = self.account_costs.get_mut(account) {
+                *account_cost = account_cost.saturating_sub(sum);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{
+        hash::Hash,
+        instruction::{AccountMeta, Instruction},
+        signature::{Keypair, Signer},
+        system_transaction,
+    };
+
+    #[test]
+    fn test_transaction_cost() {
+        let keypair = Keypair::new();
+        let to = solana_sdk::pubkey::new_rand();
+        let transfer = system_transaction::transfer(&keypair, &to, 1, Hash::default());
+        assert_eq!(
+            transaction_cost(&transfer),
+            TransactionCost {
+                account_access_cost: SIGNATURE_COST + 2 * WRITE_LOCK_COST,
+                execution_cost: BUILTIN_INSTRUCTION_COST,
+                writable_accounts: vec![keypair.pubkey(), to],
+            }
+        );
+
+        let program_id = solana_sdk::pubkey::new_rand();
+        let instruction = Instruction::new(program_id, &0u8, vec![AccountMeta::new(to, false)]);
+        let invoke = Transaction::new_signed_with_payer(
+            &[instruction.clone(), instruction],
+            Some(&keypair.pubkey()),
+            &[&keypair],
+            Hash::default(),
+        );
+        assert_eq!(
+            transaction_cost(&invoke).execution_cost,
+            2 * PROGRAM_INSTRUCTION_COST
+        );
+
+        let mut invalid = transfer;
+        invalid.message.instructions[0].program_id_index = 10;
+        assert_eq!(transaction_cost(&invalid).sum(), SIGNATURE_COST);
+    }
+
+    #[test]
+    fn test_cost_tracker() {
+        let account = solana_sdk::pubkey::new_rand();
+        let other_account = solana_sdk::pubkey::new_rand();
+        let cost = |account: &Pubkey| TransactionCost {
+            account_access_cost: 1,
+            execution_cost: 9,
+            writable_accounts: vec![*account],
+        };
+        let mut cost_tracker = CostTracker::new(30, 20);
+
+        assert_eq!(cost_tracker.try_add(&cost(&account)), Ok(()));
+        assert_eq!(cost_tracker.try_add(&cost(&account)), Ok(()));
+        assert_eq!(
+            cost_tracker.try_add(&cost(&account)),
+            Err(CostTrackerError::WouldExceedAccountLimit(account))
+        );
+        assert_eq!(cost_tracker.try_add(&cost(&other_account)), Ok(()));
+        assert_eq!(cost_tracker.block_cost(), 30);
+        assert_eq!(
+            cost_tracker.try_add(&cost(&other_account)),
+            Err(CostTrackerError::WouldExceedBlockLimit)
+        );
+
+        cost_tracker.remove(&cost(&account));
+        assert_eq!(cost_tracker.try_add(&cost(&account)), Ok(()));
+
+        cost_tracker.reset_if_new_bank(1);
+        assert_eq!(cost_tracker.slot(), 1);
+        assert_eq!(cost_tracker.block_cost(), 0);
+        assert_eq!(cost_tracker.try_add(&cost(&account)), Ok(()));
+    }
+}
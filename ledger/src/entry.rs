@@ -2,7 +2,7 @@
 //! unique ID that is the hash of the Entry before it, plus the hash of the
 //! transactions within it. Entries cannot be reordered, and its field `num_hashes`
 //! represents an approximate amount of time since the last Entry was created.
-use crate::poh::Poh;
+use crate::{poh::Poh, replay_thread_pool};
 use dlopen::symbor::{Container, SymBorApi, Symbol};
 use dlopen_derive::SymBorApi;
 use log::*;
@@ -16,12 +16,12 @@ use solana_metrics::*;
 use solana_perf::cuda_runtime::PinnedVec;
 use solana_perf::perf_libs;
 use solana_perf::recycler::Recycler;
-use solana_rayon_threadlimit::get_thread_count;
 use solana_sdk::hash::Hash;
 use solana_sdk::timing;
 use solana_sdk::transaction::Transaction;
 use std::cell::RefCell;
 use std::ffi::OsStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::Once;
 use std::sync::{Arc, Mutex};
@@ -29,11 +29,47 @@ use std::thread::JoinHandle;
 use std::time::Instant;
 use std::{cmp, thread};
 
-thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> = RefCell::new(rayon::ThreadPoolBuilder::new()
-                    .num_threads(get_thread_count())
-                    .thread_name(|ix| format!("entry_{}", ix))
-                    .build()
-                    .unwrap()));
+toml_config::package_config! {
+    POH_VERIFY_GPU_MIN_ENTRIES: usize,
+    MAX_GPU_POH_VERIFICATIONS: usize,
+}
+
+// Number of PoH verifications running on the GPU
+static GPU_POH_VERIFICATIONS: AtomicUsize = AtomicUsize::new(0);
+
+// Holds one of the `MAX_GPU_POH_VERIFICATIONS` slots of the GPU until dropped
+struct GpuVerificationSlot;
+
+impl GpuVerificationSlot {
+    fn try_reserve() -> Option<Self> {
+        GPU_POH_VERIFICATIONS
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |verifications| {
+                if verifications < CFG.MAX_GPU_POH_VERIFICATIONS {
+                    Some(verifications + 1)
+                } else {
+                    None
+                }
+            })
+            .ok()
+            .map(|_| GpuVerificationSlot)
+    }
+}
+
+impl Drop for GpuVerificationSlot {
+    fn drop(&mut self) {
+        GPU_POH_VERIFICATIONS.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+thread_local!(static PAR_THREAD_POOL: RefCell<ThreadPool> =
+    RefCell::new(replay_thread_pool::poh_verify_config().build("entry")));
+
+/// Rebuilds this thread's verification pool after `replay_thread_pool::set_poh_verify_config`
+pub fn rebuild_thread_pool() {
+    PAR_THREAD_POOL.with(|pool| {
+        *pool.borrow_mut() = replay_thread_pool::poh_verify_config().build("entry");
+    });
+}
 
 pub type EntrySender = Sender<Vec<Entry>>;
 pub type EntryReceiver = Receiver<Vec<Entry>>;
@@ -207,9 +243,12 @@ pub fn next_hash(start_hash: &Hash, num_hashes: u64, transactions: &[Transaction
 }
 
 pub struct GpuVerificationData {
-    thread_h: Option<JoinHandle<u64>>,
+    // Returns the time spent on the GPU, or None if the GPU failed to verify
+    thread_h: Option<JoinHandle<Option<u64>>>,
     hashes: Option<Arc<Mutex<PinnedVec<Hash>>>>,
     tx_hashes: Vec<Option<Hash>>,
+    // To verify on the CPU instead if the GPU fails
+    start_hash: Hash,
 }
 
 pub enum DeviceVerificationData {
@@ -257,7 +296,18 @@ impl EntryVerificationState {
     pub fn finish_verify(&mut self, entries: &[Entry]) -> bool {
         match &mut self.device_verification_data {
             DeviceVerificationData::GPU(verification_state) => {
-                let gpu_time_us = verification_state.thread_h.take().unwrap().join().unwrap();
+                let gpu_time_us = match verification_state.thread_h.take().unwrap().join().unwrap()
+                {
+                    Some(gpu_time_us) => gpu_time_us,
+                    None => {
+                        warn!("GPU PoH verification failed, verifying on the CPU");
+                        inc_new_counter_info!("entry_verify-gpu_failed", 1);
+                        let cpu_verification = entries.verify_cpu(&verification_state.start_hash);
+                        self.poh_duration_us += cpu_verification.poh_duration_us;
+                        self.verification_status = cpu_verification.verification_status;
+                        return self.verification_status == EntryVerificationStatus::Success;
+                    }
+                };
 
                 let mut verify_check_time = Measure::start("verify_check");
                 let hashes = verification_state.hashes.take().unwrap();
@@ -527,13 +577,26 @@ impl EntrySlice for [Entry] {
         }
 
         let start = Instant::now();
-        let api = perf_libs::api();
-        if api.is_none() {
-            let mut res: EntryVerificationState = self.verify_cpu(start_hash);
-            res.set_transaction_duration_us(transaction_duration_us);
-            return res;
-        }
-        let api = api.unwrap();
+        // Short slices are verified on the CPU faster than they are copied to the GPU, and once
+        // the GPU is busy with as many verifications as it runs at once, more only queue up
+        let gpu_verification = perf_libs::api().and_then(|api| {
+            if self.len() < CFG.POH_VERIFY_GPU_MIN_ENTRIES {
+                return None;
+            }
+            let slot = GpuVerificationSlot::try_reserve();
+            if slot.is_none() {
+                inc_new_counter_info!("entry_verify-gpu_saturated", 1);
+            }
+            slot.map(|slot| (api, slot))
+        });
+        let (api, gpu_slot) = match gpu_verification {
+            Some(gpu_verification) => gpu_verification,
+            None => {
+                let mut res: EntryVerificationState = self.verify_cpu(start_hash);
+                res.set_transaction_duration_us(transaction_duration_us);
+                return res;
+            }
+        };
         inc_new_counter_info!("entry_verify-num_entries", self.len() as usize);
 
         let genesis = [Entry {
@@ -567,6 +630,7 @@ impl EntrySlice for [Entry] {
         let hashes_clone = hashes.clone();
 
         let gpu_verify_thread = thread::spawn(move || {
+            let _gpu_slot = gpu_slot;
             let mut hashes = hashes_clone.lock().unwrap();
             let gpu_wait = Instant::now();
             let res;
@@ -579,13 +643,14 @@ impl EntrySlice for [Entry] {
                 );
             }
             if res != 0 {
-                panic!("GPU PoH verify many failed");
+                error!("GPU PoH verify many failed: {}", res);
+                return None;
             }
             inc_new_counter_info!(
                 "entry_verify-gpu_thread",
                 timing::duration_as_us(&gpu_wait.elapsed()) as usize
             );
-            timing::duration_as_us(&gpu_wait.elapsed())
+            Some(timing::duration_as_us(&gpu_wait.elapsed()))
         });
 
         let tx_hashes = PAR_THREAD_POOL.with(|thread_pool| {
@@ -606,6 +671,7 @@ impl EntrySlice for [Entry] {
             thread_h: Some(gpu_verify_thread),
             tx_hashes,
             hashes: Some(hashes),
+            start_hash: *start_hash,
         });
         EntryVerificationState {
             verification_status: EntryVerificationStatus::Pending,
@@ -896,6 +962,17 @@ mod tests {
         assert_eq!(bad_ticks.verify(&one), false); // inductive step, bad
     }
 
+    #[test]
+    fn test_gpu_verification_slot() {
+        let slots: Vec<_> = (0..CFG.MAX_GPU_POH_VERIFICATIONS)
+            .map(|_| GpuVerificationSlot::try_reserve().unwrap())
+            .collect();
+        assert!(GpuVerificationSlot::try_reserve().is_none());
+        drop(slots);
+        assert!(GpuVerificationSlot::try_reserve().is_some());
+        assert_eq!(GPU_POH_VERIFICATIONS.load(Ordering::Acquire), 0);
+    }
+
     #[test]
     fn test_verify_tick_hash_count() {
         let hashes_per_tick = 10;
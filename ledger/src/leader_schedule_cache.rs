@@ -168,6 +168,31 @@ impl LeaderScheduleCache {
         first_slot.map(|slot| (slot, last_slot))
     }
 
+    /// Return the leaders of the slots from `start_slot` to `end_slot` inclusive, or only the
+    /// slots led by `identity` if given. The range is cut short at the first unconfirmed epoch.
+    pub fn slot_leaders(
+        &self,
+        start_slot: Slot,
+        end_slot: Slot,
+        identity: Option<&Pubkey>,
+        bank: &Bank,
+    ) -> Vec<(Slot, Pubkey)> {
+        let max_epoch = *self.max_epoch.read().unwrap();
+        leader_schedule_utils::slot_leaders_in_range(
+            start_slot,
+            end_slot,
+            identity,
+            &self.epoch_schedule,
+            |epoch| {
+                if epoch > max_epoch {
+                    None
+                } else {
+                    self.get_epoch_schedule_else_compute(epoch, bank)
+                }
+            },
+        )
+    }
+
     pub fn set_fixed_leader_schedule(&mut self, fixed_schedule: Option<FixedSchedule>) {
         self.fixed_schedule = fixed_schedule.map(Arc::new);
     }
@@ -516,6 +541,54 @@ mod tests {
         Blockstore::destroy(&ledger_path).unwrap();
     }
 
+    #[test]
+    fn test_slot_leaders() {
+        let pubkey = solana_sdk::pubkey::new_rand();
+        let other_pubkey = solana_sdk::pubkey::new_rand();
+        let mut genesis_config = create_genesis_config_with_leader(
+            GENESIS_CFG.BOOTSTRAP_VALIDATOR_LAMPORTS,
+            &pubkey,
+            GENESIS_CFG.BOOTSTRAP_VALIDATOR_LAMPORTS,
+        )
+        .genesis_config;
+        genesis_config.epoch_schedule = EpochSchedule::custom(
+            *DEFAULT_SLOTS_PER_EPOCH,
+            *DEFAULT_LEADER_SCHEDULE_SLOT_OFFSET,
+            false,
+        );
+        let slots_per_epoch = genesis_config.epoch_schedule.slots_per_epoch;
+
+        let bank = Bank::new(&genesis_config);
+        let mut cache = LeaderScheduleCache::new_from_bank(&bank);
+        cache.set_fixed_leader_schedule(Some(FixedSchedule {
+            leader_schedule: Arc::new(LeaderSchedule::new_from_schedule(vec![
+                pubkey,
+                other_pubkey,
+            ])),
+            start_epoch: 1,
+        }));
+
+        assert_eq!(
+            cache.slot_leaders(0, 2, None, &bank),
+            vec![(0, pubkey), (1, pubkey), (2, pubkey)]
+        );
+        // The slots are absolute across epochs
+        assert_eq!(
+            cache.slot_leaders(
+                slots_per_epoch - 1,
+                slots_per_epoch + 2,
+                Some(&other_pubkey),
+                &bank
+            ),
+            vec![(slots_per_epoch + 1, other_pubkey)]
+        );
+        // No schedule is generated for epoch 2
+        assert_eq!(
+            cache.slot_leaders(2 * slots_per_epoch - 1, 2 * slots_per_epoch, None, &bank),
+            vec![(2 * slots_per_epoch - 1, other_pubkey)]
+        );
+    }
+
     #[test]
     fn test_next_leader_slot_next_epoch() {
         let GenesisConfigInfo {
@@ -3,8 +3,10 @@ use crate::staking_utils;
 use solana_runtime::bank::Bank;
 use solana_sdk::{
     clock::{Epoch, Slot, CFG as CLOCK_CFG},
+    epoch_schedule::EpochSchedule,
     pubkey::Pubkey,
 };
+use std::sync::Arc;
 
 /// Return the leader schedule for the given epoch.
 pub fn leader_schedule(epoch: Epoch, bank: &Bank) -> Option<LeaderSchedule> {
@@ -29,7 +31,61 @@ pub fn slot_leader_at(slot: Slot, bank: &Bank) -> Option<Pubkey> {
     leader_schedule(epoch, bank).map(|leader_schedule| leader_schedule[slot_index])
 }
 
-// Returns the number of ticks remaining from the specified tick_height to the end of the
+/// Return the leaders of the slots from `start_slot` to `end_slot` inclusive, or only the slots
+/// led by `identity` if given. The range is cut short at the first epoch the bank does not know
+/// the stakes of.
+pub fn slot_leaders(
+    start_slot: Slot,
+    end_slot: Slot,
+    identity: Option<&Pubkey>,
+    bank: &Bank,
+) -> Vec<(Slot, Pubkey)> {
+    slot_leaders_in_range(
+        start_slot,
+        end_slot,
+        identity,
+        bank.epoch_schedule(),
+        |epoch| leader_schedule(epoch, bank).map(Arc::new),
+    )
+}
+
+/// Return the leaders of the slots from `start_slot` to `end_slot` inclusive, or only the slots
+/// led by `identity` if given, as scheduled by the leader schedules `get_leader_schedule`
+/// returns for each epoch. The range is cut short at the first epoch without a schedule.
+pub fn slot_leaders_in_range<F>(
+    start_slot: Slot,
+    end_slot: Slot,
+    identity: Option<&Pubkey>,
+    epoch_schedule: &EpochSchedule,
+    mut get_leader_schedule: F,
+) -> Vec<(Slot, Pubkey)>
+where
+    F: FnMut(Epoch) -> Option<Arc<LeaderSchedule>>,
+{
+    let mut slot_leaders = vec![];
+    let mut slot = start_slot;
+    while slot <= end_slot {
+        let (epoch, slot_index) = epoch_schedule.get_epoch_and_slot_index(slot);
+        let leader_schedule = match get_leader_schedule(epoch) {
+            Some(leader_schedule) => leader_schedule,
+            None => break,
+        };
+        let last_slot = epoch_schedule.get_last_slot_in_epoch(epoch).min(end_slot);
+        let first_slot = slot;
+        slot_leaders.extend(
+            (first_slot..=last_slot)
+                .map(|slot| (slot, leader_schedule[slot_index + slot - first_slot]))
+                .filter(|(_, leader)| identity.map_or(true, |identity| identity == leader)),
+        );
+        if last_slot == end_slot {
+            break;
+        }
+        slot = last_slot + 1;
+    }
+    slot_leaders
+}
+
+// Returns the number of ticks remaining// Returns the number of ticks remaining from the specified tick_height to the end of the
 // slot implied by the tick_height
 pub fn num_ticks_left_in_slot(bank: &Bank, tick_height: u64) -> u64 {
     bank.ticks_per_slot() - tick_height % bank.ticks_per_slot()
@@ -11,6 +11,7 @@ pub mod blockstore_db;
 pub mod blockstore_meta;
 pub mod blockstore_processor;
 pub mod builtins;
+pub mod cost_model;
 pub mod entry;
 pub mod erasure;
 pub mod genesis_utils;
@@ -19,10 +20,13 @@ pub mod leader_schedule_cache;
 pub mod leader_schedule_utils;
 pub mod next_slots_iterator;
 pub mod poh;
+pub mod replay_thread_pool;
 pub mod rooted_slot_iterator;
 pub mod shred;
 pub mod sigverify_shreds;
 pub mod staking_utils;
+pub mod transaction_trace;
+pub mod write_trace;
 
 #[macro_use]
 extern crate solana_metrics;
@@ -0,0 +1,79 @@
+//! The `replay_thread_pool` module configures the size and CPU affinity of the thread pools that
+//! replay entries, so that replay can be kept from starving the other threads of the validator,
+//! such as gossip and voting, on machines that also serve RPC.
+//!
+//! The configuration is process wide: the thread pools are created per replaying thread the
+//! first time they are used, so it must be set before replay starts.
+
+use rayon::ThreadPool;
+use solana_rayon_threadlimit::get_thread_count;
+use std::sync::RwLock;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ThreadPoolConfig {
+    /// Number of threads in the pool, `get_thread_count()` if not set
+    pub num_threads: Option<usize>,
+    /// Cores to pin the threads of the pool to, round robin. Threads are not pinned if empty
+    pub cores: Vec<usize>,
+}
+
+impl ThreadPoolConfig {
+    pub fn build(&self, name: &'static str) -> ThreadPool {
+        let cores = self.cores.clone();
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(self.num_threads.unwrap_or_else(get_thread_count))
+            .thread_name(move |ix| format!("{}_{}", name, ix))
+            .start_handler(move |ix| pin_current_thread(&cores, ix))
+            .build()
+            .unwrap()
+    }
+}
+
+fn pin_current_thread(cores: &[usize], index: usize) {
+    if !cores.is_empty() {
+        let id = cores[index % cores.len()];
+        core_affinity::set_for_current(core_affinity::CoreId { id });
+    }
+}
+
+lazy_static! {
+    static ref REPLAY_CONFIG: RwLock<ThreadPoolConfig> = RwLock::new(ThreadPoolConfig::default());
+    static ref POH_VERIFY_CONFIG: RwLock<ThreadPoolConfig> =
+        RwLock::new(ThreadPoolConfig::default());
+}
+
+/// Configures the pools executing the transactions of replayed entries
+pub fn set_replay_config(config: ThreadPoolConfig) {
+    *REPLAY_CONFIG.write().unwrap() = config;
+}
+
+pub fn replay_config() -> ThreadPoolConfig {
+    REPLAY_CONFIG.read().unwrap().clone()
+}
+
+/// Configures the pools verifying the PoH and transaction signatures of replayed entries
+pub fn set_poh_verify_config(config: ThreadPoolConfig) {
+    *POH_VERIFY_CONFIG.write().unwrap() = config;
+}
+
+pub fn poh_verify_config() -> ThreadPoolConfig {
+    POH_VERIFY_CONFIG.read().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_thread_pool_config() {
+        let config = ThreadPoolConfig {
+            num_threads: Some(3),
+            cores: vec![0],
+        };
+        let thread_pool = config.build("test");
+        assert_eq!(thread_pool.current_num_threads(), 3);
+        let thread_name =
+            thread_pool.install(|| std::thread::current().name().unwrap().to_string());
+        assert!(thread_name.starts_with("test_"));
+    }
+}
@@ -0,0 +1,176 @@
+//! The `transaction_trace` module follows transactions submitted with a client-supplied trace id
+//! through the validator, emitting a timestamped `transaction-trace` datapoint at each stage so
+//! that operators and users can tell where latency accrues.
+//!
+//! Trace ids are not part of the transaction and never leave the node: the events of a trace are
+//! only emitted by the node that accepted the transaction over RPC, so `Packed` is only seen if
+//! that node is the leader when the transaction is processed.
+
+use solana_sdk::{clock::Slot, signature::Signature, timing::timestamp};
+use std::{collections::HashMap, sync::RwLock, time::Instant};
+
+/// Maximum number of transactions traced at once, further trace ids are ignored
+const MAX_TRACED_TRANSACTIONS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceEvent {
+    /// Accepted by the RPC service
+    Received,
+    /// Sent, or re-sent, to the TPU of the leader
+    Forwarded,
+    /// Recorded into a block by the banking stage
+    Packed,
+    /// Executed by replay
+    Replayed,
+    /// Found in a rooted bank
+    Confirmed,
+    /// Expired, failed or evicted before being rooted
+    Dropped,
+}
+
+impl TraceEvent {
+    fn as_str(self) -> &'static str {
+        match self {
+            TraceEvent::Received => "received",
+            TraceEvent::Forwarded => "forwarded",
+            TraceEvent::Packed => "packed",
+            TraceEvent::Replayed => "replayed",
+            TraceEvent::Confirmed => "confirmed",
+            TraceEvent::Dropped => "dropped",
+        }
+    }
+
+    fn is_final(self) -> bool {
+        matches!(self, TraceEvent::Confirmed | TraceEvent::Dropped)
+    }
+}
+
+struct Trace {
+    trace_id: u64,
+    received: Instant,
+}
+
+#[derive(Default)]
+pub struct TransactionTracer {
+    traces: RwLock<HashMap<Signature, Trace>>,
+}
+
+impl TransactionTracer {
+    /// Starts tracing the transaction with `signature`, emitting a `Received` event. Returns false
+    /// if too many transactions are already being traced
+    pub fn start(&self, signature: Signature, trace_id: u64) -> bool {
+        {
+            let mut traces = self.traces.write().unwrap();
+            if traces.len() >= MAX_TRACED_TRANSACTIONS && !traces.contains_key(&signature) {
+                inc_new_counter_info!("transaction-trace-overflow", 1);
+                return false;
+            }
+            traces.insert(
+                signature,
+                Trace {
+                    trace_id,
+                    received: Instant::now(),
+                },
+            );
+        }
+        self.record(&signature, TraceEvent::Received, None);
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.read().unwrap().is_empty()
+    }
+
+    pub fn is_traced(&self, signature: &Signature) -> bool {
+        self.traces.read().unwrap().contains_key(signature)
+    }
+
+    /// Emits `event` if the transaction with `signature` is traced. The trace ends on `Confirmed`
+    /// and `Dropped`
+    pub fn record(&self, signature: &Signature, event: TraceEvent, slot: Option<Slot>) {
+        match self.traces.read().unwrap().get(signature) {
+            Some(trace) => Self::report(trace.trace_id, signature, event, slot, trace.received),
+            None => return,
+        }
+        if event.is_final() {
+            self.traces.write().unwrap().remove(signature);
+        }
+    }
+
+    /// Emits `event` for the traced transactions among `signatures`
+    pub fn record_all<'a>(
+        &self,
+        signatures: impl Iterator<Item = &'a Signature>,
+        event: TraceEvent,
+        slot: Option<Slot>,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+        for signature in signatures {
+            self.record(signature, event, slot);
+        }
+    }
+
+    fn report(
+        trace_id: u64,
+        signature: &Signature,
+        event: TraceEvent,
+        slot: Option<Slot>,
+        received: Instant,
+    ) {
+        datapoint_info!(
+            "transaction-trace",
+            ("trace_id", trace_id, i64),
+            ("signature", signature.to_string(), String),
+            ("event", event.as_str().to_string(), String),
+            ("slot", slot.unwrap_or_default(), i64),
+            ("elapsed_us", received.elapsed().as_micros(), i64),
+            ("timestamp", timestamp(), i64),
+        );
+    }
+}
+
+lazy_static! {
+    static ref TRACER: TransactionTracer = TransactionTracer::default();
+}
+
+/// The process wide tracer shared by the RPC service, the banking stage and replay
+pub fn tracer() -> &'static TransactionTracer {
+    &TRACER
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_tracer() {
+        let tracer = TransactionTracer::default();
+        let signature = Signature::new(&[1u8; 64]);
+        let other_signature = Signature::new(&[2u8; 64]);
+        assert!(tracer.is_empty());
+
+        assert!(tracer.start(signature, 1));
+        assert!(tracer.is_traced(&signature));
+        assert!(!tracer.is_traced(&other_signature));
+
+        tracer.record_all(
+            [signature, other_signature].iter(),
+            TraceEvent::Packed,
+            Some(1),
+        );
+        tracer.record(&other_signature, TraceEvent::Confirmed, None);
+        assert!(tracer.is_traced(&signature));
+        tracer.record(&signature, TraceEvent::Confirmed, Some(2));
+        assert!(tracer.is_empty());
+
+        for i in 0..MAX_TRACED_TRANSACTIONS {
+            let mut bytes = [0u8; 64];
+            bytes[..8].copy_from_slice(&(i as u64).to_le_bytes());
+            assert!(tracer.start(Signature::new(&bytes), i as u64));
+        }
+        assert!(!tracer.start(signature, 2));
+        assert!(!tracer.is_traced(&signature));
+    }
+}
@@ -0,0 +1,179 @@
+//! The `write_trace` module records the state of the accounts each replayed transaction may
+//! write, before and after it executes, so that a bank hash diverging between two nodes can be
+//! traced back to the first transaction whose writes differ.
+//!
+//! Transactions are traced in the order of the entries of the slot, whatever order they were
+//! executed in, so that the traces of the same slot replayed by two nodes can be compared.
+
+use crossbeam_channel::Sender;
+use serde::{Deserialize, Serialize};
+use solana_runtime::bank::Bank;
+use solana_sdk::{
+    account::Account,
+    clock::Slot,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{Result, Transaction},
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// State of an account before and after a transaction, `None` if the account does not exist
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountWrite {
+    pub pubkey: Pubkey,
+    pub pre: Option<Account>,
+    pub post: Option<Account>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionWrites {
+    pub slot: Slot,
+    pub signature: Signature,
+    pub result: Result<()>,
+    /// Every account the transaction locked for writing, in the order of its account keys
+    pub writes: Vec<AccountWrite>,
+}
+
+impl TransactionWrites {
+    /// Returns the writes which changed the account
+    pub fn changed(&self) -> impl Iterator<Item = &AccountWrite> {
+        self.writes.iter().filter(|write| write.pre != write.post)
+    }
+}
+
+/// Where the traced transactions go
+#[derive(Clone)]
+pub enum WriteTraceSink {
+    Channel(Sender<TransactionWrites>),
+    /// bincode encoded traces, one after the other, as read by `read_write_traces`
+    File(Arc<Mutex<BufWriter<File>>>),
+}
+
+impl WriteTraceSink {
+    pub fn create_file(path: &Path) -> io::Result<Self> {
+        Ok(WriteTraceSink::File(Arc::new(Mutex::new(BufWriter::new(
+            File::create(path)?,
+        )))))
+    }
+
+    pub fn emit(&self, writes: TransactionWrites) {
+        match self {
+            // Tracing stops, without failing replay, once the receiver is gone
+            WriteTraceSink::Channel(sender) => {
+                let _ = sender.send(writes);
+            }
+            WriteTraceSink::File(file) => {
+                if let Err(err) = bincode::serialize_into(&mut *file.lock().unwrap(), &writes) {
+                    error!("Unable to write the trace of {}: {}", writes.signature, err);
+                }
+            }
+        }
+    }
+
+    /// Flushes the traces written to a file
+    pub fn flush(&self) -> io::Result<()> {
+        match self {
+            WriteTraceSink::Channel(_) => Ok(()),
+            WriteTraceSink::File(file) => file.lock().unwrap().flush(),
+        }
+    }
+}
+
+/// Reads the traces written to `path` by a `WriteTraceSink::File`
+pub fn read_write_traces(path: &Path) -> io::Result<Vec<TransactionWrites>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut traces = vec![];
+    loop {
+        match bincode::deserialize_from(&mut reader) {
+            Ok(writes) => traces.push(writes),
+            Err(err) => match *err {
+                bincode::ErrorKind::Io(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(traces)
+                }
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+            },
+        }
+    }
+}
+
+/// Loads the accounts `transaction` may write, before it executes
+pub fn load_pre_writes(bank: &Bank, transaction: &Transaction) -> Vec<AccountWrite> {
+    let message = &transaction.message;
+    message
+        .account_keys
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| message.is_writable(*i))
+        .map(|(_, pubkey)| AccountWrite {
+            pubkey: *pubkey,
+            pre: bank.get_account(pubkey),
+            post: None,
+        })
+        .collect()
+}
+
+/// Completes the writes loaded by `load_pre_writes` once the transaction is committed. The
+/// accounts are still locked by the transaction, so no other transaction wrote them in between
+pub fn load_post_writes(
+    bank: &Bank,
+    transaction: &Transaction,
+    mut writes: Vec<AccountWrite>,
+    result: Result<()>,
+) -> TransactionWrites {
+    for write in writes.iter_mut() {
+        write.post = bank.get_account(&write.pubkey);
+    }
+    TransactionWrites {
+        slot: bank.slot(),
+        signature: transaction.signatures.first().cloned().unwrap_or_default(),
+        result,
+        writes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesis_utils::{create_genesis_config, GenesisConfigInfo};
+    use solana_sdk::{signature::Signer, system_transaction};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_trace_file() {
+        let GenesisConfigInfo {
+            genesis_config,
+            mint_keypair,
+            ..
+        } = create_genesis_config(10_000);
+        let bank = Bank::new(&genesis_config);
+        let to = Pubkey::new_unique();
+        let transaction =
+            system_transaction::transfer(&mint_keypair, &to, 100, genesis_config.hash());
+
+        let pre_writes = load_pre_writes(&bank, &transaction);
+        assert_eq!(pre_writes.len(), 2);
+        assert_eq!(pre_writes[0].pubkey, mint_keypair.pubkey());
+        assert_eq!(pre_writes[1].pre, None);
+        let result = bank.process_transaction(&transaction);
+        let writes = load_post_writes(&bank, &transaction, pre_writes, result);
+        assert_eq!(writes.result, Ok(()));
+        assert_eq!(writes.changed().count(), 2);
+        assert_eq!(writes.writes[1].post.as_ref().unwrap().lamports, 100);
+
+        let trace_dir = TempDir::new().unwrap();
+        let trace_path = trace_dir.path().join("trace");
+        let sink = WriteTraceSink::create_file(&trace_path).unwrap();
+        sink.emit(writes.clone());
+        sink.emit(writes.clone());
+        sink.flush().unwrap();
+        assert_eq!(
+            read_write_traces(&trace_path).unwrap(),
+            vec![writes.clone(), writes]
+        );
+    }
+}
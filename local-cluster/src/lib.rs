@@ -1,3 +1,4 @@
 pub mod cluster;
 pub mod cluster_tests;
+pub mod link_rules;
 pub mod local_cluster;
@@ -0,0 +1,248 @@
+//! The `link_rules` module programs the links between the nodes of a `LocalCluster`: packets
+//! received on the gossip, TVU or TPU sockets of a node can be dropped or delayed depending on
+//! the node which sent them, to test consensus under partitions, asymmetric ones included.
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use solana_core::link_conditioner::{LinkAction, LinkConditioner, LinkSocket};
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkRule {
+    /// Node sending the packets, `None` for any sender, clients included
+    pub from: Option<Pubkey>,
+    /// Node receiving the packets, `None` for any node
+    pub to: Option<Pubkey>,
+    /// Sockets the rule applies to, all of them if empty
+    pub sockets: Vec<LinkSocket>,
+    /// Probability of dropping a packet, 1.0 cuts the link
+    pub drop_probability: f64,
+    pub latency: Duration,
+    /// When the rule starts applying, after it is added
+    pub start: Duration,
+    /// When the rule stops applying, after it is added. `None` = until the rules are cleared
+    pub end: Option<Duration>,
+}
+
+impl Default for LinkRule {
+    fn default() -> Self {
+        Self {
+            from: None,
+            to: None,
+            sockets: vec![],
+            drop_probability: 0.0,
+            latency: Duration::default(),
+            start: Duration::default(),
+            end: None,
+        }
+    }
+}
+
+impl LinkRule {
+    /// Cuts the link from `from` to `to`, in that direction only
+    pub fn cut(from: Pubkey, to: Pubkey) -> Self {
+        Self {
+            from: Some(from),
+            to: Some(to),
+            drop_probability: 1.0,
+            ..Self::default()
+        }
+    }
+
+    fn applies(
+        &self,
+        from: Option<&Pubkey>,
+        to: &Pubkey,
+        socket: LinkSocket,
+        elapsed: Duration,
+    ) -> bool {
+        self.from.as_ref().map(|rule_from| Some(rule_from) == from) != Some(false)
+            && self.to.as_ref().map(|rule_to| rule_to == to) != Some(false)
+            && (self.sockets.is_empty() || self.sockets.contains(&socket))
+            && self.start <= elapsed
+            && self.end.map(|end| elapsed < end) != Some(false)
+    }
+}
+
+/// Rules conditioning the links of every node they are set on with
+/// `ValidatorConfig::link_conditioner`, or of the whole cluster with `ClusterConfig::link_rules`
+#[derive(Debug)]
+pub struct LinkRules {
+    rules: RwLock<Vec<(Instant, LinkRule)>>,
+    /// Node sending from each port; the nodes of a local cluster all share the same address
+    senders: RwLock<HashMap<u16, Pubkey>>,
+    rng: Mutex<StdRng>,
+}
+
+impl LinkRules {
+    /// Packets are dropped as drawn from a generator seeded with `seed`
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rules: RwLock::default(),
+            senders: RwLock::default(),
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    pub fn add_rule(&self, rule: LinkRule) {
+        self.rules.write().unwrap().push((Instant::now(), rule));
+    }
+
+    pub fn clear(&self) {
+        self.rules.write().unwrap().clear();
+    }
+
+    /// Cuts every link between nodes of different partitions, in both directions
+    pub fn partition(&self, partitions: &[Vec<Pubkey>]) {
+        for (i, partition) in partitions.iter().enumerate() {
+            for (j, other) in partitions.iter().enumerate() {
+                if i != j {
+                    self.cut(partition, other);
+                }
+            }
+        }
+    }
+
+    /// Cuts every link from the `from` nodes to the `to` nodes, leaving the other direction up
+    pub fn cut(&self, from: &[Pubkey], to: &[Pubkey]) {
+        for from in from {
+            for to in to {
+                self.add_rule(LinkRule::cut(*from, *to));
+            }
+        }
+    }
+
+    pub fn sender(&self, addr: &SocketAddr) -> Option<Pubkey> {
+        self.senders.read().unwrap().get(&addr.port()).cloned()
+    }
+}
+
+impl LinkConditioner for LinkRules {
+    fn register_node(&self, id: &Pubkey, addrs: &[SocketAddr]) {
+        let mut senders = self.senders.write().unwrap();
+        // A restarted node sends from new ports
+        senders.retain(|_, sender| sender != id);
+        senders.extend(addrs.iter().map(|addr| (addr.port(), *id)));
+    }
+
+    fn condition(&self, id: &Pubkey, socket: LinkSocket, from: &SocketAddr) -> LinkAction {
+        let sender = self.sender(from);
+        let now = Instant::now();
+        let mut latency = Duration::default();
+        for (added, rule) in self.rules.read().unwrap().iter() {
+            if !rule.applies(sender.as_ref(), id, socket, now.duration_since(*added)) {
+                continue;
+            }
+            if rule.drop_probability > 0.0
+                && self.rng.lock().unwrap().gen::<f64>() < rule.drop_probability
+            {
+                return LinkAction::Drop;
+            }
+            latency = latency.max(rule.latency);
+        }
+        if latency > Duration::default() {
+            LinkAction::Delay(latency)
+        } else {
+            LinkAction::Deliver
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)
+    }
+
+    #[test]
+    fn test_link_rules() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let c = Pubkey::new_unique();
+        let link_rules = LinkRules::new(0);
+        link_rules.register_node(&a, &[addr(1), addr(2)]);
+        link_rules.register_node(&b, &[addr(3)]);
+        link_rules.register_node(&c, &[addr(4)]);
+        assert_eq!(link_rules.sender(&addr(2)), Some(a));
+        assert_eq!(link_rules.sender(&addr(5)), None);
+
+        link_rules.cut(&[a], &[b]);
+        link_rules.add_rule(LinkRule {
+            to: Some(c),
+            sockets: vec![LinkSocket::Tvu],
+            latency: Duration::from_millis(50),
+            ..LinkRule::default()
+        });
+        link_rules.add_rule(LinkRule {
+            to: Some(c),
+            drop_probability: 1.0,
+            start: Duration::from_secs(3600),
+            ..LinkRule::default()
+        });
+        assert_eq!(
+            link_rules.condition(&b, LinkSocket::Gossip, &addr(2)),
+            LinkAction::Drop
+        );
+        assert_eq!(
+            link_rules.condition(&a, LinkSocket::Gossip, &addr(3)),
+            LinkAction::Deliver
+        );
+        assert_eq!(
+            link_rules.condition(&b, LinkSocket::Tpu, &addr(4)),
+            LinkAction::Deliver
+        );
+        assert_eq!(
+            link_rules.condition(&c, LinkSocket::Tvu, &addr(5)),
+            LinkAction::Delay(Duration::from_millis(50))
+        );
+        assert_eq!(
+            link_rules.condition(&c, LinkSocket::Tpu, &addr(1)),
+            LinkAction::Deliver
+        );
+
+        // The restarted node sends from other ports
+        link_rules.register_node(&a, &[addr(6)]);
+        assert_eq!(link_rules.sender(&addr(1)), None);
+        assert_eq!(
+            link_rules.condition(&b, LinkSocket::Gossip, &addr(6)),
+            LinkAction::Drop
+        );
+
+        link_rules.clear();
+        assert_eq!(
+            link_rules.condition(&b, LinkSocket::Gossip, &addr(6)),
+            LinkAction::Deliver
+        );
+    }
+
+    #[test]
+    fn test_link_rules_drop_probability() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let num_dropped = |seed| {
+            let link_rules = LinkRules::new(seed);
+            link_rules.register_node(&a, &[addr(1)]);
+            link_rules.add_rule(LinkRule {
+                drop_probability: 0.5,
+                ..LinkRule::cut(a, b)
+            });
+            (0..1000)
+                .filter(|_| {
+                    link_rules.condition(&b, LinkSocket::Gossip, &addr(1)) == LinkAction::Drop
+                })
+                .count()
+        };
+        let dropped = num_dropped(7);
+        assert!(dropped > 400 && dropped < 600);
+        // The same seed drops the same packets
+        assert_eq!(num_dropped(7), dropped);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::{
     cluster::{Cluster, ClusterValidatorInfo, ValidatorInfo},
     cluster_tests,
+    link_rules::LinkRules,
 };
 use itertools::izip;
 use log::*;
@@ -67,6 +68,8 @@ pub struct ClusterConfig {
     pub native_instruction_processors: Vec<(String, Pubkey)>,
     pub cluster_type: ClusterType,
     pub poh_config: PohConfig,
+    /// Rules conditioning the links between the nodes started with the cluster
+    pub link_rules: Option<Arc<LinkRules>>,
 }
 
 impl Default for ClusterConfig {
@@ -84,6 +87,7 @@ impl Default for ClusterConfig {
             cluster_type: ClusterType::Development,
             poh_config: PohConfig::default(),
             skip_warmup_slots: false,
+            link_rules: None,
         }
     }
 }
@@ -115,6 +119,17 @@ impl LocalCluster {
 
     pub fn new(config: &ClusterConfig) -> Self {
         assert_eq!(config.validator_configs.len(), config.node_stakes.len());
+        let validator_configs: Vec<_> = config
+            .validator_configs
+            .iter()
+            .map(|validator_config| {
+                let mut validator_config = validator_config.clone();
+                if let Some(link_rules) = &config.link_rules {
+                    validator_config.link_conditioner = Some(link_rules.clone());
+                }
+                validator_config
+            })
+            .collect();
         let mut validator_keys = {
             if let Some(ref keys) = config.validator_keys {
                 assert_eq!(config.validator_configs.len(), keys.len());
@@ -187,7 +202,7 @@ impl LocalCluster {
 
         let (leader_ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_config);
         let leader_contact_info = leader_node.info.clone();
-        let mut leader_config = config.validator_configs[0].clone();
+        let mut leader_config = validator_configs[0].clone();
         leader_config.rpc_addrs = Some((
             leader_node.info.rpc,
             leader_node.info.rpc_pubsub,
@@ -215,11 +230,8 @@ impl LocalCluster {
             contact_info: leader_contact_info.clone(),
         };
 
-        let cluster_leader = ClusterValidatorInfo::new(
-            leader_info,
-            config.validator_configs[0].clone(),
-            leader_server,
-        );
+        let cluster_leader =
+            ClusterValidatorInfo::new(leader_info, validator_configs[0].clone(), leader_server);
 
         validators.insert(leader_pubkey, cluster_leader);
 
@@ -241,7 +253,7 @@ impl LocalCluster {
             .collect();
         for (stake, validator_config, (key, _)) in izip!(
             (&config.node_stakes[1..]).iter(),
-            config.validator_configs[1..].iter(),
+            validator_configs[1..].iter(),
             validator_keys[1..].iter(),
         ) {
             cluster.add_validator(
@@ -254,7 +266,7 @@ impl LocalCluster {
 
         let listener_config = ValidatorConfig {
             voting_disabled: true,
-            ..config.validator_configs[0].clone()
+            ..validator_configs[0].clone()
         };
         (0..config.num_listeners).for_each(|_| {
             cluster.add_validator(&listener_config, 0, Arc::new(Keypair::new()), None);
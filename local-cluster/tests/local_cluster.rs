@@ -12,9 +12,10 @@ use solana_core::{
     consensus::{Tower, CFG as CONSENSUS_CFG},
     gossip_service::discover_cluster,
     optimistic_confirmation_verifier::OptimisticConfirmationVerifier,
+    tower_storage::{FileTowerStorage, TowerSyncMode},
     validator::ValidatorConfig,
 };
-use solana_download_utils::download_snapshot;
+use solana_download_utils::{download_snapshot, DownloadProgressRecord};
 use solana_ledger::{
     ancestor_iterator::AncestorIterator,
     blockstore::{Blockstore, PurgeType},
@@ -25,10 +26,11 @@ use solana_ledger::{
 use solana_local_cluster::{
     cluster::Cluster,
     cluster_tests,
+    link_rules::LinkRules,
     local_cluster::{ClusterConfig, LocalCluster},
 };
 use solana_runtime::{
-    bank_forks::{CompressionType, SnapshotConfig},
+    bank_forks::{CompressionOptions, CompressionType, SnapshotConfig, SnapshotThrottleOptions},
     snapshot_utils,
 };
 use solana_sdk::{
@@ -445,6 +447,35 @@ fn test_cluster_partition_1_1_1() {
     run_cluster_partition(&[&[1], &[1], &[1]], None, empty, on_partition_resolved)
 }
 
+#[test]
+#[serial]
+fn test_cluster_one_way_partition() {
+    solana_logger::setup();
+    let num_nodes = 3;
+    let link_rules = Arc::new(LinkRules::new(0));
+    let config = ClusterConfig {
+        cluster_lamports: 10_000,
+        node_stakes: vec![100; num_nodes],
+        validator_configs: vec![ValidatorConfig::default(); num_nodes],
+        link_rules: Some(link_rules.clone()),
+        ..ClusterConfig::default()
+    };
+    let cluster = LocalCluster::new(&config);
+    let leader = cluster.entry_point_info.id;
+    let silenced = *cluster
+        .validators
+        .keys()
+        .find(|pubkey| **pubkey != leader)
+        .unwrap();
+
+    // The leader stops hearing from one of the validators, which still hears from the leader
+    link_rules.cut(&[silenced], &[leader]);
+    cluster.check_for_new_roots(16, &"test_cluster_one_way_partition");
+
+    link_rules.clear();
+    cluster.check_for_new_roots(16, &"test_cluster_one_way_partition");
+}
+
 #[test]
 #[serial]
 fn test_kill_heaviest_partition() {
@@ -1038,12 +1069,22 @@ fn test_snapshot_download() {
     );
 
     // Download the snapshot, then boot a validator from it.
+    let mut last_progress = None;
     download_snapshot(
         &cluster.entry_point_info.rpc,
         &validator_archive_path,
         archive_snapshot_hash,
+        &mut Some(Box::new(|record: &DownloadProgressRecord| {
+            last_progress = Some(record.clone())
+        })),
     )
     .unwrap();
+    // The completed download is reported
+    let last_progress = last_progress.unwrap();
+    assert_eq!(
+        Some(last_progress.downloaded_bytes),
+        last_progress.total_bytes
+    );
 
     cluster.add_validator(
         &validator_snapshot_test_config.validator_config,
@@ -1513,6 +1554,7 @@ fn test_validator_saves_tower() {
         .info
         .ledger_path
         .clone();
+    let tower_storage = FileTowerStorage::new(ledger_path, TowerSyncMode::None);
 
     // Wait for some votes to be generated
     let mut last_replayed_root;
@@ -1530,7 +1572,7 @@ fn test_validator_saves_tower() {
 
     // Stop validator and check saved tower
     let validator_info = cluster.exit_node(&validator_id);
-    let tower1 = Tower::restore(&ledger_path, &validator_id).unwrap();
+    let tower1 = Tower::restore(&tower_storage, &validator_id).unwrap();
     trace!("tower1: {:?}", tower1);
     assert_eq!(tower1.root(), 0);
 
@@ -1555,7 +1597,7 @@ fn test_validator_saves_tower() {
         .get_slot_with_commitment(CommitmentConfig::recent())
         .unwrap();
     let validator_info = cluster.exit_node(&validator_id);
-    let tower2 = Tower::restore(&ledger_path, &validator_id).unwrap();
+    let tower2 = Tower::restore(&tower_storage, &validator_id).unwrap();
     trace!("tower2: {:?}", tower2);
     assert_eq!(tower2.root(), last_replayed_root);
     last_replayed_root = recent_slot;
@@ -1584,7 +1626,7 @@ fn test_validator_saves_tower() {
 
     // Check the new root is reflected in the saved tower state
     let mut validator_info = cluster.exit_node(&validator_id);
-    let tower3 = Tower::restore(&ledger_path, &validator_id).unwrap();
+    let tower3 = Tower::restore(&tower_storage, &validator_id).unwrap();
     trace!("tower3: {:?}", tower3);
     assert!(tower3.root() > last_replayed_root);
 
@@ -1612,7 +1654,7 @@ fn test_validator_saves_tower() {
 
     cluster.close_preserve_ledgers();
 
-    let tower4 = Tower::restore(&ledger_path, &validator_id).unwrap();
+    let tower4 = Tower::restore(&tower_storage, &validator_id).unwrap();
     trace!("tower4: {:?}", tower4);
     // should tower4 advance 1 slot compared to tower3????
     assert_eq!(tower4.root(), tower3.root() + 1);
@@ -1631,8 +1673,12 @@ fn purge_slots(blockstore: &Blockstore, start_slot: Slot, slot_count: Slot) {
     blockstore.purge_slots(start_slot, start_slot + slot_count, PurgeType::Exact);
 }
 
+fn tower_storage(ledger_path: &Path) -> FileTowerStorage {
+    FileTowerStorage::new(ledger_path.to_path_buf(), TowerSyncMode::None)
+}
+
 fn restore_tower(ledger_path: &Path, node_pubkey: &Pubkey) -> Option<Tower> {
-    let tower = Tower::restore(&ledger_path, &node_pubkey);
+    let tower = Tower::restore(&tower_storage(ledger_path), &node_pubkey);
     if let Err(tower_err) = tower {
         if tower_err.is_file_missing() {
             return None;
@@ -1641,7 +1687,7 @@ fn restore_tower(ledger_path: &Path, node_pubkey: &Pubkey) -> Option<Tower> {
         }
     }
     // actually saved tower must have at least one vote.
-    Tower::restore(&ledger_path, &node_pubkey).ok()
+    Tower::restore(&tower_storage(ledger_path), &node_pubkey).ok()
 }
 
 fn last_vote_in_tower(ledger_path: &Path, node_pubkey: &Pubkey) -> Option<Slot> {
@@ -1653,7 +1699,7 @@ fn root_in_tower(ledger_path: &Path, node_pubkey: &Pubkey) -> Option<Slot> {
 }
 
 fn remove_tower(ledger_path: &Path, node_pubkey: &Pubkey) {
-    fs::remove_file(Tower::get_filename(&ledger_path, &node_pubkey)).unwrap();
+    fs::remove_file(tower_storage(ledger_path).filename(&node_pubkey)).unwrap();
 }
 
 // A bit convoluted test case; but this roughly follows this test theoretical scenario:
@@ -2062,6 +2108,8 @@ fn setup_snapshot_validator_config(
         snapshot_package_output_path: PathBuf::from(snapshot_output_path.path()),
         snapshot_path: PathBuf::from(snapshot_dir.path()),
         compression: CompressionType::Bzip2,
+        compression_options: CompressionOptions::default(),
+        throttle_options: SnapshotThrottleOptions::default(),
         snapshot_version: snapshot_utils::SnapshotVersion::default(),
     };
 
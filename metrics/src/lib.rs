@@ -1,4 +1,5 @@
 pub mod counter;
 pub mod datapoint;
 mod metrics;
+pub mod registry;
 pub use crate::metrics::{flush, query, set_host_id, set_panic_hook, submit};
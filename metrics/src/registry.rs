@@ -0,0 +1,554 @@
+//! The `registry` module holds the typed metrics of the process: counters, gauges and histograms,
+//! grouped in namespaces. Each namespace is submitted as one datapoint, named after the
+//! namespace, with a field per metric, and the current value of every metric can be dumped at any
+//! time. A namespace registered for an instance, such as one of several `ClusterInfo` of a
+//! process, is kept apart from the namespaces of the same name of the other instances.
+
+use crate::datapoint::DataPoint;
+use lazy_static::lazy_static;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+lazy_static! {
+    static ref REGISTRY: RwLock<BTreeMap<(&'static str, Option<String>), Arc<Namespace>>> =
+        RwLock::new(BTreeMap::new());
+}
+
+/// Metric and namespace names are lowercase snake case, so that they are consistent across
+/// modules and usable as field names as is
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// A count of events, submitted as the number of events since the previous submission
+#[derive(Debug, Default)]
+pub struct Counter {
+    unsubmitted: AtomicU64,
+    total: AtomicU64,
+}
+
+impl Counter {
+    pub fn add(&self, count: u64) {
+        self.unsubmitted.fetch_add(count, Ordering::Relaxed);
+        self.total.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Count since the process started
+    pub fn total(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    fn take_unsubmitted(&self) -> u64 {
+        self.unsubmitted.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// A value that is set rather than accumulated
+#[derive(Debug, Default)]
+pub struct Gauge(AtomicI64);
+
+impl Gauge {
+    pub fn set(&self, value: i64) {
+        self.0.store(value, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> i64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HistogramSnapshot {
+    pub count: u64,
+    pub sum: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+/// The distribution of recorded values, submitted as the count, sum, minimum and maximum of the
+/// values recorded since the previous submission
+#[derive(Debug)]
+pub struct Histogram {
+    count: AtomicU64,
+    sum: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+    // Datapoint field names, suffixed with `_count`, `_sum`, `_min` and `_max`
+    field_names: [&'static str; 4],
+}
+
+impl Histogram {
+    fn new(name: &str) -> Self {
+        let field_name = |suffix: &str| -> &'static str {
+            Box::leak(format!("{}_{}", name, suffix).into_boxed_str())
+        };
+        Self {
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            min: AtomicU64::new(std::u64::MAX),
+            max: AtomicU64::new(0),
+            field_names: [
+                field_name("count"),
+                field_name("sum"),
+                field_name("min"),
+                field_name("max"),
+            ],
+        }
+    }
+
+    pub fn record(&self, value: u64) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.min.fetch_min(value, Ordering::Relaxed);
+        self.max.fetch_max(value, Ordering::Relaxed);
+    }
+
+    /// Values recorded since the previous submission
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let count = self.count.load(Ordering::Relaxed);
+        HistogramSnapshot {
+            count,
+            sum: self.sum.load(Ordering::Relaxed),
+            min: if count == 0 {
+                0
+            } else {
+                self.min.load(Ordering::Relaxed)
+            },
+            max: self.max.load(Ordering::Relaxed),
+        }
+    }
+
+    fn take_snapshot(&self) -> HistogramSnapshot {
+        let count = self.count.swap(0, Ordering::Relaxed);
+        let min = self.min.swap(std::u64::MAX, Ordering::Relaxed);
+        HistogramSnapshot {
+            count,
+            sum: self.sum.swap(0, Ordering::Relaxed),
+            min: if count == 0 { 0 } else { min },
+            max: self.max.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Metric {
+    Counter(Arc<Counter>),
+    Gauge(Arc<Gauge>),
+    Histogram(Arc<Histogram>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetricValue {
+    Counter(u64),
+    Gauge(i64),
+    Histogram(HistogramSnapshot),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricSnapshot {
+    pub namespace: &'static str,
+    pub instance: Option<String>,
+    pub name: &'static str,
+    pub value: MetricValue,
+}
+
+/// The metrics of a module, registered once and then updated through the returned handles
+#[derive(Debug)]
+pub struct Namespace {
+    name: &'static str,
+    instance: Option<String>,
+    metrics: RwLock<BTreeMap<&'static str, Metric>>,
+}
+
+impl Namespace {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn instance(&self) -> Option<&str> {
+        self.instance.as_deref()
+    }
+
+    fn register<T, F, G>(&self, name: &'static str, new: F, get: G) -> Arc<T>
+    where
+        F: FnOnce() -> Metric,
+        G: Fn(&Metric) -> Option<Arc<T>>,
+    {
+        assert!(is_valid_name(name), "invalid metric name: {}", name);
+        let mut metrics = self.metrics.write().unwrap();
+        let metric = metrics.entry(name).or_insert_with(new);
+        get(metric).unwrap_or_else(|| {
+            panic!(
+                "metric {}.{} is already registered as a {:?}",
+                self.name, name, metric
+            )
+        })
+    }
+
+    /// Returns the counter `name`, registering it if needed
+    pub fn counter(&self, name: &'static str) -> Arc<Counter> {
+        self.register(
+            name,
+            || Metric::Counter(Arc::default()),
+            |metric| match metric {
+                Metric::Counter(counter) => Some(counter.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    /// Returns the gauge `name`, registering it if needed
+    pub fn gauge(&self, name: &'static str) -> Arc<Gauge> {
+        self.register(
+            name,
+            || Metric::Gauge(Arc::default()),
+            |metric| match metric {
+                Metric::Gauge(gauge) => Some(gauge.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    /// Returns the histogram `name`, registering it if needed
+    pub fn histogram(&self, name: &'static str) -> Arc<Histogram> {
+        self.register(
+            name,
+            || Metric::Histogram(Arc::new(Histogram::new(name))),
+            |metric| match metric {
+                Metric::Histogram(histogram) => Some(histogram.clone()),
+                _ => None,
+            },
+        )
+    }
+
+    /// Builds the datapoint of the namespace, resetting the counters and histograms
+    pub fn datapoint(&self) -> DataPoint {
+        let mut point = DataPoint::new(self.name);
+        if let Some(instance) = &self.instance {
+            point.add_field_str("instance", instance);
+        }
+        for (name, metric) in self.metrics.read().unwrap().iter() {
+            match metric {
+                Metric::Counter(counter) => {
+                    point.add_field_i64(name, counter.take_unsubmitted() as i64);
+                }
+                Metric::Gauge(gauge) => {
+                    point.add_field_i64(name, gauge.get());
+                }
+                Metric::Histogram(histogram) => {
+                    let snapshot = histogram.take_snapshot();
+                    let [count, sum, min, max] = histogram.field_names;
+                    point
+                        .add_field_i64(count, snapshot.count as i64)
+                        .add_field_i64(sum, snapshot.sum as i64)
+                        .add_field_i64(min, snapshot.min as i64)
+                        .add_field_i64(max, snapshot.max as i64);
+                }
+            }
+        }
+        point
+    }
+
+    pub fn submit(&self, level: log::Level) {
+        crate::submit(self.datapoint(), level);
+    }
+
+    fn snapshot(&self) -> Vec<MetricSnapshot> {
+        self.metrics
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(name, metric)| MetricSnapshot {
+                namespace: self.name,
+                instance: self.instance.clone(),
+                name,
+                value: match metric {
+                    Metric::Counter(counter) => MetricValue::Counter(counter.total()),
+                    Metric::Gauge(gauge) => MetricValue::Gauge(gauge.get()),
+                    Metric::Histogram(histogram) => MetricValue::Histogram(histogram.snapshot()),
+                },
+            })
+            .collect()
+    }
+}
+
+fn register_namespace(name: &'static str, instance: Option<String>) -> Arc<Namespace> {
+    assert!(is_valid_name(name), "invalid namespace name: {}", name);
+    REGISTRY
+        .write()
+        .unwrap()
+        .entry((name, instance.clone()))
+        .or_insert_with(|| {
+            Arc::new(Namespace {
+                name,
+                instance,
+                metrics: RwLock::default(),
+            })
+        })
+        .clone()
+}
+
+/// Returns the namespace `name`, registering it if needed
+pub fn namespace(name: &'static str) -> Arc<Namespace> {
+    register_namespace(name, None)
+}
+
+/// Returns the namespace `name` of `instance`, registering it if needed. Its datapoint carries the
+/// instance in an `instance` field
+pub fn instance_namespace(name: &'static str, instance: &str) -> Arc<Namespace> {
+    register_namespace(name, Some(instance.to_string()))
+}
+
+/// Current value of every registered metric, sorted by namespace, instance and name. Counters are
+/// reported as their count since the process started
+pub fn dump() -> Vec<MetricSnapshot> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .values()
+        .flat_map(|namespace| namespace.snapshot())
+        .collect()
+}
+
+/// Current value of every registered metric in the Prometheus text format
+pub fn dump_prometheus() -> String {
+    encode_prometheus(&dump())
+}
+
+/// Metrics are named `solana_<namespace>_<name>`, with a `_total` suffix for counters. Histograms
+/// are exported as a gauge per statistic of the values recorded since the previous submission.
+/// The metrics of instance namespaces are labelled with their instance, and grouped with those of
+/// the other instances of the namespace under a single type line
+fn encode_prometheus(metrics: &[MetricSnapshot]) -> String {
+    // Metric name -> (type, samples)
+    let mut families: BTreeMap<String, (&str, Vec<String>)> = BTreeMap::new();
+    for metric in metrics {
+        let labels = match &metric.instance {
+            Some(instance) => format!("{{instance=\"{}\"}}", instance),
+            None => String::new(),
+        };
+        let mut add_sample = |name: String, metric_type: &'static str, value: String| {
+            let sample = format!("{}{} {}", name, labels, value);
+            families
+                .entry(name)
+                .or_insert_with(|| (metric_type, vec![]))
+                .1
+                .push(sample);
+        };
+        let name = format!("solana_{}_{}", metric.namespace, metric.name);
+        match &metric.value {
+            MetricValue::Counter(value) => {
+                add_sample(format!("{}_total", name), "counter", value.to_string())
+            }
+            MetricValue::Gauge(value) => add_sample(name, "gauge", value.to_string()),
+            MetricValue::Histogram(snapshot) => {
+                for (suffix, value) in &[
+                    ("count", snapshot.count),
+                    ("sum", snapshot.sum),
+                    ("min", snapshot.min),
+                    ("max", snapshot.max),
+                ] {
+                    add_sample(format!("{}_{}", name, suffix), "gauge", value.to_string());
+                }
+            }
+        }
+    }
+    let mut text = String::new();
+    for (name, (metric_type, samples)) in families {
+        writeln!(text, "# TYPE {} {}", name, metric_type).unwrap();
+        for sample in samples {
+            writeln!(text, "{}", sample).unwrap();
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_valid_name() {
+        assert!(is_valid_name("cluster_info_stats2"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("2cluster_info"));
+        assert!(!is_valid_name("clusterInfo"));
+        assert!(!is_valid_name("cluster-info"));
+    }
+
+    #[test]
+    fn test_namespace_datapoint() {
+        let namespace = namespace("test_namespace_datapoint");
+        let counter = namespace.counter("counter");
+        counter.add(2);
+        namespace.counter("counter").inc();
+        namespace.gauge("gauge").set(-5);
+        let histogram = namespace.histogram("histogram");
+        histogram.record(10);
+        histogram.record(4);
+
+        let point = namespace.datapoint();
+        assert_eq!(point.name, "test_namespace_datapoint");
+        assert_eq!(
+            point.fields,
+            vec![
+                ("counter", "3i".to_string()),
+                ("gauge", "-5i".to_string()),
+                ("histogram_count", "2i".to_string()),
+                ("histogram_sum", "14i".to_string()),
+                ("histogram_min", "4i".to_string()),
+                ("histogram_max", "10i".to_string()),
+            ]
+        );
+
+        // Counters and histograms restart from zero, gauges keep their value
+        let point = namespace.datapoint();
+        assert_eq!(
+            point.fields,
+            vec![
+                ("counter", "0i".to_string()),
+                ("gauge", "-5i".to_string()),
+                ("histogram_count", "0i".to_string()),
+                ("histogram_sum", "0i".to_string()),
+                ("histogram_min", "0i".to_string()),
+                ("histogram_max", "0i".to_string()),
+            ]
+        );
+        assert_eq!(counter.total(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered")]
+    fn test_namespace_type_mismatch() {
+        let namespace = namespace("test_namespace_type_mismatch");
+        namespace.counter("metric");
+        namespace.gauge("metric");
+    }
+
+    #[test]
+    fn test_instance_namespace() {
+        let first = instance_namespace("test_instance_namespace", "first");
+        let second = instance_namespace("test_instance_namespace", "second");
+        first.counter("counter").add(3);
+        second.counter("counter").inc();
+        instance_namespace("test_instance_namespace", "first")
+            .counter("counter")
+            .inc();
+        assert_eq!(first.counter("counter").total(), 4);
+        assert_eq!(second.counter("counter").total(), 1);
+        assert_eq!(
+            namespace("test_instance_namespace")
+                .counter("counter")
+                .total(),
+            0
+        );
+
+        let point = first.datapoint();
+        assert_eq!(point.name, "test_instance_namespace");
+        assert_eq!(
+            point.fields,
+            vec![
+                ("instance", "\"first\"".to_string()),
+                ("counter", "4i".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_prometheus() {
+        let metrics = vec![
+            MetricSnapshot {
+                namespace: "tpu",
+                instance: None,
+                name: "packets",
+                value: MetricValue::Counter(7),
+            },
+            MetricSnapshot {
+                namespace: "replay",
+                instance: None,
+                name: "root_slot",
+                value: MetricValue::Gauge(-2),
+            },
+            MetricSnapshot {
+                namespace: "replay",
+                instance: Some("a".to_string()),
+                name: "elapsed_us",
+                value: MetricValue::Histogram(HistogramSnapshot {
+                    count: 2,
+                    sum: 14,
+                    min: 4,
+                    max: 10,
+                }),
+            },
+            MetricSnapshot {
+                namespace: "replay",
+                instance: Some("b".to_string()),
+                name: "elapsed_us",
+                value: MetricValue::Histogram(HistogramSnapshot::default()),
+            },
+        ];
+        assert_eq!(
+            encode_prometheus(&metrics),
+            "# TYPE solana_replay_elapsed_us_count gauge\n\
+             solana_replay_elapsed_us_count{instance=\"a\"} 2\n\
+             solana_replay_elapsed_us_count{instance=\"b\"} 0\n\
+             # TYPE solana_replay_elapsed_us_max gauge\n\
+             solana_replay_elapsed_us_max{instance=\"a\"} 10\n\
+             solana_replay_elapsed_us_max{instance=\"b\"} 0\n\
+             # TYPE solana_replay_elapsed_us_min gauge\n\
+             solana_replay_elapsed_us_min{instance=\"a\"} 4\n\
+             solana_replay_elapsed_us_min{instance=\"b\"} 0\n\
+             # TYPE solana_replay_elapsed_us_sum gauge\n\
+             solana_replay_elapsed_us_sum{instance=\"a\"} 14\n\
+             solana_replay_elapsed_us_sum{instance=\"b\"} 0\n\
+             # TYPE solana_replay_root_slot gauge\n\
+             solana_replay_root_slot -2\n\
+             # TYPE solana_tpu_packets_total counter\n\
+             solana_tpu_packets_total 7\n"
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let namespace = namespace("test_dump");
+        namespace.counter("counter").add(7);
+        namespace.histogram("histogram").record(3);
+        namespace.datapoint();
+
+        let dump: Vec<_> = dump()
+            .into_iter()
+            .filter(|metric| metric.namespace == "test_dump")
+            .collect();
+        assert_eq!(
+            dump,
+            vec![
+                MetricSnapshot {
+                    namespace: "test_dump",
+                    instance: None,
+                    name: "counter",
+                    value: MetricValue::Counter(7),
+                },
+                MetricSnapshot {
+                    namespace: "test_dump",
+                    instance: None,
+                    name: "histogram",
+                    value: MetricValue::Histogram(HistogramSnapshot::default()),
+                },
+            ]
+        );
+    }
+}
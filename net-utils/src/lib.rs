@@ -97,26 +97,62 @@ pub fn get_public_ip_addr(ip_echo_server_addr: &SocketAddr) -> Result<IpAddr, St
 const DEFAULT_TIMEOUT_SECS: u64 = 5;
 const DEFAULT_RETRY_COUNT: usize = 5;
 
-fn do_verify_reachable_ports(
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl std::fmt::Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Protocol::Tcp => write!(f, "tcp"),
+            Protocol::Udp => write!(f, "udp"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortReachability {
+    Reachable,
+    /// The request asking the ip echo server to reach the port failed, so this side timed out
+    /// or was refused before the port could be tested
+    EchoRequestFailed(String),
+    /// The ip echo server was asked to reach the port, but nothing arrived before the timeout
+    TimedOut,
+}
+
+/// Reachability of a local port from the ip echo server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortCheck {
+    pub protocol: Protocol,
+    pub port: u16,
+    pub reachability: PortReachability,
+}
+
+fn do_check_reachable_ports(
     ip_echo_server_addr: &SocketAddr,
     tcp_listeners: Vec<(u16, TcpListener)>,
     udp_sockets: &[&UdpSocket],
     timeout: u64,
     udp_retry_count: usize,
-) -> bool {
+) -> Vec<PortCheck> {
     info!(
         "Checking that tcp ports {:?} from {:?}",
         tcp_listeners, ip_echo_server_addr
     );
 
     let tcp_ports: Vec<_> = tcp_listeners.iter().map(|(port, _)| *port).collect();
-    let _ = ip_echo_server_request(
+    let tcp_request_error = ip_echo_server_request(
         ip_echo_server_addr,
         IpEchoServerMessage::new(&tcp_ports, &[]),
     )
-    .map_err(|err| warn!("ip_echo_server request failed: {}", err));
+    .err();
+    if let Some(err) = &tcp_request_error {
+        warn!("ip_echo_server request failed: {}", err);
+    }
 
-    let mut ok = true;
+    let mut checks = vec![];
     let timeout = Duration::from_secs(timeout);
 
     // Wait for a connection to open on each TCP port
@@ -132,9 +168,10 @@ fn do_verify_reachable_ports(
                 None => warn!("tcp incoming failed"),
             }
         });
-        match receiver.recv_timeout(timeout) {
+        let reachability = match receiver.recv_timeout(timeout) {
             Ok(_) => {
                 info!("tcp/{} is reachable", port);
+                PortReachability::Reachable
             }
             Err(err) => {
                 error!(
@@ -146,18 +183,21 @@ fn do_verify_reachable_ports(
                 // So, to close the thread cleanly, just connect from here.
                 // ref: https://github.com/rust-lang/rust/issues/31615
                 TcpStream::connect_timeout(&listening_addr, timeout).unwrap();
-                ok = false;
+                match &tcp_request_error {
+                    Some(err) => PortReachability::EchoRequestFailed(err.clone()),
+                    None => PortReachability::TimedOut,
+                }
             }
-        }
+        };
+        checks.push(PortCheck {
+            protocol: Protocol::Tcp,
+            port,
+            reachability,
+        });
         // ensure to reap the thread
         thread_handle.join().unwrap();
     }
 
-    if !ok {
-        // No retries for TCP, abort on the first failure
-        return ok;
-    }
-
     let mut udp_ports: BTreeMap<_, _> = BTreeMap::new();
     udp_sockets.iter().for_each(|udp_socket| {
         let port = udp_socket.local_addr().unwrap().port();
@@ -174,26 +214,28 @@ fn do_verify_reachable_ports(
         ip_echo_server_addr
     );
 
-    'outer: for checked_ports_and_sockets in udp_ports.chunks(MAX_PORT_COUNT_PER_MESSAGE) {
-        ok = false;
+    for checked_ports_and_sockets in udp_ports.chunks(MAX_PORT_COUNT_PER_MESSAGE) {
+        let checked_ports: Vec<_> = checked_ports_and_sockets
+            .iter()
+            .map(|(port, _)| *port)
+            .collect();
+        let mut reachable_ports = BTreeSet::new();
+        let mut udp_request_error = None;
 
         for udp_remaining_retry in (0_usize..udp_retry_count).rev() {
-            let (checked_ports, checked_socket_iter) = (
-                checked_ports_and_sockets
-                    .iter()
-                    .map(|(port, _)| *port)
-                    .collect::<Vec<_>>(),
-                checked_ports_and_sockets
-                    .iter()
-                    .map(|(_, sockets)| sockets)
-                    .flatten(),
-            );
-
-            let _ = ip_echo_server_request(
+            let checked_socket_iter = checked_ports_and_sockets
+                .iter()
+                .map(|(_, sockets)| sockets)
+                .flatten();
+
+            udp_request_error = ip_echo_server_request(
                 ip_echo_server_addr,
                 IpEchoServerMessage::new(&[], &checked_ports),
             )
-            .map_err(|err| warn!("ip_echo_server request failed: {}", err));
+            .err();
+            if let Some(err) = &udp_request_error {
+                warn!("ip_echo_server request failed: {}", err);
+            }
 
             // Spawn threads at once!
             let thread_handles: Vec<_> = checked_socket_iter
@@ -219,17 +261,13 @@ fn do_verify_reachable_ports(
             // Separate from the above by collect()-ing as an intermediately step to make the iterator
             // eager not lazy so that joining happens here at once after creating bunch of threads
             // at once.
-            let reachable_ports: BTreeSet<_> = thread_handles
-                .into_iter()
-                .filter_map(|t| t.join().unwrap())
-                .collect();
+            reachable_ports.extend(thread_handles.into_iter().filter_map(|t| t.join().unwrap()));
 
             if reachable_ports.len() == checked_ports.len() {
                 info!(
                     "checked udp ports: {:?}, reachable udp ports: {:?}",
                     checked_ports, reachable_ports
                 );
-                ok = true;
                 break;
             } else if udp_remaining_retry > 0 {
                 // Might have lost a UDP packet, retry a couple times
@@ -240,12 +278,41 @@ fn do_verify_reachable_ports(
                 error!("There are some udp ports with no response!! Retrying...");
             } else {
                 error!("Maximum retry count is reached....");
-                break 'outer;
             }
         }
+
+        checks.extend(checked_ports.into_iter().map(|port| PortCheck {
+            protocol: Protocol::Udp,
+            port,
+            reachability: if reachable_ports.contains(&port) {
+                PortReachability::Reachable
+            } else if let Some(err) = &udp_request_error {
+                PortReachability::EchoRequestFailed(err.clone())
+            } else {
+                PortReachability::TimedOut
+            },
+        }));
     }
 
-    ok
+    checks
+}
+
+fn do_verify_reachable_ports(
+    ip_echo_server_addr: &SocketAddr,
+    tcp_listeners: Vec<(u16, TcpListener)>,
+    udp_sockets: &[&UdpSocket],
+    timeout: u64,
+    udp_retry_count: usize,
+) -> bool {
+    do_check_reachable_ports(
+        ip_echo_server_addr,
+        tcp_listeners,
+        udp_sockets,
+        timeout,
+        udp_retry_count,
+    )
+    .iter()
+    .all(|check| check.reachability == PortReachability::Reachable)
 }
 
 pub fn verify_reachable_ports(
@@ -262,6 +329,22 @@ pub fn verify_reachable_ports(
     )
 }
 
+/// Checks every TCP/UDP port, reporting for each whether it is reachable by the machine at
+/// `ip_echo_server_addr`
+pub fn check_reachable_ports(
+    ip_echo_server_addr: &SocketAddr,
+    tcp_listeners: Vec<(u16, TcpListener)>,
+    udp_sockets: &[&UdpSocket],
+) -> Vec<PortCheck> {
+    do_check_reachable_ports(
+        ip_echo_server_addr,
+        tcp_listeners,
+        udp_sockets,
+        DEFAULT_TIMEOUT_SECS,
+        DEFAULT_RETRY_COUNT,
+    )
+}
+
 pub fn parse_port_or_addr(optstr: Option<&str>, default_addr: SocketAddr) -> SocketAddr {
     if let Some(addrstr) = optstr {
         if let Ok(port) = addrstr.parse() {
@@ -675,4 +758,63 @@ mod tests {
             3,
         ));
     }
+
+    #[test]
+    fn test_check_reachable_ports() {
+        solana_logger::setup();
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
+        let (_server_port, (server_udp_socket, server_tcp_listener)) =
+            bind_common_in_range(ip_addr, (3200, 3250)).unwrap();
+        let (client_port, (client_udp_socket, client_tcp_listener)) =
+            bind_common_in_range(ip_addr, (3200, 3250)).unwrap();
+        let server_ip_echo_addr = server_udp_socket.local_addr().unwrap();
+
+        let _runtime = ip_echo_server(server_tcp_listener);
+        let checks = check_reachable_ports(
+            &server_ip_echo_addr,
+            vec![(client_port, client_tcp_listener)],
+            &[&client_udp_socket],
+        );
+        assert_eq!(
+            checks,
+            vec![
+                PortCheck {
+                    protocol: Protocol::Tcp,
+                    port: client_port,
+                    reachability: PortReachability::Reachable,
+                },
+                PortCheck {
+                    protocol: Protocol::Udp,
+                    port: client_port,
+                    reachability: PortReachability::Reachable,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_reachable_ports_echo_request_failed() {
+        solana_logger::setup();
+        let ip_addr = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0));
+        let (_server_port, (server_udp_socket, _server_tcp_listener)) =
+            bind_common_in_range(ip_addr, (3200, 3250)).unwrap();
+        let (client_port, (client_udp_socket, client_tcp_listener)) =
+            bind_common_in_range(ip_addr, (3200, 3250)).unwrap();
+
+        // The ip echo server is not running, so each check fails on the request side
+        let server_ip_echo_addr = server_udp_socket.local_addr().unwrap();
+        let checks = do_check_reachable_ports(
+            &server_ip_echo_addr,
+            vec![(client_port, client_tcp_listener)],
+            &[&client_udp_socket],
+            2,
+            1,
+        );
+        assert_eq!(checks.len(), 2);
+        assert_eq!(checks[0].protocol, Protocol::Tcp);
+        assert_eq!(checks[1].protocol, Protocol::Udp);
+        assert!(checks
+            .iter()
+            .all(|check| matches!(check.reachability, PortReachability::EchoRequestFailed(_))));
+    }
 }
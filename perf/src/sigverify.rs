@@ -322,10 +322,32 @@ pub fn get_checked_scalar(scalar: &[u8; 32]) -> Result<[u8; 32], PacketError> {
     Ok(out)
 }
 
+// micro-benchmarks show GPU time for smallest batch around 15-20ms
+// and CPU speed for 64-128 sigverifies around 10-20ms. 64 is a nice
+// power-of-two number around that accounting for the fact that the CPU
+// may be busy doing other things while being a real validator
+pub const DEFAULT_GPU_VERIFY_THRESHOLD: usize = 64;
+
 pub fn ed25519_verify(
     batches: &[Packets],
     recycler: &Recycler<TxOffset>,
     recycler_out: &Recycler<PinnedVec<u8>>,
+) -> Vec<Vec<u8>> {
+    ed25519_verify_with_gpu_threshold(
+        batches,
+        recycler,
+        recycler_out,
+        DEFAULT_GPU_VERIFY_THRESHOLD,
+    )
+}
+
+/// Offloads the verification to the GPU, when available, for batches of at least
+/// `gpu_threshold` packets; smaller batches are verified on the CPU
+pub fn ed25519_verify_with_gpu_threshold(
+    batches: &[Packets],
+    recycler: &Recycler<TxOffset>,
+    recycler_out: &Recycler<PinnedVec<u8>>,
+    gpu_threshold: usize,
 ) -> Vec<Vec<u8>> {
     let api = perf_libs::api();
     if api.is_none() {
@@ -336,12 +358,7 @@ pub fn ed25519_verify(
     use crate::packet::PACKET_DATA_SIZE;
     let count = batch_size(batches);
 
-    // micro-benchmarks show GPU time for smallest batch around 15-20ms
-    // and CPU speed for 64-128 sigverifies around 10-20ms. 64 is a nice
-    // power-of-two number around that accounting for the fact that the CPU
-    // may be busy doing other things while being a real validator
-    // TODO: dynamically adjust this crossover
-    if count < 64 {
+    if count < gpu_threshold {
         return ed25519_verify_cpu(batches);
     }
 
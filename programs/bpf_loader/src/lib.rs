@@ -317,6 +317,7 @@ mod tests {
         instruction::InstructionError,
         process_instruction::{BpfComputeBudget, MockInvokeContext},
         rent::Rent,
+        system_instruction::MAX_PERMITTED_DATA_LENGTH,
     };
     use std::{cell::RefCell, fs::File, io::Read, ops::Range, rc::Rc};
 
@@ -546,6 +547,7 @@ mod tests {
                 max_call_depth: 20,
                 stack_frame_size: 4096,
                 log_pubkey_units: 100,
+                max_account_data_len: MAX_PERMITTED_DATA_LENGTH,
             },
             Rc::new(RefCell::new(Executors::default())),
             None,
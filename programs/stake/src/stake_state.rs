@@ -945,6 +945,23 @@ pub fn redeem_rewards(
     }
 }
 
+// utility function, used by runtime
+// credits stake rewards calculated by redeem_rewards() at a later slot
+pub fn credit_rewards(
+    stake_account: &mut Account,
+    stakers_reward: u64,
+    credits_observed: u64,
+) -> Result<(), InstructionError> {
+    if let StakeState::Stake(meta, mut stake) = stake_account.state()? {
+        stake.credits_observed = credits_observed;
+        stake.delegation.stake += stakers_reward;
+        stake_account.lamports += stakers_reward;
+        stake_account.set_state(&StakeState::Stake(meta, stake))
+    } else {
+        Err(InstructionError::InvalidAccountData)
+    }
+}
+
 // utility function, used by runtime
 pub fn calculate_points(
     stake_account: &Account,
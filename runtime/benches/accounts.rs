@@ -6,6 +6,7 @@ use dashmap::DashMap;
 use rand::Rng;
 use solana_runtime::{
     accounts::{create_test_accounts, Accounts},
+    append_vec::AppendVecBackend,
     bank::*,
 };
 use solana_sdk::{
@@ -14,7 +15,7 @@ use solana_sdk::{
     pubkey::Pubkey,
 };
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     path::PathBuf,
     sync::{Arc, RwLock},
     thread::Builder,
@@ -49,6 +50,8 @@ fn test_accounts_create(bencher: &mut Bencher) {
         &[],
         None,
         None,
+        HashSet::new(),
+        AppendVecBackend::default(),
     );
     bencher.iter(|| {
         let mut pubkeys: Vec<Pubkey> = vec![];
@@ -66,6 +69,8 @@ fn test_accounts_squash(bencher: &mut Bencher) {
         &[],
         None,
         None,
+        HashSet::new(),
+        AppendVecBackend::default(),
     ));
     let mut pubkeys: Vec<Pubkey> = vec![];
     deposit_many(&bank1, &mut pubkeys, 250_000);
@@ -4,7 +4,7 @@ extern crate test;
 use rand::{thread_rng, Rng};
 use solana_runtime::append_vec::{
     test_utils::{create_test_account, get_append_vec_path},
-    AppendVec,
+    AppendVec, AppendVecBackend, DEFAULT_DIRECT_IO_CACHE_SIZE,
 };
 use solana_sdk::hash::Hash;
 use std::{
@@ -23,6 +23,7 @@ fn append_vec_append(bencher: &mut Bencher) {
         let (meta, account) = create_test_account(0);
         if vec
             .append_account(meta, &account, Hash::default())
+            .unwrap()
             .is_none()
         {
             vec.reset();
@@ -35,6 +36,7 @@ fn add_test_accounts(vec: &AppendVec, size: usize) -> Vec<(usize, usize)> {
         .filter_map(|sample| {
             let (meta, account) = create_test_account(sample);
             vec.append_account(meta, &account, Hash::default())
+                .unwrap()
                 .map(|pos| (sample, pos))
         })
         .collect()
@@ -79,7 +81,10 @@ fn append_vec_concurrent_append_read(bencher: &mut Bencher) {
     spawn(move || loop {
         let sample = indexes1.lock().unwrap().len();
         let (meta, account) = create_test_account(sample);
-        if let Some(pos) = vec1.append_account(meta, &account, Hash::default()) {
+        if let Some(pos) = vec1
+            .append_account(meta, &account, Hash::default())
+            .unwrap()
+        {
             indexes1.lock().unwrap().push((sample, pos))
         } else {
             break;
@@ -119,8 +124,76 @@ fn append_vec_concurrent_read_append(bencher: &mut Bencher) {
     bencher.iter(|| {
         let sample: usize = thread_rng().gen_range(0, 256);
         let (meta, account) = create_test_account(sample);
-        if let Some(pos) = vec.append_account(meta, &account, Hash::default()) {
+        if let Some(pos) = vec.append_account(meta, &account, Hash::default()).unwrap() {
             indexes.lock().unwrap().push((sample, pos))
         }
     });
 }
+
+// The backends compare best under memory pressure, with the benches run in a cgroup whose memory
+// is limited below the size of the append vecs, e.g. with `systemd-run --scope -p MemoryMax=`
+const BACKEND_BENCH_FILE_SIZE: usize = 256 * 1024 * 1024;
+
+fn bench_append_backend(bencher: &mut Bencher, backend: AppendVecBackend, name: &str) {
+    let path = get_append_vec_path(name);
+    let vec = AppendVec::new_with_backend(&path.path, true, BACKEND_BENCH_FILE_SIZE, &backend);
+    let mut sample = 0;
+    bencher.iter(|| {
+        let (meta, account) = create_test_account(sample);
+        sample += 1;
+        if vec
+            .append_account(meta, &account, Hash::default())
+            .unwrap()
+            .is_none()
+        {
+            vec.reset();
+        }
+    });
+}
+
+fn bench_store_and_read_backend(bencher: &mut Bencher, backend: AppendVecBackend, name: &str) {
+    let path = get_append_vec_path(name);
+    let vec = AppendVec::new_with_backend(&path.path, true, BACKEND_BENCH_FILE_SIZE, &backend);
+    let indexes = add_test_accounts(&vec, 100_000);
+    // Replay reads accounts back about as often as it stores them
+    bencher.iter(|| {
+        let (sample, pos) = indexes[thread_rng().gen_range(0, indexes.len())];
+        let (_meta, test) = create_test_account(sample);
+        vec.get_account_with(pos, |account| {
+            assert_eq!(account.data, test.data.as_slice())
+        })
+        .unwrap()
+        .unwrap();
+        // Once the append vec is full only the reads go on, resetting it would lose the accounts
+        let (meta, account) = create_test_account(sample);
+        vec.append_account(meta, &account, Hash::default()).unwrap();
+    });
+}
+
+#[bench]
+fn append_vec_append_mmap(bencher: &mut Bencher) {
+    bench_append_backend(bencher, AppendVecBackend::Mmap, "append_mmap");
+}
+
+#[bench]
+fn append_vec_append_direct_io(bencher: &mut Bencher) {
+    bench_append_backend(
+        bencher,
+        AppendVecBackend::direct_io(DEFAULT_DIRECT_IO_CACHE_SIZE),
+        "append_direct_io",
+    );
+}
+
+#[bench]
+fn append_vec_store_and_read_mmap(bencher: &mut Bencher) {
+    bench_store_and_read_backend(bencher, AppendVecBackend::Mmap, "store_and_read_mmap");
+}
+
+#[bench]
+fn append_vec_store_and_read_direct_io(bencher: &mut Bencher) {
+    bench_store_and_read_backend(
+        bencher,
+        AppendVecBackend::direct_io(DEFAULT_DIRECT_IO_CACHE_SIZE),
+        "store_and_read_direct_io",
+    );
+}
@@ -2,10 +2,11 @@ use crate::{
     accounts_db::{
         AccountInfo, AccountStorage, AccountsDB, AppendVecId, BankHashInfo, ErrorCounters,
     },
-    accounts_index::{AccountsIndex, Ancestors},
-    append_vec::StoredAccount,
+    accounts_index::{AccountIndexes, AccountsIndex, Ancestors, IndexKey},
+    append_vec::{AppendVecBackend, StoredAccount},
     bank::{HashAgeKind, TransactionProcessResult},
     blockhash_queue::BlockhashQueue,
+    lock_contention::{self, LockConflict},
     rent_collector::RentCollector,
     system_instruction_processor::{get_system_account_kind, SystemAccountKind},
     transaction_utils::OrderedIterator,
@@ -58,6 +59,35 @@ pub struct Accounts {
     pub(crate) readonly_locks: Arc<RwLock<Option<HashMap<Pubkey, ReadonlyLock>>>>,
 }
 
+/// The accounts a unit of work needs exclusive (writable) or shared (readonly) access to.
+/// Schedulers can take these locks directly through `Accounts::try_lock_account_set`
+/// instead of going through `lock_accounts`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccountLockSet {
+    pub writable: Vec<Pubkey>,
+    pub readonly: Vec<Pubkey>,
+}
+
+impl AccountLockSet {
+    pub fn new(writable: Vec<Pubkey>, readonly: Vec<Pubkey>) -> Self {
+        Self { writable, readonly }
+    }
+
+    /// The locks `lock_accounts` would take for `tx`, after the same sanity checks
+    pub fn try_from_transaction(tx: &Transaction) -> Result<Self> {
+        use solana_sdk::sanitize::Sanitize;
+        tx.sanitize().map_err(TransactionError::from)?;
+        if Accounts::has_duplicates(&tx.message.account_keys) {
+            return Err(TransactionError::AccountLoadedTwice);
+        }
+        let (writable_keys, readonly_keys) = tx.message().get_account_keys_by_lock_type();
+        Ok(Self {
+            writable: writable_keys.into_iter().cloned().collect(),
+            readonly: readonly_keys.into_iter().cloned().collect(),
+        })
+    }
+}
+
 // for the load instructions
 pub type TransactionAccounts = Vec<Account>;
 pub type TransactionRent = u64;
@@ -72,8 +102,35 @@ pub enum AccountAddressFilter {
 
 impl Accounts {
     pub fn new(paths: Vec<PathBuf>, cluster_type: &ClusterType) -> Self {
+        Self::new_with_indexes(paths, cluster_type, AccountIndexes::default())
+    }
+
+    pub fn new_with_indexes(
+        paths: Vec<PathBuf>,
+        cluster_type: &ClusterType,
+        account_indexes: AccountIndexes,
+    ) -> Self {
+        Self::new_with_config(
+            paths,
+            cluster_type,
+            account_indexes,
+            AppendVecBackend::default(),
+        )
+    }
+
+    pub fn new_with_config(
+        paths: Vec<PathBuf>,
+        cluster_type: &ClusterType,
+        account_indexes: AccountIndexes,
+        append_vec_backend: AppendVecBackend,
+    ) -> Self {
         Self {
-            accounts_db: Arc::new(AccountsDB::new(paths, cluster_type)),
+            accounts_db: Arc::new(AccountsDB::new_with_config(
+                paths,
+                cluster_type,
+                account_indexes,
+                append_vec_backend,
+            )),
             account_locks: Mutex::new(HashSet::new()),
             readonly_locks: Arc::new(RwLock::new(Some(HashMap::new()))),
             ..Self::default()
@@ -528,6 +585,69 @@ impl Accounts {
         )
     }
 
+    /// Loads the accounts owned by `program_id` and passing `filter`. The filter is applied while
+    /// scanning, so that accounts failing it are never collected
+    pub fn load_by_program_with_filter<F: Fn(&Account) -> bool>(
+        &self,
+        ancestors: &Ancestors,
+        program_id: &Pubkey,
+        filter: F,
+    ) -> Vec<(Pubkey, Account)> {
+        self.accounts_db.scan_accounts(
+            ancestors,
+            |collector: &mut Vec<(Pubkey, Account)>, some_account_tuple| {
+                Self::load_while_filtering(collector, some_account_tuple, |account| {
+                    account.owner == *program_id && filter(account)
+                })
+            },
+        )
+    }
+
+    /// Loads the accounts having `index_key` and passing `filter`, using the secondary indexes
+    pub fn load_by_index_key_with_filter<F: Fn(&Account) -> bool>(
+        &self,
+        ancestors: &Ancestors,
+        index_key: IndexKey,
+        filter: F,
+    ) -> Vec<(Pubkey, Account)> {
+        self.accounts_db.index_scan_accounts(
+            ancestors,
+            index_key,
+            |collector: &mut Vec<(Pubkey, Account)>, some_account_tuple| {
+                Self::load_while_filtering(collector, some_account_tuple, |account| {
+                    index_key.matches(&account.owner, &account.data) && filter(account)
+                })
+            },
+        )
+    }
+
+    /// Loads the page of at most `limit` accounts following `cursor` in pubkey order and passing
+    /// `filter`, out of the ones listed under `index_key` in the secondary indexes if set, else
+    /// all of them. The scan starts at `cursor` and stops once the page is full.
+    pub fn load_page_with_filter<F: Fn(&Account) -> bool>(
+        &self,
+        ancestors: &Ancestors,
+        index_key: Option<IndexKey>,
+        filter: F,
+        cursor: Option<Pubkey>,
+        limit: usize,
+    ) -> Vec<(Pubkey, Account)> {
+        let mut page = vec![];
+        if limit == 0 {
+            return page;
+        }
+        self.accounts_db.scan_accounts_from(
+            ancestors,
+            index_key,
+            cursor,
+            |pubkey, account, slot| {
+                Self::load_while_filtering(&mut page, Some((pubkey, account, slot)), &filter);
+                page.len() < limit
+            },
+        );
+        page
+    }
+
     pub fn load_all(&self, ancestors: &Ancestors) -> Vec<(Pubkey, Account, Slot)> {
         self.accounts_db.scan_accounts(
             ancestors,
@@ -587,7 +707,11 @@ impl Accounts {
             .as_ref()
             .map_or(false, |locks| {
                 locks.get(key).map_or(false, |lock| {
-                    *lock.lock_count.lock().unwrap() += 1;
+                    let mut lock_count = lock.lock_count.lock().unwrap();
+                    if *lock_count > 0 {
+                        lock_contention::profiler().record_shared_readonly_lock(key);
+                    }
+                    *lock_count += 1;
                     true
                 })
             })
@@ -605,11 +729,11 @@ impl Accounts {
             })
     }
 
-    fn lock_account(
+    fn check_account_locks(
         &self,
-        locks: &mut HashSet<Pubkey>,
-        writable_keys: Vec<&Pubkey>,
-        readonly_keys: Vec<&Pubkey>,
+        locks: &HashSet<Pubkey>,
+        writable_keys: &[&Pubkey],
+        readonly_keys: &[&Pubkey],
     ) -> Result<()> {
         for k in writable_keys.iter() {
             if locks.contains(k) || self.is_locked_readonly(k) {
@@ -623,6 +747,43 @@ impl Accounts {
                 return Err(TransactionError::AccountInUse);
             }
         }
+        Ok(())
+    }
+
+    // Records every account that keeps a transaction from taking its locks
+    fn record_lock_conflicts(
+        &self,
+        locks: &HashSet<Pubkey>,
+        writable_keys: &[&Pubkey],
+        readonly_keys: &[&Pubkey],
+    ) {
+        let profiler = lock_contention::profiler();
+        for k in writable_keys.iter() {
+            if locks.contains(k) {
+                profiler.record_conflict(k, LockConflict::WriteLocked);
+            } else if self.is_locked_readonly(k) {
+                profiler.record_conflict(k, LockConflict::ReadLocked);
+            }
+        }
+        for k in readonly_keys.iter() {
+            if locks.contains(k) {
+                profiler.record_conflict(k, LockConflict::WriteLocked);
+            }
+        }
+    }
+
+    fn lock_account(
+        &self,
+        locks: &mut HashSet<Pubkey>,
+        writable_keys: Vec<&Pubkey>,
+        readonly_keys: Vec<&Pubkey>,
+    ) -> Result<()> {
+        if let Err(err) = self.check_account_locks(locks, &writable_keys, &readonly_keys) {
+            if lock_contention::profiler().is_enabled() {
+                self.record_lock_conflicts(locks, &writable_keys, &readonly_keys);
+            }
+            return Err(err);
+        }
 
         for k in writable_keys {
             locks.insert(*k);
@@ -723,6 +884,47 @@ impl Accounts {
             .for_each(|((_, tx), result)| self.unlock_account(tx, result, &mut account_locks));
     }
 
+    /// Attempts to take every lock in `lock_set`. Either all of them are taken, or none
+    /// are and `TransactionError::AccountInUse` is returned. The locks are shared with
+    /// `lock_accounts`, so transactions touching held accounts cannot be batched until
+    /// the set is released with `unlock_account_set`.
+    pub fn try_lock_account_set(&self, lock_set: &AccountLockSet) -> Result<()> {
+        let keys: Vec<_> = lock_set
+            .writable
+            .iter()
+            .chain(lock_set.readonly.iter())
+            .collect();
+        if Self::has_duplicates(&keys) {
+            return Err(TransactionError::AccountLoadedTwice);
+        }
+        let mut account_locks = self.account_locks.lock().unwrap();
+        self.lock_account(
+            &mut account_locks,
+            lock_set.writable.iter().collect(),
+            lock_set.readonly.iter().collect(),
+        )
+    }
+
+    /// Returns true if `try_lock_account_set` would currently succeed, without taking any locks
+    pub fn can_lock_account_set(&self, lock_set: &AccountLockSet) -> bool {
+        let account_locks = self.account_locks.lock().unwrap();
+        let writable_keys: Vec<_> = lock_set.writable.iter().collect();
+        let readonly_keys: Vec<_> = lock_set.readonly.iter().collect();
+        self.check_account_locks(&account_locks, &writable_keys, &readonly_keys)
+            .is_ok()
+    }
+
+    /// Releases the locks taken by a successful `try_lock_account_set`
+    pub fn unlock_account_set(&self, lock_set: &AccountLockSet) {
+        let mut account_locks = self.account_locks.lock().unwrap();
+        for k in &lock_set.writable {
+            account_locks.remove(k);
+        }
+        for k in &lock_set.readonly {
+            self.unlock_readonly(k);
+        }
+    }
+
     /// Store the accounts into the DB
     // allow(clippy) needed for various gating flags
     #[allow(clippy::too_many_arguments)]
@@ -896,7 +1098,9 @@ mod tests {
     // TODO: all the bank tests are bank specific, issue: 2194
 
     use super::*;
-    use crate::{bank::HashAgeKind, rent_collector::RentCollector};
+    use crate::{
+        bank::HashAgeKind, lock_contention::AccountLockStats, rent_collector::RentCollector,
+    };
     use solana_sdk::{
         account::Account,
         epoch_schedule::EpochSchedule,
@@ -1648,6 +1852,104 @@ mod tests {
         assert_eq!(*keypair1_lock.unwrap().lock_count.lock().unwrap(), 0);
     }
 
+    #[test]
+    fn test_account_lock_set() {
+        let keypair0 = Keypair::new();
+        let keypair1 = Keypair::new();
+        let accounts = Accounts::new(Vec::new(), &ClusterType::Development);
+
+        let instructions = vec![CompiledInstruction::new(2, &(), vec![0, 1])];
+        let message = Message::new_with_compiled_instructions(
+            1,
+            0,
+            2,
+            vec![keypair0.pubkey(), keypair1.pubkey(), native_loader::id()],
+            Hash::default(),
+            instructions,
+        );
+        let tx = Transaction::new(&[&keypair0], message, Hash::default());
+        let lock_set = AccountLockSet::try_from_transaction(&tx).unwrap();
+        assert_eq!(lock_set.writable, vec![keypair0.pubkey()]);
+        assert_eq!(
+            lock_set.readonly,
+            vec![keypair1.pubkey(), native_loader::id()]
+        );
+
+        assert!(accounts.can_lock_account_set(&lock_set));
+        assert!(accounts.try_lock_account_set(&lock_set).is_ok());
+
+        // Held locks conflict with transaction batches and other lock sets
+        let results = accounts.lock_accounts(&[tx.clone()], None);
+        assert_eq!(results[0], Err(TransactionError::AccountInUse));
+        let conflicting = AccountLockSet::new(vec![keypair1.pubkey()], vec![]);
+        assert!(!accounts.can_lock_account_set(&conflicting));
+        assert_eq!(
+            accounts.try_lock_account_set(&conflicting),
+            Err(TransactionError::AccountInUse)
+        );
+        // Read-only accounts can be shared
+        let shared = AccountLockSet::new(vec![], vec![keypair1.pubkey()]);
+        assert!(accounts.try_lock_account_set(&shared).is_ok());
+        accounts.unlock_account_set(&shared);
+
+        let duplicate = AccountLockSet::new(vec![keypair1.pubkey()], vec![keypair1.pubkey()]);
+        assert_eq!(
+            accounts.try_lock_account_set(&duplicate),
+            Err(TransactionError::AccountLoadedTwice)
+        );
+
+        accounts.unlock_account_set(&lock_set);
+        assert!(accounts.can_lock_account_set(&conflicting));
+        let results = accounts.lock_accounts(&[tx.clone()], None);
+        assert!(results[0].is_ok());
+        accounts.unlock_accounts(&[tx], None, &results);
+    }
+
+    #[test]
+    fn test_lock_contention_profiler() {
+        lock_contention::enable_lock_contention_profiler();
+        let key0 = solana_sdk::pubkey::new_rand();
+        let key1 = solana_sdk::pubkey::new_rand();
+        let accounts = Accounts::new(Vec::new(), &ClusterType::Development);
+
+        let lock_set = AccountLockSet::new(vec![key0], vec![key1]);
+        assert!(accounts.try_lock_account_set(&lock_set).is_ok());
+        for conflicting in &[
+            AccountLockSet::new(vec![key0], vec![]),
+            AccountLockSet::new(vec![], vec![key0]),
+            AccountLockSet::new(vec![key1], vec![]),
+        ] {
+            assert!(!accounts.can_lock_account_set(conflicting));
+            assert!(accounts.try_lock_account_set(conflicting).is_err());
+        }
+        let shared = AccountLockSet::new(vec![], vec![key1]);
+        assert!(accounts.try_lock_account_set(&shared).is_ok());
+
+        // Only failed attempts to take the locks are recorded, not `can_lock_account_set`
+        let stats: HashMap<_, _> = lock_contention::profiler()
+            .most_contended(usize::MAX)
+            .into_iter()
+            .collect();
+        assert_eq!(
+            stats[&key0],
+            AccountLockStats {
+                write_conflicts: 2,
+                read_conflicts: 0,
+                shared_readonly_locks: 0,
+            }
+        );
+        assert_eq!(
+            stats[&key1],
+            AccountLockStats {
+                write_conflicts: 0,
+                read_conflicts: 1,
+                shared_readonly_locks: 1,
+            }
+        );
+        accounts.unlock_account_set(&shared);
+        accounts.unlock_account_set(&lock_set);
+    }
+
     #[test]
     fn test_accounts_locks_multithreaded() {
         let counter = Arc::new(AtomicU64::new(0));
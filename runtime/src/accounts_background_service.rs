@@ -3,6 +3,7 @@
 // This can be expensive since we have to walk the append vecs being cleaned up.
 
 use crate::{
+    accounts_db::ShrinkConfig,
     bank::{Bank, BankSlotDelta},
     bank_forks::{BankForks, SnapshotConfig},
     snapshot_package::AccountsPackageSender,
@@ -22,7 +23,7 @@ use std::time::Duration;
 toml_config::package_config! {
     INTERVAL_MS: u64,
     SHRUNKEN_ACCOUNT_PER_SEC: usize,
-    CLEAN_INTERVAL_BLOCKS: u64,
+    CLEAN_INTERVAL_SLOTS: u64,
 }
 
 toml_config::derived_values! {
@@ -30,6 +31,37 @@ toml_config::derived_values! {
         CFG.SHRUNKEN_ACCOUNT_PER_SEC / (1000 / CFG.INTERVAL_MS as usize);
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AccountsBackgroundConfig {
+    /// Slots are shrunk once the ratio of their alive accounts to their stored accounts falls
+    /// below this
+    pub shrink_ratio: f64,
+    /// Roots are cleaned about every this many slots
+    pub clean_interval_slots: u64,
+    /// Shrinking stops once a pass reclaimed this many bytes, 0 shrinks a single slot per pass
+    pub max_reclaim_bytes_per_pass: u64,
+}
+
+impl Default for AccountsBackgroundConfig {
+    fn default() -> Self {
+        let shrink_config = ShrinkConfig::default();
+        Self {
+            shrink_ratio: shrink_config.shrink_ratio,
+            clean_interval_slots: CFG.CLEAN_INTERVAL_SLOTS,
+            max_reclaim_bytes_per_pass: shrink_config.max_reclaim_bytes_per_pass,
+        }
+    }
+}
+
+impl AccountsBackgroundConfig {
+    pub fn shrink_config(&self) -> ShrinkConfig {
+        ShrinkConfig {
+            shrink_ratio: self.shrink_ratio,
+            max_reclaim_bytes_per_pass: self.max_reclaim_bytes_per_pass,
+        }
+    }
+}
+
 pub type SnapshotRequestSender = Sender<SnapshotRequest>;
 pub type SnapshotRequestReceiver = Receiver<SnapshotRequest>;
 
@@ -110,7 +142,7 @@ impl SnapshotRequestHandler {
                     ),
                     ("hash_time", hash_time.as_us(), i64),
                 );
-                snapshot_root_bank.block_height()
+                snapshot_root_bank.slot()
             })
     }
 }
@@ -124,11 +156,17 @@ impl AccountsBackgroundService {
         bank_forks: Arc<RwLock<BankForks>>,
         exit: &Arc<AtomicBool>,
         snapshot_request_handler: Option<SnapshotRequestHandler>,
+        config: AccountsBackgroundConfig,
     ) -> Self {
-        info!("AccountsBackgroundService active");
+        info!("AccountsBackgroundService active: {:?}", config);
+        bank_forks
+            .read()
+            .unwrap()
+            .root_bank()
+            .set_shrink_config(config.shrink_config());
         let exit = exit.clone();
         let mut consumed_budget = 0;
-        let mut last_cleaned_block_height = 0;
+        let mut last_cleaned_slot = 0;
         let t_background = Builder::new()
             .name("solana-accounts-background".to_string())
             .spawn(move || loop {
@@ -156,28 +194,29 @@ impl AccountsBackgroundService {
                 // request for `N` to the snapshot request channel before setting a root `R > N`, and
                 // snapshot_request_handler.handle_snapshot_requests() will always look for the latest
                 // available snapshot in the channel.
-                let snapshot_block_height =
+                let snapshot_slot =
                     snapshot_request_handler
                         .as_ref()
                         .and_then(|snapshot_request_handler| {
                             snapshot_request_handler.handle_snapshot_requests()
                         });
 
-                if let Some(snapshot_block_height) = snapshot_block_height {
+                if let Some(snapshot_slot) = snapshot_slot {
                     // Safe, see proof above
-                    assert!(last_cleaned_block_height <= snapshot_block_height);
-                    last_cleaned_block_height = snapshot_block_height;
+                    assert!(last_cleaned_slot <= snapshot_slot);
+                    last_cleaned_slot = snapshot_slot;
                 } else {
                     consumed_budget = bank.process_stale_slot_with_budget(
                         consumed_budget,
                         *SHRUNKEN_ACCOUNT_PER_INTERVAL,
                     );
 
-                    if bank.block_height() - last_cleaned_block_height
-                        > (CFG.CLEAN_INTERVAL_BLOCKS + thread_rng().gen_range(0, 10))
+                    if bank.slot() - last_cleaned_slot
+                        > (config.clean_interval_slots + thread_rng().gen_range(0, 10))
                     {
                         bank.clean_accounts(true);
-                        last_cleaned_block_height = bank.block_height();
+                        last_cleaned_slot = bank.slot();
+                        Self::report_reclaimed_storage(&bank);
                     }
                 }
 
@@ -187,6 +226,16 @@ impl AccountsBackgroundService {
         Self { t_background }
     }
 
+    fn report_reclaimed_storage(bank: &Bank) {
+        let reclaimed_storage = bank.reclaimed_storage();
+        datapoint_info!(
+            "accounts_background_service-reclaimed_storage",
+            ("shrunk_slots", reclaimed_storage.shrunk_slots, i64),
+            ("shrunk_bytes", reclaimed_storage.shrunk_bytes, i64),
+            ("dead_slots", reclaimed_storage.dead_slots, i64),
+        );
+    }
+
     pub fn join(self) -> thread::Result<()> {
         self.t_background.join()
     }
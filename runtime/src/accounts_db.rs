@@ -19,8 +19,9 @@
 //! commit for each slot entry would be indexed.
 
 use crate::{
-    accounts_index::{AccountsIndex, Ancestors, SlotList, SlotSlice},
-    append_vec::{AppendVec, StoredAccount, StoredMeta},
+    accounts_index::{AccountIndexes, AccountsIndex, Ancestors, IndexKey, SlotList, SlotSlice},
+    append_vec::{AppendVec, AppendVecBackend, StoredAccount, StoredMeta},
+    incremental_accounts_hash::{AccountHash, IncrementalAccountsHash},
 };
 use blake3::traits::digest::Digest;
 use dashmap::DashMap;
@@ -37,6 +38,7 @@ use solana_sdk::{
     genesis_config::ClusterType,
     hash::{Hash, Hasher},
     pubkey::Pubkey,
+    system_instruction::MAX_PERMITTED_DATA_LENGTH,
 };
 use std::convert::TryFrom;
 use std::{
@@ -44,7 +46,7 @@ use std::{
     convert::TryInto,
     io::{Error as IOError, Result as IOResult},
     iter::FromIterator,
-    ops::RangeBounds,
+    ops::{Bound, RangeBounds},
     path::{Path, PathBuf},
     sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
     sync::{Arc, Mutex, MutexGuard, RwLock},
@@ -56,6 +58,9 @@ toml_config::package_config! {
     PAGE_SIZE: u64,
     DEFAULT_NUM_THREADS: u32,
     DEFAULT_NUM_DIRS: u32,
+    SHRINK_RATIO: f64,
+    MAX_RECLAIM_BYTES_PER_PASS: u64,
+    ACCOUNTS_HASH_PARTITIONS: usize,
 }
 
 toml_config::derived_values! {
@@ -202,9 +207,19 @@ pub struct AccountStorageEntry {
 
 impl AccountStorageEntry {
     pub fn new(path: &Path, slot: Slot, id: usize, file_size: u64) -> Self {
+        Self::new_with_backend(path, slot, id, file_size, &AppendVecBackend::Mmap)
+    }
+
+    pub fn new_with_backend(
+        path: &Path,
+        slot: Slot,
+        id: usize,
+        file_size: u64,
+        backend: &AppendVecBackend,
+    ) -> Self {
         let tail = AppendVec::new_relative_path(slot, id);
         let path = Path::new(path).join(&tail);
-        let accounts = AppendVec::new(&path, true, file_size as usize);
+        let accounts = AppendVec::new_with_backend(&path, true, file_size as usize, backend);
 
         Self {
             id,
@@ -275,11 +290,36 @@ impl AccountStorageEntry {
     }
 
     fn get_account(&self, account_info: &AccountInfo) -> Option<Account> {
-        Some(
-            self.accounts
-                .get_account(account_info.offset)?
-                .0
-                .clone_account(),
+        let (account, _) =
+            self.get_stored_account_with(account_info.offset, |account| account.clone_account())?;
+        Some(account)
+    }
+
+    /// Calls `f` with the account stored at `offset`, see `AppendVec::get_account_with`. The
+    /// accounts db can't go on once an account it stored can't be read back, just like when the
+    /// read of a memory-mapped append vec faults.
+    fn get_stored_account_with<R>(
+        &self,
+        offset: usize,
+        f: impl FnOnce(&StoredAccount) -> R,
+    ) -> Option<(R, usize)> {
+        self.accounts
+            .get_account_with(offset, f)
+            .unwrap_or_else(|err| self.read_failed(err))
+    }
+
+    /// Calls `f` with each account stored, see `get_stored_account_with`
+    fn scan_stored_accounts(&self, f: impl FnMut(&StoredAccount)) {
+        self.accounts
+            .scan_accounts(0, f)
+            .unwrap_or_else(|err| self.read_failed(err))
+    }
+
+    fn read_failed(&self, err: IOError) -> ! {
+        panic!(
+            "Unable to read the accounts of {}: {}",
+            self.accounts.get_path().display(),
+            err
         )
     }
 
@@ -335,8 +375,12 @@ impl AccountStorageEntry {
         count
     }
 
-    pub fn set_file<P: AsRef<Path>>(&mut self, path: P) -> IOResult<()> {
-        self.accounts.set_file(path)
+    pub fn set_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        backend: &AppendVecBackend,
+    ) -> IOResult<()> {
+        self.accounts.set_file_with_backend(path, backend)
     }
 
     pub fn get_relative_path(&self) -> Option<PathBuf> {
@@ -425,9 +469,16 @@ pub struct AccountsDB {
     /// Starting file size of appendvecs
     file_size: u64,
 
+    /// How the appendvecs are read and written
+    append_vec_backend: AppendVecBackend,
+
     /// Accounts that will cause a panic! if data modified or lamports decrease
     frozen_accounts: HashMap<Pubkey, FrozenAccountInfo>,
 
+    /// Accounts observed with more than `MAX_PERMITTED_DATA_LENGTH` bytes of data,
+    /// along with the data length at the time they were observed
+    quarantined_accounts: RwLock<HashMap<Pubkey, usize>>,
+
     /// Thread pool used for par_iter
     pub thread_pool: ThreadPool,
 
@@ -441,7 +492,42 @@ pub struct AccountsDB {
 
     stats: AccountsStats,
 
+    shrink_config: RwLock<ShrinkConfig>,
+
     pub cluster_type: Option<ClusterType>,
+
+    /// Secondary indexes maintained in `accounts_index`
+    account_indexes: AccountIndexes,
+
+    /// Account hashes of the last accounts hash calculation, unless the accounts hash is
+    /// calculated from a full scan every time
+    incremental_accounts_hash: Option<IncrementalAccountsHash>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShrinkConfig {
+    /// Slots are shrunk once the ratio of their alive accounts to their stored accounts falls
+    /// below this
+    pub shrink_ratio: f64,
+    /// Shrinking stops once a pass reclaimed this many bytes, 0 shrinks a single slot per pass
+    pub max_reclaim_bytes_per_pass: u64,
+}
+
+impl Default for ShrinkConfig {
+    fn default() -> Self {
+        Self {
+            shrink_ratio: CFG.SHRINK_RATIO,
+            max_reclaim_bytes_per_pass: CFG.MAX_RECLAIM_BYTES_PER_PASS,
+        }
+    }
+}
+
+/// Storage reclaimed since the accounts db was created
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReclaimedStorage {
+    pub shrunk_slots: u64,
+    pub shrunk_bytes: u64,
+    pub dead_slots: u64,
 }
 
 #[derive(Debug, Default)]
@@ -460,6 +546,10 @@ struct AccountsStats {
     store_find_store: AtomicU64,
     store_num_accounts: AtomicU64,
     store_total_data: AtomicU64,
+
+    shrunk_slots: AtomicU64,
+    shrunk_bytes: AtomicU64,
+    dead_slots: AtomicU64,
 }
 
 fn make_min_priority_thread_pool() -> ThreadPool {
@@ -502,6 +592,7 @@ impl Default for AccountsDB {
             paths: vec![],
             temp_paths: None,
             file_size: *DEFAULT_FILE_SIZE,
+            append_vec_backend: AppendVecBackend::default(),
             thread_pool: rayon::ThreadPoolBuilder::new()
                 .num_threads(num_threads)
                 .thread_name(|i| format!("solana-accounts-db-{}", i))
@@ -511,19 +602,51 @@ impl Default for AccountsDB {
             min_num_stores: num_threads,
             bank_hashes: RwLock::new(bank_hashes),
             frozen_accounts: HashMap::new(),
+            quarantined_accounts: RwLock::new(HashMap::new()),
             stats: AccountsStats::default(),
+            shrink_config: RwLock::new(ShrinkConfig::default()),
             cluster_type: None,
+            account_indexes: AccountIndexes::default(),
+            incremental_accounts_hash: if CFG.ACCOUNTS_HASH_PARTITIONS > 0 {
+                Some(IncrementalAccountsHash::new(CFG.ACCOUNTS_HASH_PARTITIONS))
+            } else {
+                None
+            },
         }
     }
 }
 
 impl AccountsDB {
     pub fn new(paths: Vec<PathBuf>, cluster_type: &ClusterType) -> Self {
+        Self::new_with_indexes(paths, cluster_type, AccountIndexes::default())
+    }
+
+    pub fn new_with_indexes(
+        paths: Vec<PathBuf>,
+        cluster_type: &ClusterType,
+        account_indexes: AccountIndexes,
+    ) -> Self {
+        Self::new_with_config(
+            paths,
+            cluster_type,
+            account_indexes,
+            AppendVecBackend::default(),
+        )
+    }
+
+    pub fn new_with_config(
+        paths: Vec<PathBuf>,
+        cluster_type: &ClusterType,
+        account_indexes: AccountIndexes,
+        append_vec_backend: AppendVecBackend,
+    ) -> Self {
         let new = if !paths.is_empty() {
             Self {
                 paths,
                 temp_paths: None,
                 cluster_type: Some(*cluster_type),
+                account_indexes,
+                append_vec_backend,
                 ..Self::default()
             }
         } else {
@@ -534,6 +657,8 @@ impl AccountsDB {
                 paths,
                 temp_paths: Some(temp_dirs),
                 cluster_type: Some(*cluster_type),
+                account_indexes,
+                append_vec_backend,
                 ..Self::default()
             }
         };
@@ -549,6 +674,30 @@ impl AccountsDB {
         self.file_size
     }
 
+    pub fn account_indexes(&self) -> &AccountIndexes {
+        &self.account_indexes
+    }
+
+    pub fn append_vec_backend(&self) -> &AppendVecBackend {
+        &self.append_vec_backend
+    }
+
+    pub fn shrink_config(&self) -> ShrinkConfig {
+        *self.shrink_config.read().unwrap()
+    }
+
+    pub fn set_shrink_config(&self, shrink_config: ShrinkConfig) {
+        *self.shrink_config.write().unwrap() = shrink_config;
+    }
+
+    pub fn reclaimed_storage(&self) -> ReclaimedStorage {
+        ReclaimedStorage {
+            shrunk_slots: self.stats.shrunk_slots.load(Ordering::Relaxed),
+            shrunk_bytes: self.stats.shrunk_bytes.load(Ordering::Relaxed),
+            dead_slots: self.stats.dead_slots.load(Ordering::Relaxed),
+        }
+    }
+
     #[cfg(test)]
     pub fn new_single() -> Self {
         AccountsDB {
@@ -565,11 +714,12 @@ impl AccountsDB {
     }
 
     fn new_storage_entry(&self, slot: Slot, path: &Path, size: u64) -> AccountStorageEntry {
-        AccountStorageEntry::new(
+        AccountStorageEntry::new_with_backend(
             path,
             slot,
             self.next_id.fetch_add(1, Ordering::Relaxed),
             size,
+            &self.append_vec_backend,
         )
     }
 
@@ -939,6 +1089,9 @@ impl AccountsDB {
         if dead_slots.is_empty() {
             return;
         }
+        self.stats
+            .dead_slots
+            .fetch_add(dead_slots.len() as u64, Ordering::Relaxed);
         let mut clean_dead_slots = Measure::start("reclaims::purge_slots");
         self.clean_dead_slots(&dead_slots, purged_account_slots);
         clean_dead_slots.stop();
@@ -956,7 +1109,7 @@ impl AccountsDB {
         );
     }
 
-    fn do_shrink_stale_slot(&self, slot: Slot) -> usize {
+    fn do_shrink_stale_slot(&self, slot: Slot) -> (usize, u64) {
         self.do_shrink_slot(slot, false)
     }
 
@@ -964,17 +1117,30 @@ impl AccountsDB {
         self.do_shrink_slot(slot, true);
     }
 
-    fn shrink_stale_slot(&self, candidates: &mut MutexGuard<Vec<Slot>>) -> usize {
-        if let Some(slot) = self.do_next_shrink_slot(candidates) {
-            self.do_shrink_stale_slot(slot)
-        } else {
-            0
+    // Shrinks the next candidate slots until `max_reclaim_bytes` are reclaimed, visiting each
+    // candidate at most once. Returns the number of alive accounts rewritten
+    fn shrink_stale_slots(
+        &self,
+        candidates: &mut MutexGuard<Vec<Slot>>,
+        max_reclaim_bytes: u64,
+    ) -> usize {
+        let mut alive_accounts = 0;
+        let mut reclaimed_bytes = 0;
+        while let Some(slot) = self.do_next_shrink_slot(candidates) {
+            let (slot_alive_accounts, slot_reclaimed_bytes) = self.do_shrink_stale_slot(slot);
+            alive_accounts += slot_alive_accounts;
+            reclaimed_bytes += slot_reclaimed_bytes;
+            if reclaimed_bytes >= max_reclaim_bytes || candidates.is_empty() {
+                break;
+            }
         }
+        alive_accounts
     }
 
     // Reads all accounts in given slot's AppendVecs and filter only to alive,
-    // then create a minimum AppendVec filled with the alive.
-    fn do_shrink_slot(&self, slot: Slot, forced: bool) -> usize {
+    // then create a minimum AppendVec filled with the alive. Returns the number of alive
+    // accounts and the number of bytes reclaimed
+    fn do_shrink_slot(&self, slot: Slot, forced: bool) -> (usize, u64) {
         trace!("shrink_stale_slot: slot: {}", slot);
 
         let mut stored_accounts = vec![];
@@ -996,26 +1162,52 @@ impl AccountsDB {
                         stored_count,
                         if forced { " (forced)" } else { "" },
                     );
-                    return 0;
-                } else if (alive_count as f32 / stored_count as f32) >= 0.80 && !forced {
+                    return (0, 0);
+                } else if (alive_count as f64 / stored_count as f64)
+                    >= self.shrink_config().shrink_ratio
+                    && !forced
+                {
                     trace!(
                         "shrink_stale_slot ({}): not enough space to shrink: {} / {}",
                         slot,
                         alive_count,
                         stored_count,
                     );
-                    return 0;
+                    return (0, 0);
                 }
                 for store in stores.values() {
                     let mut start = 0;
-                    while let Some((account, next)) = store.accounts.get_account(start) {
+                    loop {
+                        let read = store.accounts.get_account_with(start, |account| {
+                            (
+                                account.meta.pubkey,
+                                account.clone_account(),
+                                *account.hash,
+                                account.offset,
+                                account.meta.write_version,
+                            )
+                        });
+                        let ((pubkey, account, hash, offset, write_version), next) = match read {
+                            Ok(Some(read)) => read,
+                            Ok(None) => break,
+                            // The slot is left as it is, its accounts are still in place
+                            Err(err) => {
+                                warn!(
+                                    "shrink_stale_slot ({}): unable to read {}: {}",
+                                    slot,
+                                    store.accounts.get_path().display(),
+                                    err
+                                );
+                                return (0, 0);
+                            }
+                        };
                         stored_accounts.push((
-                            account.meta.pubkey,
-                            account.clone_account(),
-                            *account.hash,
+                            pubkey,
+                            account,
+                            hash,
                             next - start,
-                            (store.id, account.offset),
-                            account.meta.write_version,
+                            (store.id, offset),
+                            write_version,
                         ));
                         start = next;
                     }
@@ -1137,6 +1329,18 @@ impl AccountsDB {
         }
         rewrite_elapsed.stop();
 
+        let dead_bytes: u64 = dead_storages
+            .iter()
+            .map(|store| store.accounts.capacity())
+            .sum();
+        let reclaimed_bytes = dead_bytes.saturating_sub(aligned_total);
+        if !dead_storages.is_empty() {
+            self.stats.shrunk_slots.fetch_add(1, Ordering::Relaxed);
+            self.stats
+                .shrunk_bytes
+                .fetch_add(reclaimed_bytes, Ordering::Relaxed);
+        }
+
         let mut drop_storage_entries_elapsed = Measure::start("drop_storage_entries_elapsed");
         drop(dead_storages);
         drop_storage_entries_elapsed.stop();
@@ -1161,8 +1365,9 @@ impl AccountsDB {
                 drop_storage_entries_elapsed.as_us(),
                 i64
             ),
+            ("reclaimed_bytes", reclaimed_bytes, i64),
         );
-        alive_accounts.len()
+        (alive_accounts.len(), reclaimed_bytes)
     }
 
     // Infinitely returns rooted roots in cyclic order
@@ -1210,7 +1415,8 @@ impl AccountsDB {
         // with clean_accounts().
         let mut candidates = candidates.unwrap();
 
-        let count = self.shrink_stale_slot(&mut candidates);
+        let max_reclaim_bytes = self.shrink_config().max_reclaim_bytes_per_pass;
+        let count = self.shrink_stale_slots(&mut candidates, max_reclaim_bytes);
         measure.stop();
         inc_new_counter_info!("stale_slot_shrink-ms", measure.as_ms() as usize);
 
@@ -1266,6 +1472,55 @@ impl AccountsDB {
         collector
     }
 
+    /// Scans the accounts listed under `index_key` in the secondary indexes, in pubkey order. The
+    /// scanned version of an account may no longer have the key
+    pub fn index_scan_accounts<F, A>(
+        &self,
+        ancestors: &Ancestors,
+        index_key: IndexKey,
+        scan_func: F,
+    ) -> A
+    where
+        F: Fn(&mut A, Option<(&Pubkey, Account, Slot)>),
+        A: Default,
+    {
+        let mut collector = A::default();
+        self.accounts_index.index_scan_accounts(
+            ancestors,
+            index_key,
+            |pubkey, (account_info, slot)| {
+                let account_slot = self
+                    .get_account_from_storage(slot, account_info)
+                    .map(|account| (pubkey, account, slot));
+                scan_func(&mut collector, account_slot)
+            },
+        );
+        collector
+    }
+
+    /// Scans the accounts following `cursor` in pubkey order, until `scan_func` returns false.
+    /// The accounts are the ones listed under `index_key` in the secondary indexes if set, else
+    /// all of them
+    pub fn scan_accounts_from<F>(
+        &self,
+        ancestors: &Ancestors,
+        index_key: Option<IndexKey>,
+        cursor: Option<Pubkey>,
+        mut scan_func: F,
+    ) where
+        F: FnMut(&Pubkey, Account, Slot) -> bool,
+    {
+        self.accounts_index.scan_accounts_from(
+            ancestors,
+            index_key,
+            cursor,
+            |pubkey, (account_info, slot)| {
+                self.get_account_from_storage(slot, account_info)
+                    .map_or(true, |account| scan_func(pubkey, account, slot))
+            },
+        );
+    }
+
     /// Scan a specific slot through all the account storage in parallel
     pub fn scan_account_storage<F, B>(&self, slot: Slot, scan_func: F) -> Vec<B>
     where
@@ -1289,9 +1544,8 @@ impl AccountsDB {
             storage_maps
                 .into_par_iter()
                 .map(|storage| {
-                    let accounts = storage.accounts.accounts(0);
                     let mut retval = B::default();
-                    accounts.iter().for_each(|stored_account| {
+                    storage.scan_stored_accounts(|stored_account| {
                         scan_func(stored_account, storage.id, &mut retval)
                     });
                     retval
@@ -1342,9 +1596,8 @@ impl AccountsDB {
             .get_account_storage_entry(slot, store_id)
             .and_then(|store| {
                 store
-                    .accounts
-                    .get_account(offset)
-                    .map(|account| (account.0.clone_account(), slot))
+                    .get_stored_account_with(offset, |account| account.clone_account())
+                    .map(|(account, _)| (account, slot))
             })
     }
 
@@ -1370,8 +1623,10 @@ impl AccountsDB {
             .storage
             .get_account_storage_entry(slot, store_id)
             .unwrap();
-        let account = entry.accounts.get_account(offset);
-        *account.as_ref().unwrap().0.hash
+        let (hash, _) = entry
+            .get_stored_account_with(offset, |account| *account.hash)
+            .unwrap();
+        hash
     }
 
     pub fn load_slow(&self, ancestors: &Ancestors, pubkey: &Pubkey) -> Option<(Account, Slot)> {
@@ -1453,6 +1708,21 @@ impl AccountsDB {
         store
     }
 
+    /// Bytes of account data stored in `slot`
+    pub fn slot_stored_bytes(&self, slot: Slot) -> u64 {
+        self.storage
+            .get_slot_stores(slot)
+            .map(|slot_stores| {
+                slot_stores
+                    .read()
+                    .unwrap()
+                    .values()
+                    .map(|store| store.accounts.len() as u64)
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+
     pub fn purge_slot(&self, slot: Slot) {
         let mut slots = HashSet::new();
         slots.insert(slot);
@@ -1469,6 +1739,12 @@ impl AccountsDB {
         let mut total_removed_storage_entries = 0;
         let mut total_removed_bytes = 0;
 
+        if !non_roots.is_empty() {
+            if let Some(incremental_accounts_hash) = &self.incremental_accounts_hash {
+                incremental_accounts_hash.invalidate();
+            }
+        }
+
         let mut remove_storages_elapsed = Measure::start("remove_storages_elapsed");
         for slot in non_roots {
             if let Some((_, slot_removed_storages)) = self.storage.0.remove(&slot) {
@@ -1544,6 +1820,10 @@ impl AccountsDB {
         // 2) Purge this slot's storage entries from self.storage
         self.handle_reclaims(&reclaims, Some(remove_slot), false, None);
         assert!(self.storage.get_slot_stores(remove_slot).is_none());
+
+        if let Some(incremental_accounts_hash) = &self.incremental_accounts_hash {
+            incremental_accounts_hash.invalidate();
+        }
     }
 
     fn include_owner(cluster_type: &ClusterType, slot: Slot) -> bool {
@@ -1790,7 +2070,14 @@ impl AccountsDB {
             let mut append_accounts = Measure::start("append_accounts");
             let rvs = storage
                 .accounts
-                .append_accounts(&with_meta[infos.len()..], &hashes[infos.len()..]);
+                .append_accounts(&with_meta[infos.len()..], &hashes[infos.len()..])
+                .unwrap_or_else(|err| {
+                    panic!(
+                        "Unable to append accounts to {}: {}",
+                        storage.accounts.get_path().display(),
+                        err
+                    )
+                });
             append_accounts.stop();
             total_append_accounts_us += append_accounts.as_us();
             if rvs.is_empty() {
@@ -1982,24 +2269,24 @@ impl AccountsDB {
         }
     }
 
-    fn calculate_accounts_hash(
+    // Collects the hashes and balances of the accounts of `slot` within the pubkey `ranges`
+    fn scan_account_hashes(
         &self,
         slot: Slot,
         ancestors: &Ancestors,
         check_hash: bool,
-    ) -> Result<(Hash, u64), BankHashVerificationError> {
+        ranges: &[(Bound<Pubkey>, Bound<Pubkey>)],
+    ) -> Result<Vec<AccountHash>, BankHashVerificationError> {
         use BankHashVerificationError::*;
-        let mut scan = Measure::start("scan");
-        let keys: Vec<_> = self
-            .accounts_index
-            .account_maps
-            .read()
-            .unwrap()
-            .keys()
-            .cloned()
-            .collect();
+        let keys: Vec<_> = {
+            let account_maps = self.accounts_index.account_maps.read().unwrap();
+            ranges
+                .iter()
+                .flat_map(|range| account_maps.range(*range).map(|(pubkey, _)| *pubkey))
+                .collect()
+        };
         let mismatch_found = AtomicU64::new(0);
-        let hashes: Vec<(Pubkey, Hash, u64)> = keys
+        let hashes: Vec<AccountHash> = keys
             .par_iter()
             .filter_map(|pubkey| {
                 if let Some((lock, index)) =
@@ -2010,28 +2297,33 @@ impl AccountsDB {
                         self.storage
                             .get_account_storage_entry(*slot, account_info.store_id)
                             .and_then(|store| {
-                                let account = store.accounts.get_account(account_info.offset)?.0;
-                                let balance = Self::account_balance_for_capitalization(
-                                    account_info.lamports,
-                                    &account.account_meta.owner,
-                                    account.account_meta.executable,
-                                );
-
-                                if check_hash {
-                                    let hash = Self::hash_stored_account(
-                                        *slot,
-                                        &account,
-                                        &self
-                                            .cluster_type
-                                            .expect("Cluster type must be set at initialization"),
-                                    );
-                                    if hash != *account.hash {
-                                        mismatch_found.fetch_add(1, Ordering::Relaxed);
-                                        return None;
-                                    }
-                                }
-
-                                Some((*pubkey, *account.hash, balance))
+                                let (account_hash, _) = store.get_stored_account_with(
+                                    account_info.offset,
+                                    |account| {
+                                        let balance = Self::account_balance_for_capitalization(
+                                            account_info.lamports,
+                                            &account.account_meta.owner,
+                                            account.account_meta.executable,
+                                        );
+
+                                        if check_hash {
+                                            let hash = Self::hash_stored_account(
+                                                *slot,
+                                                account,
+                                                &self.cluster_type.expect(
+                                                    "Cluster type must be set at initialization",
+                                                ),
+                                            );
+                                            if hash != *account.hash {
+                                                mismatch_found.fetch_add(1, Ordering::Relaxed);
+                                                return None;
+                                            }
+                                        }
+
+                                        Some((*pubkey, *account.hash, balance))
+                                    },
+                                )?;
+                                account_hash
                             })
                     } else {
                         None
@@ -2049,6 +2341,32 @@ impl AccountsDB {
             return Err(MismatchedAccountHash);
         }
 
+        Ok(hashes)
+    }
+
+    fn calculate_accounts_hash(
+        &self,
+        slot: Slot,
+        ancestors: &Ancestors,
+        check_hash: bool,
+    ) -> Result<(Hash, u64), BankHashVerificationError> {
+        if let Some(incremental_accounts_hash) = &self.incremental_accounts_hash {
+            // Checking the hashes of the accounts needs them all to be loaded anyway
+            if !check_hash {
+                return Ok(self.calculate_accounts_hash_incremental(
+                    slot,
+                    ancestors,
+                    incremental_accounts_hash,
+                ));
+            }
+        }
+        let mut scan = Measure::start("scan");
+        let hashes = self.scan_account_hashes(
+            slot,
+            ancestors,
+            check_hash,
+            &[(Bound::Unbounded, Bound::Unbounded)],
+        )?;
         scan.stop();
         let hash_total = hashes.len();
 
@@ -2065,6 +2383,52 @@ impl AccountsDB {
         Ok((accumulated_hash, total_lamports))
     }
 
+    fn calculate_accounts_hash_incremental(
+        &self,
+        slot: Slot,
+        ancestors: &Ancestors,
+        incremental_accounts_hash: &IncrementalAccountsHash,
+    ) -> (Hash, u64) {
+        let is_ancestor = |calculated_slot: Slot| {
+            ancestors.contains_key(&calculated_slot)
+                || (self.accounts_index.is_root(calculated_slot)
+                    && self.accounts_index.is_root(slot))
+        };
+        let mut rehashed_partitions = 0;
+        let mut hash_total = 0;
+        let mut scan_us = 0;
+        let mut measure = Measure::start("incremental_accounts_hash");
+        let (accounts_hash, total_lamports) =
+            incremental_accounts_hash.calculate(slot, is_ancestor, |partitions| {
+                let mut scan = Measure::start("scan");
+                let ranges: Vec<_> = partitions
+                    .iter()
+                    .map(|partition| incremental_accounts_hash.partition_bounds(*partition))
+                    .collect();
+                let hashes = self
+                    .scan_account_hashes(slot, ancestors, false, &ranges)
+                    .expect("account hashes are not checked");
+                scan.stop();
+                rehashed_partitions = partitions.len();
+                hash_total = hashes.len();
+                scan_us = scan.as_us();
+                hashes
+            });
+        measure.stop();
+        datapoint_info!(
+            "update_accounts_hash",
+            ("accounts_scan", scan_us, i64),
+            (
+                "hash_accumulate",
+                measure.as_us().saturating_sub(scan_us),
+                i64
+            ),
+            ("hash_total", hash_total, i64),
+            ("rehashed_partitions", rehashed_partitions, i64),
+        );
+        (accounts_hash, total_lamports)
+    }
+
     pub fn get_accounts_hash(&self, slot: Slot) -> Hash {
         let bank_hashes = self.bank_hashes.read().unwrap();
         let bank_hash_info = bank_hashes.get(&slot).unwrap();
@@ -2164,9 +2528,16 @@ impl AccountsDB {
     ) -> SlotList<AccountInfo> {
         let mut reclaims = SlotList::<AccountInfo>::with_capacity(infos.len() * 2);
         for (info, pubkey_account) in infos.into_iter().zip(accounts.iter()) {
-            let pubkey = pubkey_account.0;
+            let (pubkey, account) = pubkey_account;
             self.accounts_index
                 .upsert(slot, pubkey, info, &mut reclaims);
+            self.accounts_index.update_secondary_indexes(
+                slot,
+                pubkey,
+                &account.owner,
+                &account.data,
+                &self.account_indexes,
+            );
         }
         reclaims
     }
@@ -2238,11 +2609,11 @@ impl AccountsDB {
                 stores
                     .into_par_iter()
                     .map(|store| {
-                        let accounts = store.accounts.accounts(0);
-                        accounts
-                            .into_iter()
-                            .map(|account| (store.slot, account.meta.pubkey))
-                            .collect::<HashSet<(Slot, Pubkey)>>()
+                        let mut store_pubkeys = HashSet::new();
+                        store.scan_stored_accounts(|account| {
+                            store_pubkeys.insert((store.slot, account.meta.pubkey));
+                        });
+                        store_pubkeys
                     })
                     .reduce(HashSet::new, |mut reduced, store_pubkeys| {
                         reduced.extend(store_pubkeys);
@@ -2349,9 +2720,39 @@ impl AccountsDB {
         }
     }
 
+    fn quarantine_account(&self, pubkey: &Pubkey, data_len: usize) {
+        let mut quarantined_accounts = self.quarantined_accounts.write().unwrap();
+        if quarantined_accounts.insert(*pubkey, data_len).is_none() {
+            warn!(
+                "Account {} quarantined. Data length {} exceeds {}",
+                pubkey, data_len, MAX_PERMITTED_DATA_LENGTH
+            );
+        }
+    }
+
+    /// Quarantine any account in `accounts` holding more data than is permitted
+    fn quarantine_oversized_accounts(&self, accounts: &[(&Pubkey, &Account)]) {
+        for (pubkey, account) in accounts.iter() {
+            if account.data.len() as u64 > MAX_PERMITTED_DATA_LENGTH {
+                self.quarantine_account(pubkey, account.data.len());
+            }
+        }
+    }
+
+    /// Accounts that have been observed to exceed the maximum permitted data length
+    pub fn quarantined_accounts(&self) -> Vec<(Pubkey, usize)> {
+        self.quarantined_accounts
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(pubkey, data_len)| (*pubkey, *data_len))
+            .collect()
+    }
+
     /// Store the account update.
     pub fn store(&self, slot: Slot, accounts: &[(&Pubkey, &Account)]) {
         self.assert_frozen_accounts(accounts);
+        self.quarantine_oversized_accounts(accounts);
         let mut hash_time = Measure::start("hash_accounts");
         let hashes = self.hash_accounts(
             slot,
@@ -2440,6 +2841,9 @@ impl AccountsDB {
         self.stats
             .store_update_index
             .fetch_add(update_index_time.as_us(), Ordering::Relaxed);
+        if let Some(incremental_accounts_hash) = &self.incremental_accounts_hash {
+            incremental_accounts_hash.touch(slot, accounts.iter().map(|(pubkey, _)| *pubkey));
+        }
 
         // A store for a single slot should:
         // 1) Only make "reclaims" for the same slot
@@ -2520,6 +2924,19 @@ impl AccountsDB {
                             offset: stored_account.offset,
                             lamports: stored_account.account_meta.lamports,
                         };
+                        if stored_account.meta.data_len > MAX_PERMITTED_DATA_LENGTH {
+                            self.quarantine_account(
+                                &stored_account.meta.pubkey,
+                                stored_account.meta.data_len as usize,
+                            );
+                        }
+                        self.accounts_index.update_secondary_indexes(
+                            *slot,
+                            &stored_account.meta.pubkey,
+                            &stored_account.account_meta.owner,
+                            stored_account.data,
+                            &self.account_indexes,
+                        );
                         let entry = accum
                             .entry(stored_account.meta.pubkey)
                             .or_insert_with(Vec::new);
@@ -2579,9 +2996,11 @@ impl AccountsDB {
                     trace!("id: {} clearing count", id);
                     store.count_and_status.write().unwrap().0 = 0;
                 }
+                let mut stored_count = 0;
+                store.scan_stored_accounts(|_| stored_count += 1);
                 store
                     .approx_store_count
-                    .store(store.accounts.accounts(0).len(), Ordering::Relaxed);
+                    .store(stored_count, Ordering::Relaxed);
             }
         }
     }
@@ -4181,6 +4600,26 @@ pub mod tests {
         db.store(3, &[(&frozen_pubkey, &account)]);
     }
 
+    #[test]
+    fn test_quarantine_oversized_accounts() {
+        let db = AccountsDB::new_sized(Vec::new(), 2 * MAX_PERMITTED_DATA_LENGTH);
+        let owner = Pubkey::default();
+
+        let pubkey = solana_sdk::pubkey::new_rand();
+        let account = Account::new(1, MAX_PERMITTED_DATA_LENGTH as usize, &owner);
+        db.store(0, &[(&pubkey, &account)]);
+        assert!(db.quarantined_accounts().is_empty());
+
+        let oversized_pubkey = solana_sdk::pubkey::new_rand();
+        let oversized_len = MAX_PERMITTED_DATA_LENGTH as usize + 1;
+        let oversized_account = Account::new(1, oversized_len, &owner);
+        db.store(0, &[(&oversized_pubkey, &oversized_account)]);
+        assert_eq!(
+            db.quarantined_accounts(),
+            vec![(oversized_pubkey, oversized_len)]
+        );
+    }
+
     #[test]
     #[should_panic(
         expected = "Frozen account My11111111111111111111111111111111111111111 modified.  Lamports decreased from 1 to 0"
@@ -4359,6 +4798,43 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_incremental_accounts_hash() {
+        solana_logger::setup();
+        let mut db = AccountsDB::new(Vec::new(), &ClusterType::Development);
+        assert!(db.incremental_accounts_hash.is_none());
+        db.incremental_accounts_hash = Some(IncrementalAccountsHash::new(16));
+        let keys: Vec<_> = (0..100).map(|_| solana_sdk::pubkey::new_rand()).collect();
+        let accounts: Vec<_> = (0..100)
+            .map(|lamports| Account::new(lamports, 0, &Pubkey::default()))
+            .collect();
+        let mut ancestors = Ancestors::default();
+
+        for slot in 0..4 {
+            ancestors.insert(slot, 0);
+            // Store a few accounts in each slot, removing the first of them in the last slot
+            for i in (slot as usize * 10)..(slot as usize * 10 + 30) {
+                db.store(slot, &[(&keys[i], &accounts[i])]);
+            }
+            if slot == 3 {
+                db.store(slot, &[(&keys[30], &Account::default())]);
+            }
+            db.add_root(slot);
+            assert_eq!(
+                db.calculate_accounts_hash(slot, &ancestors, false).unwrap(),
+                db.calculate_accounts_hash(slot, &ancestors, true).unwrap(),
+            );
+        }
+
+        // An unrooted slot which does not list the last calculated slot as an ancestor
+        let ancestors = vec![(4, 0)].into_iter().collect();
+        db.store(4, &[(&keys[0], &accounts[1])]);
+        assert_eq!(
+            db.calculate_accounts_hash(4, &ancestors, false).unwrap(),
+            db.calculate_accounts_hash(4, &ancestors, true).unwrap(),
+        );
+    }
+
     #[test]
     fn test_verify_bank_capitalization() {
         use BankHashVerificationError::*;
@@ -4977,6 +5453,41 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_shrink_stale_slots_configured() {
+        solana_logger::setup();
+
+        let accounts = AccountsDB::new_single();
+        let pubkeys: Vec<_> = (0..100).map(|_| solana_sdk::pubkey::new_rand()).collect();
+        let account = Account::new(223, 0, &Account::default().owner);
+
+        for pubkey in &pubkeys {
+            accounts.store(1, &[(&pubkey, &account)]);
+        }
+        accounts.add_root(1);
+        for pubkey in &pubkeys[0..10] {
+            accounts.store(2, &[(&pubkey, &account)]);
+        }
+        accounts.add_root(2);
+        accounts.clean_accounts(None);
+
+        // 90% of the accounts of slot 1 are alive
+        assert_eq!(accounts.process_stale_slot(), 0);
+        assert_eq!(accounts.process_stale_slot(), 0);
+        assert_eq!(accounts.reclaimed_storage(), ReclaimedStorage::default());
+
+        // A single pass visits both slots, and shrinks slot 1
+        accounts.set_shrink_config(ShrinkConfig {
+            shrink_ratio: 0.95,
+            max_reclaim_bytes_per_pass: std::u64::MAX,
+        });
+        assert_eq!(accounts.process_stale_slot(), 90);
+        assert_eq!(accounts.all_account_count_in_append_vec(1), 90);
+        let reclaimed_storage = accounts.reclaimed_storage();
+        assert_eq!(reclaimed_storage.shrunk_slots, 1);
+        assert!(reclaimed_storage.shrunk_bytes > 0);
+    }
+
     #[test]
     fn test_delete_dependencies() {
         solana_logger::setup();
@@ -1,3 +1,4 @@
+use crate::{bank::inline_spl_token_v2_0, secondary_index::SecondaryIndex};
 use ouroboros::self_referencing;
 use solana_sdk::{clock::Slot, pubkey::Pubkey};
 use std::ops::{
@@ -27,6 +28,44 @@ pub type AccountMap<K, V> = BTreeMap<K, V>;
 
 type AccountMapEntry<T> = Arc<AccountMapEntryInner<T>>;
 
+/// A secondary index of the accounts, enabled with `--account-index`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AccountIndex {
+    /// Accounts by owner program
+    ProgramId,
+    /// SPL token accounts by mint
+    SplTokenMint,
+}
+
+pub type AccountIndexes = HashSet<AccountIndex>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexKey {
+    ProgramId(Pubkey),
+    SplTokenMint(Pubkey),
+}
+
+impl IndexKey {
+    /// Whether `owner` and `data` are those of an account having this key
+    pub fn matches(&self, owner: &Pubkey, data: &[u8]) -> bool {
+        match self {
+            IndexKey::ProgramId(program_id) => owner == program_id,
+            IndexKey::SplTokenMint(mint) => spl_token_mint(owner, data) == Some(*mint),
+        }
+    }
+}
+
+fn spl_token_mint(owner: &Pubkey, data: &[u8]) -> Option<Pubkey> {
+    use inline_spl_token_v2_0::state::{ACCOUNT_LEN, ACCOUNT_MINT_OFFSET};
+    if *owner == inline_spl_token_v2_0::id() && data.len() == ACCOUNT_LEN {
+        Some(Pubkey::new(
+            &data[ACCOUNT_MINT_OFFSET..ACCOUNT_MINT_OFFSET + std::mem::size_of::<Pubkey>()],
+        ))
+    } else {
+        None
+    }
+}
+
 #[derive(Debug)]
 pub struct AccountMapEntryInner<T> {
     ref_count: AtomicU64,
@@ -183,6 +222,8 @@ impl<'a, T: 'static + Clone> Iterator for AccountsIndexIterator<'a, T> {
 #[derive(Debug, Default)]
 pub struct AccountsIndex<T> {
     pub account_maps: RwLock<AccountMap<Pubkey, AccountMapEntry<T>>>,
+    program_id_index: SecondaryIndex,
+    spl_token_mint_index: SecondaryIndex,
     roots_tracker: RwLock<RootsTracker>,
 }
 
@@ -258,6 +299,8 @@ impl<T: 'static + Clone> AccountsIndex<T> {
                 if let btree_map::Entry::Occupied(index_entry) = w_index.entry(*key) {
                     if index_entry.get().slot_list.read().unwrap().is_empty() {
                         index_entry.remove();
+                        self.program_id_index.remove(key);
+                        self.spl_token_mint_index.remove(key);
                     }
                 }
             }
@@ -281,6 +324,103 @@ impl<T: 'static + Clone> AccountsIndex<T> {
         self.do_scan_accounts(ancestors, func, Some(range));
     }
 
+    /// call func, in pubkey order, with every pubkey listed under `index_key` in the secondary
+    /// indexes and its index visible from a given set of ancestors. The visible version of the
+    /// account may no longer have the key
+    pub(crate) fn index_scan_accounts<F>(
+        &self,
+        ancestors: &Ancestors,
+        index_key: IndexKey,
+        mut func: F,
+    ) where
+        F: FnMut(&Pubkey, (&T, Slot)),
+    {
+        let mut pubkeys = match index_key {
+            IndexKey::ProgramId(key) => self.program_id_index.get(&key),
+            IndexKey::SplTokenMint(key) => self.spl_token_mint_index.get(&key),
+        };
+        pubkeys.sort_unstable();
+        for pubkey in pubkeys {
+            if let Some((locked_entry, index)) = self.get(&pubkey, Some(ancestors), None) {
+                let list_r = locked_entry.slot_list();
+                func(&pubkey, (&list_r[index].1, list_r[index].0));
+            }
+        }
+    }
+
+    /// call func, in pubkey order, with every pubkey following `cursor` and its index visible from
+    /// a given set of ancestors, until func returns false. The pubkeys are the ones listed under
+    /// `index_key` in the secondary indexes if set, else all of them
+    pub(crate) fn scan_accounts_from<F>(
+        &self,
+        ancestors: &Ancestors,
+        index_key: Option<IndexKey>,
+        cursor: Option<Pubkey>,
+        mut func: F,
+    ) where
+        F: FnMut(&Pubkey, (&T, Slot)) -> bool,
+    {
+        let follows_cursor = |pubkey: &Pubkey| cursor.map_or(true, |cursor| *pubkey > cursor);
+        match index_key {
+            Some(index_key) => {
+                let mut pubkeys = match index_key {
+                    IndexKey::ProgramId(key) => self.program_id_index.get(&key),
+                    IndexKey::SplTokenMint(key) => self.spl_token_mint_index.get(&key),
+                };
+                pubkeys.retain(follows_cursor);
+                pubkeys.sort_unstable();
+                for pubkey in pubkeys {
+                    if let Some((locked_entry, index)) = self.get(&pubkey, Some(ancestors), None) {
+                        let list_r = locked_entry.slot_list();
+                        if !func(&pubkey, (&list_r[index].1, list_r[index].0)) {
+                            return;
+                        }
+                    }
+                }
+            }
+            None => {
+                let start = cursor.map_or(Unbounded, Excluded);
+                for pubkey_list in self.iter(Some((start, Unbounded))) {
+                    for (pubkey, list) in pubkey_list {
+                        let list_r = &list.slot_list.read().unwrap();
+                        if let Some(index) = self.latest_slot(Some(ancestors), &list_r, None) {
+                            if !func(&pubkey, (&list_r[index].1, list_r[index].0)) {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lists `pubkey`, stored in `slot`, under its keys in the enabled secondary indexes
+    pub fn update_secondary_indexes(
+        &self,
+        slot: Slot,
+        pubkey: &Pubkey,
+        account_owner: &Pubkey,
+        account_data: &[u8],
+        account_indexes: &AccountIndexes,
+    ) {
+        if account_indexes.contains(&AccountIndex::ProgramId) {
+            self.program_id_index.insert(account_owner, pubkey, slot);
+        }
+        if account_indexes.contains(&AccountIndex::SplTokenMint) {
+            if let Some(mint) = spl_token_mint(account_owner, account_data) {
+                self.spl_token_mint_index.insert(&mint, pubkey, slot);
+            }
+        }
+    }
+
+    fn purge_secondary_indexes(&self, pubkey: &Pubkey, reclaims: SlotSlice<T>) {
+        if !reclaims.is_empty() {
+            let slots: HashSet<Slot> = reclaims.iter().map(|(slot, _)| *slot).collect();
+            self.program_id_index.remove_slots(pubkey, &slots);
+            self.spl_token_mint_index.remove_slots(pubkey, &slots);
+        }
+    }
+
     pub fn get_rooted_entries(&self, slice: SlotSlice<T>) -> SlotList<T> {
         slice
             .iter()
@@ -307,18 +447,20 @@ impl<T: 'static + Clone> AccountsIndex<T> {
         let slot_list = write_account_map_entry.slot_list_mut();
         let reclaims = self.get_rooted_entries(&slot_list);
         slot_list.retain(|(slot, _)| !self.is_root(*slot));
+        self.purge_secondary_indexes(pubkey, &reclaims);
         (reclaims, slot_list.is_empty())
     }
 
     pub fn purge_exact(&self, pubkey: &Pubkey, slots: HashSet<Slot>) -> (SlotList<T>, bool) {
         let mut write_account_map_entry = self.get_account_write_entry(pubkey).unwrap();
         let slot_list = write_account_map_entry.slot_list_mut();
-        let reclaims = slot_list
+        let reclaims: SlotList<T> = slot_list
             .iter()
             .filter(|(slot, _)| slots.contains(&slot))
             .cloned()
             .collect();
         slot_list.retain(|(slot, _)| !slots.contains(slot));
+        self.purge_secondary_indexes(pubkey, &reclaims);
         (reclaims, slot_list.is_empty())
     }
 
@@ -445,7 +587,9 @@ impl<T: 'static + Clone> AccountsIndex<T> {
         max_clean_root: Option<Slot>,
     ) {
         if let Some(mut locked_entry) = self.get_account_write_entry(pubkey) {
+            let num_reclaims = reclaims.len();
             self.purge_older_root_entries(locked_entry.slot_list_mut(), reclaims, max_clean_root);
+            self.purge_secondary_indexes(pubkey, &reclaims[num_reclaims..]);
         }
     }
 
@@ -456,6 +600,7 @@ impl<T: 'static + Clone> AccountsIndex<T> {
         reclaims: &mut SlotList<T>,
     ) {
         if let Some(mut locked_entry) = self.get_account_write_entry(pubkey) {
+            let num_reclaims = reclaims.len();
             let slot_list = locked_entry.slot_list_mut();
             slot_list.retain(|(slot, entry)| {
                 if *slot == purge_slot {
@@ -463,6 +608,7 @@ impl<T: 'static + Clone> AccountsIndex<T> {
                 }
                 *slot != purge_slot
             });
+            self.purge_secondary_indexes(pubkey, &reclaims[num_reclaims..]);
         }
     }
 
@@ -551,6 +697,69 @@ mod tests {
         assert_eq!(num, 0);
     }
 
+    #[test]
+    fn test_secondary_indexes() {
+        let index = AccountsIndex::<bool>::default();
+        let account_indexes: AccountIndexes = [AccountIndex::ProgramId, AccountIndex::SplTokenMint]
+            .iter()
+            .copied()
+            .collect();
+        let pubkey = solana_sdk::pubkey::new_rand();
+        let owner = solana_sdk::pubkey::new_rand();
+        let mint = solana_sdk::pubkey::new_rand();
+        let token_program_id = inline_spl_token_v2_0::id();
+        let mut token_data = vec![0; inline_spl_token_v2_0::state::ACCOUNT_LEN];
+        token_data[..32].copy_from_slice(mint.as_ref());
+        let mut gc = Vec::new();
+        index.upsert(0, &pubkey, true, &mut gc);
+        index.update_secondary_indexes(
+            0,
+            &pubkey,
+            &token_program_id,
+            &token_data,
+            &account_indexes,
+        );
+        index.upsert(1, &pubkey, true, &mut gc);
+        index.update_secondary_indexes(1, &pubkey, &owner, &[], &account_indexes);
+
+        let ancestors: Ancestors = vec![(0, 0), (1, 1)].into_iter().collect();
+        let scan = |index_key| {
+            let mut found = vec![];
+            index.index_scan_accounts(&ancestors, index_key, |pubkey, (_, slot)| {
+                found.push((*pubkey, slot))
+            });
+            found
+        };
+        assert_eq!(scan(IndexKey::ProgramId(owner)), vec![(pubkey, 1)]);
+        // Still listed under the keys it had in slot 0
+        assert_eq!(scan(IndexKey::SplTokenMint(mint)), vec![(pubkey, 1)]);
+        assert_eq!(
+            scan(IndexKey::ProgramId(token_program_id)),
+            vec![(pubkey, 1)]
+        );
+        assert!(IndexKey::SplTokenMint(mint).matches(&token_program_id, &token_data));
+        assert!(!IndexKey::SplTokenMint(mint).matches(&owner, &token_data));
+        assert!(!IndexKey::SplTokenMint(mint).matches(&token_program_id, &[]));
+
+        // until slot 0 is purged
+        index.purge_exact(&pubkey, vec![0].into_iter().collect());
+        assert!(scan(IndexKey::SplTokenMint(mint)).is_empty());
+        assert!(scan(IndexKey::ProgramId(token_program_id)).is_empty());
+        assert_eq!(scan(IndexKey::ProgramId(owner)), vec![(pubkey, 1)]);
+
+        // Only the enabled indexes are updated
+        let other_pubkey = solana_sdk::pubkey::new_rand();
+        let program_id_index: AccountIndexes = vec![AccountIndex::ProgramId].into_iter().collect();
+        index.update_secondary_indexes(
+            1,
+            &other_pubkey,
+            &token_program_id,
+            &token_data,
+            &program_id_index,
+        );
+        assert!(scan(IndexKey::SplTokenMint(mint)).is_empty());
+    }
+
     #[test]
     fn test_insert_no_ancestors() {
         let key = Keypair::new();
@@ -8,13 +8,15 @@ use solana_sdk::{
     pubkey::Pubkey,
 };
 use std::{
-    fs::{remove_file, OpenOptions},
+    collections::{HashMap, VecDeque},
+    fmt,
+    fs::{remove_file, File, OpenOptions},
     io,
     io::{Seek, SeekFrom, Write},
     mem,
     path::{Path, PathBuf},
-    sync::atomic::{AtomicUsize, Ordering},
-    sync::Mutex,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    sync::{Arc, Mutex, RwLock},
 };
 
 //Data placement should be aligned at the next boundary. Without alignment accessing the memory may
@@ -28,6 +30,462 @@ macro_rules! u64_align {
 
 const MAXIMUM_APPEND_VEC_FILE_SIZE: usize = 16 * 1024 * 1024 * 1024; // 16 GiB
 
+/// Length of the fixed size part of a stored account, up to its data
+const STORED_ACCOUNT_HEADER_LEN: usize = u64_align!(
+    u64_align!(u64_align!(mem::size_of::<StoredMeta>()) + mem::size_of::<AccountMeta>())
+        + mem::size_of::<Hash>()
+);
+
+/// Size of the blocks the direct IO backend caches and writes, and alignment of the offsets,
+/// lengths and buffers of `O_DIRECT` IO
+const DIRECT_IO_BLOCK_SIZE: usize = 4096;
+
+/// Number of independently locked shards of the direct IO block cache
+const BLOCK_CACHE_SHARDS: usize = 16;
+
+/// Default size, in bytes, of the block cache of the direct IO backend
+pub const DEFAULT_DIRECT_IO_CACHE_SIZE: usize = 1024 * 1024 * 1024; // 1 GiB
+
+/// How the data of the append vecs is read and written
+#[derive(Clone, Debug)]
+pub enum AppendVecBackend {
+    /// The files are memory-mapped, the kernel pages the data in and writes it back as it sees
+    /// fit, competing with the rest of the page cache
+    Mmap,
+    /// The data is read with `pread` through a bounded cache of blocks shared by the append vecs,
+    /// and each append is written through with `pwrite`, with `O_DIRECT` where the file system
+    /// supports it so that the page cache is bypassed. Unix only.
+    DirectIo(Arc<BlockCache>),
+}
+
+impl Default for AppendVecBackend {
+    fn default() -> Self {
+        AppendVecBackend::Mmap
+    }
+}
+
+impl AppendVecBackend {
+    /// The direct IO backend, caching up to `cache_size` bytes of the append vecs
+    pub fn direct_io(cache_size: usize) -> Self {
+        if cfg!(not(unix)) {
+            warn!("The direct IO append vec backend is only available on unix, using mmap");
+        }
+        AppendVecBackend::DirectIo(Arc::new(BlockCache::new(cache_size)))
+    }
+}
+
+#[repr(C, align(4096))] // DIRECT_IO_BLOCK_SIZE
+struct Block([u8; DIRECT_IO_BLOCK_SIZE]);
+
+impl Block {
+    fn zeroed() -> Box<Self> {
+        Box::new(Block([0; DIRECT_IO_BLOCK_SIZE]))
+    }
+}
+
+#[derive(Default)]
+struct CachedBlocks {
+    blocks: HashMap<(u64, usize), Box<Block>>,
+    // Oldest first
+    order: VecDeque<(u64, usize)>,
+}
+
+/// Blocks of the append vecs read or written by the direct IO backend, evicting the oldest
+/// blocks past its capacity. The blocks of a file are keyed by an id renewed whenever its
+/// contents are discarded, the stale blocks are left to be evicted.
+pub struct BlockCache {
+    shard_capacity: usize,
+    next_file_id: AtomicU64,
+    shards: Vec<Mutex<CachedBlocks>>,
+}
+
+impl BlockCache {
+    pub fn new(size: usize) -> Self {
+        Self {
+            shard_capacity: (size / DIRECT_IO_BLOCK_SIZE / BLOCK_CACHE_SHARDS).max(1),
+            next_file_id: AtomicU64::default(),
+            shards: (0..BLOCK_CACHE_SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    fn new_file_id(&self) -> u64 {
+        self.next_file_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn shard(&self, file_id: u64, index: usize) -> &Mutex<CachedBlocks> {
+        &self.shards[(file_id as usize).wrapping_add(index) % BLOCK_CACHE_SHARDS]
+    }
+
+    /// Copies the cached bytes of the block from `start` into `dst`, returns false if the block
+    /// is not cached
+    fn read(&self, file_id: u64, index: usize, start: usize, dst: &mut [u8]) -> bool {
+        let shard = self.shard(file_id, index).lock().unwrap();
+        match shard.blocks.get(&(file_id, index)) {
+            Some(block) => {
+                dst.copy_from_slice(&block.0[start..start + dst.len()]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&self, file_id: u64, index: usize, block: Box<Block>) {
+        let mut shard = self.shard(file_id, index).lock().unwrap();
+        if shard.blocks.insert((file_id, index), block).is_none() {
+            shard.order.push_back((file_id, index));
+            while shard.order.len() > self.shard_capacity {
+                let evicted = shard.order.pop_front().unwrap();
+                shard.blocks.remove(&evicted);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for BlockCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BlockCache")
+            .field("capacity", &(self.shard_capacity * BLOCK_CACHE_SHARDS))
+            .finish()
+    }
+}
+
+// Views plain data, made of bytes or integers without padding, as bytes
+#[cfg(unix)]
+fn as_bytes_mut<T>(values: &mut [T]) -> &mut [u8] {
+    //UNSAFE: Every byte pattern is a valid value of the plain data
+    unsafe {
+        std::slice::from_raw_parts_mut(values.as_mut_ptr() as *mut u8, mem::size_of_val(values))
+    }
+}
+
+/// Where an append vec keeps its data, see `AppendVecBackend`
+trait AppendVecStorage: fmt::Debug + Send + Sync {
+    /// The data, if it is memory-mapped, for the accounts to be lent out
+    fn mapped(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Calls `f` with the `len` bytes at `offset`, aligned like the append vec
+    fn read_with(&self, offset: usize, len: usize, f: &mut dyn FnMut(&[u8])) -> io::Result<()>;
+
+    /// Writes `vals` one after the other from `offset`, each one aligned. Only called by the
+    /// appending thread, past the data appended so far.
+    fn append(&self, offset: usize, vals: &[&[u8]]) -> io::Result<()>;
+
+    fn flush(&self) -> io::Result<()>;
+
+    /// Discards the data, once the append vec is reset
+    fn reset(&self) {}
+}
+
+fn new_storage(
+    backend: &AppendVecBackend,
+    file: File,
+    path: &Path,
+    file_size: usize,
+    len: usize,
+) -> io::Result<Box<dyn AppendVecStorage>> {
+    match backend {
+        #[cfg(unix)]
+        AppendVecBackend::DirectIo(cache) => Ok(Box::new(DirectIoStorage::new(
+            file,
+            path,
+            file_size,
+            len,
+            cache.clone(),
+        )?)),
+        _ => {
+            //UNSAFE: Required to create a Mmap
+            let map = unsafe { MmapMut::map_mut(&file)? };
+            Ok(Box::new(MmapStorage(map)))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MmapStorage(MmapMut);
+
+impl AppendVecStorage for MmapStorage {
+    fn mapped(&self) -> Option<&[u8]> {
+        Some(&self.0)
+    }
+
+    fn read_with(&self, offset: usize, len: usize, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        f(&self.0[offset..offset + len]);
+        Ok(())
+    }
+
+    fn append(&self, mut offset: usize, vals: &[&[u8]]) -> io::Result<()> {
+        for val in vals {
+            let pos = u64_align!(offset);
+            let data = &self.0[pos..(pos + val.len())];
+            //UNSAFE: This mut append is safe because only 1 thread can append at a time
+            //Mutex<append_offset> guarantees exclusive write access to the memory occupied in
+            //the range.
+            unsafe {
+                let dst = data.as_ptr() as *mut u8;
+                std::ptr::copy(val.as_ptr(), dst, val.len());
+            };
+            offset = pos + val.len();
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(unix)]
+struct TailBlock {
+    index: usize,
+    block: Box<Block>,
+}
+
+#[cfg(unix)]
+struct DirectIoStorage {
+    /// The only descriptor of the file, opened with `O_DIRECT` if `direct`
+    file: File,
+    direct: bool,
+    file_size: usize,
+    cache: Arc<BlockCache>,
+    file_id: AtomicU64,
+    /// The block being appended to. The blocks before it are complete and no longer change, so
+    /// they can be cached.
+    tail: RwLock<TailBlock>,
+}
+
+#[cfg(unix)]
+impl fmt::Debug for DirectIoStorage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DirectIoStorage")
+            .field("file", &self.file)
+            .field("direct", &self.direct)
+            .field("file_size", &self.file_size)
+            .finish()
+    }
+}
+
+#[cfg(unix)]
+impl DirectIoStorage {
+    fn new(
+        file: File,
+        path: &Path,
+        file_size: usize,
+        len: usize,
+        cache: Arc<BlockCache>,
+    ) -> io::Result<Self> {
+        let (file, direct) = match Self::open_direct(path) {
+            Some(direct_file) => (direct_file, true),
+            None => (file, false),
+        };
+        let file_id = cache.new_file_id();
+        let mut storage = Self {
+            file,
+            direct,
+            file_size,
+            cache,
+            file_id: AtomicU64::new(file_id),
+            tail: RwLock::new(TailBlock {
+                index: 0,
+                block: Block::zeroed(),
+            }),
+        };
+        let index = len / DIRECT_IO_BLOCK_SIZE;
+        let block = if len % DIRECT_IO_BLOCK_SIZE == 0 {
+            Block::zeroed()
+        } else {
+            storage.read_block(index)?
+        };
+        *storage.tail.get_mut().unwrap() = TailBlock { index, block };
+        Ok(storage)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn open_direct(path: &Path) -> Option<File> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(path)
+            .map_err(|err| debug!("O_DIRECT unavailable for {}: {}", path.display(), err))
+            .ok()
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn open_direct(_path: &Path) -> Option<File> {
+        None
+    }
+
+    #[cfg(target_os = "linux")]
+    fn set_direct(&self, direct: bool) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = self.file.as_raw_fd();
+        //UNSAFE: fcntl on a descriptor owned by self.file
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = if direct {
+            flags | libc::O_DIRECT
+        } else {
+            flags & !libc::O_DIRECT
+        };
+        if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn set_direct(&self, _direct: bool) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn read_block(&self, index: usize) -> io::Result<Box<Block>> {
+        use std::os::unix::fs::FileExt;
+
+        let mut block = Block::zeroed();
+        let offset = index * DIRECT_IO_BLOCK_SIZE;
+        let len = DIRECT_IO_BLOCK_SIZE.min(self.file_size.saturating_sub(offset));
+        // O_DIRECT reads whole blocks, the read of the partial last block stops at the end of
+        // the file
+        let read = if self.direct {
+            self.file.read_at(&mut block.0, offset as u64)?
+        } else {
+            self.file
+                .read_exact_at(&mut block.0[..len], offset as u64)?;
+            len
+        };
+        if read < len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("short read of block {}", index),
+            ));
+        }
+        Ok(block)
+    }
+
+    fn read(&self, offset: usize, buf: &mut [u8]) -> io::Result<()> {
+        let mut pos = 0;
+        while pos < buf.len() {
+            let index = (offset + pos) / DIRECT_IO_BLOCK_SIZE;
+            let start = (offset + pos) % DIRECT_IO_BLOCK_SIZE;
+            let len = (DIRECT_IO_BLOCK_SIZE - start).min(buf.len() - pos);
+            let dst = &mut buf[pos..pos + len];
+            pos += len;
+            {
+                let tail = self.tail.read().unwrap();
+                if index == tail.index {
+                    dst.copy_from_slice(&tail.block.0[start..start + len]);
+                    continue;
+                }
+            }
+            let file_id = self.file_id.load(Ordering::Relaxed);
+            if !self.cache.read(file_id, index, start, dst) {
+                let block = self.read_block(index)?;
+                dst.copy_from_slice(&block.0[start..start + len]);
+                self.cache.insert(file_id, index, block);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes `data` at `offset`, past the data appended so far. Only called by the appending
+    /// thread.
+    fn write(&self, offset: usize, data: &[u8]) -> io::Result<()> {
+        use std::os::unix::fs::FileExt;
+
+        let end = offset + data.len();
+        let first = offset / DIRECT_IO_BLOCK_SIZE;
+        let last = (end + DIRECT_IO_BLOCK_SIZE - 1) / DIRECT_IO_BLOCK_SIZE;
+        let start = first * DIRECT_IO_BLOCK_SIZE;
+        let mut blocks: Vec<_> = (first..last)
+            .map(|_| Block([0; DIRECT_IO_BLOCK_SIZE]))
+            .collect();
+        {
+            let tail = self.tail.read().unwrap();
+            if tail.index == first {
+                blocks[0].0 = tail.block.0;
+            }
+        }
+        let buf = as_bytes_mut(&mut blocks);
+        buf[offset - start..end - start].copy_from_slice(data);
+        if self.direct {
+            // O_DIRECT writes whole blocks, the partial last block of the file goes through the
+            // page cache
+            let direct_end = (last * DIRECT_IO_BLOCK_SIZE)
+                .min(self.file_size / DIRECT_IO_BLOCK_SIZE * DIRECT_IO_BLOCK_SIZE);
+            if start < direct_end {
+                self.file
+                    .write_all_at(&buf[..direct_end - start], start as u64)?;
+            }
+            if direct_end < end {
+                self.set_direct(false)?;
+                let result = self
+                    .file
+                    .write_all_at(&buf[direct_end - start..end - start], direct_end as u64);
+                self.set_direct(true)?;
+                result?;
+            }
+        } else {
+            self.file
+                .write_all_at(&buf[offset - start..end - start], offset as u64)?;
+        }
+
+        let tail_index = end / DIRECT_IO_BLOCK_SIZE;
+        let file_id = self.file_id.load(Ordering::Relaxed);
+        let mut tail = self.tail.write().unwrap();
+        for (index, block) in (first..).zip(blocks) {
+            if index < tail_index {
+                self.cache.insert(file_id, index, Box::new(block));
+            } else {
+                tail.block = Box::new(block);
+            }
+        }
+        if tail_index == last {
+            tail.block = Block::zeroed();
+        }
+        tail.index = tail_index;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+impl AppendVecStorage for DirectIoStorage {
+    fn read_with(&self, offset: usize, len: usize, f: &mut dyn FnMut(&[u8])) -> io::Result<()> {
+        let mut buf = vec![0u64; u64_align!(len) / ALIGN_BOUNDARY_OFFSET];
+        let buf = &mut as_bytes_mut(&mut buf)[..len];
+        self.read(offset, buf)?;
+        f(buf);
+        Ok(())
+    }
+
+    fn append(&self, offset: usize, vals: &[&[u8]]) -> io::Result<()> {
+        let mut data = vec![];
+        for val in vals {
+            data.resize(u64_align!(data.len()), 0);
+            data.extend_from_slice(val);
+        }
+        self.write(offset, &data)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.file.sync_data()
+    }
+
+    fn reset(&self) {
+        let mut tail = self.tail.write().unwrap();
+        self.file_id
+            .store(self.cache.new_file_id(), Ordering::Relaxed);
+        tail.index = 0;
+        tail.block = Block::zeroed();
+    }
+}
+
 /// Meta contains enough context to recover the index from storage itself
 /// This struct will be backed by mmaped and snapshotted data files.
 /// So the data layout must be stable and consistent across the entire cluster!
@@ -101,11 +559,11 @@ impl<'a> StoredAccount<'a> {
     }
 }
 
-#[derive(Debug, AbiExample)]
+#[derive(Debug)]
 #[allow(clippy::mutex_atomic)]
 pub struct AppendVec {
     path: PathBuf,
-    map: MmapMut,
+    storage: Box<dyn AppendVecStorage>,
     // This mutex forces append to be single threaded, but concurrent with reads
     #[allow(clippy::mutex_atomic)]
     append_offset: Mutex<usize>,
@@ -113,6 +571,13 @@ pub struct AppendVec {
     file_size: u64,
 }
 
+#[cfg(RUSTC_WITH_SPECIALIZATION)]
+impl solana_frozen_abi::abi_example::AbiExample for AppendVec {
+    fn example() -> Self {
+        AppendVec::new_empty_map(0)
+    }
+}
+
 impl Drop for AppendVec {
     fn drop(&mut self) {
         let _ignored = remove_file(&self.path);
@@ -120,8 +585,17 @@ impl Drop for AppendVec {
 }
 
 impl AppendVec {
-    #[allow(clippy::mutex_atomic)]
     pub fn new(file: &Path, create: bool, size: usize) -> Self {
+        Self::new_with_backend(file, create, size, &AppendVecBackend::Mmap)
+    }
+
+    #[allow(clippy::mutex_atomic)]
+    pub fn new_with_backend(
+        file: &Path,
+        create: bool,
+        size: usize,
+        backend: &AppendVecBackend,
+    ) -> Self {
         let initial_len = 0;
         AppendVec::sanitize_len_and_size(initial_len, size).unwrap();
 
@@ -157,9 +631,7 @@ impl AppendVec {
         data.write_all(&[0]).unwrap();
         data.seek(SeekFrom::Start(0)).unwrap();
         data.flush().unwrap();
-        //UNSAFE: Required to create a Mmap
-        let map = unsafe { MmapMut::map_mut(&data) };
-        let map = map.unwrap_or_else(|e| {
+        let storage = new_storage(backend, data, file, size, initial_len).unwrap_or_else(|e| {
             error!(
                 "Failed to map the data file (size: {}): {}.\n
                     Please increase sysctl vm.max_map_count or equivalent for your platform.",
@@ -170,7 +642,7 @@ impl AppendVec {
 
         AppendVec {
             path: file.to_path_buf(),
-            map,
+            storage,
             // This mutex forces append to be single threaded, but concurrent with reads
             // See UNSAFE usage in `append_ptr`
             append_offset: Mutex::new(initial_len),
@@ -192,7 +664,7 @@ impl AppendVec {
 
         AppendVec {
             path: PathBuf::from(String::default()),
-            map,
+            storage: Box::new(MmapStorage(map)),
             append_offset: Mutex::new(current_len),
             current_len: AtomicUsize::new(current_len),
             file_size: 0, // will be filled by set_file()
@@ -221,7 +693,7 @@ impl AppendVec {
     }
 
     pub fn flush(&self) -> io::Result<()> {
-        self.map.flush()
+        self.storage.flush()
     }
 
     #[allow(clippy::mutex_atomic)]
@@ -231,6 +703,7 @@ impl AppendVec {
         let mut offset = self.append_offset.lock().unwrap();
         self.current_len.store(0, Ordering::Relaxed);
         *offset = 0;
+        self.storage.reset();
     }
 
     pub fn len(&self) -> usize {
@@ -254,8 +727,16 @@ impl AppendVec {
         PathBuf::from(&format!("{}.{}", slot, id))
     }
 
-    #[allow(clippy::mutex_atomic)]
     pub fn set_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.set_file_with_backend(path, &AppendVecBackend::Mmap)
+    }
+
+    #[allow(clippy::mutex_atomic)]
+    pub fn set_file_with_backend<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        backend: &AppendVecBackend,
+    ) -> io::Result<()> {
         // this AppendVec must not hold actual file;
         assert_eq!(self.file_size, 0);
 
@@ -271,13 +752,19 @@ impl AppendVec {
         let file_size = std::fs::metadata(&path)?.len();
         AppendVec::sanitize_len_and_size(current_len, file_size as usize)?;
 
-        let map = unsafe { MmapMut::map_mut(&data)? };
+        let storage = new_storage(
+            backend,
+            data,
+            path.as_ref(),
+            file_size as usize,
+            current_len,
+        )?;
 
         self.file_size = file_size;
         self.path = path.as_ref().to_path_buf();
-        self.map = map;
+        self.storage = storage;
 
-        if !self.sanitize_layout_and_length() {
+        if !self.sanitize_layout_and_length()? {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
                 "incorrect layout/length/data",
@@ -287,55 +774,40 @@ impl AppendVec {
         Ok(())
     }
 
-    fn sanitize_layout_and_length(&self) -> bool {
+    fn sanitize_layout_and_length(&self) -> io::Result<bool> {
         let mut offset = 0;
 
         // This discards allocated accounts immediately after check at each loop iteration.
         //
-        // This code should not reuse AppendVec.accounts() method as the current form or
+        // This code should not reuse AppendVec.scan_accounts() method as the current form or
         // extend it to be reused here because it would allow attackers to accumulate
         // some measurable amount of memory needlessly.
-        while let Some((account, next_offset)) = self.get_account(offset) {
-            if !account.sanitize() {
-                return false;
+        while let Some((sanitized, next_offset)) =
+            self.get_account_with(offset, |account| account.sanitize())?
+        {
+            if !sanitized {
+                return Ok(false);
             }
             offset = next_offset;
         }
         let aligned_current_len = u64_align!(self.current_len.load(Ordering::Relaxed));
 
-        offset == aligned_current_len
+        Ok(offset == aligned_current_len)
     }
 
-    fn get_slice(&self, offset: usize, size: usize) -> Option<(&[u8], usize)> {
+    fn get_slice(data: &[u8], offset: usize, size: usize) -> Option<(&[u8], usize)> {
         let (next, overflow) = offset.overflowing_add(size);
-        if overflow || next > self.len() {
+        if overflow || next > data.len() {
             return None;
         }
-        let data = &self.map[offset..next];
-        let next = u64_align!(next);
-
-        Some((
-            //UNSAFE: This unsafe creates a slice that represents a chunk of self.map memory
-            //The lifetime of this slice is tied to &self, since it points to self.map memory
-            unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, size) },
-            next,
-        ))
-    }
-
-    fn append_ptr(&self, offset: &mut usize, src: *const u8, len: usize) {
-        let pos = u64_align!(*offset);
-        let data = &self.map[pos..(pos + len)];
-        //UNSAFE: This mut append is safe because only 1 thread can append at a time
-        //Mutex<append_offset> guarantees exclusive write access to the memory occupied in
-        //the range.
-        unsafe {
-            let dst = data.as_ptr() as *mut u8;
-            std::ptr::copy(src, dst, len);
-        };
-        *offset = pos + len;
+        Some((&data[offset..next], u64_align!(next)))
     }
 
-    fn append_ptrs_locked(&self, offset: &mut usize, vals: &[(*const u8, usize)]) -> Option<usize> {
+    fn append_ptrs_locked(
+        &self,
+        offset: &mut usize,
+        vals: &[(*const u8, usize)],
+    ) -> io::Result<Option<usize>> {
         let mut end = *offset;
         for val in vals {
             end = u64_align!(end);
@@ -343,30 +815,34 @@ impl AppendVec {
         }
 
         if (self.file_size as usize) < end {
-            return None;
+            return Ok(None);
         }
 
         let pos = u64_align!(*offset);
-        for val in vals {
-            self.append_ptr(offset, val.0, val.1)
-        }
+        //UNSAFE: The pointers are valid for their lengths, see `append_accounts`
+        let vals: Vec<&[u8]> = vals
+            .iter()
+            .map(|(src, len)| unsafe { std::slice::from_raw_parts(*src, *len) })
+            .collect();
+        self.storage.append(pos, &vals)?;
+        *offset = end;
         self.current_len.store(*offset, Ordering::Relaxed);
-        Some(pos)
+        Ok(Some(pos))
     }
 
-    fn get_type<'a, T>(&self, offset: usize) -> Option<(&'a T, usize)> {
-        let (data, next) = self.get_slice(offset, mem::size_of::<T>())?;
+    fn get_type<T>(data: &[u8], offset: usize) -> Option<(&T, usize)> {
+        let (data, next) = Self::get_slice(data, offset, mem::size_of::<T>())?;
         let ptr: *const T = data.as_ptr() as *const T;
         //UNSAFE: The cast is safe because the slice is aligned and fits into the memory
-        //and the lifetime of he &T is tied to self, which holds the underlying memory map
+        //and the lifetime of he &T is tied to the data
         Some((unsafe { &*ptr }, next))
     }
 
-    pub fn get_account<'a>(&'a self, offset: usize) -> Option<(StoredAccount<'a>, usize)> {
-        let (meta, next): (&'a StoredMeta, _) = self.get_type(offset)?;
-        let (account_meta, next): (&'a AccountMeta, _) = self.get_type(next)?;
-        let (hash, next): (&'a Hash, _) = self.get_type(next)?;
-        let (data, next) = self.get_slice(next, meta.data_len as usize)?;
+    fn stored_account(data: &[u8], offset: usize) -> Option<(StoredAccount, usize)> {
+        let (meta, next): (&StoredMeta, _) = Self::get_type(data, offset)?;
+        let (account_meta, next): (&AccountMeta, _) = Self::get_type(data, next)?;
+        let (hash, next): (&Hash, _) = Self::get_type(data, next)?;
+        let (data, next) = Self::get_slice(data, next, meta.data_len as usize)?;
         Some((
             StoredAccount {
                 meta,
@@ -378,16 +854,82 @@ impl AppendVec {
             next,
         ))
     }
+
+    /// Calls `f` with the account stored at `offset`, returns its result and the offset of the
+    /// next account, or None past the last account. The direct IO backend reads the account into
+    /// a temporary buffer, failing if the read does.
+    pub fn get_account_with<R>(
+        &self,
+        offset: usize,
+        f: impl FnOnce(&StoredAccount) -> R,
+    ) -> io::Result<Option<(R, usize)>> {
+        let len = self.len();
+        match offset.checked_add(STORED_ACCOUNT_HEADER_LEN) {
+            Some(header_end) if header_end <= len => (),
+            _ => return Ok(None),
+        }
+        let mut data_len = 0;
+        self.storage
+            .read_with(offset, STORED_ACCOUNT_HEADER_LEN, &mut |header| {
+                if let Some((meta, _)) = Self::get_type::<StoredMeta>(header, 0) {
+                    data_len = meta.data_len as usize;
+                }
+            })?;
+        let account_len = match STORED_ACCOUNT_HEADER_LEN.checked_add(data_len) {
+            Some(account_len) if offset.saturating_add(account_len) <= len => account_len,
+            _ => return Ok(None),
+        };
+        let mut f = Some(f);
+        let mut result = None;
+        self.storage.read_with(offset, account_len, &mut |data| {
+            if let Some((mut account, next)) = Self::stored_account(data, 0) {
+                account.offset = offset;
+                result = f.take().map(|f| (f(&account), offset + next));
+            }
+        })?;
+        Ok(result)
+    }
+
+    /// Calls `f` with each account stored from `start`
+    pub fn scan_accounts(
+        &self,
+        mut start: usize,
+        mut f: impl FnMut(&StoredAccount),
+    ) -> io::Result<()> {
+        while let Some(((), next)) = self.get_account_with(start, &mut f)? {
+            start = next;
+        }
+        Ok(())
+    }
+
+    pub fn count_accounts(&self) -> io::Result<usize> {
+        let mut count = 0;
+        self.scan_accounts(0, |_| count += 1)?;
+        Ok(count)
+    }
+
+    /// The account stored at `offset` and the offset of the next account, borrowing the data of
+    /// the append vec. Only memory-mapped append vecs lend out their accounts, this is None for
+    /// the others, whose accounts are read with `get_account_with`.
+    pub fn get_account<'a>(&'a self, offset: usize) -> Option<(StoredAccount<'a>, usize)> {
+        let data = self.storage.mapped()?;
+        Self::stored_account(&data[..self.len().min(data.len())], offset)
+    }
+
     pub fn get_account_test(&self, offset: usize) -> Option<(StoredMeta, Account)> {
-        let (stored_account, _) = self.get_account(offset)?;
-        let meta = stored_account.meta.clone();
-        Some((meta, stored_account.clone_account()))
+        let (account, _) = self
+            .get_account_with(offset, |stored_account| {
+                (stored_account.meta.clone(), stored_account.clone_account())
+            })
+            .unwrap()?;
+        Some(account)
     }
 
     pub fn get_path(&self) -> PathBuf {
         self.path.clone()
     }
 
+    /// The accounts stored from `start`, see `get_account`
     pub fn accounts<'a>(&'a self, mut start: usize) -> Vec<StoredAccount<'a>> {
         let mut accounts = vec![];
         while let Some((account, next)) = self.get_account(start) {
@@ -397,12 +939,13 @@ impl AppendVec {
         accounts
     }
 
+    /// Appends the accounts until the append vec is full, returns their offsets
     #[allow(clippy::mutex_atomic)]
     pub fn append_accounts(
         &self,
         accounts: &[(StoredMeta, &Account)],
         hashes: &[Hash],
-    ) -> Vec<usize> {
+    ) -> io::Result<Vec<usize>> {
         let mut offset = self.append_offset.lock().unwrap();
         let mut rv = Vec::with_capacity(accounts.len());
         for ((stored_meta, account), hash) in accounts.iter().zip(hashes) {
@@ -423,13 +966,13 @@ impl AppendVec {
                 (hash_ptr as *const u8, mem::size_of::<Hash>()),
                 (data_ptr, data_len),
             ];
-            if let Some(res) = self.append_ptrs_locked(&mut offset, &ptrs) {
+            if let Some(res) = self.append_ptrs_locked(&mut offset, &ptrs)? {
                 rv.push(res)
             } else {
                 break;
             }
         }
-        rv
+        Ok(rv)
     }
 
     pub fn append_account(
@@ -437,10 +980,11 @@ impl AppendVec {
         storage_meta: StoredMeta,
         account: &Account,
         hash: Hash,
-    ) -> Option<usize> {
-        self.append_accounts(&[(storage_meta, account)], &[hash])
+    ) -> io::Result<Option<usize>> {
+        Ok(self
+            .append_accounts(&[(storage_meta, account)], &[hash])?
             .first()
-            .cloned()
+            .cloned())
     }
 }
 
@@ -504,6 +1048,7 @@ pub mod tests {
     impl AppendVec {
         fn append_account_test(&self, data: &(StoredMeta, Account)) -> Option<usize> {
             self.append_account(data.0.clone(), &data.1, Hash::default())
+                .unwrap()
         }
     }
 
@@ -606,6 +1151,57 @@ pub mod tests {
         assert_eq!(av.get_account_test(index).unwrap(), account);
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_append_vec_direct_io() {
+        let path = get_append_vec_path("test_append_vec_direct_io");
+        let mmap_path = get_append_vec_path("test_append_vec_direct_io_mmap");
+        // The last block of the file is partial, and written through the page cache. The cache
+        // holds a single block per shard, so that blocks get evicted and read back.
+        let file_size = 3 * DIRECT_IO_BLOCK_SIZE + 100;
+        let backend = AppendVecBackend::direct_io(BLOCK_CACHE_SHARDS * DIRECT_IO_BLOCK_SIZE);
+        let av = AppendVec::new_with_backend(&path.path, true, file_size, &backend);
+        let mmap_av = AppendVec::new(&mmap_path.path, true, file_size);
+        let mut indexes = vec![];
+        for sample in 0.. {
+            let account = create_test_account(sample);
+            match av.append_account_test(&account) {
+                Some(pos) => {
+                    assert_eq!(mmap_av.append_account_test(&account), Some(pos));
+                    indexes.push((pos, account));
+                }
+                None => break,
+            }
+        }
+        assert!(av.len() > 3 * DIRECT_IO_BLOCK_SIZE);
+        assert_eq!(av.count_accounts().unwrap(), indexes.len());
+        // Only the accounts of memory-mapped append vecs are lent out
+        assert!(av.get_account(0).is_none());
+        assert_eq!(mmap_av.accounts(0).len(), indexes.len());
+
+        // Every append is written through to the file
+        mmap_av.flush().unwrap();
+        let data = std::fs::read(&path.path).unwrap();
+        assert_eq!(data.len(), file_size);
+        assert_eq!(data, std::fs::read(&mmap_path.path).unwrap());
+
+        let mut reopened = AppendVec::new_empty_map(av.len());
+        reopened
+            .set_file_with_backend(&path.path, &backend)
+            .unwrap();
+        for (pos, account) in &indexes {
+            assert_eq!(av.get_account_test(*pos).unwrap(), *account);
+            assert_eq!(reopened.get_account_test(*pos).unwrap(), *account);
+        }
+
+        // The cached blocks of the previous contents are not read back once reset
+        av.reset();
+        let account = create_test_account(1000);
+        let pos = av.append_account_test(&account).unwrap();
+        assert_eq!(av.get_account_test(pos).unwrap(), account);
+        assert_eq!(av.count_accounts().unwrap(), 1);
+    }
+
     #[test]
     fn test_append_vec_data() {
         let path = get_append_vec_path("test_append_data");
@@ -4,13 +4,15 @@
 //! already been signed and verified.
 use crate::{
     accounts::{
-        AccountAddressFilter, Accounts, TransactionAccounts, TransactionLoadResult,
+        AccountAddressFilter, AccountLockSet, Accounts, TransactionAccounts, TransactionLoadResult,
         TransactionLoaders,
     },
-    accounts_db::{ErrorCounters, SnapshotStorages},
-    accounts_index::Ancestors,
+    accounts_db::{ErrorCounters, ReclaimedStorage, ShrinkConfig, SnapshotStorages},
+    accounts_index::{AccountIndex, AccountIndexes, Ancestors, IndexKey},
+    append_vec::AppendVecBackend,
     blockhash_queue::BlockhashQueue,
     builtins,
+    epoch_rewards::{EpochRewardsDistribution, StakeReward, NUM_EPOCH_REWARDS_PARTITIONS},
     epoch_stakes::{EpochStakes, NodeVoteAccounts},
     instruction_recorder::InstructionRecorder,
     log_collector::LogCollector,
@@ -68,7 +70,7 @@ use solana_stake_program::stake_state::{self, Delegation, PointValue};
 use solana_vote_program::{vote_instruction::VoteInstruction, vote_state::VoteState};
 use std::{
     cell::RefCell,
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     convert::{TryFrom, TryInto},
     fmt, mem,
     ops::RangeInclusive,
@@ -85,6 +87,12 @@ use std::{
 // Partial SPL Token v2.0.x declarations inlined to avoid an external dependency on the spl-token crate
 pub mod inline_spl_token_v2_0 {
     solana_sdk::declare_id!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+    pub mod state {
+        /// Length of a token account
+        pub const ACCOUNT_LEN: usize = 165;
+        /// Offset of the mint in a token account
+        pub const ACCOUNT_MINT_OFFSET: usize = 0;
+    }
     pub mod native_mint {
         solana_sdk::declare_id!("So11111111111111111111111111111111111111112");
 
@@ -406,6 +414,44 @@ pub type InnerInstructionsList = Vec<InnerInstructions>;
 /// A list of log messages emitted during a transaction
 pub type TransactionLogMessages = Vec<String>;
 
+/// Which transaction logs banks keep, for `Bank::get_recent_transaction_logs`
+#[derive(AbiExample, Clone, Debug, Default, PartialEq)]
+pub struct TransactionLogCollectorConfig {
+    /// Number of transaction logs each bank keeps, dropping its oldest ones first. 0 keeps none
+    pub max_logs_per_bank: usize,
+    /// Whether the logs of simple vote transactions are kept as well
+    pub include_votes: bool,
+}
+
+/// The logs a bank kept of one of its transactions
+#[derive(AbiExample, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionLogInfo {
+    pub signature: Signature,
+    pub result: Result<()>,
+    pub is_vote: bool,
+    /// The accounts the transaction references, including the programs it invokes
+    pub account_keys: Vec<Pubkey>,
+    pub log_messages: TransactionLogMessages,
+}
+
+impl TransactionLogInfo {
+    pub fn mentions(&self, address: &Pubkey) -> bool {
+        self.account_keys.contains(address)
+    }
+}
+
+/// The outcome of a simulated transaction, along with what it recorded while executing
+#[derive(Debug, PartialEq)]
+pub struct TransactionSimulationDetails {
+    pub result: Result<()>,
+    pub logs: TransactionLogMessages,
+    /// Only recorded when requested
+    pub inner_instructions: Option<InnerInstructionsList>,
+    /// State of each requested address after the simulation, or the current state of the bank
+    /// if the transaction failed or does not reference the address
+    pub accounts: Vec<Option<Account>>,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum HashAgeKind {
     Extant,
@@ -454,6 +500,7 @@ pub(crate) struct BankFieldsToDeserialize {
     pub(crate) stakes: Stakes,
     pub(crate) epoch_stakes: HashMap<Epoch, EpochStakes>,
     pub(crate) is_delta: bool,
+    pub(crate) epoch_rewards_distribution: Option<EpochRewardsDistribution>,
 }
 
 // Bank's common fields shared by all supported snapshot versions for serialization.
@@ -493,6 +540,7 @@ pub(crate) struct BankFieldsToSerialize<'a> {
     pub(crate) stakes: &'a RwLock<Stakes>,
     pub(crate) epoch_stakes: &'a HashMap<Epoch, EpochStakes>,
     pub(crate) is_delta: bool,
+    pub(crate) epoch_rewards_distribution: Option<&'a EpochRewardsDistribution>,
 }
 
 // Can't derive PartialEq because RwLock doesn't implement PartialEq
@@ -532,6 +580,7 @@ impl PartialEq for Bank {
             && *self.stakes.read().unwrap() == *other.stakes.read().unwrap()
             && self.epoch_stakes == other.epoch_stakes
             && self.is_delta.load(Relaxed) == other.is_delta.load(Relaxed)
+            && self.epoch_rewards_distribution == other.epoch_rewards_distribution
     }
 }
 
@@ -698,7 +747,16 @@ pub struct Bank {
 
     transaction_debug_keys: Option<Arc<HashSet<Pubkey>>>,
 
+    /// Shared by the bank and its descendants
+    transaction_log_collector_config: Arc<RwLock<TransactionLogCollectorConfig>>,
+
+    /// Logs of the most recent transactions of this bank, oldest first
+    transaction_log_collector: RwLock<VecDeque<TransactionLogInfo>>,
+
     pub feature_set: Arc<FeatureSet>,
+
+    /// Stake rewards still to be paid by this bank's descendants
+    epoch_rewards_distribution: Option<Arc<EpochRewardsDistribution>>,
 }
 
 impl Default for BlockhashQueue {
@@ -709,7 +767,15 @@ impl Default for BlockhashQueue {
 
 impl Bank {
     pub fn new(genesis_config: &GenesisConfig) -> Self {
-        Self::new_with_paths(&genesis_config, Vec::new(), &[], None, None)
+        Self::new_with_paths(
+            &genesis_config,
+            Vec::new(),
+            &[],
+            None,
+            None,
+            AccountIndexes::default(),
+            AppendVecBackend::default(),
+        )
     }
 
     pub fn new_with_paths(
@@ -718,13 +784,20 @@ impl Bank {
         frozen_account_pubkeys: &[Pubkey],
         debug_keys: Option<Arc<HashSet<Pubkey>>>,
         additional_builtins: Option<&Builtins>,
+        account_indexes: AccountIndexes,
+        append_vec_backend: AppendVecBackend,
     ) -> Self {
         let mut bank = Self::default();
         bank.transaction_debug_keys = debug_keys;
         bank.cluster_type = Some(genesis_config.cluster_type);
         bank.ancestors.insert(bank.slot(), 0);
 
-        bank.rc.accounts = Arc::new(Accounts::new(paths, &genesis_config.cluster_type));
+        bank.rc.accounts = Arc::new(Accounts::new_with_config(
+            paths,
+            &genesis_config.cluster_type,
+            account_indexes,
+            append_vec_backend,
+        ));
         bank.process_genesis_config(genesis_config);
         bank.finish_init(genesis_config, additional_builtins);
 
@@ -822,7 +895,10 @@ impl Bank {
             rewards_pool_pubkeys: parent.rewards_pool_pubkeys.clone(),
             cached_executors: RwLock::new((*parent.cached_executors.read().unwrap()).clone()),
             transaction_debug_keys: parent.transaction_debug_keys.clone(),
+            transaction_log_collector_config: parent.transaction_log_collector_config.clone(),
+            transaction_log_collector: RwLock::new(VecDeque::new()),
             feature_set: parent.feature_set.clone(),
+            epoch_rewards_distribution: parent.epoch_rewards_distribution.clone(),
         };
 
         datapoint_info!(
@@ -845,6 +921,7 @@ impl Bank {
 
         new.update_slot_hashes();
         new.update_rewards(parent.epoch());
+        new.distribute_epoch_rewards();
         new.update_stake_history(Some(parent.epoch()));
         new.update_clock();
         new.update_fees();
@@ -929,7 +1006,10 @@ impl Bank {
                 CachedExecutors::new(MAX_CACHED_EXECUTORS),
             )))),
             transaction_debug_keys: debug_keys,
+            transaction_log_collector_config: new(),
+            transaction_log_collector: new(),
             feature_set: new(),
+            epoch_rewards_distribution: fields.epoch_rewards_distribution.map(Arc::new),
         };
         bank.finish_init(genesis_config, additional_builtins);
 
@@ -1002,6 +1082,7 @@ impl Bank {
             stakes: &self.stakes,
             epoch_stakes: &self.epoch_stakes,
             is_delta: self.is_delta.load(Relaxed),
+            epoch_rewards_distribution: self.epoch_rewards_distribution.as_deref(),
         }
     }
 
@@ -1214,6 +1295,9 @@ impl Bank {
         }
         // if I'm the first Bank in an epoch, count, claim, disburse rewards from Inflation
 
+        // finish paying out the previous epoch's stake rewards before calculating new ones
+        self.flush_epoch_rewards_distribution();
+
         // calculated as: prev_slot / (slots / year)
         let slot_in_year =
             (self.epoch_schedule.get_last_slot_in_epoch(prev_epoch)) as f64 / self.slots_per_year;
@@ -1233,8 +1317,20 @@ impl Bank {
             (validator_rate * capitalization as f64 * epoch_duration_in_years) as u64;
 
         let vote_balance_and_staked = self.stakes.read().unwrap().vote_balance_and_staked();
+        let num_previous_rewards = self.rewards.read().unwrap().len();
 
-        let validator_point_value = self.pay_validator_rewards(validator_rewards);
+        // with partitioned rewards, only vote rewards are paid here, and stake rewards are
+        // paid by the following blocks, one partition each, by distribute_epoch_rewards()
+        let mut stake_rewards = if self
+            .feature_set
+            .is_active(&feature_set::partitioned_epoch_rewards::id())
+        {
+            Some(vec![])
+        } else {
+            None
+        };
+        let validator_point_value =
+            self.pay_validator_rewards(validator_rewards, stake_rewards.as_mut());
 
         if !self
             .feature_set
@@ -1258,6 +1354,7 @@ impl Bank {
                     .read()
                     .unwrap()
                     .iter()
+                    .skip(num_previous_rewards)
                     .map(|(_address, reward_info)| {
                         match reward_info.reward_type {
                             RewardType::Voting | RewardType::Staking => reward_info.lamports,
@@ -1269,12 +1366,27 @@ impl Bank {
             .unwrap()
         );
 
+        let deferred_stake_rewards = stake_rewards.map_or(0, |stake_rewards| {
+            let distribution = EpochRewardsDistribution::new(
+                prev_epoch,
+                self.slot,
+                self.block_height,
+                stake_rewards,
+                NUM_EPOCH_REWARDS_PARTITIONS,
+            );
+            let total_stake_rewards = distribution.total_stake_rewards;
+            if distribution.num_partitions() > 0 {
+                self.epoch_rewards_distribution = Some(Arc::new(distribution));
+            }
+            total_stake_rewards
+        });
+
         // verify that we didn't pay any more than we expected to
-        assert!(validator_rewards >= validator_rewards_paid);
+        assert!(validator_rewards >= validator_rewards_paid + deferred_stake_rewards);
 
         info!(
-            "distributed inflation: {} (rounded from: {})",
-            validator_rewards_paid, validator_rewards
+            "distributed inflation: {} (rounded from: {}), deferred stake rewards: {}",
+            validator_rewards_paid, validator_rewards, deferred_stake_rewards
         );
 
         self.capitalization
@@ -1296,6 +1408,7 @@ impl Bank {
             ("foundation_rate", foundation_rate, f64),
             ("epoch_duration_in_years", epoch_duration_in_years, f64),
             ("validator_rewards", validator_rewards_paid, i64),
+            ("deferred_stake_rewards", deferred_stake_rewards, i64),
             ("active_stake", active_stake, i64),
             ("pre_capitalization", capitalization, i64),
             ("post_capitalization", self.capitalization(), i64)
@@ -1333,8 +1446,14 @@ impl Bank {
     }
 
     /// iterate over all stakes, redeem vote credits for each stake we can
-    ///   successfully load and parse, return the lamport value of one point
-    fn pay_validator_rewards(&mut self, rewards: u64) -> f64 {
+    ///   successfully load and parse, return the lamport value of one point.
+    ///   If `deferred_stake_rewards` is given, stake rewards are collected into it
+    ///   to be paid later instead of being stored now
+    fn pay_validator_rewards(
+        &mut self,
+        rewards: u64,
+        mut deferred_stake_rewards: Option<&mut Vec<StakeReward>>,
+    ) -> f64 {
         let stake_history = self.stakes.read().unwrap().history().clone();
 
         let mut stake_delegation_accounts = self.stake_delegation_accounts();
@@ -1372,9 +1491,21 @@ impl Bank {
                     Some(&stake_history),
                 );
                 if let Ok((stakers_reward, _voters_reward)) = redeemed {
-                    self.store_account(&stake_pubkey, &stake_account);
                     vote_account_changed = true;
 
+                    if let Some(deferred_stake_rewards) = deferred_stake_rewards.as_mut() {
+                        let credits_observed = stake_state::StakeState::stake_from(&stake_account)
+                            .map(|stake| stake.credits_observed)
+                            .unwrap_or_default();
+                        deferred_stake_rewards.push(StakeReward {
+                            stake_pubkey: *stake_pubkey,
+                            stakers_reward,
+                            credits_observed,
+                        });
+                        continue;
+                    }
+                    self.store_account(&stake_pubkey, &stake_account);
+
                     if stakers_reward > 0 {
                         rewards.push((
                             *stake_pubkey,
@@ -1414,6 +1545,94 @@ impl Bank {
         point_value.rewards as f64 / point_value.points as f64
     }
 
+    /// Stake rewards that are still being paid out as of this bank, if any
+    pub fn epoch_rewards_distribution(&self) -> Option<Arc<EpochRewardsDistribution>> {
+        self.epoch_rewards_distribution.clone()
+    }
+
+    pub fn is_distributing_epoch_rewards(&self) -> bool {
+        self.epoch_rewards_distribution.is_some()
+    }
+
+    // pay the partition of stake rewards scheduled for this block, if any
+    fn distribute_epoch_rewards(&mut self) {
+        if let Some(distribution) = self.epoch_rewards_distribution.clone() {
+            if let Some(index) = distribution.partition_index(self.block_height) {
+                self.distribute_epoch_rewards_partition(&distribution, index);
+            }
+            if distribution.is_complete(self.block_height) {
+                self.epoch_rewards_distribution = None;
+            }
+        }
+    }
+
+    // pay every partition the ancestors of this bank have not paid yet
+    fn flush_epoch_rewards_distribution(&mut self) {
+        if let Some(distribution) = self.epoch_rewards_distribution.take() {
+            let first_index = distribution.num_distributed_partitions(self.parent_block_height());
+            for index in first_index..distribution.num_partitions() {
+                self.distribute_epoch_rewards_partition(&distribution, index);
+            }
+        }
+    }
+
+    fn parent_block_height(&self) -> u64 {
+        self.block_height.saturating_sub(1)
+    }
+
+    fn distribute_epoch_rewards_partition(
+        &self,
+        distribution: &EpochRewardsDistribution,
+        index: usize,
+    ) {
+        let partition = distribution.partition(index);
+        let mut rewards = vec![];
+        let mut rewards_paid = 0;
+        for reward in partition {
+            let mut stake_account = match self.get_account(&reward.stake_pubkey) {
+                Some(stake_account) => stake_account,
+                None => continue,
+            };
+            // the reward is forfeited if the stake was deactivated or withdrawn in the meantime
+            let credited = stake_state::credit_rewards(
+                &mut stake_account,
+                reward.stakers_reward,
+                reward.credits_observed,
+            );
+            if credited.is_err() {
+                debug!(
+                    "stake_state::credit_rewards() failed for {}: {:?}",
+                    reward.stake_pubkey, credited
+                );
+                continue;
+            }
+            self.store_account(&reward.stake_pubkey, &stake_account);
+            rewards_paid += reward.stakers_reward;
+            if reward.stakers_reward > 0 {
+                rewards.push((
+                    reward.stake_pubkey,
+                    RewardInfo {
+                        reward_type: RewardType::Staking,
+                        lamports: reward.stakers_reward as i64,
+                        post_balance: stake_account.lamports,
+                    },
+                ));
+            }
+        }
+        self.rewards.write().unwrap().append(&mut rewards);
+        self.capitalization.fetch_add(rewards_paid, Relaxed);
+
+        datapoint_info!(
+            "epoch_rewards_partition",
+            ("slot", self.slot, i64),
+            ("epoch", distribution.rewarded_epoch, i64),
+            ("partition", index, i64),
+            ("num_partitions", distribution.num_partitions(), i64),
+            ("num_stake_accounts", partition.len(), i64),
+            ("stake_rewards", rewards_paid, i64),
+        );
+    }
+
     fn update_recent_blockhashes_locked(&self, locked_blockhash_queue: &BlockhashQueue) {
         self.update_sysvar_account(&sysvar::recent_blockhashes::id(), |account| {
             let recent_blockhash_iter = locked_blockhash_queue.get_recent_blockhashes();
@@ -1600,6 +1819,12 @@ impl Bank {
         self.parent_slot
     }
 
+    /// Rough estimate of the memory this bank holds on top of its parents: the account data
+    /// stored in its slot
+    pub fn estimated_memory_bytes(&self) -> u64 {
+        self.rc.accounts.accounts_db.slot_stored_bytes(self.slot())
+    }
+
     fn process_genesis_config(&mut self, genesis_config: &GenesisConfig) {
         // Bootstrap validator collects fees until `new_from_parent` is called.
         self.fee_rate_governor = genesis_config.fee_rate_governor.clone();
@@ -1858,17 +2083,61 @@ impl Bank {
         batch
     }
 
+    /// Builds a batch for transactions whose account locks the caller already holds through
+    /// `try_lock_account_set`. The batch leaves those locks held when it is dropped.
+    pub fn prepare_batch_with_held_locks<'a, 'b>(
+        &'a self,
+        txs: &'b [Transaction],
+        iteration_order: Option<Vec<usize>>,
+    ) -> TransactionBatch<'a, 'b> {
+        let lock_results: Vec<_> = OrderedIterator::new(txs, iteration_order.as_deref())
+            .map(|(_, tx)| tx.sanitize().map_err(|e| e.into()))
+            .collect();
+        let mut batch = TransactionBatch::new(lock_results, &self, txs, iteration_order);
+        batch.needs_unlock = false;
+        batch
+    }
+
+    /// Attempts to take every account lock in `lock_set`, independent of any
+    /// `TransactionBatch`. Intended for schedulers that hold locks across batches.
+    pub fn try_lock_account_set(&self, lock_set: &AccountLockSet) -> Result<()> {
+        self.rc.accounts.try_lock_account_set(lock_set)
+    }
+
+    /// Returns true if `lock_set` could currently be locked, without taking any locks
+    pub fn can_lock_account_set(&self, lock_set: &AccountLockSet) -> bool {
+        self.rc.accounts.can_lock_account_set(lock_set)
+    }
+
+    /// Releases the locks taken by a successful `try_lock_account_set`
+    pub fn unlock_account_set(&self, lock_set: &AccountLockSet) {
+        self.rc.accounts.unlock_account_set(lock_set)
+    }
+
     /// Run transactions against a frozen bank without committing the results
     pub fn simulate_transaction(&self, transaction: Transaction) -> (Result<()>, Vec<String>) {
+        let TransactionSimulationDetails { result, logs, .. } =
+            self.simulate_transaction_with_details(transaction, false, &[]);
+        (result, logs)
+    }
+
+    /// Run a transaction against a frozen bank without committing the results, optionally
+    /// recording its inner instructions and returning the resulting state of `addresses`
+    pub fn simulate_transaction_with_details(
+        &self,
+        transaction: Transaction,
+        enable_cpi_recording: bool,
+        addresses: &[Pubkey],
+    ) -> TransactionSimulationDetails {
         assert!(self.is_frozen(), "simulation bank must be frozen");
 
         let txs = &[transaction];
         let batch = self.prepare_simulation_batch(txs);
 
         let (
-            _loaded_accounts,
+            loaded_accounts,
             executed,
-            _inner_instructions,
+            inner_instructions,
             transaction_logs,
             _retryable_transactions,
             _transaction_count,
@@ -1879,7 +2148,7 @@ impl Bank {
             // for processing. During forwarding, the transaction could expire if the
             // delay is not accounted for.
             *MAX_PROCESSING_AGE - CLOCK_CFG.MAX_TRANSACTION_FORWARDING_DELAY,
-            false,
+            enable_cpi_recording,
             true,
         );
 
@@ -1888,7 +2157,34 @@ impl Bank {
             .get(0)
             .map_or(vec![], |messages| messages.to_vec());
 
-        (transaction_result, log_messages)
+        let post_accounts = match (&transaction_result, &loaded_accounts[0].0) {
+            (Ok(()), Ok((accounts, _loaders, _rents))) => Some(accounts),
+            _ => None,
+        };
+        let account_keys = &txs[0].message.account_keys;
+        let accounts = addresses
+            .iter()
+            .map(|address| {
+                let post_account = post_accounts.and_then(|accounts| {
+                    account_keys
+                        .iter()
+                        .position(|key| key == address)
+                        .map(|i| &accounts[i])
+                });
+                match post_account {
+                    Some(account) if account.lamports == 0 => None,
+                    Some(account) => Some(account.clone()),
+                    None => self.get_account(address),
+                }
+            })
+            .collect();
+
+        TransactionSimulationDetails {
+            result: transaction_result,
+            logs: log_messages,
+            inner_instructions: inner_instructions.into_iter().next().flatten(),
+            accounts,
+        }
     }
 
     pub fn unlock_accounts(&self, batch: &mut TransactionBatch) {
@@ -3181,11 +3477,17 @@ impl Bank {
             vec![]
         };
 
+        let transaction_log_collector_config = self
+            .transaction_log_collector_config
+            .read()
+            .unwrap()
+            .clone();
+        let collect_logs = transaction_log_collector_config.max_logs_per_bank > 0;
         let (
             mut loaded_accounts,
             executed,
             inner_instructions,
-            transaction_logs,
+            mut transaction_logs,
             _,
             tx_count,
             signature_count,
@@ -3193,7 +3495,7 @@ impl Bank {
             batch,
             max_age,
             enable_cpi_recording,
-            enable_log_recording,
+            enable_log_recording || collect_logs,
         );
 
         let results = self.commit_transactions(
@@ -3204,6 +3506,18 @@ impl Bank {
             tx_count,
             signature_count,
         );
+        if collect_logs {
+            self.collect_transaction_logs(
+                batch,
+                &loaded_accounts,
+                &executed,
+                &transaction_logs,
+                &transaction_log_collector_config,
+            );
+            if !enable_log_recording {
+                transaction_logs.clear();
+            }
+        }
         let post_balances = if collect_balances {
             self.collect_balances(batch)
         } else {
@@ -3217,6 +3531,91 @@ impl Bank {
         )
     }
 
+    /// Keeps the logs of the committed transactions of `batch`. Only the transactions which
+    /// executed have logs, in the order they executed
+    fn collect_transaction_logs(
+        &self,
+        batch: &TransactionBatch,
+        loaded_accounts: &[(Result<TransactionLoadResult>, Option<HashAgeKind>)],
+        executed: &[TransactionProcessResult],
+        transaction_logs: &[TransactionLogMessages],
+        config: &TransactionLogCollectorConfig,
+    ) {
+        let mut transaction_logs = transaction_logs.iter();
+        let mut collector = self.transaction_log_collector.write().unwrap();
+        for (((_, tx), (load_result, _)), (result, _)) in
+            OrderedIterator::new(batch.transactions(), batch.iteration_order())
+                .zip(loaded_accounts)
+                .zip(executed)
+        {
+            if load_result.is_err() {
+                continue;
+            }
+            let log_messages = match transaction_logs.next() {
+                Some(log_messages) => log_messages,
+                None => break,
+            };
+            let is_vote = is_simple_vote_transaction(tx);
+            if is_vote && !config.include_votes {
+                continue;
+            }
+            while collector.len() >= config.max_logs_per_bank {
+                collector.pop_front();
+            }
+            collector.push_back(TransactionLogInfo {
+                signature: tx.signatures.first().cloned().unwrap_or_default(),
+                result: result.clone(),
+                is_vote,
+                account_keys: tx.message.account_keys.clone(),
+                log_messages: log_messages.clone(),
+            });
+        }
+    }
+
+    /// Sets which transaction logs this bank and its future descendants keep
+    pub fn set_transaction_log_collector_config(&self, config: TransactionLogCollectorConfig) {
+        *self.transaction_log_collector_config.write().unwrap() = config;
+    }
+
+    /// Returns the transaction logs this bank kept, newest first, of the transactions which
+    /// mention `address` if given
+    pub fn get_transaction_logs(&self, address: Option<&Pubkey>) -> Vec<TransactionLogInfo> {
+        self.transaction_log_collector
+            .read()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|info| address.map_or(true, |address| info.mentions(address)))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns up to `limit` transaction logs kept by this bank and its parents, newest first
+    pub fn get_recent_transaction_logs(
+        &self,
+        address: Option<&Pubkey>,
+        limit: usize,
+    ) -> Vec<(Slot, TransactionLogInfo)> {
+        let mut logs = vec![];
+        let mut bank = Some(self);
+        let parents = self.parents();
+        let mut parents = parents.iter();
+        while let Some(current) = bank {
+            if logs.len() >= limit {
+                break;
+            }
+            logs.extend(
+                current
+                    .get_transaction_logs(address)
+                    .into_iter()
+                    .map(|info| (current.slot(), info)),
+            );
+            bank = parents.next().map(|parent| parent.as_ref());
+        }
+        logs.truncate(limit);
+        logs
+    }
+
     #[must_use]
     pub fn process_transactions(&self, txs: &[Transaction]) -> Vec<Result<()>> {
         let batch = self.prepare_batch(txs, None);
@@ -3400,11 +3799,93 @@ impl Bank {
     }
 
     pub fn get_program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        if self.is_account_index_enabled(AccountIndex::ProgramId) {
+            return self.get_filtered_indexed_accounts(&IndexKey::ProgramId(*program_id), |_| true);
+        }
         self.rc
             .accounts
             .load_by_program(&self.ancestors, program_id)
     }
 
+    /// Loads the accounts owned by `program_id` and passing `filter`, in pubkey order
+    pub fn get_filtered_program_accounts<F: Fn(&Account) -> bool>(
+        &self,
+        program_id: &Pubkey,
+        filter: F,
+    ) -> Vec<(Pubkey, Account)> {
+        if self.is_account_index_enabled(AccountIndex::ProgramId) {
+            return self.get_filtered_indexed_accounts(&IndexKey::ProgramId(*program_id), filter);
+        }
+        self.rc
+            .accounts
+            .load_by_program_with_filter(&self.ancestors, program_id, filter)
+    }
+
+    pub fn is_account_index_enabled(&self, account_index: AccountIndex) -> bool {
+        self.rc
+            .accounts
+            .accounts_db
+            .account_indexes()
+            .contains(&account_index)
+    }
+
+    /// Loads the accounts having `index_key` and passing `filter`. The secondary index of the key
+    /// must be enabled
+    pub fn get_filtered_indexed_accounts<F: Fn(&Account) -> bool>(
+        &self,
+        index_key: &IndexKey,
+        filter: F,
+    ) -> Vec<(Pubkey, Account)> {
+        self.rc
+            .accounts
+            .load_by_index_key_with_filter(&self.ancestors, *index_key, filter)
+    }
+
+    /// Loads the page of at most `limit` accounts following `cursor` in pubkey order, owned by
+    /// `program_id` and passing `filter`. The scan starts at `cursor` and stops once the page is
+    /// full.
+    pub fn get_filtered_program_accounts_page<F: Fn(&Account) -> bool>(
+        &self,
+        program_id: &Pubkey,
+        filter: F,
+        cursor: Option<Pubkey>,
+        limit: usize,
+    ) -> Vec<(Pubkey, Account)> {
+        if self.is_account_index_enabled(AccountIndex::ProgramId) {
+            return self.get_filtered_indexed_accounts_page(
+                &IndexKey::ProgramId(*program_id),
+                filter,
+                cursor,
+                limit,
+            );
+        }
+        self.rc.accounts.load_page_with_filter(
+            &self.ancestors,
+            None,
+            |account| account.owner == *program_id && filter(account),
+            cursor,
+            limit,
+        )
+    }
+
+    /// Loads the page of at most `limit` accounts following `cursor` in pubkey order, having
+    /// `index_key` and passing `filter`, using the secondary indexes
+    pub fn get_filtered_indexed_accounts_page<F: Fn(&Account) -> bool>(
+        &self,
+        index_key: &IndexKey,
+        filter: F,
+        cursor: Option<Pubkey>,
+        limit: usize,
+    ) -> Vec<(Pubkey, Account)> {
+        self.rc.accounts.load_page_with_filter(
+            &self.ancestors,
+            Some(*index_key),
+            |account| index_key.matches(&account.owner, &account.data) && filter(account),
+            cursor,
+            limit,
+        )
+    }
+
     pub fn get_all_accounts_with_modified_slots(&self) -> Vec<(Pubkey, Account, Slot)> {
         self.rc.accounts.load_all(&self.ancestors)
     }
@@ -3443,6 +3924,26 @@ impl Bank {
             .load_largest_accounts(&self.ancestors, num, filter_by_address, filter)
     }
 
+    /// Returns the quarantined accounts that still exceed the maximum account data length as of
+    /// this bank, along with their current data length
+    pub fn get_quarantined_accounts(&self) -> Vec<(Pubkey, usize)> {
+        let max_account_data_len = self
+            .bpf_compute_budget
+            .unwrap_or_else(|| BpfComputeBudget::new(&self.feature_set))
+            .max_account_data_len;
+        self.rc
+            .accounts
+            .accounts_db
+            .quarantined_accounts()
+            .into_iter()
+            .filter_map(|(pubkey, _)| {
+                self.get_account(&pubkey)
+                    .map(|account| (pubkey, account.data.len()))
+            })
+            .filter(|(_, data_len)| *data_len as u64 > max_account_data_len)
+            .collect()
+    }
+
     pub fn transaction_count(&self) -> u64 {
         self.transaction_count.load(Relaxed)
     }
@@ -3847,6 +4348,17 @@ impl Bank {
         self.rc.accounts.accounts_db.shrink_all_slots();
     }
 
+    pub fn set_shrink_config(&self, shrink_config: ShrinkConfig) {
+        self.rc
+            .accounts
+            .accounts_db
+            .set_shrink_config(shrink_config);
+    }
+
+    pub fn reclaimed_storage(&self) -> ReclaimedStorage {
+        self.rc.accounts.accounts_db.reclaimed_storage()
+    }
+
     pub fn print_accounts_stats(&self) {
         self.rc.accounts.accounts_db.print_accounts_stats("");
     }
@@ -4074,6 +4586,16 @@ impl Drop for Bank {
     }
 }
 
+/// Whether `transaction` only invokes the vote program, once
+fn is_simple_vote_transaction(transaction: &Transaction) -> bool {
+    let message = &transaction.message;
+    message.instructions.len() == 1
+        && message
+            .account_keys
+            .get(message.instructions[0].program_id_index as usize)
+            == Some(&solana_vote_program::id())
+}
+
 pub fn goto_end_of_slot(bank: &mut Bank) {
     let mut tick_hash = bank.last_blockhash();
     loop {
@@ -4087,7 +4609,7 @@ pub fn goto_end_of_slot(bank: &mut Bank) {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::{
         accounts_index::{AccountMap, Ancestors},
@@ -5919,6 +6441,266 @@ mod tests {
         assert!(bank1.calculate_and_verify_capitalization());
     }
 
+    #[test]
+    fn test_bank_partitioned_epoch_rewards() {
+        solana_logger::setup();
+
+        // create a bank that ticks really slowly...
+        let mut genesis_config = GenesisConfig {
+            accounts: (0..42)
+                .map(|_| {
+                    (
+                        solana_sdk::pubkey::new_rand(),
+                        Account::new(1_000_000_000, 0, &Pubkey::default()),
+                    )
+                })
+                .collect(),
+            // set it up so the first epoch is a full year long
+            poh_config: PohConfig {
+                target_tick_duration: Duration::from_secs(
+                    SECONDS_PER_YEAR as u64
+                        / EPOCH_CFG.MINIMUM_SLOTS_PER_EPOCH as u64
+                        / CLOCK_CFG.DEFAULT_TICKS_PER_SLOT,
+                ),
+                hashes_per_tick: None,
+                target_tick_count: None,
+            },
+            cluster_type: ClusterType::MainnetBeta,
+
+            ..GenesisConfig::default()
+        };
+        genesis_config.accounts.insert(
+            feature_set::partitioned_epoch_rewards::id(),
+            feature::create_account(
+                &Feature {
+                    activated_at: Some(0),
+                },
+                42,
+            ),
+        );
+        let bank = Arc::new(Bank::new(&genesis_config));
+        bank.lazy_rent_collection.store(true, Relaxed);
+
+        let ((vote_id, mut vote_account), (stake_id, stake_account)) =
+            crate::stakes::tests::create_staked_node_accounts(1_0000);
+        bank.add_account_and_update_capitalization(&stake_id, &stake_account);
+        let mut stake_ids = vec![stake_id];
+        for _ in 1..NUM_EPOCH_REWARDS_PARTITIONS * 2 {
+            let (stake_id, stake_account) =
+                crate::stakes::tests::create_stake_account(1_0000, &vote_id);
+            bank.add_account_and_update_capitalization(&stake_id, &stake_account);
+            stake_ids.push(stake_id);
+        }
+
+        // generate some rewards
+        let mut vote_state = VoteState::from(&vote_account).unwrap();
+        for i in 0..MAX_LOCKOUT_HISTORY + 42 {
+            vote_state.process_slot_vote_unchecked(i as u64);
+            let versioned = VoteStateVersions::Current(Box::new(vote_state.clone()));
+            VoteState::to(&versioned, &mut vote_account).unwrap();
+            bank.add_account_and_update_capitalization(&vote_id, &vote_account);
+        }
+        let pre_balances: Vec<_> = stake_ids.iter().map(|id| bank.get_balance(id)).collect();
+
+        // the first bank of epoch 1 only calculates the stake rewards
+        let mut bank = Arc::new(Bank::new_from_parent(
+            &bank,
+            &Pubkey::default(),
+            bank.get_slots_in_epoch(bank.epoch()) + 1,
+        ));
+        let distribution = bank.epoch_rewards_distribution().unwrap();
+        assert_eq!(distribution.rewarded_epoch, 0);
+        assert_eq!(
+            distribution.num_partitions() as u64,
+            NUM_EPOCH_REWARDS_PARTITIONS
+        );
+        assert_eq!(distribution.num_stake_rewards(), stake_ids.len());
+        assert!(distribution.total_stake_rewards > 0);
+        for (stake_id, pre_balance) in stake_ids.iter().zip(pre_balances.iter()) {
+            assert_eq!(bank.get_balance(stake_id), *pre_balance);
+        }
+        assert!(bank
+            .rewards
+            .read()
+            .unwrap()
+            .iter()
+            .all(|(_, reward_info)| reward_info.reward_type == RewardType::Voting));
+        bank.freeze();
+        assert!(bank.calculate_and_verify_capitalization());
+
+        // the following banks pay one partition each
+        let mut num_stake_rewards = 0;
+        while bank.is_distributing_epoch_rewards() {
+            bank = Arc::new(new_from_parent(&bank));
+            let rewards = bank.rewards.read().unwrap();
+            assert!(!rewards.is_empty());
+            assert!(rewards
+                .iter()
+                .all(|(_, reward_info)| reward_info.reward_type == RewardType::Staking));
+            num_stake_rewards += rewards.len();
+            drop(rewards);
+            bank.freeze();
+            assert!(bank.calculate_and_verify_capitalization());
+        }
+        assert_eq!(num_stake_rewards, stake_ids.len());
+        assert_eq!(bank.block_height(), distribution.end_block_height());
+
+        let stake_rewards_paid: u64 = stake_ids
+            .iter()
+            .zip(pre_balances)
+            .map(|(stake_id, pre_balance)| bank.get_balance(stake_id) - pre_balance)
+            .sum();
+        assert_eq!(stake_rewards_paid, distribution.total_stake_rewards);
+    }
+
+    pub(crate) fn new_epoch_rewards_genesis_config(
+        genesis_accounts: &[(Pubkey, Account)],
+        partitioned: bool,
+    ) -> GenesisConfig {
+        let mut genesis_config = GenesisConfig {
+            accounts: genesis_accounts.iter().cloned().collect(),
+            // set it up so the first epoch is a full year long
+            poh_config: PohConfig {
+                target_tick_duration: Duration::from_secs(
+                    SECONDS_PER_YEAR as u64
+                        / EPOCH_CFG.MINIMUM_SLOTS_PER_EPOCH as u64
+                        / CLOCK_CFG.DEFAULT_TICKS_PER_SLOT,
+                ),
+                hashes_per_tick: None,
+                target_tick_count: None,
+            },
+            cluster_type: ClusterType::MainnetBeta,
+
+            ..GenesisConfig::default()
+        };
+        // the feature account exists either way, so that the capitalization is the same
+        genesis_config.accounts.insert(
+            feature_set::partitioned_epoch_rewards::id(),
+            feature::create_account(
+                &Feature {
+                    activated_at: if partitioned { Some(0) } else { None },
+                },
+                42,
+            ),
+        );
+        genesis_config
+    }
+
+    // A bank of epoch 0 with a vote account having earned credits, and `stakes` delegated to it
+    pub(crate) fn new_epoch_rewards_bank(
+        genesis_config: &GenesisConfig,
+        vote: &(Pubkey, Account),
+        stakes: &[(Pubkey, Account)],
+    ) -> Arc<Bank> {
+        let bank = Arc::new(Bank::new(genesis_config));
+        bank.lazy_rent_collection.store(true, Relaxed);
+        for (stake_id, stake_account) in stakes {
+            bank.add_account_and_update_capitalization(stake_id, stake_account);
+        }
+        let (vote_id, mut vote_account) = vote.clone();
+        let mut vote_state = VoteState::from(&vote_account).unwrap();
+        for i in 0..MAX_LOCKOUT_HISTORY + 42 {
+            vote_state.process_slot_vote_unchecked(i as u64);
+        }
+        let versioned = VoteStateVersions::Current(Box::new(vote_state));
+        VoteState::to(&versioned, &mut vote_account).unwrap();
+        bank.add_account_and_update_capitalization(&vote_id, &vote_account);
+        bank
+    }
+
+    pub(crate) fn new_epoch_rewards_accounts() -> (
+        Vec<(Pubkey, Account)>,
+        (Pubkey, Account),
+        Vec<(Pubkey, Account)>,
+    ) {
+        let genesis_accounts = (0..42)
+            .map(|_| {
+                (
+                    solana_sdk::pubkey::new_rand(),
+                    Account::new(1_000_000_000, 0, &Pubkey::default()),
+                )
+            })
+            .collect();
+        let (vote, stake) = crate::stakes::tests::create_staked_node_accounts(1_0000);
+        let mut stakes = vec![stake];
+        for _ in 1..NUM_EPOCH_REWARDS_PARTITIONS * 2 {
+            stakes.push(crate::stakes::tests::create_stake_account(1_0000, &vote.0));
+        }
+        (genesis_accounts, vote, stakes)
+    }
+
+    #[test]
+    fn test_bank_partitioned_epoch_rewards_paid_in_full() {
+        let (genesis_accounts, vote, stakes) = new_epoch_rewards_accounts();
+        let balances_after_rewards = |partitioned| -> Vec<u64> {
+            let genesis_config = new_epoch_rewards_genesis_config(&genesis_accounts, partitioned);
+            let bank = new_epoch_rewards_bank(&genesis_config, &vote, &stakes);
+            let mut bank = Arc::new(Bank::new_from_parent(
+                &bank,
+                &Pubkey::default(),
+                bank.get_slots_in_epoch(bank.epoch()) + 1,
+            ));
+            while bank.is_distributing_epoch_rewards() {
+                bank = Arc::new(new_from_parent(&bank));
+            }
+            bank.freeze();
+            assert!(bank.calculate_and_verify_capitalization());
+            stakes
+                .iter()
+                .map(|(stake_id, _)| bank.get_balance(stake_id))
+                .collect()
+        };
+
+        let balances = balances_after_rewards(false);
+        for ((_, stake_account), balance) in stakes.iter().zip(&balances) {
+            assert!(*balance > stake_account.lamports);
+        }
+        // every stake account is paid the same reward once the partitions are all paid
+        assert_eq!(balances_after_rewards(true), balances);
+    }
+
+    #[test]
+    fn test_bank_partitioned_epoch_rewards_flushed_by_next_epoch() {
+        let (genesis_accounts, vote, stakes) = new_epoch_rewards_accounts();
+        let genesis_config = new_epoch_rewards_genesis_config(&genesis_accounts, true);
+        let bank0 = new_epoch_rewards_bank(&genesis_config, &vote, &stakes);
+        let bank1 = Arc::new(Bank::new_from_parent(
+            &bank0,
+            &Pubkey::default(),
+            bank0.get_slots_in_epoch(bank0.epoch()) + 1,
+        ));
+        assert!(bank1.epoch_rewards_distribution().unwrap().num_partitions() > 1);
+        let bank2 = Arc::new(new_from_parent(&bank1));
+        assert!(bank2.is_distributing_epoch_rewards());
+
+        // the first bank of the next epoch pays the partitions left
+        let bank3 = Bank::new_from_parent(
+            &bank2,
+            &Pubkey::default(),
+            bank2
+                .epoch_schedule()
+                .get_first_slot_in_epoch(bank2.epoch() + 1),
+        );
+        let stake_rewards = |bank: &Bank| -> Vec<Pubkey> {
+            bank.rewards
+                .read()
+                .unwrap()
+                .iter()
+                .filter(|(_, reward_info)| reward_info.reward_type == RewardType::Staking)
+                .map(|(stake_id, _)| *stake_id)
+                .collect()
+        };
+        let mut paid = stake_rewards(&bank2);
+        assert!(!paid.is_empty());
+        paid.extend(stake_rewards(&bank3));
+        paid.sort();
+        let mut stake_ids: Vec<_> = stakes.iter().map(|(stake_id, _)| *stake_id).collect();
+        stake_ids.sort();
+        assert_eq!(paid, stake_ids);
+        bank3.freeze();
+        assert!(bank3.calculate_and_verify_capitalization());
+    }
+
     fn do_test_bank_update_rewards_determinism() -> u64 {
         // create a bank that ticks really slowly...
         let bank = Arc::new(Bank::new(&GenesisConfig {
@@ -6236,6 +7018,35 @@ mod tests {
         assert_eq!(bank.get_balance(&pubkey), 500);
     }
 
+    #[test]
+    fn test_simulate_transaction_with_details() {
+        let (genesis_config, mint_keypair) = create_genesis_config(10_000);
+        let bank = Bank::new(&genesis_config);
+        let pubkey = solana_sdk::pubkey::new_rand();
+        let unreferenced_pubkey = solana_sdk::pubkey::new_rand();
+        bank.transfer(100, &mint_keypair, &unreferenced_pubkey)
+            .unwrap();
+        bank.freeze();
+
+        let blockhash = bank.last_blockhash();
+        let tx = system_transaction::transfer(&mint_keypair, &pubkey, 500, blockhash);
+        let details =
+            bank.simulate_transaction_with_details(tx, true, &[pubkey, unreferenced_pubkey]);
+        assert_eq!(details.result, Ok(()));
+        assert_eq!(details.inner_instructions, Some(vec![vec![]]));
+        assert_eq!(details.accounts[0].as_ref().unwrap().lamports, 500);
+        assert_eq!(details.accounts[1].as_ref().unwrap().lamports, 100);
+        // Nothing was committed
+        assert_eq!(bank.get_balance(&pubkey), 0);
+
+        // A failed transaction reports the current state of the bank
+        let tx = system_transaction::transfer(&mint_keypair, &pubkey, 1_000_000, blockhash);
+        let details = bank.simulate_transaction_with_details(tx, false, &[pubkey]);
+        assert!(details.result.is_err());
+        assert_eq!(details.inner_instructions, None);
+        assert_eq!(details.accounts, vec![None]);
+    }
+
     #[test]
     fn test_transfer_to_sysvar() {
         solana_logger::setup();
@@ -6735,6 +7546,44 @@ mod tests {
         assert!(batch2.lock_results()[0].is_ok());
     }
 
+    #[test]
+    fn test_account_lock_set() {
+        let (genesis_config, mint_keypair) = create_genesis_config(500);
+        let bank = Bank::new(&genesis_config);
+        let key1 = solana_sdk::pubkey::new_rand();
+        let tx = system_transaction::transfer(&mint_keypair, &key1, 1, genesis_config.hash());
+        let txs = vec![tx];
+        let lock_set = AccountLockSet::try_from_transaction(&txs[0]).unwrap();
+
+        assert!(bank.try_lock_account_set(&lock_set).is_ok());
+        assert!(!bank.can_lock_account_set(&lock_set));
+        {
+            let batch = bank.prepare_batch(&txs, None);
+            assert_eq!(batch.lock_results()[0], Err(TransactionError::AccountInUse));
+        }
+
+        // Execute under the held locks, which outlive the batch
+        {
+            let batch = bank.prepare_batch_with_held_locks(&txs, None);
+            let results = bank
+                .load_execute_and_commit_transactions(
+                    &batch,
+                    *MAX_PROCESSING_AGE,
+                    false,
+                    false,
+                    false,
+                )
+                .0
+                .fee_collection_results;
+            assert_eq!(results, vec![Ok(())]);
+        }
+        assert!(!bank.can_lock_account_set(&lock_set));
+
+        bank.unlock_account_set(&lock_set);
+        assert!(bank.can_lock_account_set(&lock_set));
+        assert_eq!(bank.get_balance(&key1), 1);
+    }
+
     #[test]
     fn test_bank_invalid_account_index() {
         let (genesis_config, mint_keypair) = create_genesis_config(1);
@@ -7678,6 +8527,108 @@ mod tests {
         bank3.squash();
         assert_eq!(bank1.get_program_accounts(&program_id).len(), 2);
         assert_eq!(bank3.get_program_accounts(&program_id).len(), 2);
+        assert_eq!(
+            bank3.get_filtered_program_accounts(&program_id, |account| account.lamports == 3),
+            vec![(pubkey1, account1)]
+        );
+    }
+
+    #[test]
+    fn test_bank_get_indexed_accounts() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(500);
+        let account_indexes = [AccountIndex::ProgramId, AccountIndex::SplTokenMint]
+            .iter()
+            .copied()
+            .collect();
+        let bank0 = Arc::new(Bank::new_with_paths(
+            &genesis_config,
+            Vec::new(),
+            &[],
+            None,
+            None,
+            account_indexes,
+            AppendVecBackend::default(),
+        ));
+        assert!(bank0.is_account_index_enabled(AccountIndex::ProgramId));
+
+        let program_id = Pubkey::new(&[2; 32]);
+        let pubkey0 = solana_sdk::pubkey::new_rand();
+        let account0 = Account::new(1, 0, &program_id);
+        bank0.store_account(&pubkey0, &account0);
+        let mint = solana_sdk::pubkey::new_rand();
+        let pubkey1 = solana_sdk::pubkey::new_rand();
+        let mut token_account = Account::new(
+            1,
+            inline_spl_token_v2_0::state::ACCOUNT_LEN,
+            &inline_spl_token_v2_0::id(),
+        );
+        token_account.data[..32].copy_from_slice(mint.as_ref());
+        bank0.store_account(&pubkey1, &token_account);
+        assert_eq!(
+            bank0.get_program_accounts(&program_id),
+            vec![(pubkey0, account0)]
+        );
+        assert_eq!(
+            bank0.get_filtered_indexed_accounts(&IndexKey::SplTokenMint(mint), |_| true),
+            vec![(pubkey1, token_account)]
+        );
+
+        // Accounts no longer having the key are filtered out
+        let bank1 = Arc::new(new_from_parent(&bank0));
+        let other_program_id = Pubkey::new(&[3; 32]);
+        let account1 = Account::new(1, 0, &other_program_id);
+        bank1.store_account(&pubkey0, &account1);
+        bank1.store_account(&pubkey1, &account1);
+        assert!(bank1.get_program_accounts(&program_id).is_empty());
+        assert!(bank1
+            .get_filtered_indexed_accounts(&IndexKey::SplTokenMint(mint), |_| true)
+            .is_empty());
+        let mut accounts = bank1.get_program_accounts(&other_program_id);
+        accounts.sort_by_key(|(pubkey, _)| *pubkey);
+        let mut expected = vec![(pubkey0, account1.clone()), (pubkey1, account1)];
+        expected.sort_by_key(|(pubkey, _)| *pubkey);
+        assert_eq!(accounts, expected);
+        assert_eq!(bank0.get_program_accounts(&program_id).len(), 1);
+    }
+
+    #[test]
+    fn test_bank_get_program_accounts_page() {
+        let (genesis_config, _mint_keypair) = create_genesis_config(500);
+        let bank = Bank::new(&genesis_config);
+        let program_id = Pubkey::new(&[2; 32]);
+        let mut pubkeys: Vec<_> = (0..5).map(|_| solana_sdk::pubkey::new_rand()).collect();
+        pubkeys.sort();
+        for (i, pubkey) in pubkeys.iter().enumerate() {
+            bank.store_account(pubkey, &Account::new(i as u64 + 1, 0, &program_id));
+        }
+        bank.store_account(
+            &solana_sdk::pubkey::new_rand(),
+            &Account::new(1, 0, &Pubkey::new(&[3; 32])),
+        );
+        let page_pubkeys = |page: Vec<(Pubkey, Account)>| -> Vec<Pubkey> {
+            page.into_iter().map(|(pubkey, _)| pubkey).collect()
+        };
+
+        let page = bank.get_filtered_program_accounts_page(&program_id, |_| true, None, 2);
+        assert_eq!(page_pubkeys(page), pubkeys[..2].to_vec());
+        // The scan resumes after the cursor
+        let page =
+            bank.get_filtered_program_accounts_page(&program_id, |_| true, Some(pubkeys[1]), 2);
+        assert_eq!(page_pubkeys(page), pubkeys[2..4].to_vec());
+        let page =
+            bank.get_filtered_program_accounts_page(&program_id, |_| true, Some(pubkeys[3]), 2);
+        assert_eq!(page_pubkeys(page), pubkeys[4..].to_vec());
+        // Accounts failing the filter don't count toward the limit
+        let page = bank.get_filtered_program_accounts_page(
+            &program_id,
+            |account| account.lamports % 2 == 1,
+            None,
+            2,
+        );
+        assert_eq!(page_pubkeys(page), vec![pubkeys[0], pubkeys[2]]);
+        assert!(bank
+            .get_filtered_program_accounts_page(&program_id, |_| true, None, 0)
+            .is_empty());
     }
 
     #[test]
@@ -9912,6 +10863,64 @@ mod tests {
         assert_eq!(old_hash, new_bank1_hash);
     }
 
+    #[test]
+    fn test_transaction_log_collector() {
+        let (genesis_config, mint_keypair) = create_genesis_config(10_000);
+        let bank0 = Arc::new(Bank::new(&genesis_config));
+        bank0.set_transaction_log_collector_config(TransactionLogCollectorConfig {
+            max_logs_per_bank: 2,
+            include_votes: false,
+        });
+        let blockhash = bank0.last_blockhash();
+
+        let alice = solana_sdk::pubkey::new_rand();
+        let bob = solana_sdk::pubkey::new_rand();
+        let to_alice = system_transaction::transfer(&mint_keypair, &alice, 1, blockhash);
+        let to_bob = system_transaction::transfer(&mint_keypair, &bob, 1, blockhash);
+        let too_much = system_transaction::transfer(&mint_keypair, &bob, 100_000, blockhash);
+        assert_eq!(bank0.process_transaction(&to_alice), Ok(()));
+        assert_eq!(bank0.process_transaction(&to_bob), Ok(()));
+        assert!(bank0.process_transaction(&too_much).is_err());
+
+        // The oldest log is dropped, and the failed transaction kept
+        let logs = bank0.get_transaction_logs(None);
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].signature, too_much.signatures[0]);
+        assert!(logs[0].result.is_err());
+        assert_eq!(logs[1].signature, to_bob.signatures[0]);
+        assert_eq!(logs[1].result, Ok(()));
+        assert!(!logs[1].is_vote);
+        assert!(logs[1].mentions(&system_program::id()));
+        assert!(bank0.get_transaction_logs(Some(&alice)).is_empty());
+
+        // The config is inherited, while each bank keeps its own logs
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        let to_alice = system_transaction::transfer(&mint_keypair, &alice, 2, blockhash);
+        assert_eq!(bank1.process_transaction(&to_alice), Ok(()));
+        assert_eq!(bank1.get_transaction_logs(None).len(), 1);
+        let recent_logs: Vec<_> = bank1
+            .get_recent_transaction_logs(None, 10)
+            .into_iter()
+            .map(|(slot, info)| (slot, info.signature))
+            .collect();
+        assert_eq!(
+            recent_logs,
+            vec![
+                (1, to_alice.signatures[0]),
+                (0, too_much.signatures[0]),
+                (0, to_bob.signatures[0]),
+            ]
+        );
+        assert_eq!(bank1.get_recent_transaction_logs(Some(&bob), 1).len(), 1);
+        assert_eq!(bank1.get_recent_transaction_logs(Some(&alice), 10).len(), 1);
+
+        // No logs are kept once disabled
+        bank1.set_transaction_log_collector_config(TransactionLogCollectorConfig::default());
+        let to_bob = system_transaction::transfer(&mint_keypair, &bob, 2, blockhash);
+        assert_eq!(bank1.process_transaction(&to_bob), Ok(()));
+        assert_eq!(bank1.get_transaction_logs(None).len(), 1);
+    }
+
     #[test]
     fn test_truncate_log_messages() {
         let mut messages = vec![
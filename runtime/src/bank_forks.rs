@@ -25,6 +25,29 @@ pub enum CompressionType {
     NoCompression,
 }
 
+/// Tuning of the snapshot archive compression, currently only honored by `CompressionType::Zstd`
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CompressionOptions {
+    // zstd compression level, 0 selects the zstd default level
+    pub zstd_level: i32,
+
+    // Number of compression worker threads, 0 compresses on the snapshot packager thread
+    pub threads: u32,
+}
+
+/// Throttling of the snapshot archive creation, so that it leaves IO and CPU to replay
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SnapshotThrottleOptions {
+    // Maximum rate at which the archive is written, 0 writes it as fast as possible
+    pub max_write_bytes_per_sec: u64,
+
+    // Package snapshots with the lowest CPU priority and the idle IO priority
+    pub low_priority: bool,
+
+    // Pause writing the archive while this validator is producing a block
+    pub pause_during_leader_slots: bool,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct SnapshotConfig {
     // Generate a new snapshot every this many slots
@@ -38,10 +61,31 @@ pub struct SnapshotConfig {
 
     pub compression: CompressionType,
 
+    pub compression_options: CompressionOptions,
+
+    pub throttle_options: SnapshotThrottleOptions,
+
     // Snapshot version to generate
     pub snapshot_version: SnapshotVersion,
 }
 
+/// Limits on the unrooted banks kept during unstable periods, enforced by
+/// `BankForks::prune_forks`. The limits left to `None` are not enforced.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PruningConfig {
+    // Prune the forks whose highest slot is this many slots behind the highest bank
+    pub max_fork_depth: Option<Slot>,
+
+    // Maximum number of banks descending from the root
+    pub max_root_descendants: Option<usize>,
+
+    // Maximum number of banks, including the ones kept below the root
+    pub max_banks: Option<usize>,
+
+    // Budget for the estimated memory held by the banks descending from the root
+    pub max_memory_bytes: Option<u64>,
+}
+
 pub struct BankForks {
     pub banks: HashMap<Slot, Arc<Bank>>,
     root: Slot,
@@ -49,6 +93,8 @@ pub struct BankForks {
 
     pub accounts_hash_interval_slots: Slot,
     last_accounts_hash_slot: Slot,
+
+    pruning_config: PruningConfig,
 }
 
 impl Index<u64> for BankForks {
@@ -145,6 +191,7 @@ impl BankForks {
             snapshot_config: None,
             accounts_hash_interval_slots: std::u64::MAX,
             last_accounts_hash_slot: root,
+            pruning_config: PruningConfig::default(),
         }
     }
 
@@ -216,7 +263,16 @@ impl BankForks {
                 bank.squash();
                 is_root_bank_squashed = bank_slot == root;
 
-                if self.snapshot_config.is_some() && snapshot_request_sender.is_some() {
+                let snapshots_enabled =
+                    self.snapshot_config.is_some() && snapshot_request_sender.is_some();
+                if snapshots_enabled && self.root_bank().is_distributing_epoch_rewards() {
+                    // Pending stake rewards are not part of the snapshot, so none can be
+                    // taken until they have all been paid
+                    info!(
+                        "Skipping snapshot of bank {} during epoch rewards distribution",
+                        root
+                    );
+                } else if snapshots_enabled {
                     let snapshot_root_bank = self.root_bank().clone();
                     let root_slot = snapshot_root_bank.slot();
                     if let Err(e) =
@@ -276,6 +332,111 @@ impl BankForks {
         );
     }
 
+    /// Prunes the forks exceeding the limits of the pruning config, the ones with the oldest
+    /// highest slot first, and returns the slots of the banks removed.
+    ///
+    /// The forks of `protected_slots` are kept, ancestors and descendants: callers pass the slots
+    /// they vote on and build on. Of the other forks, only the ones for which `can_prune` returns
+    /// true, given the slot at which they branch off, are pruned: callers only allow the forks
+    /// they could not vote on or switch to. Only the banks are dropped, the pruned slots are
+    /// replayed again if they become relevant.
+    pub fn prune_forks<F>(&mut self, protected_slots: &[Slot], can_prune: F) -> Vec<Slot>
+    where
+        F: Fn(Slot) -> bool,
+    {
+        let config = self.pruning_config.clone();
+        if config == PruningConfig::default() {
+            return vec![];
+        }
+        let root = self.root;
+        let descendants = self.descendants();
+        let mut kept: HashSet<Slot> = HashSet::new();
+        kept.insert(root);
+        for slot in protected_slots {
+            if let Some(bank) = self.banks.get(slot) {
+                kept.extend(bank.ancestors.keys().filter(|slot| **slot >= root));
+                kept.extend(&descendants[slot]);
+            }
+        }
+
+        // The forks branching off the kept banks, with their highest slot
+        let mut forks: Vec<(Slot, Vec<Slot>)> = self
+            .banks
+            .values()
+            .filter(|bank| {
+                bank.slot() > root
+                    && !kept.contains(&bank.slot())
+                    && kept.contains(&bank.parent_slot())
+                    && can_prune(bank.slot())
+            })
+            .map(|bank| {
+                let mut slots: Vec<Slot> = descendants[&bank.slot()].iter().cloned().collect();
+                slots.push(bank.slot());
+                (*slots.iter().max().unwrap(), slots)
+            })
+            .collect();
+        forks.sort_unstable_by_key(|(highest_slot, _)| *highest_slot);
+
+        let highest_slot = self.highest_slot();
+        let mut num_banks = self.banks.len();
+        let mut num_root_descendants = descendants[&root].len();
+        let mut memory_bytes: u64 = descendants[&root]
+            .iter()
+            .map(|slot| self.banks[slot].estimated_memory_bytes())
+            .sum();
+        let mut num_pruned_forks = 0;
+        let mut pruned_memory_bytes = 0;
+        let mut pruned = vec![];
+        let exceeds = |limit: Option<u64>, value: u64| limit.map_or(false, |limit| value > limit);
+        for (fork_highest_slot, slots) in forks {
+            if !exceeds(config.max_fork_depth, highest_slot - fork_highest_slot)
+                && !exceeds(config.max_banks.map(|l| l as u64), num_banks as u64)
+                && !exceeds(
+                    config.max_root_descendants.map(|l| l as u64),
+                    num_root_descendants as u64,
+                )
+                && !exceeds(config.max_memory_bytes, memory_bytes)
+            {
+                continue;
+            }
+            let fork_memory_bytes: u64 = slots
+                .iter()
+                .map(|slot| self.banks[slot].estimated_memory_bytes())
+                .sum();
+            num_banks -= slots.len();
+            num_root_descendants -= slots.len();
+            memory_bytes -= fork_memory_bytes;
+            num_pruned_forks += 1;
+            pruned_memory_bytes += fork_memory_bytes;
+            pruned.extend(slots);
+        }
+        if pruned.is_empty() {
+            return pruned;
+        }
+
+        for slot in &pruned {
+            self.banks.remove(slot);
+        }
+        info!(
+            "pruned {} forks, slots: {:?}, kept {} banks",
+            num_pruned_forks,
+            pruned,
+            self.banks.len()
+        );
+        datapoint_info!(
+            "bank_forks_prune_forks",
+            ("num_forks", num_pruned_forks, i64),
+            ("num_banks", pruned.len(), i64),
+            ("memory_bytes", pruned_memory_bytes, i64),
+            ("num_banks_retained", self.banks.len(), i64),
+        );
+        pruned
+    }
+
+    pub fn set_pruning_config(&mut self, pruning_config: PruningConfig) {
+        self.pruning_config = pruning_config;
+    }
+
     pub fn set_snapshot_config(&mut self, snapshot_config: Option<SnapshotConfig>) {
         self.snapshot_config = snapshot_config;
     }
@@ -378,4 +539,42 @@ mod tests {
         bank_forks.insert(child_bank);
         assert_eq!(bank_forks.active_banks(), vec![1]);
     }
+
+    #[test]
+    fn test_bank_forks_prune_forks() {
+        let GenesisConfigInfo { genesis_config, .. } = create_genesis_config(10_000);
+        let bank = Bank::new(&genesis_config);
+        let mut bank_forks = BankForks::new(bank);
+        // 0 - 1 - 2 - 5
+        //  \- 3
+        //  \- 4 - 6
+        for (parent, slot) in &[(0, 1), (1, 2), (0, 3), (0, 4), (2, 5), (4, 6)] {
+            let bank = Bank::new_from_parent(&bank_forks[*parent], &Pubkey::default(), *slot);
+            bank_forks.insert(bank);
+        }
+        // Nothing is pruned without limits
+        assert!(bank_forks.prune_forks(&[5], |_| true).is_empty());
+
+        bank_forks.set_pruning_config(PruningConfig {
+            max_fork_depth: Some(1),
+            ..PruningConfig::default()
+        });
+        // Nor the forks the caller does not allow to prune
+        assert!(bank_forks.prune_forks(&[5], |_| false).is_empty());
+        assert_eq!(bank_forks.prune_forks(&[5], |_| true), vec![3]);
+        assert!(bank_forks.get(3).is_none());
+        // The protected forks are kept, however deep
+        assert!(bank_forks.prune_forks(&[1, 4], |_| true).is_empty());
+
+        bank_forks.set_pruning_config(PruningConfig {
+            max_banks: Some(4),
+            ..PruningConfig::default()
+        });
+        let mut pruned = bank_forks.prune_forks(&[5], |slot| slot == 4);
+        pruned.sort_unstable();
+        assert_eq!(pruned, vec![4, 6]);
+        let mut slots: Vec<_> = bank_forks.banks.keys().cloned().collect();
+        slots.sort_unstable();
+        assert_eq!(slots, vec![0, 1, 2, 5]);
+    }
 }
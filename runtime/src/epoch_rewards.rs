@@ -0,0 +1,156 @@
+//! Stake rewards that are paid out over several blocks following the first block of an epoch,
+//! instead of all at once, to avoid a replay spike at the epoch boundary. The first block of the
+//! epoch calculates the rewards and pays the vote rewards; with the `partitioned_epoch_rewards`
+//! feature active, the stake rewards are then split into partitions, each paid by one of the
+//! blocks that follow.
+
+use serde::{Deserialize, Serialize};
+use solana_sdk::{
+    clock::{Epoch, Slot},
+    pubkey::Pubkey,
+};
+
+/// Maximum number of partitions the stake rewards of an epoch are split into. The partitions are
+/// runs of consecutive stake accounts in pubkey order, of `ceil(num_stake_accounts /
+/// NUM_EPOCH_REWARDS_PARTITIONS)` accounts each save for the last one, so there may be fewer
+/// partitions than this when there are few stake accounts. As every node must pay the same
+/// partitions in the same blocks, this can't change without a feature.
+pub const NUM_EPOCH_REWARDS_PARTITIONS: u64 = 16;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, AbiExample)]
+pub struct StakeReward {
+    pub stake_pubkey: Pubkey,
+    pub stakers_reward: u64,
+    /// The stake's `credits_observed` once the reward has been redeemed
+    pub credits_observed: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize, AbiExample)]
+pub struct EpochRewardsDistribution {
+    /// The epoch the rewards were earned in
+    pub rewarded_epoch: Epoch,
+    /// Slot of the bank that calculated the rewards
+    pub calculation_slot: Slot,
+    /// Block height of the bank that calculated the rewards. Partition `i` is paid by the
+    /// descendant at block height `calculation_block_height + 1 + i`, or all at once by the first
+    /// block of the next epoch if it comes first
+    pub calculation_block_height: u64,
+    pub total_stake_rewards: u64,
+    partitions: Vec<Vec<StakeReward>>,
+}
+
+impl EpochRewardsDistribution {
+    pub fn new(
+        rewarded_epoch: Epoch,
+        calculation_slot: Slot,
+        calculation_block_height: u64,
+        mut stake_rewards: Vec<StakeReward>,
+        num_partitions: u64,
+    ) -> Self {
+        // Sort so that every node builds the same partitions
+        stake_rewards.sort_unstable_by(|a, b| a.stake_pubkey.cmp(&b.stake_pubkey));
+        let total_stake_rewards = stake_rewards.iter().map(|r| r.stakers_reward).sum();
+        let num_partitions = num_partitions.max(1) as usize;
+        let partition_size = (stake_rewards.len() + num_partitions - 1) / num_partitions;
+        let partitions = if partition_size == 0 {
+            vec![]
+        } else {
+            stake_rewards
+                .chunks(partition_size)
+                .map(|chunk| chunk.to_vec())
+                .collect()
+        };
+        Self {
+            rewarded_epoch,
+            calculation_slot,
+            calculation_block_height,
+            total_stake_rewards,
+            partitions,
+        }
+    }
+
+    pub fn num_partitions(&self) -> usize {
+        self.partitions.len()
+    }
+
+    pub fn num_stake_rewards(&self) -> usize {
+        self.partitions.iter().map(Vec::len).sum()
+    }
+
+    pub fn partition(&self, index: usize) -> &[StakeReward] {
+        &self.partitions[index]
+    }
+
+    /// The partition to be paid by the bank at `block_height`, if any
+    pub fn partition_index(&self, block_height: u64) -> Option<usize> {
+        let offset = block_height.checked_sub(self.calculation_block_height + 1)? as usize;
+        if offset < self.num_partitions() {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    /// Number of partitions paid by the bank at `block_height` and its ancestors
+    pub fn num_distributed_partitions(&self, block_height: u64) -> usize {
+        (block_height.saturating_sub(self.calculation_block_height) as usize)
+            .min(self.num_partitions())
+    }
+
+    pub fn is_complete(&self, block_height: u64) -> bool {
+        self.num_distributed_partitions(block_height) == self.num_partitions()
+    }
+
+    /// Block height of the bank that pays the last partition
+    pub fn end_block_height(&self) -> u64 {
+        self.calculation_block_height + self.num_partitions() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_rewards_distribution_partitions() {
+        let stake_rewards: Vec<_> = (0..10)
+            .map(|i| StakeReward {
+                stake_pubkey: solana_sdk::pubkey::new_rand(),
+                stakers_reward: i,
+                credits_observed: 0,
+            })
+            .collect();
+        let distribution = EpochRewardsDistribution::new(1, 32, 30, stake_rewards.clone(), 4);
+        assert_eq!(distribution.num_partitions(), 4);
+        assert_eq!(distribution.num_stake_rewards(), 10);
+        assert_eq!(distribution.total_stake_rewards, 45);
+        assert_eq!(distribution.end_block_height(), 34);
+
+        assert_eq!(distribution.partition_index(30), None);
+        assert_eq!(distribution.partition_index(31), Some(0));
+        assert_eq!(distribution.partition_index(34), Some(3));
+        assert_eq!(distribution.partition_index(35), None);
+        assert!(!distribution.is_complete(33));
+        assert!(distribution.is_complete(34));
+
+        // Every reward lands in exactly one partition, in a deterministic order
+        let mut distributed: Vec<_> = (0..distribution.num_partitions())
+            .flat_map(|i| distribution.partition(i).to_vec())
+            .collect();
+        let mut reversed = stake_rewards;
+        reversed.reverse();
+        assert_eq!(
+            EpochRewardsDistribution::new(1, 32, 30, reversed, 4),
+            distribution
+        );
+        distributed.dedup();
+        assert_eq!(distributed.len(), 10);
+
+        // More partitions than rewards
+        let distribution = EpochRewardsDistribution::new(1, 32, 30, distributed, 16);
+        assert_eq!(distribution.num_partitions(), 10);
+        let distribution = EpochRewardsDistribution::new(1, 32, 30, vec![], 16);
+        assert_eq!(distribution.num_partitions(), 0);
+        assert!(distribution.is_complete(30));
+    }
+}
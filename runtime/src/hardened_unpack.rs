@@ -1,15 +1,22 @@
 use bzip2::bufread::BzDecoder;
+use crossbeam_channel::{bounded, Sender};
 use log::*;
 use regex::Regex;
 use solana_sdk::genesis_config::GenesisConfig;
 use std::{
+    fmt,
     fs::{self, File},
     io::{BufReader, Read},
     path::{
         Component::{CurDir, Normal},
-        Path,
+        Path, PathBuf,
     },
-    time::Instant,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{Builder, JoinHandle},
+    time::{Duration, Instant},
 };
 use tar::{
     Archive,
@@ -64,12 +71,207 @@ fn check_unpack_result(unpack_result: bool, path: String) -> Result<()> {
     Ok(())
 }
 
+/// Progress of an archive being unpacked
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct UnpackProgress {
+    /// Bytes of archive entries unpacked so far
+    pub unpacked_bytes: u64,
+    /// Archive entries unpacked so far
+    pub unpacked_entries: u64,
+    /// Time spent unpacking so far
+    pub elapsed: Duration,
+    /// Estimated time remaining, only known if the archive is read through an
+    /// `ArchiveReadTracker`
+    pub eta: Option<Duration>,
+}
+
+impl fmt::Display for UnpackProgress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unpacked {} entries, {} bytes in {:?}",
+            self.unpacked_entries, self.unpacked_bytes, self.elapsed
+        )?;
+        if let Some(eta) = self.eta {
+            write!(f, ", ETA {:?}", eta)?;
+        }
+        Ok(())
+    }
+}
+
+pub type UnpackProgressCallback<'a> = &'a dyn Fn(&UnpackProgress);
+
+/// Counts the bytes read from an archive file of known size, so that the time remaining to
+/// unpack it can be estimated
+#[derive(Clone)]
+pub struct ArchiveReadTracker {
+    bytes_read: Arc<AtomicU64>,
+    archive_size: u64,
+}
+
+impl ArchiveReadTracker {
+    pub fn new(archive_size: u64) -> Self {
+        Self {
+            bytes_read: Arc::new(AtomicU64::new(0)),
+            archive_size,
+        }
+    }
+
+    /// Wrap `reader`, which must read the archive file this tracker was created for
+    pub fn reader<R: Read>(&self, reader: R) -> TrackedReader<R> {
+        TrackedReader {
+            reader,
+            bytes_read: self.bytes_read.clone(),
+        }
+    }
+
+    fn eta(&self, elapsed: Duration) -> Option<Duration> {
+        let bytes_read = self.bytes_read.load(Ordering::Relaxed);
+        if bytes_read == 0 || bytes_read > self.archive_size {
+            return None;
+        }
+        let remaining = (self.archive_size - bytes_read) as f64 / bytes_read as f64;
+        Some(elapsed.mul_f64(remaining))
+    }
+}
+
+pub struct TrackedReader<R> {
+    reader: R,
+    bytes_read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for TrackedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.bytes_read.fetch_add(len as u64, Ordering::Relaxed);
+        Ok(len)
+    }
+}
+
+#[derive(Default)]
+pub struct UnpackOptions<'a> {
+    /// Number of threads writing out unpacked files. With less than 2, files are written
+    /// out by the thread reading the archive
+    pub parallel_workers: usize,
+    /// Called every `PROGRESS_INTERVAL` with the progress so far, and once unpacking completes
+    pub progress_callback: Option<UnpackProgressCallback<'a>>,
+    /// Tracker the archive is read through, used to estimate the time remaining
+    pub read_tracker: Option<ArchiveReadTracker>,
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+// Larger entries are unpacked by the reading thread, rather than buffered for a worker
+const MAX_PARALLEL_UNPACK_ENTRY_SIZE: u64 = 64 * 1024 * 1024; // 64 MiB
+
+fn unpacked_entry_size<R: Read>(entry: &tar::Entry<R>) -> Result<u64> {
+    let header = entry.header();
+    match (header.entry_type(), header.as_gnu()) {
+        (GNUSparse, Some(gnu_header)) => Ok(gnu_header.real_size()?),
+        _ => Ok(header.size()?),
+    }
+}
+
+/// A sanitized regular file read out of the archive, to be written out by an unpack worker
+struct UnpackedFile {
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+struct UnpackWorkers {
+    sender: Option<Sender<UnpackedFile>>,
+    handles: Vec<JoinHandle<Result<()>>>,
+}
+
+impl UnpackWorkers {
+    fn new(num_workers: usize) -> Self {
+        let (sender, receiver) = bounded::<UnpackedFile>(num_workers * 2);
+        let handles = (0..num_workers)
+            .map(|i| {
+                let receiver = receiver.clone();
+                Builder::new()
+                    .name(format!("solana-unpack-{}", i))
+                    .spawn(move || {
+                        for file in receiver.iter() {
+                            if let Some(parent) = file.path.parent() {
+                                fs::create_dir_all(parent)?;
+                            }
+                            fs::write(&file.path, &file.data)?;
+                        }
+                        Ok(())
+                    })
+                    .unwrap()
+            })
+            .collect();
+        Self {
+            sender: Some(sender),
+            handles,
+        }
+    }
+
+    fn send(&mut self, file: UnpackedFile) -> Result<()> {
+        let path_str = file.path.display().to_string();
+        match self.sender.as_ref().map(|sender| sender.send(file)) {
+            Some(Ok(())) => Ok(()),
+            // Every worker has exited, so one must have failed; report its error
+            _ => self
+                .join()
+                .and_then(|_| check_unpack_result(false, path_str)),
+        }
+    }
+
+    fn join(&mut self) -> Result<()> {
+        self.sender.take();
+        let mut result = Ok(());
+        for handle in self.handles.drain(..) {
+            let worker_result = handle
+                .join()
+                .unwrap_or_else(|_| Err(UnpackError::Archive("unpack worker panicked".into())));
+            if result.is_ok() {
+                result = worker_result;
+            }
+        }
+        result
+    }
+}
+
 fn unpack_archive<A: Read, P: AsRef<Path>, C>(
     archive: &mut Archive<A>,
     unpack_dir: P,
     limit_size: u64,
     limit_count: u64,
     entry_checker: C,
+    options: &UnpackOptions,
+) -> Result<()>
+where
+    C: Fn(&[&str], tar::EntryType) -> bool,
+{
+    let mut workers = if options.parallel_workers > 1 {
+        Some(UnpackWorkers::new(options.parallel_workers))
+    } else {
+        None
+    };
+    let result = unpack_archive_entries(
+        archive,
+        unpack_dir.as_ref(),
+        limit_size,
+        limit_count,
+        entry_checker,
+        options,
+        workers.as_mut(),
+    );
+    // Always wait for the workers, so no file is still being written once this returns
+    let workers_result = workers.map_or(Ok(()), |mut workers| workers.join());
+    result.and(workers_result)
+}
+
+fn unpack_archive_entries<A: Read, C>(
+    archive: &mut Archive<A>,
+    unpack_dir: &Path,
+    limit_size: u64,
+    limit_count: u64,
+    entry_checker: C,
+    options: &UnpackOptions,
+    mut workers: Option<&mut UnpackWorkers>,
 ) -> Result<()>
 where
     C: Fn(&[&str], tar::EntryType) -> bool,
@@ -77,7 +279,23 @@ where
     let mut total_size: u64 = 0;
     let mut total_count: u64 = 0;
 
-    let mut total_entries = 0;
+    let start = Instant::now();
+    let report_progress = |unpacked_bytes, unpacked_entries| {
+        let elapsed = start.elapsed();
+        let progress = UnpackProgress {
+            unpacked_bytes,
+            unpacked_entries,
+            elapsed,
+            eta: options
+                .read_tracker
+                .as_ref()
+                .and_then(|tracker| tracker.eta(elapsed)),
+        };
+        info!("{}", progress);
+        if let Some(progress_callback) = options.progress_callback {
+            progress_callback(&progress);
+        }
+    };
     let mut last_log_update = Instant::now();
     for entry in archive.entries()? {
         let mut entry = entry?;
@@ -101,27 +319,42 @@ where
         }
 
         let parts: Vec<_> = parts.map(|p| p.unwrap()).collect();
-        if !entry_checker(parts.as_slice(), entry.header().entry_type()) {
+        let entry_type = entry.header().entry_type();
+        if !entry_checker(parts.as_slice(), entry_type) {
             return Err(UnpackError::Archive(format!(
                 "extra entry found: {:?} {:?}",
-                path_str,
-                entry.header().entry_type(),
+                path_str, entry_type,
             )));
         }
-        total_size = checked_total_size_sum(total_size, entry.header().size()?, limit_size)?;
+        let entry_size = entry.header().size()?;
+        total_size = checked_total_size_sum(total_size, entry_size, limit_size)?;
         total_count = checked_total_count_increment(total_count, limit_count)?;
 
-        // unpack_in does its own sanitization
-        // ref: https://docs.rs/tar/*/tar/struct.Entry.html#method.unpack_in
-        check_unpack_result(entry.unpack_in(&unpack_dir)?, path_str)?;
-        total_entries += 1;
-        let now = Instant::now();
-        if now.duration_since(last_log_update).as_secs() >= 10 {
-            info!("unpacked {} entries so far...", total_entries);
-            last_log_update = now;
+        match workers.as_mut() {
+            // Only regular files are handed off to the workers. Their path has been checked
+            // to consist solely of normal components above, and as no links are ever
+            // unpacked, joining it onto `unpack_dir` cannot escape it
+            Some(workers)
+                if entry_type != Directory
+                    && unpacked_entry_size(&entry)? <= MAX_PARALLEL_UNPACK_ENTRY_SIZE =>
+            {
+                let path = parts
+                    .iter()
+                    .fold(unpack_dir.to_path_buf(), |path, part| path.join(part));
+                let mut data = vec![];
+                entry.read_to_end(&mut data)?;
+                workers.send(UnpackedFile { path, data })?;
+            }
+            // unpack_in does its own sanitization
+            // ref: https://docs.rs/tar/*/tar/struct.Entry.html#method.unpack_in
+            _ => check_unpack_result(entry.unpack_in(unpack_dir)?, path_str)?,
+        }
+        if last_log_update.elapsed() >= PROGRESS_INTERVAL {
+            report_progress(total_size, total_count);
+            last_log_update = Instant::now();
         }
     }
-    info!("unpacked {} entries total", total_entries);
+    report_progress(total_size, total_count);
 
     Ok(())
 }
@@ -129,6 +362,14 @@ where
 pub fn unpack_snapshot<A: Read, P: AsRef<Path>>(
     archive: &mut Archive<A>,
     unpack_dir: P,
+) -> Result<()> {
+    unpack_snapshot_with_options(archive, unpack_dir, &UnpackOptions::default())
+}
+
+pub fn unpack_snapshot_with_options<A: Read, P: AsRef<Path>>(
+    archive: &mut Archive<A>,
+    unpack_dir: P,
+    options: &UnpackOptions,
 ) -> Result<()> {
     unpack_archive(
         archive,
@@ -136,6 +377,7 @@ pub fn unpack_snapshot<A: Read, P: AsRef<Path>>(
         MAX_SNAPSHOT_ARCHIVE_UNPACKED_SIZE,
         MAX_SNAPSHOT_ARCHIVE_UNPACKED_COUNT,
         is_valid_snapshot_archive_entry,
+        options,
     )
 }
 
@@ -221,6 +463,7 @@ fn unpack_genesis<A: Read, P: AsRef<Path>>(
         max_genesis_archive_unpacked_size,
         MAX_GENESIS_ARCHIVE_UNPACKED_COUNT,
         is_valid_genesis_archive_entry,
+        &UnpackOptions::default(),
     )
 }
 
@@ -373,6 +616,47 @@ mod tests {
         assert_matches!(result, Ok(()));
     }
 
+    #[test]
+    fn test_archive_unpack_snapshot_parallel_with_progress() {
+        let paths = ["version", "snapshots/2/2", "accounts/2.0", "accounts/2.1"];
+        let mut archive = Builder::new(Vec::new());
+        for (i, path) in paths.iter().enumerate() {
+            let data = vec![i as u8; 4];
+            let mut header = Header::new_gnu();
+            header.set_path(path).unwrap();
+            header.set_size(data.len() as u64);
+            header.set_cksum();
+            archive.append(&header, &data[..]).unwrap();
+        }
+        let data = archive.into_inner().unwrap();
+        let read_tracker = ArchiveReadTracker::new(data.len() as u64);
+        let mut archive = Archive::new(read_tracker.reader(&data[..]));
+        let temp_dir = tempfile::TempDir::new().unwrap();
+
+        let last_progress = std::cell::Cell::new(None);
+        let progress_callback = |progress: &UnpackProgress| last_progress.set(Some(*progress));
+        let options = UnpackOptions {
+            parallel_workers: 2,
+            progress_callback: Some(&progress_callback),
+            read_tracker: Some(read_tracker),
+        };
+        assert_matches!(
+            unpack_snapshot_with_options(&mut archive, temp_dir.path(), &options),
+            Ok(())
+        );
+
+        for (i, path) in paths.iter().enumerate() {
+            assert_eq!(
+                fs::read(temp_dir.path().join(path)).unwrap(),
+                vec![i as u8; 4]
+            );
+        }
+        let last_progress = last_progress.get().unwrap();
+        assert_eq!(last_progress.unpacked_entries, paths.len() as u64);
+        assert_eq!(last_progress.unpacked_bytes, 4 * paths.len() as u64);
+        assert!(last_progress.eta.is_some());
+    }
+
     #[test]
     fn test_archive_unpack_snapshot_invalid_path() {
         let mut header = Header::new_gnu();
@@ -0,0 +1,446 @@
+//! The `incremental_accounts_hash` module keeps the account hashes of the last accounts hash
+//! calculation, split into partitions of the pubkey range, so that the next calculation only
+//! rescans the partitions in which accounts were stored since.
+//!
+//! The accounts hash is the merkle root of the hashes of all the accounts sorted by pubkey, so
+//! the partitions are contiguous ranges of pubkeys and concatenating them gives the hashes in
+//! the same order as a full scan, and the same accounts hash. The nodes of the merkle tree
+//! covering only the partitions before the first rescanned one are kept as well.
+
+use rayon::prelude::*;
+use solana_sdk::{
+    clock::Slot,
+    hash::{Hash, Hasher},
+    pubkey::Pubkey,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    ops::Bound,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Pubkey, hash and balance for capitalization of an account
+pub type AccountHash = (Pubkey, Hash, u64);
+
+// Partitions are picked from the first two bytes of the pubkeys
+const MAX_PARTITIONS: usize = 1 << 16;
+
+// Same as the fanout of `AccountsDB::compute_merkle_root`
+const MERKLE_FANOUT: usize = 16;
+
+#[derive(Debug, Default)]
+struct CachedHashes {
+    slot: Slot,
+    generation: u64,
+    // Account hashes of each partition, sorted by pubkey
+    partitions: Vec<Vec<AccountHash>>,
+    // Nodes of the merkle tree above the account hashes, up to the level hashed into the root
+    merkle_levels: Vec<Vec<Hash>>,
+    accounts_hash: Hash,
+    capitalization: u64,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    // Latest slot calculated, the partitions touched in slots up to it are forgotten
+    calculated_slot: Option<Slot>,
+    cached: Option<CachedHashes>,
+}
+
+#[derive(Debug)]
+pub struct IncrementalAccountsHash {
+    num_partitions: usize,
+    // Stores are only tracked once there is a calculation to update
+    tracking: AtomicBool,
+    // Partitions in which accounts were stored, by slot, since the last calculation
+    touched_partitions: Mutex<HashMap<Slot, HashSet<usize>>>,
+    // Bumped when accounts are removed without being stored, which voids the cached hashes
+    generation: AtomicU64,
+    // Held for the whole calculation, so that calculations do not race each other
+    state: Mutex<State>,
+}
+
+impl IncrementalAccountsHash {
+    pub fn new(num_partitions: usize) -> Self {
+        Self {
+            num_partitions: num_partitions.max(1).min(MAX_PARTITIONS),
+            tracking: AtomicBool::new(false),
+            touched_partitions: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn num_partitions(&self) -> usize {
+        self.num_partitions
+    }
+
+    pub fn partition_of(&self, pubkey: &Pubkey) -> usize {
+        let bytes = pubkey.as_ref();
+        let prefix = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+        (prefix * self.num_partitions) / MAX_PARTITIONS
+    }
+
+    /// The range of the pubkeys of `partition`
+    pub fn partition_bounds(&self, partition: usize) -> (Bound<Pubkey>, Bound<Pubkey>) {
+        // First prefix of the partition, rounding up the inverse of `partition_of`
+        let first_prefix = |partition: usize| {
+            (partition * MAX_PARTITIONS + self.num_partitions - 1) / self.num_partitions
+        };
+        let pubkey_of = |prefix: usize| {
+            let mut bytes = [0; 32];
+            bytes[..2].copy_from_slice(&(prefix as u16).to_be_bytes());
+            Pubkey::new(&bytes)
+        };
+        let end = if partition + 1 < self.num_partitions {
+            Bound::Excluded(pubkey_of(first_prefix(partition + 1)))
+        } else {
+            Bound::Unbounded
+        };
+        (Bound::Included(pubkey_of(first_prefix(partition))), end)
+    }
+
+    /// Records that the accounts of `pubkeys` were stored in `slot`. Must be called after the
+    /// accounts index is updated, so that a calculation seeing the record also sees the accounts.
+    pub fn touch<'a>(&self, slot: Slot, pubkeys: impl Iterator<Item = &'a Pubkey>) {
+        if !self.tracking.load(Ordering::Acquire) {
+            return;
+        }
+        let partitions: HashSet<_> = pubkeys.map(|pubkey| self.partition_of(pubkey)).collect();
+        self.touched_partitions
+            .lock()
+            .unwrap()
+            .entry(slot)
+            .or_default()
+            .extend(partitions);
+    }
+
+    /// Voids the cached hashes, for when accounts are removed from the index without being stored
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Returns the accounts hash and capitalization of `slot`. `rehash` returns the account
+    /// hashes of the given partitions, in any order, and is only asked for the partitions in
+    /// which accounts were stored since the last calculation, or for all of them if the cached
+    /// hashes cannot be reused: `is_ancestor` tells whether a slot is an ancestor of `slot`.
+    pub fn calculate<R, A>(&self, slot: Slot, is_ancestor: A, rehash: R) -> (Hash, u64)
+    where
+        R: FnOnce(&HashSet<usize>) -> Vec<AccountHash>,
+        A: Fn(Slot) -> bool,
+    {
+        let mut state = self.state.lock().unwrap();
+        let all_partitions: HashSet<_> = (0..self.num_partitions).collect();
+        if state
+            .calculated_slot
+            .map(|calculated_slot| slot < calculated_slot)
+            .unwrap_or(false)
+        {
+            // Stores in the slots between this one and the calculated one are no longer
+            // known, so the result cannot be cached
+            let mut hashes = rehash(&all_partitions);
+            hashes.par_sort_by(|a, b| a.0.cmp(&b.0));
+            let capitalization = capitalization(&hashes);
+            let leaves = hashes.into_iter().map(|(_, hash, _)| hash).collect();
+            return (merkle_root(&mut vec![], leaves, 0), capitalization);
+        }
+
+        self.tracking.store(true, Ordering::Release);
+        let generation = self.generation.load(Ordering::Acquire);
+        let touched_partitions: HashSet<_> = {
+            let mut touched_partitions = self.touched_partitions.lock().unwrap();
+            let touched_slots: Vec<_> = touched_partitions
+                .keys()
+                .filter(|touched_slot| **touched_slot <= slot)
+                .cloned()
+                .collect();
+            touched_slots
+                .iter()
+                .filter_map(|touched_slot| touched_partitions.remove(touched_slot))
+                .flatten()
+                .collect()
+        };
+        state.calculated_slot = Some(slot);
+
+        let reusable = state.cached.take().filter(|cached| {
+            cached.generation == generation
+                && (cached.slot == slot || is_ancestor(cached.slot))
+                && cached.partitions.len() == self.num_partitions
+        });
+        let (mut cached, dirty_partitions) = match reusable {
+            Some(cached) if touched_partitions.is_empty() => {
+                let result = (cached.accounts_hash, cached.capitalization);
+                state.cached = Some(CachedHashes { slot, ..cached });
+                return result;
+            }
+            Some(cached) => (cached, touched_partitions),
+            None => (
+                CachedHashes {
+                    partitions: vec![vec![]; self.num_partitions],
+                    ..CachedHashes::default()
+                },
+                all_partitions,
+            ),
+        };
+
+        for partition in &dirty_partitions {
+            cached.partitions[*partition].clear();
+        }
+        for account_hash in rehash(&dirty_partitions) {
+            cached.partitions[self.partition_of(&account_hash.0)].push(account_hash);
+        }
+        cached
+            .partitions
+            .par_iter_mut()
+            .enumerate()
+            .filter(|(partition, _)| dirty_partitions.contains(partition))
+            .for_each(|(_, account_hashes)| account_hashes.par_sort_by(|a, b| a.0.cmp(&b.0)));
+
+        // The account hashes before the first rescanned partition are unchanged
+        let first_dirty_partition = dirty_partitions.iter().min().copied().unwrap_or_default();
+        let num_unchanged = cached.partitions[..first_dirty_partition]
+            .iter()
+            .map(Vec::len)
+            .sum();
+        let leaves = cached
+            .partitions
+            .iter()
+            .flatten()
+            .map(|(_, hash, _)| *hash)
+            .collect();
+        cached.accounts_hash = merkle_root(&mut cached.merkle_levels, leaves, num_unchanged);
+        cached.capitalization = cached
+            .partitions
+            .iter()
+            .map(|account_hashes| capitalization(account_hashes) as u128)
+            .sum::<u128>()
+            .try_into()
+            .expect("overflow is detected while summing capitalization");
+        cached.slot = slot;
+        cached.generation = generation;
+        let result = (cached.accounts_hash, cached.capitalization);
+        state.cached = Some(cached);
+        result
+    }
+}
+
+fn capitalization(account_hashes: &[AccountHash]) -> u64 {
+    account_hashes
+        .iter()
+        .map(|(_, _, lamports)| *lamports as u128)
+        .sum::<u128>()
+        .try_into()
+        .expect("overflow is detected while summing capitalization")
+}
+
+fn hash_chunk(hashes: &[Hash]) -> Hash {
+    let mut hasher = Hasher::default();
+    for hash in hashes {
+        hasher.hash(hash.as_ref());
+    }
+    hasher.result()
+}
+
+// Computes the same root as `AccountsDB::compute_merkle_root`, replacing `levels` by the nodes
+// of the tree of `leaves` above them. The nodes of `levels` covering only the first
+// `num_unchanged` leaves are reused, the others are rehashed.
+fn merkle_root(levels: &mut Vec<Vec<Hash>>, leaves: Vec<Hash>, num_unchanged: usize) -> Hash {
+    let mut old_levels = std::mem::take(levels).into_iter();
+    let mut num_unchanged = num_unchanged;
+    let mut level = &leaves;
+    while level.len() > MERKLE_FANOUT {
+        num_unchanged /= MERKLE_FANOUT;
+        let mut next_level = old_levels.next().unwrap_or_default();
+        next_level.truncate(num_unchanged);
+        let num_reused = next_level.len();
+        next_level.par_extend(
+            level[num_reused * MERKLE_FANOUT..]
+                .par_chunks(MERKLE_FANOUT)
+                .map(hash_chunk),
+        );
+        levels.push(next_level);
+        level = levels.last().unwrap();
+    }
+    hash_chunk(level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts_db::AccountsDB;
+    use std::ops::RangeBounds;
+
+    #[test]
+    fn test_partition_of() {
+        let incremental_accounts_hash = IncrementalAccountsHash::new(16);
+        let mut pubkeys: Vec<_> = (0..1000).map(|_| Pubkey::new_unique()).collect();
+        pubkeys.push(Pubkey::new(&[0; 32]));
+        pubkeys.push(Pubkey::new(&[0xff; 32]));
+        pubkeys.sort();
+        let partitions: Vec<_> = pubkeys
+            .iter()
+            .map(|pubkey| incremental_accounts_hash.partition_of(pubkey))
+            .collect();
+        // Partitions are contiguous ranges of pubkeys
+        assert!(partitions.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(partitions[0], 0);
+        assert_eq!(*partitions.last().unwrap(), 15);
+    }
+
+    #[test]
+    fn test_partition_bounds() {
+        for num_partitions in &[1, 3, 16, 1000] {
+            let incremental_accounts_hash = IncrementalAccountsHash::new(*num_partitions);
+            let mut pubkeys: Vec<_> = (0..1000).map(|_| solana_sdk::pubkey::new_rand()).collect();
+            pubkeys.push(Pubkey::new(&[0; 32]));
+            pubkeys.push(Pubkey::new(&[0xff; 32]));
+            for pubkey in pubkeys {
+                let partition = incremental_accounts_hash.partition_of(&pubkey);
+                assert!(incremental_accounts_hash
+                    .partition_bounds(partition)
+                    .contains(&pubkey));
+                if partition + 1 < *num_partitions {
+                    assert!(!incremental_accounts_hash
+                        .partition_bounds(partition + 1)
+                        .contains(&pubkey));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_merkle_root() {
+        let leaves: Vec<_> = (0..1000).map(|_| Hash::new_unique()).collect();
+        let expected = |leaves: &[Hash]| {
+            let hashes = leaves
+                .iter()
+                .map(|hash| (Pubkey::default(), *hash, 0))
+                .collect();
+            AccountsDB::compute_merkle_root(hashes, MERKLE_FANOUT)
+        };
+        for num_leaves in &[0, 1, MERKLE_FANOUT, MERKLE_FANOUT + 1, 1000] {
+            let leaves = &leaves[..*num_leaves];
+            assert_eq!(
+                merkle_root(&mut vec![], leaves.to_vec(), 0),
+                expected(leaves)
+            );
+        }
+
+        // Nodes of the unchanged leaves are reused
+        let mut levels = vec![];
+        merkle_root(&mut levels, leaves.clone(), 0);
+        let mut new_leaves = leaves[..600].to_vec();
+        new_leaves.extend((0..300).map(|_| Hash::new_unique()));
+        assert_eq!(
+            merkle_root(&mut levels, new_leaves.clone(), 600),
+            expected(&new_leaves)
+        );
+        assert_eq!(
+            merkle_root(&mut levels, leaves.clone(), 0),
+            expected(&leaves)
+        );
+    }
+
+    #[test]
+    fn test_calculate() {
+        let incremental_accounts_hash = IncrementalAccountsHash::new(4);
+        let account_hash = |pubkey: u8, lamports: u64| {
+            let mut bytes = [0; 32];
+            bytes[0] = pubkey;
+            (
+                Pubkey::new(&bytes),
+                Hash::new(&[lamports as u8; 32]),
+                lamports,
+            )
+        };
+        let expected = |mut hashes: Vec<AccountHash>| {
+            hashes.sort_by(|a, b| a.0.cmp(&b.0));
+            let capitalization = hashes.iter().map(|(_, _, lamports)| lamports).sum();
+            (
+                AccountsDB::compute_merkle_root(hashes, MERKLE_FANOUT),
+                capitalization,
+            )
+        };
+        let mut accounts = vec![account_hash(0xc0, 1), account_hash(0x00, 1)];
+
+        // The first calculation rehashes every partition
+        let result = incremental_accounts_hash.calculate(
+            1,
+            |_| false,
+            |partitions| {
+                assert_eq!(partitions.len(), 4);
+                accounts.clone()
+            },
+        );
+        assert_eq!(result, expected(accounts.clone()));
+
+        // Only the partitions stored to are rehashed
+        accounts.push(account_hash(0x40, 2));
+        incremental_accounts_hash.touch(2, [accounts[2].0].iter());
+        let result = incremental_accounts_hash.calculate(
+            2,
+            |slot| slot == 1,
+            |partitions| {
+                assert_eq!(*partitions, vec![1].into_iter().collect::<HashSet<_>>());
+                accounts
+                    .iter()
+                    .filter(|(pubkey, _, _)| {
+                        partitions.contains(&(pubkey.as_ref()[0] as usize >> 6))
+                    })
+                    .cloned()
+                    .collect()
+            },
+        );
+        assert_eq!(result, expected(accounts.clone()));
+
+        // Nothing is rehashed if no account was stored
+        let result = incremental_accounts_hash.calculate(
+            3,
+            |slot| slot == 2,
+            |_| panic!("nothing to rehash"),
+        );
+        assert_eq!(result, expected(accounts.clone()));
+
+        // Cached hashes are not reused by a slot on another fork, or once invalidated
+        let result = incremental_accounts_hash.calculate(
+            4,
+            |_| false,
+            |partitions| {
+                assert_eq!(partitions.len(), 4);
+                vec![account_hash(0x00, 1)]
+            },
+        );
+        assert_eq!(result, expected(vec![account_hash(0x00, 1)]));
+        incremental_accounts_hash.invalidate();
+        incremental_accounts_hash.calculate(
+            5,
+            |_| true,
+            |partitions| {
+                assert_eq!(partitions.len(), 4);
+                vec![]
+            },
+        );
+
+        // Slots older than the tracked ones are rehashed but not cached
+        incremental_accounts_hash.calculate(
+            2,
+            |_| true,
+            |partitions| {
+                assert_eq!(partitions.len(), 4);
+                vec![]
+            },
+        );
+        incremental_accounts_hash.calculate(
+            6,
+            |slot| slot == 5,
+            |partitions| {
+                assert!(partitions.is_empty());
+                vec![]
+            },
+        );
+    }
+}
@@ -12,15 +12,19 @@ mod blockhash_queue;
 pub mod bloom;
 pub mod builtins;
 pub mod commitment;
+pub mod epoch_rewards;
 pub mod epoch_stakes;
 pub mod genesis_utils;
 pub mod hardened_unpack;
+pub mod incremental_accounts_hash;
 pub mod instruction_recorder;
 pub mod loader_utils;
+pub mod lock_contention;
 pub mod log_collector;
 pub mod message_processor;
 mod native_loader;
 pub mod rent_collector;
+pub mod secondary_index;
 pub mod serde_snapshot;
 pub mod snapshot_package;
 pub mod snapshot_utils;
@@ -0,0 +1,202 @@
+//! The `lock_contention` module is an opt-in profiler of the account locks. It counts, for each
+//! account, how many transactions could not be batched with others because of its lock, and how
+//! often its read-only lock was shared, so that operators can identify the hot accounts
+//! serializing replay and banking.
+
+use lazy_static::lazy_static;
+use solana_sdk::{pubkey::Pubkey, timing::timestamp};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Interval between the submissions of the most contended accounts to the metrics database
+const METRICS_INTERVAL_MS: u64 = 10_000;
+/// Number of accounts submitted to the metrics database every interval
+const METRICS_NUM_ACCOUNTS: usize = 5;
+
+/// Why a transaction could not lock an account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockConflict {
+    /// Another transaction holds the account writable
+    WriteLocked,
+    /// The transaction writes the account, which other transactions hold read-only
+    ReadLocked,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccountLockStats {
+    /// Transactions that could not lock the account as another one held it writable
+    pub write_conflicts: u64,
+    /// Transactions that could not write the account as others held it read-only
+    pub read_conflicts: u64,
+    /// Read-only locks taken while other transactions already held the account read-only
+    pub shared_readonly_locks: u64,
+}
+
+impl AccountLockStats {
+    pub fn conflicts(&self) -> u64 {
+        self.write_conflicts + self.read_conflicts
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    /// Since the profiler was enabled
+    total: HashMap<Pubkey, AccountLockStats>,
+    /// Since the last submission to the metrics database
+    interval: HashMap<Pubkey, AccountLockStats>,
+}
+
+#[derive(Default)]
+pub struct LockContentionProfiler {
+    enabled: AtomicBool,
+    stats: Mutex<Stats>,
+    last_submit: AtomicU64,
+}
+
+lazy_static! {
+    static ref PROFILER: LockContentionProfiler = LockContentionProfiler::default();
+}
+
+/// The profiler of the account locks of this process
+pub fn profiler() -> &'static LockContentionProfiler {
+    &PROFILER
+}
+
+/// Enables the profiler of the account locks of this process
+pub fn enable_lock_contention_profiler() {
+    PROFILER.enable();
+}
+
+impl LockContentionProfiler {
+    pub fn enable(&self) {
+        self.last_submit.store(timestamp(), Ordering::Relaxed);
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn record_conflict(&self, key: &Pubkey, conflict: LockConflict) {
+        self.record(key, |stats| match conflict {
+            LockConflict::WriteLocked => stats.write_conflicts += 1,
+            LockConflict::ReadLocked => stats.read_conflicts += 1,
+        });
+    }
+
+    pub fn record_shared_readonly_lock(&self, key: &Pubkey) {
+        self.record(key, |stats| stats.shared_readonly_locks += 1);
+    }
+
+    fn record(&self, key: &Pubkey, update: impl Fn(&mut AccountLockStats)) {
+        if !self.is_enabled() {
+            return;
+        }
+        let mut stats = self.stats.lock().unwrap();
+        update(stats.total.entry(*key).or_default());
+        update(stats.interval.entry(*key).or_default());
+        drop(stats);
+        self.maybe_submit();
+    }
+
+    /// The `limit` accounts with the most conflicts, then the most shared read-only locks
+    pub fn most_contended(&self, limit: usize) -> Vec<(Pubkey, AccountLockStats)> {
+        Self::top(&self.stats.lock().unwrap().total, limit)
+    }
+
+    fn top(
+        stats: &HashMap<Pubkey, AccountLockStats>,
+        limit: usize,
+    ) -> Vec<(Pubkey, AccountLockStats)> {
+        let mut stats: Vec<_> = stats.iter().map(|(key, stats)| (*key, *stats)).collect();
+        stats.sort_by(|(_, a), (_, b)| {
+            (b.conflicts(), b.shared_readonly_locks).cmp(&(a.conflicts(), a.shared_readonly_locks))
+        });
+        stats.truncate(limit);
+        stats
+    }
+
+    fn maybe_submit(&self) {
+        let now = timestamp();
+        let last_submit = self.last_submit.load(Ordering::Relaxed);
+        if now.saturating_sub(last_submit) < METRICS_INTERVAL_MS
+            || self
+                .last_submit
+                .compare_exchange(last_submit, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_err()
+        {
+            return;
+        }
+        let interval = std::mem::take(&mut self.stats.lock().unwrap().interval);
+        for (key, stats) in Self::top(&interval, METRICS_NUM_ACCOUNTS) {
+            datapoint_info!(
+                "account-lock-contention",
+                ("pubkey", key.to_string(), String),
+                ("write_conflicts", stats.write_conflicts, i64),
+                ("read_conflicts", stats.read_conflicts, i64),
+                ("shared_readonly_locks", stats.shared_readonly_locks, i64),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_contention_profiler() {
+        let profiler = LockContentionProfiler::default();
+        let hot = solana_sdk::pubkey::new_rand();
+        let warm = solana_sdk::pubkey::new_rand();
+        let shared = solana_sdk::pubkey::new_rand();
+
+        // Nothing is recorded until the profiler is enabled
+        profiler.record_conflict(&hot, LockConflict::WriteLocked);
+        assert!(profiler.most_contended(10).is_empty());
+
+        profiler.enable();
+        profiler.record_conflict(&hot, LockConflict::WriteLocked);
+        profiler.record_conflict(&hot, LockConflict::ReadLocked);
+        profiler.record_conflict(&warm, LockConflict::WriteLocked);
+        profiler.record_shared_readonly_lock(&warm);
+        profiler.record_shared_readonly_lock(&shared);
+        profiler.record_shared_readonly_lock(&shared);
+
+        assert_eq!(
+            profiler.most_contended(10),
+            vec![
+                (
+                    hot,
+                    AccountLockStats {
+                        write_conflicts: 1,
+                        read_conflicts: 1,
+                        shared_readonly_locks: 0,
+                    }
+                ),
+                (
+                    warm,
+                    AccountLockStats {
+                        write_conflicts: 1,
+                        read_conflicts: 0,
+                        shared_readonly_locks: 1,
+                    }
+                ),
+                (
+                    shared,
+                    AccountLockStats {
+                        write_conflicts: 0,
+                        read_conflicts: 0,
+                        shared_readonly_locks: 2,
+                    }
+                ),
+            ]
+        );
+        assert_eq!(profiler.most_contended(1).len(), 1);
+    }
+}
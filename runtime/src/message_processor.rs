@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use solana_sdk::{
     account::Account,
     clock::Epoch,
-    feature_set::{instructions_sysvar_enabled, FeatureSet},
+    feature_set::{instructions_sysvar_enabled, max_account_data_size_enforcement, FeatureSet},
     instruction::{CompiledInstruction, Instruction, InstructionError},
     keyed_account::{create_keyed_readonly_accounts, KeyedAccount},
     message::Message,
@@ -72,10 +72,13 @@ impl PreAccount {
         }
     }
 
+    /// Verify the changes made to the account by `program_id`. Writable accounts may not hold
+    /// more than `max_data_len` bytes of data, if any
     pub fn verify(
         &self,
         program_id: &Pubkey,
         rent: &Rent,
+        max_data_len: Option<u64>,
         post: &Account,
     ) -> Result<(), InstructionError> {
         // Only the owner of the account may change owner and
@@ -115,6 +118,12 @@ impl PreAccount {
             return Err(InstructionError::AccountDataSizeChanged);
         }
 
+        if let Some(max_data_len) = max_data_len {
+            if self.is_writable && post.data.len() as u64 > max_data_len {
+                return Err(InstructionError::InvalidRealloc);
+            }
+        }
+
         // Only the owner may change account data
         //   and if the account is writable
         //   and if the account is not executable
@@ -270,6 +279,7 @@ impl<'a> InvokeContext for ThisInvokeContext<'a> {
                 accounts,
                 key,
                 &self.rent,
+                MessageProcessor::max_account_data_len(&self.feature_set, &self.bpf_compute_budget),
             ),
             None => Err(InstructionError::GenericError), // Should never happen
         }
@@ -569,6 +579,7 @@ impl MessageProcessor {
         executable_accounts: &[(Pubkey, RefCell<Account>)],
         accounts: &[Rc<RefCell<Account>>],
         rent: &Rent,
+        max_account_data_len: Option<u64>,
     ) -> Result<(), InstructionError> {
         // Verify all executable accounts have zero outstanding refs
         Self::verify_account_references(executable_accounts)?;
@@ -582,7 +593,12 @@ impl MessageProcessor {
                 let account = accounts[account_index]
                     .try_borrow_mut()
                     .map_err(|_| InstructionError::AccountBorrowOutstanding)?;
-                pre_accounts[unique_index].verify(&program_id, rent, &account)?;
+                pre_accounts[unique_index].verify(
+                    &program_id,
+                    rent,
+                    max_account_data_len,
+                    &account,
+                )?;
                 pre_sum += u128::from(pre_accounts[unique_index].lamports());
                 post_sum += u128::from(account.lamports);
                 Ok(())
@@ -597,6 +613,18 @@ impl MessageProcessor {
         Ok(())
     }
 
+    /// The maximum data length of writable accounts, if enforced
+    pub fn max_account_data_len(
+        feature_set: &FeatureSet,
+        bpf_compute_budget: &BpfComputeBudget,
+    ) -> Option<u64> {
+        if feature_set.is_active(&max_account_data_size_enforcement::id()) {
+            Some(bpf_compute_budget.max_account_data_len)
+        } else {
+            None
+        }
+    }
+
     /// Verify the results of a cross-program instruction
     fn verify_and_update(
         message: &Message,
@@ -605,6 +633,7 @@ impl MessageProcessor {
         accounts: &[Rc<RefCell<Account>>],
         program_id: &Pubkey,
         rent: &Rent,
+        max_account_data_len: Option<u64>,
     ) -> Result<(), InstructionError> {
         // Verify the per-account instruction results
         let (mut pre_sum, mut post_sum) = (0_u128, 0_u128);
@@ -619,7 +648,7 @@ impl MessageProcessor {
                         .try_borrow_mut()
                         .map_err(|_| InstructionError::AccountBorrowOutstanding)?;
 
-                    pre_account.verify(&program_id, &rent, &account)?;
+                    pre_account.verify(&program_id, &rent, max_account_data_len, &account)?;
                     pre_sum += u128::from(pre_account.lamports());
                     post_sum += u128::from(account.lamports);
 
@@ -673,6 +702,7 @@ impl MessageProcessor {
             }
         }
 
+        let max_account_data_len = Self::max_account_data_len(&feature_set, &bpf_compute_budget);
         let pre_accounts = Self::create_pre_accounts(message, instruction, accounts);
         let mut invoke_context = ThisInvokeContext::new(
             instruction.program_id(&message.account_keys),
@@ -695,8 +725,8 @@ impl MessageProcessor {
             executable_accounts,
             accounts,
             &rent_collector.rent,
-        )?;
-        Ok(())
+            max_account_data_len,
+        )
     }
 
     /// Process a message.
@@ -874,9 +904,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_invoke_context_max_account_data_len() {
+        let key = solana_sdk::pubkey::new_rand();
+        let account = Account::new(1, 10, &system_program::id());
+        let message = Message::new(
+            &[Instruction::new(
+                system_program::id(),
+                &0u8,
+                vec![AccountMeta::new(key, false)],
+            )],
+            None,
+        );
+        let accounts = vec![
+            Rc::new(RefCell::new(account.clone())),
+            Rc::new(RefCell::new(Account::default())),
+        ];
+        accounts[0].borrow_mut().data.resize(11, 0);
+        let bpf_compute_budget = BpfComputeBudget {
+            max_account_data_len: 10,
+            ..BpfComputeBudget::default()
+        };
+        let new_invoke_context = |feature_set| {
+            ThisInvokeContext::new(
+                &system_program::id(),
+                Rent::default(),
+                vec![PreAccount::new(&key, &account, false, true)],
+                &[],
+                None,
+                bpf_compute_budget,
+                Rc::new(RefCell::new(Executors::default())),
+                None,
+                Arc::new(feature_set),
+            )
+        };
+
+        // Cross-program invocations may not outgrow the limit once it is enforced
+        assert_eq!(
+            new_invoke_context(FeatureSet::all_enabled()).verify_and_update(
+                &message,
+                &message.instructions[0],
+                &accounts
+            ),
+            Err(InstructionError::InvalidRealloc)
+        );
+        assert_eq!(
+            new_invoke_context(FeatureSet::default()).verify_and_update(
+                &message,
+                &message.instructions[0],
+                &accounts
+            ),
+            Ok(())
+        );
+    }
+
     struct Change {
         program_id: Pubkey,
         rent: Rent,
+        max_data_len: Option<u64>,
         pre: PreAccount,
         post: Account,
     }
@@ -885,6 +970,7 @@ mod tests {
             Self {
                 program_id: *program_id,
                 rent: Rent::default(),
+                max_data_len: None,
                 pre: PreAccount::new(
                     &solana_sdk::pubkey::new_rand(),
                     &Account {
@@ -926,13 +1012,18 @@ mod tests {
             self.post.data = post;
             self
         }
+        pub fn max_data_len(mut self, max_data_len: u64) -> Self {
+            self.max_data_len = Some(max_data_len);
+            self
+        }
         pub fn rent_epoch(mut self, pre: u64, post: u64) -> Self {
             self.pre.rent_epoch = pre;
             self.post.rent_epoch = post;
             self
         }
         pub fn verify(&self) -> Result<(), InstructionError> {
-            self.pre.verify(&self.program_id, &self.rent, &self.post)
+            self.pre
+                .verify(&self.program_id, &self.rent, self.max_data_len, &self.post)
         }
     }
 
@@ -1245,6 +1336,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_verify_account_changes_max_data_len() {
+        assert_eq!(
+            Change::new(&system_program::id(), &system_program::id())
+                .data(vec![0], vec![0, 0])
+                .max_data_len(2)
+                .verify(),
+            Ok(()),
+        );
+        assert_eq!(
+            Change::new(&system_program::id(), &system_program::id())
+                .data(vec![0], vec![0, 0, 0])
+                .max_data_len(2)
+                .verify(),
+            Err(InstructionError::InvalidRealloc),
+            "writable accounts should not outgrow the maximum data length"
+        );
+        assert_eq!(
+            Change::new(&system_program::id(), &system_program::id())
+                .read_only()
+                .data(vec![0, 0, 0], vec![0, 0, 0])
+                .max_data_len(2)
+                .verify(),
+            Ok(()),
+            "read-only accounts should remain usable"
+        );
+    }
+
     #[test]
     fn test_process_message_readonly_handling() {
         #[derive(Serialize, Deserialize)]
@@ -0,0 +1,136 @@
+//! The `secondary_index` module maps a secondary key of accounts, such as their owner program or
+//! the mint of an SPL token account, to the accounts stored with that key.
+//!
+//! An account stays listed under a key for as long as the accounts index has a version of it,
+//! stored with that key, in some slot. Lookups may therefore return accounts whose visible version
+//! no longer has the key, and callers re-check the key of the version they load.
+
+use dashmap::DashMap;
+use solana_sdk::{clock::Slot, pubkey::Pubkey};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Default)]
+pub struct SecondaryIndex {
+    // Secondary key -> account -> slots in which the account was stored with the key
+    index: DashMap<Pubkey, HashMap<Pubkey, HashSet<Slot>>>,
+    // Account -> secondary keys it is listed under
+    reverse_index: DashMap<Pubkey, HashSet<Pubkey>>,
+}
+
+impl SecondaryIndex {
+    pub fn insert(&self, key: &Pubkey, pubkey: &Pubkey, slot: Slot) {
+        let is_present = self
+            .index
+            .get(key)
+            .and_then(|accounts| accounts.get(pubkey).map(|slots| slots.contains(&slot)))
+            .unwrap_or(false);
+        if !is_present {
+            self.index
+                .entry(*key)
+                .or_default()
+                .entry(*pubkey)
+                .or_default()
+                .insert(slot);
+            self.reverse_index.entry(*pubkey).or_default().insert(*key);
+        }
+    }
+
+    /// Removes `pubkey` from the keys it was stored with in `slots`
+    pub fn remove_slots(&self, pubkey: &Pubkey, slots: &HashSet<Slot>) {
+        let keys = match self.reverse_index.get(pubkey) {
+            Some(keys) => keys.clone(),
+            None => return,
+        };
+        for key in keys {
+            let is_removed = match self.index.get_mut(&key) {
+                Some(mut accounts) => {
+                    let is_removed = accounts.get_mut(pubkey).map_or(true, |account_slots| {
+                        account_slots.retain(|slot| !slots.contains(slot));
+                        account_slots.is_empty()
+                    });
+                    if is_removed {
+                        accounts.remove(pubkey);
+                    }
+                    is_removed
+                }
+                None => true,
+            };
+            if is_removed {
+                self.index
+                    .remove_if(&key, |_, accounts| accounts.is_empty());
+                if let Some(mut keys) = self.reverse_index.get_mut(pubkey) {
+                    keys.remove(&key);
+                }
+            }
+        }
+        self.reverse_index
+            .remove_if(pubkey, |_, keys| keys.is_empty());
+    }
+
+    /// Removes `pubkey` from every key
+    pub fn remove(&self, pubkey: &Pubkey) {
+        if let Some((_, keys)) = self.reverse_index.remove(pubkey) {
+            for key in keys {
+                if let Some(mut accounts) = self.index.get_mut(&key) {
+                    accounts.remove(pubkey);
+                }
+                self.index
+                    .remove_if(&key, |_, accounts| accounts.is_empty());
+            }
+        }
+    }
+
+    /// Accounts listed under `key`
+    pub fn get(&self, key: &Pubkey) -> Vec<Pubkey> {
+        self.index
+            .get(key)
+            .map(|accounts| accounts.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secondary_index() {
+        let index = SecondaryIndex::default();
+        let key = solana_sdk::pubkey::new_rand();
+        let other_key = solana_sdk::pubkey::new_rand();
+        let pubkey = solana_sdk::pubkey::new_rand();
+        let other_pubkey = solana_sdk::pubkey::new_rand();
+
+        index.insert(&key, &pubkey, 0);
+        index.insert(&key, &pubkey, 1);
+        index.insert(&key, &other_pubkey, 1);
+        index.insert(&other_key, &pubkey, 2);
+        let mut accounts = index.get(&key);
+        accounts.sort();
+        let mut expected = vec![pubkey, other_pubkey];
+        expected.sort();
+        assert_eq!(accounts, expected);
+        assert_eq!(index.get(&other_key), vec![pubkey]);
+        assert!(index.get(&solana_sdk::pubkey::new_rand()).is_empty());
+
+        // The account stays listed under `key` until every slot it was stored with it is removed
+        index.remove_slots(&pubkey, &[0, 2].iter().copied().collect());
+        assert_eq!(index.get(&key).len(), 2);
+        assert!(index.get(&other_key).is_empty());
+        assert_eq!(index.len(), 1);
+        index.remove_slots(&pubkey, &[1].iter().copied().collect());
+        assert_eq!(index.get(&key), vec![other_pubkey]);
+
+        index.remove(&other_pubkey);
+        assert!(index.is_empty());
+        assert!(index.reverse_index.is_empty());
+    }
+}
@@ -2,8 +2,8 @@ use {
     crate::{
         accounts::Accounts,
         accounts_db::{AccountStorageEntry, AccountsDB, AppendVecId, BankHashInfo},
-        accounts_index::Ancestors,
-        append_vec::AppendVec,
+        accounts_index::{AccountIndexes, Ancestors},
+        append_vec::{AppendVec, AppendVecBackend},
         bank::{Bank, BankFieldsToDeserialize, BankRc, Builtins},
         blockhash_queue::BlockhashQueue,
         epoch_stakes::EpochStakes,
@@ -30,7 +30,7 @@ use {
     },
     std::{
         collections::{HashMap, HashSet},
-        io::{BufReader, BufWriter, Read, Write},
+        io::{BufRead, BufReader, BufWriter, Read, Write},
         path::{Path, PathBuf},
         result::Result,
         sync::{atomic::Ordering, Arc, RwLock},
@@ -128,6 +128,8 @@ pub(crate) fn bank_from_stream<R, P>(
     frozen_account_pubkeys: &[Pubkey],
     debug_keys: Option<Arc<HashSet<Pubkey>>>,
     additional_builtins: Option<&Builtins>,
+    account_indexes: AccountIndexes,
+    append_vec_backend: AppendVecBackend,
 ) -> std::result::Result<Bank, Error>
 where
     R: Read,
@@ -146,6 +148,8 @@ where
                 append_vecs_path,
                 debug_keys,
                 additional_builtins,
+                account_indexes,
+                append_vec_backend,
             )?;
             Ok(bank)
         }};
@@ -232,6 +236,8 @@ fn reconstruct_bank_from_fields<E, P>(
     append_vecs_path: P,
     debug_keys: Option<Arc<HashSet<Pubkey>>>,
     additional_builtins: Option<&Builtins>,
+    account_indexes: AccountIndexes,
+    append_vec_backend: AppendVecBackend,
 ) -> Result<Bank, Error>
 where
     E: Into<AccountStorageEntry>,
@@ -242,6 +248,8 @@ where
         account_paths,
         append_vecs_path,
         &genesis_config.cluster_type,
+        account_indexes,
+        append_vec_backend,
     )?;
     accounts_db.freeze_accounts(&bank_fields.ancestors, frozen_account_pubkeys);
 
@@ -262,12 +270,19 @@ fn reconstruct_accountsdb_from_fields<E, P>(
     account_paths: &[PathBuf],
     stream_append_vecs_path: P,
     cluster_type: &ClusterType,
+    account_indexes: AccountIndexes,
+    append_vec_backend: AppendVecBackend,
 ) -> Result<AccountsDB, Error>
 where
     E: Into<AccountStorageEntry>,
     P: AsRef<Path>,
 {
-    let mut accounts_db = AccountsDB::new(account_paths.to_vec(), cluster_type);
+    let mut accounts_db = AccountsDB::new_with_config(
+        account_paths.to_vec(),
+        cluster_type,
+        account_indexes,
+        append_vec_backend,
+    );
 
     let AccountsDbFields(storage, version, slot, bank_hash_info) = accounts_db_fields;
 
@@ -324,7 +339,7 @@ where
                 // Notify the AppendVec of the new file location
                 let local_path = local_dir.join(append_vec_relative_path);
                 let mut u_storage_entry = Arc::try_unwrap(storage_entry).unwrap();
-                u_storage_entry.set_file(local_path)?;
+                u_storage_entry.set_file(local_path, accounts_db.append_vec_backend())?;
                 new_slot_storage.insert(id, Arc::new(u_storage_entry));
             }
             Ok((slot, new_slot_storage))
@@ -105,6 +105,7 @@ impl Into<BankFieldsToDeserialize> for DeserializableVersionedBank {
             stakes: self.stakes,
             epoch_stakes: self.epoch_stakes,
             is_delta: self.is_delta,
+            epoch_rewards_distribution: None,
         }
     }
 }
@@ -204,14 +205,19 @@ impl<'a> TypeContext<'a> for Context {
     where
         Self: std::marker::Sized,
     {
+        let fields = serializable_bank.bank.get_fields_to_serialize();
+        // The pending epoch rewards distribution goes after the accounts db fields, so that
+        // snapshots taken before it was added still deserialize
+        let epoch_rewards_distribution = fields.epoch_rewards_distribution;
         (
-            SerializableVersionedBank::from(serializable_bank.bank.get_fields_to_serialize()),
+            SerializableVersionedBank::from(fields),
             SerializableAccountsDB::<'a, Self> {
                 accounts_db: &*serializable_bank.bank.rc.accounts.accounts_db,
                 slot: serializable_bank.bank.rc.slot,
                 account_storage_entries: serializable_bank.snapshot_storages,
                 phantom: std::marker::PhantomData::default(),
             },
+            epoch_rewards_distribution,
         )
             .serialize(serializer)
     }
@@ -269,8 +275,13 @@ impl<'a> TypeContext<'a> for Context {
     where
         R: Read,
     {
-        let bank_fields = deserialize_from::<_, DeserializableVersionedBank>(&mut stream)?.into();
+        let mut bank_fields: BankFieldsToDeserialize =
+            deserialize_from::<_, DeserializableVersionedBank>(&mut stream)?.into();
         let accounts_db_fields = Self::deserialize_accounts_db_fields(stream)?;
+        // Snapshots taken before the epoch rewards distribution was persisted end here
+        if !stream.fill_buf()?.is_empty() {
+            bank_fields.epoch_rewards_distribution = deserialize_from(&mut stream)?;
+        }
         Ok((bank_fields, accounts_db_fields))
     }
 
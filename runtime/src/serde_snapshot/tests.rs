@@ -70,6 +70,8 @@ where
         account_paths,
         stream_append_vecs_path,
         &ClusterType::Development,
+        AccountIndexes::default(),
+        AppendVecBackend::default(),
     )
 }
 
@@ -213,6 +215,8 @@ fn test_bank_serialize_style(serde_style: SerdeStyle) {
         &[],
         None,
         None,
+        AccountIndexes::default(),
+        AppendVecBackend::default(),
     )
     .unwrap();
     dbank.src = ref_sc;
@@ -222,6 +226,91 @@ fn test_bank_serialize_style(serde_style: SerdeStyle) {
     assert!(bank2 == dbank);
 }
 
+#[test]
+fn test_bank_serialize_epoch_rewards_distribution() {
+    use crate::bank::{
+        tests::{
+            new_epoch_rewards_accounts, new_epoch_rewards_bank, new_epoch_rewards_genesis_config,
+        },
+        RewardType,
+    };
+
+    let (genesis_accounts, vote, stakes) = new_epoch_rewards_accounts();
+    let genesis_config = new_epoch_rewards_genesis_config(&genesis_accounts, true);
+    let bank0 = new_epoch_rewards_bank(&genesis_config, &vote, &stakes);
+    let bank1 = Arc::new(Bank::new_from_parent(
+        &bank0,
+        &Pubkey::default(),
+        bank0.get_slots_in_epoch(bank0.epoch()) + 1,
+    ));
+    let bank2 = Arc::new(Bank::new_from_parent(
+        &bank1,
+        &Pubkey::default(),
+        bank1.slot() + 1,
+    ));
+    // snapshot the bank while some partitions are left to pay
+    assert!(bank2.is_distributing_epoch_rewards());
+    bank2.squash();
+
+    let snapshot_storages = bank2.get_snapshot_storages();
+    let mut buf = vec![];
+    crate::serde_snapshot::bank_to_stream(
+        SerdeStyle::NEWER,
+        &mut std::io::BufWriter::new(&mut buf),
+        &bank2,
+        &snapshot_storages,
+    )
+    .unwrap();
+    let mut reader = std::io::BufReader::new(&buf[..]);
+    let (_accounts_dir, dbank_paths) = get_temp_accounts_paths(4).unwrap();
+    let copied_accounts = TempDir::new().unwrap();
+    copy_append_vecs(&bank2.rc.accounts.accounts_db, copied_accounts.path()).unwrap();
+    let mut dbank = crate::serde_snapshot::bank_from_stream(
+        SerdeStyle::NEWER,
+        &mut reader,
+        copied_accounts.path(),
+        &dbank_paths,
+        &genesis_config,
+        &[],
+        None,
+        None,
+        AccountIndexes::default(),
+        AppendVecBackend::default(),
+    )
+    .unwrap();
+    dbank.src = bank2.src.clone();
+    assert_eq!(
+        dbank.epoch_rewards_distribution(),
+        bank2.epoch_rewards_distribution()
+    );
+    assert!(*bank2 == dbank);
+
+    // the restored bank's descendants pay the partitions left, just like the original's
+    let stake_rewards_paid = |mut bank: Arc<Bank>| -> Vec<Vec<(Pubkey, i64)>> {
+        let mut paid = vec![];
+        while bank.is_distributing_epoch_rewards() {
+            bank = Arc::new(Bank::new_from_parent(
+                &bank,
+                &Pubkey::default(),
+                bank.slot() + 1,
+            ));
+            paid.push(
+                bank.rewards
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .filter(|(_, reward_info)| reward_info.reward_type == RewardType::Staking)
+                    .map(|(stake_id, reward_info)| (*stake_id, reward_info.lamports))
+                    .collect(),
+            );
+        }
+        paid
+    };
+    let paid = stake_rewards_paid(bank2);
+    assert!(!paid.is_empty());
+    assert_eq!(stake_rewards_paid(Arc::new(dbank)), paid);
+}
+
 #[cfg(test)]
 pub(crate) fn reconstruct_accounts_db_via_serialization(
     accounts: &AccountsDB,
@@ -1,7 +1,11 @@
 use crate::{
+    accounts_index::AccountIndexes,
+    append_vec::AppendVecBackend,
     bank::{Bank, BankSlotDelta, Builtins},
-    bank_forks::CompressionType,
-    hardened_unpack::{unpack_snapshot, UnpackError},
+    bank_forks::{CompressionOptions, CompressionType},
+    hardened_unpack::{
+        unpack_snapshot_with_options, ArchiveReadTracker, UnpackError, UnpackOptions,
+    },
     serde_snapshot::{
         bank_from_stream, bank_to_stream, SerdeStyle, SnapshotStorage, SnapshotStorages,
     },
@@ -31,11 +35,17 @@ use std::{
     path::{Path, PathBuf},
     process::{self, ExitStatus},
     str::FromStr,
+    thread::sleep,
+    time::{Duration, Instant},
 };
 use tar::Archive;
 use tempfile::TempDir;
 use thiserror::Error;
 
+toml_config::package_config! {
+    SNAPSHOT_UNPACK_PARALLEL_WORKERS: usize,
+}
+
 pub const SNAPSHOT_STATUS_CACHE_FILE_NAME: &str = "status_cache";
 pub const TAR_SNAPSHOTS_DIR: &str = "snapshots";
 pub const TAR_ACCOUNTS_DIR: &str = "accounts";
@@ -45,6 +55,9 @@ const MAX_SNAPSHOT_DATA_FILE_SIZE: u64 = 32 * 1024 * 1024 * 1024; // 32 GiB
 const VERSION_STRING_V1_2_0: &str = "1.2.0";
 const DEFAULT_SNAPSHOT_VERSION: SnapshotVersion = SnapshotVersion::V1_2_0;
 
+// How often a paused snapshot archive write checks whether it may resume
+const THROTTLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum SnapshotVersion {
     V1_2_0,
@@ -128,6 +141,24 @@ pub enum SnapshotError {
 
     #[error("accounts package send error")]
     AccountsPackageSendError(#[from] AccountsPackageSendError),
+
+    #[error("invalid snapshot archive name: {0:?}")]
+    InvalidArchiveName(PathBuf),
+
+    #[error("snapshot bank for slot {0} failed to verify its bank hash or accounts hash")]
+    BankVerificationFailed(Slot),
+
+    #[error(
+        "snapshot archive name is for slot {} with hash {}, but it contains slot {} with hash {}",
+        archive.0,
+        archive.1,
+        deserialized.0,
+        deserialized.1
+    )]
+    ArchiveHashMismatch {
+        archive: (Slot, Hash),
+        deserialized: (Slot, Hash),
+    },
 }
 pub type Result<T> = std::result::Result<T, SnapshotError>;
 
@@ -213,7 +244,120 @@ fn get_compression_ext(compression: &CompressionType) -> &'static str {
     }
 }
 
+/// Limits the rate at which a snapshot archive is written, and pauses writing it on demand
+#[derive(Default)]
+pub struct ArchiveThrottle<'a> {
+    /// Maximum bytes written per second, 0 is unlimited
+    pub max_write_bytes_per_sec: u64,
+    /// Polled every `THROTTLE_POLL_INTERVAL` while writing, which is paused while it returns true
+    pub is_paused: Option<&'a dyn Fn() -> bool>,
+}
+
+struct ThrottledWriter<'a, W> {
+    writer: W,
+    throttle: &'a ArchiveThrottle<'a>,
+    // Bytes written since `since`, which is reset after a pause
+    written: u64,
+    since: Instant,
+    last_pause_check: Instant,
+}
+
+impl<'a, W: Write> ThrottledWriter<'a, W> {
+    fn new(writer: W, throttle: &'a ArchiveThrottle<'a>) -> Self {
+        let now = Instant::now();
+        Self {
+            writer,
+            throttle,
+            written: 0,
+            since: now,
+            last_pause_check: now,
+        }
+    }
+}
+
+impl<W: Write> Write for ThrottledWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(is_paused) = self.throttle.is_paused {
+            if self.last_pause_check.elapsed() >= THROTTLE_POLL_INTERVAL {
+                if is_paused() {
+                    while is_paused() {
+                        sleep(THROTTLE_POLL_INTERVAL);
+                    }
+                    self.written = 0;
+                    self.since = Instant::now();
+                }
+                self.last_pause_check = Instant::now();
+            }
+        }
+
+        let len = self.writer.write(buf)?;
+        if self.throttle.max_write_bytes_per_sec > 0 {
+            self.written += len as u64;
+            let expected = Duration::from_secs_f64(
+                self.written as f64 / self.throttle.max_write_bytes_per_sec as f64,
+            );
+            let elapsed = self.since.elapsed();
+            if expected > elapsed {
+                sleep(expected - elapsed);
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Lowers the CPU priority of the calling thread to the lowest and its IO priority to idle,
+/// which the threads and processes it starts afterwards inherit
+#[cfg(target_os = "linux")]
+pub fn set_thread_low_priority() -> io::Result<()> {
+    const LOWEST_NICE: libc::c_int = 19;
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_long = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_long = 13;
+
+    // On linux the priority set for a thread id only applies to that thread
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) } as libc::id_t;
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, tid, LOWEST_NICE) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        )
+    } != 0
+    {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_thread_low_priority() -> io::Result<()> {
+    Err(IOError::new(
+        ErrorKind::Other,
+        "thread priorities are not supported on this platform",
+    ))
+}
+
 pub fn archive_snapshot_package(snapshot_package: &AccountsPackage) -> Result<()> {
+    archive_snapshot_package_with_options(
+        snapshot_package,
+        &CompressionOptions::default(),
+        &ArchiveThrottle::default(),
+    )
+}
+
+pub fn archive_snapshot_package_with_options(
+    snapshot_package: &AccountsPackage,
+    compression_options: &CompressionOptions,
+    throttle: &ArchiveThrottle,
+) -> Result<()> {
     info!(
         "Generating snapshot archive for slot {}",
         snapshot_package.root
@@ -301,7 +445,7 @@ pub fn archive_snapshot_package(snapshot_package: &AccountsPackage) -> Result<()
             )));
         }
         Some(tar_output) => {
-            let mut archive_file = fs::File::create(&archive_path)?;
+            let mut archive_file = ThrottledWriter::new(fs::File::create(&archive_path)?, throttle);
 
             match snapshot_package.compression {
                 CompressionType::Bzip2 => {
@@ -320,7 +464,9 @@ pub fn archive_snapshot_package(snapshot_package: &AccountsPackage) -> Result<()
                     io::copy(tar_output, &mut archive_file)?;
                 }
                 CompressionType::Zstd => {
-                    let mut encoder = zstd::stream::Encoder::new(archive_file, 0)?;
+                    let mut encoder =
+                        zstd::stream::Encoder::new(archive_file, compression_options.zstd_level)?;
+                    encoder.multithread(compression_options.threads)?;
                     io::copy(tar_output, &mut encoder)?;
                     let _ = encoder.finish()?;
                 }
@@ -579,6 +725,8 @@ pub fn bank_from_archive<P: AsRef<Path>>(
     genesis_config: &GenesisConfig,
     debug_keys: Option<Arc<HashSet<Pubkey>>>,
     additional_builtins: Option<&Builtins>,
+    account_indexes: AccountIndexes,
+    append_vec_backend: AppendVecBackend,
 ) -> Result<Bank> {
     // Untar the snapshot into a temp directory under `snapshot_config.snapshot_path()`
     let unpack_dir = tempfile::tempdir_in(snapshot_path)?;
@@ -601,10 +749,12 @@ pub fn bank_from_archive<P: AsRef<Path>>(
         genesis_config,
         debug_keys,
         additional_builtins,
+        account_indexes,
+        append_vec_backend,
     )?;
 
     if !bank.verify_snapshot_bank() {
-        panic!("Snapshot bank for slot {} failed to verify", bank.slot());
+        return Err(SnapshotError::BankVerificationFailed(bank.slot()));
     }
     if genesis_config.cluster_type == ClusterType::Testnet {
         // remove me after we transitions to the fixed rent distribution with no overflow
@@ -638,6 +788,60 @@ pub fn bank_from_archive<P: AsRef<Path>>(
     Ok(bank)
 }
 
+/// Unpacks `snapshot_archive` into a temporary directory under `unpack_path`, rebuilds its bank
+/// and checks the bank hash and the accounts hash, and that the accounts hash and slot match
+/// the ones in the archive name. Returns the slot, bank hash and accounts hash of the snapshot
+pub fn verify_snapshot_archive<P: AsRef<Path>, Q: AsRef<Path>>(
+    snapshot_archive: P,
+    unpack_path: Q,
+    genesis_config: &GenesisConfig,
+    additional_builtins: Option<&Builtins>,
+) -> Result<(Slot, Hash, Hash)> {
+    let snapshot_archive = snapshot_archive.as_ref();
+    let (archive_slot, archive_hash, compression) = snapshot_archive
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .and_then(snapshot_hash_of)
+        .ok_or_else(|| SnapshotError::InvalidArchiveName(snapshot_archive.to_path_buf()))?;
+
+    // Keep everything the archive unpacks to, including the rebuilt account storage, inside a
+    // temporary directory that is removed once verification completes
+    let temp_dir = tempfile::tempdir_in(unpack_path)?;
+    let unpack_dir = temp_dir.path().join("unpack");
+    let account_paths = vec![temp_dir.path().join("accounts")];
+    fs::create_dir(&unpack_dir)?;
+    fs::create_dir(&account_paths[0])?;
+    untar_snapshot_in(snapshot_archive, &unpack_dir, compression)?;
+
+    let mut snapshot_version = String::new();
+    File::open(unpack_dir.join(TAR_VERSION_FILE))
+        .and_then(|mut f| f.read_to_string(&mut snapshot_version))?;
+
+    let bank = rebuild_bank_from_snapshots(
+        snapshot_version.trim(),
+        &account_paths,
+        &[],
+        &unpack_dir.join(TAR_SNAPSHOTS_DIR),
+        unpack_dir.join(TAR_ACCOUNTS_DIR),
+        genesis_config,
+        None,
+        additional_builtins,
+        AccountIndexes::default(),
+    )?;
+    if !bank.verify_snapshot_bank() {
+        return Err(SnapshotError::BankVerificationFailed(bank.slot()));
+    }
+
+    let deserialized = (bank.slot(), bank.get_accounts_hash());
+    if deserialized != (archive_slot, archive_hash) {
+        return Err(SnapshotError::ArchiveHashMismatch {
+            archive: (archive_slot, archive_hash),
+            deserialized,
+        });
+    }
+    Ok((bank.slot(), bank.hash(), bank.get_accounts_hash()))
+}
+
 pub fn get_snapshot_archive_path<P: AsRef<Path>>(
     snapshot_output_dir: P,
     snapshot_hash: &(Slot, Hash),
@@ -725,26 +929,33 @@ pub fn untar_snapshot_in<P: AsRef<Path>, Q: AsRef<Path>>(
 ) -> Result<()> {
     let mut measure = Measure::start("snapshot untar");
     let tar_name = File::open(&snapshot_tar)?;
+    let read_tracker = ArchiveReadTracker::new(tar_name.metadata()?.len());
+    let tar_name = read_tracker.reader(tar_name);
+    let options = UnpackOptions {
+        parallel_workers: CFG.SNAPSHOT_UNPACK_PARALLEL_WORKERS,
+        read_tracker: Some(read_tracker),
+        ..UnpackOptions::default()
+    };
     match compression {
         CompressionType::Bzip2 => {
             let tar = BzDecoder::new(BufReader::new(tar_name));
             let mut archive = Archive::new(tar);
-            unpack_snapshot(&mut archive, unpack_dir)?;
+            unpack_snapshot_with_options(&mut archive, unpack_dir, &options)?;
         }
         CompressionType::Gzip => {
             let tar = GzDecoder::new(BufReader::new(tar_name));
             let mut archive = Archive::new(tar);
-            unpack_snapshot(&mut archive, unpack_dir)?;
+            unpack_snapshot_with_options(&mut archive, unpack_dir, &options)?;
         }
         CompressionType::Zstd => {
             let tar = zstd::stream::read::Decoder::new(BufReader::new(tar_name))?;
             let mut archive = Archive::new(tar);
-            unpack_snapshot(&mut archive, unpack_dir)?;
+            unpack_snapshot_with_options(&mut archive, unpack_dir, &options)?;
         }
         CompressionType::NoCompression => {
             let tar = BufReader::new(tar_name);
             let mut archive = Archive::new(tar);
-            unpack_snapshot(&mut archive, unpack_dir)?;
+            unpack_snapshot_with_options(&mut archive, unpack_dir, &options)?;
         }
     };
     measure.stop();
@@ -761,6 +972,8 @@ fn rebuild_bank_from_snapshots<P>(
     genesis_config: &GenesisConfig,
     debug_keys: Option<Arc<HashSet<Pubkey>>>,
     additional_builtins: Option<&Builtins>,
+    account_indexes: AccountIndexes,
+    append_vec_backend: AppendVecBackend,
 ) -> Result<Bank>
 where
     P: AsRef<Path>,
@@ -794,6 +1007,8 @@ where
                 frozen_account_pubkeys,
                 debug_keys,
                 additional_builtins,
+                account_indexes,
+                append_vec_backend,
             ),
         }?)
     })?;
@@ -902,13 +1117,77 @@ pub fn snapshot_bank(
     Ok(())
 }
 
+/// Writes a snapshot archive of `bank` into `snapshot_package_output_path`, using a temporary
+/// directory in `snapshot_path` while packaging. `bank` is squashed, making it a root.
+/// Returns the path of the archive
+pub fn bank_to_snapshot_archive<P: AsRef<Path>, Q: AsRef<Path>>(
+    snapshot_path: P,
+    bank: &Bank,
+    snapshot_version: Option<SnapshotVersion>,
+    snapshot_package_output_path: Q,
+    compression: CompressionType,
+) -> Result<PathBuf> {
+    let snapshot_version = snapshot_version.unwrap_or_default();
+
+    assert!(bank.is_complete());
+    bank.squash(); // Bank may not be a root
+    bank.clean_accounts(true);
+    bank.update_accounts_hash();
+
+    let temp_dir = tempfile::tempdir_in(snapshot_path)?;
+    let storages: Vec<_> = bank.get_snapshot_storages();
+    let slot_snapshot_paths = add_snapshot(&temp_dir, bank, &storages, snapshot_version)?;
+    let package = package_snapshot(
+        bank,
+        &slot_snapshot_paths,
+        &temp_dir,
+        bank.src.slot_deltas(&bank.src.roots()),
+        snapshot_package_output_path,
+        storages,
+        compression,
+        snapshot_version,
+    )?;
+
+    archive_snapshot_package(&package)?;
+    Ok(package.tar_output_file)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::genesis_utils::create_genesis_config;
     use assert_matches::assert_matches;
     use bincode::{deserialize_from, serialize_into};
+    use solana_sdk::hash::hash;
     use std::mem::size_of;
 
+    #[test]
+    fn test_throttled_writer() {
+        let pauses = std::cell::Cell::new(2);
+        let is_paused = || {
+            let paused = pauses.get() > 0;
+            pauses.set(pauses.get().saturating_sub(1));
+            paused
+        };
+        let throttle = ArchiveThrottle {
+            max_write_bytes_per_sec: 10_000,
+            is_paused: Some(&is_paused),
+        };
+        let mut archive = vec![];
+        let start = Instant::now();
+        {
+            let mut writer = ThrottledWriter::new(&mut archive, &throttle);
+            sleep(THROTTLE_POLL_INTERVAL);
+            for _ in 0..4 {
+                writer.write_all(&[1; 500]).unwrap();
+            }
+        }
+        assert_eq!(archive, vec![1; 2000]);
+        assert_eq!(pauses.get(), 0);
+        // Paused for a poll interval, then written at 10k bytes per second
+        assert!(start.elapsed() >= 2 * THROTTLE_POLL_INTERVAL + Duration::from_millis(200));
+    }
+
     #[test]
     fn test_serialize_snapshot_data_file_under_limit() {
         let temp_dir = tempfile::TempDir::new().unwrap();
@@ -1027,4 +1306,53 @@ mod tests {
 
         assert!(snapshot_hash_of("invalid").is_none());
     }
+
+    #[test]
+    fn test_verify_snapshot_archive() {
+        let genesis_config = create_genesis_config(500).genesis_config;
+        let bank = Bank::new(&genesis_config);
+        while !bank.is_complete() {
+            bank.register_tick(&hash(&bank.tick_height().to_le_bytes()));
+        }
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let archive_path = bank_to_snapshot_archive(
+            temp_dir.path(),
+            &bank,
+            None,
+            temp_dir.path(),
+            CompressionType::Bzip2,
+        )
+        .unwrap();
+
+        assert_eq!(
+            verify_snapshot_archive(&archive_path, temp_dir.path(), &genesis_config, None).unwrap(),
+            (bank.slot(), bank.hash(), bank.get_accounts_hash())
+        );
+
+        // An archive renamed to claim another accounts hash is rejected
+        let renamed_archive_path = get_snapshot_archive_path(
+            temp_dir.path(),
+            &(bank.slot(), Hash::default()),
+            &CompressionType::Bzip2,
+        );
+        fs::rename(&archive_path, &renamed_archive_path).unwrap();
+        assert_matches!(
+            verify_snapshot_archive(&renamed_archive_path, temp_dir.path(), &genesis_config, None),
+            Err(SnapshotError::ArchiveHashMismatch { archive, deserialized })
+                if archive == (bank.slot(), Hash::default())
+                    && deserialized == (bank.slot(), bank.get_accounts_hash())
+        );
+
+        let invalid_archive_path = temp_dir.path().join("snapshot.tar.bz2");
+        fs::rename(&renamed_archive_path, &invalid_archive_path).unwrap();
+        assert_matches!(
+            verify_snapshot_archive(
+                &invalid_archive_path,
+                temp_dir.path(),
+                &genesis_config,
+                None
+            ),
+            Err(SnapshotError::InvalidArchiveName(_))
+        );
+    }
 }
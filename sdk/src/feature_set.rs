@@ -78,6 +78,46 @@ pub mod pull_request_ping_pong_check {
     solana_sdk::declare_id!("5RzEHTnf6D7JPZCvwEzjM19kzBsyjSU3HoMfXaQmVgnZ");
 }
 
+pub mod max_account_data_size_enforcement {
+    solana_sdk::declare_id!("6h7SaENK6Xkt9meARZXWxRaXM4C2xyMaWv6V24nfgNBN");
+}
+
+pub mod partitioned_epoch_rewards {
+    solana_sdk::declare_id!("XwJiGoBZ9CjetEH4QgUPAQoD1h4aQgcgPvA31pDyegy");
+}
+
+pub mod block_cost_limits {
+    solana_sdk::declare_id!("GNfPYXfkW6NXkpekHmpLVCxCHMWvMi7Fg9u2qMKutjKj");
+}
+
+pub mod sharded_epoch_slots {
+    solana_sdk::declare_id!("8owJrD3qyWQ6dYLuiuuRHvmBXM9Dd74cGbq8eyJo7QXS");
+}
+
+pub mod turbine_tree {
+    solana_sdk::declare_id!("9mFQNjdWD4vDYGjQxTJCWCqrKcySiCR8njhHce4ULasC");
+}
+
+pub mod gossip_node_instance {
+    solana_sdk::declare_id!("4EbEwVLwQeaFLsjzZPUhV5FoezBvbf33JNhQd9ZCUYNx");
+}
+
+pub mod gossip_node_capabilities {
+    solana_sdk::declare_id!("6ZCpn4VmaUR8PsQY7GwKbY8UsyLoiG4AVtsT7a8xMcZY");
+}
+
+pub mod gossip_validator_info {
+    solana_sdk::declare_id!("4sbtYViP86XAG4QzdEf5usLNiv7V4RsGkYiDiHR2AoTv");
+}
+
+pub mod gossip_planned_hard_fork {
+    solana_sdk::declare_id!("FZo3CDenXG64Gzjfak9MgvYbjMhiu9KAcnr9JHDQbqb8");
+}
+
+pub mod gossip_restart_bank_hash {
+    solana_sdk::declare_id!("Edp7hoYvEb1NpX6wjM4x6sA1pgjCweM6NnMP1py8kN4a");
+}
+
 lazy_static! {
     /// Map of feature identifiers to user-visible description
     pub static ref FEATURE_NAMES: HashMap<Pubkey, &'static str> = [
@@ -99,6 +139,16 @@ lazy_static! {
         (sol_log_compute_units_syscall::id(), "sol_log_compute_units syscall (#13243)"),
         (pubkey_log_syscall_enabled::id(), "pubkey log syscall"),
         (pull_request_ping_pong_check::id(), "ping-pong packet check #12794"),
+        (max_account_data_size_enforcement::id(), "max account data size enforcement"),
+        (partitioned_epoch_rewards::id(), "partitioned epoch rewards distribution"),
+        (block_cost_limits::id(), "reject blocks exceeding the cost model limits"),
+        (sharded_epoch_slots::id(), "publish epoch slots as generation-numbered gossip shards"),
+        (turbine_tree::id(), "derive the turbine tree of each shred from its slot, index and leader"),
+        (gossip_node_instance::id(), "publish node instances in gossip"),
+        (gossip_node_capabilities::id(), "publish node capabilities in gossip"),
+        (gossip_validator_info::id(), "publish validator info in gossip"),
+        (gossip_planned_hard_fork::id(), "publish planned hard forks in gossip"),
+        (gossip_restart_bank_hash::id(), "publish restart bank hashes in gossip"),
         /*************** ADD NEW FEATURES HERE ***************/
     ]
     .iter()
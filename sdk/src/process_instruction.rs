@@ -8,6 +8,7 @@ use solana_sdk::{
     keyed_account::KeyedAccount,
     message::Message,
     pubkey::Pubkey,
+    system_instruction::MAX_PERMITTED_DATA_LENGTH,
 };
 use std::{cell::RefCell, fmt::Debug, rc::Rc, sync::Arc};
 
@@ -87,6 +88,9 @@ pub struct BpfComputeBudget {
     pub stack_frame_size: usize,
     /// Number of compute units consumed by logging a `Pubkey`
     pub log_pubkey_units: u64,
+    /// Maximum data length of the writable accounts of an instruction, once the
+    /// `max_account_data_size_enforcement` feature is active
+    pub max_account_data_len: u64,
 }
 impl Default for BpfComputeBudget {
     fn default() -> Self {
@@ -109,6 +113,7 @@ impl BpfComputeBudget {
             max_call_depth: 20,
             stack_frame_size: 4_096,
             log_pubkey_units: 0,
+            max_account_data_len: MAX_PERMITTED_DATA_LENGTH,
         };
 
         if feature_set.is_active(&bpf_compute_budget_balancing::id()) {
@@ -1,3 +1,4 @@
+use async_trait::async_trait;
 use log::*;
 use serde::{Deserialize, Serialize};
 use solana_sdk::{
@@ -12,7 +13,7 @@ use solana_transaction_status::{
     ConfirmedBlock, ConfirmedTransaction, ConfirmedTransactionStatusWithSignature, Reward,
     TransactionStatus, TransactionStatusMeta, TransactionWithStatusMeta,
 };
-use std::{collections::HashMap, convert::TryInto};
+use std::{collections::HashMap, convert::TryInto, path::Path, sync::Arc};
 use thiserror::Error;
 
 #[macro_use]
@@ -21,13 +22,28 @@ extern crate serde_derive;
 mod access_token;
 mod bigtable;
 mod compression;
+mod postgres;
 mod root_ca_certificate;
 
+pub use postgres::PostgresLedgerStorage;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("BigTable: {0}")]
     BigTableError(bigtable::Error),
 
+    #[error("PostgreSQL: {0}")]
+    PostgresError(tokio_postgres::Error),
+
+    #[error("TLS: {0}")]
+    TlsError(native_tls::Error),
+
+    #[error("Storage is read-only")]
+    ReadOnly,
+
+    #[error("Object is corrupt: {0}")]
+    ObjectCorrupt(String),
+
     #[error("I/O Error: {0}")]
     IoError(std::io::Error),
 
@@ -55,6 +71,69 @@ impl std::convert::From<std::io::Error> for Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Storage for historical blocks and transactions that have been purged from the local ledger
+#[async_trait]
+pub trait HistoricalLedgerStorage: Send + Sync {
+    /// Return the available slot that contains a block
+    async fn get_first_available_block(&self) -> Result<Option<Slot>>;
+
+    /// Fetch the next slots after the provided slot that contains a block
+    ///
+    /// start_slot: slot to start the search from (inclusive)
+    /// limit: stop after this many slots have been found.
+    async fn get_confirmed_blocks(&self, start_slot: Slot, limit: usize) -> Result<Vec<Slot>>;
+
+    /// Fetch the confirmed block from the desired slot
+    async fn get_confirmed_block(&self, slot: Slot) -> Result<ConfirmedBlock>;
+
+    async fn get_signature_status(&self, signature: &Signature) -> Result<TransactionStatus>;
+
+    /// Fetch a confirmed transaction
+    async fn get_confirmed_transaction(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<ConfirmedTransaction>>;
+
+    /// Get confirmed signatures for the provided address, in descending ledger order
+    ///
+    /// address: address to search for
+    /// before_signature: start with the first signature older than this one
+    /// until_signature: end with the last signature newer than this one
+    /// limit: stop after this many signatures.
+    async fn get_confirmed_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before_signature: Option<&Signature>,
+        until_signature: Option<&Signature>,
+        limit: usize,
+    ) -> Result<
+        Vec<(
+            ConfirmedTransactionStatusWithSignature,
+            u32, /*slot index*/
+        )>,
+    >;
+
+    // Upload a new confirmed block and associated meta data.
+    async fn upload_confirmed_block(
+        &self,
+        slot: Slot,
+        confirmed_block: ConfirmedBlock,
+    ) -> Result<()>;
+}
+
+/// Connect to the historical ledger storage at `url`, a `postgres://` connection string, or to
+/// BigTable if no url is provided. `ca_certificate` is trusted for TLS connections to PostgreSQL
+pub async fn connect_historical_ledger_storage(
+    url: Option<&str>,
+    ca_certificate: Option<&Path>,
+    read_only: bool,
+) -> Result<Arc<dyn HistoricalLedgerStorage>> {
+    Ok(match url {
+        Some(url) => Arc::new(PostgresLedgerStorage::new(url, ca_certificate, read_only).await?),
+        None => Arc::new(LedgerStorage::new(read_only).await?),
+    })
+}
+
 // Convert a slot to its bucket representation whereby lower slots are always lexically ordered
 // before higher slots
 fn slot_to_key(slot: Slot) -> String {
@@ -255,6 +334,7 @@ impl From<TransactionInfo> for TransactionStatus {
             confirmations: None,
             status,
             err,
+            optimistically_confirmed: true,
         }
     }
 }
@@ -269,6 +349,35 @@ struct TransactionByAddrInfo {
     memo: Option<String>,          // Transaction memo
 }
 
+// Extract the transaction at `index` from `block`, checking that it is the one with `signature`
+fn transaction_from_block(
+    signature: &Signature,
+    slot: Slot,
+    index: u32,
+    block: ConfirmedBlock,
+) -> Option<ConfirmedTransaction> {
+    match block.transactions.into_iter().nth(index as usize) {
+        None => {
+            warn!("Transaction info for {} is corrupt", signature);
+            None
+        }
+        Some(bucket_block_transaction) => {
+            if bucket_block_transaction.transaction.signatures[0] != *signature {
+                warn!(
+                    "Transaction info or confirmed block for {} is corrupt",
+                    signature
+                );
+                None
+            } else {
+                Some(ConfirmedTransaction {
+                    slot,
+                    transaction: bucket_block_transaction,
+                })
+            }
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct LedgerStorage {
     connection: bigtable::BigTableConnection,
@@ -341,26 +450,7 @@ impl LedgerStorage {
 
         // Load the block and return the transaction
         let block = self.get_confirmed_block(slot).await?;
-        match block.transactions.into_iter().nth(index as usize) {
-            None => {
-                warn!("Transaction info for {} is corrupt", signature);
-                Ok(None)
-            }
-            Some(bucket_block_transaction) => {
-                if bucket_block_transaction.transaction.signatures[0] != *signature {
-                    warn!(
-                        "Transaction info or confirmed block for {} is corrupt",
-                        signature
-                    );
-                    Ok(None)
-                } else {
-                    Ok(Some(ConfirmedTransaction {
-                        slot,
-                        transaction: bucket_block_transaction,
-                    }))
-                }
-            }
-        }
+        Ok(transaction_from_block(signature, slot, index, block))
     }
 
     /// Get confirmed signatures for the provided address, in descending ledger order
@@ -554,6 +644,62 @@ impl LedgerStorage {
     }
 }
 
+#[async_trait]
+impl HistoricalLedgerStorage for LedgerStorage {
+    async fn get_first_available_block(&self) -> Result<Option<Slot>> {
+        LedgerStorage::get_first_available_block(self).await
+    }
+
+    async fn get_confirmed_blocks(&self, start_slot: Slot, limit: usize) -> Result<Vec<Slot>> {
+        LedgerStorage::get_confirmed_blocks(self, start_slot, limit).await
+    }
+
+    async fn get_confirmed_block(&self, slot: Slot) -> Result<ConfirmedBlock> {
+        LedgerStorage::get_confirmed_block(self, slot).await
+    }
+
+    async fn get_signature_status(&self, signature: &Signature) -> Result<TransactionStatus> {
+        LedgerStorage::get_signature_status(self, signature).await
+    }
+
+    async fn get_confirmed_transaction(
+        &self,
+        signature: &Signature,
+    ) -> Result<Option<ConfirmedTransaction>> {
+        LedgerStorage::get_confirmed_transaction(self, signature).await
+    }
+
+    async fn get_confirmed_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before_signature: Option<&Signature>,
+        until_signature: Option<&Signature>,
+        limit: usize,
+    ) -> Result<
+        Vec<(
+            ConfirmedTransactionStatusWithSignature,
+            u32, /*slot index*/
+        )>,
+    > {
+        LedgerStorage::get_confirmed_signatures_for_address(
+            self,
+            address,
+            before_signature,
+            until_signature,
+            limit,
+        )
+        .await
+    }
+
+    async fn upload_confirmed_block(
+        &self,
+        slot: Slot,
+        confirmed_block: ConfirmedBlock,
+    ) -> Result<()> {
+        LedgerStorage::upload_confirmed_block(self, slot, confirmed_block).await
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -43,18 +43,35 @@ pub enum TomlConfigErr {
 }
 
 pub fn parse_config<'a, T: serde::Deserialize<'a>>(pkg_name: &str) -> Result<T, TomlConfigErr> {
+    let (_toml_file, table) = read_config()?;
+    package_section(&table, pkg_name)?
+        .clone()
+        .try_into()
+        .map_err(TomlConfigErr::Parse)
+}
+
+/// Checks that the toml config can be read and has a section for each of `pkg_names`, without
+/// panicking as the first access to the `CFG` of a package would. Returns the path of the config.
+pub fn check_config(pkg_names: &[&str]) -> Result<String, TomlConfigErr> {
+    let (toml_file, table) = read_config()?;
+    for pkg_name in pkg_names {
+        if !package_section(&table, pkg_name)?.is_table() {
+            return Err(TomlConfigErr::BadConfig(format!(
+                "Expected table for the section of package {}",
+                pkg_name
+            )));
+        }
+    }
+    Ok(toml_file)
+}
+
+fn read_config() -> Result<(String, toml::value::Table), TomlConfigErr> {
     let toml_file = env::var(TOML_CONFIG_ENV_VAR)?;
-    let content = fs::read_to_string(toml_file)?;
+    let content = fs::read_to_string(&toml_file)?;
     let value: toml::Value = content.parse()?;
 
     if let toml::Value::Table(table) = value {
-        let value = table.get(pkg_name).ok_or_else(|| {
-            TomlConfigErr::BadConfig(format!(
-                "Table doesn't contains required section for package {}",
-                pkg_name
-            ))
-        })?;
-        value.clone().try_into().map_err(TomlConfigErr::Parse)
+        Ok((toml_file, table))
     } else {
         Err(TomlConfigErr::BadConfig(format!(
             "Expected table at toml top level, but got: {:?}",
@@ -63,6 +80,18 @@ pub fn parse_config<'a, T: serde::Deserialize<'a>>(pkg_name: &str) -> Result<T,
     }
 }
 
+fn package_section<'a>(
+    table: &'a toml::value::Table,
+    pkg_name: &str,
+) -> Result<&'a toml::Value, TomlConfigErr> {
+    table.get(pkg_name).ok_or_else(|| {
+        TomlConfigErr::BadConfig(format!(
+            "Table doesn't contains required section for package {}",
+            pkg_name
+        ))
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use crate as toml_config;
@@ -77,4 +106,10 @@ mod tests {
         assert_eq!(CFG.FOO, 42);
         assert_eq!(CFG.BAR, 13);
     }
+
+    #[test]
+    fn test_check_config() {
+        assert!(toml_config::check_config(&[env!("CARGO_PKG_NAME")]).is_ok());
+        assert!(toml_config::check_config(&["no-such-package"]).is_err());
+    }
 }
@@ -221,11 +221,15 @@ pub struct TransactionStatus {
     pub confirmations: Option<usize>, // None = rooted
     pub status: Result<()>,           // legacy field
     pub err: Option<TransactionError>,
+    #[serde(default)]
+    pub optimistically_confirmed: bool, // voted on by a supermajority, observed in gossip or replay
 }
 
 impl TransactionStatus {
     pub fn satisfies_commitment(&self, commitment_config: CommitmentConfig) -> bool {
         (commitment_config == CommitmentConfig::default() && self.confirmations.is_none())
+            || (commitment_config == CommitmentConfig::single_gossip()
+                && (self.optimistically_confirmed || self.confirmations.is_none()))
             || commitment_config == CommitmentConfig::recent()
     }
 }
@@ -499,9 +503,11 @@ mod test {
             confirmations: None,
             status: Ok(()),
             err: None,
+            optimistically_confirmed: true,
         };
 
         assert!(status.satisfies_commitment(CommitmentConfig::default()));
+        assert!(status.satisfies_commitment(CommitmentConfig::single_gossip()));
         assert!(status.satisfies_commitment(CommitmentConfig::recent()));
 
         let status = TransactionStatus {
@@ -509,9 +515,23 @@ mod test {
             confirmations: Some(10),
             status: Ok(()),
             err: None,
+            optimistically_confirmed: true,
         };
 
         assert!(!status.satisfies_commitment(CommitmentConfig::default()));
+        assert!(status.satisfies_commitment(CommitmentConfig::single_gossip()));
+        assert!(status.satisfies_commitment(CommitmentConfig::recent()));
+
+        let status = TransactionStatus {
+            slot: 0,
+            confirmations: Some(1),
+            status: Ok(()),
+            err: None,
+            optimistically_confirmed: false,
+        };
+
+        assert!(!status.satisfies_commitment(CommitmentConfig::default()));
+        assert!(!status.satisfies_commitment(CommitmentConfig::single_gossip()));
         assert!(status.satisfies_commitment(CommitmentConfig::recent()));
     }
 }
@@ -0,0 +1,105 @@
+//! The `dry_run` module collects the outcome of the checks `--dry-run` performs in place of
+//! starting the validator services, so that a misconfigured validator is reported at once
+//! rather than by the first service failing.
+
+use serde_json::json;
+use solana_client::rpc_client::RpcClient;
+use solana_core::gossip_service::{discover_rpc_nodes, RpcNodeFilter};
+use solana_sdk::hash::Hash;
+use std::{fmt, fs, net::SocketAddr, path::Path, time::Duration};
+
+/// How long to spy on gossip for an RPC node to check the genesis hash of the cluster against
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default)]
+pub struct DryRunReport {
+    // Name of each check, with the details of what passed or why it failed
+    checks: Vec<(String, Result<String, String>)>,
+}
+
+impl DryRunReport {
+    pub fn check(&mut self, name: &str, result: Result<String, String>) {
+        self.checks.push((name.to_string(), result));
+    }
+
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|(_, result)| result.is_ok())
+    }
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let checks: Vec<_> = self
+            .checks
+            .iter()
+            .map(|(name, result)| match result {
+                Ok(detail) => json!({ "check": name, "status": "ok", "detail": detail }),
+                Err(err) => json!({ "check": name, "status": "failed", "detail": err }),
+            })
+            .collect();
+        let report = json!({ "passed": self.passed(), "checks": checks });
+        write!(f, "{}", serde_json::to_string_pretty(&report).unwrap())
+    }
+}
+
+/// Checks that files can be created in the directory at `path`
+pub fn check_writable_dir(path: &Path) -> Result<String, String> {
+    let probe = path.join(".dry-run");
+    fs::write(&probe, b"")
+        .and_then(|_| fs::remove_file(&probe))
+        .map(|_| format!("{:?} is writable", path))
+        .map_err(|err| format!("{:?} is not writable: {}", path, err))
+}
+
+/// Checks the genesis hash of an RPC node of the cluster rendezvoused with at `entrypoint`
+/// against `expected_genesis_hash`
+pub fn check_cluster_genesis_hash(
+    entrypoint: &SocketAddr,
+    shred_version: u16,
+    expected_genesis_hash: Option<Hash>,
+) -> Result<String, String> {
+    let filter = RpcNodeFilter {
+        shred_version,
+        min_version: None,
+    };
+    let rpc_node = discover_rpc_nodes(entrypoint, 1, &filter, DISCOVERY_TIMEOUT)
+        .map_err(|err| format!("Unable to find an RPC node: {}", err))?
+        .remove(0);
+    let genesis_hash = RpcClient::new_socket(rpc_node.rpc)
+        .get_genesis_hash()
+        .map_err(|err| format!("Failed to get genesis hash from {}: {}", rpc_node.rpc, err))?;
+    match expected_genesis_hash {
+        Some(expected_genesis_hash) if expected_genesis_hash != genesis_hash => Err(format!(
+            "Genesis hash mismatch: expected {} but RPC node {} genesis hash is {}",
+            expected_genesis_hash, rpc_node.id, genesis_hash
+        )),
+        _ => Ok(format!(
+            "RPC node {} genesis hash is {}",
+            rpc_node.id, genesis_hash
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dry_run_report() {
+        let ledger_path = std::env::temp_dir();
+        let mut report = DryRunReport::default();
+        report.check("ledger", check_writable_dir(&ledger_path));
+        assert!(report.passed());
+        report.check(
+            "accounts",
+            check_writable_dir(&ledger_path.join("no-such-directory")),
+        );
+        assert!(!report.passed());
+
+        let report: serde_json::Value = serde_json::from_str(&report.to_string()).unwrap();
+        assert_eq!(report["passed"], false);
+        assert_eq!(report["checks"][0]["status"], "ok");
+        assert_eq!(report["checks"][1]["check"], "accounts");
+        assert_eq!(report["checks"][1]["status"], "failed");
+    }
+}
@@ -0,0 +1,322 @@
+//! The `rpc_node_selector` module picks the RPC node a validator bootstraps from. The selected
+//! node is kept for as long as it remains eligible, instead of picking a new random candidate
+//! every round, and nodes that fail are backed off exponentially rather than blacklisted. The
+//! trusted validators are only backed off briefly, as the node may have no other source.
+
+use log::*;
+use rand::{seq::SliceRandom, thread_rng};
+use solana_core::contact_info::ContactInfo;
+use solana_sdk::pubkey::Pubkey;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+/// How long a candidate has to respond to a probe
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(600);
+const MAX_TRUSTED_BACKOFF: Duration = INITIAL_BACKOFF;
+// Maximum number of candidates probed when a new node has to be selected
+const MAX_PROBED_CANDIDATES: usize = 8;
+
+#[derive(Debug, Default)]
+struct PeerHealth {
+    failures: u32,
+    last_error: String,
+    backoff_until: Option<Instant>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CandidateStatus {
+    Selected {
+        latency: Option<Duration>,
+    },
+    Eligible {
+        latency: Option<Duration>,
+    },
+    BackedOff {
+        failures: u32,
+        remaining: Duration,
+        last_error: String,
+    },
+    Rejected(String),
+}
+
+impl fmt::Display for CandidateStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let latency = |latency: &Option<Duration>| {
+            latency
+                .map(|latency| format!(", latency {}ms", latency.as_millis()))
+                .unwrap_or_default()
+        };
+        match self {
+            CandidateStatus::Selected { latency: l } => write!(f, "selected{}", latency(l)),
+            CandidateStatus::Eligible { latency: l } => write!(f, "eligible{}", latency(l)),
+            CandidateStatus::BackedOff {
+                failures,
+                remaining,
+                last_error,
+            } => write!(
+                f,
+                "backed off for {}s after {} failure{}, last: {}",
+                remaining.as_secs(),
+                failures,
+                if *failures == 1 { "" } else { "s" },
+                last_error
+            ),
+            CandidateStatus::Rejected(reason) => write!(f, "rejected, {}", reason),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RpcNodeSelector {
+    trusted_validators: HashSet<Pubkey>,
+    health: HashMap<Pubkey, PeerHealth>,
+    selected: Option<Pubkey>,
+    // Status of the candidates considered in the current round
+    statuses: Vec<(Pubkey, SocketAddr, CandidateStatus)>,
+}
+
+impl RpcNodeSelector {
+    pub fn new(trusted_validators: HashSet<Pubkey>) -> Self {
+        Self {
+            trusted_validators,
+            ..Self::default()
+        }
+    }
+
+    /// Starts a new round of selection, forgetting the statuses of the previous one
+    pub fn start_round(&mut self) {
+        self.statuses.clear();
+    }
+
+    /// Removes the candidates that are backed off
+    pub fn filter_backed_off(&mut self, candidates: Vec<ContactInfo>) -> Vec<ContactInfo> {
+        let now = Instant::now();
+        candidates
+            .into_iter()
+            .filter(|candidate| match self.health.get(&candidate.id) {
+                Some(health) if health.backoff_until.map_or(false, |until| until > now) => {
+                    let status = CandidateStatus::BackedOff {
+                        failures: health.failures,
+                        remaining: health.backoff_until.unwrap() - now,
+                        last_error: health.last_error.clone(),
+                    };
+                    self.statuses.push((candidate.id, candidate.rpc, status));
+                    false
+                }
+                _ => true,
+            })
+            .collect()
+    }
+
+    /// Records why `candidate` is not eligible this round, unless it already has a status
+    pub fn reject(&mut self, candidate: &ContactInfo, reason: String) {
+        if !self.statuses.iter().any(|(id, _, _)| *id == candidate.id) {
+            self.statuses.push((
+                candidate.id,
+                candidate.rpc,
+                CandidateStatus::Rejected(reason),
+            ));
+        }
+    }
+
+    /// Selects a node among the eligible `candidates`. The previously selected node is kept if
+    /// it still is a candidate. Otherwise some candidates are probed, `probe` returning how long
+    /// they took to respond, and the fastest is selected. Candidates failing the probe are backed
+    /// off
+    pub fn select<F>(&mut self, candidates: &[ContactInfo], probe: F) -> Option<ContactInfo>
+    where
+        F: Fn(&ContactInfo) -> Result<Duration, String>,
+    {
+        if let Some(selected) = self
+            .selected
+            .and_then(|selected| candidates.iter().find(|c| c.id == selected))
+        {
+            for candidate in candidates {
+                let status = if candidate.id == selected.id {
+                    CandidateStatus::Selected { latency: None }
+                } else {
+                    CandidateStatus::Eligible { latency: None }
+                };
+                self.statuses.push((candidate.id, candidate.rpc, status));
+            }
+            return Some(selected.clone());
+        }
+
+        let mut probed = vec![];
+        for candidate in candidates.choose_multiple(&mut thread_rng(), MAX_PROBED_CANDIDATES) {
+            match probe(candidate) {
+                Ok(latency) => probed.push((candidate, latency)),
+                Err(err) => {
+                    let reason = format!("probe failed: {}", err);
+                    self.record_failure(&candidate.id, err);
+                    self.reject(candidate, reason);
+                }
+            }
+        }
+        let selected = probed
+            .iter()
+            .min_by_key(|(_, latency)| *latency)
+            .map(|(candidate, _)| (*candidate).clone());
+        for (candidate, latency) in probed {
+            let latency = Some(latency);
+            let status = if Some(candidate.id) == selected.as_ref().map(|s| s.id) {
+                CandidateStatus::Selected { latency }
+            } else {
+                CandidateStatus::Eligible { latency }
+            };
+            self.statuses.push((candidate.id, candidate.rpc, status));
+        }
+        self.selected = selected.as_ref().map(|selected| selected.id);
+        selected
+    }
+
+    /// Backs off a node that failed, for twice as long as the previous time, up to
+    /// `MAX_TRUSTED_BACKOFF` for the trusted validators
+    pub fn record_failure(&mut self, id: &Pubkey, error: String) {
+        let health = self.health.entry(*id).or_default();
+        health.failures += 1;
+        let mut backoff = backoff_duration(health.failures);
+        if self.trusted_validators.contains(id) {
+            backoff = backoff.min(MAX_TRUSTED_BACKOFF);
+        }
+        warn!(
+            "Backing off RPC node {} for {}s: {}",
+            id,
+            backoff.as_secs(),
+            error
+        );
+        health.last_error = error;
+        health.backoff_until = Some(Instant::now() + backoff);
+        if self.selected == Some(*id) {
+            self.selected = None;
+        }
+    }
+
+    pub fn statuses(&self) -> &[(Pubkey, SocketAddr, CandidateStatus)] {
+        &self.statuses
+    }
+
+    /// Logs the status of the candidates of the current round
+    pub fn report(&self) {
+        if self.statuses.is_empty() {
+            return;
+        }
+        let statuses: Vec<_> = self
+            .statuses
+            .iter()
+            .map(|(id, rpc, status)| format!("  {} {}: {}", id, rpc, status))
+            .collect();
+        info!("RPC node candidates:\n{}", statuses.join("\n"));
+    }
+}
+
+fn backoff_duration(failures: u32) -> Duration {
+    let exponent = failures.saturating_sub(1).min(16);
+    (INITIAL_BACKOFF * 2u32.pow(exponent)).min(MAX_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_candidate() -> ContactInfo {
+        ContactInfo::new_localhost(&solana_sdk::pubkey::new_rand(), 0)
+    }
+
+    #[test]
+    fn test_backoff_duration() {
+        assert_eq!(backoff_duration(1), INITIAL_BACKOFF);
+        assert_eq!(backoff_duration(2), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff_duration(3), INITIAL_BACKOFF * 4);
+        assert_eq!(backoff_duration(100), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_rpc_node_selector() {
+        let candidates: Vec<_> = (0..3).map(|_| new_candidate()).collect();
+        let latency_of = |candidate: &ContactInfo| {
+            let index = candidates
+                .iter()
+                .position(|c| c.id == candidate.id)
+                .unwrap();
+            Duration::from_millis(100 - index as u64)
+        };
+        let mut selector = RpcNodeSelector::default();
+
+        // The fastest candidate is selected
+        let selected = selector.select(&candidates, |c| Ok(latency_of(c)));
+        assert_eq!(selected.unwrap().id, candidates[2].id);
+        let (_, _, status) = selector
+            .statuses()
+            .iter()
+            .find(|(id, _, _)| *id == candidates[2].id)
+            .unwrap();
+        assert_eq!(
+            *status,
+            CandidateStatus::Selected {
+                latency: Some(Duration::from_millis(98))
+            }
+        );
+
+        // and kept, even if another one responds faster
+        selector.start_round();
+        let selected = selector.select(&candidates, |_| Ok(Duration::from_millis(1)));
+        assert_eq!(selected.unwrap().id, candidates[2].id);
+
+        // until it fails
+        selector.record_failure(&candidates[2].id, "timed out".to_string());
+        selector.start_round();
+        let eligible = selector.filter_backed_off(candidates.clone());
+        assert_eq!(eligible.len(), 2);
+        assert!(matches!(
+            selector.statuses()[0].2,
+            CandidateStatus::BackedOff { failures: 1, .. }
+        ));
+        let selected = selector.select(&eligible, |c| {
+            if c.id == candidates[1].id {
+                Err("connection refused".to_string())
+            } else {
+                Ok(latency_of(c))
+            }
+        });
+        assert_eq!(selected.unwrap().id, candidates[0].id);
+        assert_eq!(selector.filter_backed_off(candidates.clone()).len(), 1);
+
+        // Rejections are reported along with the other statuses
+        selector.start_round();
+        selector.reject(&candidates[0], "no snapshot".to_string());
+        assert_eq!(
+            selector.statuses()[0].2,
+            CandidateStatus::Rejected("no snapshot".to_string())
+        );
+        assert!(selector.select(&[], |c| Ok(latency_of(c))).is_none());
+    }
+
+    #[test]
+    fn test_rpc_node_selector_trusted_backoff() {
+        let candidates: Vec<_> = (0..2).map(|_| new_candidate()).collect();
+        let mut selector = RpcNodeSelector::new(vec![candidates[0].id].into_iter().collect());
+        for _ in 0..10 {
+            selector.record_failure(&candidates[0].id, "timed out".to_string());
+            selector.record_failure(&candidates[1].id, "timed out".to_string());
+        }
+        selector.start_round();
+        selector.filter_backed_off(candidates);
+        let remaining: Vec<_> = selector
+            .statuses()
+            .iter()
+            .map(|(_, _, status)| match status {
+                CandidateStatus::BackedOff { remaining, .. } => *remaining,
+                _ => panic!("not backed off"),
+            })
+            .collect();
+        assert!(remaining[0] <= MAX_TRUSTED_BACKOFF);
+        assert!(remaining[1] > MAX_TRUSTED_BACKOFF);
+    }
+}
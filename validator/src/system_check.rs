@@ -0,0 +1,238 @@
+//! The `system_check` module checks that the host is set up to run a validator before the
+//! validator starts, so that a misconfigured host is reported with how to fix it rather than
+//! by a panic hours later.
+
+#[cfg(target_os = "linux")]
+use std::fs;
+
+/// Open file descriptor limit the blockstore raises the process limit to
+pub const DESIRED_OPEN_FILES: u64 = 500_000;
+/// `vm.max_map_count` needed to memory map the account storage files
+pub const DESIRED_MAX_MAP_COUNT: u64 = 500_000;
+/// Size of the kernel UDP buffers, as set by `solana-sys-tuner`
+pub const DESIRED_UDP_BUFFER_SIZE: u64 = 134_217_728;
+
+#[derive(Debug)]
+pub struct SystemCheck {
+    pub name: &'static str,
+    /// Details of what passed, or why the check failed and how to fix it
+    pub result: Result<String, String>,
+    /// Whether the validator cannot run at all when the check fails
+    pub fatal: bool,
+}
+
+/// Runs the checks which apply to this platform
+pub fn check_system() -> Vec<SystemCheck> {
+    let mut checks = vec![];
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        checks.push(SystemCheck {
+            name: "cpu-features",
+            result: check_cpu_features(),
+            fatal: true,
+        });
+    }
+    #[cfg(unix)]
+    {
+        checks.push(SystemCheck {
+            name: "open-files",
+            result: check_open_files(),
+            fatal: true,
+        });
+    }
+    #[cfg(target_os = "linux")]
+    {
+        checks.push(SystemCheck {
+            name: "max-map-count",
+            result: check_max_map_count(),
+            fatal: false,
+        });
+        checks.push(SystemCheck {
+            name: "udp-buffers",
+            result: check_udp_buffers(),
+            fatal: false,
+        });
+        checks.push(SystemCheck {
+            name: "clock-sync",
+            result: check_clock_sync(),
+            fatal: false,
+        });
+    }
+    checks
+}
+
+// Binaries built for a target with AVX or AVX2 execute invalid opcodes on machines without
+// them, which aborts the process without a clear reason
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+fn check_cpu_features() -> Result<String, String> {
+    let features = [
+        (
+            "avx",
+            cfg!(target_feature = "avx"),
+            is_x86_feature_detected!("avx"),
+        ),
+        (
+            "avx2",
+            cfg!(target_feature = "avx2"),
+            is_x86_feature_detected!("avx2"),
+        ),
+    ];
+    let missing: Vec<_> = features
+        .iter()
+        .filter(|(_, built_with, detected)| *built_with && !detected)
+        .map(|(feature, _, _)| *feature)
+        .collect();
+    if missing.is_empty() {
+        let detected: Vec<_> = features
+            .iter()
+            .filter(|(_, _, detected)| *detected)
+            .map(|(feature, _, _)| *feature)
+            .collect();
+        Ok(format!("Detected: {}", detected.join(",")))
+    } else {
+        Err(format!(
+            "This validator was built for {} which this machine does not support. \
+             Rebuild it from source on this machine",
+            missing.join(",")
+        ))
+    }
+}
+
+#[cfg(unix)]
+fn check_open_files() -> Result<String, String> {
+    let mut nofile = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut nofile) } != 0 {
+        return Err(format!(
+            "getrlimit(RLIMIT_NOFILE) failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    // The blockstore raises the soft limit itself, which only root can do past the hard limit
+    let (soft_limit, hard_limit) = (nofile.rlim_cur as u64, nofile.rlim_max as u64);
+    if soft_limit >= DESIRED_OPEN_FILES
+        || hard_limit >= DESIRED_OPEN_FILES
+        || unsafe { libc::geteuid() } == 0
+    {
+        return Ok(format!(
+            "Open file limit is {} (hard limit {})",
+            soft_limit, hard_limit
+        ));
+    }
+    let remediation = if cfg!(target_os = "macos") {
+        format!(
+            "Run |sudo launchctl limit maxfiles {} {}|",
+            DESIRED_OPEN_FILES, DESIRED_OPEN_FILES
+        )
+    } else {
+        format!(
+            "Add LimitNOFILE={} to the [Service] section of the systemd service file of the \
+             validator, or add |* - nofile {}| to /etc/security/limits.d/90-solana-nofiles.conf \
+             and log in again",
+            DESIRED_OPEN_FILES, DESIRED_OPEN_FILES
+        )
+    };
+    Err(format!(
+        "Open file hard limit is {}, less than the {} needed by the ledger. {}",
+        hard_limit, DESIRED_OPEN_FILES, remediation
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn read_sysctl(name: &str) -> Result<u64, String> {
+    let path = format!("/proc/sys/{}", name.replace('.', "/"));
+    fs::read_to_string(&path)
+        .map_err(|err| format!("Unable to read {}: {}", path, err))?
+        .trim()
+        .parse()
+        .map_err(|err| format!("Unable to parse {}: {}", path, err))
+}
+
+#[cfg(target_os = "linux")]
+fn check_max_map_count() -> Result<String, String> {
+    let max_map_count = read_sysctl("vm.max_map_count")?;
+    if max_map_count >= DESIRED_MAX_MAP_COUNT {
+        Ok(format!("vm.max_map_count is {}", max_map_count))
+    } else {
+        Err(format!(
+            "vm.max_map_count is {}, less than {}, so the validator will panic once it has \
+             mapped as many account storage files. Run |sudo sysctl -w vm.max_map_count={}| \
+             or solana-sys-tuner",
+            max_map_count, DESIRED_MAX_MAP_COUNT, DESIRED_MAX_MAP_COUNT
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn check_udp_buffers() -> Result<String, String> {
+    let mut too_small = vec![];
+    for name in &[
+        "net.core.rmem_default",
+        "net.core.rmem_max",
+        "net.core.wmem_default",
+        "net.core.wmem_max",
+    ] {
+        let size = read_sysctl(name)?;
+        if size < DESIRED_UDP_BUFFER_SIZE {
+            too_small.push(format!("{} is {}", name, size));
+        }
+    }
+    if too_small.is_empty() {
+        Ok(format!(
+            "UDP buffers are at least {} bytes",
+            DESIRED_UDP_BUFFER_SIZE
+        ))
+    } else {
+        Err(format!(
+            "{}, less than {}, so packets will be dropped under load. Set them with \
+             |sudo sysctl -w <name>={}| or solana-sys-tuner",
+            too_small.join(", "),
+            DESIRED_UDP_BUFFER_SIZE,
+            DESIRED_UDP_BUFFER_SIZE
+        ))
+    }
+}
+
+// Votes and gossip values carry wallclock timestamps, which peers reject when the clock drifts
+#[cfg(target_os = "linux")]
+fn check_clock_sync() -> Result<String, String> {
+    let output = std::process::Command::new("timedatectl")
+        .args(&["show", "--property=NTPSynchronized", "--value"])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            match String::from_utf8_lossy(&output.stdout).trim() {
+                "yes" => Ok("System clock is synchronized".to_string()),
+                _ => Err(
+                    "System clock is not synchronized. Enable time synchronization with \
+                     |sudo timedatectl set-ntp true|, or run an NTP daemon"
+                        .to_string(),
+                ),
+            }
+        }
+        // Without systemd there is no common way to query the synchronization
+        _ => Ok("Unable to query timedatectl, clock synchronization not checked".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_system() {
+        let checks = check_system();
+        // The machine running the tests must be able to run the binary it built
+        assert!(checks
+            .iter()
+            .filter(|check| check.name == "cpu-features")
+            .all(|check| check.result.is_ok()));
+        for check in checks {
+            match check.result {
+                Ok(detail) | Err(detail) => assert!(!detail.is_empty()),
+            }
+        }
+    }
+}
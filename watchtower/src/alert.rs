@@ -0,0 +1,150 @@
+//! The `alert` module defines the alerts raised by the `Monitor`, and the ways of delivering them
+
+use log::*;
+use reqwest::blocking::Client;
+use serde_json::json;
+use solana_notifier::Notifier;
+use std::{fmt, time::Duration};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Alert {
+    /// A sanity test failed
+    Failure { test: &'static str, message: String },
+    /// The sanity tests pass again after failing for `alarm_duration`
+    AllClear { alarm_duration: Duration },
+}
+
+impl fmt::Display for Alert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Alert::Failure { test, message } => {
+                write!(f, "solana-watchtower: Error: {}: {}", test, message)
+            }
+            Alert::AllClear { alarm_duration } => {
+                // Drop milliseconds in message
+                let alarm_duration = Duration::from_secs(alarm_duration.as_secs());
+                write!(
+                    f,
+                    "solana-watchtower: All clear after {}",
+                    humantime::format_duration(alarm_duration)
+                )
+            }
+        }
+    }
+}
+
+pub trait AlertNotifier: Send {
+    fn notify(&self, alert: &Alert);
+}
+
+/// Sends the alerts to the services configured through environment variables
+impl AlertNotifier for Notifier {
+    fn notify(&self, alert: &Alert) {
+        self.send(&alert.to_string());
+    }
+}
+
+pub struct SlackNotifier {
+    client: Client,
+    webhook: String,
+}
+
+impl SlackNotifier {
+    pub fn new(webhook: String) -> Self {
+        Self {
+            client: Client::new(),
+            webhook,
+        }
+    }
+}
+
+impl AlertNotifier for SlackNotifier {
+    fn notify(&self, alert: &Alert) {
+        let data = json!({ "text": alert.to_string() });
+        if let Err(err) = self.client.post(&self.webhook).json(&data).send() {
+            warn!("Failed to send Slack message: {:?}", err);
+        }
+    }
+}
+
+/// Posts the alerts as JSON objects to an arbitrary URL
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+        }
+    }
+}
+
+impl AlertNotifier for WebhookNotifier {
+    fn notify(&self, alert: &Alert) {
+        let data = match alert {
+            Alert::Failure { test, message } => json!({
+                "status": "failure",
+                "test": test,
+                "message": message,
+                "text": alert.to_string(),
+            }),
+            Alert::AllClear { alarm_duration } => json!({
+                "status": "all_clear",
+                "alarm_duration_secs": alarm_duration.as_secs(),
+                "text": alert.to_string(),
+            }),
+        };
+        if let Err(err) = self.client.post(&self.url).json(&data).send() {
+            warn!("Failed to send webhook notification: {:?}", err);
+        }
+    }
+}
+
+const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Triggers a PagerDuty incident on failure, and resolves it once all clear. Failures are
+/// deduplicated into a single incident per `dedup_key`.
+pub struct PagerDutyNotifier {
+    client: Client,
+    routing_key: String,
+    dedup_key: String,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(routing_key: String, dedup_key: String) -> Self {
+        Self {
+            client: Client::new(),
+            routing_key,
+            dedup_key,
+        }
+    }
+}
+
+impl AlertNotifier for PagerDutyNotifier {
+    fn notify(&self, alert: &Alert) {
+        let data = match alert {
+            Alert::Failure { test, .. } => json!({
+                "routing_key": self.routing_key,
+                "dedup_key": self.dedup_key,
+                "event_action": "trigger",
+                "payload": {
+                    "summary": alert.to_string(),
+                    "source": "solana-watchtower",
+                    "severity": "critical",
+                    "component": test,
+                },
+            }),
+            Alert::AllClear { .. } => json!({
+                "routing_key": self.routing_key,
+                "dedup_key": self.dedup_key,
+                "event_action": "resolve",
+            }),
+        };
+        if let Err(err) = self.client.post(PAGERDUTY_EVENTS_URL).json(&data).send() {
+            warn!("Failed to send PagerDuty event: {:?}", err);
+        }
+    }
+}
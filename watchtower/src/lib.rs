@@ -0,0 +1,418 @@
+//! The `solana_watchtower` library monitors the health of a cluster over RPC and raises alerts
+//! through `AlertNotifier`s, so that the monitoring can be embedded in other programs as well as
+//! run by `solana-watchtower`.
+
+pub mod alert;
+
+use crate::alert::{Alert, AlertNotifier};
+use log::*;
+use solana_cli_output::display::format_labeled_address;
+use solana_client::{
+    client_error::Result as ClientResult, rpc_client::RpcClient, rpc_response::RpcVoteAccountStatus,
+};
+use solana_metrics::{datapoint_error, datapoint_info};
+use solana_sdk::{
+    hash::Hash,
+    native_token::{lamports_to_sol, sol_to_lamports},
+    pubkey::Pubkey,
+};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+#[derive(Clone, Debug)]
+pub struct MonitorConfig {
+    /// Validators to monitor, instead of the entire cluster
+    pub validator_identity_pubkeys: Vec<String>,
+    /// Suppress subsequent identical notifications
+    pub no_duplicate_notifications: bool,
+    /// Alert when the current stake for the cluster drops below 80%
+    pub monitor_active_stake: bool,
+    /// Alert when the balance of a monitored validator drops below this, in lamports
+    pub minimum_validator_balance: u64,
+    /// Alert when the last vote of a monitored validator is more than this many slots behind the
+    /// latest vote of the cluster
+    pub max_slots_behind: Option<u64>,
+    pub address_labels: HashMap<String, String>,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        Self {
+            validator_identity_pubkeys: vec![],
+            no_duplicate_notifications: false,
+            monitor_active_stake: false,
+            // At 1 SOL/day for validator voting fees, this gives over a week to find some more SOL
+            minimum_validator_balance: sol_to_lamports(10.0),
+            max_slots_behind: None,
+            address_labels: HashMap::new(),
+        }
+    }
+}
+
+pub struct ClusterInfo {
+    pub transaction_count: u64,
+    pub recent_blockhash: Hash,
+    pub vote_accounts: RpcVoteAccountStatus,
+    /// Balances of the monitored validators, missing if they could not be fetched
+    pub validator_balances: HashMap<String, u64>,
+}
+
+pub fn get_cluster_info(
+    rpc_client: &RpcClient,
+    validator_identity_pubkeys: &[String],
+) -> ClientResult<ClusterInfo> {
+    let transaction_count = rpc_client.get_transaction_count()?;
+    let recent_blockhash = rpc_client.get_recent_blockhash()?.0;
+    let vote_accounts = rpc_client.get_vote_accounts()?;
+    let mut validator_balances = HashMap::new();
+    for validator_identity in validator_identity_pubkeys {
+        match rpc_client.get_balance(&Pubkey::from_str(validator_identity).unwrap_or_default()) {
+            Ok(balance) => {
+                validator_balances.insert(validator_identity.clone(), balance);
+            }
+            Err(err) => warn!("Failed to get balance of {}: {:?}", validator_identity, err),
+        }
+    }
+    Ok(ClusterInfo {
+        transaction_count,
+        recent_blockhash,
+        vote_accounts,
+        validator_balances,
+    })
+}
+
+pub struct Monitor {
+    config: MonitorConfig,
+    notifiers: Vec<Box<dyn AlertNotifier>>,
+    last_transaction_count: u64,
+    last_recent_blockhash: Hash,
+    last_notification_msg: String,
+    last_success: Instant,
+}
+
+impl Monitor {
+    pub fn new(config: MonitorConfig) -> Self {
+        Self {
+            config,
+            notifiers: vec![],
+            last_transaction_count: 0,
+            last_recent_blockhash: Hash::default(),
+            last_notification_msg: String::new(),
+            last_success: Instant::now(),
+        }
+    }
+
+    pub fn add_notifier(&mut self, notifier: Box<dyn AlertNotifier>) {
+        self.notifiers.push(notifier);
+    }
+
+    /// Runs the sanity tests against `cluster_info`, returning the failures
+    pub fn check_cluster_info(
+        &mut self,
+        cluster_info: &ClusterInfo,
+    ) -> Vec<(&'static str, String)> {
+        let vote_accounts = &cluster_info.vote_accounts;
+        info!(
+            "Current transaction count: {}",
+            cluster_info.transaction_count
+        );
+        info!("Recent blockhash: {}", cluster_info.recent_blockhash);
+        info!("Current validator count: {}", vote_accounts.current.len());
+        info!(
+            "Delinquent validator count: {}",
+            vote_accounts.delinquent.len()
+        );
+
+        let mut failures = vec![];
+
+        let total_current_stake: u64 = vote_accounts
+            .current
+            .iter()
+            .map(|vote_account| vote_account.activated_stake)
+            .sum();
+        let total_delinquent_stake: u64 = vote_accounts
+            .delinquent
+            .iter()
+            .map(|vote_account| vote_account.activated_stake)
+            .sum();
+
+        let total_stake = total_current_stake + total_delinquent_stake;
+        let current_stake_percent = total_current_stake * 100 / total_stake.max(1);
+        info!(
+            "Current stake: {}% | Total stake: {} SOL, current stake: {} SOL, delinquent: {} SOL",
+            current_stake_percent,
+            lamports_to_sol(total_stake),
+            lamports_to_sol(total_current_stake),
+            lamports_to_sol(total_delinquent_stake)
+        );
+
+        if cluster_info.transaction_count > self.last_transaction_count {
+            self.last_transaction_count = cluster_info.transaction_count;
+        } else {
+            failures.push((
+                "transaction-count",
+                format!(
+                    "Transaction count is not advancing: {} <= {}",
+                    cluster_info.transaction_count, self.last_transaction_count
+                ),
+            ));
+        }
+
+        if cluster_info.recent_blockhash != self.last_recent_blockhash {
+            self.last_recent_blockhash = cluster_info.recent_blockhash;
+        } else {
+            failures.push((
+                "recent-blockhash",
+                format!(
+                    "Unable to get new blockhash: {}",
+                    cluster_info.recent_blockhash
+                ),
+            ));
+        }
+
+        if self.config.monitor_active_stake && current_stake_percent < 80 {
+            failures.push((
+                "current-stake",
+                format!("Current stake is {}%", current_stake_percent),
+            ));
+        }
+
+        if self.config.validator_identity_pubkeys.is_empty() {
+            if !vote_accounts.delinquent.is_empty() {
+                failures.push((
+                    "delinquent",
+                    format!("{} delinquent validators", vote_accounts.delinquent.len()),
+                ));
+            }
+        } else {
+            let latest_vote = vote_accounts
+                .current
+                .iter()
+                .chain(vote_accounts.delinquent.iter())
+                .map(|vai| vai.last_vote)
+                .max()
+                .unwrap_or_default();
+            let mut errors = vec![];
+            let mut behind = vec![];
+            for validator_identity in self.config.validator_identity_pubkeys.iter() {
+                let formatted_validator_identity =
+                    format_labeled_address(&validator_identity, &self.config.address_labels);
+                let vote_account = vote_accounts
+                    .current
+                    .iter()
+                    .chain(vote_accounts.delinquent.iter())
+                    .find(|vai| vai.node_pubkey == *validator_identity);
+                if vote_accounts
+                    .delinquent
+                    .iter()
+                    .any(|vai| vai.node_pubkey == *validator_identity)
+                {
+                    errors.push(format!("{} delinquent", formatted_validator_identity));
+                } else if vote_account.is_none() {
+                    errors.push(format!("{} missing", formatted_validator_identity));
+                }
+
+                if let (Some(vote_account), Some(max_slots_behind)) =
+                    (vote_account, self.config.max_slots_behind)
+                {
+                    let slots_behind = latest_vote.saturating_sub(vote_account.last_vote);
+                    if slots_behind > max_slots_behind {
+                        behind.push(format!(
+                            "{} is {} slots behind",
+                            formatted_validator_identity, slots_behind
+                        ));
+                    }
+                }
+
+                if let Some(balance) = cluster_info.validator_balances.get(validator_identity) {
+                    if *balance < self.config.minimum_validator_balance {
+                        failures.push((
+                            "balance",
+                            format!(
+                                "{} has {} SOL",
+                                formatted_validator_identity,
+                                lamports_to_sol(*balance)
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            if !errors.is_empty() {
+                failures.push(("delinquent", errors.join(",")));
+            }
+            if !behind.is_empty() {
+                failures.push(("behind", behind.join(",")));
+            }
+        }
+
+        for failure in failures.iter() {
+            error!("{} sanity failure: {}", failure.0, failure.1);
+        }
+        failures
+    }
+
+    /// Runs the sanity tests against the cluster, and notifies of the first failure if any, or of
+    /// the all clear after failures. Returns the first failure.
+    pub fn check(&mut self, rpc_client: &RpcClient) -> Option<(&'static str, String)> {
+        let failure = match get_cluster_info(rpc_client, &self.config.validator_identity_pubkeys) {
+            Ok(cluster_info) => self.check_cluster_info(&cluster_info).into_iter().next(),
+            Err(err) => Some(("rpc", err.to_string())),
+        };
+
+        datapoint_info!("watchtower-sanity", ("ok", failure.is_none(), bool));
+        if let Some((failure_test_name, failure_error_message)) = &failure {
+            let alert = Alert::Failure {
+                test: *failure_test_name,
+                message: failure_error_message.clone(),
+            };
+            let notification_msg = alert.to_string();
+            if !self.config.no_duplicate_notifications
+                || self.last_notification_msg != notification_msg
+            {
+                self.notify(&alert);
+            }
+            datapoint_error!(
+                "watchtower-sanity-failure",
+                ("test", failure_test_name, String),
+                ("err", failure_error_message, String)
+            );
+            self.last_notification_msg = notification_msg;
+        } else {
+            if !self.last_notification_msg.is_empty() {
+                let alert = Alert::AllClear {
+                    alarm_duration: self.last_success.elapsed(),
+                };
+                info!("{}", alert);
+                self.notify(&alert);
+            }
+            self.last_notification_msg.clear();
+            self.last_success = Instant::now();
+        }
+        failure
+    }
+
+    /// Checks the cluster every `interval` until `exit` is set
+    pub fn run(&mut self, rpc_client: &RpcClient, interval: Duration, exit: &AtomicBool) {
+        while !exit.load(Ordering::Relaxed) {
+            self.check(rpc_client);
+            sleep(interval);
+        }
+    }
+
+    fn notify(&self, alert: &Alert) {
+        for notifier in &self.notifiers {
+            notifier.notify(alert);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_client::rpc_response::RpcVoteAccountInfo;
+    use std::sync::{Arc, Mutex};
+
+    fn vote_account_info(node_pubkey: &str, last_vote: u64) -> RpcVoteAccountInfo {
+        RpcVoteAccountInfo {
+            vote_pubkey: solana_sdk::pubkey::new_rand().to_string(),
+            node_pubkey: node_pubkey.to_string(),
+            activated_stake: 100,
+            commission: 0,
+            epoch_vote_account: true,
+            epoch_credits: vec![],
+            last_vote,
+            root_slot: 0,
+        }
+    }
+
+    struct TestNotifier(Arc<Mutex<Vec<Alert>>>);
+
+    impl AlertNotifier for TestNotifier {
+        fn notify(&self, alert: &Alert) {
+            self.0.lock().unwrap().push(alert.clone());
+        }
+    }
+
+    #[test]
+    fn test_check_cluster_info() {
+        let validator = solana_sdk::pubkey::new_rand().to_string();
+        let other = solana_sdk::pubkey::new_rand().to_string();
+        let mut monitor = Monitor::new(MonitorConfig {
+            validator_identity_pubkeys: vec![validator.clone()],
+            max_slots_behind: Some(10),
+            ..MonitorConfig::default()
+        });
+        let mut cluster_info = ClusterInfo {
+            transaction_count: 1,
+            recent_blockhash: Hash::new_unique(),
+            vote_accounts: RpcVoteAccountStatus {
+                current: vec![
+                    vote_account_info(&validator, 95),
+                    vote_account_info(&other, 100),
+                ],
+                delinquent: vec![],
+            },
+            validator_balances: vec![(validator.clone(), sol_to_lamports(20.0))]
+                .into_iter()
+                .collect(),
+        };
+        assert!(monitor.check_cluster_info(&cluster_info).is_empty());
+
+        // Nothing advanced, the validator fell behind and is running out of SOL
+        cluster_info.vote_accounts.current[1].last_vote = 110;
+        cluster_info
+            .validator_balances
+            .insert(validator.clone(), sol_to_lamports(1.0));
+        let tests: Vec<_> = monitor
+            .check_cluster_info(&cluster_info)
+            .into_iter()
+            .map(|(test, _)| test)
+            .collect();
+        assert_eq!(
+            tests,
+            vec!["transaction-count", "recent-blockhash", "balance", "behind"]
+        );
+
+        // The validator is delinquent
+        cluster_info.transaction_count = 2;
+        cluster_info.recent_blockhash = Hash::new_unique();
+        cluster_info.validator_balances.clear();
+        let vote_account = cluster_info.vote_accounts.current.remove(0);
+        cluster_info.vote_accounts.delinquent.push(vote_account);
+        cluster_info.vote_accounts.delinquent[0].last_vote = 105;
+        assert_eq!(
+            monitor.check_cluster_info(&cluster_info),
+            vec![("delinquent", format!("{} delinquent", validator))]
+        );
+    }
+
+    #[test]
+    fn test_alert_notifiers() {
+        let alerts = Arc::new(Mutex::new(vec![]));
+        let mut monitor = Monitor::new(MonitorConfig::default());
+        monitor.add_notifier(Box::new(TestNotifier(alerts.clone())));
+        let alert = Alert::Failure {
+            test: "rpc",
+            message: "connection refused".to_string(),
+        };
+        monitor.notify(&alert);
+        assert_eq!(*alerts.lock().unwrap(), vec![alert.clone()]);
+        assert_eq!(
+            alert.to_string(),
+            "solana-watchtower: Error: rpc: connection refused"
+        );
+        assert_eq!(
+            Alert::AllClear {
+                alarm_duration: Duration::from_millis(61_500)
+            }
+            .to_string(),
+            "solana-watchtower: All clear after 1m 1s"
+        );
+    }
+}
@@ -3,37 +3,29 @@
 use clap::{crate_description, crate_name, value_t, value_t_or_exit, App, Arg};
 use log::*;
 use solana_clap_utils::{
-    input_parsers::pubkeys_of,
-    input_validators::{is_pubkey_or_keypair, is_url},
+    input_parsers::{lamports_of_sol, pubkeys_of, value_of},
+    input_validators::{is_amount, is_pubkey_or_keypair, is_slot, is_url},
 };
-use solana_cli_output::display::{format_labeled_address, write_transaction};
-use solana_client::{
-    client_error::Result as ClientResult, rpc_client::RpcClient, rpc_response::RpcVoteAccountStatus,
-};
-use solana_metrics::{datapoint_error, datapoint_info};
+use solana_cli_output::display::write_transaction;
+use solana_client::{client_error::Result as ClientResult, rpc_client::RpcClient};
+use solana_metrics::datapoint_error;
 use solana_notifier::Notifier;
-use solana_sdk::{
-    clock::Slot, hash::Hash, native_token::lamports_to_sol, program_utils::limited_deserialize,
-    pubkey::Pubkey,
-};
+use solana_sdk::{clock::Slot, program_utils::limited_deserialize, pubkey::Pubkey};
 use solana_transaction_status::{EncodedConfirmedBlock, UiTransactionEncoding};
 use solana_vote_program::vote_instruction::VoteInstruction;
-use std::{
-    collections::HashMap,
-    error,
-    str::FromStr,
-    thread::sleep,
-    time::{Duration, Instant},
+use solana_watchtower::{
+    alert::{PagerDutyNotifier, WebhookNotifier},
+    Monitor, MonitorConfig,
 };
+use std::{error, sync::atomic::AtomicBool, thread::sleep, time::Duration};
 
 struct Config {
     interval: Duration,
     json_rpc_url: String,
-    validator_identity_pubkeys: Vec<String>,
-    no_duplicate_notifications: bool,
-    monitor_active_stake: bool,
     notify_on_transactions: bool,
-    address_labels: HashMap<String, String>,
+    webhook_url: Option<String>,
+    pagerduty_routing_key: Option<String>,
+    monitor_config: MonitorConfig,
 }
 
 fn get_config() -> Config {
@@ -108,6 +100,38 @@ fn get_config() -> Config {
                 .takes_value(false)
                 .help("Alert when the current stake for the cluster drops below 80%"),
         )
+        .arg(
+            Arg::with_name("minimum_validator_balance")
+                .long("minimum-validator-balance")
+                .value_name("SOL")
+                .takes_value(true)
+                .validator(is_amount)
+                .default_value("10")
+                .help("Alert when the balance of a monitored validator drops below this amount"),
+        )
+        .arg(
+            Arg::with_name("max_slots_behind")
+                .long("max-slots-behind")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .validator(is_slot)
+                .help("Alert when a monitored validator votes more than this many slots behind the cluster"),
+        )
+        .arg(
+            Arg::with_name("webhook_url")
+                .long("webhook-url")
+                .value_name("URL")
+                .takes_value(true)
+                .validator(is_url)
+                .help("Also post the notifications as JSON objects to this URL"),
+        )
+        .arg(
+            Arg::with_name("pagerduty_routing_key")
+                .long("pagerduty-routing-key")
+                .value_name("KEY")
+                .takes_value(true)
+                .help("Also trigger and resolve PagerDuty incidents with this integration key"),
+        )
         .arg(
             Arg::with_name("notify_on_transactions")
                 .long("notify-on-transactions")
@@ -140,18 +164,25 @@ fn get_config() -> Config {
     let config = Config {
         interval,
         json_rpc_url,
-        validator_identity_pubkeys,
-        no_duplicate_notifications,
-        monitor_active_stake,
         notify_on_transactions,
-        address_labels: config.address_labels,
+        webhook_url: value_of(&matches, "webhook_url"),
+        pagerduty_routing_key: value_of(&matches, "pagerduty_routing_key"),
+        monitor_config: MonitorConfig {
+            validator_identity_pubkeys,
+            no_duplicate_notifications,
+            monitor_active_stake,
+            minimum_validator_balance: lamports_of_sol(&matches, "minimum_validator_balance")
+                .unwrap(),
+            max_slots_behind: value_of(&matches, "max_slots_behind"),
+            address_labels: config.address_labels,
+        },
     };
 
     info!("RPC URL: {}", config.json_rpc_url);
-    if !config.validator_identity_pubkeys.is_empty() {
+    if !config.monitor_config.validator_identity_pubkeys.is_empty() {
         info!(
             "Monitored validators: {:?}",
-            config.validator_identity_pubkeys
+            config.monitor_config.validator_identity_pubkeys
         );
     }
     config
@@ -287,13 +318,6 @@ fn transaction_monitor(rpc_client: RpcClient) {
     }
 }
 
-fn get_cluster_info(rpc_client: &RpcClient) -> ClientResult<(u64, Hash, RpcVoteAccountStatus)> {
-    let transaction_count = rpc_client.get_transaction_count()?;
-    let recent_blockhash = rpc_client.get_recent_blockhash()?.0;
-    let vote_accounts = rpc_client.get_vote_accounts()?;
-    Ok((transaction_count, recent_blockhash, vote_accounts))
-}
-
 fn main() -> Result<(), Box<dyn error::Error>> {
     let config = get_config();
 
@@ -308,167 +332,17 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     };
 
     let rpc_client = RpcClient::new(config.json_rpc_url.clone());
-    let notifier = Notifier::default();
-    let mut last_transaction_count = 0;
-    let mut last_recent_blockhash = Hash::default();
-    let mut last_notification_msg = "".into();
-    let mut last_success = Instant::now();
-
-    loop {
-        let failure = match get_cluster_info(&rpc_client) {
-            Ok((transaction_count, recent_blockhash, vote_accounts)) => {
-                info!("Current transaction count: {}", transaction_count);
-                info!("Recent blockhash: {}", recent_blockhash);
-                info!("Current validator count: {}", vote_accounts.current.len());
-                info!(
-                    "Delinquent validator count: {}",
-                    vote_accounts.delinquent.len()
-                );
-
-                let mut failures = vec![];
-
-                let total_current_stake = vote_accounts
-                    .current
-                    .iter()
-                    .map(|vote_account| vote_account.activated_stake)
-                    .sum();
-                let total_delinquent_stake = vote_accounts
-                    .delinquent
-                    .iter()
-                    .map(|vote_account| vote_account.activated_stake)
-                    .sum();
-
-                let total_stake = total_current_stake + total_delinquent_stake;
-                let current_stake_percent = total_current_stake * 100 / total_stake;
-                info!(
-                    "Current stake: {}% | Total stake: {} SOL, current stake: {} SOL, delinquent: {} SOL",
-                    current_stake_percent,
-                    lamports_to_sol(total_stake),
-                    lamports_to_sol(total_current_stake),
-                    lamports_to_sol(total_delinquent_stake)
-                );
-
-                if transaction_count > last_transaction_count {
-                    last_transaction_count = transaction_count;
-                } else {
-                    failures.push((
-                        "transaction-count",
-                        format!(
-                            "Transaction count is not advancing: {} <= {}",
-                            transaction_count, last_transaction_count
-                        ),
-                    ));
-                }
-
-                if recent_blockhash != last_recent_blockhash {
-                    last_recent_blockhash = recent_blockhash;
-                } else {
-                    failures.push((
-                        "recent-blockhash",
-                        format!("Unable to get new blockhash: {}", recent_blockhash),
-                    ));
-                }
-
-                if config.monitor_active_stake && current_stake_percent < 80 {
-                    failures.push((
-                        "current-stake",
-                        format!("Current stake is {}%", current_stake_percent),
-                    ));
-                }
-
-                if config.validator_identity_pubkeys.is_empty() {
-                    if !vote_accounts.delinquent.is_empty() {
-                        failures.push((
-                            "delinquent",
-                            format!("{} delinquent validators", vote_accounts.delinquent.len()),
-                        ));
-                    }
-                } else {
-                    let mut errors = vec![];
-                    for validator_identity in config.validator_identity_pubkeys.iter() {
-                        let formatted_validator_identity =
-                            format_labeled_address(&validator_identity, &config.address_labels);
-                        if vote_accounts
-                            .delinquent
-                            .iter()
-                            .any(|vai| vai.node_pubkey == *validator_identity)
-                        {
-                            errors.push(format!("{} delinquent", formatted_validator_identity));
-                        } else if !vote_accounts
-                            .current
-                            .iter()
-                            .any(|vai| vai.node_pubkey == *validator_identity)
-                        {
-                            errors.push(format!("{} missing", formatted_validator_identity));
-                        }
-
-                        rpc_client
-                            .get_balance(&Pubkey::from_str(&validator_identity).unwrap_or_default())
-                            .map(lamports_to_sol)
-                            .map(|balance| {
-                                if balance < 10.0 {
-                                    // At 1 SOL/day for validator voting fees, this gives over a week to
-                                    // find some more SOL
-                                    failures.push((
-                                        "balance",
-                                        format!(
-                                            "{} has {} SOL",
-                                            formatted_validator_identity, balance
-                                        ),
-                                    ));
-                                }
-                            })
-                            .unwrap_or_else(|err| {
-                                warn!(
-                                    "Failed to get balance of {}: {:?}",
-                                    formatted_validator_identity, err
-                                );
-                            });
-                    }
-
-                    if !errors.is_empty() {
-                        failures.push(("delinquent", errors.join(",")));
-                    }
-                }
-
-                for failure in failures.iter() {
-                    error!("{} sanity failure: {}", failure.0, failure.1);
-                }
-                failures.into_iter().next() // Only report the first failure if any
-            }
-            Err(err) => Some(("rpc", err.to_string())),
-        };
-
-        datapoint_info!("watchtower-sanity", ("ok", failure.is_none(), bool));
-        if let Some((failure_test_name, failure_error_message)) = &failure {
-            let notification_msg = format!(
-                "solana-watchtower: Error: {}: {}",
-                failure_test_name, failure_error_message
-            );
-            if !config.no_duplicate_notifications || last_notification_msg != notification_msg {
-                notifier.send(&notification_msg);
-            }
-            datapoint_error!(
-                "watchtower-sanity-failure",
-                ("test", failure_test_name, String),
-                ("err", failure_error_message, String)
-            );
-            last_notification_msg = notification_msg;
-        } else {
-            if !last_notification_msg.is_empty() {
-                let alarm_duration = Instant::now().duration_since(last_success);
-                let alarm_duration = Duration::from_secs(alarm_duration.as_secs()); // Drop milliseconds in message
-
-                let all_clear_msg = format!(
-                    "All clear after {}",
-                    humantime::format_duration(alarm_duration)
-                );
-                info!("{}", all_clear_msg);
-                notifier.send(&format!("solana-watchtower: {}", all_clear_msg));
-            }
-            last_notification_msg = "".into();
-            last_success = Instant::now();
-        }
-        sleep(config.interval);
+    let mut monitor = Monitor::new(config.monitor_config);
+    monitor.add_notifier(Box::new(Notifier::default()));
+    if let Some(webhook_url) = config.webhook_url {
+        monitor.add_notifier(Box::new(WebhookNotifier::new(webhook_url)));
+    }
+    if let Some(routing_key) = config.pagerduty_routing_key {
+        monitor.add_notifier(Box::new(PagerDutyNotifier::new(
+            routing_key,
+            "solana-watchtower".to_string(),
+        )));
     }
+    monitor.run(&rpc_client, config.interval, &AtomicBool::new(false));
+    Ok(())
 }